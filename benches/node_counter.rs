@@ -0,0 +1,76 @@
+// Node counter benchmarks.  Run with `cargo bench --bench node_counter`; each benchmark has 1 to
+// 32 threads count nodes for a while, once through a sharded `NodeCounter` and once through a
+// single shared atomic, and reports the nodes counted per second across all the threads.  The
+// sharded counter should scale with the threads, where the shared one slows down as they fight
+// over its cache line.
+
+extern crate chess_uci;
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+use chess_uci::NodeCounter;
+
+const MEASURE_FOR: Duration = Duration::from_millis(500);
+const THREADS: [usize; 6] = [1, 2, 4, 8, 16, 32];
+
+// What each thread does: count nodes with `count` until time is up, returning how many it
+// counted.
+fn run<C: FnMut() -> u64>(mut count: C) -> u64 {
+    let start = Instant::now();
+    let mut nodes = 0;
+    while start.elapsed() < MEASURE_FOR {
+        for _ in 0..4096 {
+            black_box(count());
+        }
+        nodes += 4096;
+    }
+    nodes
+}
+
+fn report(name: &str, threads: usize, nodes: u64) {
+    println!(
+        "{:<8} {:>2} threads {:>9.1} Mnodes/s",
+        name,
+        threads,
+        nodes as f64 / MEASURE_FOR.as_secs_f64() / 1e6
+    );
+}
+
+fn bench_sharded(threads: usize) {
+    let counter = NodeCounter::new(threads);
+    let handles = (0..threads)
+        .map(|thread| {
+            let mut handle = counter.handle(thread);
+            spawn(move || run(|| handle.increment()))
+        })
+        .collect::<Vec<_>>();
+    let nodes = handles.into_iter().map(|t| t.join().unwrap()).sum();
+    assert_eq!(counter.total(), nodes);
+    report("sharded", threads, nodes);
+}
+
+fn bench_shared(threads: usize) {
+    let counter = Arc::new(AtomicU64::new(0));
+    let handles = (0..threads)
+        .map(|_| {
+            let counter = counter.clone();
+            spawn(move || run(|| counter.fetch_add(1, Ordering::Relaxed)))
+        })
+        .collect::<Vec<_>>();
+    report(
+        "shared",
+        threads,
+        handles.into_iter().map(|t| t.join().unwrap()).sum(),
+    );
+}
+
+fn main() {
+    for &threads in THREADS.iter() {
+        bench_sharded(threads);
+        bench_shared(threads);
+    }
+}
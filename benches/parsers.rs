@@ -0,0 +1,79 @@
+// Parser throughput benchmarks.  Run with `cargo bench`; each benchmark parses a batch of lines
+// repeatedly and reports how many lines per second were parsed.
+
+extern crate chess_uci;
+
+use std::hint::black_box;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use chess_uci::{Command, EngineCommand, GuiCommand, Info};
+
+const MEASURE_FOR: Duration = Duration::from_secs(2);
+
+fn bench<F: Fn(&str)>(name: &str, lines: &[&str], parse: F) {
+    // Warm up, so the first measurement isn't paying for cold caches.
+    for line in lines {
+        parse(line);
+    }
+
+    let start = Instant::now();
+    let mut parsed: u64 = 0;
+    while start.elapsed() < MEASURE_FOR {
+        for line in lines {
+            parse(black_box(line));
+        }
+        parsed += lines.len() as u64;
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "{:<24} {:>12.0} lines/s {:>10.0} ns/line",
+        name,
+        parsed as f64 / elapsed.as_secs_f64(),
+        elapsed.as_nanos() as f64 / parsed as f64
+    );
+}
+
+fn main() {
+    let short_info = [
+        "info depth 1 seldepth 1 multipv 1 score cp 116 nodes 20 nps 10000 tbhits 0 time 2 pv e2e4",
+        "info depth 10 currmove g1f3 currmovenumber 2",
+        "info nodes 1000000 nps 2500000 hashfull 12 time 400",
+    ];
+    let long_info = [
+        "info depth 24 seldepth 33 multipv 1 score cp 34 upperbound nodes 51476000 nps 1029520 \
+         hashfull 821 tbhits 0 time 50000 pv e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1 f8e7 \
+         f1e1 b7b5 a4b3 d7d6 c2c3 e8g8 h2h3 c6a5 b3c2 c7c5 d2d4 d8c7",
+        "info depth 6 seldepth 17 time 3104 nodes 10645 score cp 14 wdl 324 449 227 hashfull 6 \
+         nps 9924 tbhits 0 movesleft 42 pv d2d4 g8f6 c2c4 e7e6 g1f3 d7d5 b1c3 f8e7",
+    ];
+    let gui = [
+        "position startpos moves e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1 f8e7",
+        "go wtime 300000 btime 300000 winc 2000 binc 2000",
+        "setoption name Hash value 128",
+    ];
+    let mixed = [
+        "id name Stockfish 10 64 POPCNT",
+        "option name Contempt type spin default 24 min -100 max 100",
+        "info depth 12 seldepth 16 multipv 1 score cp 37 nodes 28268 nps 1061280 pv e2e4 e7e5",
+        "bestmove e2e4 ponder e7e5",
+        "readyok",
+    ];
+
+    bench("info (short)", &short_info, |line| {
+        black_box(Info::from_str(line).unwrap());
+    });
+    bench("info (long pv)", &long_info, |line| {
+        black_box(Info::from_str(line).unwrap());
+    });
+    bench("engine command", &mixed, |line| {
+        black_box(EngineCommand::from_str(line).unwrap());
+    });
+    bench("gui command", &gui, |line| {
+        black_box(GuiCommand::from_str(line).unwrap());
+    });
+    bench("command (mixed)", &mixed, |line| {
+        black_box(Command::from_str(line).unwrap());
+    });
+}
@@ -0,0 +1,76 @@
+// Principal variation benchmarks.  Run with `cargo bench --bench pv`; each benchmark backs a PV up
+// from a leaf to the root the way a search does, once with a `Pv` per node and once through the
+// search's `PvTable`, and reports the time (and, for `Pv`, bytes copied) per update.
+
+extern crate chess;
+extern crate chess_uci;
+
+use std::hint::black_box;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+use chess::{ChessMove, Square};
+use chess_uci::{Pv, PvTable};
+
+const MEASURE_FOR: Duration = Duration::from_secs(2);
+
+// What each update used to copy when a PV was a fixed 512-ply array.
+const OLD_PV_BYTES: usize = 512 * size_of::<ChessMove>() + size_of::<usize>();
+
+fn bench(depth: usize) {
+    let m = ChessMove::new(Square::E2, Square::E4, None);
+
+    let start = Instant::now();
+    let mut updates: u64 = 0;
+    let mut bytes: u64 = 0;
+    while start.elapsed() < MEASURE_FOR {
+        let mut child = Pv::new();
+        for _ in 0..depth {
+            let mut parent = Pv::new();
+            parent.update(black_box(m), &child);
+            bytes += (size_of::<Pv>() + parent.len() * size_of::<ChessMove>()) as u64;
+            child = parent;
+        }
+        black_box(&child);
+        updates += depth as u64;
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "pv    depth {:<4} {:>4.1} ns/update {:>8.0} bytes/update (was {})",
+        depth,
+        elapsed.as_nanos() as f64 / updates as f64,
+        bytes as f64 / updates as f64,
+        OLD_PV_BYTES
+    );
+}
+
+fn bench_table(depth: usize) {
+    let m = ChessMove::new(Square::E2, Square::E4, None);
+    let mut table = PvTable::new(depth);
+
+    let start = Instant::now();
+    let mut updates: u64 = 0;
+    while start.elapsed() < MEASURE_FOR {
+        for ply in (0..depth).rev() {
+            table.clear(ply);
+            table.update(ply, black_box(m));
+        }
+        black_box(table.get_line(0));
+        updates += depth as u64;
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "table depth {:<4} {:>4.1} ns/update",
+        depth,
+        elapsed.as_nanos() as f64 / updates as f64
+    );
+}
+
+fn main() {
+    for depth in [8, 32, 64, 128].iter() {
+        bench(*depth);
+        bench_table(*depth);
+    }
+}
@@ -0,0 +1,137 @@
+// Transposition table benchmarks.  Run with `cargo bench --bench tt`; each benchmark has 8
+// threads probe and store random keys for a while, once through the lockless
+// `TranspositionTable` and once through the same packed entries behind a `Mutex`, and reports
+// the operations per second across all the threads.  Then a few positions are searched to a
+// fixed depth with a table, once prefetching each child's bucket and once not, and the nodes
+// per second of each are reported.
+
+extern crate chess;
+extern crate chess_uci;
+
+use std::hint::black_box;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+use chess::{Board, ChessMove, Square};
+use chess_uci::{DefaultEvaluate, DefaultSearch, Search, TranspositionTable, TtEntry};
+
+const MEASURE_FOR: Duration = Duration::from_secs(2);
+const THREADS: u64 = 8;
+const MEGABYTES: usize = 64;
+// One operation in four is a store, roughly as in a search.
+const STORE_MASK: u64 = 3;
+
+// What each thread does: probe and store keys from its own xorshift stream until time is up,
+// returning how many operations it managed.  The generator is written out here because the
+// crate's own is private to it, and benches only see the public API.
+fn run<P, S>(seed: u64, probe: P, store: S) -> u64
+where
+    P: Fn(u64) -> bool,
+    S: Fn(u64, &TtEntry<i32>),
+{
+    let entry = TtEntry::new_exact(12i32, 8, ChessMove::new(Square::E2, Square::E4, None));
+    let start = Instant::now();
+    let mut state = seed | 1;
+    let mut operations = 0;
+    while start.elapsed() < MEASURE_FOR {
+        for _ in 0..1024 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            if state & STORE_MASK == 0 {
+                store(state, &entry);
+            } else {
+                black_box(probe(state));
+            }
+        }
+        operations += 1024;
+    }
+    operations
+}
+
+fn report(name: &str, operations: u64) {
+    println!(
+        "{:<8} {} threads {:>7.1} Mops/s",
+        name,
+        THREADS,
+        operations as f64 / MEASURE_FOR.as_secs_f64() / 1e6
+    );
+}
+
+fn bench_lockless() {
+    let table = Arc::new(TranspositionTable::new(MEGABYTES));
+    let threads = (0..THREADS)
+        .map(|thread| {
+            let table = table.clone();
+            spawn(move || {
+                run(
+                    thread + 1,
+                    |key| table.probe::<i32>(key).is_some(),
+                    |key, entry| table.store(key, entry),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    report("lockless", threads.into_iter().map(|t| t.join().unwrap()).sum());
+}
+
+fn bench_mutex() {
+    let len = MEGABYTES * 1024 * 1024 / 16;
+    let table = Arc::new(Mutex::new(vec![(0u64, 0u64); len]));
+    let threads = (0..THREADS)
+        .map(|thread| {
+            let table = table.clone();
+            spawn(move || {
+                let index = |key: u64| ((key as u128 * len as u128) >> 64) as usize;
+                run(
+                    thread + 1,
+                    |key| table.lock().unwrap()[index(key)].0 == key,
+                    |key, entry| table.lock().unwrap()[index(key)] = (key, entry.pack(0)),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    report("mutex", threads.into_iter().map(|t| t.join().unwrap()).sum());
+}
+
+const SEARCH_DEPTH: i16 = 5;
+const SEARCH_POSITIONS: [&str; 3] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r5k1/p1p3bp/1p2q1p1/5p2/8/P1P4P/1P2BPP1/3QR1K1 w - - 0 1",
+];
+
+fn bench_search(prefetch: bool) {
+    let tt = Arc::new(TranspositionTable::new(MEGABYTES));
+    let mut nodes = 0;
+    let start = Instant::now();
+    for fen in SEARCH_POSITIONS.iter() {
+        tt.clear();
+        let mut searcher =
+            DefaultSearch::new(Arc::new(AtomicBool::new(false)), DefaultEvaluate::default())
+                .transposition_table(tt.clone())
+                .prefetch(prefetch);
+        let board = Board::from_str(fen).unwrap();
+        let result = searcher.search(board, i32::MIN + 20, i32::MAX - 20, SEARCH_DEPTH);
+        nodes += result.get_nodes();
+    }
+    println!(
+        "{:<8} depth {} {:>7.1} knps",
+        if prefetch { "prefetch" } else { "none" },
+        SEARCH_DEPTH,
+        nodes as f64 / start.elapsed().as_secs_f64() / 1e3
+    );
+}
+
+fn main() {
+    bench_lockless();
+    bench_mutex();
+    // Alternated, so that neither gains from going second.
+    for _ in 0..3 {
+        bench_search(true);
+        bench_search(false);
+    }
+}
@@ -0,0 +1,13 @@
+#![no_main]
+use chess_uci::Command;
+use libfuzzer_sys::fuzz_target;
+
+// Whole transcripts at once, the way a buffered transport hands them over.
+fuzz_target!(|data: &[u8]| {
+    let buffer = String::from_utf8_lossy(data);
+    let (commands, tail) = Command::parse_many(&buffer);
+    assert!(buffer.ends_with(tail));
+    for command in commands {
+        let _ = command.to_string();
+    }
+});
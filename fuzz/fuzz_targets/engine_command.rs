@@ -0,0 +1,14 @@
+#![no_main]
+use std::str::FromStr;
+
+use chess_uci::EngineCommand;
+use libfuzzer_sys::fuzz_target;
+
+// Whatever an engine prints, parsing it must fail cleanly rather than panic.  Anything that does
+// parse must print as something that parses again.
+fuzz_target!(|data: &[u8]| {
+    let line = String::from_utf8_lossy(data);
+    if let Ok(command) = EngineCommand::from_str(&line) {
+        let _ = EngineCommand::from_str(&command.to_string());
+    }
+});
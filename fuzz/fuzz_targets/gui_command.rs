@@ -0,0 +1,12 @@
+#![no_main]
+use std::str::FromStr;
+
+use chess_uci::GuiCommand;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let line = String::from_utf8_lossy(data);
+    if let Ok(command) = GuiCommand::from_str(&line) {
+        let _ = GuiCommand::from_str(&command.to_string());
+    }
+});
@@ -0,0 +1,314 @@
+use chess::{Board, Color};
+
+use engine::score::Score;
+use engine_connection::EngineConnection;
+use game_history::GameHistory;
+use game_result::{is_insufficient_material, GameResult};
+
+#[cfg(test)]
+use chess::{ChessMove, Square};
+#[cfg(test)]
+use std::str::FromStr;
+
+// Where a mate score counts, when compared with centipawn thresholds.
+const MATE_CP: i64 = 100_000;
+
+/// Decides the result of a game from a position, such as by probing endgame tablebases.
+pub trait TablebaseProbe {
+    fn probe(&mut self, board: &Board) -> Option<GameResult>;
+}
+
+impl<F: FnMut(&Board) -> Option<GameResult>> TablebaseProbe for F {
+    fn probe(&mut self, board: &Board) -> Option<GameResult> {
+        self(board)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AdjudicationReason {
+    /// Both engines agreed one side was lost.
+    Resign,
+    /// Both engines agreed the position was level.
+    Draw,
+    Tablebase,
+    /// Neither side has the material to mate.
+    InsufficientMaterial,
+}
+
+/// Why a game was ended early, and how.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adjudication {
+    result: GameResult,
+    reason: AdjudicationReason,
+    ply: usize,
+    evidence: Vec<Score>,
+}
+
+impl Adjudication {
+    pub fn get_result(&self) -> GameResult {
+        self.result
+    }
+
+    pub fn get_reason(&self) -> AdjudicationReason {
+        self.reason
+    }
+
+    /// How many moves into the game it was adjudicated.
+    pub fn get_ply(&self) -> usize {
+        self.ply
+    }
+
+    /// The scores that triggered the adjudication, oldest first, each as reported by the engine
+    /// that played the move.  Empty for a tablebase result.
+    pub fn get_evidence(&self) -> &[Score] {
+        &self.evidence
+    }
+}
+
+/// Ends games that aren't worth playing out, the way match runners such as Cute Chess do.
+///
+/// Feed it every move of the game with the score of the engine that played it.  No rule is on
+/// until it is set up.
+#[derive(Default)]
+pub struct Adjudicator {
+    resign: Option<(i64, usize)>,
+    draw: Option<(i64, usize, usize)>,
+    tablebase: Option<Box<dyn TablebaseProbe>>,
+    insufficient_material: bool,
+    // Each score with its value in centipawns for white.
+    scores: Vec<Option<(Score, i64)>>,
+}
+
+impl Adjudicator {
+    pub fn new() -> Adjudicator {
+        Adjudicator::default()
+    }
+
+    /// Resign for the side both engines put at `cp` centipawns or more behind, for `moves` moves
+    /// each in a row.
+    pub fn resign(mut self, cp: i64, moves: usize) -> Adjudicator {
+        self.resign = Some((cp, moves));
+        self
+    }
+
+    /// Call a draw once both engines have kept within `cp` centipawns of level for `moves` moves
+    /// each in a row, from move `after` on.
+    pub fn draw(mut self, cp: i64, moves: usize, after: usize) -> Adjudicator {
+        self.draw = Some((cp, moves, after));
+        self
+    }
+
+    /// Ask `probe` about every position, taking the result it gives.
+    pub fn tablebase<T: TablebaseProbe + 'static>(mut self, probe: T) -> Adjudicator {
+        self.tablebase = Some(Box::new(probe));
+        self
+    }
+
+    /// Draw a game once neither side has the material to mate, as the rules do.
+    pub fn insufficient_material(mut self) -> Adjudicator {
+        self.insufficient_material = true;
+        self
+    }
+
+    /// Forget the scores seen so far, to start a new game.
+    pub fn clear(&mut self) {
+        self.scores.clear();
+    }
+
+    /// Record the last move of `game`, which the engine played thinking it was worth `score`, and
+    /// adjudicate the game if a rule says to.
+    pub fn update(&mut self, game: &GameHistory, score: Option<Score>) -> Option<Adjudication> {
+        let board = game.get_board();
+        let mover = !board.side_to_move();
+        self.scores.push(score.map(|s| (s, white_cp(s, mover))));
+        let ply = game.get_moves().len();
+
+        if self.insufficient_material && is_insufficient_material(&board) {
+            return Some(Adjudication {
+                result: GameResult::Draw,
+                reason: AdjudicationReason::InsufficientMaterial,
+                ply,
+                evidence: vec![],
+            });
+        }
+
+        if let Some(result) = self.tablebase.as_mut().and_then(|probe| probe.probe(&board)) {
+            return Some(Adjudication {
+                result,
+                reason: AdjudicationReason::Tablebase,
+                ply,
+                evidence: vec![],
+            });
+        }
+
+        if let Some((cp, moves)) = self.resign {
+            if let Some(recent) = self.recent(moves) {
+                let winner = if recent.iter().all(|&(_, v)| v >= cp) {
+                    Some(Color::White)
+                } else if recent.iter().all(|&(_, v)| v <= -cp) {
+                    Some(Color::Black)
+                } else {
+                    None
+                };
+                if let Some(winner) = winner {
+                    let result = GameResult::Win(winner);
+                    return Some(adjudication(result, AdjudicationReason::Resign, ply, &recent));
+                }
+            }
+        }
+
+        if let Some((cp, moves, after)) = self.draw {
+            if let Some(recent) = self.recent(moves).filter(|_| ply >= 2 * after) {
+                if recent.iter().all(|&(_, v)| v.abs() <= cp) {
+                    let result = GameResult::Draw;
+                    return Some(adjudication(result, AdjudicationReason::Draw, ply, &recent));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// `update` with the game on `connection`, just after its engine played, and the score of its
+    /// deepest search.
+    pub fn update_from(&mut self, connection: &EngineConnection) -> Option<Adjudication> {
+        let score = connection.analysis_history().get_last().and_then(|info| info.get_score());
+        self.update(connection.game_history(), score)
+    }
+
+    // The last `moves` scores from each engine, if all of them were given.
+    fn recent(&self, moves: usize) -> Option<Vec<(Score, i64)>> {
+        if moves == 0 || self.scores.len() < 2 * moves {
+            return None;
+        }
+        self.scores[self.scores.len() - 2 * moves..].iter().cloned().collect()
+    }
+}
+
+fn adjudication(
+    result: GameResult,
+    reason: AdjudicationReason,
+    ply: usize,
+    recent: &[(Score, i64)],
+) -> Adjudication {
+    Adjudication {
+        result,
+        reason,
+        ply,
+        evidence: recent.iter().map(|&(s, _)| s).collect(),
+    }
+}
+
+fn white_cp(score: Score, mover: Color) -> i64 {
+    let cp = match (score.is_mate(), score.get_value()) {
+        (false, value) => value,
+        (true, value) if value > 0 => MATE_CP,
+        (true, _) => -MATE_CP,
+    };
+    if mover == Color::White {
+        cp
+    } else {
+        -cp
+    }
+}
+
+// Shuffle the knights back and forth, `plies` moves in all.
+#[cfg(test)]
+fn play_knights<F>(adjudicator: &mut Adjudicator, plies: usize, score: F) -> Vec<Adjudication>
+where
+    F: Fn(usize) -> Option<Score>,
+{
+    let shuffle = [
+        ChessMove::new(Square::G1, Square::F3, None),
+        ChessMove::new(Square::G8, Square::F6, None),
+        ChessMove::new(Square::F3, Square::G1, None),
+        ChessMove::new(Square::F6, Square::G8, None),
+    ];
+    let mut game = GameHistory::new(Board::default());
+    let mut adjudications = vec![];
+    for ply in 0..plies {
+        game.push(shuffle[ply % 4]).unwrap();
+        adjudications.extend(adjudicator.update(&game, score(ply)));
+    }
+    adjudications
+}
+
+#[test]
+fn test_resign_adjudication() {
+    // From the third move on, white thinks it is winning and black that it is getting mated.
+    let mut adjudicator = Adjudicator::new().resign(500, 3);
+    let scores = |ply: usize| match (ply < 4, ply % 2) {
+        (true, _) => Some(Score::cp(0)),
+        (false, 0) => Some(Score::cp(600)),
+        (false, _) => Some(Score::mate(-4)),
+    };
+    let adjudications = play_knights(&mut adjudicator, 12, scores);
+
+    assert_eq!(adjudications.len(), 3);
+    let first = &adjudications[0];
+    assert_eq!(first.get_result(), GameResult::Win(Color::White));
+    assert_eq!(first.get_reason(), AdjudicationReason::Resign);
+    assert_eq!(first.get_ply(), 10);
+    assert_eq!(first.get_evidence().len(), 6);
+    assert_eq!(first.get_evidence()[1], Score::mate(-4));
+
+    // One engine disagreeing, or not giving a score, is enough to keep playing.
+    let mut adjudicator = Adjudicator::new().resign(500, 3);
+    let disagreeing = |ply: usize| Some(Score::cp(if ply.is_multiple_of(2) { 600 } else { 0 }));
+    assert!(play_knights(&mut adjudicator, 12, disagreeing).is_empty());
+    let mut adjudicator = Adjudicator::new().resign(500, 3);
+    let missing = |ply: usize| {
+        Some(Score::cp(if ply.is_multiple_of(2) { 600 } else { -600 })).filter(|_| !ply.is_multiple_of(5))
+    };
+    assert!(play_knights(&mut adjudicator, 12, missing).is_empty());
+}
+
+#[test]
+fn test_draw_adjudication() {
+    let mut adjudicator = Adjudicator::new().draw(10, 2, 5);
+    let scores = |ply: usize| Some(Score::cp(if ply == 8 { 20 } else { 5 }));
+    let adjudications = play_knights(&mut adjudicator, 13, scores);
+
+    // Not before move five, and not until four level scores in a row.
+    assert_eq!(adjudications.iter().map(|a| a.get_ply()).collect::<Vec<usize>>(), vec![13]);
+    assert_eq!(adjudications[0].get_result(), GameResult::Draw);
+
+    let mut adjudicator = Adjudicator::new().draw(10, 2, 0).resign(500, 2);
+    adjudicator.clear();
+    assert!(play_knights(&mut adjudicator, 3, |_| Some(Score::cp(0))).is_empty());
+}
+
+#[test]
+fn test_tablebase_adjudication() {
+    let mut adjudicator = Adjudicator::new().resign(1, 1).tablebase(|board: &Board| {
+        if board.side_to_move() == Color::Black {
+            Some(GameResult::Draw)
+        } else {
+            None
+        }
+    });
+    let adjudications = play_knights(&mut adjudicator, 1, |_| Some(Score::cp(900)));
+
+    assert_eq!(adjudications.len(), 1);
+    assert_eq!(adjudications[0].get_reason(), AdjudicationReason::Tablebase);
+    assert!(adjudications[0].get_evidence().is_empty());
+}
+
+#[test]
+fn test_insufficient_material_adjudication() {
+    // Taking the last rook leaves king and knight against king.
+    let board = Board::from_str("8/8/4k3/8/8/8/2K5/1r4N1 w - - 0 1").unwrap();
+    let mut adjudicator = Adjudicator::new().insufficient_material();
+    let mut game = GameHistory::new(board);
+    game.push(ChessMove::new(Square::C2, Square::D2, None)).unwrap();
+    assert_eq!(adjudicator.update(&game, None), None);
+
+    // Only with the rule set up.
+    let mut game = GameHistory::new(board);
+    game.push(ChessMove::new(Square::C2, Square::B1, None)).unwrap();
+    assert_eq!(Adjudicator::new().update(&game, None), None);
+    let adjudication = adjudicator.update(&game, Some(Score::cp(0))).unwrap();
+    assert_eq!(adjudication.get_result(), GameResult::Draw);
+    assert_eq!(adjudication.get_reason(), AdjudicationReason::InsufficientMaterial);
+    assert_eq!(adjudication.get_ply(), 1);
+}
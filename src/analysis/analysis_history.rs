@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use chess::ChessMove;
+
+use engine::info::Info;
+use engine::score::Score;
+
+// Mates are compared as if they were worth this many centipawns, less the moves to mate.
+const MATE_CP: i64 = 100_000;
+
+// Orders scores so that any mate for the engine beats any centipawn score, and a shorter mate
+// beats a longer one.
+fn comparable_cp(score: Score) -> i64 {
+    if !score.is_mate() {
+        score.get_value()
+    } else if score.get_value() > 0 {
+        MATE_CP - score.get_value()
+    } else {
+        -MATE_CP - score.get_value()
+    }
+}
+
+/// The score moved a lot from one depth to the next.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EvalSwing {
+    depth: u64,
+    from: Score,
+    to: Score,
+}
+
+impl EvalSwing {
+    /// The depth that brought the new score.
+    pub fn get_depth(&self) -> u64 {
+        self.depth
+    }
+
+    pub fn get_from(&self) -> Score {
+        self.from
+    }
+
+    pub fn get_to(&self) -> Score {
+        self.to
+    }
+}
+
+/// The principal line's final `info` at each depth of one search, for plotting the eval against
+/// depth, or seeing how settled the search was.
+///
+/// Only `info` lines for multipv 1 with a depth, an exact score and a PV are kept; a later one
+/// at the same depth replaces an earlier one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnalysisHistory {
+    depths: BTreeMap<u64, Info>,
+}
+
+impl AnalysisHistory {
+    pub fn new() -> AnalysisHistory {
+        AnalysisHistory::default()
+    }
+
+    /// Record `info` if it belongs in the history, returning whether it did.
+    pub fn update(&mut self, info: &Info) -> bool {
+        let depth = match info.get_depth() {
+            Some(depth) => depth,
+            None => return false,
+        };
+        if info.get_multi_pv().unwrap_or(1) != 1
+            || info.get_pv().is_empty()
+            || !info.get_score().is_some_and(|s| s.is_exact())
+        {
+            return false;
+        }
+
+        self.depths.insert(depth, info.clone());
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.depths.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.depths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depths.is_empty()
+    }
+
+    pub fn get(&self, depth: u64) -> Option<&Info> {
+        self.depths.get(&depth)
+    }
+
+    /// The deepest result.
+    pub fn get_last(&self) -> Option<&Info> {
+        self.depths.values().next_back()
+    }
+
+    /// Each depth with its `info`, shallowest first.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &Info)> {
+        self.depths.iter().map(|(depth, info)| (*depth, info))
+    }
+
+    /// The score at each depth, shallowest first.
+    pub fn get_scores(&self) -> Vec<(u64, Score)> {
+        self.iter()
+            .filter_map(|(depth, info)| info.get_score().map(|s| (depth, s)))
+            .collect()
+    }
+
+    /// The best move at each depth, shallowest first.
+    pub fn get_best_moves(&self) -> Vec<(u64, ChessMove)> {
+        self.iter().map(|(depth, info)| (depth, info.get_pv()[0])).collect()
+    }
+
+    /// How many times the best move changed from one recorded depth to the next.
+    pub fn best_move_changes(&self) -> usize {
+        self.get_best_moves()
+            .windows(2)
+            .filter(|w| w[0].1 != w[1].1)
+            .count()
+    }
+
+    /// Every change of at least `threshold` centipawns from one recorded depth to the next.
+    /// Finding or losing a mate always counts.
+    pub fn get_swings(&self, threshold: i64) -> Vec<EvalSwing> {
+        self.get_scores()
+            .windows(2)
+            .filter(|w| {
+                let (from, to) = (w[0].1, w[1].1);
+                from.is_mate() != to.is_mate()
+                    || (comparable_cp(to) - comparable_cp(from)).abs() >= threshold
+            })
+            .map(|w| EvalSwing {
+                depth: w[1].0,
+                from: w[0].1,
+                to: w[1].1,
+            })
+            .collect()
+    }
+
+    /// Over the last `depths` recorded depths, did the score move by more than `threshold`
+    /// centipawns, or the best move change?
+    pub fn is_unstable(&self, depths: usize, threshold: i64) -> bool {
+        let scores = self.get_scores();
+        let recent = &scores[scores.len().saturating_sub(depths)..];
+        let moves = self.get_best_moves();
+        let recent_moves = &moves[moves.len().saturating_sub(depths)..];
+
+        let cps = recent.iter().map(|&(_, s)| comparable_cp(s));
+        let spread = match (cps.clone().min(), cps.max()) {
+            (Some(min), Some(max)) => max - min,
+            _ => 0,
+        };
+        spread > threshold || recent_moves.windows(2).any(|w| w[0].1 != w[1].1)
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use engine::score::ScoreBound;
+
+#[cfg(test)]
+fn depth_info(depth: u64, score: Score, m: ChessMove) -> Info {
+    Info::depth(depth)
+        .combine(&Info::score(score))
+        .combine(&Info::pv(vec![m]))
+}
+
+#[test]
+fn test_history_keeps_final_exact_lines() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+    let mut history = AnalysisHistory::new();
+
+    assert!(history.update(&depth_info(1, Score::cp(20), e2e4)));
+    assert!(!history.update(&depth_info(2, Score::cp(90).with_bound(ScoreBound::Lower), d2d4)));
+    assert!(!history.update(&depth_info(2, Score::cp(30), d2d4).combine(&Info::multi_pv(2))));
+    assert!(!history.update(&Info::depth(2)));
+    assert!(history.update(&depth_info(2, Score::cp(25), d2d4)));
+    assert!(history.update(&depth_info(2, Score::cp(35), e2e4)));
+
+    assert_eq!(history.len(), 2);
+    assert_eq!(history.get_scores(), vec![(1, Score::cp(20)), (2, Score::cp(35))]);
+    assert_eq!(history.get_last().unwrap().get_depth(), Some(2));
+    assert_eq!(history.best_move_changes(), 0);
+}
+
+#[test]
+fn test_history_swings_and_instability() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+    let mut history = AnalysisHistory::new();
+    history.update(&depth_info(1, Score::cp(20), e2e4));
+    history.update(&depth_info(2, Score::cp(30), e2e4));
+    history.update(&depth_info(3, Score::cp(-150), d2d4));
+    history.update(&depth_info(4, Score::cp(-140), d2d4));
+    history.update(&depth_info(5, Score::mate(3), d2d4));
+
+    let swings = history.get_swings(100);
+    assert_eq!(swings.len(), 2);
+    assert_eq!(swings[0].get_depth(), 3);
+    assert_eq!(swings[0].get_from(), Score::cp(30));
+    assert_eq!(swings[1].get_to(), Score::mate(3));
+
+    assert_eq!(history.best_move_changes(), 1);
+    assert!(history.is_unstable(3, 50));
+    assert!(!history.is_unstable(2, 50 + 200_000));
+
+    history.clear();
+    history.update(&depth_info(1, Score::cp(20), e2e4));
+    history.update(&depth_info(2, Score::cp(30), e2e4));
+    assert!(!history.is_unstable(2, 50));
+}
@@ -0,0 +1,284 @@
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use chess::{Board, ChessMove, Color};
+
+use analysis::analysis_state::{AnalysisChange, AnalysisState};
+use engine::best_move::BestMove;
+use engine::engine_command::EngineCommand;
+use engine_connection::EngineConnection;
+use error::Error;
+use gui::go::Go;
+
+#[cfg(test)]
+use chess::MoveGen;
+#[cfg(test)]
+use engine::score::Score;
+#[cfg(test)]
+use gui::gui_command::GuiCommand;
+#[cfg(test)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(test)]
+use std::net::TcpListener;
+#[cfg(test)]
+use std::thread::{sleep, spawn};
+#[cfg(test)]
+use test_engines::{connect_fake, fake_engine};
+
+// How long the engine gets to answer `stop` with its best move.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A `go infinite` search that keeps running until it is stopped, from
+/// `EngineConnection::start_infinite_analysis`.
+///
+/// The engine's output is only read when asked for, by `latest`.  Stopping waits for the
+/// engine's best move, and then for `readyok`, so a best move an engine sends late, or twice, is
+/// never mistaken for the result of the next search.  Dropping the session stops the search.
+pub struct AnalysisSession<'c, 'a: 'c> {
+    connection: &'c mut EngineConnection<'a>,
+    board: Board,
+    moves: Vec<ChessMove>,
+    state: AnalysisState,
+    // The best move, if the engine has already sent it without being stopped.
+    finished: Option<BestMove>,
+    stopped: bool,
+    white_pov: bool,
+    // Who is to move in the position being searched, whose side the engine's scores are from.
+    side_to_move: Color,
+}
+
+impl<'c, 'a> AnalysisSession<'c, 'a> {
+    pub(crate) fn start(
+        connection: &'c mut EngineConnection<'a>,
+        board: Board,
+    ) -> Result<AnalysisSession<'c, 'a>, Error> {
+        let mut session = AnalysisSession {
+            connection,
+            board,
+            moves: vec![],
+            state: AnalysisState::new(),
+            finished: None,
+            stopped: true,
+            white_pov: false,
+            side_to_move: board.side_to_move(),
+        };
+        session.go()?;
+        Ok(session)
+    }
+
+    fn go(&mut self) -> Result<(), Error> {
+        self.connection.send_position(self.board, self.moves.clone())?;
+        self.side_to_move = self.connection.current_board().side_to_move();
+        self.connection.send_go_with(Go::infinite(true))?;
+        self.stopped = false;
+        Ok(())
+    }
+
+    /// Whether scores, and win, draw and loss chances, are turned from the side to move's to
+    /// white's as they arrive, so they don't flip sign from one position to the next.
+    pub fn white_pov(mut self, white_pov: bool) -> AnalysisSession<'c, 'a> {
+        self.white_pov = white_pov;
+        self
+    }
+
+    pub fn is_white_pov(&self) -> bool {
+        self.white_pov
+    }
+
+    /// Fold everything the engine has sent so far into the state, and return it.
+    pub fn latest(&mut self) -> Result<&AnalysisState, Error> {
+        while let Some(command) = self.connection.try_recv_command()? {
+            self.receive(command)?;
+        }
+        Ok(&self.state)
+    }
+
+    fn receive(&mut self, command: EngineCommand) -> Result<(), Error> {
+        match command {
+            EngineCommand::Info(ref info) if self.white_pov => {
+                self.state.update(&info.white_pov(self.side_to_move));
+            }
+            EngineCommand::Info(ref info) => {
+                self.state.update(info);
+            }
+            EngineCommand::BestMove(best_move) if !self.stopped && self.finished.is_none() => {
+                self.finished = Some(self.connection.check_best_move(best_move)?);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Receive every change to the state, as `latest` finds them.
+    pub fn subscribe(&mut self) -> Receiver<AnalysisChange> {
+        self.state.subscribe()
+    }
+
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    pub fn get_moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    /// Whether the engine has already sent its best move, as some do on finding a mate.
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_some()
+    }
+
+    // Stop the search and return its best move, once the engine has said everything it will
+    // about it.
+    fn halt(&mut self) -> Result<BestMove, Error> {
+        let best_move = match self.finished.take() {
+            Some(best_move) => best_move,
+            None => {
+                self.connection.send_stop()?;
+                let deadline = Instant::now() + STOP_TIMEOUT;
+                loop {
+                    let command = self.connection.recv_until(
+                        |c| matches!(c, EngineCommand::Info(_) | EngineCommand::BestMove(_)),
+                        deadline,
+                    )?;
+                    match command {
+                        EngineCommand::BestMove(best_move) => {
+                            break self.connection.check_best_move(best_move)?
+                        }
+                        info => self.receive(info)?,
+                    }
+                }
+            }
+        };
+        self.stopped = true;
+
+        // Anything the engine sends before `readyok` belongs to the search just stopped.
+        self.connection.send_isready()?;
+        Ok(best_move)
+    }
+
+    /// Search the position after `moves` from the session's board instead, starting afresh.
+    pub fn update_position(&mut self, moves: Vec<ChessMove>) -> Result<(), Error> {
+        self.halt()?;
+        self.moves = moves;
+        self.state.clear();
+        self.go()
+    }
+
+    /// Stop the search, and return the engine's best move.
+    pub fn stop(mut self) -> Result<BestMove, Error> {
+        self.halt()
+    }
+}
+
+impl<'c, 'a> Drop for AnalysisSession<'c, 'a> {
+    fn drop(&mut self) {
+        if !self.stopped {
+            let _ = self.halt();
+        }
+    }
+}
+
+impl<'a> EngineConnection<'a> {
+    /// Start a `go infinite` search of `board`, to be followed through the session.
+    pub fn start_infinite_analysis<'c>(
+        &'c mut self,
+        board: Board,
+    ) -> Result<AnalysisSession<'c, 'a>, Error> {
+        AnalysisSession::start(self, board)
+    }
+}
+
+// A pretend engine that only answers `stop` during a search, with its best move twice over, as
+// some engines do.
+#[cfg(test)]
+fn infinite_engine() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    spawn(move || {
+        let stream = listener.incoming().next().unwrap().unwrap();
+        let mut output = stream.try_clone().unwrap();
+        let mut board = Board::default();
+        for line in BufReader::new(stream).lines() {
+            let reply = match GuiCommand::parse_validated(&line.unwrap()) {
+                Ok((GuiCommand::Uci, _)) => "uciok".to_string(),
+                Ok((GuiCommand::IsReady, _)) => "readyok".to_string(),
+                Ok((GuiCommand::Position(..), Some(after))) => {
+                    board = after;
+                    continue;
+                }
+                Ok((GuiCommand::Go(_), _)) => {
+                    let m = MoveGen::new_legal(&board).next().unwrap();
+                    format!("info depth 1 score cp 13 pv {}", m)
+                }
+                Ok((GuiCommand::Stop, _)) => {
+                    let m = MoveGen::new_legal(&board).next().unwrap();
+                    format!("bestmove {0}\nbestmove {0}", m)
+                }
+                _ => continue,
+            };
+            if writeln!(output, "{}", reply).is_err() {
+                break;
+            }
+        }
+    });
+    address
+}
+
+#[test]
+fn test_analysis_session() {
+    let address = infinite_engine();
+    let mut connection = connect_fake(&address);
+    let board = Board::default();
+    let first = MoveGen::new_legal(&board).next().unwrap();
+    let mut session = connection.start_infinite_analysis(board).unwrap();
+    let changes = session.subscribe();
+
+    sleep(Duration::from_millis(50));
+    let latest = session.latest().unwrap();
+    assert_eq!(latest.get_best_line().unwrap().get_score(), Some(Score::cp(13)));
+    assert_eq!(changes.try_recv(), Ok(AnalysisChange::Line(1)));
+    assert!(!session.is_finished());
+
+    session.update_position(vec![first]).unwrap();
+    sleep(Duration::from_millis(50));
+    assert!(session.latest().unwrap().get_best_line().is_some());
+    // The second best move of the first search was thrown away.
+    assert!(!session.is_finished());
+
+    let after = board.make_move_new(first);
+    let reply = MoveGen::new_legal(&after).next().unwrap();
+    assert_eq!(session.stop().unwrap().get_move(), Some(reply));
+}
+
+#[test]
+fn test_analysis_session_finished_early() {
+    // Answers every `go` with a best move straight away.
+    let address = fake_engine(false);
+    let mut connection = connect_fake(&address);
+    let board = Board::default();
+    let first = MoveGen::new_legal(&board).next();
+    let mut session = connection.start_infinite_analysis(board).unwrap();
+    sleep(Duration::from_millis(50));
+    session.latest().unwrap();
+    assert!(session.is_finished());
+    assert_eq!(session.stop().unwrap().get_move(), first);
+}
+
+#[test]
+fn test_analysis_session_white_pov() {
+    let address = infinite_engine();
+    let mut connection = connect_fake(&address);
+    let board = Board::default();
+    let first = MoveGen::new_legal(&board).next().unwrap();
+    let mut session = connection
+        .start_infinite_analysis(board.make_move_new(first))
+        .unwrap()
+        .white_pov(true);
+    assert!(session.is_white_pov());
+
+    // The engine likes black's position, which is bad news for white.
+    sleep(Duration::from_millis(50));
+    let latest = session.latest().unwrap();
+    assert_eq!(latest.get_best_line().unwrap().get_score(), Some(Score::cp(-13)));
+    session.stop().unwrap();
+}
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use chess::ChessMove;
+
+use engine::info::Info;
+use engine::score::Score;
+use parsers::Counter;
+
+/// The latest known state of one multipv line.
+#[derive(Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct AnalysisLine {
+    depth: Option<u64>,
+    seldepth: Option<u64>,
+    score: Option<Score>,
+    wdl: Option<(u64, u64, u64)>,
+    pv: Vec<ChessMove>,
+}
+
+impl AnalysisLine {
+    pub fn get_depth(&self) -> Option<u64> {
+        self.depth
+    }
+
+    pub fn get_seldepth(&self) -> Option<u64> {
+        self.seldepth
+    }
+
+    pub fn get_score(&self) -> Option<Score> {
+        self.score
+    }
+
+    pub fn get_wdl(&self) -> Option<(u64, u64, u64)> {
+        self.wdl
+    }
+
+    pub fn get_pv(&self) -> &Vec<ChessMove> {
+        &self.pv
+    }
+
+    fn update(&mut self, info: &Info) -> bool {
+        let mut updated = self.clone();
+
+        if info.get_depth().is_some() {
+            updated.depth = info.get_depth();
+        }
+        if info.get_seldepth().is_some() {
+            updated.seldepth = info.get_seldepth();
+        }
+        if info.get_score().is_some() {
+            updated.score = info.get_score();
+        }
+        if info.get_wdl().is_some() {
+            updated.wdl = info.get_wdl();
+        }
+        if !info.get_pv().is_empty() {
+            updated.pv = info.get_pv().clone();
+        }
+
+        let changed = updated != *self;
+        *self = updated;
+        changed
+    }
+}
+
+/// What changed in an `AnalysisState` after an update.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub enum AnalysisChange {
+    /// The given multipv line has a new depth, score or pv.
+    Line(u64),
+    /// The nodes, nps, time, hash or tablebase statistics changed.
+    Statistics,
+    /// The engine is now searching a different root move.
+    CurrentMove,
+    /// The engine sent a new `info string`.
+    EngineString,
+}
+
+/// Combines the stream of partial `info` lines an engine sends into the latest known picture of
+/// the search: one `AnalysisLine` per multipv index, plus the search-wide statistics.
+#[derive(Default)]
+pub struct AnalysisState {
+    lines: BTreeMap<u64, AnalysisLine>,
+    nodes: Option<Counter>,
+    nps: Option<Counter>,
+    time: Option<u64>,
+    hash_full: Option<f32>,
+    tb_hits: Option<Counter>,
+    cur_move: Option<ChessMove>,
+    cur_move_number: Option<u64>,
+    engine_string: Option<String>,
+    subscribers: Vec<Sender<AnalysisChange>>,
+}
+
+macro_rules! update_field {
+    ($self:ident, $info:ident, $field:ident, $getter:ident, $changed:ident) => {
+        if let Some(x) = $info.$getter() {
+            if $self.$field != Some(x) {
+                $self.$field = Some(x);
+                $changed = true;
+            }
+        }
+    };
+}
+
+impl AnalysisState {
+    pub fn new() -> AnalysisState {
+        AnalysisState::default()
+    }
+
+    /// Fold an `info` line into the state, returning what changed.  Updates that repeat what is
+    /// already known produce no changes.
+    pub fn update(&mut self, info: &Info) -> Vec<AnalysisChange> {
+        let mut changes = vec![];
+
+        if info.get_depth().is_some() || info.get_score().is_some() || !info.get_pv().is_empty()
+        {
+            let index = info.get_multi_pv().unwrap_or(1);
+            if self.lines.entry(index).or_default().update(info) {
+                changes.push(AnalysisChange::Line(index));
+            }
+        }
+
+        let mut statistics = false;
+        update_field!(self, info, nodes, get_nodes, statistics);
+        update_field!(self, info, nps, get_nps, statistics);
+        update_field!(self, info, time, get_time, statistics);
+        update_field!(self, info, hash_full, get_hash_full, statistics);
+        update_field!(self, info, tb_hits, get_tbhits, statistics);
+        if statistics {
+            changes.push(AnalysisChange::Statistics);
+        }
+
+        let mut current_move = false;
+        update_field!(self, info, cur_move, cur_get_move, current_move);
+        update_field!(self, info, cur_move_number, get_cur_move_number, current_move);
+        if current_move {
+            changes.push(AnalysisChange::CurrentMove);
+        }
+
+        if let Some(ref s) = *info.get_engine_string() {
+            self.engine_string = Some(s.clone());
+            changes.push(AnalysisChange::EngineString);
+        }
+
+        self.notify(&changes);
+        changes
+    }
+
+    /// Receive every future change through a channel.  Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<AnalysisChange> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    fn notify(&mut self, changes: &[AnalysisChange]) {
+        if changes.is_empty() {
+            return;
+        }
+        self.subscribers
+            .retain(|s| changes.iter().all(|change| s.send(*change).is_ok()));
+    }
+
+    /// Forget everything about the previous search, keeping the subscribers.
+    pub fn clear(&mut self) {
+        let subscribers = std::mem::take(&mut self.subscribers);
+        *self = AnalysisState {
+            subscribers,
+            ..AnalysisState::default()
+        };
+    }
+
+    pub fn get_line(&self, multi_pv: u64) -> Option<&AnalysisLine> {
+        self.lines.get(&multi_pv)
+    }
+
+    /// The principal line (multipv 1).
+    pub fn get_best_line(&self) -> Option<&AnalysisLine> {
+        self.get_line(1)
+    }
+
+    /// All known lines, ordered by multipv index.
+    pub fn get_lines(&self) -> impl Iterator<Item = (u64, &AnalysisLine)> {
+        self.lines.iter().map(|(index, line)| (*index, line))
+    }
+
+    pub fn get_nodes(&self) -> Option<Counter> {
+        self.nodes
+    }
+
+    pub fn get_nps(&self) -> Option<Counter> {
+        self.nps
+    }
+
+    pub fn get_time(&self) -> Option<u64> {
+        self.time
+    }
+
+    pub fn get_hash_full(&self) -> Option<f32> {
+        self.hash_full
+    }
+
+    pub fn get_tbhits(&self) -> Option<Counter> {
+        self.tb_hits
+    }
+
+    pub fn get_cur_move(&self) -> Option<ChessMove> {
+        self.cur_move
+    }
+
+    pub fn get_cur_move_number(&self) -> Option<u64> {
+        self.cur_move_number
+    }
+
+    pub fn get_engine_string(&self) -> &Option<String> {
+        &self.engine_string
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+
+#[test]
+fn test_lines_per_multipv() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+
+    let mut state = AnalysisState::new();
+    state.update(
+        &Info::depth(5)
+            .combine(&Info::multi_pv(1))
+            .combine(&Info::score(Score::cp(30)))
+            .combine(&Info::pv(vec![e2e4])),
+    );
+    state.update(
+        &Info::depth(5)
+            .combine(&Info::multi_pv(2))
+            .combine(&Info::score(Score::cp(20)))
+            .combine(&Info::pv(vec![d2d4])),
+    );
+
+    assert_eq!(state.get_lines().count(), 2);
+    assert_eq!(state.get_best_line().unwrap().get_pv(), &vec![e2e4]);
+    assert_eq!(state.get_line(2).unwrap().get_score(), Some(Score::cp(20)));
+}
+
+#[test]
+fn test_partial_updates_are_merged() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+
+    let mut state = AnalysisState::new();
+    state.update(&Info::depth(3).combine(&Info::pv(vec![e2e4])));
+    state.update(&Info::score(Score::cp(15)));
+
+    let line = state.get_best_line().unwrap();
+    assert_eq!(line.get_depth(), Some(3));
+    assert_eq!(line.get_score(), Some(Score::cp(15)));
+    assert_eq!(line.get_pv(), &vec![e2e4]);
+}
+
+#[test]
+fn test_changes_are_deduplicated() {
+    let mut state = AnalysisState::new();
+    let subscriber = state.subscribe();
+
+    let info = Info::depth(3).combine(&Info::nodes(100));
+    assert_eq!(
+        state.update(&info),
+        vec![AnalysisChange::Line(1), AnalysisChange::Statistics]
+    );
+    assert_eq!(state.update(&info), vec![]);
+    assert_eq!(
+        state.update(&Info::cur_move(ChessMove::default())),
+        vec![AnalysisChange::CurrentMove]
+    );
+
+    assert_eq!(
+        subscriber.try_iter().collect::<Vec<AnalysisChange>>(),
+        vec![
+            AnalysisChange::Line(1),
+            AnalysisChange::Statistics,
+            AnalysisChange::CurrentMove
+        ]
+    );
+}
+
+#[test]
+fn test_clear() {
+    let mut state = AnalysisState::new();
+    let subscriber = state.subscribe();
+    state.update(&Info::depth(3));
+    state.clear();
+
+    assert!(state.get_best_line().is_none());
+    state.update(&Info::depth(1));
+    assert_eq!(subscriber.try_iter().count(), 2);
+}
@@ -0,0 +1,432 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use chess::{Board, BoardStatus, ChessMove};
+
+use analysis::analysis_history::AnalysisHistory;
+use engine::info::Info;
+use engine::score::Score;
+use engine_base::engine::DEFAULT_MAX_DEPTH;
+use engine_base::iterative_deepening::IterativeDeepening;
+use engine_base::search_limits::SearchLimits;
+use engine_connection::EngineConnection;
+use error::Error;
+use gui::go::Go;
+use gui::gui_command::apply_moves;
+use san::to_san;
+
+// How long an engine gets to finish one position.
+const EVALUATE_TIMEOUT: Duration = Duration::from_secs(60);
+// Scores beyond this are all just winning, so losing a little of one isn't a mistake.
+const WINNING_CP: i64 = 1000;
+
+/// Something that can score a position for the side to move and pick its best move.
+pub trait PositionEvaluator {
+    /// Evaluate the position reached by playing `moves` from `board`, searching no further than
+    /// `limit`.
+    fn evaluate(
+        &mut self,
+        board: Board,
+        moves: &[ChessMove],
+        limit: &Go,
+    ) -> Result<(Score, Option<ChessMove>), Error>;
+}
+
+impl<'a> PositionEvaluator for EngineConnection<'a> {
+    fn evaluate(
+        &mut self,
+        board: Board,
+        moves: &[ChessMove],
+        limit: &Go,
+    ) -> Result<(Score, Option<ChessMove>), Error> {
+        self.send_position(board, moves.to_vec())?;
+        self.send_go_with(limit.clone())?;
+        let best_move = self.recv_best_move_timeout(EVALUATE_TIMEOUT)?;
+        let score = self
+            .analysis_history()
+            .get_last()
+            .and_then(|info| info.get_score())
+            .ok_or(Error::NoCommandError)?;
+        Ok((score, best_move.get_move()))
+    }
+}
+
+/// Evaluates positions with this crate's own search.  Only the depth and node limits of a `go`
+/// are used; the searcher's own timer still applies.
+pub struct SearchEvaluator<I: IterativeDeepening> {
+    searcher: I,
+}
+
+impl<I: IterativeDeepening> SearchEvaluator<I> {
+    pub fn new(searcher: I) -> SearchEvaluator<I> {
+        SearchEvaluator { searcher }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.searcher
+    }
+}
+
+impl<I: IterativeDeepening> PositionEvaluator for SearchEvaluator<I> {
+    fn evaluate(
+        &mut self,
+        board: Board,
+        moves: &[ChessMove],
+        limit: &Go,
+    ) -> Result<(Score, Option<ChessMove>), Error> {
+        let position = apply_moves(board, moves)?;
+        let limits = SearchLimits::from_go(limit);
+        let max_depth = limits.cap_depth(DEFAULT_MAX_DEPTH);
+        self.searcher.set_limits(limits);
+
+        let mut output = vec![];
+        let pv = self
+            .searcher
+            .id_search(position, max_depth, moves.len() as u16, &mut output);
+
+        let mut history = AnalysisHistory::new();
+        for line in String::from_utf8_lossy(&output).lines() {
+            if let Ok(info) = Info::from_str(line) {
+                history.update(&info);
+            }
+        }
+        let score = history
+            .get_last()
+            .and_then(|info| info.get_score())
+            .ok_or(Error::NoCommandError)?;
+        Ok((score, pv.as_slice().first().copied()))
+    }
+}
+
+/// How bad a move was, by the centipawns it gave away.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum MoveClass {
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClass {
+    /// The PGN NAG for the class: `$6` (?!), `$2` (?) or `$4` (??).
+    pub fn get_nag(&self) -> Option<u8> {
+        match self {
+            MoveClass::Good => None,
+            MoveClass::Inaccuracy => Some(6),
+            MoveClass::Mistake => Some(2),
+            MoveClass::Blunder => Some(4),
+        }
+    }
+}
+
+/// What the engine thought of one move of a game.  Scores are from the point of view of the
+/// player who made the move.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Annotation {
+    ply: usize,
+    chess_move: ChessMove,
+    san: String,
+    best_move: Option<ChessMove>,
+    best_san: Option<String>,
+    before: Score,
+    after: Score,
+    loss: i64,
+    class: MoveClass,
+}
+
+// Write a score the way PGN comments usually do: `+0.35`, `-1.20` or `#-3`.
+fn format_score(score: Score) -> String {
+    if score.is_mate() {
+        format!("#{}", score.get_value())
+    } else {
+        format!("{:+.2}", score.get_value() as f64 / 100.0)
+    }
+}
+
+impl Annotation {
+    /// Which move of the game this is, from zero.
+    pub fn get_ply(&self) -> usize {
+        self.ply
+    }
+
+    pub fn get_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    pub fn get_san(&self) -> &str {
+        &self.san
+    }
+
+    pub fn get_best_move(&self) -> Option<ChessMove> {
+        self.best_move
+    }
+
+    /// The score of the best move.
+    pub fn get_before(&self) -> Score {
+        self.before
+    }
+
+    /// The score of the move played.
+    pub fn get_after(&self) -> Score {
+        self.after
+    }
+
+    /// Centipawns given away, never negative.
+    pub fn get_loss(&self) -> i64 {
+        self.loss
+    }
+
+    pub fn get_class(&self) -> MoveClass {
+        self.class
+    }
+
+    /// A PGN comment, without the braces, such as `Blunder (-0.10 → #-1). Best was d4.`, or
+    /// `None` for a good move.
+    pub fn get_comment(&self) -> Option<String> {
+        let class = match self.class {
+            MoveClass::Good => return None,
+            MoveClass::Inaccuracy => "Inaccuracy",
+            MoveClass::Mistake => "Mistake",
+            MoveClass::Blunder => "Blunder",
+        };
+        let mut comment = format!(
+            "{} ({} → {}).",
+            class,
+            format_score(self.before),
+            format_score(self.after)
+        );
+        if let Some(ref best) = self.best_san {
+            comment += &format!(" Best was {}.", best);
+        }
+        Some(comment)
+    }
+}
+
+// The opponent's score for the position after a move, as a score for the move itself.
+fn for_mover(score: Score) -> Score {
+    let value = score.get_value();
+    if !score.is_mate() {
+        Score::cp(-value)
+    } else if value <= 0 {
+        // The move itself is one more move towards the mate.
+        Score::mate(1 - value)
+    } else {
+        Score::mate(-value)
+    }
+}
+
+fn clamped_cp(score: Score) -> i64 {
+    if score.is_mate() {
+        if score.get_value() > 0 {
+            WINNING_CP
+        } else {
+            -WINNING_CP
+        }
+    } else {
+        score.get_value().clamp(-WINNING_CP, WINNING_CP)
+    }
+}
+
+/// Reviews a game with an engine, flagging the moves that lost too much.
+///
+/// By default an inaccuracy loses 50 centipawns, a mistake 100 and a blunder 300.  All scores
+/// are capped at ten pawns either way, so a winning position only loses its gloss when it stops
+/// being clearly winning.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotator {
+    limit: Go,
+    inaccuracy: i64,
+    mistake: i64,
+    blunder: i64,
+}
+
+impl Annotator {
+    /// Search every position to `limit`, which should be a fixed limit like `Go::depth`.
+    pub fn new(limit: Go) -> Annotator {
+        Annotator {
+            limit,
+            inaccuracy: 50,
+            mistake: 100,
+            blunder: 300,
+        }
+    }
+
+    /// The smallest losses, in centipawns, counted as each class.
+    pub fn thresholds(mut self, inaccuracy: i64, mistake: i64, blunder: i64) -> Annotator {
+        self.inaccuracy = inaccuracy;
+        self.mistake = mistake;
+        self.blunder = blunder;
+        self
+    }
+
+    pub fn classify(&self, loss: i64) -> MoveClass {
+        if loss >= self.blunder {
+            MoveClass::Blunder
+        } else if loss >= self.mistake {
+            MoveClass::Mistake
+        } else if loss >= self.inaccuracy {
+            MoveClass::Inaccuracy
+        } else {
+            MoveClass::Good
+        }
+    }
+
+    /// Annotate every move of the game played from `board`.  Positions that end the game are
+    /// scored without asking the evaluator.
+    pub fn annotate<E: PositionEvaluator>(
+        &self,
+        evaluator: &mut E,
+        board: Board,
+        moves: &[ChessMove],
+    ) -> Result<Vec<Annotation>, Error> {
+        // Fails on the first illegal move, before any searching.
+        apply_moves(board, moves)?;
+
+        let mut annotations = vec![];
+        let mut current = board;
+        let mut evaluation = evaluator.evaluate(board, &[], &self.limit)?;
+        for (ply, &m) in moves.iter().enumerate() {
+            let (before, best_move) = evaluation;
+            let next = current.make_move_new(m);
+
+            let after = match next.status() {
+                BoardStatus::Checkmate => Score::mate(1),
+                BoardStatus::Stalemate => Score::cp(0),
+                BoardStatus::Ongoing => {
+                    evaluation = evaluator.evaluate(board, &moves[..=ply], &self.limit)?;
+                    for_mover(evaluation.0)
+                }
+            };
+
+            let loss = if best_move == Some(m) {
+                0
+            } else {
+                (clamped_cp(before) - clamped_cp(after)).max(0)
+            };
+            annotations.push(Annotation {
+                ply,
+                chess_move: m,
+                san: to_san(&current, m)?,
+                best_move,
+                best_san: best_move.and_then(|b| to_san(&current, b).ok()),
+                before,
+                after,
+                loss,
+                class: self.classify(loss),
+            });
+            current = next;
+        }
+        Ok(annotations)
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use engine_base::evaluate::DefaultEvaluate;
+#[cfg(test)]
+use engine_base::iterative_deepening::DefaultIterativeDeepening;
+#[cfg(test)]
+use engine_base::search::DefaultSearch;
+#[cfg(test)]
+use engine_base::time_manager::DefaultTimeManager;
+#[cfg(test)]
+use std::sync::atomic::AtomicBool;
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
+use timer::timer::Timer;
+
+// Gives canned evaluations, one per position, in order.
+#[cfg(test)]
+struct Scripted(Vec<(Score, Option<ChessMove>)>);
+
+#[cfg(test)]
+impl PositionEvaluator for Scripted {
+    fn evaluate(
+        &mut self,
+        _board: Board,
+        _moves: &[ChessMove],
+        _limit: &Go,
+    ) -> Result<(Score, Option<ChessMove>), Error> {
+        Ok(self.0.remove(0))
+    }
+}
+
+#[test]
+fn test_annotate_fools_mate() {
+    let mv = |from, to| ChessMove::new(from, to, None);
+    let moves = [
+        mv(Square::F2, Square::F3),
+        mv(Square::E7, Square::E5),
+        mv(Square::G2, Square::G4),
+        mv(Square::D8, Square::H4),
+    ];
+    let mut evaluator = Scripted(vec![
+        (Score::cp(30), Some(mv(Square::E2, Square::E4))),
+        (Score::cp(20), Some(mv(Square::E7, Square::E5))),
+        (Score::cp(-10), Some(mv(Square::D2, Square::D4))),
+        (Score::mate(1), Some(mv(Square::D8, Square::H4))),
+    ]);
+
+    let annotations = Annotator::new(Go::depth(10))
+        .annotate(&mut evaluator, Board::default(), &moves)
+        .unwrap();
+    assert!(evaluator.0.is_empty());
+    let classes = annotations.iter().map(|a| a.get_class()).collect::<Vec<MoveClass>>();
+    assert_eq!(
+        classes,
+        vec![MoveClass::Inaccuracy, MoveClass::Good, MoveClass::Blunder, MoveClass::Good]
+    );
+
+    let blunder = &annotations[2];
+    assert_eq!(blunder.get_san(), "g4");
+    assert_eq!(blunder.get_after(), Score::mate(-1));
+    assert_eq!(blunder.get_loss(), 990);
+    assert_eq!(blunder.get_class().get_nag(), Some(4));
+    assert_eq!(
+        blunder.get_comment(),
+        Some("Blunder (-0.10 → #-1). Best was d4.".to_string())
+    );
+    assert_eq!(annotations[3].get_after(), Score::mate(1));
+    assert_eq!(annotations[3].get_comment(), None);
+
+    let strict = Annotator::new(Go::depth(10)).thresholds(10, 20, 40);
+    assert_eq!(strict.classify(50), MoveClass::Blunder);
+    assert_eq!(strict.classify(9), MoveClass::Good);
+}
+
+#[test]
+fn test_annotate_rejects_illegal_moves() {
+    let mut evaluator = Scripted(vec![]);
+    let illegal = [ChessMove::new(Square::E2, Square::E5, None)];
+    match Annotator::new(Go::depth(1)).annotate(&mut evaluator, Board::default(), &illegal) {
+        Err(Error::IllegalMove { index, .. }) => assert_eq!(index, 0),
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn test_annotate_with_search() {
+    let searcher = DefaultIterativeDeepening::new(
+        DefaultSearch::new(
+            Arc::<AtomicBool>::new(AtomicBool::new(false)),
+            DefaultEvaluate::default(),
+        ),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+    let mut evaluator = SearchEvaluator::new(searcher);
+
+    // Rxd5 wins the queen; Kf1 leaves the rook hanging to it instead.
+    let board = Board::from_str("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+    let annotations = Annotator::new(Go::depth(2))
+        .annotate(&mut evaluator, board, &[ChessMove::new(Square::E1, Square::F1, None)])
+        .unwrap();
+    assert!(annotations[0].get_before().get_value() > 0);
+    assert_eq!(
+        annotations[0].get_best_move(),
+        Some(ChessMove::new(Square::D2, Square::D5, None))
+    );
+    assert_eq!(annotations[0].get_class(), MoveClass::Blunder);
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use chess::{Board, ChessMove};
+
+use san::from_san;
+
+// ECO code, name, and the moves from the starting position, in SAN.  Later entries are deeper
+// lines of earlier ones; classifying finds the deepest position the game passed through, so the
+// order of the table doesn't matter.
+const ECO_TABLE: &[(&str, &str, &str)] = &[
+    ("A00", "Polish Opening", "b4"),
+    ("A00", "Grob Opening", "g4"),
+    ("A01", "Nimzo-Larsen Attack", "b3"),
+    ("A02", "Bird's Opening", "f4"),
+    ("A04", "Réti Opening", "Nf3"),
+    ("A05", "Réti Opening", "Nf3 Nf6"),
+    ("A06", "Réti Opening", "Nf3 d5"),
+    ("A07", "King's Indian Attack", "Nf3 d5 g3"),
+    ("A10", "English Opening", "c4"),
+    ("A20", "English Opening", "c4 e5"),
+    ("A30", "English Opening: Symmetrical Variation", "c4 c5"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A43", "Old Benoni Defence", "d4 c5"),
+    ("A45", "Indian Defence", "d4 Nf6"),
+    ("A46", "Indian Defence", "d4 Nf6 Nf3"),
+    ("A50", "Indian Defence", "d4 Nf6 c4"),
+    ("A56", "Benoni Defence", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    ("A60", "Modern Benoni", "d4 Nf6 c4 c5 d5 e6"),
+    ("A80", "Dutch Defence", "d4 f5"),
+    ("B00", "King's Pawn Opening", "e4"),
+    ("B01", "Scandinavian Defence", "e4 d5"),
+    ("B02", "Alekhine's Defence", "e4 Nf6"),
+    ("B06", "Modern Defence", "e4 g6"),
+    ("B07", "Pirc Defence", "e4 d6 d4 Nf6"),
+    ("B10", "Caro-Kann Defence", "e4 c6"),
+    ("B12", "Caro-Kann Defence: Advance Variation", "e4 c6 d4 d5 e5"),
+    ("B13", "Caro-Kann Defence: Exchange Variation", "e4 c6 d4 d5 exd5 cxd5"),
+    ("B20", "Sicilian Defence", "e4 c5"),
+    ("B22", "Sicilian Defence: Alapin Variation", "e4 c5 c3"),
+    ("B23", "Sicilian Defence: Closed", "e4 c5 Nc3"),
+    ("B27", "Sicilian Defence", "e4 c5 Nf3"),
+    ("B30", "Sicilian Defence", "e4 c5 Nf3 Nc6"),
+    ("B33", "Sicilian Defence: Open", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6"),
+    ("B40", "Sicilian Defence", "e4 c5 Nf3 e6"),
+    ("B50", "Sicilian Defence", "e4 c5 Nf3 d6"),
+    ("B54", "Sicilian Defence: Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    ("B70", "Sicilian Defence: Dragon Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6"),
+    ("B90", "Sicilian Defence: Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6"),
+    ("C00", "French Defence", "e4 e6"),
+    ("C01", "French Defence: Exchange Variation", "e4 e6 d4 d5 exd5"),
+    ("C02", "French Defence: Advance Variation", "e4 e6 d4 d5 e5"),
+    ("C03", "French Defence: Tarrasch Variation", "e4 e6 d4 d5 Nd2"),
+    ("C10", "French Defence", "e4 e6 d4 d5 Nc3"),
+    ("C15", "French Defence: Winawer Variation", "e4 e6 d4 d5 Nc3 Bb4"),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C41", "Philidor Defence", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defence", "e4 e5 Nf3 Nf6"),
+    ("C44", "King's Pawn Game", "e4 e5 Nf3 Nc6"),
+    ("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    ("C46", "Three Knights Game", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    ("C51", "Evans Gambit", "e4 e5 Nf3 Nc6 Bc4 Bc5 b4"),
+    ("C55", "Two Knights Defence", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C65", "Ruy Lopez: Berlin Defence", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    ("C68", "Ruy Lopez: Exchange Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6"),
+    ("C70", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4"),
+    ("C80", "Ruy Lopez: Open", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Nxe4"),
+    ("C84", "Ruy Lopez: Closed", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7"),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D02", "Queen's Pawn Game", "d4 d5 Nf3"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    ("D07", "Queen's Gambit Declined: Chigorin Defence", "d4 d5 c4 Nc6"),
+    ("D08", "Queen's Gambit Declined: Albin Countergambit", "d4 d5 c4 e5"),
+    ("D10", "Slav Defence", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D35", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3 Nf6"),
+    ("D43", "Semi-Slav Defence", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6"),
+    ("D80", "Grünfeld Defence", "d4 Nf6 c4 g6 Nc3 d5"),
+    ("E00", "Indian Defence", "d4 Nf6 c4 e6"),
+    ("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    ("E11", "Bogo-Indian Defence", "d4 Nf6 c4 e6 Nf3 Bb4+"),
+    ("E12", "Queen's Indian Defence", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defence", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E60", "King's Indian Defence", "d4 Nf6 c4 g6"),
+    ("E61", "King's Indian Defence", "d4 Nf6 c4 g6 Nc3 Bg7"),
+    ("E70", "King's Indian Defence", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6"),
+];
+
+/// A named opening from the ECO (Encyclopaedia of Chess Openings) classification.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Opening {
+    eco: &'static str,
+    name: &'static str,
+}
+
+impl Opening {
+    /// The ECO code, such as `C60`.
+    pub fn get_eco(&self) -> &'static str {
+        self.eco
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The `[ECO]` and `[Opening]` PGN tag pairs, one per line.
+    pub fn to_pgn_tags(&self) -> String {
+        format!("[ECO \"{}\"]\n[Opening \"{}\"]\n", self.eco, self.name)
+    }
+}
+
+// Every position in the table, by hash, with the opening it belongs to.
+fn positions() -> &'static HashMap<u64, Opening> {
+    static POSITIONS: OnceLock<HashMap<u64, Opening>> = OnceLock::new();
+    POSITIONS.get_or_init(|| {
+        let mut positions = HashMap::new();
+        for &(eco, name, moves) in ECO_TABLE {
+            let mut board = Board::default();
+            for san in moves.split_whitespace() {
+                let m = from_san(&board, san).expect("The ECO table must only hold legal moves.");
+                board = board.make_move_new(m);
+            }
+            positions.insert(board.get_hash(), Opening { eco, name });
+        }
+        positions
+    })
+}
+
+/// The opening `board` is a position of, if it is in the table.
+pub fn classify_position(board: &Board) -> Option<Opening> {
+    positions().get(&board.get_hash()).copied()
+}
+
+/// The opening of the game played from `board`: that of the last position in the table that
+/// the game reached, so transpositions are classified by where they ended up.  Moves after
+/// the first illegal one are ignored.
+pub fn classify(board: Board, moves: &[ChessMove]) -> Option<Opening> {
+    let mut current = board;
+    let mut opening = classify_position(&current);
+    for &m in moves {
+        if !current.legal(m) {
+            break;
+        }
+        current = current.make_move_new(m);
+        opening = classify_position(&current).or(opening);
+    }
+    opening
+}
+
+#[cfg(test)]
+use std::str::FromStr;
+
+#[cfg(test)]
+fn san_moves(moves: &str) -> Vec<ChessMove> {
+    let mut board = Board::default();
+    let mut result = vec![];
+    for san in moves.split_whitespace() {
+        let m = from_san(&board, san).unwrap();
+        board = board.make_move_new(m);
+        result.push(m);
+    }
+    result
+}
+
+#[test]
+fn test_eco_table_is_legal() {
+    // Two lines reaching the same position would hide one of them.
+    assert_eq!(positions().len(), ECO_TABLE.len());
+}
+
+#[test]
+fn test_classify_openings() {
+    let ruy_lopez = classify(Board::default(), &san_moves("e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1"))
+        .unwrap();
+    assert_eq!(ruy_lopez.get_eco(), "C84");
+    assert_eq!(
+        ruy_lopez.to_pgn_tags(),
+        "[ECO \"C84\"]\n[Opening \"Ruy Lopez: Closed\"]\n"
+    );
+
+    // Reached through the Queen's Gambit Declined move order instead.
+    let semi_slav = classify(Board::default(), &san_moves("d4 d5 c4 e6 Nf3 Nf6 Nc3 c6")).unwrap();
+    assert_eq!(semi_slav.get_name(), "Semi-Slav Defence");
+
+    assert_eq!(classify(Board::default(), &[]), None);
+    assert_eq!(classify(Board::default(), &san_moves("a3")), None);
+    assert_eq!(classify(Board::default(), &san_moves("e4 a6")).unwrap().get_eco(), "B00");
+
+    let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+    assert_eq!(classify_position(&Board::from_str(fen).unwrap()), None);
+}
@@ -0,0 +1,6 @@
+pub mod analysis_session;
+pub mod analysis_history;
+pub mod analysis_state;
+pub mod annotate;
+pub mod eco;
+pub mod move_selector;
@@ -0,0 +1,161 @@
+use chess::ChessMove;
+
+use analysis::analysis_state::{AnalysisLine, AnalysisState};
+use engine::score::Score;
+use rng::Rng;
+
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use engine::info::Info;
+
+// Mates sort above every centipawn score, sooner mates first.
+const MATE_CP: i64 = 100_000;
+
+/// How a `MoveSelector` picks among the lines of a multipv search.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SelectionPolicy {
+    /// Always the engine's best move.
+    Best,
+    /// Any move scoring within this many centipawns of the best, each as likely as the others.
+    WithinCp(i64),
+    /// Any move, with odds in proportion to the points its win/draw/loss chances are worth.
+    /// Lines without a `wdl` aren't picked.
+    WeightedByWdl,
+}
+
+/// Picks a move from an `AnalysisState`, for sparring partners that shouldn't always play the
+/// engine's best move.  The choices are random, but the same every time for the same seed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveSelector {
+    policy: SelectionPolicy,
+    rng: Rng,
+}
+
+fn sort_value(score: Score) -> i64 {
+    let value = score.get_value();
+    if !score.is_mate() {
+        value.clamp(-MATE_CP + 1000, MATE_CP - 1000)
+    } else if value > 0 {
+        MATE_CP - value
+    } else {
+        -MATE_CP - value
+    }
+}
+
+impl MoveSelector {
+    pub fn new(policy: SelectionPolicy, seed: u64) -> MoveSelector {
+        MoveSelector {
+            policy,
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn get_policy(&self) -> SelectionPolicy {
+        self.policy
+    }
+
+    /// The move to play, or `None` if no line has a move yet.  When the policy can't choose,
+    /// because no line has a score or a `wdl`, the best move is played.
+    pub fn select(&mut self, analysis: &AnalysisState) -> Option<ChessMove> {
+        let lines = analysis
+            .get_lines()
+            .filter(|(_, line)| !line.get_pv().is_empty())
+            .map(|(_, line)| line)
+            .collect::<Vec<&AnalysisLine>>();
+        let best = lines.first()?.get_pv()[0];
+
+        let candidates = match self.policy {
+            SelectionPolicy::Best => vec![],
+            SelectionPolicy::WithinCp(margin) => {
+                let scored = lines
+                    .iter()
+                    .filter_map(|line| line.get_score().map(|s| (line.get_pv()[0], sort_value(s))))
+                    .collect::<Vec<(ChessMove, i64)>>();
+                match scored.iter().map(|&(_, value)| value).max() {
+                    Some(top) => scored
+                        .into_iter()
+                        .filter(|&(_, value)| value >= top.saturating_sub(margin.max(0)))
+                        .map(|(m, _)| (m, 1))
+                        .collect(),
+                    None => vec![],
+                }
+            }
+            SelectionPolicy::WeightedByWdl => lines
+                .iter()
+                .filter_map(|line| {
+                    line.get_wdl().map(|(win, draw, _)| (line.get_pv()[0], 2 * win + draw))
+                })
+                .filter(|&(_, weight)| weight > 0)
+                .collect(),
+        };
+
+        let total = candidates.iter().map(|&(_, weight)| weight).sum::<u64>();
+        if total == 0 {
+            return Some(best);
+        }
+        let mut pick = self.rng.below(total);
+        for (m, weight) in candidates {
+            if pick < weight {
+                return Some(m);
+            }
+            pick -= weight;
+        }
+        Some(best)
+    }
+}
+
+#[cfg(test)]
+fn sparring_state() -> AnalysisState {
+    let mut state = AnalysisState::new();
+    let lines = vec![
+        (Square::E2, Square::E4, Score::cp(40), (300, 600, 100)),
+        (Square::D2, Square::D4, Score::cp(25), (250, 650, 100)),
+        (Square::G2, Square::G4, Score::cp(-120), (0, 0, 1000)),
+    ];
+    for (index, (from, to, score, wdl)) in lines.into_iter().enumerate() {
+        state.update(
+            &Info::multi_pv(index as u64 + 1)
+                .combine(&Info::score(score))
+                .combine(&Info::wdl(wdl))
+                .combine(&Info::pv(vec![ChessMove::new(from, to, None)])),
+        );
+    }
+    state
+}
+
+#[test]
+fn test_move_selector() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+    let state = sparring_state();
+
+    assert_eq!(MoveSelector::new(SelectionPolicy::Best, 1).select(&AnalysisState::new()), None);
+    let mut best = MoveSelector::new(SelectionPolicy::Best, 1);
+    assert!((0..20).all(|_| best.select(&state) == Some(e2e4)));
+    let mut tight = MoveSelector::new(SelectionPolicy::WithinCp(10), 1);
+    assert!((0..20).all(|_| tight.select(&state) == Some(e2e4)));
+
+    for policy in [SelectionPolicy::WithinCp(20), SelectionPolicy::WeightedByWdl] {
+        let mut selector = MoveSelector::new(policy, 7);
+        let picks = (0..200).map(|_| selector.select(&state).unwrap()).collect::<Vec<ChessMove>>();
+        assert!(picks.contains(&e2e4) && picks.contains(&d2d4));
+        assert!(picks.iter().all(|m| *m == e2e4 || *m == d2d4));
+
+        let mut again = MoveSelector::new(policy, 7);
+        assert!(picks.iter().all(|m| again.select(&state) == Some(*m)));
+    }
+}
+
+#[test]
+fn test_move_selector_mates() {
+    let mut state = AnalysisState::new();
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+    for (index, score, m) in [(1, Score::mate(3), e2e4), (2, Score::cp(900), d2d4)] {
+        state.update(&Info::multi_pv(index).combine(&Info::score(score)).combine(&Info::pv(vec![m])));
+    }
+
+    let mut selector = MoveSelector::new(SelectionPolicy::WithinCp(500), 3);
+    assert!((0..20).all(|_| selector.select(&state) == Some(e2e4)));
+}
@@ -0,0 +1,122 @@
+use std::fs::read_to_string;
+use std::thread::available_parallelism;
+
+use engine::engine_info::EngineInfo;
+use engine::option_type::OptionType;
+
+#[cfg(test)]
+use engine::engine_command::EngineCommand;
+#[cfg(test)]
+use engine::engine_option::EngineOption;
+
+/// The machine the engine is running on.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SystemResources {
+    cores: u64,
+    memory_mb: Option<u64>,
+}
+
+impl SystemResources {
+    pub fn new(cores: u64, memory_mb: Option<u64>) -> SystemResources {
+        SystemResources { cores, memory_mb }
+    }
+
+    /// Count the cores we may use, and, where the OS tells us, the total memory.
+    pub fn detect() -> SystemResources {
+        let cores = available_parallelism().map(|n| n.get() as u64).unwrap_or(1);
+        SystemResources::new(cores, total_memory_mb())
+    }
+
+    pub fn get_cores(&self) -> u64 {
+        self.cores
+    }
+
+    pub fn get_memory_mb(&self) -> Option<u64> {
+        self.memory_mb
+    }
+}
+
+// Only Linux is supported for now; elsewhere `Hash` is left alone.
+fn total_memory_mb() -> Option<u64> {
+    let meminfo = read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemTotal:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb / 1024)
+}
+
+/// The `Threads` and `Hash` values picked by `auto_configure`.  `None` means the engine doesn't
+/// have that option (or the memory size isn't known), so it was not set.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct AutoConfig {
+    threads: Option<i64>,
+    hash: Option<i64>,
+}
+
+impl AutoConfig {
+    /// Leave one core for the GUI, and use a quarter of the memory for the hash table, clamped to
+    /// what the engine accepts.
+    pub fn choose(info: &EngineInfo, resources: SystemResources) -> AutoConfig {
+        let threads = resources.get_cores().saturating_sub(1).max(1) as i64;
+        AutoConfig {
+            threads: clamp_spin(info, "Threads", threads),
+            hash: resources
+                .get_memory_mb()
+                .and_then(|mb| clamp_spin(info, "Hash", (mb / 4) as i64)),
+        }
+    }
+
+    pub fn get_threads(&self) -> Option<i64> {
+        self.threads
+    }
+
+    pub fn get_hash(&self) -> Option<i64> {
+        self.hash
+    }
+}
+
+fn clamp_spin(info: &EngineInfo, name: &str, value: i64) -> Option<i64> {
+    match info.get_option(name).map(|o| o.get_option_type()) {
+        Some(OptionType::Spin(_, min, max)) => Some(value.min(*max).max(*min)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+fn info_with(options: Vec<EngineOption>) -> EngineInfo {
+    let mut info = EngineInfo::default();
+    for option in options.into_iter() {
+        info.update(&EngineCommand::EngineOption(option));
+    }
+    info
+}
+
+#[test]
+fn test_auto_config_choose() {
+    let info = info_with(vec![
+        EngineOption::new("Threads".to_string(), OptionType::Spin(1, 1, 512)),
+        EngineOption::new("Hash".to_string(), OptionType::Spin(16, 1, 1024)),
+    ]);
+
+    let config = AutoConfig::choose(&info, SystemResources::new(8, Some(16384)));
+    assert_eq!(config.get_threads(), Some(7));
+    assert_eq!(config.get_hash(), Some(1024));
+
+    let config = AutoConfig::choose(&info, SystemResources::new(1, Some(2048)));
+    assert_eq!(config.get_threads(), Some(1));
+    assert_eq!(config.get_hash(), Some(512));
+}
+
+#[test]
+fn test_auto_config_missing_options() {
+    let info = info_with(vec![EngineOption::new("Threads".to_string(), OptionType::Check(false))]);
+
+    assert_eq!(
+        AutoConfig::choose(&info, SystemResources::new(4, None)),
+        AutoConfig::default()
+    );
+}
+
+#[test]
+fn test_system_resources_detect() {
+    assert!(SystemResources::detect().get_cores() >= 1);
+}
@@ -0,0 +1,353 @@
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use chess::{Board, Color};
+
+use adjudication::Adjudicator;
+use engine::best_move::BestMove;
+use engine::engine_command::EngineCommand;
+use engine::score::Score;
+use engine_base::engine::EngineBase;
+use engine_base::iterative_deepening::IterativeDeepening;
+use engine_connection::EngineConnection;
+use error::Error;
+use game_history::GameHistory;
+use game_result::GameResult;
+use gui::go::Go;
+use gui::gui_command::GuiCommand;
+use timer::timer::Timer;
+use tournament::game::{game_over, GameRecord, Termination};
+
+#[cfg(test)]
+use chess::MoveGen;
+#[cfg(test)]
+use engine::id::Id;
+#[cfg(test)]
+use engine_base::engine_options::EngineOptions;
+#[cfg(test)]
+use engine_base::pv::Pv;
+#[cfg(test)]
+use engine_base::resettable::Resettable;
+#[cfg(test)]
+use std::io::Write;
+#[cfg(test)]
+use test_engines::{connect_fake, fake_engine};
+
+// How long a side without a clock may think about a move.
+const DEFAULT_MOVE_TIMEOUT: Duration = Duration::from_secs(60);
+// How often an `EngineBase` is polled for its best move.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+type Transform = Box<dyn FnMut(Color, GuiCommand) -> Option<GuiCommand>>;
+
+/// One side of a `Bridge`: an engine that can be sent commands and asked for its best move,
+/// whether it runs in another process or in this one.
+pub trait BridgeEnd {
+    fn send_command(&mut self, command: GuiCommand) -> Result<(), Error>;
+
+    /// Wait up to `timeout` for the best move of the last `go`.
+    fn recv_best_move(&mut self, timeout: Duration) -> Result<BestMove, Error>;
+
+    /// The score of the last search, for adjudication, if the engine gave one.
+    fn get_score(&self) -> Option<Score> {
+        None
+    }
+}
+
+impl<'a> BridgeEnd for EngineConnection<'a> {
+    // Positions and searches go through the connection's own calls, so that it keeps track of
+    // the game and checks the best move against it.
+    fn send_command(&mut self, command: GuiCommand) -> Result<(), Error> {
+        match command {
+            GuiCommand::Position(position) => {
+                self.send_position(position.get_start_board(), position.get_moves().to_vec())
+            }
+            GuiCommand::Go(go) => self.send_go_with(go),
+            command => self.send(command),
+        }
+    }
+
+    fn recv_best_move(&mut self, timeout: Duration) -> Result<BestMove, Error> {
+        self.recv_best_move_timeout(timeout)
+    }
+
+    fn get_score(&self) -> Option<Score> {
+        self.analysis_history()
+            .get_last()
+            .and_then(|info| info.get_score())
+    }
+}
+
+impl<I: IterativeDeepening + Send + 'static> BridgeEnd for EngineBase<I> {
+    fn send_command(&mut self, command: GuiCommand) -> Result<(), Error> {
+        self.handle(command, &mut vec![])?;
+        Ok(())
+    }
+
+    fn recv_best_move(&mut self, timeout: Duration) -> Result<BestMove, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut output = vec![];
+            self.poll_search(&mut output)?;
+            let best_move = String::from_utf8_lossy(&output)
+                .lines()
+                .filter_map(|line| match EngineCommand::from_str(line) {
+                    Ok(EngineCommand::BestMove(best_move)) => Some(best_move),
+                    _ => None,
+                })
+                .next_back();
+            if let Some(best_move) = best_move {
+                return Ok(best_move);
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::RecvTimeout { waited: timeout });
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Plays two engines against each other, passing each the position and the clocks when it is
+/// to move, like a GUI would.  Either end can be an `EngineConnection` or an `EngineBase`, so an
+/// engine written with this crate can be played against one in another process without either
+/// speaking UCI over a pipe.
+///
+/// The clocks are kept by the bridge's `Timer`, whose player must be the side to move at the
+/// start: a side that runs out of time loses, as does one that plays an illegal move or fails.
+/// Every command is passed through the `transform`, if there is one, on its way to each side,
+/// to change it or drop it, such as an option one of the engines doesn't have.
+pub struct Bridge<W: BridgeEnd, B: BridgeEnd> {
+    white: W,
+    black: B,
+    timer: Timer,
+    start: Board,
+    move_timeout: Duration,
+    transform: Option<Transform>,
+}
+
+impl<W: BridgeEnd, B: BridgeEnd> Bridge<W, B> {
+    pub fn new(white: W, black: B, timer: Timer) -> Bridge<W, B> {
+        Bridge {
+            white,
+            black,
+            timer,
+            start: Board::default(),
+            move_timeout: DEFAULT_MOVE_TIMEOUT,
+            transform: None,
+        }
+    }
+
+    /// Play from `board` rather than the starting position.
+    pub fn start(mut self, board: Board) -> Bridge<W, B> {
+        self.start = board;
+        self
+    }
+
+    /// How long a side may think when the timer has no clock for it.
+    pub fn move_timeout(mut self, timeout: Duration) -> Bridge<W, B> {
+        self.move_timeout = timeout;
+        self
+    }
+
+    /// Pass each command through `transform` on its way to the side of that color.  A command
+    /// it turns into `None` isn't sent.
+    pub fn transform<F>(mut self, transform: F) -> Bridge<W, B>
+    where
+        F: FnMut(Color, GuiCommand) -> Option<GuiCommand> + 'static,
+    {
+        self.transform = Some(Box::new(transform));
+        self
+    }
+
+    pub fn get_timer(&self) -> &Timer {
+        &self.timer
+    }
+
+    pub fn get_white(&mut self) -> &mut W {
+        &mut self.white
+    }
+
+    pub fn get_black(&mut self) -> &mut B {
+        &mut self.black
+    }
+
+    /// Send `command` to the side of `color`, through the transform.
+    pub fn send_to(&mut self, color: Color, command: GuiCommand) -> Result<(), Error> {
+        let command = match self.transform {
+            Some(ref mut transform) => match transform(color, command) {
+                Some(command) => command,
+                None => return Ok(()),
+            },
+            None => command,
+        };
+        match color {
+            Color::White => self.white.send_command(command),
+            Color::Black => self.black.send_command(command),
+        }
+    }
+
+    /// Send `command` to both sides, such as a `setoption` for the game.
+    pub fn send_to_both(&mut self, command: GuiCommand) -> Result<(), Error> {
+        self.send_to(Color::White, command.clone())?;
+        self.send_to(Color::Black, command)
+    }
+
+    /// Play the game to the end.  Anything that goes wrong with a side during the game loses it
+    /// for that side.
+    pub fn run(&mut self, adjudicator: &mut Adjudicator) -> GameRecord {
+        let mut game = GameHistory::new(self.start);
+        adjudicator.clear();
+        for &color in &[Color::White, Color::Black] {
+            if let Err(e) = self.send_to(color, GuiCommand::UciNewGame) {
+                return GameRecord::forfeit(self.start, color, e);
+            }
+        }
+
+        let (result, termination) = loop {
+            if let Some(end) = game_over(&game) {
+                break end;
+            }
+
+            let mover = game.get_board().side_to_move();
+            let best_move = match self.relay_move(&game) {
+                Ok(best_move) => best_move,
+                Err(e) => {
+                    // It may still be thinking.
+                    let _ = self.send_to(mover, GuiCommand::Stop);
+                    break (GameResult::Win(!mover), Termination::Forfeit(e));
+                }
+            };
+            let chess_move = match best_move.get_move() {
+                Some(chess_move) => chess_move,
+                None => break (GameResult::Win(!mover), Termination::NullMove),
+            };
+            if game.push(chess_move).is_err() {
+                break (
+                    GameResult::Win(!mover),
+                    Termination::IllegalMove(chess_move),
+                );
+            }
+            let score = match mover {
+                Color::White => self.white.get_score(),
+                Color::Black => self.black.get_score(),
+            };
+            if let Some(adjudication) = adjudicator.update(&game, score) {
+                break (
+                    adjudication.get_result(),
+                    Termination::Adjudication(adjudication),
+                );
+            }
+        };
+        GameRecord::new(self.start, game.get_moves().to_vec(), result, termination)
+    }
+
+    // Ask the side to move for its move, charging the time it takes to its clock.
+    fn relay_move(&mut self, game: &GameHistory) -> Result<BestMove, Error> {
+        let mover = game.get_board().side_to_move();
+        let go: Go = self.timer.into();
+        self.send_to(
+            mover,
+            GuiCommand::position(game.get_start(), game.get_moves().to_vec()),
+        )?;
+        self.send_to(mover, GuiCommand::Go(go))?;
+        self.timer.start();
+        let timeout = self.timer.remaining().unwrap_or(self.move_timeout);
+        let received = match mover {
+            Color::White => self.white.recv_best_move(timeout),
+            Color::Black => self.black.recv_best_move(timeout),
+        };
+        self.timer.made_move();
+        if self.timer.timeout_for(mover) {
+            return Err(Error::Timeout);
+        }
+        received
+    }
+}
+
+// Plays the first legal move it finds.
+#[cfg(test)]
+pub struct FirstMoveSearch;
+
+#[cfg(test)]
+impl Resettable for FirstMoveSearch {}
+
+#[cfg(test)]
+impl IterativeDeepening for FirstMoveSearch {
+    fn id_search<W: Write>(&mut self, board: Board, _: i16, _: u16, _: W) -> Pv {
+        let mut pv = Pv::new();
+        if let Some(m) = MoveGen::new_legal(&board).next() {
+            pv.push(m);
+        }
+        pv
+    }
+}
+
+#[test]
+fn test_bridge_connection_and_engine_base() {
+    let white = connect_fake(&fake_engine(false));
+    let black = EngineBase::new(
+        Id::name("First"),
+        EngineOptions::new(vec![]),
+        FirstMoveSearch,
+    );
+    let endgame = Board::from_str("7k/8/8/8/8/8/8/K5Q1 w - - 0 1").unwrap();
+    let mut bridge = Bridge::new(
+        white,
+        black,
+        Timer::new_without_increment(Duration::new(60, 0)),
+    )
+    .start(endgame);
+    let record = bridge.run(&mut Adjudicator::new());
+
+    assert_eq!(record.get_start(), endgame);
+    assert!(!record.get_termination().is_abnormal());
+    let game = GameHistory::from_moves(endgame, record.get_moves()).unwrap();
+    assert_eq!(
+        Some(&record.get_moves()[0]),
+        MoveGen::new_legal(&endgame).next().as_ref()
+    );
+    assert_eq!(
+        game_over(&game).map(|(result, _)| result),
+        Some(record.get_result())
+    );
+    assert_eq!(bridge.get_black().get_board().side_to_move(), Color::Black);
+}
+
+#[test]
+fn test_bridge_clock_and_transform() {
+    let white = EngineBase::new(
+        Id::name("White"),
+        EngineOptions::new(vec![]),
+        FirstMoveSearch,
+    );
+    let black = connect_fake(&fake_engine(false));
+    let timer = Timer::new_without_increment(Duration::from_millis(200));
+    // Black never hears that it is to move, so it loses on time.
+    let mut bridge = Bridge::new(white, black, timer).transform(|color, command| match command {
+        GuiCommand::Go(_) if color == Color::Black => None,
+        GuiCommand::SetOption(..) if color == Color::White => None,
+        command => Some(command),
+    });
+    bridge.get_black().start_transcript();
+    bridge
+        .send_to_both(GuiCommand::SetOption(
+            "Hash".to_string(),
+            Some("1".to_string()),
+        ))
+        .unwrap();
+    let record = bridge.run(&mut Adjudicator::new());
+
+    assert_eq!(record.get_result(), GameResult::Win(Color::White));
+    assert_eq!(
+        record.get_termination(),
+        &Termination::Forfeit(Error::Timeout)
+    );
+    assert_eq!(record.get_moves().len(), 1);
+    assert!(bridge.get_timer().timeout_for(Color::Black));
+    let transcript = bridge.get_black().take_transcript().unwrap();
+    assert_eq!(
+        transcript.get_lines()[0].get_line(),
+        "setoption name Hash value 1"
+    );
+}
@@ -0,0 +1,77 @@
+use std::fmt;
+use std::ops::BitOr;
+
+/// Which optional parts of the protocol an engine was seen to honor.  See
+/// `EngineConnection::probe_capabilities`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub const SEARCH_MOVES: Capabilities = Capabilities(1);
+    pub const MULTI_PV: Capabilities = Capabilities(1 << 1);
+    pub const PONDER: Capabilities = Capabilities(1 << 2);
+    pub const CHESS960: Capabilities = Capabilities(1 << 3);
+    pub const NODES: Capabilities = Capabilities(1 << 4);
+
+    const NAMES: [(Capabilities, &'static str); 5] = [
+        (Capabilities::SEARCH_MOVES, "searchmoves"),
+        (Capabilities::MULTI_PV, "multipv"),
+        (Capabilities::PONDER, "ponder"),
+        (Capabilities::CHESS960, "chess960"),
+        (Capabilities::NODES, "nodes"),
+    ];
+
+    pub fn empty() -> Capabilities {
+        Capabilities(0)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Are all of `other`'s capabilities in `self`?
+    pub fn contains(&self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Capabilities) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Capabilities;
+
+    fn bitor(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 | other.0)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = Capabilities::NAMES
+            .iter()
+            .filter(|(c, _)| self.contains(*c))
+            .map(|(_, name)| *name)
+            .collect::<Vec<&str>>();
+        write!(f, "{}", names.join(" "))
+    }
+}
+
+#[test]
+fn test_capabilities() {
+    let mut c = Capabilities::empty();
+    assert!(c.is_empty());
+
+    c.insert(Capabilities::PONDER);
+    c = c | Capabilities::NODES;
+
+    assert!(c.contains(Capabilities::PONDER | Capabilities::NODES));
+    assert!(!c.contains(Capabilities::PONDER | Capabilities::MULTI_PV));
+    assert_eq!(c.bits(), 0b10100);
+    assert_eq!(c.to_string(), "ponder nodes");
+}
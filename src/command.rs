@@ -19,6 +19,23 @@ impl Command {
     pub fn new_from_gui(c: GuiCommand) -> Command {
         Command::Gui(c)
     }
+
+    /// Parse every complete (newline terminated) line in `buffer`, skipping blank lines.  The
+    /// partial line at the end, if any, is returned so it can be completed by the next read.
+    pub fn parse_many(buffer: &str) -> (Vec<Command>, &str) {
+        let (complete, tail) = match buffer.rfind('\n') {
+            Some(end) => buffer.split_at(end + 1),
+            None => ("", buffer),
+        };
+
+        let commands = complete
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Command::from_str(line).expect("Command::from_str never fails"))
+            .collect();
+
+        (commands, tail)
+    }
 }
 
 impl FromStr for Command {
@@ -48,3 +65,102 @@ impl fmt::Display for Command {
         }
     }
 }
+
+#[cfg(test)]
+use engine::engine_command::EngineCommand as Engine;
+#[cfg(test)]
+use engine::info::Info;
+#[cfg(test)]
+use test_transcripts::{lc0_transcript, stockfish_transcript};
+
+#[cfg(test)]
+fn parse_transcript(transcript: &str) -> Vec<Command> {
+    transcript
+        .lines()
+        .map(|line| Command::from_str(line).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+fn count_unknown(commands: &[Command]) -> usize {
+    commands
+        .iter()
+        .filter(|c| matches!(c, Command::Unknown(_)))
+        .count()
+}
+
+#[test]
+fn test_stockfish_transcript() {
+    let commands = parse_transcript(stockfish_transcript());
+
+    // Only the banner is not UCI.
+    assert_eq!(count_unknown(&commands), 1);
+
+    match commands[17] {
+        Command::Engine(Engine::Info(ref info)) => {
+            assert_eq!(info.get_depth(), Some(12));
+            assert_eq!(info.get_hash_full(), Some(12.0));
+            assert_eq!(info.get_pv().len(), 4);
+        }
+        ref x => panic!("Expected info, got {:?}", x),
+    }
+}
+
+#[test]
+fn test_lc0_transcript() {
+    let commands = parse_transcript(lc0_transcript());
+
+    // The ascii-art banner is not UCI.
+    assert_eq!(count_unknown(&commands), 3);
+
+    match commands[13] {
+        Command::Engine(Engine::Info(ref info)) => {
+            assert_eq!(info.get_depth(), Some(1));
+            assert_eq!(info.get_wdl(), Some((319, 455, 226)));
+            assert_eq!(info.get_multi_pv(), Some(1));
+            assert_eq!(info.get_pv().len(), 2);
+        }
+        ref x => panic!("Expected info, got {:?}", x),
+    }
+
+    // `movesleft` is not part of UCI, but must not hide the pv after it.
+    match commands[15] {
+        Command::Engine(Engine::Info(ref info)) => {
+            assert_eq!(info.get_nps(), Some(9924));
+            assert_eq!(info.get_pv().len(), 3);
+        }
+        ref x => panic!("Expected info, got {:?}", x),
+    }
+
+    match commands[16] {
+        Command::Engine(Engine::Info(ref info)) => {
+            assert!(info.get_engine_string().as_ref().unwrap().starts_with("d2d4"));
+        }
+        ref x => panic!("Expected info, got {:?}", x),
+    }
+}
+
+#[test]
+fn test_parse_many() {
+    let (commands, tail) = Command::parse_many("uciok\r\n\nreadyok\ninfo depth 3\nbestmove e2");
+
+    assert_eq!(
+        commands,
+        vec![
+            Command::Engine(Engine::UciOk),
+            Command::Engine(Engine::ReadyOk),
+            Command::Engine(Engine::Info(Info::depth(3))),
+        ]
+    );
+    assert_eq!(tail, "bestmove e2");
+}
+
+#[test]
+fn test_parse_many_partial() {
+    assert_eq!(Command::parse_many("uci"), (vec![], "uci"));
+    assert_eq!(Command::parse_many(""), (vec![], ""));
+    assert_eq!(
+        Command::parse_many("quit\n"),
+        (vec![Command::Gui(GuiCommand::Quit)], "")
+    );
+}
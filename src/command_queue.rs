@@ -0,0 +1,294 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{RecvError, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use command::Command;
+use engine::engine_command::EngineCommand;
+use engine::info::Info;
+use error::Error;
+
+/// What the reader does when the queue of commands from the engine is full.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub enum OverflowPolicy {
+    /// Stop reading from the engine until there is room.  Nothing is lost, but an engine in
+    /// infinite analysis will eventually block on its own output.
+    #[default]
+    Block,
+    /// Throw away the oldest queued `info` line to make room.  Any other command is never
+    /// dropped; if the queue has no `info` lines left, the reader blocks.
+    DropOldestInfo,
+    /// Merge runs of queued `info` lines (for the same multipv line) into a single `info`
+    /// carrying the latest value of every field.  If nothing can be merged, the reader blocks.
+    CoalesceInfo,
+}
+
+struct QueueState {
+    commands: VecDeque<Command>,
+    sender_alive: bool,
+    receiver_alive: bool,
+    dropped: u64,
+}
+
+struct Queue {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+pub struct QueueSender {
+    queue: Arc<Queue>,
+}
+
+pub struct QueueReceiver {
+    queue: Arc<Queue>,
+}
+
+pub fn command_queue(capacity: usize, policy: OverflowPolicy) -> (QueueSender, QueueReceiver) {
+    let queue = Arc::new(Queue {
+        state: Mutex::new(QueueState {
+            commands: VecDeque::with_capacity(capacity),
+            sender_alive: true,
+            receiver_alive: true,
+            dropped: 0,
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        capacity: capacity.max(1),
+        policy,
+    });
+
+    (
+        QueueSender {
+            queue: queue.clone(),
+        },
+        QueueReceiver { queue },
+    )
+}
+
+fn as_info(command: &Command) -> Option<&Info> {
+    match command {
+        Command::Engine(EngineCommand::Info(ref info)) => Some(info),
+        _ => None,
+    }
+}
+
+fn drop_oldest_info(state: &mut QueueState) -> bool {
+    let position = state.commands.iter().position(|c| as_info(c).is_some());
+    if let Some(position) = position {
+        state.commands.remove(position);
+        state.dropped += 1;
+        true
+    } else {
+        false
+    }
+}
+
+fn coalesce_info(state: &mut QueueState) -> bool {
+    let before = state.commands.len();
+    let mut coalesced: VecDeque<Command> = VecDeque::with_capacity(before);
+
+    for command in state.commands.drain(..) {
+        let merged = match (coalesced.back().and_then(as_info), as_info(&command)) {
+            (Some(last), Some(next)) if last.get_multi_pv() == next.get_multi_pv() => {
+                Some(last.combine(next))
+            }
+            _ => None,
+        };
+
+        if let Some(merged) = merged {
+            coalesced.pop_back();
+            coalesced.push_back(Command::Engine(EngineCommand::Info(merged)));
+        } else {
+            coalesced.push_back(command);
+        }
+    }
+
+    state.commands = coalesced;
+    state.dropped += (before - state.commands.len()) as u64;
+    state.commands.len() < before
+}
+
+impl QueueSender {
+    /// Queue a command, applying the overflow policy if the queue is full.
+    ///
+    /// Fails with `Error::SendError` if the receiving side has gone away.
+    pub fn send(&self, command: Command) -> Result<(), Error> {
+        let queue = &self.queue;
+        let mut state = queue.state.lock().unwrap();
+
+        loop {
+            if !state.receiver_alive {
+                return Err(Error::SendError);
+            }
+
+            if state.commands.len() < queue.capacity {
+                break;
+            }
+
+            let made_room = match queue.policy {
+                OverflowPolicy::Block => false,
+                OverflowPolicy::DropOldestInfo => drop_oldest_info(&mut state),
+                OverflowPolicy::CoalesceInfo => coalesce_info(&mut state),
+            };
+
+            if !made_room {
+                state = queue.not_full.wait(state).unwrap();
+            }
+        }
+
+        state.commands.push_back(command);
+        queue.not_empty.notify_one();
+        Ok(())
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.sender_alive = false;
+        self.queue.not_empty.notify_all();
+    }
+}
+
+impl QueueReceiver {
+    fn pop(&self, state: &mut QueueState) -> Option<Command> {
+        let command = state.commands.pop_front();
+        if command.is_some() {
+            self.queue.not_full.notify_one();
+        }
+        command
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Command, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.queue.state.lock().unwrap();
+
+        loop {
+            if let Some(command) = self.pop(&mut state) {
+                return Ok(command);
+            }
+            if !state.sender_alive {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self.queue.not_empty.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Wait for as long as it takes for a command, or for the sender to go away.
+    pub fn recv(&self) -> Result<Command, RecvError> {
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if let Some(command) = self.pop(&mut state) {
+                return Ok(command);
+            }
+            if !state.sender_alive {
+                return Err(RecvError);
+            }
+            state = self.queue.not_empty.wait(state).unwrap();
+        }
+    }
+
+    /// How many commands were thrown away (or merged into others) to make room in the queue.
+    pub fn dropped(&self) -> u64 {
+        self.queue.state.lock().unwrap().dropped
+    }
+}
+
+impl Drop for QueueReceiver {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.receiver_alive = false;
+        self.queue.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+use std::thread::spawn;
+
+#[cfg(test)]
+fn try_recv(rx: &QueueReceiver) -> Result<Command, RecvTimeoutError> {
+    rx.recv_timeout(Duration::new(0, 0))
+}
+
+#[cfg(test)]
+fn info_command(info: Info) -> Command {
+    Command::Engine(EngineCommand::Info(info))
+}
+
+#[test]
+fn test_queue_in_order() {
+    let (tx, rx) = command_queue(4, OverflowPolicy::Block);
+    tx.send(Command::Engine(EngineCommand::UciOk)).unwrap();
+    tx.send(Command::Engine(EngineCommand::ReadyOk)).unwrap();
+
+    assert_eq!(try_recv(&rx), Ok(Command::Engine(EngineCommand::UciOk)));
+    assert_eq!(try_recv(&rx), Ok(Command::Engine(EngineCommand::ReadyOk)));
+    assert_eq!(try_recv(&rx), Err(RecvTimeoutError::Timeout));
+
+    drop(tx);
+    assert_eq!(try_recv(&rx), Err(RecvTimeoutError::Disconnected));
+}
+
+#[test]
+fn test_queue_blocks_until_room() {
+    let (tx, rx) = command_queue(1, OverflowPolicy::Block);
+
+    let writer = spawn(move || {
+        for depth in 0..10 {
+            tx.send(info_command(Info::depth(depth))).unwrap();
+        }
+    });
+
+    for depth in 0..10 {
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)),
+            Ok(info_command(Info::depth(depth)))
+        );
+    }
+    writer.join().unwrap();
+    assert_eq!(rx.dropped(), 0);
+}
+
+#[test]
+fn test_queue_drop_oldest_info() {
+    let (tx, rx) = command_queue(2, OverflowPolicy::DropOldestInfo);
+    tx.send(Command::Engine(EngineCommand::UciOk)).unwrap();
+    tx.send(info_command(Info::depth(1))).unwrap();
+    tx.send(info_command(Info::depth(2))).unwrap();
+
+    assert_eq!(try_recv(&rx), Ok(Command::Engine(EngineCommand::UciOk)));
+    assert_eq!(try_recv(&rx), Ok(info_command(Info::depth(2))));
+    assert_eq!(rx.dropped(), 1);
+}
+
+#[test]
+fn test_queue_coalesce_info() {
+    let (tx, rx) = command_queue(3, OverflowPolicy::CoalesceInfo);
+    tx.send(info_command(Info::depth(1).combine(&Info::nodes(10)))).unwrap();
+    tx.send(info_command(Info::depth(2))).unwrap();
+    tx.send(info_command(Info::multi_pv(2))).unwrap();
+    tx.send(Command::Engine(EngineCommand::ReadyOk)).unwrap();
+
+    assert_eq!(
+        try_recv(&rx),
+        Ok(info_command(Info::depth(2).combine(&Info::nodes(10))))
+    );
+    assert_eq!(try_recv(&rx), Ok(info_command(Info::multi_pv(2))));
+    assert_eq!(try_recv(&rx), Ok(Command::Engine(EngineCommand::ReadyOk)));
+    assert_eq!(rx.dropped(), 1);
+}
+
+#[test]
+fn test_queue_receiver_dropped() {
+    let (tx, rx) = command_queue(1, OverflowPolicy::Block);
+    drop(rx);
+    assert!(tx.send(Command::Engine(EngineCommand::UciOk)).is_err());
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+use gui::gui_command::GuiCommand;
+
+#[cfg(test)]
+use chess::{ChessMove, Square};
+#[cfg(test)]
+use gui::go::Go;
+
+/// Where an `EngineConnection` is with its engine's searches, going by what has been sent and
+/// received.  Commands the engine can't make sense of in a state, such as a second `go` before
+/// the first has its best move, are refused rather than sent.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Nothing is being searched: positions can be set and searches started.
+    Idle,
+    Searching,
+    /// Searching on the opponent's time, after `go ponder`, until `ponderhit` or `stop`.
+    Pondering,
+    /// The search was told to `stop`, and its best move hasn't arrived yet.
+    WaitingBestMove,
+}
+
+impl ConnectionState {
+    /// The state once `command` is sent, or `None` if it can't be sent in this one.  `stop` is
+    /// let through when idle, where it does nothing, as the search may only just have finished.
+    pub fn after_sending(self, command: &GuiCommand) -> Option<ConnectionState> {
+        match (self, command) {
+            (ConnectionState::Idle, GuiCommand::Go(go)) if go.get_ponder().is_some() => {
+                Some(ConnectionState::Pondering)
+            }
+            (ConnectionState::Idle, GuiCommand::Go(_)) => Some(ConnectionState::Searching),
+            (_, GuiCommand::Go(_)) => None,
+            (ConnectionState::Idle, GuiCommand::Position(_)) => Some(self),
+            (_, GuiCommand::Position(_)) => None,
+            (ConnectionState::Pondering, GuiCommand::PonderHit) => Some(ConnectionState::Searching),
+            (_, GuiCommand::PonderHit) => None,
+            (ConnectionState::Idle, GuiCommand::Stop) => Some(self),
+            (_, GuiCommand::Stop) => Some(ConnectionState::WaitingBestMove),
+            _ => Some(self),
+        }
+    }
+
+    /// Whether a search has been started and hasn't sent its best move yet.
+    pub fn is_searching(self) -> bool {
+        self != ConnectionState::Idle
+    }
+}
+
+/// What an `EngineConnection` does with a `setoption` while a search is running, which some
+/// engines can't cope with.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum OptionsDuringSearch {
+    /// Hold it back, and send it once the search has its best move.
+    #[default]
+    Queue,
+    /// Fail with `InvalidTransition`, without sending it.
+    Refuse,
+    /// Send it anyway, for engines known to apply options mid-search.
+    Send,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ConnectionState::Idle => "idle",
+            ConnectionState::Searching => "searching",
+            ConnectionState::Pondering => "pondering",
+            ConnectionState::WaitingBestMove => "waiting for the best move",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[test]
+fn test_connection_state_transitions() {
+    let go = GuiCommand::Go(Go::depth(5));
+    let ponder = GuiCommand::Go(Go::ponder(ChessMove::new(Square::E7, Square::E5, None)));
+    let position = GuiCommand::position(Default::default(), vec![]);
+    let idle = ConnectionState::Idle;
+
+    assert_eq!(idle.after_sending(&go), Some(ConnectionState::Searching));
+    assert_eq!(
+        idle.after_sending(&ponder),
+        Some(ConnectionState::Pondering)
+    );
+    assert_eq!(idle.after_sending(&position), Some(idle));
+    assert_eq!(idle.after_sending(&GuiCommand::Stop), Some(idle));
+    assert_eq!(idle.after_sending(&GuiCommand::PonderHit), None);
+    assert!(!idle.is_searching());
+
+    let searching = ConnectionState::Searching;
+    assert_eq!(searching.after_sending(&go), None);
+    assert_eq!(searching.after_sending(&position), None);
+    assert_eq!(
+        searching.after_sending(&GuiCommand::IsReady),
+        Some(searching)
+    );
+    assert_eq!(
+        searching.after_sending(&GuiCommand::Stop),
+        Some(ConnectionState::WaitingBestMove)
+    );
+
+    let pondering = ConnectionState::Pondering;
+    assert_eq!(
+        pondering.after_sending(&GuiCommand::PonderHit),
+        Some(searching)
+    );
+    assert_eq!(
+        pondering.after_sending(&GuiCommand::Stop),
+        Some(ConnectionState::WaitingBestMove)
+    );
+    assert_eq!(ConnectionState::WaitingBestMove.after_sending(&go), None);
+    assert!(ConnectionState::WaitingBestMove.is_searching());
+}
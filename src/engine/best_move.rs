@@ -1,9 +1,10 @@
-use error::Error;
+use error::{CommandFamily, Error};
 use std::fmt;
 use std::str::FromStr;
 
-use chess::ChessMove;
+use chess::{Board, ChessMove};
 use parsers::*;
+use san::{line_to_san, to_san};
 
 #[cfg(test)]
 use chess::{File, Rank, Square};
@@ -14,34 +15,74 @@ use nom::sequence::tuple;
 use nom::branch::alt;
 use nom::bytes::streaming::tag;
 
+/// The move an engine settled on.  The default is the null move, `bestmove 0000`, which engines
+/// send when there is nothing to play, or when stopped before they found anything.
 #[derive(Clone, PartialEq, PartialOrd, Debug, Default)]
 pub struct BestMove {
-    chess_move: ChessMove,
+    chess_move: Option<ChessMove>,
     ponder_move: Option<ChessMove>,
 }
 
 impl BestMove {
     pub fn new(m: ChessMove) -> BestMove {
         BestMove {
-            chess_move: m,
+            chess_move: Some(m),
             ponder_move: None,
         }
     }
 
     pub fn new_with_ponder(m: ChessMove, ponder: ChessMove) -> BestMove {
         BestMove {
-            chess_move: m,
+            chess_move: Some(m),
             ponder_move: Some(ponder),
         }
     }
 
-    pub fn get_move(&self) -> ChessMove {
+    pub fn null() -> BestMove {
+        BestMove::default()
+    }
+
+    /// The move, or `None` for the null move.
+    pub fn get_move(&self) -> Option<ChessMove> {
         self.chess_move
     }
 
+    pub fn is_null(&self) -> bool {
+        self.chess_move.is_none()
+    }
+
     pub fn get_ponder(&self) -> Option<ChessMove> {
         self.ponder_move
     }
+
+    /// The best move in SAN, with the null move written `--` as in PGN.
+    pub fn san(&self, board: &Board) -> Result<String, Error> {
+        match self.chess_move {
+            Some(m) => to_san(board, m),
+            None => Ok("--".to_string()),
+        }
+    }
+
+    /// The move to ponder on in SAN, if there is one.  It is played after the best move.
+    pub fn ponder_san(&self, board: &Board) -> Result<Option<String>, Error> {
+        match (self.chess_move, self.ponder_move) {
+            (Some(m), Some(ponder)) => Ok(line_to_san(board, &[m, ponder])?.pop()),
+            _ => Ok(None),
+        }
+    }
+}
+
+// `0000` as the spec has it, or `(none)` as some engines send instead.  Anything after it, such
+// as a ponder move, means nothing.
+fn parse_null_best_move(input: &str) -> IResult<&str, BestMove> {
+    map(
+        tuple((
+            tag("bestmove"),
+            space,
+            alt((tag("0000"), tag("(none)"))),
+        )),
+        |_| BestMove::null()
+    )(input)
 }
 
 fn parse_best_move_noponder(input: &str) -> IResult<&str, BestMove> {
@@ -72,6 +113,7 @@ fn parse_best_move_ponder(input: &str) -> IResult<&str, BestMove> {
 
 pub fn parse_best_move(input: &str) -> IResult<&str, BestMove> {
     alt((
+        complete(parse_null_best_move),
         complete(parse_best_move_ponder),
         complete(parse_best_move_noponder)
     ))(input)
@@ -81,16 +123,21 @@ impl FromStr for BestMove {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_best_move(s)?.1)
+        parse_best_move(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::BestMove, s, e))
     }
 }
 
 impl fmt::Display for BestMove {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "bestmove {}", self.chess_move)?;
+        match self.chess_move {
+            Some(m) => write!(f, "bestmove {}", m)?,
+            None => write!(f, "bestmove 0000")?,
+        }
         match self.ponder_move {
-            Some(x) => write!(f, " ponder {}", x)?,
-            None => {}
+            Some(x) if self.chess_move.is_some() => write!(f, " ponder {}", x)?,
+            _ => {}
         };
 
         writeln!(f, "")
@@ -135,3 +182,23 @@ fn test_bestmove_noponder() {
 
     test_parse("bestmove e2e4\n", BestMove::new(e2e4));
 }
+
+#[test]
+fn test_best_move_san() {
+    let best_move = BestMove::from_str("bestmove g1f3 ponder g8f6\n").unwrap();
+    assert_eq!(best_move.san(&Board::default()), Ok("Nf3".to_string()));
+    assert_eq!(best_move.ponder_san(&Board::default()), Ok(Some("Nf6".to_string())));
+}
+
+#[test]
+fn test_null_best_move() {
+    test_parse("bestmove 0000\n", BestMove::null());
+    assert_eq!(BestMove::from_str("bestmove (none)\n"), Ok(BestMove::null()));
+    assert_eq!(BestMove::from_str("bestmove 0000 ponder 0000\n"), Ok(BestMove::null()));
+
+    let null = BestMove::null();
+    assert!(null.is_null());
+    assert_eq!(null.get_move(), None);
+    assert_eq!(null.san(&Board::default()), Ok("--".to_string()));
+    assert_eq!(null.ponder_san(&Board::default()), Ok(None));
+}
@@ -1,4 +1,4 @@
-use error::Error;
+use error::{CommandFamily, Error};
 use std::fmt;
 use std::str::FromStr;
 
@@ -34,7 +34,9 @@ impl FromStr for CopyProtection {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_copyprotection(s)?.1)
+        parse_copyprotection(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::CopyProtection, s, e))
     }
 }
 
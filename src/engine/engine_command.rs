@@ -1,4 +1,4 @@
-use error::Error;
+use error::{CommandFamily, Error};
 use std::fmt;
 use std::str::FromStr;
 
@@ -21,6 +21,7 @@ use engine::option_type::OptionType;
 #[cfg(test)]
 use engine::score::Score;
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, PartialEq, PartialOrd, Debug)]
 pub enum EngineCommand {
     Id(Id),
@@ -94,7 +95,9 @@ impl FromStr for EngineCommand {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_engine_command(s)?.1)
+        parse_engine_command(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::Engine, s, e))
     }
 }
 
@@ -198,7 +201,7 @@ fn test_engine_command_info() {
                         .combine(&Info::multi_pv(1))
                         .combine(&Info::nodes(100))
                         .combine(&Info::time(1))
-                        .combine(&Info::score(Score::Cp(6)))
+                        .combine(&Info::score(Score::cp(6)))
                         .combine(&Info::cur_move(e2e4))
                         .combine(&Info::cur_move_number(1))
                         .combine(&Info::nps(1000))
@@ -0,0 +1,121 @@
+use engine::engine_command::EngineCommand;
+use engine::engine_option::EngineOption;
+
+#[cfg(test)]
+use engine::id::Id;
+#[cfg(test)]
+use engine::option_type::OptionType;
+#[cfg(test)]
+use std::str::FromStr;
+
+/// What an engine told us about itself while answering `uci`.
+#[derive(Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct EngineInfo {
+    name: Option<String>,
+    author: Option<String>,
+    options: Vec<EngineOption>,
+    supports_ponder: bool,
+    supports_multipv: bool,
+}
+
+impl EngineInfo {
+    /// Record one command sent during the handshake.  Anything other than `id` and `option` is
+    /// ignored.
+    pub fn update(&mut self, command: &EngineCommand) {
+        match command {
+            EngineCommand::Id(id) => {
+                if let Some(ref name) = id.name {
+                    self.name = Some(name.clone());
+                }
+                if let Some(ref author) = id.author {
+                    self.author = Some(author.clone());
+                }
+            }
+            EngineCommand::EngineOption(option) => {
+                // The spec says an engine that can ponder or report several lines announces the
+                // `Ponder` or `MultiPV` option.
+                if option.get_name().eq_ignore_ascii_case("Ponder") {
+                    self.supports_ponder = true;
+                }
+                if option.get_name().eq_ignore_ascii_case("MultiPV") {
+                    self.supports_multipv = true;
+                }
+                self.options.retain(|o| !o.get_name().eq_ignore_ascii_case(option.get_name()));
+                self.options.push(option.clone());
+            }
+            _ => {}
+        }
+    }
+
+    pub fn get_name(&self) -> Option<&String> {
+        self.name.as_ref()
+    }
+
+    pub fn get_author(&self) -> Option<&String> {
+        self.author.as_ref()
+    }
+
+    pub fn get_options(&self) -> &Vec<EngineOption> {
+        &self.options
+    }
+
+    /// Option names are case insensitive.
+    pub fn get_option(&self, name: &str) -> Option<&EngineOption> {
+        self.options.iter().find(|o| o.get_name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn supports_ponder(&self) -> bool {
+        self.supports_ponder
+    }
+
+    pub fn supports_multipv(&self) -> bool {
+        self.supports_multipv
+    }
+}
+
+#[test]
+fn test_engine_info_from_handshake() {
+    let mut info = EngineInfo::default();
+    for line in [
+        "id name Stockfish 10\n",
+        "id author T. Romstad, M. Costalba, J. Kiiski, G. Linscott\n",
+        "option name Hash type spin default 16 min 1 max 131072\n",
+        "option name Ponder type check default false\n",
+        "option name multipv type spin default 1 min 1 max 500\n",
+        "uciok\n",
+    ]
+    .iter()
+    {
+        info.update(&EngineCommand::from_str(line).unwrap());
+    }
+
+    assert_eq!(info.get_name(), Some(&"Stockfish 10".to_string()));
+    assert_eq!(
+        info.get_author(),
+        Some(&"T. Romstad, M. Costalba, J. Kiiski, G. Linscott".to_string())
+    );
+    assert_eq!(info.get_options().len(), 3);
+    assert_eq!(
+        info.get_option("hash"),
+        Some(&EngineOption::new("Hash".to_string(), OptionType::Spin(16, 1, 131072)))
+    );
+    assert!(info.supports_ponder());
+    assert!(info.supports_multipv());
+}
+
+#[test]
+fn test_engine_info_repeated_option() {
+    let first = EngineOption::new("Hash".to_string(), OptionType::Spin(1, 1, 2));
+    let second = EngineOption::new("Hash".to_string(), OptionType::Spin(8, 1, 64));
+
+    let mut info = EngineInfo::default();
+    info.update(&EngineCommand::Id(Id::name("Minimal")));
+    info.update(&EngineCommand::EngineOption(first));
+    info.update(&EngineCommand::EngineOption(second.clone()));
+
+    assert_eq!(info.get_name(), Some(&"Minimal".to_string()));
+    assert_eq!(info.get_author(), None);
+    assert_eq!(info.get_options(), &vec![second]);
+    assert!(!info.supports_ponder());
+    assert!(!info.supports_multipv());
+}
@@ -1,4 +1,4 @@
-use error::Error;
+use error::{CommandFamily, Error};
 use std::fmt;
 use std::str::FromStr;
 
@@ -51,13 +51,15 @@ impl FromStr for EngineOption {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_engine_option(s)?.1)
+        parse_engine_option(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::EngineOption, s, e))
     }
 }
 
 impl fmt::Display for EngineOption {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "option name {} {}", self.name, self.option_type)
+        writeln!(f, "option name {} {}", self.name, self.option_type.to_string().trim())
     }
 }
 
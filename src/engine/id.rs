@@ -1,4 +1,4 @@
-use error::Error;
+use error::{CommandFamily, Error};
 use nom::combinator::rest;
 use std::fmt;
 use std::str::FromStr;
@@ -71,7 +71,9 @@ impl FromStr for Id {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_engine_id(s)?.1)
+        parse_engine_id(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::Id, s, e))
     }
 }
 
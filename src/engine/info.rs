@@ -1,19 +1,21 @@
-use error::Error;
+use error::{CommandFamily, Error};
 use std::fmt;
 use std::str::FromStr;
 
-use chess::ChessMove;
+use chess::{Board, ChessMove, Color};
 use engine::score::{parse_score, Score};
+#[cfg(test)]
+use engine::score::ScoreBound;
 use parsers::*;
+use san::line_to_san;
 
 #[cfg(test)]
 use chess::{File, Rank, Square};
 
 use nom::IResult;
-use nom::combinator::{map, complete};
+use nom::combinator::{map, opt};
 use nom::bytes::streaming::tag;
-use nom::multi::fold_many1;
-use nom::branch::alt;
+use nom::error::ErrorKind;
 use nom::sequence::tuple;
 
 
@@ -22,15 +24,16 @@ pub struct Info {
     depth: Option<u64>,
     seldepth: Option<u64>,
     time: Option<u64>,
-    nodes: Option<u64>,
+    nodes: Option<Counter>,
     pv: Vec<ChessMove>,
     multi_pv: Option<u64>,
     score: Option<Score>,
+    wdl: Option<(u64, u64, u64)>,
     cur_move: Option<ChessMove>,
     cur_move_number: Option<u64>,
     hash_full: Option<f32>,
-    nps: Option<u64>,
-    tb_hits: Option<u64>,
+    nps: Option<Counter>,
+    tb_hits: Option<Counter>,
     cpu_load: Option<f32>,
     engine_string: Option<String>,
     refutation: Vec<ChessMove>,
@@ -50,7 +53,7 @@ impl Info {
         self.time
     }
 
-    pub fn get_nodes(&self) -> Option<u64> {
+    pub fn get_nodes(&self) -> Option<Counter> {
         self.nodes
     }
 
@@ -66,6 +69,10 @@ impl Info {
         self.score
     }
 
+    pub fn get_wdl(&self) -> Option<(u64, u64, u64)> {
+        self.wdl
+    }
+
     pub fn cur_get_move(&self) -> Option<ChessMove> {
         self.cur_move
     }
@@ -78,11 +85,11 @@ impl Info {
         self.hash_full
     }
 
-    pub fn get_nps(&self) -> Option<u64> {
+    pub fn get_nps(&self) -> Option<Counter> {
         self.nps
     }
 
-    pub fn get_tbhits(&self) -> Option<u64> {
+    pub fn get_tbhits(&self) -> Option<Counter> {
         self.tb_hits
     }
 
@@ -101,6 +108,33 @@ impl Info {
     pub fn get_cur_line(&self) -> &Vec<ChessMove> {
         &self.cur_line
     }
+
+    /// The score, given for `side_to_move`, from white's side.
+    pub fn score_white_pov(&self, side_to_move: Color) -> Option<Score> {
+        self.score.map(|score| score.white_pov(side_to_move))
+    }
+
+    /// The win, draw and loss chances, given for `side_to_move`, from white's side.
+    pub fn wdl_white_pov(&self, side_to_move: Color) -> Option<(u64, u64, u64)> {
+        match (self.wdl, side_to_move) {
+            (Some((win, draw, loss)), Color::Black) => Some((loss, draw, win)),
+            (wdl, _) => wdl,
+        }
+    }
+
+    /// This info, sent while searching for `side_to_move`, with its score and win, draw and loss
+    /// chances from white's side.
+    pub fn white_pov(&self, side_to_move: Color) -> Info {
+        let mut info = self.clone();
+        info.score = self.score_white_pov(side_to_move);
+        info.wdl = self.wdl_white_pov(side_to_move);
+        info
+    }
+
+    /// The principal variation in SAN, starting from `board`.
+    pub fn pv_san(&self, board: &Board) -> Result<Vec<String>, Error> {
+        line_to_san(board, &self.pv)
+    }
 }
 
 macro_rules! set_non_default {
@@ -140,14 +174,15 @@ impl Info {
     add_builder_option!(depth, u64);
     add_builder_option!(seldepth, u64);
     add_builder_option!(time, u64);
-    add_builder_option!(nodes, u64);
+    add_builder_option!(nodes, Counter);
     add_builder_option!(multi_pv, u64);
     add_builder_option!(score, Score);
+    add_builder_option!(wdl, (u64, u64, u64));
     add_builder_option!(cur_move, ChessMove);
     add_builder_option!(cur_move_number, u64);
     add_builder_option!(hash_full, f32);
-    add_builder_option!(nps, u64);
-    add_builder_option!(tb_hits, u64);
+    add_builder_option!(nps, Counter);
+    add_builder_option!(tb_hits, Counter);
     add_builder_option!(cpu_load, f32);
     add_builder_option!(engine_string, String);
 
@@ -163,6 +198,7 @@ impl Info {
         set_non_default!(result, self, b, nodes); // done
         set_non_default!(result, self, b, multi_pv); // done
         set_non_default!(result, self, b, score); // done
+        set_non_default!(result, self, b, wdl);
         set_non_default!(result, self, b, cur_move); // done
         set_non_default!(result, self, b, cur_move_number); // done
         set_non_default!(result, self, b, hash_full);
@@ -175,167 +211,186 @@ impl Info {
     }
 }
 
-fn parse_info_pv(input: &str) -> IResult<&str, Info> {
+fn parse_wdl(input: &str, policy: NumberOverflow) -> IResult<&str, (u64, u64, u64)> {
     map(
         tuple((
+            unsigned_with(policy),
             space,
-            tag("pv"),
+            unsigned_with(policy),
             space,
-            parse_movelist
+            unsigned_with(policy)
         )),
-        |(_, _, _, moves)| Info::pv(moves)
+        |(w, _, d, _, l)| (w, d, l)
     )(input)
 }
 
-fn parse_info_depth(input: &str) -> IResult<&str, Info> {
+fn parse_cur_line(input: &str) -> IResult<&str, Vec<ChessMove>> {
     map(
         tuple((
-            space,
-            tag("depth"),
-            space,
-            integer,
-        )),
-        |(_, _, _, depth)| Info::depth(depth)
-    )(input)
-}
-
-fn parse_info_seldepth(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            tag("seldepth"),
-            space,
-            integer,
+            opt(tuple((integer, space))),
+            parse_movelist
         )),
-        |(_, _, _, seldepth)| Info::seldepth(seldepth)
+        |(_, moves)| moves
     )(input)
 }
 
-fn parse_info_time(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            tag("time"),
-            space,
-            integer
-        )),
-        |(_, _, _, time)| Info::time(time)
-    )(input)
-}
+// Parse one `keyword value...` field, starting at the keyword, straight into `info`.  Fields are
+// dispatched on their keyword instead of being tried one after the other, and nothing is
+// allocated unless the field itself holds moves or a string: info lines are by far the most
+// common thing an engine sends, so this is the hot path when reading from fast engines.
+fn parse_info_field<'a>(
+    field: &'a str,
+    info: &mut Info,
+    policy: NumberOverflow,
+) -> IResult<&'a str, ()> {
+    let integer = unsigned_with::<u64>(policy);
+    let counter = counter_with(policy);
+    let (after_keyword, keyword) = token(field)?;
+    let (input, _) = space(after_keyword)?;
+
+    let rest = match keyword {
+        "depth" => {
+            let (rest, x) = integer(input)?;
+            info.depth = Some(x);
+            rest
+        }
+        "seldepth" => {
+            let (rest, x) = integer(input)?;
+            info.seldepth = Some(x);
+            rest
+        }
+        "time" => {
+            let (rest, x) = integer(input)?;
+            info.time = Some(x);
+            rest
+        }
+        "nodes" => {
+            let (rest, x) = counter(input)?;
+            info.nodes = Some(x);
+            rest
+        }
+        "multipv" => {
+            let (rest, x) = integer(input)?;
+            info.multi_pv = Some(x);
+            rest
+        }
+        "currmovenumber" => {
+            let (rest, x) = integer(input)?;
+            info.cur_move_number = Some(x);
+            rest
+        }
+        "nps" => {
+            let (rest, x) = counter(input)?;
+            info.nps = Some(x);
+            rest
+        }
+        "tbhits" => {
+            let (rest, x) = counter(input)?;
+            info.tb_hits = Some(x);
+            rest
+        }
+        "hashfull" => {
+            let (rest, x) = integer(input)?;
+            info.hash_full = Some(x as f32);
+            rest
+        }
+        "cpuload" => {
+            let (rest, x) = integer(input)?;
+            info.cpu_load = Some(x as f32);
+            rest
+        }
+        "wdl" => {
+            let (rest, x) = parse_wdl(input, policy)?;
+            info.wdl = Some(x);
+            rest
+        }
+        "score" => {
+            let (rest, x) = parse_score(field)?;
+            info.score = Some(x);
+            rest
+        }
+        "currmove" => {
+            let (rest, x) = parse_move(input)?;
+            info.cur_move = Some(x);
+            rest
+        }
+        "pv" => {
+            let (rest, x) = parse_movelist(input)?;
+            info.pv = x;
+            rest
+        }
+        "refutation" => {
+            let (rest, x) = parse_movelist(input)?;
+            info.refutation = x;
+            rest
+        }
+        "currline" => {
+            let (rest, x) = parse_cur_line(input)?;
+            info.cur_line = x;
+            rest
+        }
+        // `string` consumes the rest of the line.
+        "string" => {
+            info.engine_string = Some(after_keyword.trim().to_string());
+            ""
+        }
+        _ => return Err(nom::Err::Error((field, ErrorKind::Tag))),
+    };
 
-fn parse_info_nodes(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            tag("nodes"),
-            space,
-            integer,
-        )),
-        |(_, _, _, nodes)| Info::nodes(nodes)
-    )(input)
+    Ok((rest, ()))
 }
 
-fn parse_info_multi_pv(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            tag("multipv"),
-            space,
-            integer,
-        )),
-        |(_, _, _, mpv)| Info::multi_pv(mpv)
-    )(input)
+pub fn parse_info(input: &str) -> IResult<&str, Info> {
+    parse_info_with(input, NumberOverflow::Saturate)
 }
 
-fn parse_info_score(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            parse_score
-        )),
-        |(_, score)| Info::score(score)
-    )(input)
-}
+/// Parse an info line, with numbers too large for their fields handled as `policy` says.
+pub fn parse_info_with(input: &str, policy: NumberOverflow) -> IResult<&str, Info> {
+    let (mut input, _) = tag("info")(input)?;
+    let mut info = Info::default();
+    let mut fields = 0;
 
-fn parse_info_cur_move(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            tag("currmove"),
-            space,
-            parse_move
-        )),
-        |(_, _, _, m)| Info::cur_move(m)
-    )(input)
-}
-
-fn parse_info_cur_move_number(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            tag("currmovenumber"),
-            space,
-            integer
-        )),
-        |(_, _, _, i)| Info::cur_move_number(i)
-    )(input)
-}
+    loop {
+        let (field, _) = space(input)?;
+        if field.is_empty() {
+            break;
+        }
 
-fn parse_info_nps(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            tag("nps"),
-            space,
-            integer
-        )),
-        |(_, _, _, nps)| Info::nps(nps)
-    )(input)
-}
+        input = match parse_info_field(field, &mut info, policy) {
+            Ok((rest, _)) => rest,
+            // Engines such as Lc0 emit tokens outside of the UCI specification (`movesleft`,
+            // values that aren't numbers, ...).  Skip over them one token at a time, so the rest
+            // of the line is still understood.  The line ends where there are no more tokens.
+            Err(nom::Err::Error(_)) | Err(nom::Err::Incomplete(_)) => match token(field) {
+                Ok((rest, _)) => rest,
+                Err(nom::Err::Error(_)) => break,
+                Err(e) => return Err(e),
+            },
+            Err(e) => return Err(e),
+        };
+        fields += 1;
+    }
 
-fn parse_info_tb_hits(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            space,
-            tag("tbhits"),
-            space,
-            integer
-        )),
-        |(_, _, _, tb_hits)| Info::tb_hits(tb_hits)
-    )(input)
+    if fields == 0 {
+        return Err(nom::Err::Error((input, ErrorKind::Many1)));
+    }
+    Ok((input, info))
 }
 
-pub fn parse_info(input: &str) -> IResult<&str, Info> {
-    map(
-        tuple((
-            tag("info"),
-            fold_many1(
-                alt((
-                    complete(parse_info_pv),
-                    complete(parse_info_depth),
-                    complete(parse_info_seldepth),
-                    complete(parse_info_time),
-                    complete(parse_info_nodes),
-                    complete(parse_info_multi_pv),
-                    complete(parse_info_score),
-                    complete(parse_info_cur_move),
-                    complete(parse_info_cur_move_number),
-                    complete(parse_info_nps),
-                    complete(parse_info_tb_hits)
-                )),
-                Info::default(),
-                |acc: Info, next: Info| acc.combine(&next)
-            ),
-        )),
-        |(_, info)| info
-    )(input)
+impl Info {
+    /// Like `from_str`, which saturates numbers that are too large, but with a choice of policy.
+    pub fn parse_with(s: &str, policy: NumberOverflow) -> Result<Info, Error> {
+        parse_info_with(s, policy)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::Info, s, e))
+    }
 }
 
 impl FromStr for Info {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_info(s)?.1)
+        Info::parse_with(s, NumberOverflow::Saturate)
     }
 }
 
@@ -359,6 +414,10 @@ impl fmt::Display for Info {
             write!(f, " {}", score.to_string().trim())?;
         }
 
+        if let Some((w, d, l)) = self.wdl {
+            write!(f, " wdl {} {} {}", w, d, l)?;
+        }
+
         if let Some(nodes) = self.nodes {
             write!(f, " nodes {}", nodes)?;
         }
@@ -383,12 +442,39 @@ impl fmt::Display for Info {
             write!(f, " tbhits {}", tb_hits)?;
         }
 
+        if let Some(hash_full) = self.hash_full {
+            write!(f, " hashfull {}", hash_full)?;
+        }
+
+        if let Some(cpu_load) = self.cpu_load {
+            write!(f, " cpuload {}", cpu_load)?;
+        }
+
         if self.pv.len() > 0 {
             write!(f, " pv")?;
             for x in self.pv.iter() {
                 write!(f, " {}", x)?;
             }
         }
+
+        if !self.refutation.is_empty() {
+            write!(f, " refutation")?;
+            for x in self.refutation.iter() {
+                write!(f, " {}", x)?;
+            }
+        }
+
+        if !self.cur_line.is_empty() {
+            write!(f, " currline")?;
+            for x in self.cur_line.iter() {
+                write!(f, " {}", x)?;
+            }
+        }
+
+        // `string` consumes the rest of the line, so it must come last.
+        if let Some(ref engine_string) = self.engine_string {
+            write!(f, " string {}", engine_string)?;
+        }
         writeln!(f, "")
     }
 }
@@ -422,9 +508,81 @@ fn test_normal_info() {
               .combine(&Info::multi_pv(1))
               .combine(&Info::nodes(100))
               .combine(&Info::time(1))
-              .combine(&Info::score(Score::Cp(6)))
+              .combine(&Info::score(Score::cp(6)))
               .combine(&Info::cur_move(e2e4))
               .combine(&Info::cur_move_number(1))
               .combine(&Info::nps(1000))
               .combine(&Info::tb_hits(0)));
 }
+
+#[test]
+fn test_extended_info() {
+    let d2d4 = ChessMove::new(
+        Square::make_square(Rank::Second, File::D),
+        Square::make_square(Rank::Fourth, File::D),
+        None,
+    );
+
+    test_info("info depth 1 score cp 12 wdl 319 455 226 tbhits 0 hashfull 5 cpuload 999 pv d2d4 string hello world\n",
+              Info::pv(vec![d2d4])
+              .combine(&Info::depth(1))
+              .combine(&Info::score(Score::cp(12)))
+              .combine(&Info::wdl((319, 455, 226)))
+              .combine(&Info::tb_hits(0))
+              .combine(&Info::hash_full(5.0))
+              .combine(&Info::cpu_load(999.0))
+              .combine(&Info::engine_string("hello world".to_string())));
+}
+
+#[test]
+fn test_info_skips_unknown_tokens() {
+    let parsed = Info::from_str("info depth 3 movesleft 40 nodes 10\n");
+    assert_eq!(parsed, Ok(Info::depth(3).combine(&Info::nodes(10))));
+}
+
+#[test]
+fn test_info_bounded_score() {
+    test_info("info depth 20 seldepth 28 multipv 1 score cp 30 upperbound nodes 2000 time 10\n",
+              Info::depth(20)
+              .combine(&Info::seldepth(28))
+              .combine(&Info::multi_pv(1))
+              .combine(&Info::score(Score::cp(30).with_bound(ScoreBound::Upper)))
+              .combine(&Info::nodes(2000))
+              .combine(&Info::time(10)));
+}
+
+#[test]
+fn test_info_pv_san() {
+    let info = Info::from_str("info depth 2 pv e2e4 e7e5 g1f3\n").unwrap();
+    assert_eq!(
+        info.pv_san(&Board::default()),
+        Ok(vec!["e4".to_string(), "e5".to_string(), "Nf3".to_string()])
+    );
+}
+
+#[test]
+fn test_info_huge_numbers() {
+    let line = "info depth 400 nodes 99999999999999999999999 nps 1.2e6 tbhits 7 pv e2e4\n";
+    let info = Info::from_str(line).unwrap();
+    assert_eq!(info.get_nps(), Some(1_200_000));
+    assert_eq!(info.get_tbhits(), Some(7));
+    assert_eq!(info.get_pv().len(), 1);
+    #[cfg(not(feature = "wide-counters"))]
+    assert_eq!(info.get_nodes(), Some(u64::MAX));
+    #[cfg(feature = "wide-counters")]
+    assert_eq!(info.get_nodes(), Some(99999999999999999999999));
+
+    let strict = Info::parse_with("info depth 99999999999999999999\n", NumberOverflow::Error);
+    assert!(matches!(strict, Err(Error::ParseError { error: ErrorKind::TooLarge, .. })));
+}
+
+#[test]
+fn test_info_white_pov() {
+    let info = Info::from_str("info depth 5 score cp 40 lowerbound wdl 500 300 200 pv e7e5").unwrap();
+    assert_eq!(info.score_white_pov(Color::White), Some(Score::cp(40).with_bound(ScoreBound::Lower)));
+    let white = info.white_pov(Color::Black);
+    assert_eq!(white.get_score(), Some(Score::cp(-40).with_bound(ScoreBound::Upper)));
+    assert_eq!(white.get_wdl(), Some((200, 300, 500)));
+    assert_eq!(white.get_pv(), info.get_pv());
+    assert_eq!(Info::depth(1).score_white_pov(Color::Black), None);
+}
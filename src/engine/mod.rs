@@ -1,6 +1,7 @@
 pub mod best_move;
 pub mod copyprotection;
 pub mod engine_command;
+pub mod engine_info;
 pub mod engine_option;
 pub mod id;
 pub mod info;
@@ -1,4 +1,4 @@
-use error::Error;
+use error::{CommandFamily, Error};
 use std::fmt;
 use std::str::FromStr;
 
@@ -10,7 +10,6 @@ use nom::bytes::streaming::tag;
 use nom::branch::alt;
 use nom::sequence::tuple;
 use nom::multi::fold_many1;
-use nom::character::complete::alphanumeric1;
 
 #[derive(Clone, PartialEq, PartialOrd, Debug)]
 pub enum OptionType {
@@ -63,7 +62,7 @@ fn parse_combo_var(input: &str) -> IResult<&str, &str> {
         tuple((
             tag("var"),
             space,
-            alphanumeric1
+            token
         )),
         |(_, _, x)| x
     )(input)
@@ -86,7 +85,7 @@ fn parse_combo(input: &str) -> IResult<&str, OptionType> {
             space,
             tag("default"),
             space,
-            alphanumeric1,
+            token,
             space,
             fold_many1(
                 alt((
@@ -158,7 +157,9 @@ impl FromStr for OptionType {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_option_type(s)?.1)
+        parse_option_type(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::OptionType, s, e))
     }
 }
 
@@ -218,6 +219,21 @@ fn test_option_type_combo() {
     );
 }
 
+#[test]
+fn test_option_type_combo_punctuation() {
+    test_option_type(
+        "type combo default cuda-auto var cuda-auto var cuda-fp16 var blas\n",
+        OptionType::Combo(
+            "cuda-auto".to_string(),
+            vec![
+                "cuda-auto".to_string(),
+                "cuda-fp16".to_string(),
+                "blas".to_string(),
+            ],
+        ),
+    );
+}
+
 #[test]
 fn test_option_type_button() {
     test_option_type("type button\n", OptionType::Button);
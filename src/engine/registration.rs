@@ -1,4 +1,4 @@
-use error::Error;
+use error::{CommandFamily, Error};
 use std::fmt;
 use std::str::FromStr;
 
@@ -36,7 +36,9 @@ impl FromStr for Registration {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_registration(s)?.1)
+        parse_registration(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::Registration, s, e))
     }
 }
 
@@ -1,5 +1,6 @@
 use crate::engine_base::eval::Eval;
-use error::Error;
+use chess::Color;
+use error::{CommandFamily, Error};
 use num_traits::NumCast;
 use parsers::*;
 use std::convert::From;
@@ -7,104 +8,179 @@ use std::fmt;
 use std::str::FromStr;
 
 use nom::IResult;
-use nom::combinator::{map, complete};
+use nom::combinator::complete;
 use nom::bytes::streaming::tag;
-use nom::branch::alt;
-use nom::sequence::tuple;
+use nom::error::ErrorKind;
 
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
-pub enum Score {
-    Cp(i64),
-    Mate(i64),
-    Lower(i64),
-    Upper(i64),
-}
-
-fn parse_score_cp(input: &str) -> IResult<&str, Score> {
-    map(
-        tuple((
-            tag("cp"),
-            space,
-            parse_i64,
-        )),
-        |(_, _, v)| Score::Cp(v)
-    )(input)
-}
-
-fn parse_score_mate(input: &str) -> IResult<&str, Score> {
-    map(
-        tuple((
-            tag("mate"),
-            space,
-            parse_i64,
-        )),
-        |(_, _, v)| Score::Mate(v)
-    )(input)
-}
-
-fn parse_score_lower(input: &str) -> IResult<&str, Score> {
-    map(
-        tuple((
-            tag("lowerbound"),
-            space,
-            parse_i64
-        )),
-        |(_, _, v)| Score::Lower(v)
-    )(input)
-}
-
-fn parse_score_upper(input: &str) -> IResult<&str, Score> {
-    map(
-        tuple((
-            tag("upperbound"),
-            space,
-            parse_i64,
-        )),
-        |(_, _, v)| Score::Upper(v)
-    )(input)
+pub enum ScoreKind {
+    Cp,
+    Mate,
 }
 
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub enum ScoreBound {
+    #[default]
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct Score {
+    kind: ScoreKind,
+    value: i64,
+    bound: ScoreBound,
+}
+
+impl Score {
+    pub fn new(kind: ScoreKind, value: i64, bound: ScoreBound) -> Score {
+        Score { kind, value, bound }
+    }
+
+    pub fn cp(value: i64) -> Score {
+        Score::new(ScoreKind::Cp, value, ScoreBound::Exact)
+    }
+
+    pub fn mate(value: i64) -> Score {
+        Score::new(ScoreKind::Mate, value, ScoreBound::Exact)
+    }
+
+    pub fn with_bound(&self, bound: ScoreBound) -> Score {
+        Score::new(self.kind, self.value, bound)
+    }
+
+    pub fn get_kind(&self) -> ScoreKind {
+        self.kind
+    }
+
+    pub fn get_value(&self) -> i64 {
+        self.value
+    }
+
+    pub fn get_bound(&self) -> ScoreBound {
+        self.bound
+    }
+
+    pub fn is_mate(&self) -> bool {
+        self.kind == ScoreKind::Mate
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.bound == ScoreBound::Exact
+    }
+
+    /// The score for the other side: the value negated, and a lower bound made an upper bound
+    /// and the other way round.  `i64::MIN` saturates to `i64::MAX`.
+    pub fn negated(&self) -> Score {
+        let bound = match self.bound {
+            ScoreBound::Exact => ScoreBound::Exact,
+            ScoreBound::Lower => ScoreBound::Upper,
+            ScoreBound::Upper => ScoreBound::Lower,
+        };
+        Score::new(self.kind, self.value.saturating_neg(), bound)
+    }
+
+    /// This score, which engines give for `side_to_move`, as `viewer` sees it.
+    pub fn pov(&self, side_to_move: Color, viewer: Color) -> Score {
+        if side_to_move == viewer {
+            *self
+        } else {
+            self.negated()
+        }
+    }
+
+    /// This score, given for `side_to_move`, from white's side, as GUIs usually show it.
+    pub fn white_pov(&self, side_to_move: Color) -> Score {
+        self.pov(side_to_move, Color::White)
+    }
+}
+
+// Shims for code written against the old `enum Score { Cp, Mate, Lower, Upper }`.  They keep
+// `Score::Cp(x)` style constructors compiling; matching on scores must move to the getters.
+#[allow(non_snake_case)]
+impl Score {
+    #[deprecated(note = "use Score::cp")]
+    pub fn Cp(value: i64) -> Score {
+        Score::cp(value)
+    }
+
+    #[deprecated(note = "use Score::mate")]
+    pub fn Mate(value: i64) -> Score {
+        Score::mate(value)
+    }
+
+    #[deprecated(note = "use Score::cp(..).with_bound(ScoreBound::Lower)")]
+    pub fn Lower(value: i64) -> Score {
+        Score::cp(value).with_bound(ScoreBound::Lower)
+    }
+
+    #[deprecated(note = "use Score::cp(..).with_bound(ScoreBound::Upper)")]
+    pub fn Upper(value: i64) -> Score {
+        Score::cp(value).with_bound(ScoreBound::Upper)
+    }
+}
+
+fn parse_score_bound(input: &str) -> IResult<&str, ScoreBound> {
+    let (after, _) = space(input)?;
+    if let Some(rest) = after.strip_prefix("lowerbound") {
+        Ok((rest, ScoreBound::Lower))
+    } else if let Some(rest) = after.strip_prefix("upperbound") {
+        Ok((rest, ScoreBound::Upper))
+    } else {
+        Ok((input, ScoreBound::Exact))
+    }
+}
+
+// Scores are in nearly every info line, so the kind and bound are matched directly rather than
+// tried as alternatives.
 pub fn parse_score(input: &str) -> IResult<&str, Score> {
-    map(
-        tuple((
-            tag("score"),
-            space,
-            alt((
-                complete(parse_score_cp),
-                complete(parse_score_mate),
-                complete(parse_score_upper),
-                complete(parse_score_lower)
-            )),
-        )),
-        |(_, _, score)| score
-    )(input)
+    let (input, _) = tag("score")(input)?;
+    let (input, _) = space(input)?;
+    let (input, kind) = if let Some(rest) = input.strip_prefix("cp") {
+        (rest, ScoreKind::Cp)
+    } else if let Some(rest) = input.strip_prefix("mate") {
+        (rest, ScoreKind::Mate)
+    } else {
+        return Err(nom::Err::Error((input, ErrorKind::Tag)));
+    };
+    let (input, _) = space(input)?;
+    let (input, value) = complete(parse_i64)(input)?;
+    let (input, bound) = parse_score_bound(input)?;
+    Ok((input, Score::new(kind, value, bound)))
 }
 
 impl FromStr for Score {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_score(s)?.1)
+        parse_score(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::Score, s, e))
     }
 }
 
+// Evals count mates in plies, and UCI in moves: a mate in three plies is a mate in two moves.
 impl<E: Eval> From<E> for Score {
     fn from(eval: E) -> Score {
-        if let Some(mate) = eval.depth_to_mate() {
-            Score::Mate(mate)
+        if let Some(plies) = eval.depth_to_mate() {
+            Score::mate(plies.signum() * ((plies.abs() + 1) / 2))
         } else {
-            Score::Cp(NumCast::from::<E>(eval).expect("eval is in the i64 range."))
+            Score::cp(NumCast::from::<E>(eval).expect("eval is in the i64 range."))
         }
     }
 }
 
 impl fmt::Display for Score {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Score::Cp(x) => writeln!(f, "score cp {}", x),
-            Score::Mate(x) => writeln!(f, "score mate {}", x),
-            Score::Lower(x) => writeln!(f, "score lowerbound {}", x),
-            Score::Upper(x) => writeln!(f, "score upperbound {}", x),
+        match self.kind {
+            ScoreKind::Cp => write!(f, "score cp {}", self.value)?,
+            ScoreKind::Mate => write!(f, "score mate {}", self.value)?,
+        };
+        match self.bound {
+            ScoreBound::Exact => writeln!(f),
+            ScoreBound::Lower => writeln!(f, " lowerbound"),
+            ScoreBound::Upper => writeln!(f, " upperbound"),
         }
     }
 }
@@ -120,29 +196,66 @@ fn test_parse(s: &str, score: Score) {
 
 #[test]
 fn test_score_negative() {
-    test_parse("score cp -100\n", Score::Cp(-100));
+    test_parse("score cp -100\n", Score::cp(-100));
 }
 #[test]
 fn test_score_zero() {
-    test_parse("score cp 0\n", Score::Cp(0));
+    test_parse("score cp 0\n", Score::cp(0));
 }
 
 #[test]
 fn test_score_cp() {
-    test_parse("score cp 100\n", Score::Cp(100));
+    test_parse("score cp 100\n", Score::cp(100));
 }
 
 #[test]
 fn test_score_mate() {
-    test_parse("score mate 100\n", Score::Mate(100));
+    test_parse("score mate 100\n", Score::mate(100));
 }
 
 #[test]
 fn test_score_upper() {
-    test_parse("score upperbound 100\n", Score::Upper(100));
+    test_parse(
+        "score cp 100 upperbound\n",
+        Score::cp(100).with_bound(ScoreBound::Upper),
+    );
 }
 
 #[test]
 fn test_score_lower() {
-    test_parse("score lowerbound 100\n", Score::Lower(100));
+    test_parse(
+        "score cp 100 lowerbound\n",
+        Score::cp(100).with_bound(ScoreBound::Lower),
+    );
+}
+
+#[test]
+fn test_score_mate_lower() {
+    test_parse(
+        "score mate -3 lowerbound\n",
+        Score::mate(-3).with_bound(ScoreBound::Lower),
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_score_shims() {
+    assert_eq!(Score::Cp(5), Score::cp(5));
+    assert_eq!(Score::Mate(5), Score::mate(5));
+    assert_eq!(Score::Lower(5), Score::new(ScoreKind::Cp, 5, ScoreBound::Lower));
+    assert_eq!(Score::Upper(5), Score::new(ScoreKind::Cp, 5, ScoreBound::Upper));
+}
+
+#[test]
+fn test_score_white_pov() {
+    let lower = Score::cp(30).with_bound(ScoreBound::Lower);
+    assert_eq!(lower.white_pov(Color::White), lower);
+    assert_eq!(
+        lower.white_pov(Color::Black),
+        Score::cp(-30).with_bound(ScoreBound::Upper)
+    );
+    assert_eq!(Score::mate(3).white_pov(Color::Black), Score::mate(-3));
+    assert_eq!(Score::mate(3).pov(Color::White, Color::Black), Score::mate(-3));
+    assert_eq!(Score::cp(5).negated().negated(), Score::cp(5));
+    assert_eq!(Score::cp(i64::MIN).negated(), Score::cp(i64::MAX));
 }
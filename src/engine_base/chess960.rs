@@ -0,0 +1,112 @@
+use chess::{Board, ChessMove, File, Piece, Square};
+
+use engine::best_move::BestMove;
+
+/// Castling as the chess crate makes it, from a Chess960 move, where the king takes its own
+/// rook.  Any other move is returned as it is.
+///
+/// The chess crate only castles with the king on the e-file and the rooks in the corners, so
+/// these are the only Chess960 castling moves it can play.  A start position with castling
+/// rights for the king or rooks anywhere else can't be parsed, and the engine says so rather
+/// than search it.
+pub fn from_chess960(board: &Board, m: ChessMove) -> ChessMove {
+    let (source, dest) = (m.get_source(), m.get_dest());
+    let own = |square: Square, piece: Piece| {
+        board.piece_on(square) == Some(piece)
+            && board.color_on(square) == Some(board.side_to_move())
+    };
+    if !own(source, Piece::King) || !own(dest, Piece::Rook) || source.get_rank() != dest.get_rank()
+    {
+        return m;
+    }
+    let file = if dest.get_file() > source.get_file() {
+        File::G
+    } else {
+        File::C
+    };
+    ChessMove::new(source, Square::make_square(source.get_rank(), file), None)
+}
+
+/// A move the chess crate makes, written the Chess960 way: castling as the king taking its own
+/// rook.  Any other move is returned as it is.
+pub fn to_chess960(board: &Board, m: ChessMove) -> ChessMove {
+    let (source, dest) = (m.get_source(), m.get_dest());
+    if board.piece_on(source) != Some(Piece::King) || source.get_file() != File::E {
+        return m;
+    }
+    let rook = match dest.get_file() {
+        File::G => File::H,
+        File::C => File::A,
+        _ => return m,
+    };
+    ChessMove::new(source, Square::make_square(source.get_rank(), rook), None)
+}
+
+/// The moves of a `position` command, as the chess crate makes them.  The moves after the first
+/// one that isn't legal are left as they are, for `apply_moves` to report.
+pub fn moves_from_chess960(board: Board, moves: &[ChessMove]) -> Vec<ChessMove> {
+    let mut result = Vec::with_capacity(moves.len());
+    let mut board = board;
+    for (index, &m) in moves.iter().enumerate() {
+        let m = from_chess960(&board, m);
+        if !board.legal(m) {
+            result.extend_from_slice(&moves[index..]);
+            break;
+        }
+        result.push(m);
+        board = board.make_move_new(m);
+    }
+    result
+}
+
+/// `best_move`, found on `board`, with its move and ponder move written the Chess960 way.
+pub fn best_move_to_chess960(board: &Board, best_move: BestMove) -> BestMove {
+    match (best_move.get_move(), best_move.get_ponder()) {
+        (Some(m), Some(ponder)) => {
+            let after = board.make_move_new(m);
+            BestMove::new_with_ponder(to_chess960(board, m), to_chess960(&after, ponder))
+        }
+        (Some(m), None) => BestMove::new(to_chess960(board, m)),
+        _ => best_move,
+    }
+}
+
+#[cfg(test)]
+use std::str::FromStr;
+
+#[test]
+fn test_chess960_castling() {
+    let board = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let e1h1 = ChessMove::new(Square::E1, Square::H1, None);
+    let e1g1 = ChessMove::new(Square::E1, Square::G1, None);
+    let e1c1 = ChessMove::new(Square::E1, Square::C1, None);
+    assert_eq!(from_chess960(&board, e1h1), e1g1);
+    assert_eq!(
+        from_chess960(&board, ChessMove::new(Square::E1, Square::A1, None)),
+        e1c1
+    );
+    assert_eq!(to_chess960(&board, e1g1), e1h1);
+    assert_eq!(
+        to_chess960(&board, e1c1),
+        ChessMove::new(Square::E1, Square::A1, None)
+    );
+
+    // Other moves, including the rook's, are left alone.
+    let h1h8 = ChessMove::new(Square::H1, Square::H8, None);
+    assert_eq!(from_chess960(&board, h1h8), h1h8);
+    let e1f1 = ChessMove::new(Square::E1, Square::F1, None);
+    assert_eq!(to_chess960(&board, e1f1), e1f1);
+
+    let e8a8 = ChessMove::new(Square::E8, Square::A8, None);
+    let moves = moves_from_chess960(board, &[e1h1, e8a8, e1f1]);
+    assert_eq!(
+        moves,
+        vec![e1g1, ChessMove::new(Square::E8, Square::C8, None), e1f1]
+    );
+
+    let best_move = best_move_to_chess960(
+        &board,
+        BestMove::new_with_ponder(e1g1, ChessMove::new(Square::E8, Square::C8, None)),
+    );
+    assert_eq!(best_move, BestMove::new_with_ponder(e1h1, e8a8));
+}
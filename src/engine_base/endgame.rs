@@ -0,0 +1,433 @@
+use std::sync::Arc;
+
+use chess::{
+    get_file, get_king_moves, BitBoard, Board, Color, File, Piece, Rank, Square, ALL_SQUARES, EMPTY,
+};
+
+#[cfg(test)]
+use std::str::FromStr;
+
+/// What a won ending is worth beyond its material, enough that the search heads for one over
+/// any middlegame advantage, and well clear of the mate scores.
+pub const KNOWN_WIN: i32 = 1000;
+
+// Positions with the strong side to move, or the weak side to move; its king; the strong king;
+// and the pawn, on files a to d and ranks 2 to 7.
+const KPK_POSITIONS: usize = 2 * 64 * 64 * 24;
+
+// What the KPK generator knows about a position so far.
+#[derive(Copy, Clone, PartialEq)]
+enum Kpk {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+fn kpk_index(strong_to_move: bool, weak_king: usize, strong_king: usize, pawn: usize) -> usize {
+    let pawn = (pawn & 7) + 4 * ((pawn >> 3) - 1);
+    (strong_to_move as usize) | weak_king << 1 | strong_king << 7 | pawn << 13
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    let files = ((a & 7) as isize - (b & 7) as isize).abs();
+    let ranks = ((a >> 3) as isize - (b >> 3) as isize).abs();
+    files.max(ranks) as usize
+}
+
+fn king_moves(square: usize) -> BitBoard {
+    get_king_moves(ALL_SQUARES[square])
+}
+
+fn white_pawn_attacks(pawn: usize) -> u64 {
+    let square = 1u64 << pawn;
+    ((square & !0x0101_0101_0101_0101) << 7) | ((square & !0x8080_8080_8080_8080) << 9)
+}
+
+/// Whether king and pawn beat a lone king, for every position, worked out by retrograde
+/// analysis when it is built.  It takes 24 KB, and is shared between searchers.
+pub struct KpkBitbase {
+    wins: Vec<u64>,
+}
+
+impl KpkBitbase {
+    pub fn new() -> KpkBitbase {
+        let mut positions: Vec<Kpk> = (0..KPK_POSITIONS).map(KpkBitbase::classify_start).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..KPK_POSITIONS {
+                if positions[index] == Kpk::Unknown {
+                    let result = KpkBitbase::classify(&positions, index);
+                    if result != Kpk::Unknown {
+                        positions[index] = result;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        let mut wins = vec![0u64; KPK_POSITIONS / 64];
+        for (index, &result) in positions.iter().enumerate() {
+            if result == Kpk::Win {
+                wins[index / 64] |= 1 << (index % 64);
+            }
+        }
+        KpkBitbase { wins }
+    }
+
+    fn decode(index: usize) -> (bool, usize, usize, usize) {
+        let pawn = index >> 13;
+        (
+            index & 1 == 1,
+            (index >> 1) & 63,
+            (index >> 7) & 63,
+            (pawn & 3) + 8 * (pawn / 4 + 1),
+        )
+    }
+
+    fn classify_start(index: usize) -> Kpk {
+        let (strong_to_move, weak_king, strong_king, pawn) = KpkBitbase::decode(index);
+        let weak_attacks = king_moves(weak_king).0;
+        let strong_attacks = king_moves(strong_king).0;
+        let pawn_attacks = white_pawn_attacks(pawn);
+
+        if distance(weak_king, strong_king) <= 1
+            || strong_king == pawn
+            || weak_king == pawn
+            || (strong_to_move && pawn_attacks & (1 << weak_king) != 0)
+        {
+            Kpk::Invalid
+        } else if strong_to_move
+            && pawn >> 3 == 6
+            && strong_king != pawn + 8
+            && weak_king != pawn + 8
+            && (distance(weak_king, pawn + 8) > 1 || distance(strong_king, pawn + 8) == 1)
+        {
+            // The pawn promotes, and the queen can't be taken.
+            Kpk::Win
+        } else if !strong_to_move
+            && (weak_attacks & !(strong_attacks | pawn_attacks) == 0
+                || weak_attacks & !strong_attacks & (1 << pawn) != 0)
+        {
+            // Stalemate, or the pawn is lost.
+            Kpk::Draw
+        } else {
+            Kpk::Unknown
+        }
+    }
+
+    fn classify(positions: &[Kpk], index: usize) -> Kpk {
+        let (strong_to_move, weak_king, strong_king, pawn) = KpkBitbase::decode(index);
+        let mut results = [false; 4];
+        if strong_to_move {
+            for to in king_moves(strong_king) {
+                let child = kpk_index(false, weak_king, to.to_index(), pawn);
+                results[positions[child] as usize] = true;
+            }
+            if pawn >> 3 < 6 {
+                let push = pawn + 8;
+                results[positions[kpk_index(false, weak_king, strong_king, push)] as usize] = true;
+                if pawn >> 3 == 1 && push != strong_king && push != weak_king {
+                    let double = kpk_index(false, weak_king, strong_king, push + 8);
+                    results[positions[double] as usize] = true;
+                }
+            }
+            if results[Kpk::Win as usize] {
+                Kpk::Win
+            } else if results[Kpk::Unknown as usize] {
+                Kpk::Unknown
+            } else {
+                Kpk::Draw
+            }
+        } else {
+            for to in king_moves(weak_king) {
+                let child = kpk_index(true, to.to_index(), strong_king, pawn);
+                results[positions[child] as usize] = true;
+            }
+            if results[Kpk::Draw as usize] {
+                Kpk::Draw
+            } else if results[Kpk::Unknown as usize] {
+                Kpk::Unknown
+            } else {
+                Kpk::Win
+            }
+        }
+    }
+
+    /// Whether the side with the pawn wins, for white's king and pawn against black's king.
+    /// The pawn has to be on the second to seventh rank.
+    pub fn probe(
+        &self,
+        strong_king: Square,
+        pawn: Square,
+        weak_king: Square,
+        strong_to_move: bool,
+    ) -> bool {
+        // The files e to h are mirrored onto a to d.
+        let mirror = if pawn.get_file().to_index() > 3 { 7 } else { 0 };
+        let index = kpk_index(
+            strong_to_move,
+            weak_king.to_index() ^ mirror,
+            strong_king.to_index() ^ mirror,
+            pawn.to_index() ^ mirror,
+        );
+        self.wins[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Whether the side with the pawn wins `board`, if it is king and pawn against king.
+    pub fn probe_board(&self, board: &Board) -> Option<bool> {
+        let pawns = *board.pieces(Piece::Pawn);
+        if board.combined().popcnt() != 3 || pawns.popcnt() != 1 {
+            return None;
+        }
+        let pawn = pawns.to_square();
+        let strong = board.color_on(pawn)?;
+        // Black's side is flipped onto white's.
+        let flip = |square: Square| match strong {
+            Color::White => square,
+            Color::Black => ALL_SQUARES[square.to_index() ^ 56],
+        };
+        Some(self.probe(
+            flip(board.king_square(strong)),
+            flip(pawn),
+            flip(board.king_square(!strong)),
+            board.side_to_move() == strong,
+        ))
+    }
+}
+
+impl Default for KpkBitbase {
+    fn default() -> Self {
+        KpkBitbase::new()
+    }
+}
+
+// How far `square` is from the centre, from 0 on d4 to 6 in a corner.
+fn centre_distance(square: Square) -> i32 {
+    let file = square.get_file().to_index() as i32;
+    let rank = square.get_rank().to_index() as i32;
+    (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+}
+
+fn is_light(square: Square) -> bool {
+    (square.get_file().to_index() + square.get_rank().to_index()) % 2 == 1
+}
+
+/// Knowledge of endings that an evaluation counting material and pawns gets wrong, for a search
+/// that should convert them rather than shuffle.  Each piece of it can be turned off.
+///
+/// * King and pawn against king is looked up in a `KpkBitbase`: a win is worth `KNOWN_WIN`,
+///   more as the pawn advances, and anything else is a draw.
+/// * Mop-up: a lone king against a queen or rook is a `KNOWN_WIN`, more the closer it is to the
+///   edge and to the other king, so the search drives it to where it can be mated.
+/// * Wrong bishop: a bishop and pawns on one rook file can't win against a king holding the
+///   corner, when the bishop doesn't cover the promotion square.
+#[derive(Clone)]
+pub struct EndgameKnowledge {
+    kpk: Option<Arc<KpkBitbase>>,
+    mop_up: bool,
+    wrong_bishop: bool,
+}
+
+impl EndgameKnowledge {
+    /// All of it, building the KPK bitbase.
+    pub fn new() -> EndgameKnowledge {
+        EndgameKnowledge {
+            kpk: Some(Arc::new(KpkBitbase::new())),
+            mop_up: true,
+            wrong_bishop: true,
+        }
+    }
+
+    /// None of it.
+    pub fn none() -> EndgameKnowledge {
+        EndgameKnowledge {
+            kpk: None,
+            mop_up: false,
+            wrong_bishop: false,
+        }
+    }
+
+    /// Look up king and pawn against king in `bitbase`, or don't.
+    pub fn kpk(mut self, bitbase: Option<Arc<KpkBitbase>>) -> EndgameKnowledge {
+        self.kpk = bitbase;
+        self
+    }
+
+    pub fn mop_up(mut self, mop_up: bool) -> EndgameKnowledge {
+        self.mop_up = mop_up;
+        self
+    }
+
+    pub fn wrong_bishop(mut self, wrong_bishop: bool) -> EndgameKnowledge {
+        self.wrong_bishop = wrong_bishop;
+        self
+    }
+
+    pub fn get_kpk(&self) -> Option<&Arc<KpkBitbase>> {
+        self.kpk.as_ref()
+    }
+
+    pub fn get_mop_up(&self) -> bool {
+        self.mop_up
+    }
+
+    pub fn get_wrong_bishop(&self) -> bool {
+        self.wrong_bishop
+    }
+
+    /// The score of `board`, from white's point of view, given what the evaluator made of it.
+    /// Positions this knows nothing about keep their score.
+    pub fn evaluate(&self, board: &Board, score: i32) -> i32 {
+        if let Some(ref kpk) = self.kpk {
+            if let Some(wins) = kpk.probe_board(board) {
+                return if wins {
+                    let pawn = board.pieces(Piece::Pawn).to_square();
+                    match board.color_on(pawn) {
+                        Some(Color::White) => {
+                            KNOWN_WIN + 100 + 20 * pawn.get_rank().to_index() as i32
+                        }
+                        _ => -KNOWN_WIN - 100 - 20 * (7 - pawn.get_rank().to_index() as i32),
+                    }
+                } else {
+                    0
+                };
+            }
+        }
+        for &strong in [Color::White, Color::Black].iter() {
+            let sign = if strong == Color::White { 1 } else { -1 };
+            if board.color_combined(!strong).popcnt() != 1 {
+                continue;
+            }
+            if self.wrong_bishop && self.is_wrong_bishop(board, strong) {
+                return 0;
+            }
+            let majors = (board.pieces(Piece::Queen) | board.pieces(Piece::Rook))
+                & board.color_combined(strong);
+            if self.mop_up && majors != EMPTY {
+                let strong_king = board.king_square(strong);
+                let weak_king = board.king_square(!strong);
+                let close = 7 - distance(strong_king.to_index(), weak_king.to_index()) as i32;
+                return score + sign * (KNOWN_WIN + 20 * centre_distance(weak_king) + 10 * close);
+            }
+        }
+        score
+    }
+
+    // Whether `strong` has only a bishop and pawns on one rook file, against a bare king in
+    // front of them that the bishop can't drive out of the corner.
+    fn is_wrong_bishop(&self, board: &Board, strong: Color) -> bool {
+        let ours = *board.color_combined(strong);
+        let bishops = board.pieces(Piece::Bishop) & ours;
+        let pawns = board.pieces(Piece::Pawn) & ours;
+        if bishops.popcnt() != 1 || pawns == EMPTY || ours.popcnt() != pawns.popcnt() + 2 {
+            return false;
+        }
+        let file = pawns.to_square().get_file();
+        if (file != File::A && file != File::H) || pawns & get_file(file) != pawns {
+            return false;
+        }
+        let promotion = match strong {
+            Color::White => Square::make_square(Rank::Eighth, file),
+            Color::Black => Square::make_square(Rank::First, file),
+        };
+        is_light(bishops.to_square()) != is_light(promotion)
+            && distance(board.king_square(!strong).to_index(), promotion.to_index()) <= 1
+    }
+}
+
+impl Default for EndgameKnowledge {
+    fn default() -> Self {
+        EndgameKnowledge::new()
+    }
+}
+
+#[cfg(test)]
+fn knowledge_score(knowledge: &EndgameKnowledge, fen: &str) -> i32 {
+    knowledge.evaluate(&Board::from_str(fen).unwrap(), 100)
+}
+
+#[test]
+fn test_kpk() {
+    let knowledge = EndgameKnowledge::new();
+    // The black king is outside the pawn's square.
+    assert!(knowledge_score(&knowledge, "8/8/8/8/8/k7/6P1/6K1 w - - 0 1") > KNOWN_WIN);
+    // Stalemate.
+    assert_eq!(
+        knowledge_score(&knowledge, "4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"),
+        0
+    );
+    // The rook pawn can't be queened with the king in the corner.
+    assert_eq!(
+        knowledge_score(&knowledge, "k7/8/8/8/8/8/P7/K7 w - - 0 1"),
+        0
+    );
+    // The king on the sixth in front of its pawn wins whoever is to move.
+    assert!(knowledge_score(&knowledge, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1") > KNOWN_WIN);
+    // Further back, whoever has the opposition.
+    assert_eq!(
+        knowledge_score(&knowledge, "8/4k3/8/4K3/4P3/8/8/8 b - - 0 1"),
+        KNOWN_WIN + 100 + 60
+    );
+    assert_eq!(
+        knowledge_score(&knowledge, "8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"),
+        0
+    );
+    // The same for black, on the h-file.
+    assert!(knowledge_score(&knowledge, "6k1/6p1/K7/8/8/8/8/8 b - - 0 1") < -KNOWN_WIN);
+    assert_eq!(
+        knowledge_score(&knowledge, "8/7p/8/8/8/8/k7/7K b - - 0 1"),
+        0
+    );
+}
+
+#[test]
+fn test_mop_up() {
+    let knowledge = EndgameKnowledge::new();
+    let centre = knowledge_score(&knowledge, "8/8/8/3k4/8/8/8/KQ6 w - - 0 1");
+    let edge = knowledge_score(&knowledge, "3k4/8/8/8/8/8/8/KQ6 w - - 0 1");
+    assert!(centre > KNOWN_WIN);
+    assert!(edge > centre);
+    assert!(knowledge_score(&knowledge, "3k4/8/3K4/8/8/8/8/1Q6 w - - 0 1") > edge);
+    assert!(knowledge_score(&knowledge, "8/8/8/8/8/8/r7/k6K w - - 0 1") < -KNOWN_WIN);
+
+    let knowledge = EndgameKnowledge::none();
+    assert_eq!(
+        knowledge_score(&knowledge, "3k4/8/8/8/8/8/8/KQ6 w - - 0 1"),
+        100
+    );
+}
+
+#[test]
+fn test_wrong_bishop() {
+    let knowledge = EndgameKnowledge::new();
+    // The light squared bishop can't cover h8.
+    assert_eq!(
+        knowledge_score(&knowledge, "7k/8/7P/8/8/8/8/K4B2 w - - 0 1"),
+        0
+    );
+    assert_eq!(
+        knowledge_score(&knowledge, "7k/8/7P/8/7P/8/8/K4B2 w - - 0 1"),
+        0
+    );
+    // The dark squared one can.
+    assert_eq!(
+        knowledge_score(&knowledge, "7k/8/7P/8/8/8/8/K3B3 w - - 0 1"),
+        100
+    );
+    // The king is too far from the corner.
+    assert_eq!(
+        knowledge_score(&knowledge, "8/8/4k2P/8/8/8/8/K4B2 w - - 0 1"),
+        100
+    );
+    // For black, a1 is dark.
+    assert_eq!(
+        knowledge_score(&knowledge, "k3b3/8/8/8/8/p7/8/1K6 w - - 0 1"),
+        0
+    );
+    assert_eq!(
+        knowledge_score(&EndgameKnowledge::none(), "7k/8/7P/8/8/8/8/K4B2 w - - 0 1"),
+        100
+    );
+}
@@ -1,83 +1,976 @@
-use super::engine_options::EngineOptions;
-use engine::Id;
-use super::search::Search;
-use chess_uci::timer::timer::Timer;
-use std::sync::mpsc::{Sender, Receiver};
-use std::sync::mpsc;
-use std::thread;
-use crate::gui::gui_command;
-use std::io::{Read, BufReader, Write};
-
-struct EngineBase<E: Eval, T: TimeManager<E>, S: Search<E>> {
-    options: EngineOptions,
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+use chess::{Board, Square, ALL_FILES, ALL_RANKS};
+
+use super::chess960::{best_move_to_chess960, moves_from_chess960};
+use super::engine_options::{EngineOptions, PARAMS_FILE};
+use super::eval_params::EvalParams;
+use super::info_throttle::InfoThrottle;
+use super::zobrist;
+#[cfg(test)]
+use super::engine_options::{INFO_INTERVAL, PONDER, UCI_ANALYSE_MODE, UCI_CHESS960};
+use super::iterative_deepening::IterativeDeepening;
+use super::pruning::PruningMargins;
+use super::pv::Pv;
+#[cfg(test)]
+use super::resettable::Resettable;
+use super::search_handle::SearchHandle;
+use super::search_limits::SearchLimits;
+use super::search_trace::{SearchTrace, DEFAULT_TRACE_NODES};
+use super::watchdog::{hard_limit, Watchdog};
+use engine::best_move::BestMove;
+use engine::engine_command::EngineCommand;
+use engine::id::Id;
+use engine::info::Info;
+use error::Error;
+use gui::go::Go;
+use gui::gui_command::{apply_moves, GuiCommand};
+use gui::position::{to_fen, MoveCounters};
+
+pub(crate) const DEFAULT_MAX_DEPTH: i16 = 64;
+// How often search output is forwarded to the GUI while a search is running.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// What the engine says about a `setoption` it can't take.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum OptionDiagnostics {
+    /// Ignore it, as most engines do.
+    #[default]
+    Silent,
+    /// Send an `info string` saying what was wrong with it.
+    Info,
+    /// Send the `info string`, and fail `handle` with a `CommandError`, which ends `main_loop`.
+    Strict,
+}
+
+/// The GUI-facing half of an engine: keeps track of the position and options the GUI sends, and
+/// runs the iterative deepening search on its own thread when asked to `go`, so that `stop`,
+/// `ponderhit` and `quit` are answered while it runs.
+///
+/// A search with a clock or a `movetime` is stopped by a `Watchdog` at its hard time limit, less
+/// the `Move Overhead` if the engine offers it, whatever the searcher's own time management
+/// does.  A pondering search is only timed from the `ponderhit`.
+///
+/// Engines that offer `UCI_AnalyseMode` have it passed to the searcher before each search.
+/// There is no opening book and no contempt here, so analysis differs from play only in the
+/// search not stopping early; draws are scored as zero either way.
+///
+/// With `UCI_Chess960` on, castling is read and written as the king taking its own rook.  The
+/// search and evaluation need nothing more, as the chess crate decides which castling moves are
+/// legal and the evaluators don't assume where the king starts; but the crate can only castle
+/// from the standard squares, so see `from_chess960`.  A `position` that can't be set up, such
+/// as a Chess960 start castling from anywhere else, is answered with an `info string`, and a
+/// `go` before the next one with `bestmove 0000`.
+pub struct EngineBase<I: IterativeDeepening> {
     id: Id,
-    searcher: S,
-    timer: T,
-    sent: HashMap<Instant, EngineCommand>,
-    received: HashMap<Instant, GuiCommand>,
-    _eval: PhantomData<E>,
+    options: EngineOptions,
+    // `None` while a search has it.
+    searcher: Option<I>,
+    search: Option<SearchHandle<I>>,
+    // Set for `go infinite` and `go ponder`, where the best move waits for `stop` or `ponderhit`.
+    hold_best_move: bool,
+    watchdog: Option<Watchdog>,
+    // The stop flag and hard time limit of a pondering search, for its watchdog on `ponderhit`.
+    ponder_limit: Option<(Arc<AtomicBool>, Duration)>,
+    // Weights loaded from the `ParamsFile`, waiting for the searcher to be free.
+    eval_params: Option<EvalParams>,
+    debug: bool,
+    diagnostics: OptionDiagnostics,
+    board: Board,
+    moves_made: u16,
+    counters: MoveCounters,
+    // Why the last `position` couldn't be set up, so that `go` doesn't search the one before it.
+    position_error: Option<String>,
+    info_throttle: InfoThrottle,
+    // The search's output after its last newline, waiting for the rest of the line.
+    partial_output: Vec<u8>,
 }
 
-impl<E: Eval, T: TimeManager<E>, S: Search<E>, W: Write> EngineBase<E, T, S, W> {
-    pub fn new<R: BufReader>(time_manager: TimeManager, id: Id, options: EngineOptions) {
+impl<I: IterativeDeepening + Send + 'static> EngineBase<I> {
+    pub fn new(id: Id, options: EngineOptions, searcher: I) -> EngineBase<I> {
         EngineBase {
-            options,
             id,
-            timer,
-            _eval: PhantomData,
+            options,
+            searcher: Some(searcher),
+            search: None,
+            hold_best_move: false,
+            watchdog: None,
+            ponder_limit: None,
+            eval_params: None,
+            debug: false,
+            diagnostics: OptionDiagnostics::default(),
+            board: Board::default(),
+            moves_made: 0,
+            counters: MoveCounters::default(),
+            position_error: None,
+            info_throttle: InfoThrottle::default(),
+            partial_output: vec![],
         }
     }
 
-    pub fn send<W: Write>(&mut self, command: EngineCommand, mut writer: W) {
-        self.sent[Instant::now()] = command.clone();
-        write!(writer, "{}", command);
+    /// How often the search's `info` lines are passed on, if not all of them.  The interval is
+    /// taken from the `InfoInterval` option instead if the engine offers it.
+    pub fn info_throttle(mut self, throttle: InfoThrottle) -> EngineBase<I> {
+        self.info_throttle = throttle;
+        self
     }
 
-    pub fn main_loop<R: BufReader, W: Write>(&mut self, mut reader: R, mut writer: W) {
-        let (tx, rx): (Sender<GuiCommand>, Receiver<GuiCommand>) = mpsc::channel();
+    /// How to answer a `setoption` for an option we don't have, or with a value it can't take.
+    /// With `debug` on, options that are taken are also echoed unless this is `Silent`.
+    pub fn diagnostics(mut self, diagnostics: OptionDiagnostics) -> EngineBase<I> {
+        self.diagnostics = diagnostics;
+        self
+    }
 
-        thread::spawn(move || {
-            EngineBase::monitor_stdin(reader, tx);
-        });
+    pub fn get_diagnostics(&self) -> OptionDiagnostics {
+        self.diagnostics
+    }
 
-        let mut board = Board::default();
-        let mut timer = Timer::new_static_move_time(Duration::from_secs(5));
-        let mut moves_made = 0;
+    pub fn get_id(&self) -> &Id {
+        &self.id
+    }
 
-        for message in rx.iter() {
-            received[Instant::now()] = message.clone();
+    pub fn get_options(&self) -> &EngineOptions {
+        &self.options
+    }
 
-            match message {
-                GuiCommand::Uci => {
-                    self.send(EngineCommand::UciOk, writer);
-                },
-                GuiCommand::IsReady => {
-                    self.send(EngineCommand::ReadyOk, writer);
-                },
-                GuiCommand::Position(position, moves) => {
-                    board = position;
-                    moves_made = moves.len();
-                    for x in moves {
-                        board.make_move(*x);
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    fn send<W: Write>(&self, command: EngineCommand, writer: &mut W) -> Result<(), Error> {
+        write!(writer, "{}", command)?;
+        Ok(())
+    }
+
+    fn send_string<W: Write>(&self, message: String, writer: &mut W) -> Result<(), Error> {
+        self.send(EngineCommand::Info(Info::engine_string(message)), writer)
+    }
+
+    // Tell the GUI, and play no move until a position we can set up arrives.
+    fn reject_position<W: Write>(&mut self, message: String, writer: &mut W) -> Result<(), Error> {
+        self.send_string(message.clone(), writer)?;
+        self.position_error = Some(message);
+        Ok(())
+    }
+
+    fn set_option<W: Write>(
+        &mut self,
+        name: &str,
+        value: Option<&str>,
+        writer: &mut W,
+    ) -> Result<(), Error> {
+        let result = self.options.set_option(name, value);
+        let result = match result {
+            Ok(()) if name == PARAMS_FILE => self.load_eval_params(),
+            result => result,
+        };
+        if self.diagnostics == OptionDiagnostics::Silent {
+            return Ok(());
+        }
+        let value = value.unwrap_or("");
+        match result {
+            Ok(()) if self.debug => {
+                self.send_string(format!("option {} set to {}", name, value), writer)
+            }
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let message = if name == PARAMS_FILE && self.options.contains(name) {
+                    format!("could not load evaluation parameters from {}: {}", value, e)
+                } else if self.options.contains(name) {
+                    format!("invalid value {} for option {}", value, name)
+                } else {
+                    format!("unknown option {}", name)
+                };
+                self.send_string(message, writer)?;
+                if self.diagnostics == OptionDiagnostics::Strict {
+                    writer.flush()?;
+                    return Err(e);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Read the `ParamsFile` that was just set.  The weights are handed to the searcher now if it
+    // is free, or when the next search starts.
+    fn load_eval_params(&mut self) -> Result<(), Error> {
+        if let Some(path) = self.options.get_params_file() {
+            let params = EvalParams::load(path)?;
+            match self.searcher {
+                Some(ref mut searcher) => searcher.set_eval_params(&params),
+                None => self.eval_params = Some(params),
+            }
+        }
+        Ok(())
+    }
+
+    fn send_best_move<W: Write>(&self, pv: &Pv, writer: &mut W) -> Result<(), Error> {
+        if !pv.is_empty() {
+            let best_move = if self.options.is_ponder_enabled() {
+                pv.to_best_move(self.board)
+            } else {
+                pv.into()
+            };
+            let best_move = if self.options.is_chess960() {
+                best_move_to_chess960(&self.board, best_move)
+            } else {
+                best_move
+            };
+            self.send(EngineCommand::BestMove(best_move), writer)
+        } else {
+            // No legal moves: the spec's null move.
+            writeln!(writer, "bestmove 0000")?;
+            Ok(())
+        }
+    }
+
+    // Answer `d`: the board, its FEN and key, and what the evaluator makes of it.
+    fn show_board<W: Write>(&mut self, writer: &mut W) -> Result<(), Error> {
+        for line in diagram(&self.board) {
+            self.send_string(line, writer)?;
+        }
+        self.send_string(format!("Fen: {}", to_fen(&self.board, self.counters)), writer)?;
+        self.send_string(format!("Key: {:016x}", zobrist::hash(&self.board)), writer)?;
+        let eval = match self.searcher {
+            Some(ref mut searcher) => match searcher.evaluate(self.board) {
+                Some(score) => format!("{} cp, for white", score),
+                None => "none".to_string(),
+            },
+            None => "not while searching".to_string(),
+        };
+        self.send_string(format!("Eval: {}", eval), writer)
+    }
+
+    // Where to write the trace of each search, if anywhere.
+    fn trace_path(&self) -> Option<String> {
+        if self.debug {
+            self.options.get_search_trace()
+        } else {
+            None
+        }
+    }
+
+    fn save_trace<W: Write>(
+        &self,
+        trace: &SearchTrace,
+        path: &str,
+        writer: &mut W,
+    ) -> Result<(), Error> {
+        let message = match trace.save(path) {
+            Ok(()) => format!(
+                "search trace of {} nodes written to {}",
+                trace.get_nodes().len(),
+                path
+            ),
+            Err(e) => format!("could not write search trace to {}: {}", path, e),
+        };
+        self.send_string(message, writer)
+    }
+
+    // Call off the watchdog, returning whether it stopped the search.
+    fn cancel_watchdog(&mut self) -> bool {
+        self.ponder_limit = None;
+        self.watchdog.take().is_some_and(|w| w.has_fired())
+    }
+
+    // Stop the running search and throw its result away.
+    fn cancel_search(&mut self) {
+        self.cancel_watchdog();
+        if let Some(search) = self.search.take() {
+            search.stop();
+            if let Ok((searcher, _, _)) = search.join() {
+                self.searcher = Some(searcher);
+            }
+        }
+        self.partial_output.clear();
+        self.info_throttle.finish();
+    }
+
+    fn go(&mut self, go: &Go) {
+        // A `go` while searching replaces the old search.
+        self.cancel_search();
+
+        if let Some(mut searcher) = self.searcher.take() {
+            if let Some(params) = self.eval_params.take() {
+                searcher.set_eval_params(&params);
+            }
+            searcher.set_debug(self.debug);
+            searcher.set_analyse_mode(self.options.is_analyse_mode());
+            searcher.set_nodes_time(self.options.get_nodes_time());
+            searcher.set_margins(PruningMargins::from_options(&self.options));
+            searcher.set_trace(self.trace_path().map(|_| DEFAULT_TRACE_NODES));
+            if let Some(interval) = self.options.get_info_interval() {
+                self.info_throttle = self.info_throttle.clone().min_interval(interval);
+            }
+            self.info_throttle.finish();
+            // Every `go` brings its own limits, so a `searchmoves` doesn't outlast its search.
+            let limits = SearchLimits::from_go(go);
+            let max_depth = limits.cap_depth(DEFAULT_MAX_DEPTH);
+            searcher.set_limits(limits);
+            self.hold_best_move = go.get_infinite() || go.get_ponder().is_some();
+            let overhead = self.options.get_move_overhead();
+            let limit = searcher
+                .get_stop_flag()
+                .and_then(|stop| Some((stop, hard_limit(go, self.board.side_to_move(), overhead)?)));
+            self.search = Some(match go.get_mate() {
+                Some(mate) => {
+                    let moves = mate.clamp(1, DEFAULT_MAX_DEPTH as u64 / 2) as u8;
+                    SearchHandle::launch_mate(searcher, self.board, moves)
+                }
+                None => SearchHandle::launch(searcher, self.board, max_depth, self.moves_made),
+            });
+            // Started after the launch, which clears the stop flag.
+            if go.get_ponder().is_some() {
+                self.ponder_limit = limit;
+            } else if let Some((stop, limit)) = limit {
+                self.watchdog = Some(Watchdog::start(stop, limit));
+            }
+        }
+    }
+
+    // Pass on the whole lines of what the search wrote, through the throttle.
+    fn write_search_output<W: Write>(&mut self, output: &[u8], writer: &mut W) -> Result<(), Error> {
+        self.partial_output.extend_from_slice(output);
+        let end = match self.partial_output.iter().rposition(|&b| b == b'\n') {
+            Some(end) => end + 1,
+            None => return Ok(()),
+        };
+        let lines = self.partial_output.drain(..end).collect::<Vec<u8>>();
+        let now = Instant::now();
+        for line in String::from_utf8_lossy(&lines).lines() {
+            if self.info_throttle.pass(line, now) {
+                writeln!(writer, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    // The end of the search's output: an unfinished last line, and the last `info` held back.
+    fn finish_search_output<W: Write>(&mut self, writer: &mut W) -> Result<(), Error> {
+        if !self.partial_output.is_empty() {
+            writeln!(writer, "{}", String::from_utf8_lossy(&self.partial_output))?;
+            self.partial_output.clear();
+        }
+        if let Some(line) = self.info_throttle.finish() {
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    // Wait for the running search, and send its best move unless `best_move` is false.
+    fn finish_search<W: Write>(&mut self, best_move: bool, writer: &mut W) -> Result<(), Error> {
+        if let Some(search) = self.search.take() {
+            let (mut searcher, pv, output) = search.join()?;
+            let timed_out = self.cancel_watchdog();
+            self.write_search_output(&output, writer)?;
+            self.finish_search_output(writer)?;
+            if timed_out && self.debug {
+                self.send_string("hard time limit reached".to_string(), writer)?;
+            }
+            if let (Some(path), Some(trace)) = (self.trace_path(), searcher.take_trace()) {
+                self.save_trace(&trace, &path, writer)?;
+            }
+            self.searcher = Some(searcher);
+            if best_move {
+                self.send_best_move(&pv, writer)?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Forward what the running search has written, and send the best move once it is done.
+    pub fn poll_search<W: Write>(&mut self, writer: &mut W) -> Result<(), Error> {
+        let (output, finished) = match self.search {
+            Some(ref search) => (search.take_output(), search.is_finished()),
+            None => (vec![], false),
+        };
+        self.write_search_output(&output, writer)?;
+        if finished && !self.hold_best_move {
+            self.finish_search(true, writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// React to one command from the GUI.  Returns `false` once the GUI has asked us to quit.
+    /// A `go` only starts the search; keep calling `poll_search` to get its output.
+    pub fn handle<W: Write>(&mut self, command: GuiCommand, writer: &mut W) -> Result<bool, Error> {
+        match command {
+            GuiCommand::Uci => {
+                self.send(EngineCommand::Id(self.id.clone()), writer)?;
+                write!(writer, "{}", self.options)?;
+                self.send(EngineCommand::UciOk, writer)?;
+            }
+            GuiCommand::IsReady => self.send(EngineCommand::ReadyOk, writer)?,
+            GuiCommand::SetOption(name, value) => {
+                self.set_option(&name, value.as_deref(), writer)?;
+            }
+            GuiCommand::UciNewGame => {
+                // Nothing from the last game may carry over into this one.
+                self.cancel_search();
+                if let Some(ref mut searcher) = self.searcher {
+                    searcher.reset();
+                }
+                self.board = Board::default();
+                self.moves_made = 0;
+                self.counters = MoveCounters::default();
+                self.position_error = None;
+            }
+            GuiCommand::Position(position) => {
+                // A position we can't reach isn't searched, and nor is the one before it.
+                let start = position.get_start_board();
+                let moves = if self.options.is_chess960() {
+                    moves_from_chess960(start, position.get_moves())
+                } else {
+                    position.get_moves().to_vec()
+                };
+                match apply_moves(start, &moves) {
+                    Ok(board) => {
+                        self.board = board;
+                        self.moves_made = moves.len() as u16;
+                        self.position_error = None;
+                        let mut board = start;
+                        self.counters = position.get_counters();
+                        for m in moves {
+                            self.counters = self.counters.after(&board, m);
+                            board = board.make_move_new(m);
+                        }
                     }
+                    Err(error) => {
+                        self.reject_position(format!("invalid position: {}", error), writer)?
+                    }
+                }
+            }
+            GuiCommand::Go(go) => match self.position_error {
+                Some(ref error) => {
+                    self.send_string(format!("no position to search: {}", error), writer)?;
+                    self.send(EngineCommand::BestMove(BestMove::null()), writer)?;
+                }
+                None => self.go(&go),
+            },
+            GuiCommand::Stop => {
+                if let Some(ref search) = self.search {
+                    search.stop();
+                }
+                self.finish_search(true, writer)?;
+            }
+            GuiCommand::PonderHit => {
+                // The GUI played the move we were pondering on, so this is a normal search now.
+                self.hold_best_move = false;
+                if let Some((stop, limit)) = self.ponder_limit.take() {
+                    self.watchdog = Some(Watchdog::start(stop, limit));
+                }
+                self.poll_search(writer)?;
+            }
+            GuiCommand::Quit => {
+                if let Some(ref search) = self.search {
+                    search.stop();
+                }
+                self.finish_search(false, writer)?;
+                return Ok(false);
+            }
+            GuiCommand::Debug(debug) => self.debug = debug,
+            GuiCommand::Register(_) => {}
+            GuiCommand::Board => self.show_board(writer)?,
+        }
+
+        writer.flush()?;
+        Ok(true)
+    }
+
+    /// Read commands from the GUI until it quits or closes the connection.  Lines that aren't
+    /// commands are ignored, as the spec requires.  If the connection closes during a search,
+    /// the search is allowed to finish.
+    pub fn main_loop<R, W>(&mut self, reader: R, mut writer: W) -> Result<(), Error>
+    where
+        R: BufRead + Send + 'static,
+        W: Write,
+    {
+        let (tx, rx) = channel();
+        spawn(move || {
+            for line in reader.lines() {
+                match line {
+                    Ok(line) => {
+                        if tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        loop {
+            let line = if self.is_searching() {
+                self.poll_search(&mut writer)?;
+                rx.recv_timeout(POLL_INTERVAL)
+            } else {
+                rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            };
+
+            match line {
+                Ok(line) => match GuiCommand::from_str(&line) {
+                    Ok(command) => {
+                        if !self.handle(command, &mut writer)? {
+                            break;
+                        }
+                    }
+                    // Other lines we can't read are ignored, as the spec asks, but a `position`
+                    // such as a Chess960 start the chess crate can't castle in mustn't leave the
+                    // last position to be searched.
+                    Err(error) if line.split_whitespace().next() == Some("position") => {
+                        self.reject_position(format!("invalid position: {}", error), &mut writer)?;
+                    }
+                    Err(_) => {}
                 },
-                GuiCommand::Go(go) => {
-                    timer = Timer::new_from_go(go, board.side_to_move());
-                    self.id_search(board, 1000, moves_made, writer);
-                },
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Some(ref search) = self.search {
+                        if self.hold_best_move {
+                            search.stop();
+                        }
+                    }
+                    self.finish_search(true, &mut writer)?;
+                    break;
+                }
             }
         }
+        Ok(())
+    }
+}
+
+// The board as text, white at the bottom, one line for each rank and each border.
+fn diagram(board: &Board) -> Vec<String> {
+    let border = format!(" {}+", "+---".repeat(8));
+    let mut lines = vec![border.clone()];
+    for rank in ALL_RANKS.iter().rev() {
+        let squares = ALL_FILES
+            .iter()
+            .map(|&file| {
+                let square = Square::make_square(*rank, file);
+                match (board.piece_on(square), board.color_on(square)) {
+                    (Some(piece), Some(color)) => piece.to_string(color),
+                    _ => " ".to_string(),
+                }
+            })
+            .collect::<Vec<String>>();
+        lines.push(format!(" | {} | {}", squares.join(" | "), rank.to_index() + 1));
+        lines.push(border.clone());
     }
+    lines.push("   a   b   c   d   e   f   g   h".to_string());
+    lines
+}
+
+#[cfg(test)]
+use engine::option_type::OptionType;
+#[cfg(test)]
+use engine::engine_option::EngineOption;
+#[cfg(test)]
+use chess::ChessMove;
+#[cfg(test)]
+use std::io::Cursor;
+#[cfg(test)]
+use std::sync::atomic::Ordering;
+#[cfg(test)]
+use std::thread::sleep;
+
+// Stands in for a real search, doing only what a test sets it up to: it sends `infos`, each in
+// two writes, waits to be stopped if it has a stop flag, and finds `pv`.  With `report` on, it
+// says what it was told by the engine before searching.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct TestSearch {
+    pv: Vec<ChessMove>,
+    infos: Vec<String>,
+    stop: Option<Arc<AtomicBool>>,
+    report: bool,
+    analyse: bool,
+    resets: u32,
+    pawn: i32,
+}
+
+#[cfg(test)]
+impl TestSearch {
+    fn finding(moves: &[(Square, Square)]) -> TestSearch {
+        TestSearch {
+            pv: moves.iter().map(|&(from, to)| ChessMove::new(from, to, None)).collect(),
+            ..TestSearch::default()
+        }
+    }
+
+    fn reporting() -> TestSearch {
+        TestSearch {
+            report: true,
+            ..TestSearch::default()
+        }
+    }
+}
 
-    fn monitor_stdin(mut reader: BufReader, sender: Sender<GuiCommand>) {
-        let mut handle = reader.lock();
-        for line in handle.lines() {
-            match GuiCommand::parse_str(line + "\n") {
-                Ok(command) => sender.send(command).expect("Unable to send the GuiCommand to the main thread."),
-                Err(_) => {}, // The standard states you should do nothing here.  Should we still do something?
+#[cfg(test)]
+impl Resettable for TestSearch {
+    fn reset(&mut self) {
+        self.resets += 1;
+    }
+}
+
+#[cfg(test)]
+impl IterativeDeepening for TestSearch {
+    fn id_search<W: Write>(&mut self, _: Board, _: i16, _: u16, mut writer: W) -> Pv {
+        if self.report {
+            writeln!(
+                writer,
+                "info string analyse {} resets {} pawn {}",
+                self.analyse, self.resets, self.pawn
+            )
+            .unwrap();
+        }
+        for info in self.infos.iter() {
+            let (start, end) = info.split_at(info.len() / 2);
+            write!(writer, "{}", start).unwrap();
+            writeln!(writer, "{}", end).unwrap();
+        }
+        if let Some(ref stop) = self.stop {
+            while !stop.load(Ordering::Relaxed) {
+                sleep(Duration::from_millis(1));
             }
         }
+        let mut pv = Pv::new();
+        for m in self.pv.iter() {
+            pv.push(*m);
+        }
+        pv
+    }
+
+    fn set_analyse_mode(&mut self, analyse: bool) {
+        self.analyse = analyse;
+    }
+
+    fn set_eval_params(&mut self, params: &EvalParams) {
+        params.apply("material.pawn", &mut self.pawn);
+    }
+
+    fn get_stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        self.stop.clone()
     }
 }
 
+#[cfg(test)]
+fn engine_with(search: TestSearch, options: Vec<EngineOption>) -> EngineBase<TestSearch> {
+    EngineBase::new(Id::name("Test"), EngineOptions::new(options), search)
+}
+
+#[cfg(test)]
+fn check_option(name: &str) -> EngineOption {
+    EngineOption::new(name.to_string(), OptionType::Check(false))
+}
+
+// Never finds anything; enough to drive the protocol.
+#[cfg(test)]
+pub(crate) fn test_engine() -> EngineBase<TestSearch> {
+    engine_with(
+        TestSearch::default(),
+        vec![EngineOption::new("Hash".to_string(), OptionType::Spin(16, 1, 1024))],
+    )
+}
+
+// Feed `engine` a UCI script, returning what it sent back.  Any search it started is over by
+// the time it returns.
+#[cfg(test)]
+fn run(engine: &mut EngineBase<TestSearch>, input: &str) -> String {
+    let mut output = vec![];
+    engine.main_loop(Cursor::new(input.to_string()), &mut output).unwrap();
+    assert!(!engine.is_searching());
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_engine_base_handshake() {
+    let mut engine = test_engine();
+    assert_eq!(
+        run(&mut engine, "uci\nisready\n"),
+        "id name Test\noption name Hash type spin default 16 min 1 max 1024\nuciok\nreadyok\n"
+    );
+}
+
+#[test]
+fn test_engine_base_setoption_position_and_quit() {
+    let mut engine = test_engine();
+    let output = run(
+        &mut engine,
+        "setoption name Hash value 64\nnot a command\nposition startpos moves e2e4\nquit\nisready\n",
+    );
 
+    assert_eq!(output, "");
+    assert_eq!(engine.get_options().get_spin("Hash"), 64);
+    assert_eq!(
+        engine.get_board(),
+        Board::from_str("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap()
+    );
+}
+
+#[test]
+fn test_engine_base_option_diagnostics() {
+    let input = "setoption name Hash value 4096\nsetoption name Contempt value 10\n";
+    assert_eq!(run(&mut test_engine(), input), "");
+
+    let mut engine = test_engine().diagnostics(OptionDiagnostics::Info);
+    assert_eq!(
+        run(&mut engine, &format!("{}debug on\nsetoption name Hash value 64\n", input)),
+        "info string invalid value 4096 for option Hash\n\
+         info string unknown option Contempt\n\
+         info string option Hash set to 64\n"
+    );
+    assert_eq!(engine.get_options().get_spin("Hash"), 64);
+
+    let mut engine = test_engine().diagnostics(OptionDiagnostics::Strict);
+    let mut output = vec![];
+    let result = engine.main_loop(Cursor::new(format!("{}isready\n", input)), &mut output);
+    assert_eq!(result, Err(Error::CommandError));
+    assert_eq!(output, b"info string invalid value 4096 for option Hash\n");
+}
+
+#[test]
+fn test_engine_base_show_board() {
+    let mut engine = test_engine();
+    let output = run(&mut engine, "position startpos moves e2e4 g8f6\nd\n");
+    let lines = output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 21);
+    assert_eq!(lines[0], "info string  +---+---+---+---+---+---+---+---+");
+    assert_eq!(lines[1], "info string  | r | n | b | q | k | b |   | r | 8");
+    assert_eq!(lines[9], "info string  |   |   |   |   | P |   |   |   | 4");
+    assert_eq!(lines[17], "info string    a   b   c   d   e   f   g   h");
+    assert_eq!(
+        lines[18],
+        "info string Fen: rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2"
+    );
+    assert_eq!(
+        lines[19],
+        format!("info string Key: {:016x}", zobrist::hash(&engine.get_board()))
+    );
+    assert_eq!(lines[20], "info string Eval: none");
+}
+
+#[test]
+fn test_engine_base_go_without_moves() {
+    let mut engine = test_engine();
+    assert_eq!(run(&mut engine, "go depth 1\n"), "bestmove 0000\n");
+}
+
+#[test]
+fn test_engine_base_go_mate() {
+    let mut engine = test_engine();
+    let output = run(&mut engine, "position fen 6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1\ngo mate 2\n");
+
+    assert_eq!(output, "info depth 1 score mate 1 nodes 1 pv d1d8\nbestmove d1d8\n");
+}
+
+#[test]
+fn test_engine_base_ignores_illegal_position() {
+    let mut engine = test_engine();
+    run(&mut engine, "position startpos moves e2e4\nposition startpos moves e2e5\n");
+
+    assert_eq!(
+        engine.get_board(),
+        Board::from_str("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap()
+    );
+}
+
+// Thinks about e2e4 until it is told to stop.
+#[cfg(test)]
+fn run_stop_search(input: &str) -> String {
+    let search = TestSearch {
+        infos: vec!["info depth 1".to_string()],
+        stop: Some(Arc::new(AtomicBool::new(false))),
+        ..TestSearch::finding(&[(Square::E2, Square::E4)])
+    };
+    run(&mut engine_with(search, vec![]), input)
+}
+
+#[test]
+fn test_engine_base_stop_mid_search() {
+    // `readyok` is answered during the search, so it may come before or after the `info`.
+    let output = run_stop_search("go infinite\nisready\nstop\n");
+    let mut lines = output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.pop(), Some("bestmove e2e4"));
+    lines.sort();
+    assert_eq!(lines, vec!["info depth 1", "readyok"]);
+}
+
+#[test]
+fn test_engine_base_quit_mid_search() {
+    assert_eq!(run_stop_search("go infinite\nquit\nisready\n"), "info depth 1\n");
+}
+
+#[test]
+fn test_engine_base_hard_time_limit() {
+    // The search never stops by itself, so only the watchdog can end these.
+    assert_eq!(run_stop_search("go movetime 20\n"), "info depth 1\nbestmove e2e4\n");
+    assert_eq!(
+        run_stop_search("debug on\ngo wtime 100 btime 100\n"),
+        "info depth 1\ninfo string hard time limit reached\nbestmove e2e4\n"
+    );
+    assert_eq!(
+        run_stop_search("go ponder e7e5 wtime 100 btime 100\nponderhit\n"),
+        "info depth 1\nbestmove e2e4\n"
+    );
+}
+
+#[test]
+fn test_engine_base_search_outlives_input() {
+    // The GUI went away during an infinite search, so it is stopped and the move is sent anyway.
+    assert_eq!(run_stop_search("go infinite\n"), "info depth 1\nbestmove e2e4\n");
+}
+
+#[test]
+fn test_engine_base_analyse_mode() {
+    let mut engine = engine_with(TestSearch::reporting(), vec![check_option(UCI_ANALYSE_MODE)]);
+    assert_eq!(
+        run(&mut engine, "go depth 1\n"),
+        "info string analyse false resets 0 pawn 0\nbestmove 0000\n"
+    );
+    assert_eq!(
+        run(&mut engine, "setoption name UCI_AnalyseMode value true\ngo depth 1\n"),
+        "info string analyse true resets 0 pawn 0\nbestmove 0000\n"
+    );
+}
+
+#[test]
+fn test_engine_base_ucinewgame_resets() {
+    let mut engine = engine_with(TestSearch::reporting(), vec![]);
+    run(&mut engine, "position startpos moves e2e4\nucinewgame\nucinewgame\n");
+
+    assert_eq!(
+        run(&mut engine, "go depth 1\n"),
+        "info string analyse false resets 2 pawn 0\nbestmove 0000\n"
+    );
+    assert_eq!(engine.get_board(), Board::default());
+}
+
+#[test]
+fn test_engine_base_ponder_move() {
+    let search = TestSearch::finding(&[(Square::E2, Square::E4), (Square::E7, Square::E5)]);
+    let mut engine = engine_with(search, vec![check_option(PONDER)]);
+    assert_eq!(run(&mut engine, "go depth 1\n"), "bestmove e2e4\n");
+    assert_eq!(
+        run(&mut engine, "setoption name Ponder value true\ngo depth 1\n"),
+        "bestmove e2e4 ponder e7e5\n"
+    );
+    // The second move isn't legal here, so there's nothing to ponder on.
+    assert_eq!(
+        run(&mut engine, "position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\ngo depth 1\n"),
+        "bestmove e2e4\n"
+    );
+}
+
+#[test]
+fn test_engine_base_info_throttle() {
+    // Reports ten times at each of two depths.
+    let search = TestSearch {
+        infos: (1..=2)
+            .flat_map(|depth| (1..=10).map(move |nodes| format!("info depth {} nodes {}", depth, nodes)))
+            .collect(),
+        ..TestSearch::finding(&[(Square::E2, Square::E4)])
+    };
+    let interval = EngineOption::new(INFO_INTERVAL.to_string(), OptionType::Spin(0, 0, 10_000));
+    let mut engine = engine_with(search, vec![interval])
+        .info_throttle(InfoThrottle::new(Duration::from_secs(3600)));
+    assert_eq!(
+        run(&mut engine, "setoption name InfoInterval value 10000\ngo depth 2\n"),
+        "info depth 1 nodes 1\ninfo depth 2 nodes 1\ninfo depth 2 nodes 10\nbestmove e2e4\n"
+    );
+
+    // Nothing is held back with the option at zero.
+    let output = run(&mut engine, "setoption name InfoInterval value 0\ngo depth 2\n");
+    assert_eq!(output.lines().count(), 21);
+}
+
+#[test]
+fn test_engine_base_chess960_castling() {
+    let search = TestSearch::finding(&[(Square::E1, Square::G1)]);
+    let mut engine = engine_with(search, vec![check_option(UCI_CHESS960)]);
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+    assert_eq!(
+        run(&mut engine, &format!("position fen {} moves e8c8\ngo depth 1\n", fen)),
+        "bestmove e1g1\n"
+    );
+    assert_eq!(
+        run(
+            &mut engine,
+            &format!(
+                "setoption name UCI_Chess960 value true\nposition fen {} moves e8a8\ngo depth 1\n",
+                fen
+            )
+        ),
+        "bestmove e1h1\n"
+    );
+    assert_eq!(
+        engine.get_board(),
+        Board::from_str("2kr3r/8/8/8/8/8/8/R3K2R w KQ - 1 2").unwrap()
+    );
+}
+
+#[test]
+fn test_engine_base_rejects_positions_it_cant_set_up() {
+    let search = TestSearch::finding(&[(Square::E2, Square::E4)]);
+    let mut engine = engine_with(search, vec![check_option(UCI_CHESS960)]);
+    let output = run(
+        &mut engine,
+        "setoption name UCI_Chess960 value true\n\
+         position fen bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1\n\
+         go depth 1\n",
+    );
+    let lines = output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("info string invalid position: "));
+    assert!(lines[0].contains("Chess960 castling the chess crate can't play"));
+    assert!(lines[1].starts_with("info string no position to search: invalid position: "));
+    assert_eq!(lines[2], "bestmove 0000");
+
+    // An illegal move is refused the same way.
+    let output = run(&mut engine, "position startpos moves e2e5\ngo depth 1\n");
+    assert!(output.starts_with("info string invalid position: "));
+    assert!(output.ends_with("bestmove 0000\n"));
+
+    // Until a position that can be set up arrives.
+    assert_eq!(run(&mut engine, "position startpos\ngo depth 1\n"), "bestmove e2e4\n");
+}
+
+#[test]
+fn test_engine_base_params_file() {
+    let search = TestSearch {
+        pawn: 100,
+        ..TestSearch::reporting()
+    };
+    let params_file = EngineOption::new(PARAMS_FILE.to_string(), OptionType::Str("<empty>".to_string()));
+    let mut engine = engine_with(search, vec![params_file]).diagnostics(OptionDiagnostics::Info);
+    let path = std::env::temp_dir()
+        .join(format!("chess_uci_engine_params_{}.toml", std::process::id()));
+    std::fs::write(&path, "[material]\npawn = 90\n").unwrap();
+    let set_params = format!("setoption name ParamsFile value {}\ngo depth 1\n", path.display());
+    assert_eq!(
+        run(&mut engine, "go depth 1\n"),
+        "info string analyse false resets 0 pawn 100\nbestmove 0000\n"
+    );
+    assert_eq!(
+        run(&mut engine, &set_params),
+        "info string analyse false resets 0 pawn 90\nbestmove 0000\n"
+    );
+
+    // A file that can't be read keeps the old weights.
+    std::fs::remove_file(&path).unwrap();
+    let output = run(&mut engine, &set_params);
+    assert!(output.starts_with("info string could not load evaluation parameters from "));
+    assert!(output.ends_with("info string analyse false resets 0 pawn 90\nbestmove 0000\n"));
+}
@@ -1,14 +1,55 @@
 use crate::error::Error;
 use engine::engine_option::EngineOption;
 use engine::option_type::OptionType;
+use json::Json;
 use std::collections::HashMap;
 use std::fmt;
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
+
+use super::option_schema::OptionsBuilder;
+
+/// The standard option a GUI sets while the user is analysing rather than playing a game.
+pub const UCI_ANALYSE_MODE: &str = "UCI_AnalyseMode";
+/// The standard option that tells the engine it may ponder, so it should name a ponder move.
+pub const PONDER: &str = "Ponder";
+/// The standard option that tells the engine the game is Chess960, so castling moves are
+/// written as the king taking its own rook.
+pub const UCI_CHESS960: &str = "UCI_Chess960";
+/// The option, as Stockfish names it, that makes the engine count time in nodes per millisecond.
+pub const NODES_TIME: &str = "nodestime";
+/// The option, as Stockfish names it, for the milliseconds lost to lag on each move.
+pub const MOVE_OVERHEAD: &str = "Move Overhead";
+/// A spin option, in milliseconds, for the least time between a search's `info` lines.  See
+/// `InfoThrottle`.
+pub const INFO_INTERVAL: &str = "InfoInterval";
+/// A string option naming a file that each search's tree is written to while `debug` is on,
+/// in graphviz's dot language for a `.dot` file and as JSON otherwise.
+pub const SEARCH_TRACE: &str = "SearchTrace";
+/// A string option naming a file of evaluation weights, JSON or TOML, that the engine loads
+/// whenever it is set, so a tuner can change them between games.  See `EvalParams`.
+pub const PARAMS_FILE: &str = "ParamsFile";
 
 #[derive(Clone, Default)]
 pub struct EngineOptions {
     options: HashMap<String, OptionType>,
+    // Each option as it was created, to tell which ones have been changed.
+    defaults: HashMap<String, OptionType>,
     buttons: HashMap<String, fn() -> ()>,
+    // The names in the order the options were created, which is the order they are advertised.
+    order: Vec<String>,
+}
+
+// The value `setoption` takes for an option, or `None` for a button.
+fn option_value(option_type: &OptionType) -> Option<String> {
+    match option_type {
+        OptionType::Check(x) => Some(x.to_string()),
+        OptionType::Spin(x, _, _) => Some(x.to_string()),
+        OptionType::Combo(x, _) | OptionType::Str(x) => Some(x.clone()),
+        OptionType::Button => None,
+    }
 }
 
 impl EngineOptions {
@@ -19,44 +60,193 @@ impl EngineOptions {
         let mut e = EngineOptions::default();
 
         for x in options.into_iter() {
-            e.options
-                .insert(x.get_name().clone(), x.get_option_type().clone());
+            e.insert(x.get_name().clone(), x.get_option_type().clone());
         }
 
         e
     }
 
+    /// Declare the engine's options with typed `TypedOption`s, rather than one `create_*` call
+    /// and one `get_*` call per option.
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder::new()
+    }
+
+    pub(crate) fn insert(&mut self, name: String, option_type: OptionType) {
+        if !self.options.contains_key(&name) {
+            self.order.push(name.clone());
+        }
+        self.defaults.insert(name.clone(), option_type.clone());
+        self.options.insert(name, option_type);
+    }
+
     fn get_engine_options(&self) -> Vec<EngineOption> {
         let mut result = vec![];
-        for (name, option_type) in &self.options {
-            result.push(EngineOption::new(name.clone(), option_type.clone()));
+        for name in &self.order {
+            result.push(EngineOption::new(name.clone(), self.options[name].clone()));
         }
         result
     }
 
     pub fn create_check(&mut self, name: String, default: bool) {
-        self.options.insert(name, OptionType::Check(default));
+        self.insert(name, OptionType::Check(default));
     }
 
     pub fn create_spin(&mut self, name: String, default: i64, min: i64, max: i64) {
-        self.options
-            .insert(name, OptionType::Spin(default, min, max));
+        self.insert(name, OptionType::Spin(default, min, max));
     }
 
     pub fn create_combo(&mut self, name: String, default: String, options: Vec<String>) {
-        self.options
-            .insert(name, OptionType::Combo(default, options));
+        self.insert(name, OptionType::Combo(default, options));
     }
 
     pub fn create_string(&mut self, name: String, default: String) {
-        self.options.insert(name, OptionType::Str(default));
+        self.insert(name, OptionType::Str(default));
     }
 
     pub fn create_button(&mut self, name: String, f: fn() -> ()) {
-        self.options.insert(name.clone(), OptionType::Button);
+        self.insert(name.clone(), OptionType::Button);
         self.buttons.insert(name, f);
     }
 
+    /// Apply a `setoption` from the GUI.  Fails if the option doesn't exist, or the value doesn't
+    /// fit its type.
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), Error> {
+        let updated = match (self.options.get(name), value) {
+            (Some(OptionType::Check(_)), Some("true")) => OptionType::Check(true),
+            (Some(OptionType::Check(_)), Some("false")) => OptionType::Check(false),
+            (Some(OptionType::Spin(_, min, max)), Some(v)) => match v.parse::<i64>() {
+                Ok(x) if x >= *min && x <= *max => OptionType::Spin(x, *min, *max),
+                _ => return Err(Error::CommandError),
+            },
+            (Some(OptionType::Combo(_, vars)), Some(v)) if vars.iter().any(|x| x == v) => {
+                OptionType::Combo(v.to_string(), vars.clone())
+            }
+            (Some(OptionType::Str(_)), v) => OptionType::Str(v.unwrap_or("").to_string()),
+            (Some(OptionType::Button), _) => {
+                if let Some(f) = self.buttons.get(name) {
+                    f();
+                }
+                return Ok(());
+            }
+            _ => return Err(Error::CommandError),
+        };
+
+        self.options.insert(name.to_string(), updated);
+        Ok(())
+    }
+
+    /// Every option that has been set away from its default, with its value, sorted by name.
+    pub fn get_changed(&self) -> Vec<(String, String)> {
+        let mut changed = self
+            .options
+            .iter()
+            .filter(|(name, option_type)| self.defaults.get(*name) != Some(option_type))
+            .filter_map(|(name, option_type)| option_value(option_type).map(|v| (name.clone(), v)))
+            .collect::<Vec<(String, String)>>();
+        changed.sort();
+        changed
+    }
+
+    /// Write the changed options to `path` as a JSON object of names and values, such as
+    /// `{"Hash": 128, "Ponder": true}`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let members = self
+            .get_changed()
+            .into_iter()
+            .map(|(name, value)| (name, Json::from_option_value(&value)))
+            .collect();
+        fs::write(path, format!("{}\n", Json::Object(members)))?;
+        Ok(())
+    }
+
+    /// Set the options saved in `path`.  Fails, leaving the options that were already set, if
+    /// one of them doesn't exist or doesn't fit its type.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let members = match Json::parse(&fs::read_to_string(path)?)? {
+            Json::Object(members) => members,
+            _ => return Err(Error::ConfigError("expected an object of options".to_string())),
+        };
+        for (name, value) in members {
+            let value = value
+                .to_option_value()
+                .ok_or_else(|| Error::ConfigError(format!("{} has no value", name)))?;
+            self.set_option(&name, Some(&value))?;
+        }
+        Ok(())
+    }
+
+    /// The option called `name`, with its current value.
+    pub fn get_option_type(&self, name: &str) -> Option<&OptionType> {
+        self.options.get(name)
+    }
+
+    /// Whether the engine offers an option called `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    /// Whether a check option is turned on.  Unlike `get_check`, an engine that doesn't offer
+    /// the option just has it turned off.
+    pub fn is_checked(&self, name: &str) -> bool {
+        match self.options.get(name) {
+            Some(OptionType::Check(x)) => *x,
+            _ => false,
+        }
+    }
+
+    pub fn is_analyse_mode(&self) -> bool {
+        self.is_checked(UCI_ANALYSE_MODE)
+    }
+
+    pub fn is_ponder_enabled(&self) -> bool {
+        self.is_checked(PONDER)
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.is_checked(UCI_CHESS960)
+    }
+
+    /// The `InfoInterval` spin option, or `None` if the engine doesn't offer it.
+    pub fn get_info_interval(&self) -> Option<Duration> {
+        match self.options.get(INFO_INTERVAL) {
+            Some(OptionType::Spin(x, _, _)) => Some(Duration::from_millis((*x).max(0) as u64)),
+            _ => None,
+        }
+    }
+
+    /// The `nodestime` spin option, or zero if the engine doesn't offer it.
+    pub fn get_nodes_time(&self) -> u64 {
+        match self.options.get(NODES_TIME) {
+            Some(OptionType::Spin(x, _, _)) => (*x).max(0) as u64,
+            _ => 0,
+        }
+    }
+
+    /// The `Move Overhead` spin option, or zero if the engine doesn't offer it.
+    pub fn get_move_overhead(&self) -> Duration {
+        match self.options.get(MOVE_OVERHEAD) {
+            Some(OptionType::Spin(x, _, _)) => Duration::from_millis((*x).max(0) as u64),
+            _ => Duration::new(0, 0),
+        }
+    }
+
+    /// The file to trace searches to, if the engine offers `SearchTrace` and it is set.
+    pub fn get_search_trace(&self) -> Option<String> {
+        match self.options.get(SEARCH_TRACE) {
+            Some(OptionType::Str(x)) if !x.is_empty() && x != "<empty>" => Some(x.clone()),
+            _ => None,
+        }
+    }
+
+    /// The file of evaluation weights, if the engine offers `ParamsFile` and it is set.
+    pub fn get_params_file(&self) -> Option<String> {
+        match self.options.get(PARAMS_FILE) {
+            Some(OptionType::Str(x)) if !x.is_empty() && x != "<empty>" => Some(x.clone()),
+            _ => None,
+        }
+    }
+
     pub fn get_check(&self, name: &str) -> bool {
         match self.options.get(name) {
             Some(OptionType::Check(x)) => return *x,
@@ -101,7 +291,7 @@ impl FromStr for EngineOptions {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let options: Result<Vec<EngineOption>, Error> = s
             .lines()
-            .map(|v| EngineOption::from_str(&(v.to_string() + "\n")))
+            .map(EngineOption::from_str)
             .collect();
         Ok(Self::new(options?))
     }
@@ -142,13 +332,66 @@ fn read_defaults() {
     assert_eq!(eo.get_spin("MultiPV"), 1);
     assert_eq!(eo.get_spin("Skill Level"), 20);
     assert_eq!(eo.get_spin("Move Overhead"), 30);
+    assert_eq!(eo.get_move_overhead(), Duration::from_millis(30));
     assert_eq!(eo.get_spin("Minimum Thinking Time"), 20);
     assert_eq!(eo.get_spin("Slow Mover"), 84);
     assert_eq!(eo.get_spin("nodestime"), 0);
+    assert_eq!(eo.get_nodes_time(), 0);
     assert_eq!(eo.get_check("UCI_Chess960"), false);
+    assert!(!eo.is_chess960());
     assert_eq!(eo.get_check("UCI_AnalyseMode"), false);
     assert_eq!(eo.get_string("SyzygyPath"), "<empty>");
     assert_eq!(eo.get_spin("SyzygyProbeDepth"), 1);
     assert_eq!(eo.get_check("Syzygy50MoveRule"), true);
     assert_eq!(eo.get_spin("SyzygyProbeLimit"), 7);
 }
+
+#[test]
+fn set_option() {
+    let mut eo = read_stockfish().unwrap();
+    assert_eq!(eo.set_option("Hash", Some("128")), Ok(()));
+    assert_eq!(eo.get_spin("Hash"), 128);
+    assert_eq!(eo.set_option("nodestime", Some("500")), Ok(()));
+    assert_eq!(eo.get_nodes_time(), 500);
+    assert_eq!(eo.set_option("Hash", Some("0")), Err(Error::CommandError));
+    assert_eq!(eo.set_option("Ponder", Some("true")), Ok(()));
+    assert!(eo.get_check("Ponder"));
+    assert_eq!(eo.set_option("Analysis Contempt", Some("White")), Ok(()));
+    assert_eq!(eo.get_combo("Analysis Contempt"), "White");
+    assert_eq!(eo.set_option("Analysis Contempt", Some("Green")), Err(Error::CommandError));
+    assert_eq!(eo.set_option("SyzygyPath", Some("/tb")), Ok(()));
+    assert_eq!(eo.get_string("SyzygyPath"), "/tb");
+    assert_eq!(eo.set_option("Clear Hash", None), Ok(()));
+    assert_eq!(eo.set_option("No Such Option", Some("1")), Err(Error::CommandError));
+}
+
+#[test]
+fn save_and_load_changed_options() {
+    let mut eo = read_stockfish().unwrap();
+    assert!(eo.get_changed().is_empty());
+    eo.set_option("Hash", Some("128")).unwrap();
+    eo.set_option("Ponder", Some("true")).unwrap();
+    eo.set_option("SyzygyPath", Some("/tb")).unwrap();
+    eo.set_option("Threads", Some("1")).unwrap();
+    eo.set_option("Clear Hash", None).unwrap();
+    assert_eq!(
+        eo.get_changed(),
+        vec![
+            ("Hash".to_string(), "128".to_string()),
+            ("Ponder".to_string(), "true".to_string()),
+            ("SyzygyPath".to_string(), "/tb".to_string()),
+        ]
+    );
+
+    let path = std::env::temp_dir().join(format!("chess_uci_options_{}.json", std::process::id()));
+    eo.save(&path).unwrap();
+    let mut loaded = read_stockfish().unwrap();
+    loaded.load(&path).unwrap();
+    assert_eq!(loaded.get_changed(), eo.get_changed());
+    assert_eq!(loaded.get_spin("Hash"), 128);
+
+    fs::write(&path, "{\"Hash\": 0}").unwrap();
+    assert_eq!(loaded.load(&path), Err(Error::CommandError));
+    fs::remove_file(&path).unwrap();
+    assert!(loaded.load(&path).is_err());
+}
@@ -21,9 +21,18 @@ pub trait Eval:
 {
     fn max_supported_mates() -> Self;
     fn from_ply(ply: i16) -> Self;
+    /// A mate for `color` in `ply` plies.  A ply below zero counts as zero, the closest mate
+    /// there is.
     fn new_mate(ply: i16, color: Color) -> Self;
     fn depth_to_mate(&self) -> Option<i64>;
+    /// A mate `amount` plies further away, but no closer than zero plies.  Anything else is left
+    /// as it is.
     fn add_depth(&self, amount: i16) -> Self;
+    /// A score counted from the root, as a transposition table keeps it for a node `ply` moves
+    /// in: a mate is counted from the node instead, so it holds wherever the node is reached.
+    fn to_tt(&self, ply: i16) -> Self;
+    /// A score from a transposition table, counted from the root again for a node `ply` moves in.
+    fn to_search(&self, ply: i16) -> Self;
     fn min_eval() -> Self;
     fn max_eval() -> Self;
     fn null() -> Self;
@@ -81,24 +90,32 @@ where
     }
 
     fn new_mate(ply: i16, color: Color) -> Self {
+        let ply = Self::from_ply(ply.max(0));
         match color {
-            Color::White => Self::max_eval() - Self::from_ply(ply) - Self::one(),
-            Color::Black => Self::min_eval() + Self::from_ply(ply) + Self::one(),
+            Color::White => Self::max_eval() - ply - Self::one(),
+            Color::Black => Self::min_eval() + ply + Self::one(),
         }
     }
 
     fn add_depth(&self, amount: i16) -> Self {
-        if let Some(depth) = self.depth_to_mate() {
-            if depth < 0 {
-                Self::new_mate(((-depth) as i16) + amount, Color::Black)
-            } else {
-                Self::new_mate((depth as i16) + amount, Color::White)
+        // A mate in zero has no sign to tell whose it is, so the score's own sign is used.
+        match self.depth_to_mate() {
+            Some(depth) if *self > Self::zero() => {
+                Self::new_mate((depth as i16).saturating_add(amount), Color::White)
             }
-        } else {
-            *self
+            Some(depth) => Self::new_mate(((-depth) as i16).saturating_add(amount), Color::Black),
+            None => *self,
         }
     }
 
+    fn to_tt(&self, ply: i16) -> Self {
+        shift_mate(*self, -(ply as i64))
+    }
+
+    fn to_search(&self, ply: i16) -> Self {
+        shift_mate(*self, ply as i64)
+    }
+
     fn min_eval() -> Self {
         -T::max_value()
     }
@@ -140,6 +157,24 @@ where
     }
 }
 
+// Move a mate `amount` plies further away.  Unlike `add_depth`, the distance is kept between
+// zero and the longest mate supported, as a bound shifted past either end would stop being a
+// mate.
+pub(crate) fn shift_mate<T: Eval>(eval: T, amount: i64) -> T {
+    match eval.depth_to_mate() {
+        Some(depth) => {
+            let color = if eval > T::zero() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let most = T::max_supported_mates().to_i64().unwrap_or(0) - 1;
+            T::new_mate((depth.abs() + amount).clamp(0, most) as i16, color)
+        }
+        None => eval,
+    }
+}
+
 #[cfg(test)]
 fn test_mates<T: Eval>() {
     assert_eq!(
@@ -162,6 +197,17 @@ fn test_mates<T: Eval>() {
         T::new_mate(10, Color::White)
     );
 
+    // Mates in zero keep their side, and don't get any closer.
+    assert_eq!(
+        T::new_mate(0, Color::Black).add_depth(1),
+        T::new_mate(1, Color::Black)
+    );
+    assert_eq!(
+        T::new_mate(0, Color::White).add_depth(-1),
+        T::new_mate(0, Color::White)
+    );
+    assert_eq!(T::new_mate(-3, Color::Black), T::new_mate(0, Color::Black));
+
     assert_eq!(T::min_value().add_depth(1), T::min_value());
     assert_eq!(T::max_value().add_depth(1), T::max_value());
 }
@@ -199,6 +245,45 @@ fn test_mates_i16() {
     test_mates::<i16>();
 }
 
+#[cfg(test)]
+fn test_tt_ply<E: Eval>() {
+    // Mate in 7 from the root, found 3 plies in, is mate in 4 from the node.
+    let mate = E::new_mate(7, Color::White);
+    assert_eq!(mate.to_tt(3), E::new_mate(4, Color::White));
+    assert_eq!(mate.to_tt(3).to_search(3), mate);
+    // Reached again 5 plies in, it is mate in 9 from the root.
+    assert_eq!(mate.to_tt(3).to_search(5), E::new_mate(9, Color::White));
+
+    let mated = E::new_mate(6, Color::Black);
+    assert_eq!(mated.to_tt(2), E::new_mate(4, Color::Black));
+    assert_eq!(mated.to_tt(2).to_search(2), mated);
+
+    let score = E::from(150).unwrap();
+    assert_eq!(score.to_tt(10), score);
+    assert_eq!(E::null().to_tt(10), E::null());
+    assert_eq!(E::max_eval().to_search(10), E::max_eval());
+
+    // A bound can't be shifted out of the mates.
+    assert_eq!(
+        E::new_mate(2, Color::White).to_tt(5),
+        E::new_mate(0, Color::White)
+    );
+    assert_eq!(
+        E::new_mate(795, Color::Black).to_search(10),
+        E::new_mate(799, Color::Black)
+    );
+}
+
+#[test]
+fn test_tt_ply_i16() {
+    test_tt_ply::<i16>();
+}
+
+#[test]
+fn test_tt_ply_i32() {
+    test_tt_ply::<i32>();
+}
+
 #[test]
 fn test_add_depth_i16() {
     test_add_depth::<i16>();
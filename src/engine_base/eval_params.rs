@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use error::Error;
+use json::Json;
+
+/// Evaluation weights by name, as read from a `ParamsFile`, for tuning an evaluator without
+/// rebuilding the engine.
+///
+/// Nested names are joined with dots, so the JSON `{"material": {"pawn": 100}}` and the TOML
+/// `[material]` table holding `pawn = 100` both give `material.pawn`.  The elements of an array
+/// are named by their index, as in `pawns.passed.3`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EvalParams {
+    params: HashMap<String, f64>,
+}
+
+impl EvalParams {
+    pub fn new() -> EvalParams {
+        EvalParams::default()
+    }
+
+    pub fn insert(&mut self, name: &str, value: f64) {
+        self.params.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.params.get(name).cloned()
+    }
+
+    /// The weight rounded to a whole number, as most evaluators keep them.
+    pub fn get_i32(&self, name: &str) -> Option<i32> {
+        self.get(name).map(|x| x.round() as i32)
+    }
+
+    /// Set `weight` from the parameter called `name`, if there is one.
+    pub fn apply(&self, name: &str, weight: &mut i32) {
+        if let Some(x) = self.get_i32(name) {
+            *weight = x;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// The names of the weights, sorted.
+    pub fn get_names(&self) -> Vec<&str> {
+        let mut names = self.params.keys().map(|k| k.as_str()).collect::<Vec<&str>>();
+        names.sort();
+        names
+    }
+
+    pub fn from_json(text: &str) -> Result<EvalParams, Error> {
+        let mut params = EvalParams::new();
+        match Json::parse(text)? {
+            json @ Json::Object(_) => params.insert_json("", &json)?,
+            _ => return Err(config_error("expected an object of parameters")),
+        }
+        Ok(params)
+    }
+
+    fn insert_json(&mut self, name: &str, json: &Json) -> Result<(), Error> {
+        let join = |key: &str| {
+            if name.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", name, key)
+            }
+        };
+        match json {
+            Json::Number(x) => self.insert(name, *x),
+            Json::Object(members) => {
+                for (key, value) in members {
+                    self.insert_json(&join(key), value)?;
+                }
+            }
+            Json::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    self.insert_json(&join(&i.to_string()), value)?;
+                }
+            }
+            _ => return Err(config_error(&format!("{} is not a number", name))),
+        }
+        Ok(())
+    }
+
+    /// Read the subset of TOML a file of weights needs: `name = value` lines, where the value is
+    /// a number or an array of numbers, under optional `[table]` headers, with `#` comments.
+    pub fn from_toml(text: &str) -> Result<EvalParams, Error> {
+        let mut params = EvalParams::new();
+        let mut table = String::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                table = line[1..line.len() - 1].trim().to_string();
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim().trim_matches('"');
+            let value = parts
+                .next()
+                .ok_or_else(|| config_error(&format!("expected name = value, got {}", line)))?
+                .trim();
+            let name = if table.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", table, key)
+            };
+            if value.starts_with('[') && value.ends_with(']') {
+                for (i, x) in value[1..value.len() - 1].split(',').enumerate() {
+                    params.insert(&format!("{}.{}", name, i), toml_number(&name, x.trim())?);
+                }
+            } else {
+                params.insert(&name, toml_number(&name, value)?);
+            }
+        }
+        Ok(params)
+    }
+
+    /// The weights as a flat JSON object, by name.
+    pub fn to_json(&self) -> String {
+        let members = self
+            .get_names()
+            .into_iter()
+            .map(|name| (name.to_string(), Json::Number(self.params[name])))
+            .collect();
+        format!("{}\n", Json::Object(members))
+    }
+
+    /// The weights as TOML, one `name = value` line each, which `from_toml` reads back.
+    pub fn to_toml(&self) -> String {
+        self.get_names()
+            .into_iter()
+            .map(|name| format!("{} = {}\n", name, self.params[name]))
+            .collect()
+    }
+
+    /// Read `path`, as TOML for a `.toml` file and as JSON otherwise.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<EvalParams, Error> {
+        let toml = path.as_ref().extension().and_then(|e| e.to_str()) == Some("toml");
+        let text = fs::read_to_string(path)?;
+        if toml {
+            EvalParams::from_toml(&text)
+        } else {
+            EvalParams::from_json(&text)
+        }
+    }
+
+    /// Write the weights to `path`, as TOML for a `.toml` file and as JSON otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let toml = path.as_ref().extension().and_then(|e| e.to_str()) == Some("toml");
+        let text = if toml { self.to_toml() } else { self.to_json() };
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+fn config_error(message: &str) -> Error {
+    Error::ConfigError(message.to_string())
+}
+
+fn toml_number(name: &str, value: &str) -> Result<f64, Error> {
+    value
+        .replace('_', "")
+        .parse::<f64>()
+        .map_err(|_| config_error(&format!("{} is not a number: {}", name, value)))
+}
+
+#[test]
+fn test_eval_params_json() {
+    let params = EvalParams::from_json(
+        "{\"material\": {\"pawn\": 95, \"knight\": 310.6}, \"passed\": [0, 5]}",
+    )
+    .unwrap();
+    assert_eq!(params.len(), 4);
+    assert_eq!(params.get("material.pawn"), Some(95.0));
+    assert_eq!(params.get_i32("material.knight"), Some(311));
+    assert_eq!(params.get("passed.1"), Some(5.0));
+    assert_eq!(params.get("material.queen"), None);
+
+    let mut weight = 900;
+    params.apply("material.queen", &mut weight);
+    assert_eq!(weight, 900);
+    params.apply("material.pawn", &mut weight);
+    assert_eq!(weight, 95);
+
+    assert!(EvalParams::from_json("[1, 2]").is_err());
+    assert!(EvalParams::from_json("{\"pawn\": \"many\"}").is_err());
+}
+
+#[test]
+fn test_eval_params_toml() {
+    let params = EvalParams::from_toml(
+        "# Tuned weights\n\
+         scale = 1_000\n\
+         \n\
+         [material]\n\
+         pawn = 95 # was 100\n\
+         \"knight\" = 310.5\n\
+         [pawns]\n\
+         passed = [0, 5, 10]\n",
+    )
+    .unwrap();
+    assert_eq!(params.get("scale"), Some(1000.0));
+    assert_eq!(params.get("material.pawn"), Some(95.0));
+    assert_eq!(params.get("material.knight"), Some(310.5));
+    assert_eq!(params.get("pawns.passed.2"), Some(10.0));
+    assert_eq!(params.len(), 6);
+
+    assert!(EvalParams::from_toml("pawn\n").is_err());
+    assert!(EvalParams::from_toml("pawn = lots\n").is_err());
+}
+
+#[test]
+fn test_eval_params_load() {
+    let dir = std::env::temp_dir();
+    let toml = dir.join(format!("chess_uci_params_{}.toml", std::process::id()));
+    fs::write(&toml, "[material]\npawn = 90\n").unwrap();
+    assert_eq!(
+        EvalParams::load(&toml).unwrap().get("material.pawn"),
+        Some(90.0)
+    );
+    let json = dir.join(format!("chess_uci_params_{}.json", std::process::id()));
+    fs::write(&json, "{\"material.pawn\": 80}").unwrap();
+    assert_eq!(
+        EvalParams::load(&json).unwrap().get("material.pawn"),
+        Some(80.0)
+    );
+    fs::remove_file(&toml).unwrap();
+    fs::remove_file(&json).unwrap();
+    assert!(EvalParams::load(&json).is_err());
+}
+
+#[test]
+fn test_eval_params_save() {
+    let mut params = EvalParams::new();
+    params.insert("pawns.passed.2", 10.0);
+    params.insert("material.pawn", 97.5);
+    assert_eq!(params.get_names(), vec!["material.pawn", "pawns.passed.2"]);
+    assert_eq!(params.to_toml(), "material.pawn = 97.5\npawns.passed.2 = 10\n");
+
+    let dir = std::env::temp_dir();
+    for extension in &["toml", "json"] {
+        let path = dir.join(format!("chess_uci_saved_params_{}.{}", std::process::id(), extension));
+        params.save(&path).unwrap();
+        assert_eq!(EvalParams::load(&path).unwrap(), params);
+        fs::remove_file(&path).unwrap();
+    }
+}
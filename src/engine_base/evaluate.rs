@@ -1,10 +1,32 @@
+use super::endgame::EndgameKnowledge;
 use super::eval::Eval;
+use super::eval_params::EvalParams;
+use super::pawn_table::{PawnEntry, PawnTable};
+use super::resettable::Resettable;
+use super::search_stats::SearchStats;
 use super::search_window::SearchParams;
-use chess::{Color, Piece};
+use super::zobrist;
+use chess::{
+    get_adjacent_files, get_file, BitBoard, Board, Color, Piece, Square, ALL_COLORS, ALL_FILES,
+    EMPTY,
+};
 use std::default::Default;
 
-pub trait Evaluate<E: Eval> {
+pub trait Evaluate<E: Eval>: Resettable {
     fn evaluate(&mut self, sp: &mut impl SearchParams<E>) -> E;
+
+    /// Add what the evaluator counted since the last call, such as pawn table hits, to `stats`.
+    /// Called by the searcher when a search ends.
+    fn record_stats(&mut self, _stats: &mut SearchStats) {}
+
+    /// Take new weights from `params`, such as a tuner's, keeping the weight of any it doesn't
+    /// name.  Evaluators without weights ignore this.
+    fn set_params(&mut self, _params: &EvalParams) {}
+
+    /// The weights `set_params` takes, with their values now, for a tuner to start from.
+    fn get_params(&self) -> EvalParams {
+        EvalParams::new()
+    }
 }
 
 pub struct DefaultEvaluate {
@@ -47,8 +69,30 @@ impl Evaluate<i32> for DefaultEvaluate {
             + self.rook * (white_rooks - black_rooks)
             + self.queen * (white_queens - black_queens)
     }
+
+    // The piece values, as `material.pawn` to `material.queen`.
+    fn set_params(&mut self, params: &EvalParams) {
+        params.apply("material.pawn", &mut self.pawn);
+        params.apply("material.knight", &mut self.knight);
+        params.apply("material.bishop", &mut self.bishop);
+        params.apply("material.rook", &mut self.rook);
+        params.apply("material.queen", &mut self.queen);
+    }
+
+    fn get_params(&self) -> EvalParams {
+        let mut params = EvalParams::new();
+        params.insert("material.pawn", self.pawn as f64);
+        params.insert("material.knight", self.knight as f64);
+        params.insert("material.bishop", self.bishop as f64);
+        params.insert("material.rook", self.rook as f64);
+        params.insert("material.queen", self.queen as f64);
+        params
+    }
 }
 
+// Only material is counted, so there's no cache to clear.
+impl Resettable for DefaultEvaluate {}
+
 impl Default for DefaultEvaluate {
     fn default() -> Self {
         DefaultEvaluate {
@@ -61,10 +105,200 @@ impl Default for DefaultEvaluate {
     }
 }
 
+/// Pawn table entries kept by `PawnStructureEvaluate::default`, about 400 KB.
+pub const DEFAULT_PAWN_TABLE_ENTRIES: usize = 16384;
+
+// The squares in front of a pawn of `color` on `square`, on its own and the adjacent files.
+fn front_span(color: Color, square: Square) -> BitBoard {
+    let rank = square.get_rank().to_index() as u32;
+    let ahead = match color {
+        Color::White => (!0u64).checked_shl(8 * (rank + 1)).unwrap_or(0),
+        Color::Black => (1u64 << (8 * rank)) - 1,
+    };
+    (get_file(square.get_file()) | get_adjacent_files(square.get_file())) & BitBoard::new(ahead)
+}
+
+/// Material, as `DefaultEvaluate` counts it, plus penalties for doubled and isolated pawns and a
+/// bonus for passed pawns that grows as they advance.  The pawn terms are cached in a
+/// `PawnTable`, whose hits are counted in `SearchStats`.
+pub struct PawnStructureEvaluate {
+    material: DefaultEvaluate,
+    table: PawnTable,
+    doubled: i32,
+    isolated: i32,
+    // By how many ranks the pawn has advanced, from 0 to 7.
+    passed: [i32; 8],
+}
+
+impl PawnStructureEvaluate {
+    /// The default weights, with a pawn table of `entries` entries.
+    pub fn new(entries: usize) -> PawnStructureEvaluate {
+        PawnStructureEvaluate {
+            material: DefaultEvaluate::default(),
+            table: PawnTable::new(entries),
+            doubled: 15,
+            isolated: 12,
+            passed: [0, 5, 10, 20, 35, 60, 100, 0],
+        }
+    }
+
+    pub fn get_pawn_table(&self) -> &PawnTable {
+        &self.table
+    }
+
+    /// The pawn structure of `board`, worked out without the table.
+    pub fn evaluate_pawns(&self, board: &Board) -> PawnEntry {
+        let pawns = *board.pieces(Piece::Pawn);
+        let mut score = 0;
+        let mut passed = EMPTY;
+        for &color in ALL_COLORS.iter() {
+            let sign = if color == Color::White { 1 } else { -1 };
+            let ours = pawns & *board.color_combined(color);
+            let theirs = pawns & *board.color_combined(!color);
+            for &file in ALL_FILES.iter() {
+                let count = (ours & get_file(file)).popcnt() as i32;
+                if count > 1 {
+                    score -= sign * self.doubled * (count - 1);
+                }
+            }
+            for square in ours {
+                let file = square.get_file();
+                if ours & get_adjacent_files(file) == EMPTY {
+                    score -= sign * self.isolated;
+                }
+                if theirs & front_span(color, square) == EMPTY
+                    && ours & get_file(file) & front_span(color, square) == EMPTY
+                {
+                    let advanced = match color {
+                        Color::White => square.get_rank().to_index(),
+                        Color::Black => 7 - square.get_rank().to_index(),
+                    };
+                    score += sign * self.passed[advanced];
+                    passed |= BitBoard::from_square(square);
+                }
+            }
+        }
+        PawnEntry::new(zobrist::pawn_hash(board), score, passed)
+    }
+
+    fn pawn_entry(&mut self, board: &Board) -> PawnEntry {
+        let key = zobrist::pawn_hash(board);
+        match self.table.probe(key) {
+            Some(entry) => entry,
+            None => {
+                let entry = self.evaluate_pawns(board);
+                self.table.store(entry);
+                entry
+            }
+        }
+    }
+}
+
+impl Evaluate<i32> for PawnStructureEvaluate {
+    fn evaluate(&mut self, sp: &mut impl SearchParams<i32>) -> i32 {
+        let pawns = self.pawn_entry(sp.board()).get_score();
+        self.material.evaluate(sp) + pawns
+    }
+
+    fn record_stats(&mut self, stats: &mut SearchStats) {
+        let (probes, hits) = self.table.take_stats();
+        stats.pawn_probes(probes, hits);
+    }
+
+    // The material weights, then `pawns.doubled`, `pawns.isolated` and `pawns.passed.0` to
+    // `pawns.passed.7`.  The pawn table holds scores from the old weights, so it is cleared.
+    fn set_params(&mut self, params: &EvalParams) {
+        self.material.set_params(params);
+        params.apply("pawns.doubled", &mut self.doubled);
+        params.apply("pawns.isolated", &mut self.isolated);
+        for (i, bonus) in self.passed.iter_mut().enumerate() {
+            params.apply(&format!("pawns.passed.{}", i), bonus);
+        }
+        self.table.clear();
+    }
+
+    // A pawn can't be passed on its first or last rank, so those bonuses are left out.
+    fn get_params(&self) -> EvalParams {
+        let mut params = self.material.get_params();
+        params.insert("pawns.doubled", self.doubled as f64);
+        params.insert("pawns.isolated", self.isolated as f64);
+        for (i, bonus) in self.passed.iter().enumerate().take(7).skip(1) {
+            params.insert(&format!("pawns.passed.{}", i), *bonus as f64);
+        }
+        params
+    }
+}
+
+impl Resettable for PawnStructureEvaluate {
+    fn reset(&mut self) {
+        self.table.clear();
+    }
+}
+
+impl Default for PawnStructureEvaluate {
+    fn default() -> Self {
+        PawnStructureEvaluate::new(DEFAULT_PAWN_TABLE_ENTRIES)
+    }
+}
+
+/// Another evaluator's score, corrected by `EndgameKnowledge` in the endings it knows.
+pub struct EndgameEvaluate<V: Evaluate<i32>> {
+    evaluator: V,
+    knowledge: EndgameKnowledge,
+}
+
+impl<V: Evaluate<i32>> EndgameEvaluate<V> {
+    pub fn new(evaluator: V, knowledge: EndgameKnowledge) -> EndgameEvaluate<V> {
+        EndgameEvaluate {
+            evaluator,
+            knowledge,
+        }
+    }
+
+    pub fn get_evaluator(&self) -> &V {
+        &self.evaluator
+    }
+
+    pub fn get_knowledge(&self) -> &EndgameKnowledge {
+        &self.knowledge
+    }
+}
+
+impl<V: Evaluate<i32>> Evaluate<i32> for EndgameEvaluate<V> {
+    fn evaluate(&mut self, sp: &mut impl SearchParams<i32>) -> i32 {
+        let score = self.evaluator.evaluate(sp);
+        self.knowledge.evaluate(sp.board(), score)
+    }
+
+    fn record_stats(&mut self, stats: &mut SearchStats) {
+        self.evaluator.record_stats(stats);
+    }
+
+    fn set_params(&mut self, params: &EvalParams) {
+        self.evaluator.set_params(params);
+    }
+
+    fn get_params(&self) -> EvalParams {
+        self.evaluator.get_params()
+    }
+}
+
+impl<V: Evaluate<i32>> Resettable for EndgameEvaluate<V> {
+    fn reset(&mut self) {
+        self.evaluator.reset();
+    }
+}
+
+impl<V: Evaluate<i32> + Default> Default for EndgameEvaluate<V> {
+    fn default() -> Self {
+        EndgameEvaluate::new(V::default(), EndgameKnowledge::default())
+    }
+}
+
 #[cfg(test)]
 use super::search_window::AlphaBetaSearchParams;
 #[cfg(test)]
-use chess::Board;
+use std::str::FromStr;
 
 #[test]
 fn should_be_equal() {
@@ -79,3 +313,61 @@ fn should_be_equal() {
         0
     );
 }
+
+#[test]
+fn test_pawn_structure() {
+    let evaluator = PawnStructureEvaluate::default();
+    assert_eq!(evaluator.evaluate_pawns(&Board::default()).get_score(), 0);
+
+    // White's pawns are both isolated, and the a-pawn is passed on the sixth rank.  Black's
+    // f-pawns are doubled.
+    let board = Board::from_str("4k3/5p2/P4p2/6p1/8/8/6P1/4K3 w - - 0 1").unwrap();
+    let entry = evaluator.evaluate_pawns(&board);
+    assert_eq!(entry.get_passed(), BitBoard::from_square(Square::A6));
+    assert_eq!(entry.get_score(), -12 - 12 + 60 + 15);
+}
+
+#[test]
+fn test_pawn_structure_cached() {
+    let mut evaluator = PawnStructureEvaluate::default();
+    let mut sp = AlphaBetaSearchParams::new(Board::default(), -100, 100, 0);
+    assert_eq!(evaluator.evaluate(&mut sp), 0);
+    assert_eq!(evaluator.evaluate(&mut sp), 0);
+
+    let mut stats = SearchStats::new();
+    evaluator.record_stats(&mut stats);
+    assert_eq!(stats.get_pawn_probes(), 2);
+    assert_eq!(stats.get_pawn_hits(), 1);
+}
+
+#[test]
+fn test_endgame_evaluate() {
+    let mut evaluator = EndgameEvaluate::<DefaultEvaluate>::default();
+    let board = Board::from_str("k7/8/8/8/8/8/P7/K7 w - - 0 1").unwrap();
+    assert_eq!(evaluator.evaluate(&mut AlphaBetaSearchParams::new(board, -100, 100, 0)), 0);
+    let board = Board::from_str("8/8/8/8/8/k7/6P1/6K1 w - - 0 1").unwrap();
+    assert!(evaluator.evaluate(&mut AlphaBetaSearchParams::new(board, -100, 100, 0)) > 1000);
+}
+
+#[test]
+fn test_set_params() {
+    let mut params = EvalParams::new();
+    params.insert("material.pawn", 90.0);
+    params.insert("pawns.isolated", 20.0);
+    params.insert("pawns.passed.5", 80.0);
+    let mut evaluator = EndgameEvaluate::<PawnStructureEvaluate>::default();
+    let board = Board::from_str("4k3/5p2/P4p2/6p1/8/8/6P1/4K3 w - - 0 1").unwrap();
+    let mut sp = AlphaBetaSearchParams::new(board, -100, 100, 0);
+    assert_eq!(evaluator.evaluate(&mut sp), -100 - 12 - 12 + 60 + 15);
+
+    // The cached pawn score is thrown away with the old weights.
+    evaluator.set_params(&params);
+    assert_eq!(evaluator.evaluate(&mut sp), -90 - 20 - 20 + 80 + 15);
+
+    let weights = evaluator.get_params();
+    assert_eq!(weights.len(), 5 + 2 + 6);
+    assert_eq!(weights.get("material.pawn"), Some(90.0));
+    assert_eq!(weights.get("material.rook"), Some(500.0));
+    assert_eq!(weights.get("pawns.passed.5"), Some(80.0));
+    assert_eq!(weights.get("pawns.passed.7"), None);
+}
@@ -0,0 +1,340 @@
+use chess::Color;
+use num_traits::{Bounded, Float, Num, NumCast, One, Signed, ToPrimitive, Zero};
+use std::fmt::Debug;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign,
+};
+
+use super::eval::{shift_mate, Eval};
+
+/// The best score a `FloatEval` can hold, 2^23, below which every whole number is exact in an
+/// `f32`, so the mates just under it are too.
+pub const FLOAT_EVAL_MAX: f64 = 8_388_608.0;
+
+/// A floating point score, for evaluations such as a neural network's value head, which the
+/// blanket `Eval` implementation for integers can't take.
+///
+/// The number line is the integers' one (see `eval.rs`), with `FLOAT_EVAL_MAX` in place of
+/// `max_value()`:
+///
+/// -FLOAT_EVAL_MAX - 1 == null evaluation,
+/// -FLOAT_EVAL_MAX == -inf,
+/// -FLOAT_EVAL_MAX + 1 == Black mates in 0
+/// ...
+/// FLOAT_EVAL_MAX - 1 == White mates in 0
+/// FLOAT_EVAL_MAX == inf
+///
+/// Scores are in centipawns, as the searcher's margins and the UCI `score cp` expect, so a value
+/// head giving a win probability or a score in [-1, 1] should be scaled before it is wrapped.
+/// Scores further out than a mate in 800 are read as mates, and NaN is not a score at all.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct FloatEval<F: Float + Debug>(F);
+
+impl<F: Float + Debug> FloatEval<F> {
+    pub fn new(value: F) -> FloatEval<F> {
+        FloatEval(value)
+    }
+
+    pub fn get_value(&self) -> F {
+        self.0
+    }
+
+    fn constant(value: f64) -> FloatEval<F> {
+        FloatEval(F::from(value).expect("Failed to convert constant to float."))
+    }
+}
+
+impl<F: Float + Debug> Eval for FloatEval<F> {
+    fn max_supported_mates() -> Self {
+        Self::constant(800.0)
+    }
+
+    fn from_ply(ply: i16) -> Self {
+        Self::constant(ply as f64)
+    }
+
+    fn new_mate(ply: i16, color: Color) -> Self {
+        match color {
+            Color::White => Self::max_eval() - Self::from_ply(ply) - Self::one(),
+            Color::Black => Self::min_eval() + Self::from_ply(ply) + Self::one(),
+        }
+    }
+
+    fn depth_to_mate(&self) -> Option<i64> {
+        if *self <= Self::min_eval() || *self >= Self::max_eval() {
+            None
+        } else if *self > Self::max_eval() - Self::max_supported_mates() - Self::one() {
+            (Self::max_eval() - *self - Self::one()).0.round().to_i64()
+        } else if *self < Self::min_eval() + Self::max_supported_mates() + Self::one() {
+            (Self::min_eval() - *self + Self::one()).0.round().to_i64()
+        } else {
+            None
+        }
+    }
+
+    fn add_depth(&self, amount: i16) -> Self {
+        if let Some(depth) = self.depth_to_mate() {
+            if depth < 0 {
+                Self::new_mate(((-depth) as i16) + amount, Color::Black)
+            } else {
+                Self::new_mate((depth as i16) + amount, Color::White)
+            }
+        } else {
+            *self
+        }
+    }
+
+    fn to_tt(&self, ply: i16) -> Self {
+        shift_mate(*self, -(ply as i64))
+    }
+
+    fn to_search(&self, ply: i16) -> Self {
+        shift_mate(*self, ply as i64)
+    }
+
+    fn min_eval() -> Self {
+        -Self::max_eval()
+    }
+
+    fn max_eval() -> Self {
+        Self::constant(FLOAT_EVAL_MAX)
+    }
+
+    fn null() -> Self {
+        Self::min_value()
+    }
+
+    fn bound(other: Self) -> Self {
+        if other < Self::min_eval() {
+            Self::min_eval()
+        } else {
+            other
+        }
+    }
+}
+
+impl<F: Float + Debug> Bounded for FloatEval<F> {
+    fn min_value() -> Self {
+        Self::constant(-FLOAT_EVAL_MAX - 1.0)
+    }
+
+    fn max_value() -> Self {
+        Self::constant(FLOAT_EVAL_MAX)
+    }
+}
+
+impl<F: Float + Debug> Zero for FloatEval<F> {
+    fn zero() -> Self {
+        FloatEval(F::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl<F: Float + Debug> One for FloatEval<F> {
+    fn one() -> Self {
+        FloatEval(F::one())
+    }
+}
+
+impl<F: Float + Debug> Num for FloatEval<F> {
+    type FromStrRadixErr = F::FromStrRadixErr;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        F::from_str_radix(s, radix).map(FloatEval)
+    }
+}
+
+impl<F: Float + Debug> Neg for FloatEval<F> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        FloatEval(-self.0)
+    }
+}
+
+impl<F: Float + Debug> Signed for FloatEval<F> {
+    fn abs(&self) -> Self {
+        FloatEval(self.0.abs())
+    }
+
+    fn abs_sub(&self, other: &Self) -> Self {
+        if *self <= *other {
+            Self::zero()
+        } else {
+            *self - *other
+        }
+    }
+
+    fn signum(&self) -> Self {
+        FloatEval(self.0.signum())
+    }
+
+    fn is_positive(&self) -> bool {
+        self.0 > F::zero()
+    }
+
+    fn is_negative(&self) -> bool {
+        self.0 < F::zero()
+    }
+}
+
+impl<F: Float + Debug> ToPrimitive for FloatEval<F> {
+    fn to_i64(&self) -> Option<i64> {
+        self.0.to_i64()
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        self.0.to_u64()
+    }
+
+    fn to_f32(&self) -> Option<f32> {
+        self.0.to_f32()
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        self.0.to_f64()
+    }
+}
+
+impl<F: Float + Debug> NumCast for FloatEval<F> {
+    fn from<T: ToPrimitive>(n: T) -> Option<Self> {
+        F::from(n).map(FloatEval)
+    }
+}
+
+macro_rules! float_eval_op {
+    ($op:ident, $fn:ident, $op_assign:ident, $fn_assign:ident) => {
+        impl<F: Float + Debug> $op for FloatEval<F> {
+            type Output = Self;
+
+            fn $fn(self, other: Self) -> Self {
+                FloatEval(self.0.$fn(other.0))
+            }
+        }
+
+        impl<F: Float + Debug> $op_assign for FloatEval<F> {
+            fn $fn_assign(&mut self, other: Self) {
+                *self = self.$fn(other);
+            }
+        }
+    };
+}
+
+float_eval_op!(Add, add, AddAssign, add_assign);
+float_eval_op!(Sub, sub, SubAssign, sub_assign);
+float_eval_op!(Mul, mul, MulAssign, mul_assign);
+float_eval_op!(Div, div, DivAssign, div_assign);
+float_eval_op!(Rem, rem, RemAssign, rem_assign);
+
+#[cfg(test)]
+use super::evaluate::{DefaultEvaluate, Evaluate};
+#[cfg(test)]
+use super::resettable::Resettable;
+#[cfg(test)]
+use super::search::{DefaultSearch, Search};
+#[cfg(test)]
+use super::search_window::{AlphaBetaSearchParams, SearchParams};
+#[cfg(test)]
+use chess::Board;
+#[cfg(test)]
+use engine::score::Score;
+#[cfg(test)]
+use std::str::FromStr;
+#[cfg(test)]
+use std::sync::atomic::AtomicBool;
+#[cfg(test)]
+use std::sync::Arc;
+
+#[cfg(test)]
+fn test_float_mates<F: Float + Debug>() {
+    type E<F> = FloatEval<F>;
+    assert_eq!(E::<F>::null(), E::min_value());
+    assert_eq!(-E::<F>::min_eval(), E::max_eval());
+    assert!(E::<F>::null() < E::min_eval());
+
+    assert_eq!(
+        E::<F>::new_mate(0, Color::White),
+        E::constant(FLOAT_EVAL_MAX - 1.0)
+    );
+    assert_eq!(E::<F>::new_mate(0, Color::Black).depth_to_mate(), Some(0));
+    assert_eq!(E::<F>::new_mate(10, Color::White).depth_to_mate(), Some(10));
+    assert_eq!(
+        E::<F>::new_mate(10, Color::Black).depth_to_mate(),
+        Some(-10)
+    );
+    assert_eq!(
+        E::<F>::new_mate(799, Color::White).depth_to_mate(),
+        Some(799)
+    );
+    assert_eq!(
+        -E::<F>::new_mate(10, Color::Black),
+        E::new_mate(10, Color::White)
+    );
+    assert!(E::<F>::new_mate(1, Color::White) > E::new_mate(2, Color::White));
+    assert!(E::<F>::new_mate(1, Color::Black) < E::new_mate(2, Color::Black));
+
+    assert_eq!(E::<F>::constant(123.5).depth_to_mate(), None);
+    assert_eq!(E::<F>::max_eval().depth_to_mate(), None);
+    assert_eq!(
+        E::<F>::new_mate(3, Color::White).add_depth(1),
+        E::new_mate(4, Color::White)
+    );
+    assert_eq!(E::<F>::constant(0.25).add_depth(1), E::constant(0.25));
+    assert_eq!(
+        E::<F>::new_mate(9, Color::Black).to_tt(4).to_search(6),
+        E::new_mate(11, Color::Black)
+    );
+    assert_eq!(E::<F>::bound(E::null()), E::min_eval());
+}
+
+#[test]
+fn test_float_eval_mates() {
+    test_float_mates::<f32>();
+    test_float_mates::<f64>();
+}
+
+#[test]
+fn test_float_eval_score() {
+    assert_eq!(Score::from(FloatEval::new(-12.75f32)), Score::cp(-12));
+    assert_eq!(
+        Score::from(FloatEval::<f64>::new_mate(3, Color::Black)),
+        Score::mate(-2)
+    );
+}
+
+// The material count, as a network giving fractions of a centipawn might.
+#[cfg(test)]
+struct FractionalEvaluate(DefaultEvaluate);
+
+#[cfg(test)]
+impl Evaluate<FloatEval<f32>> for FractionalEvaluate {
+    fn evaluate(&mut self, sp: &mut impl SearchParams<FloatEval<f32>>) -> FloatEval<f32> {
+        let board = *sp.board();
+        let mut params = AlphaBetaSearchParams::new(board, 0, 0, 0);
+        FloatEval::new(self.0.evaluate(&mut params) as f32 + 0.5)
+    }
+}
+
+#[cfg(test)]
+impl Resettable for FractionalEvaluate {}
+
+#[test]
+fn test_float_eval_search() {
+    let mut searcher = DefaultSearch::new(
+        Arc::new(AtomicBool::new(false)),
+        FractionalEvaluate(DefaultEvaluate::default()),
+    );
+    // White mates with Qxf7.
+    let board =
+        Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4")
+            .unwrap();
+    let result = searcher.search(board, FloatEval::min_eval(), FloatEval::max_eval(), 2);
+    assert_eq!(result.get_score(), FloatEval::new_mate(1, Color::White));
+    assert_eq!(
+        result.get_best_move().map(|m| m.to_string()),
+        Some("h5f7".to_string())
+    );
+}
@@ -0,0 +1,141 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use engine::engine_command::EngineCommand;
+
+#[cfg(test)]
+use engine::info::Info;
+#[cfg(test)]
+use parsers::Counter;
+
+/// How often a search's `info` lines are passed on to the GUI, so that a searcher reporting
+/// every node or every root move doesn't flood the pipe.
+///
+/// A line is sent if `min_interval` has passed since the last one, or, unless turned off, if it
+/// is for a new depth.  Lines in between are dropped, except that the last one dropped is sent
+/// when the search finishes, so the GUI sees where it ended.  `info string` lines, and lines
+/// that aren't `info`, are always sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InfoThrottle {
+    min_interval: Duration,
+    on_depth_change: bool,
+    send_final: bool,
+    last_sent: Option<Instant>,
+    last_depth: Option<u64>,
+    held: Option<String>,
+}
+
+impl InfoThrottle {
+    pub fn new(min_interval: Duration) -> InfoThrottle {
+        InfoThrottle {
+            min_interval,
+            on_depth_change: true,
+            send_final: true,
+            last_sent: None,
+            last_depth: None,
+            held: None,
+        }
+    }
+
+    /// Whether a line for a new depth is sent however soon it follows the last.
+    pub fn on_depth_change(mut self, on_depth_change: bool) -> InfoThrottle {
+        self.on_depth_change = on_depth_change;
+        self
+    }
+
+    /// Whether the last line held back is sent when the search finishes.
+    pub fn send_final(mut self, send_final: bool) -> InfoThrottle {
+        self.send_final = send_final;
+        self
+    }
+
+    pub fn min_interval(mut self, min_interval: Duration) -> InfoThrottle {
+        self.min_interval = min_interval;
+        self
+    }
+
+    pub fn get_min_interval(&self) -> Duration {
+        self.min_interval
+    }
+
+    /// Whether to send `line` now.  A line that isn't sent is held, in case it is the last.
+    pub fn pass(&mut self, line: &str, now: Instant) -> bool {
+        let info = match EngineCommand::from_str(line) {
+            Ok(EngineCommand::Info(info)) => info,
+            _ => return true,
+        };
+        if info.get_engine_string().is_some() {
+            return true;
+        }
+
+        let depth = info.get_depth();
+        let new_depth = self.on_depth_change && depth.is_some() && depth != self.last_depth;
+        let due = match self.last_sent {
+            Some(last_sent) => now.saturating_duration_since(last_sent) >= self.min_interval,
+            None => true,
+        };
+        if due || new_depth {
+            self.last_sent = Some(now);
+            self.last_depth = depth.or(self.last_depth);
+            self.held = None;
+            true
+        } else {
+            self.held = Some(line.to_string());
+            false
+        }
+    }
+
+    /// The search has finished: the line to send for it, if one was held back.  The throttle
+    /// starts again for the next search.
+    pub fn finish(&mut self) -> Option<String> {
+        let held = self.held.take().filter(|_| self.send_final);
+        self.last_sent = None;
+        self.last_depth = None;
+        held
+    }
+}
+
+// Nothing is held back.
+impl Default for InfoThrottle {
+    fn default() -> InfoThrottle {
+        InfoThrottle::new(Duration::new(0, 0))
+    }
+}
+
+#[test]
+fn test_info_throttle() {
+    let start = Instant::now();
+    let at = |millis| start + Duration::from_millis(millis);
+    let depth = |d: u64, nodes: u64| {
+        Info::depth(d)
+            .combine(&Info::nodes(nodes as Counter))
+            .to_string()
+            .trim_end()
+            .to_string()
+    };
+    let mut throttle = InfoThrottle::new(Duration::from_millis(100));
+
+    assert!(throttle.pass(&depth(1, 10), at(0)));
+    assert!(!throttle.pass(&depth(1, 20), at(50)));
+    // A new depth, and a message, go straight through.
+    assert!(throttle.pass(&depth(2, 30), at(60)));
+    assert!(throttle.pass("info string hello", at(61)));
+    assert!(throttle.pass("bestmove e2e4", at(62)));
+    assert!(!throttle.pass(&depth(2, 40), at(100)));
+    assert!(throttle.pass(&depth(2, 50), at(160)));
+    assert!(!throttle.pass(&depth(2, 60), at(170)));
+    assert_eq!(throttle.finish(), Some(depth(2, 60)));
+    assert_eq!(throttle.finish(), None);
+
+    let mut throttle = InfoThrottle::new(Duration::from_millis(100))
+        .on_depth_change(false)
+        .send_final(false);
+    assert!(throttle.pass(&depth(1, 10), at(0)));
+    assert!(!throttle.pass(&depth(2, 20), at(50)));
+    assert_eq!(throttle.finish(), None);
+    assert!(throttle.pass(&depth(1, 10), at(60)));
+
+    let mut unthrottled = InfoThrottle::default();
+    assert!(unthrottled.pass(&depth(1, 10), at(0)));
+    assert!(unthrottled.pass(&depth(1, 20), at(0)));
+}
@@ -1,17 +1,27 @@
 use super::eval::Eval;
+use super::eval_params::EvalParams;
+use super::mate_search::MateSearch;
+use super::pruning::PruningMargins;
 use super::pv::Pv;
+use super::resettable::Resettable;
+use super::root_moves::RootMoves;
 use super::search::Search;
+use super::search_limits::SearchLimits;
+use super::search_trace::SearchTrace;
 use super::time_manager::TimeManager;
 use crate::engine::info::Info;
+use crate::parsers::Counter;
 use crate::timer::timer::Timer;
 use std::convert::TryInto;
 use std::io::Write;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 use chess::Board;
 
 use std::marker::PhantomData;
 
-pub trait IterativeDeepening {
+pub trait IterativeDeepening: Resettable {
     fn id_search<W: Write>(
         &mut self,
         board: Board,
@@ -19,12 +29,60 @@ pub trait IterativeDeepening {
         moves_made: u16,
         writer: W,
     ) -> Pv;
+
+    /// `go mate`: look for a forced mate in at most `moves` moves.
+    fn mate_search<W: Write>(&mut self, board: Board, moves: u8, writer: W) -> Pv {
+        MateSearch::new(self.get_stop_flag()).search(board, moves, writer)
+    }
+
+    /// In debug mode, search statistics are sent to the GUI as `info string`.
+    fn set_debug(&mut self, _debug: bool) {}
+
+    /// Set from `UCI_AnalyseMode`.  An analysing searcher keeps deepening positions a game
+    /// would settle early, such as a forced move, until it is stopped or reaches its depth.
+    fn set_analyse_mode(&mut self, _analyse: bool) {}
+
+    /// The `go depth`, `go nodes` and `go searchmoves` limits for the searches that follow.
+    fn set_limits(&mut self, _limits: SearchLimits) {}
+
+    /// Set from `nodestime`: measure the clock in nodes, at this many per millisecond, rather
+    /// than wall time.  Zero is the wall clock.
+    fn set_nodes_time(&mut self, _nodes_time: u64) {}
+
+    /// The pruning margins set in the engine's options, for searchers that prune.
+    fn set_margins(&mut self, _margins: PruningMargins) {}
+
+    /// Evaluation weights loaded from the engine's `ParamsFile`, for the searches that follow.
+    fn set_eval_params(&mut self, _params: &EvalParams) {}
+
+    /// The static evaluation of `board`, for white, as the `d` command shows it.
+    fn evaluate(&mut self, _board: Board) -> Option<i64> {
+        None
+    }
+
+    /// Trace the searches that follow, keeping up to `max_nodes` nodes, or stop with `None`.
+    fn set_trace(&mut self, _max_nodes: Option<usize>) {}
+
+    /// The trace of the last `id_search`, if it was traced.
+    fn take_trace(&mut self) -> Option<SearchTrace> {
+        None
+    }
+
+    /// Setting this flag makes a running `id_search` return as soon as it can.
+    fn get_stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        None
+    }
 }
 
 pub struct DefaultIterativeDeepening<E: Eval, T: TimeManager<E>, S: Search<E>> {
     searcher: S,
     time_manager: T,
     timer: Timer,
+    debug: bool,
+    analyse: bool,
+    limits: SearchLimits,
+    // The deepest iteration of the last search, when tracing.
+    trace: Option<SearchTrace>,
     _eval: PhantomData<E>,
 }
 
@@ -34,11 +92,22 @@ impl<E: Eval, T: TimeManager<E>, S: Search<E>> DefaultIterativeDeepening<E, T, S
             searcher,
             time_manager,
             timer,
+            debug: false,
+            analyse: false,
+            limits: SearchLimits::new(),
+            trace: None,
             _eval: PhantomData,
         }
     }
 }
 
+impl<E: Eval, T: TimeManager<E>, S: Search<E>> Resettable for DefaultIterativeDeepening<E, T, S> {
+    fn reset(&mut self) {
+        self.searcher.reset();
+        self.time_manager.reset();
+    }
+}
+
 impl<E: Eval, T: TimeManager<E>, S: Search<E>> IterativeDeepening
     for DefaultIterativeDeepening<E, T, S>
 {
@@ -52,28 +121,99 @@ impl<E: Eval, T: TimeManager<E>, S: Search<E>> IterativeDeepening
         let alpha = E::min_eval();
         let beta = E::max_eval();
         let mut pv = Pv::new();
+        let mut root_moves = RootMoves::new(&board);
+        root_moves.restrict(self.limits.get_search_moves());
+        let mut nodes = 0;
+        self.trace = None;
+        self.searcher.set_limits(self.limits.clone());
+        // A clock counted in nodes starts with the search, as there is no other way to count.
+        if self.timer.get_nodes_time() > 0 {
+            self.timer.start();
+        }
 
-        for depth in 1..max_depth {
-            let eval = self.searcher.search(board, alpha, beta, depth);
-            if eval != E::null() {
-                pv = (*self.searcher.get_pv()).clone();
+        for depth in 1..=self.limits.cap_depth(max_depth) {
+            let result = self
+                .searcher
+                .search_root(board, &mut root_moves, alpha, beta, depth);
+            let eval = result.get_score();
+            nodes += result.get_nodes();
+            self.timer.set_nodes_searched(nodes);
+            if !result.is_stopped() {
+                pv = result.get_pv().clone();
+                root_moves.finish_iteration();
+                self.trace = self.searcher.take_trace();
             } else {
                 break;
             }
 
             let info = Info::default()
                 .combine(&Info::depth(depth.try_into().unwrap()))
+                .combine(&Info::seldepth(result.get_seldepth() as u64))
                 .combine(&Info::score(eval.into()))
+                .combine(&Info::nodes(nodes as Counter))
                 .combine(&Info::pv(pv.clone().into_iter().collect()));
             write!(writer, "{}", info).expect("I must be able to send data to the GUI.");
+            if self.debug {
+                let stats = Info::engine_string(format!("stats {}", result.get_stats()));
+                write!(writer, "{}", stats).expect("I must be able to send data to the GUI.");
+            }
 
-            if !self.time_manager.continue_id(eval, &self.timer, moves_made) {
+            if !self.time_manager.continue_id(eval, &self.timer, moves_made)
+                || (!self.analyse && self.time_manager.easy_move(&root_moves, &self.timer, moves_made))
+            {
                 break;
             }
         }
 
+        // Stopped before the first iteration finished, but there is still a move to play.
+        if pv.is_empty() {
+            if let Some(m) = root_moves.get_moves().first() {
+                pv.push(m.get_move());
+            }
+        }
         pv
     }
+
+    fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    fn set_analyse_mode(&mut self, analyse: bool) {
+        self.analyse = analyse;
+        self.time_manager.set_analyse_mode(analyse);
+    }
+
+    fn set_nodes_time(&mut self, nodes_time: u64) {
+        self.timer.set_nodes_time(nodes_time);
+    }
+
+    fn set_margins(&mut self, margins: PruningMargins) {
+        self.searcher.set_margins(margins);
+    }
+
+    fn set_eval_params(&mut self, params: &EvalParams) {
+        self.searcher.set_eval_params(params);
+    }
+
+    fn evaluate(&mut self, board: Board) -> Option<i64> {
+        self.searcher.evaluate(board)?.to_i64()
+    }
+
+    fn set_trace(&mut self, max_nodes: Option<usize>) {
+        self.searcher.set_trace(max_nodes);
+    }
+
+    fn take_trace(&mut self) -> Option<SearchTrace> {
+        self.trace.take()
+    }
+
+    fn get_stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        self.searcher.get_stop_flag()
+    }
 }
 
 #[cfg(test)]
@@ -81,15 +221,15 @@ use super::evaluate::DefaultEvaluate;
 #[cfg(test)]
 use super::search::DefaultSearch;
 #[cfg(test)]
-use super::test_positions::{easy_tactic, super_easy_tactic};
+use super::test_positions::{easy_tactic, mate_in_one, mate_in_two, super_easy_tactic};
 #[cfg(test)]
 use super::time_manager::DefaultTimeManager;
 #[cfg(test)]
-use chess::ChessMove;
+use crate::engine::score::Score;
 #[cfg(test)]
-use std::sync::atomic::AtomicBool;
+use chess::{BoardStatus, ChessMove, Square};
 #[cfg(test)]
-use std::sync::Arc;
+use std::str::FromStr;
 #[cfg(test)]
 use std::time::Duration;
 
@@ -118,3 +258,163 @@ fn test_easy_tactic() {
     let (board, best_move) = easy_tactic();
     perform_id_search(board, best_move);
 }
+
+#[test]
+fn test_mate_in_one() {
+    let board = mate_in_one();
+    let mut id = DefaultIterativeDeepening::new(
+        DefaultSearch::new(
+            Arc::<AtomicBool>::new(AtomicBool::new(false)),
+            DefaultEvaluate::default(),
+        ),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+
+    // Bounds that are already mate are lowered into the replies without overflowing.
+    let pv = id.id_search(board, 4, 0, vec![]);
+    assert_eq!(board.make_move_new(pv[0]).status(), BoardStatus::Checkmate);
+}
+
+#[test]
+fn test_mate_reported_in_moves() {
+    let mut id = DefaultIterativeDeepening::new(
+        DefaultSearch::new(
+            Arc::<AtomicBool>::new(AtomicBool::new(false)),
+            DefaultEvaluate::default(),
+        ),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+
+    // The mate is three plies deep, which UCI counts as two moves.
+    let mut output = vec![];
+    id.id_search(mate_in_two(), 4, 0, &mut output);
+    let output = String::from_utf8(output).unwrap();
+    let info = Info::from_str(output.lines().last().unwrap()).unwrap();
+    assert_eq!(info.get_score(), Some(Score::mate(2)));
+    assert_eq!(info.get_pv().len(), 3);
+}
+
+#[test]
+fn test_debug_sends_stats() {
+    let (board, _) = super_easy_tactic();
+    let mut id = DefaultIterativeDeepening::new(
+        DefaultSearch::new(
+            Arc::<AtomicBool>::new(AtomicBool::new(false)),
+            DefaultEvaluate::default(),
+        ),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+    id.set_debug(true);
+
+    let mut output = vec![];
+    id.id_search(board, 3, 0, &mut output);
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.lines().filter(|l| l.starts_with("info string stats nodes ")).count(), 3);
+}
+
+#[test]
+fn test_single_legal_move_stops_early() {
+    let board = Board::from_str("1r5k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    let mut id = DefaultIterativeDeepening::new(
+        DefaultSearch::new(
+            Arc::<AtomicBool>::new(AtomicBool::new(false)),
+            DefaultEvaluate::default(),
+        ),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+
+    let mut output = vec![];
+    let pv = id.id_search(board, 10, 0, &mut output);
+    assert_eq!(pv[0], ChessMove::new(Square::A1, Square::A2, None));
+    assert_eq!(String::from_utf8(output).unwrap().lines().count(), 1);
+}
+
+#[test]
+fn test_easy_move_stops_early() {
+    // Taking the queen is far better than anything else.
+    let (board, best_move) = super_easy_tactic();
+    let mut id = DefaultIterativeDeepening::new(
+        DefaultSearch::new(
+            Arc::<AtomicBool>::new(AtomicBool::new(false)),
+            DefaultEvaluate::default(),
+        ),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+
+    let mut output = vec![];
+    let pv = id.id_search(board, 7, 0, &mut output);
+    assert_eq!(pv[0], best_move);
+    assert!(String::from_utf8(output).unwrap().lines().count() < 7);
+}
+
+#[test]
+fn test_single_legal_move_analysed() {
+    let board = Board::from_str("1r5k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    let mut id = DefaultIterativeDeepening::new(
+        DefaultSearch::new(
+            Arc::<AtomicBool>::new(AtomicBool::new(false)),
+            DefaultEvaluate::default(),
+        ),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+    id.set_analyse_mode(true);
+
+    let mut output = vec![];
+    id.id_search(board, 4, 0, &mut output);
+    assert_eq!(String::from_utf8(output).unwrap().lines().count(), 4);
+}
+
+#[cfg(test)]
+fn limited_id_search(limits: SearchLimits) -> (Pv, Vec<Info>) {
+    let mut id = DefaultIterativeDeepening::new(
+        DefaultSearch::new(
+            Arc::<AtomicBool>::new(AtomicBool::new(false)),
+            DefaultEvaluate::default(),
+        ),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+    id.set_limits(limits);
+
+    let mut output = vec![];
+    let pv = id.id_search(Board::default(), 20, 0, &mut output);
+    let infos = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|l| Info::from_str(l).unwrap())
+        .collect();
+    (pv, infos)
+}
+
+#[test]
+fn test_depth_limit() {
+    let (pv, infos) = limited_id_search(SearchLimits::depth(3));
+    assert!(!pv.is_empty());
+    assert_eq!(infos.iter().map(|i| i.get_depth().unwrap()).collect::<Vec<u64>>(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_node_limit() {
+    let (pv, infos) = limited_id_search(SearchLimits::nodes(2000));
+    assert!(!pv.is_empty());
+    assert!(infos.len() < 20);
+    assert!(infos.iter().all(|i| i.get_nodes().unwrap() <= 2000));
+
+    // Too few nodes to finish even one iteration, but there is still a move.
+    let (pv, infos) = limited_id_search(SearchLimits::nodes(1));
+    assert_eq!(pv.len(), 1);
+    assert!(infos.is_empty());
+}
+
+#[test]
+fn test_search_moves() {
+    let a2a3 = ChessMove::new(Square::A2, Square::A3, None);
+    let (pv, _) = limited_id_search(SearchLimits::depth(3).combine(&SearchLimits::search_moves(vec![a2a3])));
+    assert_eq!(pv[0], a2a3);
+}
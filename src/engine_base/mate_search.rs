@@ -0,0 +1,187 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chess::{Board, BoardStatus, ChessMove, MoveGen};
+
+use super::pv::Pv;
+use super::pv_table::{PvTable, DEFAULT_MAX_PLY};
+use crate::engine::info::Info;
+use crate::engine::score::Score;
+use crate::parsers::Counter;
+
+/// The search behind `go mate N`.  Deepens one move at a time until it proves a forced mate, so
+/// the first mate found is the shortest.  Only the side to move gets to mate, and on its last
+/// move it only tries checks, which keeps this far cheaper than a full search to the same depth.
+///
+/// The PV follows one defence that is mated in time, which isn't always the longest one.
+pub struct MateSearch {
+    stopping: Option<Arc<AtomicBool>>,
+    pv_table: PvTable,
+    nodes: u64,
+}
+
+fn gives_check(board: &Board, m: ChessMove) -> bool {
+    board.make_move_new(m).checkers().popcnt() > 0
+}
+
+// Checks first, then captures, then everything else.
+fn attacking_moves(board: &Board, checks_only: bool) -> Vec<ChessMove> {
+    let mut moves = MoveGen::new_legal(board)
+        .map(|m| {
+            let check = gives_check(board, m);
+            let capture = board.piece_on(m.get_dest()).is_some();
+            (m, check, capture)
+        })
+        .filter(|&(_, check, _)| check || !checks_only)
+        .collect::<Vec<(ChessMove, bool, bool)>>();
+    moves.sort_by_key(|&(_, check, capture)| (!check, !capture));
+    moves.into_iter().map(|(m, _, _)| m).collect()
+}
+
+impl MateSearch {
+    /// Setting `stopping` makes a running search give up.
+    pub fn new(stopping: Option<Arc<AtomicBool>>) -> MateSearch {
+        MateSearch {
+            stopping,
+            pv_table: PvTable::new(DEFAULT_MAX_PLY),
+            nodes: 0,
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopping
+            .as_ref()
+            .is_some_and(|s| s.load(Ordering::Relaxed))
+    }
+
+    /// Can the side to move mate within `moves` of its own moves?
+    fn attack(&mut self, board: &Board, moves: u8, ply: usize) -> bool {
+        self.nodes += 1;
+        self.pv_table.clear(ply);
+        if self.stopped() || ply + 1 >= self.pv_table.get_max_ply() {
+            return false;
+        }
+
+        for m in attacking_moves(board, moves == 1) {
+            let after = board.make_move_new(m);
+            let mates = match after.status() {
+                BoardStatus::Checkmate => {
+                    self.pv_table.clear(ply + 1);
+                    true
+                }
+                BoardStatus::Stalemate => false,
+                BoardStatus::Ongoing => moves > 1 && self.defend(&after, moves - 1, ply + 1),
+            };
+            if mates {
+                self.pv_table.update(ply, m);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Is every move for the side to move answered by a mate within `moves`?
+    fn defend(&mut self, board: &Board, moves: u8, ply: usize) -> bool {
+        self.nodes += 1;
+        self.pv_table.clear(ply);
+
+        let mut last = None;
+        for m in MoveGen::new_legal(board) {
+            if !self.attack(&board.make_move_new(m), moves, ply + 1) {
+                return false;
+            }
+            last = Some(m);
+        }
+
+        // Row `ply + 1` still holds the line against the last defence.
+        if let Some(m) = last {
+            self.pv_table.update(ply, m);
+        }
+        last.is_some()
+    }
+
+    /// Look for a mate in at most `max_moves` moves, reporting each depth as it is finished.
+    /// Without a mate, the first legal move is given so that there is still a move to play.
+    pub fn search<W: Write>(&mut self, board: Board, max_moves: u8, mut writer: W) -> Pv {
+        self.nodes = 0;
+
+        for moves in 1..=max_moves {
+            let found = self.attack(&board, moves, 0);
+            if self.stopped() {
+                break;
+            }
+
+            let mut info = Info::default()
+                .combine(&Info::depth(2 * moves as u64 - 1))
+                .combine(&Info::nodes(self.nodes as Counter));
+            if found {
+                let mut pv = Pv::new();
+                self.pv_table.copy_into(0, &mut pv);
+                info = info
+                    .combine(&Info::score(Score::mate(moves as i64)))
+                    .combine(&Info::pv(pv.as_slice().to_vec()));
+                write!(writer, "{}", info).expect("I must be able to send data to the GUI.");
+                return pv;
+            }
+            write!(writer, "{}", info).expect("I must be able to send data to the GUI.");
+        }
+
+        let mut pv = Pv::new();
+        if let Some(m) = MoveGen::new_legal(&board).next() {
+            pv.push(m);
+        }
+        pv
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use std::str::FromStr;
+
+#[cfg(test)]
+fn mate_search(fen: &str, max_moves: u8) -> (Pv, String) {
+    let mut output = vec![];
+    let pv = MateSearch::new(None).search(Board::from_str(fen).unwrap(), max_moves, &mut output);
+    (pv, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn test_mate_in_one() {
+    let (pv, output) = mate_search("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 3);
+    assert_eq!(pv.as_slice(), &[ChessMove::new(Square::D1, Square::D8, None)]);
+    assert_eq!(output, "info depth 1 score mate 1 nodes 1 pv d1d8\n");
+}
+
+#[test]
+fn test_mate_in_two() {
+    let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+    let (pv, output) = mate_search(fen, 2);
+    assert_eq!(pv.len(), 3);
+    assert!(pv.is_legal_from(Board::from_str(fen).unwrap()));
+    let (_, last) = pv.walk(Board::from_str(fen).unwrap()).last().unwrap();
+    assert_eq!(last.status(), BoardStatus::Checkmate);
+    assert_eq!(output.lines().filter(|l| l.contains("score mate 2")).count(), 1);
+}
+
+#[test]
+fn test_no_mate_in_time() {
+    let (pv, output) = mate_search("k7/8/2K5/8/8/8/8/7R w - - 0 1", 1);
+    assert_eq!(pv.len(), 1);
+    assert!(!output.contains("score"));
+
+    // Checkmated already: nothing to play.
+    let (pv, _) = mate_search("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", 2);
+    assert!(pv.is_empty());
+}
+
+#[test]
+fn test_mate_search_stops() {
+    let stop = Arc::new(AtomicBool::new(true));
+    let mut search = MateSearch::new(Some(stop));
+    let mut output = vec![];
+    let pv = search.search(Board::default(), 5, &mut output);
+    assert_eq!(pv.len(), 1);
+    assert!(output.is_empty());
+}
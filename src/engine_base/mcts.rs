@@ -0,0 +1,572 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chess::{Board, ChessMove, Color, MoveGen};
+
+use super::eval::Eval;
+use super::eval_params::EvalParams;
+use super::evaluate::Evaluate;
+use super::pv::Pv;
+use super::resettable::Resettable;
+use super::root_moves::RootMoves;
+use super::search::Search;
+use super::search_limits::SearchLimits;
+use super::search_result::SearchResult;
+use super::search_stats::SearchStats;
+use super::search_window::AlphaBetaSearchParams;
+use game_result::{game_result, is_insufficient_material, GameResult};
+
+/// How strongly the search favours moves the policy likes over moves that scored well.
+pub const DEFAULT_C_PUCT: f32 = 1.5;
+/// The playouts the first iteration runs.  Each iteration after it doubles the tree.
+pub const FIRST_ITERATION_PLAYOUTS: u64 = 32;
+/// The most nodes the tree keeps, about 160MB.  Leaves past it are evaluated, but not expanded.
+pub const DEFAULT_MAX_TREE_NODES: usize = 1 << 22;
+
+// A score turns into a value in (-1, 1) along the logistic curve an Elo difference turns into an
+// expected score on, counting centipawns as Elo.
+const VALUE_SCALE: f64 = 400.0;
+// Values further out than this are reported as this, about 13 pawns.
+const MAX_REPORTED_VALUE: f64 = 0.999;
+
+/// What a Monte-Carlo search asks of each new position: how good it is, and which of its moves
+/// are worth looking at, as a network's value and policy heads would say.
+pub trait PolicyValue<E: Eval>: Resettable {
+    /// The value of `board`, from white's point of view, as `Evaluate` gives it.
+    fn value(&mut self, board: &Board) -> E;
+
+    /// A weight for each of `moves`, the legal moves in `board`, for how likely it is to be the
+    /// best.  The search normalises them, so they only have to be non-negative.  Every move is
+    /// as likely as the next by default.
+    fn policy(&mut self, _board: &Board, moves: &[ChessMove]) -> Vec<f32> {
+        vec![1.0; moves.len()]
+    }
+
+    /// New weights, as for `Evaluate::set_params`.
+    fn set_params(&mut self, _params: &EvalParams) {}
+}
+
+/// Any evaluator as a `PolicyValue`, with a uniform policy.
+pub struct EvaluatePolicy<V> {
+    evaluator: V,
+}
+
+impl<V> EvaluatePolicy<V> {
+    pub fn new(evaluator: V) -> EvaluatePolicy<V> {
+        EvaluatePolicy { evaluator }
+    }
+
+    pub fn get_evaluator(&self) -> &V {
+        &self.evaluator
+    }
+}
+
+impl<E: Eval, V: Evaluate<E>> PolicyValue<E> for EvaluatePolicy<V> {
+    fn value(&mut self, board: &Board) -> E {
+        let mut sp = AlphaBetaSearchParams::new(*board, E::min_eval(), E::max_eval(), 0);
+        self.evaluator.evaluate(&mut sp)
+    }
+
+    fn set_params(&mut self, params: &EvalParams) {
+        self.evaluator.set_params(params);
+    }
+}
+
+impl<V: Resettable> Resettable for EvaluatePolicy<V> {
+    fn reset(&mut self) {
+        self.evaluator.reset();
+    }
+}
+
+// A score from white's point of view as a value in [-1, 1] for the side to move.
+fn to_value<E: Eval>(eval: E, side_to_move: Color) -> f32 {
+    let value = if eval.depth_to_mate().is_some() || eval >= E::max_eval() {
+        if eval > E::zero() {
+            1.0
+        } else {
+            -1.0
+        }
+    } else if eval <= E::min_eval() {
+        -1.0
+    } else {
+        let cp = eval.to_f64().unwrap_or(0.0);
+        2.0 / (1.0 + 10f64.powf(-cp / VALUE_SCALE)) - 1.0
+    };
+    match side_to_move {
+        Color::White => value as f32,
+        Color::Black => -value as f32,
+    }
+}
+
+// A value back into centipawns, for reporting.
+fn from_value<E: Eval>(value: f32) -> E {
+    let value = (value as f64).clamp(-MAX_REPORTED_VALUE, MAX_REPORTED_VALUE);
+    let cp = VALUE_SCALE * ((1.0 + value) / (1.0 - value)).log10();
+    E::from(cp.round() as i64).unwrap_or_else(E::zero)
+}
+
+// A node of the tree, reached by `chess_move`.
+struct Node {
+    chess_move: ChessMove,
+    prior: f32,
+    visits: u32,
+    // Summed from the point of view of the side that played `chess_move`.
+    value_sum: f32,
+    // The children are `children..children + child_count`, once the node is expanded.
+    children: usize,
+    child_count: usize,
+    expanded: bool,
+    // The value for the side to move of a finished game: a loss when mated, or a draw.
+    terminal: Option<f32>,
+}
+
+impl Node {
+    fn new(chess_move: ChessMove, prior: f32) -> Node {
+        Node {
+            chess_move,
+            prior,
+            visits: 0,
+            value_sum: 0.0,
+            children: 0,
+            child_count: 0,
+            expanded: false,
+            terminal: None,
+        }
+    }
+
+    fn q(&self) -> f32 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.value_sum / self.visits as f32
+        }
+    }
+}
+
+/// A Monte-Carlo tree search, choosing moves PUCT-style by the policy and value of a
+/// `PolicyValue`, for Leela-style engines.
+///
+/// It plugs into `DefaultIterativeDeepening` like an alpha-beta searcher: a search to `depth`
+/// grows the tree until it holds `FIRST_ITERATION_PLAYOUTS << (depth - 1)` playouts, keeping the
+/// tree from the last search of the same position, so each iteration doubles it.  The nodes
+/// reported are playouts, the seldepth is the deepest playout, and `alpha` and `beta` are
+/// ignored.  The move played is the most visited one, and the score its value in centipawns, or
+/// a mate when it mates at once.
+pub struct MctsSearch<E: Eval, P: PolicyValue<E>> {
+    provider: P,
+    stopping: Arc<AtomicBool>,
+    phantom: PhantomData<E>,
+    c_puct: f32,
+    max_tree_nodes: usize,
+    tree: Vec<Node>,
+    // The position at the root of the tree, and the root moves it was limited to.
+    root: Option<(Board, Vec<ChessMove>)>,
+    pv: Pv,
+    stats: SearchStats,
+    limits: SearchLimits,
+    playouts: u64,
+}
+
+impl<E: Eval, P: PolicyValue<E>> MctsSearch<E, P> {
+    pub fn new(stopping: Arc<AtomicBool>, provider: P) -> MctsSearch<E, P> {
+        MctsSearch {
+            provider,
+            stopping,
+            phantom: PhantomData,
+            c_puct: DEFAULT_C_PUCT,
+            max_tree_nodes: DEFAULT_MAX_TREE_NODES,
+            tree: vec![],
+            root: None,
+            pv: Pv::new(),
+            stats: SearchStats::new(),
+            limits: SearchLimits::new(),
+            playouts: 0,
+        }
+    }
+
+    pub fn c_puct(mut self, c_puct: f32) -> Self {
+        self.c_puct = c_puct;
+        self
+    }
+
+    pub fn max_tree_nodes(mut self, max_tree_nodes: usize) -> Self {
+        self.max_tree_nodes = max_tree_nodes.max(1);
+        self
+    }
+
+    pub fn get_c_puct(&self) -> f32 {
+        self.c_puct
+    }
+
+    pub fn get_max_tree_nodes(&self) -> usize {
+        self.max_tree_nodes
+    }
+
+    /// The visits of each move at the root of the last search's tree.
+    pub fn get_root_visits(&self) -> Vec<(ChessMove, u32)> {
+        self.children(0)
+            .map(|c| (self.tree[c].chess_move, self.tree[c].visits))
+            .collect()
+    }
+
+    fn children(&self, node: usize) -> ::std::ops::Range<usize> {
+        match self.tree.get(node) {
+            Some(n) if n.expanded => n.children..n.children + n.child_count,
+            _ => 0..0,
+        }
+    }
+
+    // Keep the tree if it is already rooted at `board`, with the same root moves.
+    fn set_root(&mut self, board: Board, root_moves: Vec<ChessMove>) {
+        let root = Some((board, root_moves));
+        if self.root != root {
+            self.tree.clear();
+            self.tree.push(Node::new(ChessMove::default(), 1.0));
+            self.root = root;
+        }
+    }
+
+    fn most_visited(&self, node: usize) -> Option<usize> {
+        self.children(node).max_by_key(|&c| self.tree[c].visits)
+    }
+
+    fn select(&self, node: usize) -> usize {
+        let parent = &self.tree[node];
+        let explore = self.c_puct * (parent.visits as f32).sqrt();
+        // Unvisited moves are taken to be as good as the node's other moves so far.
+        let first_play = -parent.q();
+        let score = |c: usize| {
+            let child = &self.tree[c];
+            let q = if child.visits == 0 {
+                first_play
+            } else {
+                child.q()
+            };
+            q + explore * child.prior / (1.0 + child.visits as f32)
+        };
+        self.children(node)
+            .max_by(|&a, &b| {
+                score(a)
+                    .partial_cmp(&score(b))
+                    .unwrap_or(::std::cmp::Ordering::Equal)
+            })
+            .expect("An expanded node has children.")
+    }
+
+    // Evaluate a leaf, and give it children if there is room.  Returns the value for the side
+    // to move.
+    fn expand(&mut self, node: usize, board: &Board) -> f32 {
+        let finished = match game_result(board) {
+            Some(GameResult::Win(_)) => Some(-1.0),
+            Some(GameResult::Draw) => Some(0.0),
+            None if is_insufficient_material(board) => Some(0.0),
+            None => None,
+        };
+        if let Some(value) = finished {
+            self.tree[node].terminal = Some(value);
+            return value;
+        }
+
+        let value = to_value(self.provider.value(board), board.side_to_move());
+        let mut moves = MoveGen::new_legal(board).collect::<Vec<ChessMove>>();
+        // As with `RootMoves::restrict`, every move is searched if none of the root moves are
+        // legal, so that the root always has children.
+        if node == 0 {
+            if let Some((_, ref root_moves)) = self.root {
+                if moves.iter().any(|m| root_moves.contains(m)) {
+                    moves.retain(|m| root_moves.contains(m));
+                }
+            }
+        }
+        if self.tree.len() + moves.len() <= self.max_tree_nodes {
+            let weights = self.provider.policy(board, &moves);
+            let total = weights.iter().map(|w| w.max(0.0)).sum::<f32>();
+            let uniform = 1.0 / moves.len() as f32;
+            let children = self.tree.len();
+            for (i, m) in moves.iter().enumerate() {
+                let weight = weights.get(i).map_or(0.0, |w| w.max(0.0));
+                let prior = if total > 0.0 { weight / total } else { uniform };
+                self.tree.push(Node::new(*m, prior));
+            }
+            let n = &mut self.tree[node];
+            n.children = children;
+            n.child_count = moves.len();
+            n.expanded = true;
+        }
+        value
+    }
+
+    // Walk down the tree to a leaf, evaluate it, and back the value up.
+    fn playout(&mut self, root: Board) {
+        let mut board = root;
+        let mut path = vec![0];
+        let mut node = 0;
+        while self.tree[node].expanded && self.tree[node].terminal.is_none() {
+            node = self.select(node);
+            board = board.make_move_new(self.tree[node].chess_move);
+            path.push(node);
+        }
+        let mut value = match self.tree[node].terminal {
+            Some(value) => value,
+            None => self.expand(node, &board),
+        };
+
+        self.stats.node();
+        self.stats.reach_ply(path.len() - 1);
+        // Each node keeps the value for the side that moved into it.
+        for &n in path.iter().rev() {
+            value = -value;
+            self.tree[n].visits += 1;
+            self.tree[n].value_sum += value;
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopping.load(Ordering::Relaxed) || self.limits.nodes_exhausted(self.playouts)
+    }
+
+    fn run(&mut self, board: Board, depth: i16) -> SearchResult<E> {
+        self.stats.clear();
+        let shift = (depth.max(1) - 1).min(40) as u32;
+        let target = FIRST_ITERATION_PLAYOUTS << shift;
+        while (self.tree[0].visits as u64) < target {
+            if self.stopped() {
+                return SearchResult::new(E::null(), self.pv.clone(), self.stats);
+            }
+            self.playout(board);
+            self.playouts += 1;
+            if self.tree[0].terminal.is_some() {
+                break;
+            }
+        }
+
+        self.pv.clear();
+        let mut node = 0;
+        while let Some(child) = self.most_visited(node) {
+            if self.tree[child].visits == 0 {
+                break;
+            }
+            self.pv.push(self.tree[child].chess_move);
+            node = child;
+        }
+        let score = match self.tree[0].terminal {
+            Some(value) if value < 0.0 => E::new_mate(0, Color::Black),
+            Some(_) => E::zero(),
+            None => self.most_visited(0).map_or(E::zero(), |c| self.score(c)),
+        };
+        SearchResult::new(score, self.pv.clone(), self.stats)
+    }
+
+    // A root move's score, for the side to move at the root.
+    fn score(&self, child: usize) -> E {
+        match self.tree[child].terminal {
+            Some(value) if value < 0.0 => E::new_mate(1, Color::White),
+            _ => from_value(self.tree[child].q()),
+        }
+    }
+}
+
+impl<E: Eval, P: PolicyValue<E>> Resettable for MctsSearch<E, P> {
+    fn reset(&mut self) {
+        self.tree.clear();
+        self.root = None;
+        self.pv.clear();
+        self.stats.clear();
+        self.playouts = 0;
+        self.provider.reset();
+    }
+}
+
+impl<E: Eval, P: PolicyValue<E>> Search<E> for MctsSearch<E, P> {
+    fn search(&mut self, board: Board, _alpha: E, _beta: E, depth: i16) -> SearchResult<E> {
+        self.set_root(board, vec![]);
+        self.run(board, depth)
+    }
+
+    fn search_root(
+        &mut self,
+        board: Board,
+        root_moves: &mut RootMoves<E>,
+        _alpha: E,
+        _beta: E,
+        depth: i16,
+    ) -> SearchResult<E> {
+        let moves = root_moves
+            .get_moves()
+            .iter()
+            .map(|m| m.get_move())
+            .collect();
+        self.set_root(board, moves);
+        let result = self.run(board, depth);
+        if !result.is_stopped() {
+            for c in self.children(0) {
+                if self.tree[c].visits == 0 {
+                    continue;
+                }
+                let chess_move = self.tree[c].chess_move;
+                if let Some(index) = root_moves
+                    .get_moves()
+                    .iter()
+                    .position(|m| m.get_move() == chess_move)
+                {
+                    root_moves.record(index, self.score(c));
+                }
+            }
+        }
+        result
+    }
+
+    fn get_pv(&self) -> &Pv {
+        &self.pv
+    }
+
+    fn get_stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn get_stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        Some(self.stopping.clone())
+    }
+
+    // As for `DefaultSearch`, the node budget covers every search until the next `set_limits`.
+    fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+        self.playouts = 0;
+    }
+
+    // The tree was grown with the old weights, so it is thrown away.
+    fn set_eval_params(&mut self, params: &EvalParams) {
+        self.provider.set_params(params);
+        self.tree.clear();
+        self.root = None;
+    }
+}
+
+#[cfg(test)]
+use super::evaluate::DefaultEvaluate;
+#[cfg(test)]
+use super::iterative_deepening::{DefaultIterativeDeepening, IterativeDeepening};
+#[cfg(test)]
+use super::test_positions::super_easy_tactic;
+#[cfg(test)]
+use super::time_manager::DefaultTimeManager;
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use std::str::FromStr;
+#[cfg(test)]
+use std::time::Duration;
+#[cfg(test)]
+use timer::timer::Timer;
+
+#[cfg(test)]
+fn mcts() -> MctsSearch<i32, EvaluatePolicy<DefaultEvaluate>> {
+    MctsSearch::new(
+        Arc::new(AtomicBool::new(false)),
+        EvaluatePolicy::new(DefaultEvaluate::default()),
+    )
+}
+
+#[test]
+fn test_values() {
+    assert_eq!(to_value(0i32, Color::White), 0.0);
+    assert!((to_value(400i32, Color::White) - 0.818).abs() < 0.001);
+    assert_eq!(
+        to_value(400i32, Color::Black),
+        -to_value(400i32, Color::White)
+    );
+    assert_eq!(to_value(i32::new_mate(5, Color::Black), Color::White), -1.0);
+    assert_eq!(from_value::<i32>(to_value(250i32, Color::White)), 250);
+    assert_eq!(from_value::<i32>(1.0), 1320);
+}
+
+#[test]
+fn test_mcts_search() {
+    let (board, best_move) = super_easy_tactic();
+    let mut searcher = mcts();
+    let result = searcher.search(board, i32::min_eval(), i32::max_eval(), 4);
+    assert_eq!(result.get_best_move(), Some(best_move));
+    assert_eq!(result.get_nodes(), FIRST_ITERATION_PLAYOUTS << 3);
+    assert!(result.get_score() > 500);
+    assert!(result.get_seldepth() > 1);
+
+    // The most visited move is the one played.
+    let visits = searcher.get_root_visits();
+    let most = visits.iter().max_by_key(|v| v.1).unwrap();
+    assert_eq!(most.0, best_move);
+    assert_eq!(
+        visits.iter().map(|v| v.1 as u64).sum::<u64>(),
+        (FIRST_ITERATION_PLAYOUTS << 3) - 1
+    );
+
+    // The next iteration grows the same tree.
+    let result = searcher.search(board, i32::min_eval(), i32::max_eval(), 5);
+    assert_eq!(result.get_nodes(), FIRST_ITERATION_PLAYOUTS << 3);
+}
+
+#[test]
+fn test_mcts_mates() {
+    let mut searcher = mcts();
+    let mate_in_one =
+        Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4")
+            .unwrap();
+    let result = searcher.search(mate_in_one, i32::min_eval(), i32::max_eval(), 4);
+    assert_eq!(result.get_score(), i32::new_mate(1, Color::White));
+    assert_eq!(result.get_pv().as_slice()[0].to_string(), "h5f7");
+
+    let mated =
+        Board::from_str("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+    let result = searcher.search(mated, i32::min_eval(), i32::max_eval(), 4);
+    assert_eq!(result.get_score(), i32::new_mate(0, Color::Black));
+    assert_eq!(result.get_best_move(), None);
+}
+
+#[test]
+fn test_mcts_limits() {
+    let (board, _) = super_easy_tactic();
+    let mut searcher = mcts().max_tree_nodes(10);
+    searcher.set_limits(SearchLimits::nodes(100));
+    assert!(!searcher
+        .search(board, i32::min_eval(), i32::max_eval(), 2)
+        .is_stopped());
+    // A full tree is still searched, without growing.
+    assert!(searcher.tree.len() <= 10);
+    // Past the node budget.
+    assert!(searcher
+        .search(board, i32::min_eval(), i32::max_eval(), 4)
+        .is_stopped());
+
+    let e1e2 = ChessMove::new(Square::E1, Square::E2, None);
+    let mut searcher = mcts();
+    let mut root_moves = RootMoves::new(&board);
+    root_moves.restrict(&[e1e2]);
+    let result = searcher.search_root(board, &mut root_moves, i32::min_eval(), i32::max_eval(), 2);
+    assert_eq!(result.get_best_move(), Some(e1e2));
+    assert_eq!(searcher.get_root_visits().len(), 1);
+    assert!(root_moves.get_moves()[0].get_score() != i32::null());
+
+    // Root moves that are all illegal here are ignored rather than leaving the root childless.
+    let mut searcher = mcts();
+    let mut root_moves = RootMoves::new(&Board::default());
+    let result = searcher.search_root(board, &mut root_moves, i32::min_eval(), i32::max_eval(), 2);
+    assert!(board.legal(result.get_best_move().unwrap()));
+    assert_eq!(searcher.get_root_visits().len(), MoveGen::new_legal(&board).len());
+}
+
+#[test]
+fn test_mcts_iterative_deepening() {
+    let (board, best_move) = super_easy_tactic();
+    let mut id = DefaultIterativeDeepening::new(
+        mcts(),
+        DefaultTimeManager::new(),
+        Timer::new_without_increment(Duration::from_secs(100000)),
+    );
+    let mut output = vec![];
+    assert_eq!(id.id_search(board, 5, 0, &mut output)[0], best_move);
+    // Each iteration doubles the playouts.
+    let output = String::from_utf8(output).unwrap();
+    let first = output.lines().next().unwrap();
+    assert!(first.starts_with("info depth 1 seldepth "));
+    assert!(first.contains(&format!(" nodes {} ", FIRST_ITERATION_PLAYOUTS)));
+}
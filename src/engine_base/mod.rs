@@ -1,13 +1,37 @@
+pub mod chess960;
+pub mod endgame;
+pub mod engine;
 pub mod engine_options;
 pub mod eval;
+pub mod eval_params;
 pub mod evaluate;
+pub mod float_eval;
+pub mod info_throttle;
 pub mod iterative_deepening;
+pub mod mate_search;
+pub mod mcts;
+pub mod node_counter;
+pub mod option_schema;
+pub mod pawn_table;
+pub mod pruning;
 pub mod pv;
+pub mod pv_table;
+pub mod resettable;
+pub mod root_moves;
 pub mod search;
+pub mod search_handle;
 pub mod search_info;
+pub mod search_limits;
+pub mod search_result;
+pub mod search_stats;
+pub mod search_trace;
 pub mod search_window;
+pub mod server;
 #[cfg(test)]
 mod test_positions;
 pub mod time_manager;
+pub mod watchdog;
+pub mod transposition_table;
 pub mod tt_entry;
 pub mod tt_score;
+pub mod zobrist;
@@ -0,0 +1,176 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How many nodes a `NodeCounterHandle` counts on its own before adding them to its shard.
+pub const NODE_COUNTER_FLUSH: u64 = 1024;
+
+// Each shard on its own cache line, so that threads counting into neighbouring shards don't
+// take the line from each other.
+#[repr(align(64))]
+#[derive(Debug, Default)]
+struct Shard(AtomicU64);
+
+/// Nodes searched by several threads, each counting into its own shard, so that counting costs
+/// no more with 32 threads than with one.  The total is the sum of the shards, read with relaxed
+/// ordering: it may miss nodes counted a moment ago, which is fine for `nps` and node limits.
+#[derive(Debug)]
+pub struct NodeCounter {
+    shards: Box<[Shard]>,
+}
+
+impl NodeCounter {
+    /// A counter for `threads` threads.
+    pub fn new(threads: usize) -> Arc<NodeCounter> {
+        Arc::new(NodeCounter {
+            shards: (0..threads.max(1)).map(|_| Shard::default()).collect(),
+        })
+    }
+
+    /// What thread `thread` counts with.  Each thread needs its own handle; two handles for the
+    /// same thread count correctly, but contend.
+    pub fn handle(self: &Arc<NodeCounter>, thread: usize) -> NodeCounterHandle {
+        NodeCounterHandle {
+            counter: self.clone(),
+            shard: thread % self.shards.len(),
+            pending: 0,
+            total: self.total(),
+        }
+    }
+
+    pub fn get_threads(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The nodes every thread has flushed.
+    pub fn total(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|s| s.0.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// The nodes of thread `thread` it has flushed.
+    pub fn get_thread_nodes(&self, thread: usize) -> u64 {
+        self.shards[thread % self.shards.len()]
+            .0
+            .load(Ordering::Relaxed)
+    }
+
+    /// Nodes per second over `elapsed`, for `info nps`.
+    pub fn nps(&self, elapsed: Duration) -> u64 {
+        let millis = elapsed.as_millis().max(1) as u64;
+        self.total().saturating_mul(1000) / millis
+    }
+
+    /// Start again from zero, for a new search.  Handles still holding nodes add them to the new
+    /// count when they flush.
+    pub fn reset(&self) {
+        for shard in self.shards.iter() {
+            shard.0.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// One thread's view of a `NodeCounter`.  Nodes are counted locally and added to the thread's
+/// shard every `NODE_COUNTER_FLUSH` nodes, and when the handle is flushed or dropped.
+#[derive(Debug)]
+pub struct NodeCounterHandle {
+    counter: Arc<NodeCounter>,
+    shard: usize,
+    pending: u64,
+    // Every thread's nodes as of the last flush, not counting `pending`.
+    total: u64,
+}
+
+impl NodeCounterHandle {
+    /// Count a node, returning about how many every thread has searched, for checking against
+    /// a node limit.  It is exact with one thread; with more, it lags by up to
+    /// `NODE_COUNTER_FLUSH` nodes for each of the others.
+    pub fn increment(&mut self) -> u64 {
+        self.pending += 1;
+        if self.pending >= NODE_COUNTER_FLUSH {
+            self.flush();
+        }
+        self.total + self.pending
+    }
+
+    /// What `increment` last returned.
+    pub fn get_total(&self) -> u64 {
+        self.total + self.pending
+    }
+
+    /// Add the nodes counted since the last flush to the shared count.
+    pub fn flush(&mut self) {
+        if self.pending > 0 {
+            self.counter.shards[self.shard]
+                .0
+                .fetch_add(self.pending, Ordering::Relaxed);
+            self.pending = 0;
+        }
+        self.total = self.counter.total();
+    }
+
+    /// The nodes this handle hasn't flushed yet.
+    pub fn get_pending(&self) -> u64 {
+        self.pending
+    }
+
+    pub fn get_counter(&self) -> &Arc<NodeCounter> {
+        &self.counter
+    }
+}
+
+impl Drop for NodeCounterHandle {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+use std::thread;
+
+#[test]
+fn test_node_counter() {
+    let counter = NodeCounter::new(2);
+    let mut handle = counter.handle(0);
+    for n in 1..=NODE_COUNTER_FLUSH + 5 {
+        assert_eq!(handle.increment(), n);
+    }
+    assert_eq!(handle.get_pending(), 5);
+    assert_eq!(counter.total(), NODE_COUNTER_FLUSH);
+
+    let mut other = counter.handle(1);
+    other.increment();
+    drop(other);
+    assert_eq!(counter.get_thread_nodes(1), 1);
+    handle.flush();
+    assert_eq!(counter.total(), NODE_COUNTER_FLUSH + 6);
+    assert_eq!(
+        counter.nps(Duration::from_millis(500)),
+        2 * (NODE_COUNTER_FLUSH + 6)
+    );
+
+    counter.reset();
+    assert_eq!(counter.total(), 0);
+}
+
+#[test]
+fn test_node_counter_threads() {
+    let counter = NodeCounter::new(4);
+    let threads = (0..4)
+        .map(|thread| {
+            let mut handle = counter.handle(thread);
+            thread::spawn(move || {
+                for _ in 0..10_000 {
+                    handle.increment();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(counter.total(), 40_000);
+    assert_eq!(counter.get_thread_nodes(3), 10_000);
+}
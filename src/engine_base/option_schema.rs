@@ -0,0 +1,281 @@
+use engine::engine_option::EngineOption;
+use engine::option_type::OptionType;
+
+use super::engine_options::EngineOptions;
+
+/// An option declared once, with its name, default and range, that reads its own value back
+/// with the right type.  Declared as constants, such as
+/// `const HASH: SpinOption = SpinOption::new("Hash", 16, 1, 1024);`, they are added to the
+/// engine's options with `EngineOptions::builder()`, and read with `HASH.get(&options)`.
+pub trait TypedOption {
+    type Value;
+
+    fn get_name(&self) -> &'static str;
+
+    /// The option as the engine advertises it after `uci`.
+    fn to_engine_option(&self) -> EngineOption;
+
+    /// The option's value, or its default if `options` doesn't have it, or has it with
+    /// another type.
+    fn get(&self, options: &EngineOptions) -> Self::Value;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CheckOption {
+    name: &'static str,
+    default: bool,
+}
+
+impl CheckOption {
+    pub const fn new(name: &'static str, default: bool) -> CheckOption {
+        CheckOption { name, default }
+    }
+
+    pub fn get_default(&self) -> bool {
+        self.default
+    }
+}
+
+impl TypedOption for CheckOption {
+    type Value = bool;
+
+    fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn to_engine_option(&self) -> EngineOption {
+        EngineOption::new(self.name.to_string(), OptionType::Check(self.default))
+    }
+
+    fn get(&self, options: &EngineOptions) -> bool {
+        match options.get_option_type(self.name) {
+            Some(OptionType::Check(x)) => *x,
+            _ => self.default,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpinOption {
+    name: &'static str,
+    default: i64,
+    min: i64,
+    max: i64,
+}
+
+impl SpinOption {
+    pub const fn new(name: &'static str, default: i64, min: i64, max: i64) -> SpinOption {
+        SpinOption {
+            name,
+            default,
+            min,
+            max,
+        }
+    }
+
+    pub fn get_default(&self) -> i64 {
+        self.default
+    }
+
+    pub fn get_min(&self) -> i64 {
+        self.min
+    }
+
+    pub fn get_max(&self) -> i64 {
+        self.max
+    }
+}
+
+impl TypedOption for SpinOption {
+    type Value = i64;
+
+    fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn to_engine_option(&self) -> EngineOption {
+        EngineOption::new(
+            self.name.to_string(),
+            OptionType::Spin(self.default, self.min, self.max),
+        )
+    }
+
+    /// Kept within this declaration's range, whatever range `options` has.
+    fn get(&self, options: &EngineOptions) -> i64 {
+        match options.get_option_type(self.name) {
+            Some(OptionType::Spin(x, _, _)) => (*x).clamp(self.min, self.max),
+            _ => self.default,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ComboOption {
+    name: &'static str,
+    default: &'static str,
+    vars: &'static [&'static str],
+}
+
+impl ComboOption {
+    pub const fn new(
+        name: &'static str,
+        default: &'static str,
+        vars: &'static [&'static str],
+    ) -> ComboOption {
+        ComboOption {
+            name,
+            default,
+            vars,
+        }
+    }
+
+    pub fn get_default(&self) -> &'static str {
+        self.default
+    }
+
+    pub fn get_vars(&self) -> &'static [&'static str] {
+        self.vars
+    }
+}
+
+impl TypedOption for ComboOption {
+    type Value = String;
+
+    fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn to_engine_option(&self) -> EngineOption {
+        EngineOption::new(
+            self.name.to_string(),
+            OptionType::Combo(
+                self.default.to_string(),
+                self.vars.iter().map(|v| v.to_string()).collect(),
+            ),
+        )
+    }
+
+    fn get(&self, options: &EngineOptions) -> String {
+        match options.get_option_type(self.name) {
+            Some(OptionType::Combo(x, _)) => x.clone(),
+            _ => self.default.to_string(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StringOption {
+    name: &'static str,
+    default: &'static str,
+}
+
+impl StringOption {
+    pub const fn new(name: &'static str, default: &'static str) -> StringOption {
+        StringOption { name, default }
+    }
+
+    pub fn get_default(&self) -> &'static str {
+        self.default
+    }
+}
+
+impl TypedOption for StringOption {
+    type Value = String;
+
+    fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn to_engine_option(&self) -> EngineOption {
+        EngineOption::new(
+            self.name.to_string(),
+            OptionType::Str(self.default.to_string()),
+        )
+    }
+
+    fn get(&self, options: &EngineOptions) -> String {
+        match options.get_option_type(self.name) {
+            Some(OptionType::Str(x)) => x.clone(),
+            _ => self.default.to_string(),
+        }
+    }
+}
+
+/// Builds an engine's `EngineOptions` from typed declarations, which then advertise themselves
+/// after `uci`, in the order they were added, and check each `setoption` against their type and
+/// range.
+#[derive(Clone, Default)]
+pub struct OptionsBuilder {
+    options: EngineOptions,
+}
+
+impl OptionsBuilder {
+    pub fn new() -> OptionsBuilder {
+        OptionsBuilder::default()
+    }
+
+    pub fn option<O: TypedOption>(mut self, option: &O) -> Self {
+        let option = option.to_engine_option();
+        self.options
+            .insert(option.get_name().clone(), option.get_option_type().clone());
+        self
+    }
+
+    pub fn button(mut self, name: &str, f: fn() -> ()) -> Self {
+        self.options.create_button(name.to_string(), f);
+        self
+    }
+
+    pub fn build(self) -> EngineOptions {
+        self.options
+    }
+}
+
+#[cfg(test)]
+const HASH: SpinOption = SpinOption::new("Hash", 16, 1, 1024);
+#[cfg(test)]
+const OWN_BOOK: CheckOption = CheckOption::new("OwnBook", false);
+#[cfg(test)]
+const STYLE: ComboOption = ComboOption::new("Style", "Normal", &["Solid", "Normal", "Risky"]);
+#[cfg(test)]
+const BOOK_FILE: StringOption = StringOption::new("BookFile", "book.bin");
+
+#[cfg(test)]
+fn clear_hash() {}
+
+#[test]
+fn test_options_builder() {
+    let mut options = EngineOptions::builder()
+        .option(&HASH)
+        .option(&OWN_BOOK)
+        .option(&STYLE)
+        .button("Clear Hash", clear_hash)
+        .option(&BOOK_FILE)
+        .build();
+    assert_eq!(
+        options.to_string(),
+        "option name Hash type spin default 16 min 1 max 1024\n\
+         option name OwnBook type check default false\n\
+         option name Style type combo default Normal var Solid var Normal var Risky\n\
+         option name Clear Hash type button\n\
+         option name BookFile type string default book.bin\n"
+    );
+
+    assert_eq!(HASH.get(&options), 16);
+    assert_eq!(STYLE.get(&options), "Normal");
+    assert_eq!(options.set_option("Hash", Some("128")), Ok(()));
+    assert!(options.set_option("Hash", Some("2048")).is_err());
+    assert_eq!(options.set_option("OwnBook", Some("true")), Ok(()));
+    assert_eq!(options.set_option("Style", Some("Risky")), Ok(()));
+    assert!(options.set_option("Style", Some("Wild")).is_err());
+    assert_eq!(options.set_option("BookFile", Some("gm.bin")), Ok(()));
+    assert_eq!(HASH.get(&options), 128);
+    assert!(OWN_BOOK.get(&options));
+    assert_eq!(STYLE.get(&options), "Risky");
+    assert_eq!(BOOK_FILE.get(&options), "gm.bin");
+
+    // Options the engine doesn't have read as their defaults.
+    let empty = EngineOptions::default();
+    assert_eq!(HASH.get(&empty), 16);
+    assert_eq!(BOOK_FILE.get(&empty), "book.bin");
+}
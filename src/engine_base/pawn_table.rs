@@ -0,0 +1,113 @@
+use chess::BitBoard;
+
+#[cfg(test)]
+use chess::EMPTY;
+
+/// What the pawn structure of one position is worth, from white's point of view, along with
+/// the passed pawns, which the rest of the evaluation may want to look at again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PawnEntry {
+    key: u64,
+    score: i32,
+    passed: BitBoard,
+}
+
+impl PawnEntry {
+    pub fn new(key: u64, score: i32, passed: BitBoard) -> PawnEntry {
+        PawnEntry { key, score, passed }
+    }
+
+    pub fn get_key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn get_score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn get_passed(&self) -> BitBoard {
+        self.passed
+    }
+}
+
+/// A cache of pawn structure evaluations, keyed by `zobrist::pawn_hash`.  Pawns move rarely, so
+/// almost every probe hits, even in a small table.
+///
+/// Each searcher has its own evaluator, so the table isn't shared between threads.  It counts
+/// its probes and hits until `take_stats` hands them over to `SearchStats`.
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+    probes: u64,
+    hits: u64,
+}
+
+impl PawnTable {
+    /// A table of `len` entries, with at least one.
+    pub fn new(len: usize) -> PawnTable {
+        PawnTable {
+            entries: vec![None; len.max(1)],
+            probes: 0,
+            hits: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.entries.len() as u128) >> 64) as usize
+    }
+
+    pub fn probe(&mut self, key: u64) -> Option<PawnEntry> {
+        self.probes += 1;
+        match self.entries[self.index(key)] {
+            Some(entry) if entry.key == key => {
+                self.hits += 1;
+                Some(entry)
+            }
+            _ => None,
+        }
+    }
+
+    /// Always replaces whatever was in the entry's slot.
+    pub fn store(&mut self, entry: PawnEntry) {
+        let index = self.index(entry.key);
+        self.entries[index] = Some(entry);
+    }
+
+    /// The probes and hits counted since the last call.
+    pub fn take_stats(&mut self) -> (u64, u64) {
+        let stats = (self.probes, self.hits);
+        self.probes = 0;
+        self.hits = 0;
+        stats
+    }
+
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry = None;
+        }
+        self.probes = 0;
+        self.hits = 0;
+    }
+}
+
+#[test]
+fn test_pawn_table() {
+    let mut table = PawnTable::new(64);
+    // The pawnless key is 0, which has to be told apart from an empty slot.
+    assert_eq!(table.probe(0), None);
+    table.store(PawnEntry::new(0, 15, EMPTY));
+    assert_eq!(table.probe(0), Some(PawnEntry::new(0, 15, EMPTY)));
+    assert_eq!(table.probe(1), None);
+    assert_eq!(table.take_stats(), (3, 1));
+    assert_eq!(table.take_stats(), (0, 0));
+
+    table.clear();
+    assert_eq!(table.probe(0), None);
+}
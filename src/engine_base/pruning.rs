@@ -0,0 +1,251 @@
+use super::engine_options::EngineOptions;
+use super::eval::Eval;
+use chess::Piece;
+
+/// The spin option for the reverse futility margin, in centipawns per ply of depth.
+pub const REVERSE_FUTILITY_MARGIN: &str = "ReverseFutilityMargin";
+/// The spin option for the razoring margin, in centipawns per ply of depth.
+pub const RAZOR_MARGIN: &str = "RazorMargin";
+/// The spin option for the futility margin, in centipawns per ply of depth.
+pub const FUTILITY_MARGIN: &str = "FutilityMargin";
+/// The spin option for the delta pruning margin in the quiescence search, in centipawns.
+pub const DELTA_MARGIN: &str = "DeltaMargin";
+
+const DEFAULT_REVERSE_FUTILITY_MARGIN: i32 = 120;
+const DEFAULT_REVERSE_FUTILITY_DEPTH: i16 = 3;
+const DEFAULT_RAZOR_MARGIN: i32 = 350;
+const DEFAULT_RAZOR_DEPTH: i16 = 1;
+const DEFAULT_FUTILITY_MARGIN: i32 = 150;
+const DEFAULT_FUTILITY_DEPTH: i16 = 2;
+const DEFAULT_DELTA_MARGIN: i32 = 200;
+const MAX_MARGIN: i64 = 2000;
+
+/// How far the static evaluation has to be from the window before a shallow node is pruned
+/// without searching its moves.
+///
+/// Reverse futility pruning returns the static evaluation less `margin * depth` when even that
+/// is at least beta.  Razoring drops into the quiescence search when the static evaluation plus
+/// `margin * depth` is below alpha.  Futility pruning skips the quiet moves after the first when
+/// the static evaluation plus `margin * depth` can't reach alpha; captures, promotions and checks
+/// are still searched.  None of these is tried in check, at PV nodes, or when alpha or beta is a
+/// mate score, where the static evaluation says nothing.
+///
+/// Delta pruning skips captures in the quiescence search that can't bring the stand pat score
+/// up to alpha even with the captured piece and the margin added, unless the side to move is in
+/// check or the capture promotes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PruningMargins {
+    reverse_futility: i32,
+    reverse_futility_depth: i16,
+    razor: i32,
+    razor_depth: i16,
+    futility: i32,
+    futility_depth: i16,
+    delta: Option<i32>,
+}
+
+impl Default for PruningMargins {
+    fn default() -> Self {
+        PruningMargins {
+            reverse_futility: DEFAULT_REVERSE_FUTILITY_MARGIN,
+            reverse_futility_depth: DEFAULT_REVERSE_FUTILITY_DEPTH,
+            razor: DEFAULT_RAZOR_MARGIN,
+            razor_depth: DEFAULT_RAZOR_DEPTH,
+            futility: DEFAULT_FUTILITY_MARGIN,
+            futility_depth: DEFAULT_FUTILITY_DEPTH,
+            delta: Some(DEFAULT_DELTA_MARGIN),
+        }
+    }
+}
+
+impl PruningMargins {
+    pub fn new() -> PruningMargins {
+        PruningMargins::default()
+    }
+
+    /// No pruning at all, for a search that has to see everything.
+    pub fn disabled() -> PruningMargins {
+        PruningMargins {
+            reverse_futility_depth: 0,
+            razor_depth: 0,
+            futility_depth: 0,
+            delta: None,
+            ..PruningMargins::default()
+        }
+    }
+
+    /// Reverse futility pruning with `margin` per ply, at depths up to `max_depth`.
+    pub fn reverse_futility(mut self, margin: i32, max_depth: i16) -> PruningMargins {
+        self.reverse_futility = margin;
+        self.reverse_futility_depth = max_depth;
+        self
+    }
+
+    /// Razoring with `margin` per ply, at depths up to `max_depth`.
+    pub fn razor(mut self, margin: i32, max_depth: i16) -> PruningMargins {
+        self.razor = margin;
+        self.razor_depth = max_depth;
+        self
+    }
+
+    /// Futility pruning with `margin` per ply, at depths up to `max_depth`.
+    pub fn futility(mut self, margin: i32, max_depth: i16) -> PruningMargins {
+        self.futility = margin;
+        self.futility_depth = max_depth;
+        self
+    }
+
+    /// Delta pruning in the quiescence search with `margin`, or none.
+    pub fn delta(mut self, margin: Option<i32>) -> PruningMargins {
+        self.delta = margin;
+        self
+    }
+
+    /// Offer the margins as spin options, with the defaults.
+    pub fn create_options(options: &mut EngineOptions) {
+        options.create_spin(
+            REVERSE_FUTILITY_MARGIN.to_string(),
+            DEFAULT_REVERSE_FUTILITY_MARGIN as i64,
+            0,
+            MAX_MARGIN,
+        );
+        options.create_spin(
+            RAZOR_MARGIN.to_string(),
+            DEFAULT_RAZOR_MARGIN as i64,
+            0,
+            MAX_MARGIN,
+        );
+        options.create_spin(
+            FUTILITY_MARGIN.to_string(),
+            DEFAULT_FUTILITY_MARGIN as i64,
+            0,
+            MAX_MARGIN,
+        );
+        options.create_spin(
+            DELTA_MARGIN.to_string(),
+            DEFAULT_DELTA_MARGIN as i64,
+            0,
+            MAX_MARGIN,
+        );
+    }
+
+    /// The margins set in `options`, with the defaults for those the engine doesn't offer.
+    pub fn from_options(options: &EngineOptions) -> PruningMargins {
+        let spin = |name: &str, default: i32| {
+            if options.contains(name) {
+                options.get_spin(name).clamp(0, MAX_MARGIN) as i32
+            } else {
+                default
+            }
+        };
+        PruningMargins {
+            reverse_futility: spin(REVERSE_FUTILITY_MARGIN, DEFAULT_REVERSE_FUTILITY_MARGIN),
+            razor: spin(RAZOR_MARGIN, DEFAULT_RAZOR_MARGIN),
+            futility: spin(FUTILITY_MARGIN, DEFAULT_FUTILITY_MARGIN),
+            delta: Some(spin(DELTA_MARGIN, DEFAULT_DELTA_MARGIN)),
+            ..PruningMargins::default()
+        }
+    }
+
+    pub fn get_reverse_futility(&self) -> i32 {
+        self.reverse_futility
+    }
+
+    pub fn get_reverse_futility_depth(&self) -> i16 {
+        self.reverse_futility_depth
+    }
+
+    pub fn get_razor(&self) -> i32 {
+        self.razor
+    }
+
+    pub fn get_razor_depth(&self) -> i16 {
+        self.razor_depth
+    }
+
+    pub fn get_futility(&self) -> i32 {
+        self.futility
+    }
+
+    pub fn get_futility_depth(&self) -> i16 {
+        self.futility_depth
+    }
+
+    pub fn get_delta(&self) -> Option<i32> {
+        self.delta
+    }
+
+    /// Whether any of the main search's pruning is tried at `depth`.
+    pub fn prunes_at(&self, depth: i16) -> bool {
+        depth > 0
+            && (depth <= self.reverse_futility_depth
+                || depth <= self.razor_depth
+                || depth <= self.futility_depth)
+    }
+
+    /// The reverse futility margin at `depth`, or `None` if it isn't tried that deep.
+    pub fn reverse_futility_margin<E: Eval>(&self, depth: i16) -> Option<E> {
+        scaled(self.reverse_futility, depth, self.reverse_futility_depth)
+    }
+
+    /// The razoring margin at `depth`, or `None` if it isn't tried that deep.
+    pub fn razor_margin<E: Eval>(&self, depth: i16) -> Option<E> {
+        scaled(self.razor, depth, self.razor_depth)
+    }
+
+    /// The futility margin at `depth`, or `None` if it isn't tried that deep.
+    pub fn futility_margin<E: Eval>(&self, depth: i16) -> Option<E> {
+        scaled(self.futility, depth, self.futility_depth)
+    }
+
+    /// The delta pruning margin for a capture of `captured`, or `None` without delta pruning.
+    pub fn delta_margin<E: Eval>(&self, captured: Piece) -> Option<E> {
+        self.delta
+            .and_then(|delta| E::from(delta as i64 + piece_value(captured) as i64))
+    }
+}
+
+/// What `piece` is worth to delta pruning, in centipawns.
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 100,
+        Piece::Knight | Piece::Bishop => 325,
+        Piece::Rook => 500,
+        Piece::Queen => 900,
+        Piece::King => 0,
+    }
+}
+
+fn scaled<E: Eval>(margin: i32, depth: i16, max_depth: i16) -> Option<E> {
+    if depth > 0 && depth <= max_depth {
+        E::from(margin as i64 * depth as i64)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_pruning_margins() {
+    let margins = PruningMargins::new();
+    assert_eq!(margins.reverse_futility_margin::<i32>(2), Some(240));
+    assert_eq!(margins.reverse_futility_margin::<i32>(4), None);
+    assert_eq!(margins.razor_margin::<i32>(1), Some(350));
+    assert_eq!(margins.razor_margin::<i32>(2), None);
+    assert_eq!(margins.futility_margin::<i32>(2), Some(300));
+    assert_eq!(margins.delta_margin::<i32>(Piece::Rook), Some(700));
+    assert!(margins.prunes_at(3));
+    assert!(!margins.prunes_at(4));
+    assert_eq!(PruningMargins::disabled().reverse_futility_margin::<i32>(1), None);
+    assert_eq!(PruningMargins::disabled().delta_margin::<i32>(Piece::Queen), None);
+    assert!(!PruningMargins::disabled().prunes_at(1));
+
+    let mut options = EngineOptions::default();
+    assert_eq!(PruningMargins::from_options(&options), margins);
+    PruningMargins::create_options(&mut options);
+    options.set_option(RAZOR_MARGIN, Some("500")).unwrap();
+    options.set_option(DELTA_MARGIN, Some("0")).unwrap();
+    assert_eq!(
+        PruningMargins::from_options(&options),
+        margins.razor(500, 1).delta(Some(0))
+    );
+}
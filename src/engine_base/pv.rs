@@ -1,29 +1,25 @@
 use crate::engine::best_move::BestMove;
-use arrayvec::ArrayVec;
-use chess::ChessMove;
-use nodrop::NoDrop;
+use chess::{Board, ChessMove};
 use std::iter::IntoIterator;
 use std::ops::Index;
 
-const MAX_PLY: usize = 512;
-
+// Most PVs are a handful of moves, so a PV only allocates once it has moves in it, and `update`
+// reuses whatever it has already allocated.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Pv {
-    pv: NoDrop<ArrayVec<[ChessMove; MAX_PLY]>>,
+    pv: Vec<ChessMove>,
 }
 
 impl Pv {
     pub fn new() -> Pv {
-        Pv {
-            pv: NoDrop::new(ArrayVec::new()),
-        }
+        Pv { pv: Vec::new() }
     }
 
     pub fn update(&mut self, chess_move: ChessMove, other: &Pv) {
         self.clear();
+        self.pv.reserve(other.len() + 1);
         self.push(chess_move);
-        for x in other.pv.iter() {
-            self.push(*x);
-        }
+        self.pv.extend_from_slice(&other.pv);
     }
 
     pub fn clear(&mut self) {
@@ -37,6 +33,67 @@ impl Pv {
     pub fn len(&self) -> usize {
         self.pv.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.pv.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[ChessMove] {
+        &self.pv
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.pv.truncate(len);
+    }
+
+    /// Play the moves from `board`, giving each move along with the position after it.  Stops at
+    /// the first illegal move, such as one that came from a hash collision.
+    pub fn walk(&self, board: Board) -> PvWalk<'_> {
+        PvWalk {
+            moves: &self.pv,
+            board,
+        }
+    }
+
+    pub fn is_legal_from(&self, board: Board) -> bool {
+        self.walk(board).count() == self.len()
+    }
+
+    /// Drop everything from the first illegal move onwards.
+    pub fn truncate_illegal(&mut self, board: Board) {
+        let legal = self.walk(board).count();
+        self.truncate(legal);
+    }
+
+    /// The best move, with the reply we expect to ponder on if the PV has a legal one.  The PV
+    /// must not be empty.
+    pub fn to_best_move(&self, board: Board) -> BestMove {
+        let mut walk = self.walk(board);
+        match (walk.next(), walk.next()) {
+            (Some((m, _)), Some((ponder, _))) => BestMove::new_with_ponder(m, ponder),
+            _ => BestMove::new(self[0]),
+        }
+    }
+}
+
+pub struct PvWalk<'a> {
+    moves: &'a [ChessMove],
+    board: Board,
+}
+
+impl<'a> Iterator for PvWalk<'a> {
+    type Item = (ChessMove, Board);
+
+    fn next(&mut self) -> Option<(ChessMove, Board)> {
+        let (&m, rest) = self.moves.split_first()?;
+        if !self.board.legal(m) {
+            self.moves = &[];
+            return None;
+        }
+        self.moves = rest;
+        self.board = self.board.make_move_new(m);
+        Some((m, self.board))
+    }
 }
 
 impl Index<usize> for Pv {
@@ -49,18 +106,10 @@ impl Index<usize> for Pv {
 
 impl IntoIterator for Pv {
     type Item = ChessMove;
-    type IntoIter = ::arrayvec::IntoIter<[ChessMove; MAX_PLY]>;
+    type IntoIter = ::std::vec::IntoIter<ChessMove>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.pv.clone().into_iter()
-    }
-}
-
-impl Clone for Pv {
-    fn clone(&self) -> Self {
-        Pv {
-            pv: NoDrop::new(self.pv.clone()),
-        }
+        self.pv.into_iter()
     }
 }
 
@@ -92,3 +141,50 @@ fn update() {
     assert_eq!(pv1[0], e2e4);
     assert_eq!(pv1[1], d7d5);
 }
+
+#[test]
+fn walk_stops_at_illegal_move() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let e7e5 = ChessMove::new(Square::E7, Square::E5, None);
+    let mut pv = Pv::new();
+    pv.push(e2e4);
+    pv.push(e7e5);
+    pv.push(e7e5);
+
+    let walked = pv.walk(Board::default()).collect::<Vec<(ChessMove, Board)>>();
+    assert_eq!(walked.len(), 2);
+    assert_eq!(walked[1].1, Board::default().make_move_new(e2e4).make_move_new(e7e5));
+
+    assert!(!pv.is_legal_from(Board::default()));
+    pv.truncate_illegal(Board::default());
+    assert_eq!(pv.len(), 2);
+    assert!(pv.is_legal_from(Board::default()));
+}
+
+#[test]
+fn pv_longer_than_512_plies() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let mut pv = Pv::new();
+    for _ in 0..1000 {
+        let child = pv.clone();
+        pv.update(e2e4, &child);
+    }
+    assert_eq!(pv.len(), 1000);
+}
+
+#[test]
+fn to_best_move_validates_ponder() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let e7e5 = ChessMove::new(Square::E7, Square::E5, None);
+    let mut pv = Pv::new();
+    pv.push(e2e4);
+    assert_eq!(pv.to_best_move(Board::default()), BestMove::new(e2e4));
+
+    pv.push(e7e5);
+    assert_eq!(pv.to_best_move(Board::default()), BestMove::new_with_ponder(e2e4, e7e5));
+
+    // White can't answer its own move.
+    pv.truncate(1);
+    pv.push(ChessMove::new(Square::D2, Square::D4, None));
+    assert_eq!(pv.to_best_move(Board::default()), BestMove::new(e2e4));
+}
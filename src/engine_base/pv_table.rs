@@ -0,0 +1,129 @@
+use super::pv::Pv;
+use super::resettable::Resettable;
+use chess::ChessMove;
+
+pub const DEFAULT_MAX_PLY: usize = 128;
+
+/// A triangular PV table: row `ply` holds the best line found from that ply, and is at most
+/// `max_ply - ply` moves long.  The whole table is allocated once, so backing a line up from a
+/// child node is a copy within the table rather than a new `Pv` per node.
+pub struct PvTable {
+    max_ply: usize,
+    moves: Vec<ChessMove>,
+    lengths: Vec<usize>,
+}
+
+impl PvTable {
+    pub fn new(max_ply: usize) -> PvTable {
+        PvTable {
+            max_ply,
+            moves: vec![ChessMove::default(); max_ply * (max_ply + 1) / 2],
+            lengths: vec![0; max_ply],
+        }
+    }
+
+    fn row(&self, ply: usize) -> usize {
+        ply * (2 * self.max_ply - ply + 1) / 2
+    }
+
+    pub fn get_max_ply(&self) -> usize {
+        self.max_ply
+    }
+
+    /// Forget the line at `ply`, as a new node is being searched there.
+    pub fn clear(&mut self, ply: usize) {
+        if ply < self.max_ply {
+            self.lengths[ply] = 0;
+        }
+    }
+
+    /// The line at `ply` becomes `chess_move` followed by the line at `ply + 1`.  Lines past
+    /// `max_ply` are cut short.
+    pub fn update(&mut self, ply: usize, chess_move: ChessMove) {
+        if ply >= self.max_ply {
+            return;
+        }
+
+        let row = self.row(ply);
+        self.moves[row] = chess_move;
+        let child_len = if ply + 1 < self.max_ply {
+            self.lengths[ply + 1]
+        } else {
+            0
+        };
+        if child_len > 0 {
+            let child = self.row(ply + 1);
+            self.moves.copy_within(child..child + child_len, row + 1);
+        }
+        self.lengths[ply] = child_len + 1;
+    }
+
+    pub fn get_line(&self, ply: usize) -> &[ChessMove] {
+        if ply >= self.max_ply {
+            return &[];
+        }
+        let row = self.row(ply);
+        &self.moves[row..row + self.lengths[ply]]
+    }
+
+    /// Copy the line at `ply` out of the table.
+    pub fn copy_into(&self, ply: usize, pv: &mut Pv) {
+        pv.clear();
+        for m in self.get_line(ply).iter() {
+            pv.push(*m);
+        }
+    }
+}
+
+impl Resettable for PvTable {
+    fn reset(&mut self) {
+        for length in self.lengths.iter_mut() {
+            *length = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+
+#[test]
+fn pv_table_backs_up_lines() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let e7e5 = ChessMove::new(Square::E7, Square::E5, None);
+    let g1f3 = ChessMove::new(Square::G1, Square::F3, None);
+
+    let mut table = PvTable::new(4);
+    table.clear(2);
+    table.update(2, g1f3);
+    table.clear(1);
+    table.update(1, e7e5);
+    table.clear(0);
+    table.update(0, e2e4);
+    assert_eq!(table.get_line(0), &[e2e4, e7e5, g1f3]);
+    assert_eq!(table.get_line(1), &[e7e5, g1f3]);
+
+    // A new node at ply 1 throws away what was below it.
+    table.clear(1);
+    table.update(0, e2e4);
+    assert_eq!(table.get_line(0), &[e2e4]);
+
+    let mut pv = Pv::new();
+    table.copy_into(0, &mut pv);
+    assert_eq!(pv.len(), 1);
+
+    table.reset();
+    assert_eq!(table.get_line(0), &[] as &[ChessMove]);
+}
+
+#[test]
+fn pv_table_cuts_off_at_max_ply() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+
+    let mut table = PvTable::new(2);
+    for ply in (0..4).rev() {
+        table.clear(ply);
+        table.update(ply, e2e4);
+    }
+    assert_eq!(table.get_line(0).len(), 2);
+    assert_eq!(table.get_line(3), &[] as &[ChessMove]);
+}
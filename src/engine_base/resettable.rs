@@ -0,0 +1,10 @@
+/// Something holding state that belongs to one game, and must be forgotten on `ucinewgame` so the
+/// next game of a match isn't searched with what was learned in the last one.
+///
+/// Every search component implements this; components with nothing to forget can keep the
+/// default, which does nothing.
+pub trait Resettable {
+    /// Forget everything learned from earlier searches.  Settings, such as evaluation weights
+    /// or options from the GUI, are kept.
+    fn reset(&mut self) {}
+}
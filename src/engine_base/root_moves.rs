@@ -0,0 +1,186 @@
+use super::eval::Eval;
+use chess::{Board, ChessMove, MoveGen};
+
+/// Centipawns the best root move has to be ahead of every other move to be an easy move.
+pub const EASY_MOVE_MARGIN: i16 = 150;
+/// Iterations the best root move has to stay the same before it can be an easy move.
+pub const EASY_MOVE_ITERATIONS: u16 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RootMove<E: Eval> {
+    chess_move: ChessMove,
+    score: E,
+}
+
+impl<E: Eval> RootMove<E> {
+    pub fn get_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// The score from the last iteration that reached this move, or `E::null()`.  Only the best
+    /// move's score is exact; the others may be upper bounds.
+    pub fn get_score(&self) -> E {
+        self.score
+    }
+}
+
+/// The legal moves at the root, kept in the order the next iteration should search them: best
+/// first, as scored by the previous iteration.
+pub struct RootMoves<E: Eval> {
+    moves: Vec<RootMove<E>>,
+    margin: E,
+    best_move: Option<ChessMove>,
+    stable_iterations: u16,
+}
+
+impl<E: Eval> RootMoves<E> {
+    pub fn new(board: &Board) -> RootMoves<E> {
+        RootMoves::with_margin(board, E::from(EASY_MOVE_MARGIN).expect("Margin in range."))
+    }
+
+    pub fn with_margin(board: &Board, margin: E) -> RootMoves<E> {
+        RootMoves {
+            moves: MoveGen::new_legal(board)
+                .map(|chess_move| RootMove {
+                    chess_move,
+                    score: E::null(),
+                })
+                .collect(),
+            margin,
+            best_move: None,
+            stable_iterations: 0,
+        }
+    }
+
+    /// Only search the moves in `search_moves`, as for `go searchmoves`.  Nothing changes if
+    /// none of them are legal, so that there is always a move to play.
+    pub fn restrict(&mut self, search_moves: &[ChessMove]) {
+        if self.moves.iter().any(|m| search_moves.contains(&m.chess_move)) {
+            self.moves.retain(|m| search_moves.contains(&m.chess_move));
+        }
+    }
+
+    pub fn get_moves(&self) -> &[RootMove<E>] {
+        &self.moves
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// How far below the best score the other moves have to be for an easy move.
+    pub fn get_margin(&self) -> E {
+        self.margin
+    }
+
+    /// Record the score the current iteration gave to the move at `index`.
+    pub fn record(&mut self, index: usize, score: E) {
+        self.moves[index].score = score;
+    }
+
+    /// Sort the moves for the next iteration, and keep track of whether the best move changed.
+    pub fn finish_iteration(&mut self) {
+        // Stable, so moves that scored the same keep their order.
+        self.moves.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        });
+
+        let best = self.moves.first().map(|m| m.chess_move);
+        if best.is_some() && best == self.best_move {
+            self.stable_iterations += 1;
+        } else {
+            self.stable_iterations = 0;
+        }
+        self.best_move = best;
+    }
+
+    pub fn get_best_move(&self) -> Option<ChessMove> {
+        self.best_move
+    }
+
+    /// How many iterations in a row have agreed on the best move.
+    pub fn get_stable_iterations(&self) -> u16 {
+        self.stable_iterations
+    }
+
+    /// The position leaves no real choice: there is only one legal move, or the best move has
+    /// been the same for a while and is well ahead of the rest.
+    pub fn is_easy_move(&self) -> bool {
+        if self.moves.len() == 1 {
+            return true;
+        }
+        if self.stable_iterations < EASY_MOVE_ITERATIONS {
+            return false;
+        }
+
+        let best = self.moves[0].score;
+        if best == E::null() || best.depth_to_mate().is_some() {
+            return false;
+        }
+        self.moves[1..]
+            .iter()
+            .all(|m| m.score != E::null() && m.score <= best - self.margin)
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use std::str::FromStr;
+
+#[test]
+fn root_moves_sort_and_stability() {
+    let mut root_moves = RootMoves::<i32>::new(&Board::default());
+    assert_eq!(root_moves.len(), 20);
+
+    let last = root_moves.get_moves()[19].get_move();
+    for iteration in 0..4 {
+        for index in 0..root_moves.len() {
+            let score = if root_moves.get_moves()[index].get_move() == last {
+                300
+            } else {
+                10
+            };
+            root_moves.record(index, score);
+        }
+        root_moves.finish_iteration();
+        assert_eq!(root_moves.get_best_move(), Some(last));
+        assert_eq!(root_moves.get_stable_iterations(), iteration);
+        assert_eq!(root_moves.is_easy_move(), iteration >= EASY_MOVE_ITERATIONS);
+    }
+
+    // A close second means it's no longer easy.
+    root_moves.record(1, 200);
+    root_moves.finish_iteration();
+    assert!(!root_moves.is_easy_move());
+}
+
+#[test]
+fn root_moves_single_legal_move() {
+    let board = Board::from_str("1r5k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    assert!(RootMoves::<i32>::new(&board).is_easy_move());
+}
+
+#[test]
+fn root_moves_restrict() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+    let mut root_moves = RootMoves::<i32>::new(&Board::default());
+    root_moves.restrict(&[]);
+    assert_eq!(root_moves.len(), 20);
+
+    root_moves.restrict(&[d2d4, ChessMove::new(Square::E2, Square::E5, None), e2e4]);
+    let moves = root_moves.get_moves().iter().map(|m| m.get_move()).collect::<Vec<ChessMove>>();
+    assert_eq!(moves.len(), 2);
+    assert!(moves.contains(&e2e4) && moves.contains(&d2d4));
+
+    // Nothing legal to restrict to.
+    root_moves.restrict(&[ChessMove::new(Square::E2, Square::E5, None)]);
+    assert_eq!(root_moves.len(), 2);
+}
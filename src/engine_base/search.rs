@@ -2,18 +2,100 @@ use std::marker::PhantomData;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use chess::{Board, Color, MoveGen};
+use chess::{Board, ChessMove, Color, MoveGen, Piece, EMPTY};
 
 use super::eval::Eval;
+use super::eval_params::EvalParams;
+use super::node_counter::NodeCounterHandle;
+use game_result::{game_result, is_insufficient_material, GameResult};
 use super::evaluate::Evaluate;
 use super::pv::Pv;
+use super::pruning::PruningMargins;
+use super::pv_table::{PvTable, DEFAULT_MAX_PLY};
+use super::resettable::Resettable;
+use super::root_moves::RootMoves;
+use super::search_limits::SearchLimits;
+use super::search_result::SearchResult;
+use super::search_stats::SearchStats;
+use super::search_trace::{PruneReason, SearchTrace};
+use super::transposition_table::TranspositionTable;
+use super::tt_entry::TtEntry;
 use super::search_window::{AlphaBetaSearchParams, SearchParams};
 
-//use super::tt_entry::TtEntry;
+// Neither a capture nor a promotion.
+pub(crate) fn is_quiet(board: &Board, m: ChessMove) -> bool {
+    let en_passant = board.piece_on(m.get_source()) == Some(Piece::Pawn)
+        && m.get_source().get_file() != m.get_dest().get_file();
+    board.piece_on(m.get_dest()).is_none() && m.get_promotion().is_none() && !en_passant
+}
+
+pub trait Search<E: Eval>: Resettable {
+    fn search(&mut self, board: Board, alpha: E, beta: E, depth: i16) -> SearchResult<E>;
+
+    /// Search the root moves in the order given, recording each move's score.  Searchers that
+    /// can't score each move only record their best one, and may play a move that was left out
+    /// of `root_moves`.
+    fn search_root(
+        &mut self,
+        board: Board,
+        root_moves: &mut RootMoves<E>,
+        alpha: E,
+        beta: E,
+        depth: i16,
+    ) -> SearchResult<E> {
+        let result = self.search(board, alpha, beta, depth);
+        if let Some(best) = result.get_best_move() {
+            if let Some(index) = root_moves.get_moves().iter().position(|m| m.get_move() == best) {
+                root_moves.record(index, result.get_score());
+            }
+        }
+        result
+    }
 
-pub trait Search<E: Eval> {
-    fn search(&mut self, board: Board, alpha: E, beta: E, depth: i16) -> E;
+    /// The PV of the last search, which a search on another thread may already be replacing.
+    #[deprecated(note = "use the PV of the `SearchResult` the search returns")]
     fn get_pv(&self) -> &Pv;
+    /// What happened during the last `search`.
+    fn get_stats(&self) -> &SearchStats;
+
+    /// Setting this flag makes a running search return `E::null()` as soon as it can.
+    fn get_stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        None
+    }
+
+    /// Limits for the searches that follow, until they are set again.  A search past its node
+    /// budget returns `E::null()`, like a stopped one.
+    fn set_limits(&mut self, _limits: SearchLimits) {}
+
+    /// Margins for pruning shallow nodes, for searchers that prune.
+    fn set_margins(&mut self, _margins: PruningMargins) {}
+
+    /// New evaluation weights, passed on to the evaluator.
+    fn set_eval_params(&mut self, _params: &EvalParams) {}
+
+    /// The evaluator's score for `board`, for white, without searching.  `None` for searchers
+    /// that don't have an evaluator to ask.
+    fn evaluate(&mut self, _board: Board) -> Option<E> {
+        None
+    }
+
+    /// Record the tree of each search that follows, keeping up to `max_nodes` nodes, or stop
+    /// recording with `None`.  Searchers that can't trace ignore this.
+    fn set_trace(&mut self, _max_nodes: Option<usize>) {}
+
+    /// The tree of the last search, if it was traced.
+    fn take_trace(&mut self) -> Option<SearchTrace> {
+        None
+    }
+}
+
+// A score as a trace keeps it, or `None` for a search that was cut short.
+fn trace_score<E: Eval>(score: E) -> Option<i64> {
+    if score == E::null() {
+        None
+    } else {
+        score.to_i64()
+    }
 }
 
 pub struct DefaultSearch<E: Eval, V: Evaluate<E>> {
@@ -21,6 +103,19 @@ pub struct DefaultSearch<E: Eval, V: Evaluate<E>> {
     stopping: Arc<AtomicBool>,
     phantom: PhantomData<E>,
     pv: Pv,
+    pv_table: PvTable,
+    stats: SearchStats,
+    limits: SearchLimits,
+    margins: PruningMargins,
+    nodes_searched: u64,
+    out_of_nodes: bool,
+    node_counter: Option<NodeCounterHandle>,
+    tt: Option<Arc<TranspositionTable>>,
+    prefetch: bool,
+    trace_nodes: Option<usize>,
+    trace: Option<SearchTrace>,
+    // Why the node being left was pruned, for the trace.
+    pruned: Option<PruneReason>,
 }
 
 impl<E: Eval, V: Evaluate<E>> DefaultSearch<E, V> {
@@ -30,15 +125,221 @@ impl<E: Eval, V: Evaluate<E>> DefaultSearch<E, V> {
             stopping: stopping,
             phantom: PhantomData,
             pv: Pv::new(),
+            pv_table: PvTable::new(DEFAULT_MAX_PLY),
+            stats: SearchStats::new(),
+            limits: SearchLimits::new(),
+            margins: PruningMargins::new(),
+            nodes_searched: 0,
+            out_of_nodes: false,
+            node_counter: None,
+            tt: None,
+            prefetch: true,
+            trace_nodes: None,
+            trace: None,
+            pruned: None,
         }
     }
 
-    pub fn qsearch(&mut self, sp: &mut impl SearchParams<E>) -> E {
-        let stand_pat = if sp.board().side_to_move() == Color::White {
+    /// Count nodes into `handle` as well, as one of several threads searching the same position.
+    /// The node limit then holds for every thread's nodes together.
+    pub fn node_counter(mut self, handle: NodeCounterHandle) -> Self {
+        self.node_counter = Some(handle);
+        self
+    }
+
+    /// Keep what the search learns in `tt`, which other searchers may share.  Nodes off the PV
+    /// are settled by its entries when they are deep enough.  The table's owner starts each new
+    /// search with `TranspositionTable::new_search`.
+    pub fn transposition_table(mut self, tt: Arc<TranspositionTable>) -> Self {
+        self.tt = Some(tt);
+        self
+    }
+
+    /// Whether to prefetch each child's table entry before searching it, as is done unless this
+    /// turns it off, for measuring what it gains.
+    pub fn prefetch(mut self, prefetch: bool) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
+    fn probe_tt(&mut self, key: u64) -> Option<TtEntry<E>> {
+        let entry = self.tt.as_ref()?.probe(key);
+        self.stats.tt_probe(entry.is_some());
+        entry
+    }
+
+    fn store_tt(&self, key: u64, entry: TtEntry<E>) {
+        if let Some(ref tt) = self.tt {
+            tt.store(key, &entry);
+        }
+    }
+
+    // Count a new node at `ply`, unless the node budget is spent, in which case the search has to
+    // stop.
+    fn enter_node(&mut self, ply: usize, qsearch: bool) -> bool {
+        let nodes = match self.node_counter {
+            Some(ref counter) => counter.get_total(),
+            None => self.nodes_searched,
+        };
+        if self.limits.nodes_exhausted(nodes) {
+            self.out_of_nodes = true;
+            return false;
+        }
+        self.nodes_searched += 1;
+        if let Some(ref mut counter) = self.node_counter {
+            counter.increment();
+        }
+        self.stats.reach_ply(ply);
+        if qsearch {
+            self.stats.qsearch_node();
+        } else {
+            self.stats.node();
+        }
+        true
+    }
+
+    fn should_stop(&self) -> bool {
+        self.out_of_nodes || self.stopping.load(Ordering::Relaxed)
+    }
+
+    // The score of the child node reached by `m` from this node's side, or `None` if the search
+    // was cut short while in it.  A cut short search scores `E::null()`, which can't be negated.
+    fn search_child(&mut self, m: ChessMove, child: &mut impl SearchParams<E>) -> Option<E> {
+        if let (Some(tt), true) = (self.tt.as_ref(), self.prefetch && child.depth() > 0) {
+            tt.prefetch(child.key());
+        }
+        if let Some(ref mut trace) = self.trace {
+            trace.push_move(m);
+        }
+        let score = self.search_line(child);
+        if let Some(ref mut trace) = self.trace {
+            trace.pop_move();
+        }
+        if self.should_stop() {
+            None
+        } else {
+            Some(-score)
+        }
+    }
+
+    fn qsearch_child(&mut self, m: ChessMove, child: &mut impl SearchParams<E>) -> Option<E> {
+        if let Some(ref mut trace) = self.trace {
+            trace.push_move(m);
+        }
+        let score = self.qsearch(child);
+        if let Some(ref mut trace) = self.trace {
+            trace.pop_move();
+        }
+        if self.should_stop() {
+            None
+        } else {
+            Some(-score)
+        }
+    }
+
+    // Start tracing a node, if this search is traced.  Returns whether it needs `trace_exit`.
+    fn trace_enter(&mut self, sp: &impl SearchParams<E>, qsearch: bool) -> bool {
+        match self.trace {
+            Some(ref mut trace) => {
+                let alpha = sp.alpha().to_i64().unwrap_or(0);
+                let beta = sp.beta().to_i64().unwrap_or(0);
+                trace.enter(sp.depth(), alpha, beta, qsearch)
+            }
+            None => false,
+        }
+    }
+
+    fn trace_exit(&mut self, entered: bool, score: E) {
+        // Taken even for a node that wasn't kept, so it can't end up on its parent.
+        let pruned = self.pruned.take();
+        if let (true, Some(trace)) = (entered, self.trace.as_mut()) {
+            trace.exit(trace_score(score), pruned);
+        }
+    }
+
+    fn trace_skip(&mut self, m: ChessMove, sp: &impl SearchParams<E>, reason: PruneReason) {
+        if let Some(ref mut trace) = self.trace {
+            let alpha = sp.alpha().to_i64().unwrap_or(0);
+            let beta = sp.beta().to_i64().unwrap_or(0);
+            trace.skip(m, sp.depth() - 1, -beta, -alpha, reason);
+        }
+    }
+
+    // Get ready for a new search from the root.
+    fn start(&mut self) {
+        self.stats.clear();
+        self.trace = self.trace_nodes.map(SearchTrace::new);
+    }
+
+    fn finish(&mut self, score: E) -> SearchResult<E> {
+        self.evaluator.record_stats(&mut self.stats);
+        if let Some(ref mut counter) = self.node_counter {
+            counter.flush();
+        }
+        SearchResult::new(score, self.pv.clone(), self.stats)
+    }
+
+    /// Nodes visited since the limits were last set.
+    pub fn get_nodes_searched(&self) -> u64 {
+        self.nodes_searched
+    }
+
+    // The evaluation from the side to move's point of view.
+    fn static_eval(&mut self, sp: &mut impl SearchParams<E>) -> E {
+        let sign = if sp.board().side_to_move() == Color::White {
             E::one()
         } else {
             -E::one()
-        } * self.evaluator.evaluate(sp);
+        };
+        sign * self.evaluator.evaluate(sp)
+    }
+
+    // Whether the static evaluation can be trusted to prune this node: not in check, not at a
+    // PV node, and with no mate score in the window.
+    fn may_prune(&self, sp: &impl SearchParams<E>) -> bool {
+        !sp.is_pv()
+            && *sp.board().checkers() == EMPTY
+            && sp.alpha().depth_to_mate().is_none()
+            && sp.beta().depth_to_mate().is_none()
+    }
+
+    // Reverse futility pruning and razoring, which settle a shallow node from its static
+    // evaluation.  `None` if the node has to be searched.
+    fn prune(&mut self, sp: &mut impl SearchParams<E>, eval: E) -> Option<E> {
+        let depth = sp.depth();
+        if let Some(margin) = self.margins.reverse_futility_margin::<E>(depth) {
+            if eval - margin >= sp.beta() {
+                self.pruned = Some(PruneReason::ReverseFutility);
+                return Some(eval - margin);
+            }
+        }
+        if let Some(margin) = self.margins.razor_margin::<E>(depth) {
+            if eval + margin < sp.alpha() {
+                let score = self.qsearch_node(sp);
+                self.pruned = Some(PruneReason::Razoring);
+                return Some(score);
+            }
+        }
+        None
+    }
+
+    pub fn qsearch(&mut self, sp: &mut impl SearchParams<E>) -> E {
+        let traced = self.trace_enter(sp, true);
+        let score = self.qsearch_node(sp);
+        self.trace_exit(traced, score);
+        score
+    }
+
+    fn qsearch_node(&mut self, sp: &mut impl SearchParams<E>) -> E {
+        sp.clear_pv(&mut self.pv_table);
+        if !self.enter_node(sp.ply(), true) {
+            return E::null();
+        }
+        if is_insufficient_material(sp.board()) {
+            self.pruned = Some(PruneReason::InsufficientMaterial);
+            return E::zero();
+        }
+        let stand_pat = self.static_eval(sp);
 
         if stand_pat >= sp.beta() {
             return sp.beta().add_depth(1);
@@ -47,20 +348,36 @@ impl<E: Eval, V: Evaluate<E>> DefaultSearch<E, V> {
         if stand_pat > sp.alpha() {
             sp.set_alpha(stand_pat);
         }
+        let delta_pruning =
+            *sp.board().checkers() == EMPTY && sp.alpha().depth_to_mate().is_none();
 
         let mut movegen = MoveGen::new_legal(sp.board());
         let targets = sp.board().color_combined(!sp.board().side_to_move());
         movegen.set_iterator_mask(*targets);
 
-        for m in movegen {
+        for (index, m) in movegen.enumerate() {
+            if delta_pruning && m.get_promotion().is_none() {
+                // Only en passant captures onto an empty square.
+                let captured = sp.board().piece_on(m.get_dest()).unwrap_or(Piece::Pawn);
+                if let Some(delta) = self.margins.delta_margin::<E>(captured) {
+                    if stand_pat + delta < sp.alpha() {
+                        self.trace_skip(m, sp, PruneReason::Delta);
+                        continue;
+                    }
+                }
+            }
             let mut child_search = sp.lower_depth(m);
-            let score = -self.qsearch(&mut child_search);
+            let score = match self.qsearch_child(m, &mut child_search) {
+                Some(score) => score,
+                None => return E::null(),
+            };
             if score >= sp.beta() {
+                self.stats.beta_cutoff(index);
                 return sp.beta().add_depth(1);
             }
             if score > sp.alpha() {
-                sp.set_alpha(sp.alpha());
-                sp.update_pv(m, child_search);
+                sp.set_alpha(score);
+                sp.update_pv(m, &mut self.pv_table);
             }
         }
 
@@ -71,72 +388,297 @@ impl<E: Eval, V: Evaluate<E>> DefaultSearch<E, V> {
         if sp.depth() <= 0 {
             return self.qsearch(sp);
         }
+        let traced = self.trace_enter(sp, false);
+        let score = self.search_node(sp);
+        self.trace_exit(traced, score);
+        score
+    }
+
+    fn search_node(&mut self, sp: &mut impl SearchParams<E>) -> E {
+        sp.clear_pv(&mut self.pv_table);
+        if !self.enter_node(sp.ply(), false) {
+            return E::null();
+        }
+        // Nothing to search for when neither side can win.
+        if is_insufficient_material(sp.board()) {
+            self.pruned = Some(PruneReason::InsufficientMaterial);
+            return E::zero();
+        }
+        let depth = sp.depth();
+        let (key, alpha) = (sp.key(), sp.alpha());
+        if let Some(entry) = self.probe_tt(key) {
+            // Settling a PV node from the table would cut its PV short.
+            if let (false, Some((score, _))) =
+                (sp.is_pv(), entry.skip_search(depth, alpha, sp.beta()))
+            {
+                self.pruned = Some(PruneReason::TranspositionTable);
+                return score;
+            }
+        }
+        let eval = if self.margins.prunes_at(depth) && self.may_prune(sp) {
+            Some(self.static_eval(sp))
+        } else {
+            None
+        };
+        if let Some(eval) = eval {
+            if let Some(score) = self.prune(sp, eval) {
+                return score;
+            }
+        }
+        // Too far below alpha for a quiet move to bring the score back up.
+        let futile = match (eval, self.margins.futility_margin::<E>(depth)) {
+            (Some(eval), Some(margin)) => eval + margin <= sp.alpha(),
+            _ => false,
+        };
 
         let mut movegen = MoveGen::new_legal(sp.board());
         let mut best_score;
+        let mut best_move;
         if let Some(first_move) = movegen.next() {
             let mut child_search = sp.lower_depth(first_move);
-            best_score = -self.search_line(&mut child_search);
+            best_score = match self.search_child(first_move, &mut child_search) {
+                Some(score) => score,
+                None => return E::null(),
+            };
+            best_move = first_move;
             if best_score > sp.alpha() {
-                sp.update_pv(first_move, child_search);
+                sp.update_pv(first_move, &mut self.pv_table);
 
                 if best_score >= sp.beta() {
-                    return best_score.add_depth(1);
+                    self.stats.beta_cutoff(0);
+                    let score = best_score.add_depth(1);
+                    self.store_tt(key, TtEntry::new_min(score, depth, first_move));
+                    return score;
                 }
                 sp.set_alpha(best_score);
             }
         } else {
-            return E::new_mate(0, Color::White);
+            // Scores are from the side to move's point of view, so being mated is always the
+            // lowest score there is.
+            return match game_result(sp.board()) {
+                Some(GameResult::Win(_)) => E::new_mate(0, Color::Black),
+                _ => E::zero(),
+            };
         }
 
-        for m in movegen {
+        for (index, m) in movegen.enumerate() {
             let mut child_search_zw = sp.lower_depth_into_null_window(m);
-            let mut score = -self.search_line(&mut child_search_zw);
+            if futile && is_quiet(sp.board(), m) && *child_search_zw.board().checkers() == EMPTY {
+                self.trace_skip(m, sp, PruneReason::Futility);
+                continue;
+            }
+            let mut score = match self.search_child(m, &mut child_search_zw) {
+                Some(score) => score,
+                None => return E::null(),
+            };
 
             if score > sp.alpha() && score < sp.beta() {
                 let mut child_search = sp.lower_depth(m);
-                score = -self.search_line(&mut child_search);
+                score = match self.search_child(m, &mut child_search) {
+                    Some(score) => score,
+                    None => return E::null(),
+                };
                 if score > sp.alpha() {
-                    sp.update_pv(m, child_search);
+                    sp.update_pv(m, &mut self.pv_table);
                     sp.set_alpha(score);
                 }
             }
 
-            if self.stopping.load(Ordering::Relaxed) {
-                return E::null();
-            }
-
             if score > best_score {
                 if score >= sp.beta() {
-                    return score.add_depth(1);
+                    self.stats.beta_cutoff(index + 1);
+                    let score = score.add_depth(1);
+                    self.store_tt(key, TtEntry::new_min(score, depth, m));
+                    return score;
+                }
+                best_score = score;
+                best_move = m;
+            }
+        }
+
+        let score = best_score.add_depth(1);
+        if best_score > alpha {
+            self.store_tt(key, TtEntry::new_exact(score, depth, best_move));
+        } else {
+            self.store_tt(key, TtEntry::new_max(score, depth, best_move));
+        }
+        score
+    }
+
+    // Like `search_node`, but in `root_moves` order and recording every move's score.  Moves after
+    // the first are tested against the best score less the easy move margin rather than alpha,
+    // so that a move that is close to the best isn't hidden behind a cutoff.
+    fn root_node(&mut self, sp: &mut AlphaBetaSearchParams<E>, root_moves: &mut RootMoves<E>) -> E {
+        let (board, beta, depth) = (*sp.board(), sp.beta(), sp.depth());
+        sp.clear_pv(&mut self.pv_table);
+        if !self.enter_node(sp.ply(), false) {
+            return E::null();
+        }
+
+        if root_moves.is_empty() {
+            self.pv.clear();
+            return match game_result(&board) {
+                Some(GameResult::Win(_)) => E::new_mate(0, Color::Black),
+                _ => E::zero(),
+            };
+        }
+
+        let mut best_score = E::null();
+        for index in 0..root_moves.len() {
+            let m = root_moves.get_moves()[index].get_move();
+            let score = if index == 0 {
+                let mut child_search = sp.lower_depth(m);
+                self.search_child(m, &mut child_search)
+            } else {
+                let threshold = if best_score.depth_to_mate().is_none() {
+                    best_score - root_moves.get_margin()
+                } else {
+                    sp.alpha()
+                };
+                let null_window = |bound: E| {
+                    AlphaBetaSearchParams::new(board, bound, beta, depth)
+                        .lower_depth_into_null_window(m)
+                };
+                let mut score = self.search_child(m, &mut null_window(threshold));
+                // Within the margin, the move may still be better than the best so far.
+                if let Some(close) = score {
+                    if close > threshold && close <= sp.alpha() && threshold < sp.alpha() {
+                        match self.search_child(m, &mut null_window(sp.alpha())) {
+                            Some(above) if above > sp.alpha() => score = Some(above),
+                            Some(_) => {}
+                            None => score = None,
+                        }
+                    }
+                }
+                match score {
+                    Some(score) if score > sp.alpha() && score < sp.beta() => {
+                        let mut child_search = sp.lower_depth(m);
+                        self.search_child(m, &mut child_search)
+                    }
+                    score => score,
                 }
+            };
+            let score = match score {
+                Some(score) => score,
+                None => return E::null(),
+            };
+
+            root_moves.record(index, score);
+            if score > sp.alpha() {
+                sp.update_pv(m, &mut self.pv_table);
+                sp.set_alpha(score);
+            }
+            if best_score == E::null() || score > best_score {
                 best_score = score;
+                if score >= sp.beta() {
+                    self.stats.beta_cutoff(index);
+                    break;
+                }
             }
         }
 
-        return best_score.add_depth(1);
+        self.pv_table.copy_into(0, &mut self.pv);
+        best_score.add_depth(1)
+    }
+}
+
+// The transposition table is cleared too, even when it is shared, as a new game shouldn't be
+// settled from the last one's entries.
+impl<E: Eval, V: Evaluate<E>> Resettable for DefaultSearch<E, V> {
+    fn reset(&mut self) {
+        if let Some(ref tt) = self.tt {
+            tt.clear();
+        }
+        self.pv.clear();
+        self.pv_table.reset();
+        self.stats.clear();
+        self.nodes_searched = 0;
+        self.out_of_nodes = false;
+        self.trace = None;
+        self.pruned = None;
+        self.evaluator.reset();
     }
 }
 
 impl<E: Eval, V: Evaluate<E>> Search<E> for DefaultSearch<E, V> {
-    fn search(&mut self, board: Board, alpha: E, beta: E, depth: i16) -> E {
+    fn search(&mut self, board: Board, alpha: E, beta: E, depth: i16) -> SearchResult<E> {
+        self.start();
         let mut sp = AlphaBetaSearchParams::new(board, alpha, beta, depth);
-        let result = self.search_line(&mut sp);
-        self.pv = sp.get_pv();
-        result
+        let score = self.search_line(&mut sp);
+        self.pv_table.copy_into(0, &mut self.pv);
+        self.finish(score)
+    }
+
+    fn search_root(
+        &mut self,
+        board: Board,
+        root_moves: &mut RootMoves<E>,
+        alpha: E,
+        beta: E,
+        depth: i16,
+    ) -> SearchResult<E> {
+        self.start();
+        let mut sp = AlphaBetaSearchParams::new(board, alpha, beta, depth);
+        let traced = self.trace_enter(&sp, false);
+        let score = self.root_node(&mut sp, root_moves);
+        self.trace_exit(traced, score);
+        self.finish(score)
     }
 
     fn get_pv(&self) -> &Pv {
         &self.pv
     }
+
+    fn get_stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn get_stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        Some(self.stopping.clone())
+    }
+
+    // The budget covers every search until the next `set_limits`, so that it holds for a whole
+    // iterative deepening run.
+    fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+        self.nodes_searched = 0;
+        self.out_of_nodes = false;
+    }
+
+    fn set_margins(&mut self, margins: PruningMargins) {
+        self.margins = margins;
+    }
+
+    fn set_eval_params(&mut self, params: &EvalParams) {
+        self.evaluator.set_params(params);
+    }
+
+    fn evaluate(&mut self, board: Board) -> Option<E> {
+        let mut sp = AlphaBetaSearchParams::new(board, E::min_eval(), E::max_eval(), 0);
+        Some(self.evaluator.evaluate(&mut sp))
+    }
+
+    fn set_trace(&mut self, max_nodes: Option<usize>) {
+        self.trace_nodes = max_nodes;
+    }
+
+    fn take_trace(&mut self) -> Option<SearchTrace> {
+        self.trace.take()
+    }
 }
 
 #[cfg(test)]
-use super::evaluate::DefaultEvaluate;
+use super::evaluate::{DefaultEvaluate, PawnStructureEvaluate};
+#[cfg(test)]
+use super::test_positions::{easy_tactic, super_easy_tactic, tactical_suite};
 #[cfg(test)]
-use super::test_positions::{easy_tactic, super_easy_tactic};
+use super::search_trace::DEFAULT_TRACE_NODES;
 #[cfg(test)]
-use chess::ChessMove;
+use super::node_counter::NodeCounter;
+#[cfg(test)]
+use super::zobrist;
+#[cfg(test)]
+use std::str::FromStr;
 
 #[cfg(test)]
 fn find_move_qsearch(board: Board, m: ChessMove) {
@@ -146,10 +688,11 @@ fn find_move_qsearch(board: Board, m: ChessMove) {
     );
     let mut search_params =
         AlphaBetaSearchParams::new(board, i32::min_value() + 20, i32::max_value() - 20, 0);
-    searcher.qsearch(&mut search_params);
-    let pv = search_params.get_pv();
+    let score = searcher.qsearch(&mut search_params);
 
-    assert_eq!(pv[0], m);
+    assert_eq!(searcher.pv_table.get_line(0)[0], m);
+    // The capture raises alpha, and so the score, above standing pat.
+    assert!(score > searcher.static_eval(&mut search_params));
 }
 
 #[cfg(test)]
@@ -159,8 +702,64 @@ fn find_move_search(board: Board, m: ChessMove) {
         DefaultEvaluate::default(),
     );
 
-    searcher.search(board, i32::min_value() + 20, i32::max_value() - 20, 4);
-    assert_eq!(searcher.get_pv()[0], m);
+    let result = searcher.search(board, i32::min_value() + 20, i32::max_value() - 20, 4);
+    assert_eq!(result.get_best_move(), Some(m));
+
+    let stats = result.get_stats();
+    assert!(stats.get_nodes() > stats.get_qsearch_nodes());
+    assert!(stats.get_beta_cutoffs().iter().sum::<u64>() > 0);
+}
+
+#[cfg(test)]
+fn search_score(fen: &str, depth: i16) -> i32 {
+    let mut searcher = DefaultSearch::new(
+        Arc::<AtomicBool>::new(AtomicBool::new(false)),
+        DefaultEvaluate::default(),
+    );
+    searcher
+        .search(
+            Board::from_str(fen).unwrap(),
+            i32::MIN + 20,
+            i32::MAX - 20,
+            depth,
+        )
+        .get_score()
+}
+
+#[test]
+fn test_search_mated() {
+    let score = search_score("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", 2);
+    assert_eq!(score, i32::new_mate(0, Color::Black));
+}
+
+#[test]
+fn test_search_stalemate() {
+    assert_eq!(search_score("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 2), 0);
+}
+
+#[test]
+fn test_search_insufficient_material() {
+    // A bishop up, but with nothing left to mate with.
+    assert_eq!(search_score("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1", 3), 0);
+    // Taking the last pawn leaves a draw, which is better than being a pawn down.
+    assert_eq!(search_score("8/8/4k3/8/4p3/3K4/8/8 w - - 0 1", 1), 0);
+}
+
+#[test]
+fn test_search_root_scores_every_move() {
+    let (board, best_move) = easy_tactic();
+    let mut searcher = DefaultSearch::new(
+        Arc::<AtomicBool>::new(AtomicBool::new(false)),
+        DefaultEvaluate::default(),
+    );
+    let mut root_moves = RootMoves::new(&board);
+    let result = searcher.search_root(board, &mut root_moves, i32::MIN + 20, i32::MAX - 20, 4);
+    root_moves.finish_iteration();
+
+    assert_eq!(result.get_best_move(), Some(best_move));
+    assert_eq!(root_moves.get_best_move(), Some(best_move));
+    assert_eq!(root_moves.get_moves()[0].get_score().add_depth(1), result.get_score());
+    assert!(root_moves.get_moves().iter().all(|m| m.get_score() != i32::null()));
 }
 
 #[test]
@@ -180,3 +779,171 @@ fn test_easy_search() {
     let (board, best_move) = easy_tactic();
     find_move_search(board, best_move);
 }
+
+#[test]
+fn test_search_pawn_table_stats() {
+    let (board, best_move) = easy_tactic();
+    let mut searcher = DefaultSearch::new(
+        Arc::<AtomicBool>::new(AtomicBool::new(false)),
+        PawnStructureEvaluate::default(),
+    );
+    let result = searcher.search(board, i32::MIN + 20, i32::MAX - 20, 3);
+    assert_eq!(result.get_best_move(), Some(best_move));
+
+    let stats = result.get_stats();
+    assert!(stats.get_pawn_probes() > 0);
+    assert!(stats.pawn_hit_rate() > 0.5);
+}
+
+#[test]
+fn test_pruning_keeps_tactics() {
+    for (board, best_move) in tactical_suite() {
+        let mut nodes = vec![];
+        for margins in [PruningMargins::disabled(), PruningMargins::new()].iter() {
+            let mut searcher = DefaultSearch::new(
+                Arc::<AtomicBool>::new(AtomicBool::new(false)),
+                DefaultEvaluate::default(),
+            );
+            searcher.set_margins(*margins);
+            let result = searcher.search(board, i32::MIN + 20, i32::MAX - 20, 4);
+            let best = result.get_best_move();
+            assert_eq!(best, Some(best_move), "{} with {:?}", board, margins);
+            nodes.push(result.get_nodes());
+        }
+        assert!(nodes[1] <= nodes[0], "{} searched {:?} nodes", board, nodes);
+    }
+}
+
+#[test]
+fn test_search_reset() {
+    let (board, _) = super_easy_tactic();
+    let mut searcher = DefaultSearch::new(
+        Arc::<AtomicBool>::new(AtomicBool::new(false)),
+        DefaultEvaluate::default(),
+    );
+    searcher.search(board, i32::MIN + 20, i32::MAX - 20, 2);
+    assert!(!searcher.pv.is_empty());
+
+    searcher.reset();
+    assert!(searcher.pv.is_empty());
+    assert_eq!(*searcher.get_stats(), SearchStats::default());
+}
+
+#[test]
+fn test_search_node_limit() {
+    let mut searcher = DefaultSearch::new(
+        Arc::<AtomicBool>::new(AtomicBool::new(false)),
+        DefaultEvaluate::default(),
+    );
+    searcher.set_limits(SearchLimits::nodes(100));
+    let result = searcher.search(Board::default(), i32::MIN + 20, i32::MAX - 20, 6);
+
+    assert!(result.is_stopped());
+    assert_eq!(searcher.get_nodes_searched(), 100);
+
+    // The budget lasts until the limits are set again.
+    assert!(searcher.search(Board::default(), i32::MIN + 20, i32::MAX - 20, 1).is_stopped());
+    searcher.set_limits(SearchLimits::new());
+    assert!(!searcher.search(Board::default(), i32::MIN + 20, i32::MAX - 20, 1).is_stopped());
+}
+
+#[test]
+fn test_search_node_counter() {
+    let counter = NodeCounter::new(2);
+    let new_searcher = |thread: usize| {
+        DefaultSearch::new(Arc::new(AtomicBool::new(false)), DefaultEvaluate::default())
+            .node_counter(counter.handle(thread))
+    };
+    let mut first = new_searcher(0);
+    first.search(Board::default(), i32::MIN + 20, i32::MAX - 20, 2);
+    let first_nodes = first.get_nodes_searched();
+    assert_eq!(counter.total(), first_nodes);
+
+    // The limit holds for both threads' nodes, so the second has what the first left.
+    let mut second = new_searcher(1);
+    second.set_limits(SearchLimits::nodes(first_nodes + 100));
+    assert!(second.search(Board::default(), i32::MIN + 20, i32::MAX - 20, 6).is_stopped());
+    assert_eq!(second.get_nodes_searched(), 100);
+    assert_eq!(counter.total(), first_nodes + 100);
+    assert_eq!(counter.get_thread_nodes(1), 100);
+}
+
+#[test]
+fn test_search_transposition_table() {
+    let tt = Arc::new(TranspositionTable::new(1));
+    let new_searcher = || {
+        DefaultSearch::new(Arc::new(AtomicBool::new(false)), DefaultEvaluate::default())
+            .transposition_table(tt.clone())
+    };
+    for (board, m) in tactical_suite() {
+        tt.clear();
+        let result = new_searcher().search(board, i32::MIN + 20, i32::MAX - 20, 4);
+        assert_eq!(result.get_best_move(), Some(m));
+        assert!(result.get_stats().get_tt_probes() > 0);
+    }
+
+    // Searched again, the position's nodes are settled from the table, with the same result.
+    let (board, m) = easy_tactic();
+    tt.clear();
+    let mut searcher = new_searcher();
+    let first = searcher.search(board, i32::MIN + 20, i32::MAX - 20, 4);
+    let second = searcher.search(board, i32::MIN + 20, i32::MAX - 20, 4);
+    assert_eq!(second.get_best_move(), Some(m));
+    assert_eq!(second.get_score(), first.get_score());
+    assert!(second.get_nodes() < first.get_nodes());
+    assert!(second.get_stats().get_tt_hits() > 0);
+
+    let unprefetched = new_searcher()
+        .prefetch(false)
+        .search(board, i32::MIN + 20, i32::MAX - 20, 4);
+    assert_eq!(unprefetched.get_score(), first.get_score());
+
+    // A reset forgets the table's entries along with everything else.
+    assert!(tt.probe::<i32>(zobrist::hash(&board)).is_some());
+    searcher.reset();
+    assert!(tt.probe::<i32>(zobrist::hash(&board)).is_none());
+}
+
+#[test]
+fn test_search_trace() {
+    let (board, best_move) = easy_tactic();
+    let mut searcher = DefaultSearch::new(
+        Arc::<AtomicBool>::new(AtomicBool::new(false)),
+        DefaultEvaluate::default(),
+    );
+    searcher.search(board, i32::MIN + 20, i32::MAX - 20, 3);
+    assert!(searcher.take_trace().is_none());
+
+    searcher.set_trace(Some(DEFAULT_TRACE_NODES));
+    let result = searcher.search(board, i32::MIN + 20, i32::MAX - 20, 3);
+    assert_eq!(result.get_best_move(), Some(best_move));
+    let trace = searcher.take_trace().unwrap();
+    let nodes = trace.get_nodes();
+    assert!(!trace.is_truncated());
+    assert_eq!(nodes[0].get_parent(), None);
+    assert_eq!(nodes[0].get_depth(), 3);
+    assert!(nodes[0].get_score().is_some());
+    assert!(nodes.iter().any(|n| n.is_qsearch()));
+    assert!(nodes.iter().any(|n| n.get_pruned().is_some()));
+    assert!(nodes[1..].iter().all(|n| n.get_parent().is_some()));
+
+    searcher.set_trace(Some(10));
+    searcher.search(board, i32::MIN + 20, i32::MAX - 20, 3);
+    let trace = searcher.take_trace().unwrap();
+    assert_eq!(trace.get_nodes().len(), 10);
+    assert!(trace.is_truncated());
+}
+
+#[test]
+fn test_search_seldepth() {
+    let (board, _) = easy_tactic();
+    let mut searcher = DefaultSearch::new(
+        Arc::<AtomicBool>::new(AtomicBool::new(false)),
+        DefaultEvaluate::default(),
+    );
+    let result = searcher.search(board, i32::MIN + 20, i32::MAX - 20, 2);
+    // The quiescence search goes on past the last full ply.
+    assert!(result.get_seldepth() > 2);
+    assert_eq!(result.get_seldepth(), result.get_stats().get_seldepth());
+    assert_eq!(result.get_nodes(), result.get_stats().get_nodes());
+}
@@ -0,0 +1,155 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{spawn, JoinHandle};
+
+use chess::Board;
+
+use super::iterative_deepening::IterativeDeepening;
+use super::pv::Pv;
+use error::Error;
+
+// Hands everything the search writes to whoever is polling the `SearchHandle`.
+struct ChannelWriter {
+    tx: Sender<Vec<u8>>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "search output dropped"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A search running on its own thread.  The searcher is handed back, along with the PV it found,
+/// by `join`.
+pub struct SearchHandle<I: IterativeDeepening> {
+    stop: Option<Arc<AtomicBool>>,
+    thread: JoinHandle<(I, Pv)>,
+    output: Receiver<Vec<u8>>,
+}
+
+impl<I: IterativeDeepening + Send + 'static> SearchHandle<I> {
+    pub fn launch(searcher: I, board: Board, max_depth: i16, moves_made: u16) -> SearchHandle<I> {
+        SearchHandle::spawn(searcher, move |searcher, writer| {
+            searcher.id_search(board, max_depth, moves_made, writer)
+        })
+    }
+
+    /// Like `launch`, but for `go mate`.
+    pub fn launch_mate(searcher: I, board: Board, moves: u8) -> SearchHandle<I> {
+        SearchHandle::spawn(searcher, move |searcher, writer| {
+            searcher.mate_search(board, moves, writer)
+        })
+    }
+
+    fn spawn<F>(mut searcher: I, search: F) -> SearchHandle<I>
+    where
+        F: FnOnce(&mut I, ChannelWriter) -> Pv + Send + 'static,
+    {
+        let stop = searcher.get_stop_flag();
+        if let Some(ref stop) = stop {
+            stop.store(false, Ordering::Relaxed);
+        }
+
+        let (tx, rx) = channel();
+        let thread = spawn(move || {
+            let pv = search(&mut searcher, ChannelWriter { tx });
+            (searcher, pv)
+        });
+
+        SearchHandle {
+            stop,
+            thread,
+            output: rx,
+        }
+    }
+
+    /// Ask the search to finish as soon as it can.  Searchers without a stop flag run until
+    /// they are done.
+    pub fn stop(&self) {
+        if let Some(ref stop) = self.stop {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Everything the search has written since the last call.
+    pub fn take_output(&self) -> Vec<u8> {
+        let mut output = vec![];
+        while let Ok(chunk) = self.output.try_recv() {
+            output.extend_from_slice(&chunk);
+        }
+        output
+    }
+
+    /// Wait for the search to finish.  Fails if the search thread panicked, in which case the
+    /// searcher is lost.
+    pub fn join(self) -> Result<(I, Pv, Vec<u8>), Error> {
+        let (searcher, pv) = self.thread.join().map_err(|_| Error::EngineDeadError)?;
+        let mut output = vec![];
+        while let Ok(chunk) = self.output.try_recv() {
+            output.extend_from_slice(&chunk);
+        }
+        Ok((searcher, pv, output))
+    }
+}
+
+#[cfg(test)]
+use super::resettable::Resettable;
+#[cfg(test)]
+use std::thread::sleep;
+#[cfg(test)]
+use std::time::Duration;
+
+// Counts up until it is stopped.
+#[cfg(test)]
+struct UntilStopped {
+    stop: Arc<AtomicBool>,
+}
+
+#[cfg(test)]
+impl Resettable for UntilStopped {}
+
+#[cfg(test)]
+impl IterativeDeepening for UntilStopped {
+    fn id_search<W: Write>(&mut self, _: Board, _: i16, _: u16, mut writer: W) -> Pv {
+        let mut depth = 0;
+        while !self.stop.load(Ordering::Relaxed) {
+            depth += 1;
+            writeln!(writer, "info depth {}", depth).unwrap();
+            sleep(Duration::from_millis(1));
+        }
+        Pv::new()
+    }
+
+    fn get_stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        Some(self.stop.clone())
+    }
+}
+
+#[test]
+fn test_search_handle_stop() {
+    let stop = Arc::new(AtomicBool::new(true));
+    let handle = SearchHandle::launch(UntilStopped { stop: stop.clone() }, Board::default(), 1, 0);
+
+    // Launching clears the flag left over from the last search.
+    sleep(Duration::from_millis(20));
+    assert!(!handle.is_finished());
+
+    handle.stop();
+    let (_, pv, output) = handle.join().unwrap();
+    assert!(pv.is_empty());
+    assert!(String::from_utf8(output).unwrap().starts_with("info depth 1\n"));
+    assert!(stop.load(Ordering::Relaxed));
+}
@@ -1,5 +1,8 @@
+use super::eval::Eval;
+use super::search_result::SearchResult;
 use crate::engine::info::Info;
 use crate::engine::score::Score;
+use crate::parsers::Counter;
 use crate::timer::timer::Timer;
 use chess::ChessMove;
 
@@ -30,6 +33,17 @@ impl SearchInfo {
         }
     }
 
+    /// The depth, seldepth, nodes, PV and score of an iteration searched to `depth`.
+    pub fn from_result<E: Eval>(depth: u64, result: &SearchResult<E>) -> SearchInfo {
+        let mut info = SearchInfo::new();
+        info.set_depth(depth);
+        info.set_seldepth(result.get_seldepth() as u64);
+        info.set_nodes(result.get_nodes());
+        info.set_pv(result.get_pv().as_slice().to_vec());
+        info.set_score(result.get_score().into());
+        info
+    }
+
     pub fn set_depth(&mut self, depth: u64) {
         self.depth = Some(depth);
     }
@@ -80,15 +94,15 @@ impl SearchInfo {
         }
 
         if let Some(nodes) = self.nodes {
-            info = info.combine(&Info::nodes(nodes));
+            info = info.combine(&Info::nodes(nodes as Counter));
             if let Some(e) = elapsed {
                 let nanos = e.as_secs() * 1_000_000_000 + (e.subsec_nanos() as u64);
-                info = info.combine(&Info::nps(nodes * 1_000_000_000 / nanos));
+                info = info.combine(&Info::nps((nodes * 1_000_000_000 / nanos) as Counter));
             }
         }
 
         if let Some(tb_hits) = self.tb_hits {
-            info = info.combine(&Info::tb_hits(tb_hits));
+            info = info.combine(&Info::tb_hits(tb_hits as Counter));
         }
 
         if self.pv.len() > 0 {
@@ -122,7 +136,7 @@ use std::time::Duration;
 fn convert_to_info() {
     let mut search_info = SearchInfo::new();
     search_info.set_depth(10);
-    search_info.set_score(Score::Cp(100));
+    search_info.set_score(Score::cp(100));
     search_info.set_nodes(1000);
     search_info.set_pv(vec![ChessMove::default()]);
     search_info.set_multi_pv(0);
@@ -136,7 +150,7 @@ fn convert_to_info() {
 
     let mut desired_info = Info::default();
     desired_info = desired_info.combine(&Info::depth(10));
-    desired_info = desired_info.combine(&Info::score(Score::Cp(100)));
+    desired_info = desired_info.combine(&Info::score(Score::cp(100)));
     desired_info = desired_info.combine(&Info::nodes(1000));
     desired_info = desired_info.combine(&Info::pv(vec![ChessMove::default()]));
     desired_info = desired_info.combine(&Info::multi_pv(0));
@@ -149,3 +163,27 @@ fn convert_to_info() {
 
     assert_eq!(info, desired_info);
 }
+
+#[cfg(test)]
+use super::search_stats::SearchStats;
+#[cfg(test)]
+use super::pv::Pv;
+
+#[test]
+fn from_search_result() {
+    let mut stats = SearchStats::new();
+    stats.node();
+    stats.reach_ply(7);
+    let mut pv = Pv::new();
+    pv.push(ChessMove::default());
+    let result = SearchResult::new(35, pv, stats);
+
+    let info = SearchInfo::from_result(4, &result).create_engine_info(Timer::new_without_increment(
+        Duration::from_millis(1000),
+    ));
+    assert_eq!(info.get_depth(), Some(4));
+    assert_eq!(info.get_seldepth(), Some(7));
+    assert_eq!(info.get_nodes(), Some(1));
+    assert_eq!(info.get_pv(), &vec![ChessMove::default()]);
+    assert_eq!(info.get_score(), Some(Score::cp(35)));
+}
@@ -0,0 +1,104 @@
+use chess::ChessMove;
+use gui::go::Go;
+
+/// The hard limits a `go` puts on one search.  Time is the time manager's business; these are
+/// the limits that are exact.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    depth: Option<i16>,
+    nodes: Option<u64>,
+    // Empty for every move.
+    search_moves: Vec<ChessMove>,
+}
+
+impl SearchLimits {
+    pub fn new() -> SearchLimits {
+        SearchLimits::default()
+    }
+
+    pub fn depth(depth: i16) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> SearchLimits {
+        SearchLimits {
+            nodes: Some(nodes),
+            ..SearchLimits::default()
+        }
+    }
+
+    pub fn search_moves(search_moves: Vec<ChessMove>) -> SearchLimits {
+        SearchLimits {
+            search_moves,
+            ..SearchLimits::default()
+        }
+    }
+
+    pub fn combine(&self, other: &SearchLimits) -> SearchLimits {
+        SearchLimits {
+            depth: self.depth.or(other.depth),
+            nodes: self.nodes.or(other.nodes),
+            search_moves: if self.search_moves.is_empty() {
+                other.search_moves.clone()
+            } else {
+                self.search_moves.clone()
+            },
+        }
+    }
+
+    pub fn from_go(go: &Go) -> SearchLimits {
+        SearchLimits {
+            depth: go.get_depth().map(|d| d.min(i16::MAX as u64) as i16),
+            nodes: go.get_nodes(),
+            search_moves: go.get_search_moves().clone(),
+        }
+    }
+
+    pub fn get_depth(&self) -> Option<i16> {
+        self.depth
+    }
+
+    pub fn get_nodes(&self) -> Option<u64> {
+        self.nodes
+    }
+
+    /// The only root moves to search, or every move if this is empty.
+    pub fn get_search_moves(&self) -> &[ChessMove] {
+        &self.search_moves
+    }
+
+    /// The deepest iteration to run, given the searcher's own maximum.
+    pub fn cap_depth(&self, max_depth: i16) -> i16 {
+        self.depth.map_or(max_depth, |d| d.min(max_depth))
+    }
+
+    /// Has a search that has visited `nodes` nodes used its whole budget?
+    pub fn nodes_exhausted(&self, nodes: u64) -> bool {
+        self.nodes.is_some_and(|limit| nodes >= limit)
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+
+#[test]
+fn search_limits_from_go() {
+    let limits = SearchLimits::from_go(&Go::depth(6).combine(&Go::nodes(5000)).combine(&Go::wtime(1000)));
+    assert_eq!(limits, SearchLimits::depth(6).combine(&SearchLimits::nodes(5000)));
+    assert_eq!(limits.cap_depth(64), 6);
+    assert_eq!(limits.cap_depth(4), 4);
+    assert!(!limits.nodes_exhausted(4999));
+    assert!(limits.nodes_exhausted(5000));
+
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let limits = SearchLimits::from_go(&Go::search_moves(vec![e2e4]));
+    assert_eq!(limits.get_search_moves(), &[e2e4]);
+
+    let unlimited = SearchLimits::from_go(&Go::infinite(true));
+    assert_eq!(unlimited.cap_depth(64), 64);
+    assert!(!unlimited.nodes_exhausted(u64::MAX));
+    assert!(unlimited.get_search_moves().is_empty());
+}
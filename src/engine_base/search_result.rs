@@ -0,0 +1,92 @@
+use chess::ChessMove;
+
+use super::eval::Eval;
+use super::pv::Pv;
+use super::search_stats::SearchStats;
+
+/// What one search found, handed back whole so nothing has to be read off the searcher
+/// afterwards, when another search may already have replaced it.
+///
+/// A search that was stopped, or ran out of nodes, scores `E::null()`; its PV is whatever the
+/// search had got to, and can't be trusted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult<E: Eval> {
+    score: E,
+    pv: Pv,
+    nodes: u64,
+    seldepth: u16,
+    stats: SearchStats,
+}
+
+impl<E: Eval> SearchResult<E> {
+    /// The nodes and seldepth are those counted in `stats`.
+    pub fn new(score: E, pv: Pv, stats: SearchStats) -> SearchResult<E> {
+        SearchResult {
+            score,
+            pv,
+            nodes: stats.get_nodes(),
+            seldepth: stats.get_seldepth(),
+            stats,
+        }
+    }
+
+    /// The result of a search that was stopped before it found anything.
+    pub fn stopped() -> SearchResult<E> {
+        SearchResult::new(E::null(), Pv::new(), SearchStats::new())
+    }
+
+    pub fn get_score(&self) -> E {
+        self.score
+    }
+
+    pub fn get_pv(&self) -> &Pv {
+        &self.pv
+    }
+
+    pub fn into_pv(self) -> Pv {
+        self.pv
+    }
+
+    /// The first move of the PV.
+    pub fn get_best_move(&self) -> Option<ChessMove> {
+        self.pv.as_slice().first().cloned()
+    }
+
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// The deepest ply reached, counting the quiescence search.
+    pub fn get_seldepth(&self) -> u16 {
+        self.seldepth
+    }
+
+    pub fn get_stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    /// Whether the search was cut short, and its score is `E::null()`.
+    pub fn is_stopped(&self) -> bool {
+        self.score == E::null()
+    }
+}
+
+#[test]
+fn test_search_result() {
+    let result = SearchResult::<i32>::stopped();
+    assert!(result.is_stopped());
+    assert_eq!(result.get_best_move(), None);
+
+    let mut stats = SearchStats::new();
+    stats.node();
+    stats.qsearch_node();
+    stats.reach_ply(3);
+    let mut pv = Pv::new();
+    pv.push(ChessMove::default());
+    let result = SearchResult::new(25, pv.clone(), stats);
+    assert!(!result.is_stopped());
+    assert_eq!(result.get_nodes(), 2);
+    assert_eq!(result.get_seldepth(), 3);
+    assert_eq!(result.get_best_move(), Some(ChessMove::default()));
+    assert_eq!(result.into_pv(), pv);
+}
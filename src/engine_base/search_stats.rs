@@ -0,0 +1,180 @@
+use std::fmt;
+
+/// Beta cutoffs are counted for each of the first `CUTOFF_SLOTS - 1` moves of a node; the last
+/// slot counts every later move.
+pub const CUTOFF_SLOTS: usize = 8;
+
+/// Counters kept during one search, for measuring how well moves are ordered and how often the
+/// search's shortcuts pay off.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    nodes: u64,
+    qsearch_nodes: u64,
+    // The deepest ply a node was searched at, quiescence search included.
+    seldepth: u16,
+    beta_cutoffs: [u64; CUTOFF_SLOTS],
+    tt_probes: u64,
+    tt_hits: u64,
+    pawn_probes: u64,
+    pawn_hits: u64,
+}
+
+fn ratio(a: u64, b: u64) -> f64 {
+    if b == 0 {
+        0.0
+    } else {
+        a as f64 / b as f64
+    }
+}
+
+impl SearchStats {
+    pub fn new() -> SearchStats {
+        SearchStats::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = SearchStats::default();
+    }
+
+    pub fn node(&mut self) {
+        self.nodes += 1;
+    }
+
+    pub fn qsearch_node(&mut self) {
+        self.nodes += 1;
+        self.qsearch_nodes += 1;
+    }
+
+    /// A node was searched `ply` moves from the root.
+    pub fn reach_ply(&mut self, ply: usize) {
+        self.seldepth = self.seldepth.max(ply.min(u16::MAX as usize) as u16);
+    }
+
+    /// The move at `index` (counting from 0) in its node's move order caused a beta cutoff.
+    pub fn beta_cutoff(&mut self, index: usize) {
+        self.beta_cutoffs[index.min(CUTOFF_SLOTS - 1)] += 1;
+    }
+
+    pub fn tt_probe(&mut self, hit: bool) {
+        self.tt_probes += 1;
+        if hit {
+            self.tt_hits += 1;
+        }
+    }
+
+    /// Add `probes` pawn table probes, `hits` of which hit.
+    pub fn pawn_probes(&mut self, probes: u64, hits: u64) {
+        self.pawn_probes += probes;
+        self.pawn_hits += hits;
+    }
+
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub fn get_qsearch_nodes(&self) -> u64 {
+        self.qsearch_nodes
+    }
+
+    pub fn get_seldepth(&self) -> u16 {
+        self.seldepth
+    }
+
+    pub fn get_beta_cutoffs(&self) -> &[u64; CUTOFF_SLOTS] {
+        &self.beta_cutoffs
+    }
+
+    pub fn get_tt_probes(&self) -> u64 {
+        self.tt_probes
+    }
+
+    pub fn get_tt_hits(&self) -> u64 {
+        self.tt_hits
+    }
+
+    pub fn get_pawn_probes(&self) -> u64 {
+        self.pawn_probes
+    }
+
+    pub fn get_pawn_hits(&self) -> u64 {
+        self.pawn_hits
+    }
+
+    /// How often the first move tried was good enough to cut off.  With good move ordering, this
+    /// is well above 90%.
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        ratio(self.beta_cutoffs[0], self.beta_cutoffs.iter().sum())
+    }
+
+    pub fn tt_hit_rate(&self) -> f64 {
+        ratio(self.tt_hits, self.tt_probes)
+    }
+
+    pub fn pawn_hit_rate(&self) -> f64 {
+        ratio(self.pawn_hits, self.pawn_probes)
+    }
+
+    pub fn qsearch_share(&self) -> f64 {
+        ratio(self.qsearch_nodes, self.nodes)
+    }
+}
+
+// Meant for `info string`, so it stays on one line.
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "nodes {} qnodes {} ({:.1}%) cutoffs {} first {:.1}% tthits {}/{} ({:.1}%) pawnhits {}/{} ({:.1}%)",
+            self.nodes,
+            self.qsearch_nodes,
+            100.0 * self.qsearch_share(),
+            self.beta_cutoffs
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+                .join("/"),
+            100.0 * self.first_move_cutoff_rate(),
+            self.tt_hits,
+            self.tt_probes,
+            100.0 * self.tt_hit_rate(),
+            self.pawn_hits,
+            self.pawn_probes,
+            100.0 * self.pawn_hit_rate()
+        )
+    }
+}
+
+#[test]
+fn search_stats_rates() {
+    let mut stats = SearchStats::new();
+    assert_eq!(stats.first_move_cutoff_rate(), 0.0);
+
+    for _ in 0..3 {
+        stats.node();
+    }
+    stats.qsearch_node();
+    stats.beta_cutoff(0);
+    stats.beta_cutoff(0);
+    stats.beta_cutoff(0);
+    stats.beta_cutoff(20);
+    stats.tt_probe(true);
+    stats.tt_probe(false);
+    stats.pawn_probes(4, 3);
+    stats.reach_ply(5);
+    stats.reach_ply(3);
+
+    assert_eq!(stats.get_nodes(), 4);
+    assert_eq!(stats.get_seldepth(), 5);
+    assert_eq!(stats.get_beta_cutoffs()[CUTOFF_SLOTS - 1], 1);
+    assert_eq!(stats.first_move_cutoff_rate(), 0.75);
+    assert_eq!(stats.tt_hit_rate(), 0.5);
+    assert_eq!(stats.pawn_hit_rate(), 0.75);
+    assert_eq!(stats.qsearch_share(), 0.25);
+    assert_eq!(
+        stats.to_string(),
+        "nodes 4 qnodes 1 (25.0%) cutoffs 3/0/0/0/0/0/0/1 first 75.0% tthits 1/2 (50.0%) pawnhits 3/4 (75.0%)"
+    );
+
+    stats.clear();
+    assert_eq!(stats, SearchStats::default());
+}
@@ -0,0 +1,294 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use chess::ChessMove;
+
+use error::Error;
+use json::Json;
+
+#[cfg(test)]
+use chess::Square;
+
+/// How many nodes a trace keeps when none is given, about as many as a depth 5 search visits.
+pub const DEFAULT_TRACE_NODES: usize = 100_000;
+
+/// Why a node was settled without searching its moves, or skipped altogether.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PruneReason {
+    ReverseFutility,
+    Razoring,
+    /// A quiet move skipped by futility pruning.
+    Futility,
+    /// A capture skipped by delta pruning in the quiescence search.
+    Delta,
+    InsufficientMaterial,
+    /// Settled by a transposition table entry.
+    TranspositionTable,
+}
+
+impl fmt::Display for PruneReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            PruneReason::ReverseFutility => "reverse futility",
+            PruneReason::Razoring => "razoring",
+            PruneReason::Futility => "futility",
+            PruneReason::Delta => "delta",
+            PruneReason::InsufficientMaterial => "insufficient material",
+            PruneReason::TranspositionTable => "transposition table",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// One node of a traced search.  Scores are from the side to move's point of view, as the
+/// search keeps them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceNode {
+    parent: Option<usize>,
+    path: Vec<ChessMove>,
+    depth: i16,
+    alpha: i64,
+    beta: i64,
+    qsearch: bool,
+    score: Option<i64>,
+    pruned: Option<PruneReason>,
+}
+
+impl TraceNode {
+    /// The index of the node this one was reached from, or `None` for the root.
+    pub fn get_parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// The moves from the root to this node.
+    pub fn get_path(&self) -> &[ChessMove] {
+        &self.path
+    }
+
+    pub fn get_depth(&self) -> i16 {
+        self.depth
+    }
+
+    pub fn get_alpha(&self) -> i64 {
+        self.alpha
+    }
+
+    pub fn get_beta(&self) -> i64 {
+        self.beta
+    }
+
+    pub fn is_qsearch(&self) -> bool {
+        self.qsearch
+    }
+
+    /// `None` if the node was skipped, or the search was stopped while in it.
+    pub fn get_score(&self) -> Option<i64> {
+        self.score
+    }
+
+    pub fn get_pruned(&self) -> Option<PruneReason> {
+        self.pruned
+    }
+
+    fn to_json(&self) -> Json {
+        let optional = |value: Option<i64>| value.map_or(Json::Null, |v| Json::Number(v as f64));
+        Json::Object(vec![
+            ("parent".to_string(), optional(self.parent.map(|p| p as i64))),
+            ("path".to_string(), Json::String(line(&self.path))),
+            ("depth".to_string(), Json::Number(self.depth as f64)),
+            ("alpha".to_string(), Json::Number(self.alpha as f64)),
+            ("beta".to_string(), Json::Number(self.beta as f64)),
+            ("qsearch".to_string(), Json::Bool(self.qsearch)),
+            ("score".to_string(), optional(self.score)),
+            (
+                "pruned".to_string(),
+                self.pruned.map_or(Json::Null, |p| Json::String(p.to_string())),
+            ),
+        ])
+    }
+}
+
+fn line(path: &[ChessMove]) -> String {
+    path.iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// The nodes a search visited, in the order it visited them, for finding out why a line was
+/// pruned.  Recording stops once `max_nodes` nodes are kept.
+///
+/// A searcher records into it with `enter` and `exit` around each node, `push_move` and
+/// `pop_move` around each child, and `skip` for moves it passes over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchTrace {
+    max_nodes: usize,
+    nodes: Vec<TraceNode>,
+    truncated: bool,
+    path: Vec<ChessMove>,
+    // The nodes entered but not yet left.
+    open: Vec<usize>,
+}
+
+impl SearchTrace {
+    pub fn new(max_nodes: usize) -> SearchTrace {
+        SearchTrace {
+            max_nodes,
+            nodes: vec![],
+            truncated: false,
+            path: vec![],
+            open: vec![],
+        }
+    }
+
+    pub fn get_nodes(&self) -> &[TraceNode] {
+        &self.nodes
+    }
+
+    /// Whether the search visited more nodes than were kept.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn push_move(&mut self, m: ChessMove) {
+        self.path.push(m);
+    }
+
+    pub fn pop_move(&mut self) {
+        self.path.pop();
+    }
+
+    fn record(&mut self, depth: i16, alpha: i64, beta: i64, qsearch: bool) -> Option<usize> {
+        if self.nodes.len() >= self.max_nodes {
+            self.truncated = true;
+            return None;
+        }
+        self.nodes.push(TraceNode {
+            parent: self.open.last().cloned(),
+            path: self.path.clone(),
+            depth,
+            alpha,
+            beta,
+            qsearch,
+            score: None,
+            pruned: None,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Start a node at the end of the current path.  Returns whether it was kept; only then
+    /// does it need an `exit`.
+    pub fn enter(&mut self, depth: i16, alpha: i64, beta: i64, qsearch: bool) -> bool {
+        match self.record(depth, alpha, beta, qsearch) {
+            Some(index) => {
+                self.open.push(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Finish the last node entered, with its score and why it was pruned, if it was.
+    pub fn exit(&mut self, score: Option<i64>, pruned: Option<PruneReason>) {
+        if let Some(index) = self.open.pop() {
+            self.nodes[index].score = score;
+            self.nodes[index].pruned = pruned;
+        }
+    }
+
+    /// Record the child reached by `m` as skipped, without entering it.
+    pub fn skip(&mut self, m: ChessMove, depth: i16, alpha: i64, beta: i64, reason: PruneReason) {
+        self.push_move(m);
+        if let Some(index) = self.record(depth, alpha, beta, false) {
+            self.nodes[index].pruned = Some(reason);
+        }
+        self.pop_move();
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes = self.nodes.iter().map(|n| n.to_json()).collect();
+        let trace = Json::Object(vec![
+            ("truncated".to_string(), Json::Bool(self.truncated)),
+            ("nodes".to_string(), Json::Array(nodes)),
+        ]);
+        format!("{}\n", trace)
+    }
+
+    /// The tree in graphviz's dot language.  Pruned nodes are drawn dashed, with the reason.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph search {\n    node [shape=box];\n".to_string();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let score = node.score.map_or("-".to_string(), |s| s.to_string());
+            let mut label = format!(
+                "{}d{} [{}, {}]\\n{}",
+                if node.qsearch { "q " } else { "" },
+                node.depth,
+                node.alpha,
+                node.beta,
+                score
+            );
+            let mut style = "";
+            if let Some(reason) = node.pruned {
+                label = format!("{}\\n{}", label, reason);
+                style = ", style=dashed";
+            }
+            dot += &format!("    n{} [label=\"{}\"{}];\n", index, label, style);
+            if let (Some(parent), Some(m)) = (node.parent, node.path.last()) {
+                dot += &format!("    n{} -> n{} [label=\"{}\"];\n", parent, index, m);
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+
+    /// Write the trace to `path`, as dot for a `.dot` or `.gv` file and as JSON otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let extension = path.as_ref().extension().and_then(|e| e.to_str());
+        let dot = extension == Some("dot") || extension == Some("gv");
+        fs::write(path, if dot { self.to_dot() } else { self.to_json() })?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_search_trace() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+    let mut trace = SearchTrace::new(3);
+    assert!(trace.enter(2, -100, 100, false));
+    trace.push_move(e2e4);
+    assert!(trace.enter(1, -100, 100, false));
+    trace.exit(Some(-20), Some(PruneReason::ReverseFutility));
+    trace.pop_move();
+    trace.skip(d2d4, 1, -100, 100, PruneReason::Futility);
+    // Over the limit.
+    trace.push_move(d2d4);
+    assert!(!trace.enter(1, -100, 100, false));
+    trace.pop_move();
+    trace.exit(Some(20), None);
+
+    let nodes = trace.get_nodes();
+    assert_eq!(nodes.len(), 3);
+    assert!(trace.is_truncated());
+    assert_eq!(nodes[0].get_score(), Some(20));
+    assert_eq!(nodes[1].get_parent(), Some(0));
+    assert_eq!(nodes[1].get_path(), &[e2e4]);
+    assert_eq!(nodes[2].get_pruned(), Some(PruneReason::Futility));
+    assert_eq!(nodes[2].get_score(), None);
+
+    let json = Json::parse(&trace.to_json()).unwrap();
+    let nodes = json.get("nodes").unwrap().as_array().unwrap();
+    assert_eq!(nodes[1].get("path").unwrap().as_str(), Some("e2e4"));
+    assert_eq!(nodes[1].get("pruned").unwrap().as_str(), Some("reverse futility"));
+    assert_eq!(
+        trace.to_dot(),
+        "digraph search {\n    node [shape=box];\n\
+         \x20   n0 [label=\"d2 [-100, 100]\\n20\"];\n\
+         \x20   n1 [label=\"d1 [-100, 100]\\n-20\\nreverse futility\", style=dashed];\n\
+         \x20   n0 -> n1 [label=\"e2e4\"];\n\
+         \x20   n2 [label=\"d1 [-100, 100]\\n-\\nfutility\", style=dashed];\n\
+         \x20   n0 -> n2 [label=\"d2d4\"];\n\
+         }\n"
+    );
+}
@@ -1,19 +1,23 @@
 use super::eval::Eval;
-use super::pv::Pv;
+use super::pv_table::PvTable;
+use super::zobrist;
 use chess::{Board, ChessMove};
 
 pub struct AlphaBetaSearchParams<E: Eval> {
     board: Board,
+    key: u64,
     alpha: E,
     beta: E,
     depth: i16,
-    pv: Pv,
+    ply: usize,
 }
 
 pub struct NullWindowSearchParams<E: Eval> {
     board: Board,
+    key: u64,
     score: E,
     depth: i16,
+    ply: usize,
 }
 
 pub trait SearchParams<E: Eval> {
@@ -21,28 +25,31 @@ pub trait SearchParams<E: Eval> {
     fn set_alpha(&mut self, alpha: E);
     fn beta(&self) -> E;
     fn depth(&self) -> i16;
+    /// How many moves from the root this node is.  Scores stay relative to the node they are
+    /// found at; this is what turns a mate score into one counted from the root.
+    fn ply(&self) -> usize;
     fn lower_depth(&self, chess_move: ChessMove) -> Self;
     fn board(&self) -> &Board;
+    /// The `zobrist` key of `board`, which the transposition table is keyed by.  It is worked
+    /// out once at the root and then updated move by move.
+    fn key(&self) -> u64;
     fn lower_depth_into_null_window(&self, chess_move: ChessMove) -> NullWindowSearchParams<E>;
     fn is_pv(&self) -> bool;
-    fn update_pv(&mut self, _chess_move: ChessMove, _other: Self);
-    fn clear_pv(&mut self);
+    fn update_pv(&self, _chess_move: ChessMove, _pv_table: &mut PvTable);
+    fn clear_pv(&self, _pv_table: &mut PvTable);
 }
 
 impl<E: Eval> AlphaBetaSearchParams<E> {
     pub fn new(board: Board, alpha: E, beta: E, depth: i16) -> AlphaBetaSearchParams<E> {
         AlphaBetaSearchParams::<E> {
             board: board,
+            key: zobrist::hash(&board),
             alpha: alpha,
             beta: beta,
             depth: depth,
-            pv: Pv::new(),
+            ply: 0,
         }
     }
-
-    pub fn get_pv(self) -> Pv {
-        self.pv
-    }
 }
 
 impl<E: Eval> SearchParams<E> for AlphaBetaSearchParams<E> {
@@ -62,25 +69,34 @@ impl<E: Eval> SearchParams<E> for AlphaBetaSearchParams<E> {
         &self.board
     }
 
-    fn clear_pv(&mut self) {
-        self.pv.clear();
+    fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn clear_pv(&self, pv_table: &mut PvTable) {
+        pv_table.clear(self.ply);
     }
 
     fn lower_depth(&self, chess_move: ChessMove) -> AlphaBetaSearchParams<E> {
+        let board = self.board.make_move_new(chess_move);
         AlphaBetaSearchParams::<E> {
-            board: self.board.make_move_new(chess_move),
+            board: board,
+            key: zobrist::update(self.key, &self.board, chess_move, &board),
             alpha: -self.beta.add_depth(-1),
             beta: -self.alpha.add_depth(-1),
             depth: self.depth - 1,
-            pv: Pv::new(),
+            ply: self.ply + 1,
         }
     }
 
     fn lower_depth_into_null_window(&self, chess_move: ChessMove) -> NullWindowSearchParams<E> {
+        let board = self.board.make_move_new(chess_move);
         NullWindowSearchParams::<E> {
-            board: self.board.make_move_new(chess_move),
+            board: board,
+            key: zobrist::update(self.key, &self.board, chess_move, &board),
             score: -self.alpha.add_depth(-1),
             depth: self.depth - 1,
+            ply: self.ply + 1,
         }
     }
 
@@ -88,13 +104,17 @@ impl<E: Eval> SearchParams<E> for AlphaBetaSearchParams<E> {
         true
     }
 
-    fn update_pv(&mut self, chess_move: ChessMove, other: AlphaBetaSearchParams<E>) {
-        self.pv.update(chess_move, &other.pv);
+    fn update_pv(&self, chess_move: ChessMove, pv_table: &mut PvTable) {
+        pv_table.update(self.ply, chess_move);
     }
 
     fn depth(&self) -> i16 {
         self.depth
     }
+
+    fn ply(&self) -> usize {
+        self.ply
+    }
 }
 
 impl<E: Eval> SearchParams<E> for NullWindowSearchParams<E> {
@@ -112,15 +132,22 @@ impl<E: Eval> SearchParams<E> for NullWindowSearchParams<E> {
         &self.board
     }
 
+    fn key(&self) -> u64 {
+        self.key
+    }
+
     fn lower_depth(&self, chess_move: ChessMove) -> NullWindowSearchParams<E> {
+        let board = self.board.make_move_new(chess_move);
         NullWindowSearchParams::<E> {
-            board: self.board.make_move_new(chess_move),
+            board: board,
+            key: zobrist::update(self.key, &self.board, chess_move, &board),
             score: E::one() - self.score.add_depth(-1),
             depth: self.depth - 1,
+            ply: self.ply + 1,
         }
     }
 
-    fn update_pv(&mut self, _chess_move: ChessMove, _other: NullWindowSearchParams<E>) {}
+    fn update_pv(&self, _chess_move: ChessMove, _pv_table: &mut PvTable) {}
 
     fn lower_depth_into_null_window(&self, chess_move: ChessMove) -> NullWindowSearchParams<E> {
         self.lower_depth(chess_move)
@@ -134,9 +161,16 @@ impl<E: Eval> SearchParams<E> for NullWindowSearchParams<E> {
         self.depth
     }
 
-    fn clear_pv(&mut self) {}
+    fn ply(&self) -> usize {
+        self.ply
+    }
+
+    fn clear_pv(&self, _pv_table: &mut PvTable) {}
 }
 
+#[cfg(test)]
+use chess::Square;
+
 #[cfg(test)]
 fn normal_window() -> AlphaBetaSearchParams<i32> {
     AlphaBetaSearchParams::new(Board::default(), -50, 100, 8)
@@ -148,3 +182,29 @@ fn test_window() {
     assert_eq!(sp.alpha(), -50);
     assert_eq!(sp.beta(), 100);
 }
+
+#[test]
+fn test_window_ply() {
+    let sp = normal_window();
+    assert_eq!(sp.ply(), 0);
+    let child = sp.lower_depth(ChessMove::new(Square::E2, Square::E4, None));
+    assert_eq!((child.depth(), child.ply()), (7, 1));
+    let null_window =
+        child.lower_depth_into_null_window(ChessMove::new(Square::E7, Square::E5, None));
+    assert_eq!((null_window.depth(), null_window.ply()), (6, 2));
+    let grandchild = null_window.lower_depth(ChessMove::new(Square::G1, Square::F3, None));
+    assert_eq!(grandchild.ply(), 3);
+}
+
+#[test]
+fn test_window_key() {
+    let sp = normal_window();
+    assert_eq!(sp.key(), zobrist::hash(sp.board()));
+    let child = sp.lower_depth(ChessMove::new(Square::E2, Square::E4, None));
+    assert_eq!(child.key(), zobrist::hash(child.board()));
+    let null_window =
+        child.lower_depth_into_null_window(ChessMove::new(Square::E7, Square::E5, None));
+    assert_eq!(null_window.key(), zobrist::hash(null_window.board()));
+    let grandchild = null_window.lower_depth(ChessMove::new(Square::G1, Square::F3, None));
+    assert_eq!(grandchild.key(), zobrist::hash(grandchild.board()));
+}
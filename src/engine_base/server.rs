@@ -0,0 +1,58 @@
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::thread::spawn;
+
+use super::engine::EngineBase;
+use super::iterative_deepening::IterativeDeepening;
+use error::Error;
+
+/// Serve UCI over TCP: every accepted connection gets its own `EngineBase`, made by
+/// `engine_factory`, running on its own thread, so connections never share search state.
+///
+/// Only returns if accepting a connection fails.
+pub fn serve_uci<I, F>(listener: TcpListener, mut engine_factory: F) -> Result<(), Error>
+where
+    I: IterativeDeepening + Send + 'static,
+    F: FnMut() -> EngineBase<I>,
+{
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let mut engine = engine_factory();
+
+        // A session ending, cleanly or not, only affects its own connection.
+        spawn(move || engine.main_loop(reader, stream));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use super::engine::test_engine;
+#[cfg(test)]
+use command::Command;
+#[cfg(test)]
+use engine::engine_command::EngineCommand;
+#[cfg(test)]
+use engine::id::Id;
+#[cfg(test)]
+use engine_connection::EngineConnectionBuilder;
+#[cfg(test)]
+use transport::tcp::TcpTransport;
+
+#[test]
+fn test_serve_uci() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    spawn(move || serve_uci(listener, test_engine));
+
+    // Two independent sessions at once.
+    let first = EngineConnectionBuilder::from_transport(TcpTransport::new(&address)).connect().unwrap();
+    let second = EngineConnectionBuilder::from_transport(TcpTransport::new(&address)).connect().unwrap();
+
+    for connection in [first, second].iter() {
+        assert_eq!(
+            connection.history()[1].get_command(),
+            &Command::Engine(EngineCommand::Id(Id::name("Test")))
+        );
+    }
+}
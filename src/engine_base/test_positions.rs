@@ -14,3 +14,37 @@ pub fn easy_tactic() -> (Board, ChessMove) {
         ChessMove::new(Square::E2, Square::F3, None),
     )
 }
+
+/// Either heavy piece mates on the back rank.
+pub fn mate_in_one() -> Board {
+    Board::from_str("6k1/5ppp/8/8/8/8/1Q6/1R5K w - - 0 1").unwrap()
+}
+
+/// Mate in two moves, with Kb6 and then Rh8.
+pub fn mate_in_two() -> Board {
+    Board::from_str("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap()
+}
+
+/// Short tactics a material-only evaluation finds within four plies, to check that pruning
+/// doesn't hide them.
+pub fn tactical_suite() -> Vec<(Board, ChessMove)> {
+    vec![
+        super_easy_tactic(),
+        easy_tactic(),
+        // A knight fork of king and rook.
+        (
+            Board::from_str("r3k3/8/8/1N6/8/8/8/4K3 w - - 0 1").unwrap(),
+            ChessMove::new(Square::B5, Square::C7, None),
+        ),
+        // A pawn fork of two rooks.
+        (
+            Board::from_str("4k3/8/8/2r1r3/8/3P4/8/7K w - - 0 1").unwrap(),
+            ChessMove::new(Square::D3, Square::D4, None),
+        ),
+        // Black wins the queen with a skewer.
+        (
+            Board::from_str("4Q3/8/8/8/4K3/k7/8/7r b - - 0 1").unwrap(),
+            ChessMove::new(Square::H1, Square::E1, None),
+        ),
+    ]
+}
@@ -1,10 +1,20 @@
 use super::eval::Eval;
+use super::resettable::Resettable;
+use super::root_moves::RootMoves;
 use crate::timer::timer::Timer;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-pub trait TimeManager<E: Eval> {
+pub trait TimeManager<E: Eval>: Resettable {
     fn continue_id(&mut self, last_eval: E, timer: &Timer, moves: u16) -> bool;
     fn continue_search(&mut self, alpha: E, beta: E, timer: &Timer, moves: u16) -> bool;
+
+    /// Stop iterating early because one root move is clearly best, such as a forced recapture.
+    fn easy_move(&mut self, root_moves: &RootMoves<E>, _timer: &Timer, _moves: u16) -> bool {
+        root_moves.is_easy_move()
+    }
+
+    /// In analysis mode there is no game clock to save time for; the GUI decides when to stop.
+    fn set_analyse_mode(&mut self, _analyse: bool) {}
 }
 
 pub struct DefaultTimeManager;
@@ -15,9 +25,11 @@ impl DefaultTimeManager {
     }
 }
 
+impl Resettable for DefaultTimeManager {}
+
 impl TimeManager<i32> for DefaultTimeManager {
     fn continue_id(&mut self, _last_eval: i32, timer: &Timer, moves: u16) -> bool {
-        if let Some(start) = timer.get_start() {
+        if let Some(elapsed) = timer.elapsed() {
             let more_time = timer.get_add_time_on_move_n();
             let time_for_move = timer.get_time();
 
@@ -34,9 +46,7 @@ impl TimeManager<i32> for DefaultTimeManager {
                 time_to_use = time_for_move / moves_to_go;
             }
 
-            let now = Instant::now();
-
-            start + time_to_use < now
+            time_to_use < elapsed
         } else {
             true
         }
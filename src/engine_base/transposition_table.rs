@@ -0,0 +1,268 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use super::eval::Eval;
+use super::tt_entry::TtEntry;
+
+#[cfg(test)]
+use chess::{ChessMove, Square};
+#[cfg(test)]
+use std::mem::{align_of, size_of};
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
+use std::thread::spawn;
+
+const SLOT_BYTES: usize = 16;
+/// Entries in a bucket, which fills a 64-byte cache line.
+pub const BUCKET_SLOTS: usize = 4;
+const BUCKET_BYTES: usize = SLOT_BYTES * BUCKET_SLOTS;
+// Slots looked at for `hash_full`, as engines do.
+const HASH_FULL_SAMPLE: usize = 1000;
+
+// The packed entry, and the entry XORed with its key.  A slot torn by two threads storing at
+// once doesn't check out against either key, so it reads as a miss rather than a wrong entry.
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+// The slots a key may be kept in, on one cache line, so that a probe touches memory once.
+#[repr(align(64))]
+#[derive(Default)]
+struct Bucket {
+    slots: [Slot; BUCKET_SLOTS],
+}
+
+/// A transposition table that searches on many threads can share without locks.  Each entry
+/// takes 16 bytes; see `TtEntry` for how it is packed.  Entries are kept in buckets of
+/// `BUCKET_SLOTS`, each on its own cache line, and a position may be in any slot of its bucket.
+///
+/// A new entry goes in the slot of the same position, or else an empty slot, or else the slot of
+/// the shallowest entry from an earlier search.  When every entry is from this search, it
+/// replaces the shallowest only if it was searched at least as deeply.
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// A table of about `megabytes`, with at least one bucket.
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let len = (megabytes * 1024 * 1024 / BUCKET_BYTES).max(1);
+        TranspositionTable {
+            buckets: (0..len).map(|_| Bucket::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    /// How many entries the table can hold.
+    pub fn len(&self) -> usize {
+        self.buckets.len() * BUCKET_SLOTS
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        // Spread the keys over the table without needing a power of two.
+        let index = ((key as u128 * self.buckets.len() as u128) >> 64) as usize;
+        &self.buckets[index]
+    }
+
+    /// Start loading the bucket for `key` into the cache, so that a probe for it soon after
+    /// doesn't wait on memory.  A search calls this as soon as it knows a child's key, before
+    /// it recurses.  Does nothing on targets without a prefetch instruction.
+    #[inline]
+    pub fn prefetch(&self, key: u64) {
+        #[cfg(target_arch = "x86_64")]
+        {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            let bucket = self.bucket(key) as *const Bucket as *const i8;
+            // Prefetching is only a hint, and any address may be prefetched.
+            unsafe { _mm_prefetch(bucket, _MM_HINT_T0) };
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = key;
+    }
+
+    // The packed entry in `slot`, if it is for `key`.
+    fn read(slot: &Slot, key: u64) -> Option<u64> {
+        let data = slot.data.load(Ordering::Relaxed);
+        let check = slot.check.load(Ordering::Relaxed);
+        if data != 0 && check ^ data == key {
+            Some(data)
+        } else {
+            None
+        }
+    }
+
+    /// The entry for the position with `zobrist` key `key`, if there is one.
+    pub fn probe<T: Eval>(&self, key: u64) -> Option<TtEntry<T>> {
+        let data = self
+            .bucket(key)
+            .slots
+            .iter()
+            .find_map(|slot| TranspositionTable::read(slot, key))?;
+        TtEntry::unpack(data).map(|(entry, _)| entry)
+    }
+
+    pub fn store<T: Eval>(&self, key: u64, entry: &TtEntry<T>) {
+        let bucket = self.bucket(key);
+        let age = self.get_age();
+        // The slot to replace, and whether it may be replaced: the position's own slot, an
+        // empty one, or the shallowest, those of earlier searches first.
+        let mut victim: Option<(&Slot, bool, i16)> = None;
+        for slot in bucket.slots.iter() {
+            let data = slot.data.load(Ordering::Relaxed);
+            let (old, old_age) = match TtEntry::<T>::unpack(data) {
+                Some(unpacked) => unpacked,
+                None => {
+                    victim = Some((slot, true, i16::MIN));
+                    break;
+                }
+            };
+            if slot.check.load(Ordering::Relaxed) ^ data == key {
+                victim = Some((slot, true, i16::MIN));
+                break;
+            }
+            let stale = old_age != age;
+            let better = match victim {
+                None => true,
+                Some((_, victim_stale, depth)) => {
+                    (stale && !victim_stale) || (stale == victim_stale && old.get_depth() < depth)
+                }
+            };
+            if better {
+                victim = Some((slot, stale, old.get_depth()));
+            }
+        }
+        if let Some((slot, stale, depth)) = victim {
+            if stale || entry.get_depth() >= depth {
+                let data = entry.pack(age);
+                slot.check.store(key ^ data, Ordering::Relaxed);
+                slot.data.store(data, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Start a new search, whose entries replace those of earlier searches first.
+    pub fn new_search(&self) {
+        let age = (self.get_age() + 1) & 0x3f;
+        self.age.store(age, Ordering::Relaxed);
+    }
+
+    /// The age entries are stored with, from 0 to 63.
+    pub fn get_age(&self) -> u8 {
+        self.age.load(Ordering::Relaxed)
+    }
+
+    fn slots(&self) -> impl Iterator<Item = &Slot> {
+        self.buckets.iter().flat_map(|bucket| bucket.slots.iter())
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots() {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// How full the table is with entries from this search, in thousandths, for `info hashfull`.
+    pub fn hash_full(&self) -> u64 {
+        let age = self.get_age() as u64;
+        let sample = self.len().min(HASH_FULL_SAMPLE);
+        let used = self
+            .slots()
+            .take(sample)
+            .map(|slot| slot.data.load(Ordering::Relaxed))
+            .filter(|&data| data != 0 && (data >> 26) & 0x3f == age)
+            .count();
+        (used * 1000 / sample) as u64
+    }
+}
+
+#[test]
+fn test_transposition_table() {
+    assert_eq!(size_of::<Slot>(), SLOT_BYTES);
+    assert_eq!(size_of::<Bucket>(), BUCKET_BYTES);
+    assert_eq!(align_of::<Bucket>(), 64);
+    let table = TranspositionTable::new(1);
+    assert_eq!(table.len(), 65536);
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+    let key = 0x1234_5678_9abc_def0;
+
+    assert_eq!(table.probe::<i32>(key), None);
+    table.store(key, &TtEntry::new_exact(25i32, 6, e2e4));
+    assert_eq!(table.probe(key), Some(TtEntry::new_exact(25i32, 6, e2e4)));
+    assert_eq!(table.probe::<i32>(key + 1), None);
+
+    // Other positions in the same bucket fill its other slots.
+    for other in 1..BUCKET_SLOTS as u64 {
+        table.store(
+            key + other,
+            &TtEntry::new_exact(0i32, 6 + other as i16, e2e4),
+        );
+    }
+    assert_eq!(table.probe(key), Some(TtEntry::new_exact(25i32, 6, e2e4)));
+    assert_eq!(
+        table.probe(key + 3),
+        Some(TtEntry::new_exact(0i32, 9, e2e4))
+    );
+
+    // With the bucket full, a shallower entry for another position doesn't replace any, until
+    // the next search, when it replaces the shallowest.
+    let other = key + BUCKET_SLOTS as u64;
+    table.store(other, &TtEntry::new_exact(-10i32, 2, d2d4));
+    assert_eq!(table.probe::<i32>(other), None);
+    table.new_search();
+    table.store(other, &TtEntry::new_exact(-10i32, 2, d2d4));
+    assert_eq!(
+        table.probe(other),
+        Some(TtEntry::new_exact(-10i32, 2, d2d4))
+    );
+    assert_eq!(table.probe::<i32>(key), None);
+    assert_eq!(
+        table.probe(key + 1),
+        Some(TtEntry::new_exact(0i32, 7, e2e4))
+    );
+
+    // An entry for a position already in the table replaces it, however shallow.
+    table.store(key + 1, &TtEntry::new_exact(5i32, 1, d2d4));
+    assert_eq!(
+        table.probe(key + 1),
+        Some(TtEntry::new_exact(5i32, 1, d2d4))
+    );
+    table.prefetch(key);
+
+    table.clear();
+    assert_eq!(table.probe::<i32>(other), None);
+    assert_eq!(table.hash_full(), 0);
+}
+
+#[test]
+fn test_transposition_table_threads() {
+    let table = Arc::new(TranspositionTable::new(1));
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let threads = (0..8u64)
+        .map(|thread| {
+            let table = table.clone();
+            spawn(move || {
+                for i in 0..10_000u64 {
+                    let key = (i * 8 + thread).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                    table.store(key, &TtEntry::new_exact(thread as i32, thread as i16, e2e4));
+                    // Whatever is read for a key was stored for that key.
+                    if let Some(entry) = table.probe::<i32>(key) {
+                        assert_eq!(entry.get_depth() as i32, thread as i32);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert!(table.hash_full() > 0);
+}
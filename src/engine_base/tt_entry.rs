@@ -1,7 +1,17 @@
 use super::eval::Eval;
 use super::tt_score::TtScore;
-use chess::ChessMove;
+use chess::{ChessMove, Color, Piece, ALL_SQUARES};
 
+// Promotions are packed as 1 to 4, with 0 for none.
+const PROMOTIONS: [Piece; 4] = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+/// What a search learned about one position.
+///
+/// An entry packs into 64 bits, for `TranspositionTable`: the move in bits 0 to 15, the depth
+/// (clamped to an `i8`) in bits 16 to 23, the bound in bits 24 and 25, a 6-bit age in bits 26 to
+/// 31, and the score as an `i32` in the top 32 bits.  Mates keep their distance whatever the
+/// size of `T`; other scores too big for an `i32` are clamped.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct TtEntry<T: Eval> {
     score: TtScore<T>,
     depth: i16,
@@ -33,6 +43,25 @@ impl<T: Eval> TtEntry<T> {
         }
     }
 
+    /// An entry for a node `ply` moves from the root, whose `score` is counted from the root, as
+    /// a search that scores from the root finds it.  Mates are stored counted from the node.
+    pub fn at_ply(score: TtScore<T>, depth: i16, chess_move: ChessMove, ply: i16) -> TtEntry<T> {
+        TtEntry {
+            score: score.to_tt(ply),
+            depth,
+            chess_move,
+        }
+    }
+
+    /// The entry as found by a search `ply` moves from the root, with mates counted from the
+    /// root again, ready for `skip_search` and `update_alpha_beta`.
+    pub fn to_search(&self, ply: i16) -> TtEntry<T> {
+        TtEntry {
+            score: self.score.to_search(ply),
+            ..*self
+        }
+    }
+
     pub fn skip_search(&self, depth: i16, alpha: T, beta: T) -> Option<(T, ChessMove)> {
         if depth <= self.depth {
             match self.score.skip_search(alpha, beta) {
@@ -55,6 +84,128 @@ impl<T: Eval> TtEntry<T> {
     pub fn get_move(&self) -> ChessMove {
         self.chess_move
     }
+
+    pub fn get_depth(&self) -> i16 {
+        self.depth
+    }
+
+    pub fn get_score(&self) -> &TtScore<T> {
+        &self.score
+    }
+
+    /// The entry as 64 bits, tagged with the low 6 bits of `age`.  Never zero, which is left
+    /// for an empty slot.
+    pub fn pack(&self, age: u8) -> u64 {
+        let (bound, eval) = match self.score {
+            TtScore::Exact(x) => (1, x),
+            TtScore::Min(x) => (2, x),
+            TtScore::Max(x) => (3, x),
+        };
+        let m = self.chess_move;
+        let promotion = m
+            .get_promotion()
+            .and_then(|p| PROMOTIONS.iter().position(|&x| x == p))
+            .map_or(0, |index| index as u64 + 1);
+        let packed_move = m.get_source().to_index() as u64
+            | (m.get_dest().to_index() as u64) << 6
+            | promotion << 12;
+        let depth = self.depth.clamp(i8::MIN as i16, i8::MAX as i16) as i8 as u8 as u64;
+
+        packed_move
+            | depth << 16
+            | bound << 24
+            | ((age & 0x3f) as u64) << 26
+            | (pack_eval(eval) as u32 as u64) << 32
+    }
+
+    /// An entry and its age from `pack`, or `None` for an empty slot or a score `T` can't hold.
+    pub fn unpack(bits: u64) -> Option<(TtEntry<T>, u8)> {
+        let eval = unpack_eval::<T>((bits >> 32) as u32 as i32)?;
+        let score = match (bits >> 24) & 3 {
+            1 => TtScore::Exact(eval),
+            2 => TtScore::Min(eval),
+            3 => TtScore::Max(eval),
+            _ => return None,
+        };
+        let promotion = match (bits >> 12) & 7 {
+            0 => None,
+            p => Some(*PROMOTIONS.get(p as usize - 1)?),
+        };
+        let chess_move = ChessMove::new(
+            ALL_SQUARES[(bits & 0x3f) as usize],
+            ALL_SQUARES[((bits >> 6) & 0x3f) as usize],
+            promotion,
+        );
+        let entry = TtEntry {
+            score,
+            depth: (bits >> 16) as u8 as i8 as i16,
+            chess_move,
+        };
+        Some((entry, ((bits >> 26) & 0x3f) as u8))
+    }
+}
+
+// The same point on `i32`'s number line as `eval` on `T`'s; see `eval.rs`.
+fn pack_eval<T: Eval>(eval: T) -> i32 {
+    if eval == T::null() {
+        i32::null()
+    } else if eval >= T::max_eval() {
+        i32::max_eval()
+    } else if eval <= T::min_eval() {
+        i32::min_eval()
+    } else if let Some(depth) = eval.depth_to_mate() {
+        let color = if eval > T::zero() { Color::White } else { Color::Black };
+        i32::new_mate(depth.abs() as i16, color)
+    } else {
+        let limit = i32::max_eval() - i32::max_supported_mates() - 2;
+        eval.to_i64().map_or(0, |x| x.clamp(-limit as i64, limit as i64) as i32)
+    }
+}
+
+fn unpack_eval<T: Eval>(eval: i32) -> Option<T> {
+    if eval == i32::null() {
+        Some(T::null())
+    } else if eval >= i32::max_eval() {
+        Some(T::max_eval())
+    } else if eval <= i32::min_eval() {
+        Some(T::min_eval())
+    } else if let Some(depth) = eval.depth_to_mate() {
+        let color = if eval > 0 { Color::White } else { Color::Black };
+        Some(T::new_mate(depth.abs() as i16, color))
+    } else {
+        T::from(eval)
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+
+#[test]
+fn test_pack() {
+    let e7e8 = ChessMove::new(Square::E7, Square::E8, Some(Piece::Knight));
+    let entries = [
+        TtEntry::new_exact(-35i16, 7, e7e8),
+        TtEntry::new_min(i16::new_mate(3, Color::White), 300, ChessMove::default()),
+        TtEntry::new_max(i16::new_mate(12, Color::Black), -4, e7e8),
+        TtEntry::new_exact(i16::null(), 0, e7e8),
+    ];
+    for entry in entries.iter() {
+        let bits = entry.pack(70);
+        assert_ne!(bits, 0);
+        let (unpacked, age) = TtEntry::<i16>::unpack(bits).unwrap();
+        assert_eq!(age, 70 & 0x3f);
+        assert_eq!(unpacked.get_move(), entry.get_move());
+        assert_eq!(unpacked.get_score(), entry.get_score());
+        assert_eq!(unpacked.get_depth(), entry.get_depth().clamp(-128, 127));
+    }
+
+    // A mate keeps its distance between sizes of score.
+    let mate = TtEntry::new_exact(i64::new_mate(5, Color::Black), 3, e7e8).pack(0);
+    assert_eq!(
+        TtEntry::<i16>::unpack(mate).unwrap().0.get_score(),
+        &TtScore::Exact(i16::new_mate(5, Color::Black))
+    );
+    assert_eq!(TtEntry::<i32>::unpack(0), None);
 }
 
 #[test]
@@ -77,3 +228,65 @@ fn test_update_alpha_beta() {
     assert_eq!(entry.update_alpha_beta(10, -100, 100), (16i32, 100));
     assert_eq!(entry.update_alpha_beta(10, -100, 0), (16, 16));
 }
+
+#[test]
+fn test_mate_bounds_at_ply() {
+    // A lower bound of mate in 9 from the root, found 4 plies in.
+    let entry = TtEntry::at_ply(
+        TtScore::Min(i32::new_mate(9, Color::White)),
+        6,
+        ChessMove::default(),
+        4,
+    );
+    assert_eq!(
+        entry.get_score(),
+        &TtScore::Min(i32::new_mate(5, Color::White))
+    );
+    let (unpacked, _) = TtEntry::<i32>::unpack(entry.pack(0)).unwrap();
+    assert_eq!(unpacked, entry);
+
+    // Transposed into 2 plies in, the mate is 2 plies nearer the root than where it was found.
+    let probed = unpacked.to_search(2);
+    assert_eq!(
+        probed.get_score(),
+        &TtScore::Min(i32::new_mate(7, Color::White))
+    );
+    // So it fails high against a beta of mate in 8, but not against one of mate in 6.
+    assert_eq!(
+        probed.skip_search(6, 0, i32::new_mate(8, Color::White)),
+        Some((i32::new_mate(7, Color::White), ChessMove::default()))
+    );
+    assert_eq!(
+        probed.skip_search(6, 0, i32::new_mate(6, Color::White)),
+        None
+    );
+
+    // An upper bound of being mated works the same way round.
+    let entry = TtEntry::at_ply(
+        TtScore::Max(i32::new_mate(8, Color::Black)),
+        3,
+        ChessMove::default(),
+        3,
+    );
+    assert_eq!(
+        entry.get_score(),
+        &TtScore::Max(i32::new_mate(5, Color::Black))
+    );
+    let probed = entry.to_search(5);
+    assert_eq!(
+        probed.get_score(),
+        &TtScore::Max(i32::new_mate(10, Color::Black))
+    );
+    // Being mated later is better, so it fails low against an alpha of mated in 12 only.
+    assert!(probed
+        .skip_search(3, i32::new_mate(12, Color::Black), 0)
+        .is_some());
+    assert_eq!(
+        probed.skip_search(3, i32::new_mate(9, Color::Black), 0),
+        None
+    );
+
+    // Other scores don't move.
+    let entry = TtEntry::at_ply(TtScore::Exact(40i32), 2, ChessMove::default(), 7);
+    assert_eq!(entry.to_search(1).get_score(), &TtScore::Exact(40));
+}
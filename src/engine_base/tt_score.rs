@@ -1,5 +1,6 @@
 use super::eval::Eval;
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum TtScore<T: Eval> {
     Min(T),
     Max(T),
@@ -23,6 +24,25 @@ impl<T: Eval> TtScore<T> {
         }
     }
 
+    /// The bound, counted from the root, as a transposition table keeps it for a node `ply`
+    /// moves in.  See `Eval::to_tt`.
+    pub fn to_tt(&self, ply: i16) -> TtScore<T> {
+        self.map(|x| x.to_tt(ply))
+    }
+
+    /// The bound from a transposition table, counted from the root for a node `ply` moves in.
+    pub fn to_search(&self, ply: i16) -> TtScore<T> {
+        self.map(|x| x.to_search(ply))
+    }
+
+    fn map<F: Fn(T) -> T>(&self, f: F) -> TtScore<T> {
+        match self {
+            TtScore::Min(x) => TtScore::Min(f(*x)),
+            TtScore::Max(x) => TtScore::Max(f(*x)),
+            TtScore::Exact(x) => TtScore::Exact(f(*x)),
+        }
+    }
+
     pub fn skip_search(&self, alpha: T, beta: T) -> Option<T> {
         match self {
             TtScore::Exact(x) => Some(*x),
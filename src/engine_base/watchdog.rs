@@ -0,0 +1,125 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{spawn, JoinHandle};
+use std::time::Duration;
+
+use chess::Color;
+
+use gui::go::Go;
+use timer::budget_policy::BudgetPolicy;
+
+/// The share of the time left that one move may never go past, whatever the time manager makes
+/// of it.
+pub const HARD_LIMIT_PERCENT: u32 = 25;
+
+/// The longest a search started by `go` may run before it is stopped: the `movetime`, or a
+/// `HARD_LIMIT_PERCENT` share of `player`'s clock plus the increment, less `overhead` either way.
+/// `None` for a search the GUI stops itself, such as `go infinite`, or one without a clock.
+pub fn hard_limit(go: &Go, player: Color, overhead: Duration) -> Option<Duration> {
+    if go.get_infinite() {
+        return None;
+    }
+    if let Some(movetime) = go.get_movetime() {
+        return Some(Duration::from_millis(movetime).saturating_sub(overhead));
+    }
+    let (time, increment) = match player {
+        Color::White => (go.get_wtime()?, go.get_winc()),
+        Color::Black => (go.get_btime()?, go.get_binc()),
+    };
+    let remaining = Duration::from_millis(time).saturating_sub(overhead);
+    let increment = Duration::from_millis(increment.unwrap_or(0));
+    Some(BudgetPolicy::new(HARD_LIMIT_PERCENT).budget(remaining, increment, 0))
+}
+
+/// Sets a search's stop flag once its hard time limit has passed, from a thread of its own, so
+/// the search stops on time however long it goes between looks at the clock.
+///
+/// Dropping the watchdog calls it off.
+pub struct Watchdog {
+    cancel: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    fired: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    pub fn start(stop: Arc<AtomicBool>, limit: Duration) -> Watchdog {
+        let (tx, rx) = channel::<()>();
+        let fired = Arc::new(AtomicBool::new(false));
+        let thread_fired = fired.clone();
+        let thread = spawn(move || {
+            // Anything but a timeout means the watchdog was called off.
+            if let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(limit) {
+                thread_fired.store(true, Ordering::Relaxed);
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+        Watchdog {
+            cancel: Some(tx),
+            thread: Some(thread),
+            fired,
+        }
+    }
+
+    /// Whether the limit passed, and the search was told to stop.
+    pub fn has_fired(&self) -> bool {
+        self.fired.load(Ordering::Relaxed)
+    }
+
+    /// Call the watchdog off, for a search that finished in time.
+    pub fn cancel(&mut self) {
+        self.cancel.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+use std::thread::sleep;
+
+#[test]
+fn test_hard_limit() {
+    let ms = Duration::from_millis;
+    let overhead = ms(10);
+    assert_eq!(
+        hard_limit(&Go::movetime(1000), Color::White, overhead),
+        Some(ms(990))
+    );
+
+    let clock = Go::wtime(1010)
+        .combine(&Go::btime(2010))
+        .combine(&Go::winc(100));
+    assert_eq!(hard_limit(&clock, Color::White, overhead), Some(ms(350)));
+    assert_eq!(hard_limit(&clock, Color::Black, overhead), Some(ms(500)));
+    // Never more than is left.
+    let short = Go::wtime(110).combine(&Go::winc(1000));
+    assert_eq!(hard_limit(&short, Color::White, overhead), Some(ms(100)));
+
+    assert_eq!(
+        hard_limit(&Go::infinite(true), Color::White, overhead),
+        None
+    );
+    assert_eq!(hard_limit(&Go::depth(5), Color::White, overhead), None);
+}
+
+#[test]
+fn test_watchdog() {
+    let stop = Arc::new(AtomicBool::new(false));
+    let watchdog = Watchdog::start(stop.clone(), Duration::from_millis(5));
+    sleep(Duration::from_millis(100));
+    assert!(watchdog.has_fired());
+    assert!(stop.load(Ordering::Relaxed));
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut watchdog = Watchdog::start(stop.clone(), Duration::from_secs(60));
+    watchdog.cancel();
+    assert!(!watchdog.has_fired());
+    assert!(!stop.load(Ordering::Relaxed));
+}
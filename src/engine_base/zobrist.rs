@@ -0,0 +1,193 @@
+//! Zobrist keys for positions, the same in every build and every version of this crate, so
+//! opening books, repetition checks and transposition tables made with them can be shared.
+//! `DefaultSearch` keys its transposition table with them too.
+//!
+//! A position's key is the XOR of a key for each piece on its square, one for each side's
+//! castling rights, one for the file of a pawn that can be taken en passant, and one more when
+//! black is to move.  Having no castling rights adds nothing.  The keys are taken in order from
+//! a xorshift64* generator seeded with `SEED`.
+//!
+//! `Board::get_hash` from the `chess` crate gives different keys, which can change with that
+//! crate.
+
+use chess::{
+    Board, CastleRights, ChessMove, Color, File, Piece, Square, ALL_COLORS, ALL_PIECES,
+    NUM_COLORS, NUM_FILES, NUM_PIECES, NUM_SQUARES,
+};
+
+use rng::xorshift_star;
+
+#[cfg(test)]
+use chess::MoveGen;
+#[cfg(test)]
+use std::str::FromStr;
+
+/// The generator's seed.  It will never change.
+pub const SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+const PIECE_KEYS: usize = NUM_COLORS * NUM_PIECES * NUM_SQUARES;
+// Castling rights come after the pieces, for each side, then the en passant files, then black.
+const CASTLE_OFFSET: usize = PIECE_KEYS;
+const EN_PASSANT_OFFSET: usize = CASTLE_OFFSET + NUM_COLORS * 4;
+const BLACK_OFFSET: usize = EN_PASSANT_OFFSET + NUM_FILES;
+const NUM_KEYS: usize = BLACK_OFFSET + 1;
+
+const fn generate() -> [u64; NUM_KEYS] {
+    let mut keys = [0; NUM_KEYS];
+    let mut state = SEED;
+    let mut i = 0;
+    while i < NUM_KEYS {
+        let (next, key) = xorshift_star(state);
+        state = next;
+        keys[i] = key;
+        i += 1;
+    }
+    keys
+}
+
+static KEYS: [u64; NUM_KEYS] = generate();
+
+pub fn piece_key(piece: Piece, color: Color, square: Square) -> u64 {
+    KEYS[(color.to_index() * NUM_PIECES + piece.to_index()) * NUM_SQUARES + square.to_index()]
+}
+
+/// Zero for `NoRights`.
+pub fn castle_key(color: Color, rights: CastleRights) -> u64 {
+    match rights {
+        CastleRights::NoRights => 0,
+        rights => KEYS[CASTLE_OFFSET + color.to_index() * 4 + rights.to_index()],
+    }
+}
+
+pub fn en_passant_key(file: File) -> u64 {
+    KEYS[EN_PASSANT_OFFSET + file.to_index()]
+}
+
+/// Added when black is to move.
+pub fn black_key() -> u64 {
+    KEYS[BLACK_OFFSET]
+}
+
+// Everything but the pieces.
+fn state_key(board: &Board) -> u64 {
+    let mut key = castle_key(Color::White, board.castle_rights(Color::White))
+        ^ castle_key(Color::Black, board.castle_rights(Color::Black));
+    if let Some(square) = board.en_passant() {
+        key ^= en_passant_key(square.get_file());
+    }
+    if board.side_to_move() == Color::Black {
+        key ^= black_key();
+    }
+    key
+}
+
+/// The key of `board`, worked out from scratch.
+pub fn hash(board: &Board) -> u64 {
+    let mut key = state_key(board);
+    for &color in ALL_COLORS.iter() {
+        for &piece in ALL_PIECES.iter() {
+            for square in *board.pieces(piece) & *board.color_combined(color) {
+                key ^= piece_key(piece, color, square);
+            }
+        }
+    }
+    key
+}
+
+/// A key for the pawns of `board` alone, made of the same piece keys, for caching pawn
+/// structure.  Positions with the same pawns share it, whatever else is on the board.
+pub fn pawn_hash(board: &Board) -> u64 {
+    let mut key = 0;
+    for &color in ALL_COLORS.iter() {
+        for square in *board.pieces(Piece::Pawn) & *board.color_combined(color) {
+            key ^= piece_key(Piece::Pawn, color, square);
+        }
+    }
+    key
+}
+
+/// The key of `after`, which `m` reaches from `before`, given `hash`, the key of `before`.
+/// Only the squares `m` changes are looked at.
+pub fn update(hash: u64, before: &Board, m: ChessMove, after: &Board) -> u64 {
+    let (source, dest) = (m.get_source(), m.get_dest());
+    let color = before.side_to_move();
+    let piece = match before.piece_on(source) {
+        Some(piece) => piece,
+        None => return hash,
+    };
+
+    let mut key = hash ^ state_key(before) ^ state_key(after);
+    key ^= piece_key(piece, color, source);
+    key ^= piece_key(m.get_promotion().unwrap_or(piece), color, dest);
+    match before.piece_on(dest) {
+        Some(captured) => key ^= piece_key(captured, !color, dest),
+        None if piece == Piece::Pawn && source.get_file() != dest.get_file() => {
+            let taken = Square::make_square(source.get_rank(), dest.get_file());
+            key ^= piece_key(Piece::Pawn, !color, taken);
+        }
+        None => {}
+    }
+
+    // Castling, which moves the rook too.
+    let (from, to) = (source.get_file().to_index(), dest.get_file().to_index());
+    if piece == Piece::King && (from as i64 - to as i64).abs() == 2 {
+        let rank = source.get_rank();
+        let (rook_from, rook_to) = if to > from {
+            (File::H, File::F)
+        } else {
+            (File::A, File::D)
+        };
+        key ^= piece_key(Piece::Rook, color, Square::make_square(rank, rook_from));
+        key ^= piece_key(Piece::Rook, color, Square::make_square(rank, rook_to));
+    }
+    key
+}
+
+#[test]
+fn test_zobrist_keys_are_fixed() {
+    let start = hash(&Board::default());
+    assert_eq!(start, hash(&Board::default()));
+    assert_eq!(start, 0x7e4f_192e_3868_c23d);
+    assert_eq!(
+        hash(&Board::from_str("8/8/8/8/8/8/8/K6k w - - 0 1").unwrap()),
+        piece_key(Piece::King, Color::White, Square::A1)
+            ^ piece_key(Piece::King, Color::Black, Square::H1)
+    );
+}
+
+#[test]
+fn test_pawn_hash() {
+    let start = Board::default();
+    let knight_out = start.make_move_new(ChessMove::new(Square::G1, Square::F3, None));
+    let pawn_out = start.make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+    assert_eq!(pawn_hash(&start), pawn_hash(&knight_out));
+    assert_ne!(pawn_hash(&start), pawn_hash(&pawn_out));
+    assert_eq!(
+        pawn_hash(&Board::from_str("8/8/8/8/8/8/8/K6k w - - 0 1").unwrap()),
+        0
+    );
+}
+
+#[test]
+fn test_zobrist_update() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        // Castling both ways.
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        // En passant, and promotions with and without a capture.
+        "1n2k3/P7/8/3pP3/8/8/8/4K3 w - d6 0 1",
+    ];
+    for fen in fens.iter() {
+        let board = Board::from_str(fen).unwrap();
+        let key = hash(&board);
+        for m in MoveGen::new_legal(&board) {
+            let after = board.make_move_new(m);
+            assert_eq!(update(key, &board, m, &after), hash(&after), "{} in {}", m, fen);
+            for reply in MoveGen::new_legal(&after) {
+                let next = after.make_move_new(reply);
+                let chained = update(update(key, &board, m, &after), &after, reply, &next);
+                assert_eq!(chained, hash(&next), "{} {} in {}", m, reply, fen);
+            }
+        }
+    }
+}
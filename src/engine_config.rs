@@ -0,0 +1,326 @@
+use std::fs;
+use std::path::Path;
+
+use error::Error;
+use json::Json;
+
+/// How to run an engine, as kept in Cute Chess's `engines.json`: the command, the directory to
+/// run it in, and the options and raw lines to send it once it starts.
+///
+/// Keys this crate doesn't use, such as `whitepov` or `stderrFile`, are kept so that saving a
+/// loaded file doesn't lose them.  Options are kept as the value to send to the engine; there is
+/// no need for the type, default and limits Cute Chess also writes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EngineConfig {
+    name: String,
+    command: String,
+    arguments: Vec<String>,
+    working_directory: Option<String>,
+    protocol: String,
+    init_strings: Vec<String>,
+    options: Vec<(String, String)>,
+    other: Vec<(String, Json)>,
+}
+
+// Split a command line on whitespace, keeping quoted words together.
+fn split_command(command: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(word.clone());
+                    word.clear();
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+fn join_command(command: &str, arguments: &[String]) -> String {
+    let mut words = vec![command.to_string()];
+    words.extend(arguments.iter().cloned());
+    words
+        .iter()
+        .map(|w| {
+            if w.is_empty() || w.contains(char::is_whitespace) {
+                format!("\"{}\"", w)
+            } else {
+                w.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn config_error(message: &str) -> Error {
+    Error::ConfigError(message.to_string())
+}
+
+impl EngineConfig {
+    /// A UCI engine run with `command`, which may include arguments.
+    pub fn new(name: &str, command: &str) -> EngineConfig {
+        let mut words = split_command(command);
+        let command = if words.is_empty() {
+            String::new()
+        } else {
+            words.remove(0)
+        };
+        EngineConfig {
+            name: name.to_string(),
+            command,
+            arguments: words,
+            working_directory: None,
+            protocol: "uci".to_string(),
+            init_strings: vec![],
+            options: vec![],
+            other: vec![],
+        }
+    }
+
+    pub fn working_directory(mut self, dir: &str) -> EngineConfig {
+        self.working_directory = Some(dir.to_string());
+        self
+    }
+
+    pub fn protocol(mut self, protocol: &str) -> EngineConfig {
+        self.protocol = protocol.to_string();
+        self
+    }
+
+    pub fn init_string(mut self, line: &str) -> EngineConfig {
+        self.init_strings.push(line.to_string());
+        self
+    }
+
+    /// Set `name` to `value`, replacing any value it already had.
+    pub fn option(mut self, name: &str, value: &str) -> EngineConfig {
+        match self.options.iter_mut().find(|(n, _)| n == name) {
+            Some(option) => option.1 = value.to_string(),
+            None => self.options.push((name.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The program to run, without its arguments.
+    pub fn get_command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn get_arguments(&self) -> &[String] {
+        &self.arguments
+    }
+
+    pub fn get_working_directory(&self) -> Option<&str> {
+        self.working_directory.as_deref()
+    }
+
+    pub fn get_protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    pub fn is_uci(&self) -> bool {
+        self.protocol.eq_ignore_ascii_case("uci")
+    }
+
+    pub fn get_init_strings(&self) -> &[String] {
+        &self.init_strings
+    }
+
+    pub fn get_options(&self) -> &[(String, String)] {
+        &self.options
+    }
+
+    pub fn get_option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn from_json(json: &Json) -> Result<EngineConfig, Error> {
+        let members = match json {
+            Json::Object(members) => members,
+            _ => return Err(config_error("an engine must be an object")),
+        };
+        let string = |key: &str| json.get(key).and_then(|v| v.as_str());
+
+        let command = string("command").ok_or_else(|| config_error("an engine has no command"))?;
+        let mut config = EngineConfig::new(string("name").unwrap_or(command), command);
+        config.working_directory = string("workingDirectory")
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| dir.to_string());
+        if let Some(protocol) = string("protocol") {
+            config.protocol = protocol.to_string();
+        }
+
+        for line in json.get("initStrings").and_then(|v| v.as_array()).into_iter().flatten() {
+            let line = line
+                .as_str()
+                .ok_or_else(|| config_error("init strings must be strings"))?;
+            config.init_strings.push(line.to_string());
+        }
+
+        for option in json.get("options").and_then(|v| v.as_array()).into_iter().flatten() {
+            let name = option
+                .get("name")
+                .and_then(|n| n.as_str())
+                .ok_or_else(|| config_error("an option has no name"))?;
+            // Options that were never changed don't need to be sent.
+            if let Some(value) = option.get("value").and_then(|v| v.to_option_value()) {
+                config.options.push((name.to_string(), value));
+            }
+        }
+
+        let known = ["name", "command", "workingDirectory", "protocol", "initStrings", "options"];
+        config.other = members
+            .iter()
+            .filter(|(key, _)| !known.contains(&key.as_str()))
+            .cloned()
+            .collect();
+        Ok(config)
+    }
+
+    fn to_json(&self) -> Json {
+        let mut members = vec![
+            ("name".to_string(), Json::String(self.name.clone())),
+            (
+                "command".to_string(),
+                Json::String(join_command(&self.command, &self.arguments)),
+            ),
+            (
+                "workingDirectory".to_string(),
+                Json::String(self.working_directory.clone().unwrap_or_default()),
+            ),
+            ("protocol".to_string(), Json::String(self.protocol.clone())),
+        ];
+        if !self.init_strings.is_empty() {
+            members.push((
+                "initStrings".to_string(),
+                Json::Array(self.init_strings.iter().map(|s| Json::String(s.clone())).collect()),
+            ));
+        }
+        if !self.options.is_empty() {
+            let options = self
+                .options
+                .iter()
+                .map(|(name, value)| {
+                    Json::Object(vec![
+                        ("name".to_string(), Json::String(name.clone())),
+                        ("value".to_string(), Json::from_option_value(value)),
+                    ])
+                })
+                .collect();
+            members.push(("options".to_string(), Json::Array(options)));
+        }
+        members.extend(self.other.iter().cloned());
+        Json::Object(members)
+    }
+
+    /// Read the engines in a Cute Chess `engines.json`.
+    pub fn from_cute_chess(text: &str) -> Result<Vec<EngineConfig>, Error> {
+        Json::parse(text)?
+            .as_array()
+            .ok_or_else(|| config_error("expected a list of engines"))?
+            .iter()
+            .map(EngineConfig::from_json)
+            .collect()
+    }
+
+    /// Write `configs` in the format of a Cute Chess `engines.json`.
+    pub fn to_cute_chess(configs: &[EngineConfig]) -> String {
+        format!(
+            "{}\n",
+            Json::Array(configs.iter().map(|c| c.to_json()).collect())
+        )
+    }
+
+    pub fn load_cute_chess<P: AsRef<Path>>(path: P) -> Result<Vec<EngineConfig>, Error> {
+        EngineConfig::from_cute_chess(&fs::read_to_string(path)?)
+    }
+
+    pub fn save_cute_chess<P: AsRef<Path>>(path: P, configs: &[EngineConfig]) -> Result<(), Error> {
+        fs::write(path, EngineConfig::to_cute_chess(configs))?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_cute_chess_engines() {
+    let text = r#"[
+        {
+            "command": "./stockfish \"--config file\"",
+            "name": "Stockfish",
+            "options": [
+                {"alias": "", "default": 16, "max": 33554432, "min": 1, "name": "Hash", "type": "spin", "value": 128},
+                {"default": false, "name": "Ponder", "type": "check", "value": true},
+                {"default": "", "name": "SyzygyPath", "type": "folder"}
+            ],
+            "protocol": "uci",
+            "initStrings": ["setoption name Threads value 4"],
+            "whitepov": false,
+            "workingDirectory": "/opt/stockfish"
+        },
+        {"command": "gnuchess -x", "name": "GNU Chess", "protocol": "xboard", "workingDirectory": ""}
+    ]"#;
+
+    let configs = EngineConfig::from_cute_chess(text).unwrap();
+    assert_eq!(configs.len(), 2);
+    let stockfish = &configs[0];
+    assert_eq!(stockfish.get_name(), "Stockfish");
+    assert_eq!(stockfish.get_command(), "./stockfish");
+    assert_eq!(stockfish.get_arguments(), &["--config file".to_string()]);
+    assert_eq!(stockfish.get_working_directory(), Some("/opt/stockfish"));
+    assert_eq!(stockfish.get_option("Hash"), Some("128"));
+    assert_eq!(stockfish.get_option("Ponder"), Some("true"));
+    assert_eq!(stockfish.get_option("SyzygyPath"), None);
+    assert_eq!(stockfish.get_init_strings(), &["setoption name Threads value 4".to_string()]);
+    assert!(stockfish.is_uci());
+    assert!(!configs[1].is_uci());
+    assert_eq!(configs[1].get_working_directory(), None);
+
+    let saved = EngineConfig::to_cute_chess(&configs);
+    assert!(saved.contains("\"whitepov\": false"));
+    assert!(saved.contains("\"value\": 128"));
+    assert_eq!(EngineConfig::from_cute_chess(&saved), Ok(configs));
+
+    assert!(EngineConfig::from_cute_chess("{}").is_err());
+    assert!(EngineConfig::from_cute_chess("[{\"name\": \"No command\"}]").is_err());
+}
+
+#[test]
+fn test_engine_config_builder() {
+    let config = EngineConfig::new("Fake", "fake-engine --uci")
+        .option("Hash", "16")
+        .option("Hash", "32")
+        .init_string("debug on");
+    assert_eq!(config.get_command(), "fake-engine");
+    assert_eq!(config.get_arguments(), &["--uci".to_string()]);
+    assert_eq!(config.get_options(), &[("Hash".to_string(), "32".to_string())]);
+    assert_eq!(
+        EngineConfig::from_cute_chess(&EngineConfig::to_cute_chess(std::slice::from_ref(&config))),
+        Ok(vec![config])
+    );
+}
@@ -1,71 +1,524 @@
-use std::io::{BufRead, BufReader, Write};
-use std::process::{self, ChildStdin, Stdio};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::mem;
 use std::str::FromStr;
-use std::sync::mpsc::{sync_channel, Receiver, TryRecvError};
-use std::thread::{sleep, spawn};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::spawn;
+#[cfg(test)]
+use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-use chess::{Board, ChessMove};
+use chess::{Board, ChessMove, Square};
 
+use auto_configure::{AutoConfig, SystemResources};
+use capabilities::Capabilities;
 use command::Command;
+use connection_state::{ConnectionState, OptionsDuringSearch};
+use command_queue::{command_queue, OverflowPolicy, QueueReceiver, QueueSender};
 use engine::best_move::BestMove;
 use engine::engine_command::EngineCommand;
+use engine::engine_info::EngineInfo;
+use engine_base::engine_options::EngineOptions;
+use engine_config::EngineConfig;
+use analysis::analysis_history::AnalysisHistory;
+use engine::info::Info;
+use num_traits::ToPrimitive;
+use parsers::Counter;
+use engine::option_type::OptionType;
 use error::Error;
+use game_history::GameHistory;
+use history::History;
 use gui::go::Go;
 use gui::gui_command::GuiCommand;
+use timer::budget_policy::BudgetPolicy;
 use timer::timer::Timer;
+use transcript::{Direction, Transcript};
+use transport::process::ProcessTransport;
+use resource_usage::{self, ResourceMonitor, ResourceUsage};
+use transport::process_controls::ProcessControls;
+use search_outcome::SearchOutcome;
+use supervision::Supervision;
+use transport::{ClosedWriter, Transport, TransportWriter};
+use warm_up::{WarmUpReport, WarmUpSearch};
 
-pub struct EngineConnection<'a> {
-    history: Vec<Command>,
-    stdin: ChildStdin,
-    receiver: Receiver<Command>,
-    timer: Option<&'a mut Timer>,
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+// Like `read_until(b'\n')`, but only the first `MAX_LINE_LENGTH` bytes of a line are kept; the
+// rest is read and thrown away.  Returns the length of the whole line.
+fn read_line<R: BufRead>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<usize> {
+    let mut read = 0;
+    loop {
+        let (done, used) = {
+            let available = match reader.fill_buf() {
+                Ok(available) => available,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let (done, used) = match available.iter().position(|&b| b == b'\n') {
+                Some(end) => (true, end + 1),
+                None => (available.is_empty(), available.len()),
+            };
+            let room = MAX_LINE_LENGTH.saturating_sub(buf.len());
+            buf.extend_from_slice(&available[..used.min(room)]);
+            (done, used)
+        };
+        reader.consume(used);
+        read += used;
+        if done {
+            return Ok(read);
+        }
+    }
 }
 
-impl<'a> EngineConnection<'a> {
-    pub fn new(path: &str) -> Result<EngineConnection, Error> {
-        let process = process::Command::new(path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()?;
-
-        let (tx, rx) = sync_channel(1024);
-
-        let mut reader = BufReader::new(process.stdout.unwrap());
-
-        spawn(move || {
-            let mut s = String::new();
-            while let Ok(_) = reader.read_line(&mut s) {
-                if let Ok(command) = Command::from_str(&s) {
-                    if let Err(_) = tx.send(command.clone()) {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-                s = String::new();
+// Reads lines from the engine until it closes its output (or the connection is dropped).  A line
+// we can't understand must never silence the engine, so everything that isn't a known command is
+// forwarded as `Command::Unknown`, and invalid UTF-8 is replaced rather than treated as an error.
+//
+// The only things that stop the reader are the engine going away, or an IO error on the pipe.
+// Either way, the reason is sent on `errors` before the command channel is closed.
+fn read_commands<R: BufRead>(mut reader: R, tx: QueueSender, errors: Sender<Error>) {
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let read = match read_line(&mut reader, &mut buf) {
+            Ok(0) => {
+                let _ = errors.send(Error::EngineDeadError);
+                break;
+            }
+            Ok(read) => read,
+            Err(e) => {
+                let _ = errors.send(e.into());
+                break;
+            }
+        };
+
+        let line = String::from_utf8_lossy(&buf);
+        // The start of an overlong line could still parse, as an info line with half a PV say.
+        let command = if read > buf.len() {
+            Command::Unknown(line.to_string())
+        } else {
+            Command::from_str(&line).unwrap_or_else(|_| Command::Unknown(line.to_string()))
+        };
+        if tx.send(command).is_err() {
+            break;
+        }
+    }
+}
+
+const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+const PROBE_NODES: u64 = 10_000;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+// How much longer than its `movetime` a warm-up search may take.
+const WARM_UP_GRACE: Duration = Duration::from_secs(5);
+
+pub struct EngineConnectionBuilder {
+    transport: Box<dyn Transport>,
+    channel_capacity: usize,
+    overflow_policy: OverflowPolicy,
+    auto_configure: bool,
+    options: Vec<(String, Option<String>)>,
+    init_strings: Vec<String>,
+    process_controls: Option<ProcessControls>,
+    supervision: Option<Supervision>,
+    options_during_search: OptionsDuringSearch,
+}
+
+impl EngineConnectionBuilder {
+    /// Run the engine at `path` as a child process.
+    pub fn new(path: &str) -> EngineConnectionBuilder {
+        EngineConnectionBuilder::from_transport(ProcessTransport::new(path))
+    }
+
+    /// Reach the engine through any transport, such as a `TcpTransport` to a remote engine.
+    pub fn from_transport<T: Transport + 'static>(transport: T) -> EngineConnectionBuilder {
+        EngineConnectionBuilder {
+            transport: Box::new(transport),
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            overflow_policy: OverflowPolicy::default(),
+            auto_configure: false,
+            options: vec![],
+            init_strings: vec![],
+            process_controls: None,
+            supervision: None,
+            options_during_search: OptionsDuringSearch::default(),
+        }
+    }
+
+    /// Run an engine set up in a GUI's configuration file.  Its options and init strings are
+    /// sent once the engine has said hello.
+    pub fn from_config(config: &EngineConfig) -> Result<EngineConnectionBuilder, Error> {
+        if !config.is_uci() {
+            return Err(Error::ConfigError(format!(
+                "{} uses the {} protocol",
+                config.get_name(),
+                config.get_protocol()
+            )));
+        }
+
+        let mut transport = ProcessTransport::new(config.get_command());
+        for arg in config.get_arguments() {
+            transport = transport.arg(arg);
+        }
+        if let Some(dir) = config.get_working_directory() {
+            transport = transport.working_dir(dir);
+        }
+
+        let mut builder = EngineConnectionBuilder::from_transport(transport);
+        for (name, value) in config.get_options() {
+            builder = builder.option(name, Some(value));
+        }
+        for line in config.get_init_strings() {
+            builder = builder.init_string(line);
+        }
+        Ok(builder)
+    }
+
+    /// How many commands from the engine may be waiting to be received.
+    pub fn channel_capacity(mut self, capacity: usize) -> EngineConnectionBuilder {
+        self.channel_capacity = capacity;
+        self
+    }
+
+    /// What to do when `channel_capacity` commands are waiting to be received.
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> EngineConnectionBuilder {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Set `Threads` and `Hash` from the machine's resources once the engine has said hello.  See
+    /// `EngineConnection::auto_configure`.
+    pub fn auto_configure(mut self, auto_configure: bool) -> EngineConnectionBuilder {
+        self.auto_configure = auto_configure;
+        self
+    }
+
+    /// Set an option once the engine has said hello.
+    pub fn option(mut self, name: &str, value: Option<&str>) -> EngineConnectionBuilder {
+        self.options.push((name.to_string(), value.map(|v| v.to_string())));
+        self
+    }
+
+    /// Send `line` once the engine has said hello, after the options.  Lines that aren't GUI
+    /// commands are sent as they are.
+    pub fn init_string(mut self, line: &str) -> EngineConnectionBuilder {
+        self.init_strings.push(line.to_string());
+        self
+    }
+
+    /// Run the engine's process with `controls`, such as pinned to cores of its own for a match.
+    /// See `EngineConnection::get_process_controls` for what was applied.  Fails to connect if
+    /// the transport doesn't start a process.
+    pub fn process_controls(mut self, controls: ProcessControls) -> EngineConnectionBuilder {
+        self.process_controls = Some(controls);
+        self
+    }
+
+    /// Stop, and then kill, an engine that searches far longer than it was given.  See
+    /// `Supervision`.
+    pub fn supervision(mut self, supervision: Supervision) -> EngineConnectionBuilder {
+        self.supervision = Some(supervision);
+        self
+    }
+
+    /// What to do with a `setoption` sent while a search is running: by default it is held back
+    /// until the search has its best move.
+    pub fn options_during_search(mut self, policy: OptionsDuringSearch) -> EngineConnectionBuilder {
+        self.options_during_search = policy;
+        self
+    }
+
+    pub fn connect<'a>(mut self) -> Result<EngineConnection<'a>, Error> {
+        if let Some(controls) = self.process_controls.take() {
+            if !self.transport.set_process_controls(controls) {
+                return Err(Error::ConfigError(
+                    "process controls need a transport that starts a process".to_string(),
+                ));
             }
-        });
+        }
+        let (reader, writer) = self.transport.open()?;
+
+        let (tx, rx) = command_queue(self.channel_capacity, self.overflow_policy);
+        let (error_tx, error_rx) = channel();
+
+        let reader = BufReader::new(reader);
+
+        spawn(move || read_commands(reader, tx, error_tx));
 
         let mut ec = EngineConnection {
-            stdin: process.stdin.unwrap(),
-            history: vec![],
+            writer,
+            history: History::new(),
+            engine_info: EngineInfo::default(),
+            analysis_history: AnalysisHistory::new(),
+            game: GameHistory::new(Board::default()),
+            auto_config: None,
+            process_controls: self.transport.get_applied_controls().cloned(),
+            process_id: self.transport.get_process_id(),
             receiver: rx,
+            errors: error_rx,
+            reader_error: None,
             timer: None,
+            transcript: None,
+            searching: Board::default(),
+            state: ConnectionState::Idle,
+            options_during_search: self.options_during_search,
+            queued_options: vec![],
+            supervision: self.supervision,
+            supervised_search: None,
+            gos_sent: 0,
+            gos_answered: 0,
+            stale_best_moves: 0,
         };
 
         ec.send_uci()?;
+        if self.auto_configure {
+            ec.auto_config = Some(ec.send_auto_config()?);
+        }
+        for (name, value) in &self.options {
+            ec.send_setoption(name, value.as_deref())?;
+        }
+        for line in &self.init_strings {
+            ec.send_line(line)?;
+        }
         ec.send_isready()?;
 
         Ok(ec)
     }
+}
+
+/// The commands from an engine, from `EngineConnection::commands`.  Everything received is still
+/// kept in the connection's history.
+pub struct Commands<'c, 'a: 'c> {
+    connection: &'c mut EngineConnection<'a>,
+    timeout: Option<Duration>,
+}
+
+impl<'c, 'a> Commands<'c, 'a> {
+    /// Give up waiting for a command after `timeout`, yielding a `RecvTimeout` error.  The
+    /// iterator can carry on after one.
+    pub fn timeout(mut self, timeout: Duration) -> Commands<'c, 'a> {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl<'c, 'a> Iterator for Commands<'c, 'a> {
+    type Item = Result<EngineCommand, Error>;
+
+    fn next(&mut self) -> Option<Result<EngineCommand, Error>> {
+        let received = match self.timeout {
+            Some(timeout) => match self.connection.recv(Instant::now(), timeout) {
+                Err(Error::NoCommandError) => Err(Error::RecvTimeout { waited: timeout }),
+                received => received,
+            },
+            None => self.connection.recv_blocking(),
+        };
+        match received {
+            Err(ref e) if e.is_fatal() => None,
+            received => Some(received),
+        }
+    }
+}
+
+pub struct EngineConnection<'a> {
+    history: History,
+    engine_info: EngineInfo,
+    analysis_history: AnalysisHistory,
+    game: GameHistory,
+    auto_config: Option<AutoConfig>,
+    process_controls: Option<ProcessControls>,
+    process_id: Option<u32>,
+    writer: TransportWriter,
+    receiver: QueueReceiver,
+    errors: Receiver<Error>,
+    reader_error: Option<Error>,
+    timer: Option<&'a mut Timer>,
+    transcript: Option<Transcript>,
+    // The position from the last `send_position`, which best moves are checked against.
+    searching: Board,
+    state: ConnectionState,
+    options_during_search: OptionsDuringSearch,
+    // Options set during the search, to be sent once it has its best move.
+    queued_options: Vec<GuiCommand>,
+    supervision: Option<Supervision>,
+    // When the search under supervision was started, and when it is to be stopped.
+    supervised_search: Option<(Instant, Instant)>,
+    // Every `go` sent, and every best move received for one.  A best move answers the oldest
+    // `go` without one, so while some are still unanswered it belongs to an older search.
+    gos_sent: u64,
+    gos_answered: u64,
+    stale_best_moves: u64,
+}
+
+impl<'a> EngineConnection<'a> {
+    pub fn new(path: &str) -> Result<EngineConnection<'a>, Error> {
+        EngineConnectionBuilder::new(path).connect()
+    }
 
     pub fn set_timer(&mut self, timer: &'a mut Timer) {
         self.timer = Some(timer);
     }
 
+    /// Set up the game the engine is to play, failing with `IllegalMove`, and sending nothing, if
+    /// one of `moves` can't be played.
     pub fn send_position(&mut self, position: Board, moves: Vec<ChessMove>) -> Result<(), Error> {
-        self.send(GuiCommand::Position(position, moves))
+        let game = GameHistory::from_moves(position, &moves)?;
+        self.send(GuiCommand::position(position, moves))?;
+        self.searching = game.get_board();
+        self.game = game;
+        Ok(())
+    }
+
+    /// Play `m` in the game, and send the engine the position after it, failing with
+    /// `IllegalMove`, and sending nothing, if it can't be played.  This is for the other side's
+    /// moves: the engine's best moves are already played as they arrive.
+    ///
+    /// With a timer, the clock of the side that moved is stopped and the other side's started,
+    /// unless `recv_best_move_using_timer` has already done so.
+    pub fn play_move(&mut self, m: ChessMove) -> Result<(), Error> {
+        let mover = self.game.get_board().side_to_move();
+        let mut game = self.game.clone();
+        game.push(m)?;
+        self.send(GuiCommand::position(game.get_start(), game.get_moves().to_vec()))?;
+        self.searching = game.get_board();
+        self.game = game;
+        if let Some(ref mut timer) = self.timer {
+            if timer.started() && timer.get_player() == mover {
+                timer.made_move();
+            }
+        }
+        Ok(())
+    }
+
+    /// Tell the engine a new game is starting, and wait until it is ready for it.
+    pub fn send_ucinewgame(&mut self) -> Result<(), Error> {
+        self.send(GuiCommand::UciNewGame)?;
+        self.send_isready()
+    }
+
+    pub fn send_quit(&mut self) -> Result<(), Error> {
+        self.send(GuiCommand::Quit)
+    }
+
+    /// Ask the engine to stop searching and send its best move.
+    pub fn send_stop(&mut self) -> Result<(), Error> {
+        self.send(GuiCommand::Stop)
+    }
+
+    pub fn send_setoption(&mut self, name: &str, value: Option<&str>) -> Result<(), Error> {
+        self.send(GuiCommand::SetOption(name.to_string(), value.map(|v| v.to_string())))
+    }
+
+    /// Set `Threads` and `Hash` to suit this machine, within the limits the engine announced, and
+    /// wait until the engine has applied them.  Options the engine doesn't have are left alone.
+    pub fn auto_configure(&mut self) -> Result<AutoConfig, Error> {
+        let config = self.send_auto_config()?;
+        self.send_isready()?;
+        self.auto_config = Some(config);
+        Ok(config)
+    }
+
+    /// What the last `auto_configure` chose, if it has been run.
+    pub fn get_auto_config(&self) -> Option<AutoConfig> {
+        self.auto_config
+    }
+
+    /// The niceness and CPU affinity the engine's process was given, when it was started by the
+    /// connection.  What the platform doesn't support, or refused, is missing.
+    pub fn get_process_controls(&self) -> Option<&ProcessControls> {
+        self.process_controls.as_ref()
+    }
+
+    /// The id of the engine's process, when it was started by the connection.
+    pub fn get_process_id(&self) -> Option<u32> {
+        self.process_id
+    }
+
+    /// The CPU time and memory the engine's process is using now.  Fails if the connection
+    /// didn't start the process, or, without the `resource-usage` feature or off Linux, always.
+    pub fn resource_usage(&self) -> Result<ResourceUsage, Error> {
+        resource_usage::sample(self.process_id.ok_or_else(resource_usage::not_a_process)?)
+    }
+
+    /// Sample the engine's resource usage every `interval` on another thread, until the monitor
+    /// is dropped, so that a memory cap can be enforced or a runaway engine noticed without
+    /// waiting on it.  Fails as `resource_usage` does.
+    pub fn monitor_resources(&self, interval: Duration) -> Result<ResourceMonitor, Error> {
+        let pid = self.process_id.ok_or_else(resource_usage::not_a_process)?;
+        resource_usage::sample(pid)?;
+        Ok(ResourceMonitor::start(pid, interval))
+    }
+
+    /// Whether a search is running, as far as the commands sent and received say.
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// What to do with a `setoption` sent while a search is running.
+    pub fn set_options_during_search(&mut self, policy: OptionsDuringSearch) {
+        self.options_during_search = policy;
+    }
+
+    pub fn get_options_during_search(&self) -> OptionsDuringSearch {
+        self.options_during_search
+    }
+
+    /// The `setoption` commands held back until the search has its best move.
+    pub fn get_queued_options(&self) -> &[GuiCommand] {
+        &self.queued_options
+    }
+
+    /// Stop, and then kill, an engine that searches far longer than it was given, from the next
+    /// `go`.  `None` lets it search as long as it likes.
+    pub fn set_supervision(&mut self, supervision: Option<Supervision>) {
+        if supervision.is_none() {
+            self.supervised_search = None;
+        }
+        self.supervision = supervision;
+    }
+
+    pub fn get_supervision(&self) -> Option<&Supervision> {
+        self.supervision.as_ref()
+    }
+
+    /// Close the connection at once, killing the engine's process if the connection started it.
+    /// Commands already received can still be read, but nothing more can be sent.
+    pub fn kill(&mut self) {
+        self.supervised_search = None;
+        self.state = ConnectionState::Idle;
+        self.queued_options.clear();
+        // Dropping the writer of a process kills the process.
+        self.writer = Box::new(ClosedWriter);
+    }
+
+    /// The last `lines` commands sent to and received from the engine, with `>` for those sent
+    /// and `<` for those received.
+    pub fn history_tail(&self, lines: usize) -> Vec<String> {
+        let entries = self.history.get_entries();
+        entries[entries.len().saturating_sub(lines)..]
+            .iter()
+            .map(|entry| {
+                let arrow = match entry.get_direction() {
+                    Direction::Sent => '>',
+                    Direction::Received => '<',
+                };
+                format!("{} {}", arrow, entry.get_command().to_string().trim_end())
+            })
+            .collect()
+    }
+
+    /// Replay the options changed in `options`, such as ones `EngineOptions::load`ed from a file,
+    /// and wait until the engine has applied them.  Options this engine didn't announce are
+    /// skipped, and their names returned.
+    pub fn apply_config(&mut self, options: &EngineOptions) -> Result<Vec<String>, Error> {
+        let mut skipped = vec![];
+        for (name, value) in options.get_changed() {
+            if self.engine_info.get_option(&name).is_some() {
+                self.send_setoption(&name, Some(&value))?;
+            } else {
+                skipped.push(name);
+            }
+        }
+        self.send_isready()?;
+        Ok(skipped)
     }
 
     pub fn send_go(&mut self) -> Result<(), Error> {
@@ -73,28 +526,221 @@ impl<'a> EngineConnection<'a> {
         if let Some(ref timer) = self.timer {
             go = go.combine(&((**timer).into()))
         }
+        self.send_timed_go(go)
+    }
 
+    // Send `go` and start the clock on it.
+    fn send_timed_go(&mut self, go: Go) -> Result<(), Error> {
         self.send(GuiCommand::Go(go))?;
+        self.analysis_history.clear();
         if let Some(ref mut timer) = self.timer {
             timer.start();
         }
         Ok(())
     }
 
+    /// Start a search with a `movetime` worked out from the timer by `policy`, rather than
+    /// telling the engine the clocks.  Without a timer, or a clock for the side to move, this is
+    /// just `send_go`.
+    pub fn send_go_budgeted(&mut self, policy: &BudgetPolicy) -> Result<(), Error> {
+        let movetime = match self.timer {
+            Some(ref timer) => timer.suggest_movetime(policy),
+            None => None,
+        };
+        let movetime = match movetime {
+            Some(movetime) => movetime,
+            None => return self.send_go(),
+        };
+
+        self.send_timed_go(Go::movetime(movetime.as_millis() as u64))
+    }
+
+    /// Start a search limited by `go` alone, whether or not there is a timer.
+    pub fn send_go_with(&mut self, go: Go) -> Result<(), Error> {
+        self.send(GuiCommand::Go(go))?;
+        self.analysis_history.clear();
+        Ok(())
+    }
+
+    /// The engine's best move, if it has already sent it.  Fails with `NoCommandError` if it
+    /// hasn't; use `recv_best_move_timeout` to wait for it.
+    ///
+    /// Like the other `recv_best_move` functions, this fails with `IllegalBestMove` if the move
+    /// can't be played in the position from the last `send_position`.  The null move is returned
+    /// as it is, for the caller to decide what it means.
     pub fn recv_best_move(&mut self) -> Result<BestMove, Error> {
         loop {
             match self.recv(Instant::now(), Duration::new(0, 0)) {
-                Ok(EngineCommand::BestMove(x)) => return Ok(x),
+                Ok(EngineCommand::BestMove(x)) => return self.check_best_move(x),
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the engine's best move, skipping everything sent before it.  On a
+    /// timeout the search is still running, so it has to be stopped, and its best move received,
+    /// before another can be started.
+    pub fn recv_best_move_timeout(&mut self, timeout: Duration) -> Result<BestMove, Error> {
+        let deadline = Instant::now() + timeout;
+        match self.recv_until(|c| matches!(c, EngineCommand::BestMove(_)), deadline)? {
+            EngineCommand::BestMove(best_move) => self.check_best_move(best_move),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Wait up to `timeout` for the engine's best move, like `recv_best_move_timeout`, and return
+    /// it with the `info`s the last search sent before it, including the final one with the score
+    /// and principal variation the move was chosen on.
+    ///
+    /// Should it time out, the `info`s received while waiting aren't returned, but are still in
+    /// the `history` and the `analysis_history`.
+    pub fn recv_search_result(&mut self, timeout: Duration) -> Result<SearchOutcome, Error> {
+        let deadline = Instant::now() + timeout;
+        let mut infos = vec![];
+        loop {
+            match self.recv_until(
+                |c| matches!(c, EngineCommand::Info(_) | EngineCommand::BestMove(_)),
+                deadline,
+            )? {
+                EngineCommand::Info(_) if self.answering_older_go() => {}
+                EngineCommand::Info(info) => infos.push(info),
+                EngineCommand::BestMove(best_move) => {
+                    let best_move = self.check_best_move(best_move)?;
+                    return Ok(SearchOutcome::new(best_move, infos));
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // The null move always gets through, as an engine that was stopped straight away may not
+    // have anything better to say.
+    pub(crate) fn check_best_move(&self, best_move: BestMove) -> Result<BestMove, Error> {
+        match best_move.get_move() {
+            Some(mv) if !self.searching.legal(mv) => Err(Error::IllegalBestMove {
+                mv,
+                board: self.searching,
+            }),
+            _ => Ok(best_move),
+        }
+    }
+
+    /// The next command from the engine, or `None` if there isn't one waiting.
+    pub fn try_recv_command(&mut self) -> Result<Option<EngineCommand>, Error> {
+        match self.recv(Instant::now(), Duration::new(0, 0)) {
+            Ok(c) => Ok(Some(c)),
+            Err(Error::NoCommandError) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Receive commands until one matches `predicate`, and return it.  The commands before it
+    /// are only kept in the history.  Fails with `RecvTimeout` once `deadline` has passed.
+    pub fn recv_until<F>(&mut self, mut predicate: F, deadline: Instant) -> Result<EngineCommand, Error>
+    where
+        F: FnMut(&EngineCommand) -> bool,
+    {
+        let start = Instant::now();
+        loop {
+            let now = Instant::now();
+            match self.recv(now, deadline.saturating_duration_since(now)) {
+                Ok(c) if predicate(&c) => return Ok(c),
                 Ok(_) => {}
+                Err(Error::NoCommandError) => {
+                    return Err(Error::RecvTimeout {
+                        waited: start.elapsed(),
+                    })
+                }
                 Err(e) => return Err(e),
             }
         }
     }
 
-    pub fn history(&self) -> &Vec<Command> {
+    /// Every command from the engine as it arrives, waiting without polling for each one.  The
+    /// iterator ends once the engine's output does; `reader_error` says why.
+    pub fn commands(&mut self) -> Commands<'_, 'a> {
+        Commands {
+            connection: self,
+            timeout: None,
+        }
+    }
+
+    /// The reason the engine output stopped being read, if it has.
+    ///
+    /// Once this returns `Some(..)`, no more commands will arrive from the engine; the commands
+    /// that were read before the error can still be received.
+    pub fn reader_error(&mut self) -> Option<Error> {
+        if self.reader_error.is_none() {
+            self.reader_error = self.errors.try_recv().ok();
+        }
+        self.reader_error.clone()
+    }
+
+    /// How many `info` lines were dropped or merged because they weren't received quickly enough.
+    pub fn dropped_infos(&self) -> u64 {
+        self.receiver.dropped()
+    }
+
+    /// How many `go` commands have been sent.  Each one starts a new search.
+    pub fn go_generation(&self) -> u64 {
+        self.gos_sent
+    }
+
+    /// How many best moves were thrown away because they answered an older `go` than the last,
+    /// or no `go` at all, as some engines send after `stop`.  They are still in the history.
+    pub fn stale_best_moves(&self) -> u64 {
+        self.stale_best_moves
+    }
+
+    /// Everything sent to and received from the engine since the connection was made.
+    pub fn history(&self) -> &History {
         &self.history
     }
 
+    /// The name, author and options the engine announced in reply to `uci`.
+    pub fn engine_info(&self) -> &EngineInfo {
+        &self.engine_info
+    }
+
+    /// The engine's result at each depth of the search started by the last `send_go`, from the
+    /// `info` lines received so far.
+    pub fn analysis_history(&self) -> &AnalysisHistory {
+        &self.analysis_history
+    }
+
+    /// The game from the last `send_position`, with every legal best move the engine has
+    /// played since.
+    pub fn game_history(&self) -> &GameHistory {
+        &self.game
+    }
+
+    pub fn current_board(&self) -> Board {
+        self.game.get_board()
+    }
+
+    pub fn is_threefold(&self) -> bool {
+        self.game.is_threefold()
+    }
+
+    pub fn is_fifty_move(&self) -> bool {
+        self.game.is_fifty_move()
+    }
+
+    pub fn legal_moves(&self) -> Vec<ChessMove> {
+        self.game.legal_moves()
+    }
+
+    /// Start recording every line sent and received, dropping any transcript already started.
+    pub fn start_transcript(&mut self) {
+        self.transcript = Some(Transcript::new());
+    }
+
+    /// Stop recording, and return what was recorded since `start_transcript`.
+    pub fn take_transcript(&mut self) -> Option<Transcript> {
+        self.transcript.take()
+    }
+
     pub fn recv_best_move_using_timer(&mut self) -> Result<BestMove, Error> {
         // check to make sure there is a timer, and that it was started
         if let Some(ref mut timer) = self.timer {
@@ -112,23 +758,23 @@ impl<'a> EngineConnection<'a> {
         // crashes.
         let mut best_move: Option<BestMove> = None;
         while best_move.is_none() {
-            match self.recv_best_move() {
-                Ok(x) => {
+            // Wait for as long as the engine has left on its clock, and no longer.
+            let remaining = match self.timer {
+                Some(ref timer) if timer.timeout_for(timer.get_player()) => break,
+                Some(ref timer) => timer.remaining(),
+                None => unreachable!(),
+            };
+            let received = match remaining {
+                Some(remaining) => self.recv(Instant::now(), remaining),
+                None => self.recv_blocking(),
+            };
+            match received {
+                Ok(EngineCommand::BestMove(x)) => {
                     best_move = Some(x);
                 }
-                Err(Error::NoCommandError) => {}
+                Ok(_) | Err(Error::NoCommandError) => {}
                 Err(x) => return Err(x),
             };
-
-            if let Some(ref timer) = self.timer {
-                if timer.timeout_for(timer.get_player()) {
-                    break;
-                }
-            }
-
-            // Don't peg the CPU.  The engine *is* using it to think,
-            // afer all...
-            sleep(Duration::from_millis(1));
         }
 
         // tell the timer the engine made its move.  Additionally,
@@ -140,7 +786,7 @@ impl<'a> EngineConnection<'a> {
                 if timer.timeout_for(!timer.get_player()) {
                     return Err(Error::Timeout);
                 }
-                return Ok(best_move);
+                return self.check_best_move(best_move);
             } else {
                 return Err(Error::Timeout);
             }
@@ -151,42 +797,302 @@ impl<'a> EngineConnection<'a> {
         unreachable!();
     }
 
-    fn send(&mut self, command: GuiCommand) -> Result<(), Error> {
-        self.stdin.write_all(command.to_string().as_bytes())?;
-        self.history.push(Command::new_from_gui(command));
+    // Fails with `InvalidTransition`, without sending anything, if `command` can't be sent in
+    // the connection's state.
+    pub(crate) fn send(&mut self, command: GuiCommand) -> Result<(), Error> {
+        let line = command.to_string();
+        if let GuiCommand::SetOption(..) = command {
+            match self.options_during_search {
+                OptionsDuringSearch::Queue if self.state.is_searching() => {
+                    self.queued_options.push(command);
+                    return Ok(());
+                }
+                OptionsDuringSearch::Refuse if self.state.is_searching() => {
+                    return Err(Error::InvalidTransition {
+                        state: self.state,
+                        command: line.trim_end().to_string(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        let state = match self.state.after_sending(&command) {
+            Some(state) => state,
+            None => {
+                return Err(Error::InvalidTransition {
+                    state: self.state,
+                    command: line.trim_end().to_string(),
+                })
+            }
+        };
+        self.writer.write_all(line.as_bytes())?;
+        self.state = state;
+        self.note(Direction::Sent, &line);
+        if let GuiCommand::Go(ref go) = command {
+            self.gos_sent += 1;
+            let now = Instant::now();
+            let player = self.searching.side_to_move();
+            self.supervised_search = self
+                .supervision
+                .as_ref()
+                .and_then(|supervision| supervision.deadline(go, player, now))
+                .map(|deadline| (now, deadline));
+        }
+        self.history.push(Direction::Sent, Command::new_from_gui(command));
         Ok(())
     }
 
+    fn note(&mut self, direction: Direction, line: &str) {
+        if let Some(ref mut transcript) = self.transcript {
+            transcript.push(direction, line);
+        }
+    }
+
+    // Send a line typed by the user, which may not be a command this crate knows.
+    fn send_line(&mut self, line: &str) -> Result<(), Error> {
+        match GuiCommand::from_str(line) {
+            Ok(command) => self.send(command),
+            Err(_) => {
+                writeln!(self.writer, "{}", line.trim_end())?;
+                self.note(Direction::Sent, line);
+                self.history.push(Direction::Sent, Command::Unknown(line.to_string()));
+                Ok(())
+            }
+        }
+    }
+
+    /// Find out which optional features the engine really supports, by running a few shallow
+    /// searches from the starting position.
+    ///
+    /// `UCI_Chess960` can only be checked against the options the engine announced, as there's no
+    /// way to set up a Chess960 position here.  Options changed while probing are put back to
+    /// their defaults, but the engine is left at the starting position.
+    pub fn probe_capabilities(&mut self) -> Result<Capabilities, Error> {
+        let mut capabilities = Capabilities::empty();
+        let start = Board::default();
+        let a3 = ChessMove::new(Square::A2, Square::A3, None);
+
+        self.send_position(start, vec![])?;
+        let (_, best_move) = self.search(Go::depth(2).combine(&Go::search_moves(vec![a3])))?;
+        if best_move.get_move() == Some(a3) {
+            capabilities.insert(Capabilities::SEARCH_MOVES);
+        }
+
+        if let Some(default) = self.option_default("MultiPV") {
+            self.send_setoption("MultiPV", Some("2"))?;
+            let (infos, _) = self.search(Go::depth(2))?;
+            if infos.iter().any(|i| i.get_multi_pv() == Some(2)) {
+                capabilities.insert(Capabilities::MULTI_PV);
+            }
+            self.send_setoption("MultiPV", Some(&default))?;
+        }
+
+        if let Some(default) = self.option_default("Ponder") {
+            self.send_setoption("Ponder", Some("true"))?;
+            let (_, best_move) = self.search(Go::depth(4))?;
+            if best_move.get_ponder().is_some() {
+                capabilities.insert(Capabilities::PONDER);
+            }
+            self.send_setoption("Ponder", Some(&default))?;
+        }
+
+        if let Some(OptionType::Check(_)) = self
+            .engine_info
+            .get_option("UCI_Chess960")
+            .map(|o| o.get_option_type())
+        {
+            capabilities.insert(Capabilities::CHESS960);
+        }
+
+        // Engines check the node count every so often, so allow some overshoot.
+        let (infos, _) = self.search(Go::nodes(PROBE_NODES))?;
+        if let Some(nodes) = infos.iter().rev().find_map(|i| i.get_nodes()) {
+            if nodes <= 2 * PROBE_NODES as Counter {
+                capabilities.insert(Capabilities::NODES);
+            }
+        }
+
+        self.send_isready()?;
+        Ok(capabilities)
+    }
+
+    /// Search each of `positions` for `movetime`, so the engine has loaded what it loads lazily
+    /// and filled its hash before a game that counts.  Call it once the engine is configured, as
+    /// changing `Hash` afterwards throws the hash away again.  The engine is left at the last of
+    /// the positions.
+    pub fn warm_up(
+        &mut self,
+        positions: &[Board],
+        movetime: Duration,
+    ) -> Result<WarmUpReport, Error> {
+        let mut searches = vec![];
+        for &board in positions {
+            self.send_position(board, vec![])?;
+            let start = Instant::now();
+            self.send_go_with(Go::movetime(movetime.as_millis() as u64))?;
+            self.recv_best_move_timeout(movetime + WARM_UP_GRACE)?;
+            let nodes = self.analysis_history.get_last().and_then(|info| info.get_nodes());
+            searches.push(WarmUpSearch::new(board, start.elapsed(), nodes));
+        }
+        self.send_isready()?;
+        Ok(WarmUpReport::new(searches))
+    }
+
+    fn option_default(&self, name: &str) -> Option<String> {
+        match self.engine_info.get_option(name).map(|o| o.get_option_type()) {
+            Some(OptionType::Spin(default, _, _)) => Some(default.to_string()),
+            Some(OptionType::Check(default)) => Some(default.to_string()),
+            _ => None,
+        }
+    }
+
+    // Run one search to the end, returning everything the engine said about it.
+    fn search(&mut self, go: Go) -> Result<(Vec<Info>, BestMove), Error> {
+        self.send(GuiCommand::Go(go))?;
+        let start = Instant::now();
+        let mut infos = vec![];
+        loop {
+            match self.recv(start, PROBE_TIMEOUT)? {
+                EngineCommand::Info(info) => infos.push(info),
+                EngineCommand::BestMove(best_move) => return Ok((infos, best_move)),
+                _ => {}
+            }
+        }
+    }
+
+    fn send_auto_config(&mut self) -> Result<AutoConfig, Error> {
+        let config = AutoConfig::choose(&self.engine_info, SystemResources::detect());
+        if let Some(threads) = config.get_threads() {
+            self.send_setoption("Threads", Some(&threads.to_string()))?;
+        }
+        if let Some(hash) = config.get_hash() {
+            self.send_setoption("Hash", Some(&hash.to_string()))?;
+        }
+        Ok(config)
+    }
+
     fn send_uci(&mut self) -> Result<(), Error> {
         self.send(GuiCommand::Uci)?;
         self.recv_uci_ok()
     }
 
+    // A supervised search that runs past its deadline is stopped here, whatever the timeout.
     fn recv(&mut self, start: Instant, timeout: Duration) -> Result<EngineCommand, Error> {
         loop {
-            match self.receiver.try_recv() {
-                Ok(Command::Engine(c)) => {
-                    self.history.push(Command::Engine(c.clone()));
-                    return Ok(c);
+            let mut remaining = timeout.checked_sub(start.elapsed()).unwrap_or_default();
+            if let Some((_, deadline)) = self.supervised_search {
+                remaining = remaining.min(deadline.saturating_duration_since(Instant::now()));
+            }
+            match self.receiver.recv_timeout(remaining) {
+                Ok(c) => {
+                    if let Some(c) = self.record(c) {
+                        return Ok(c);
+                    }
                 }
 
-                Ok(c) => {
-                    self.history.push(c);
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(self.reader_error().unwrap_or(Error::EngineDeadError))
                 }
 
-                Err(TryRecvError::Disconnected) => return Err(Error::EngineDeadError),
+                Err(RecvTimeoutError::Timeout) => match self.supervised_search {
+                    Some((_, deadline)) if Instant::now() >= deadline => return self.force_stop(),
+                    _ => break,
+                },
+            }
+        }
+
+        Err(Error::NoCommandError)
+    }
 
-                Err(TryRecvError::Empty) => {
-                    if start.elapsed() < timeout {
-                        sleep(Duration::from_millis(1));
-                    } else {
-                        break;
+    // Stop a search that has run past its deadline, waiting for its best move for the stop
+    // grace, and kill the engine if it doesn't come.
+    fn force_stop(&mut self) -> Result<EngineCommand, Error> {
+        let (started, _) = self.supervised_search.take().unwrap();
+        let supervision = self.supervision.clone().unwrap();
+        if self.send_stop().is_ok() {
+            let deadline = Instant::now() + supervision.get_stop_grace();
+            match self.recv_until(|c| matches!(c, EngineCommand::BestMove(_)), deadline) {
+                Err(Error::RecvTimeout { .. }) => {}
+                received => return received,
+            }
+        }
+        self.kill();
+        Err(Error::EngineUnresponsive {
+            waited: started.elapsed(),
+            transcript_tail: self.history_tail(supervision.get_tail_lines()),
+        })
+    }
+
+    // Like `recv`, without a timeout, unless a search is under supervision.
+    fn recv_blocking(&mut self) -> Result<EngineCommand, Error> {
+        if self.supervised_search.is_some() {
+            return self.recv(Instant::now(), Duration::MAX);
+        }
+        loop {
+            match self.receiver.recv() {
+                Ok(c) => {
+                    if let Some(c) = self.record(c) {
+                        return Ok(c);
                     }
                 }
+                Err(_) => return Err(self.reader_error().unwrap_or(Error::EngineDeadError)),
             }
         }
+    }
 
-        Err(Error::NoCommandError)
+    // Whether what the engine sends is still for an older search than the last `go` started.
+    fn answering_older_go(&self) -> bool {
+        self.gos_answered + 1 < self.gos_sent
+    }
+
+    // Keep `c` in the history, returning it if it came from the engine.
+    fn record(&mut self, c: Command) -> Option<EngineCommand> {
+        if self.transcript.is_some() {
+            self.note(Direction::Received, &c.to_string());
+        }
+        match c {
+            Command::Engine(c) => {
+                match c {
+                    // Until an older search has its best move, its output isn't this search's.
+                    EngineCommand::Info(_) if self.answering_older_go() => {}
+                    EngineCommand::Info(ref info) => {
+                        self.analysis_history.update(info);
+                        // A clock counted in nodes only moves as the engine reports them.
+                        if let (Some(timer), Some(nodes)) = (self.timer.as_mut(), info.get_nodes()) {
+                            timer.set_nodes_searched(nodes.to_u64().unwrap_or(u64::MAX));
+                        }
+                    }
+                    // An illegal move is left for the caller to deal with; it isn't part of the game.
+                    EngineCommand::BestMove(ref best_move) => {
+                        let current = self.gos_answered + 1 == self.gos_sent;
+                        self.gos_answered = (self.gos_answered + 1).min(self.gos_sent);
+                        if !current {
+                            self.stale_best_moves += 1;
+                            self.history.push(Direction::Received, Command::Engine(c));
+                            return None;
+                        }
+                        self.supervised_search = None;
+                        self.state = ConnectionState::Idle;
+                        if let Some(m) = best_move.get_move() {
+                            let _ = self.game.push(m);
+                        }
+                    }
+                    _ => {}
+                }
+                self.history.push(Direction::Received, Command::Engine(c.clone()));
+                if !self.state.is_searching() {
+                    // Should the engine have gone, the next receive says so.
+                    for option in mem::take(&mut self.queued_options) {
+                        let _ = self.send(option);
+                    }
+                }
+                Some(c)
+            }
+            c => {
+                self.history.push(Direction::Received, c);
+                None
+            }
+        }
     }
 
     fn recv_uci_ok(&mut self) -> Result<(), Error> {
@@ -195,13 +1101,13 @@ impl<'a> EngineConnection<'a> {
         loop {
             match self.recv(start, Duration::new(5, 0)) {
                 Ok(EngineCommand::UciOk) => return Ok(()),
-                Ok(_) => {}
+                Ok(c) => self.engine_info.update(&c),
                 Err(e) => return Err(e),
             }
         }
     }
 
-    fn send_isready(&mut self) -> Result<(), Error> {
+    pub(crate) fn send_isready(&mut self) -> Result<(), Error> {
         self.send(GuiCommand::IsReady)?;
         self.recv_ready_ok()
     }
@@ -218,6 +1124,646 @@ impl<'a> EngineConnection<'a> {
     }
 }
 
+#[cfg(test)]
+use std::io::Cursor;
+
+#[cfg(test)]
+use std::io::Read;
+
+#[cfg(test)]
+fn drain(rx: QueueReceiver) -> Vec<Command> {
+    let mut commands = vec![];
+    while let Ok(command) = rx.recv_timeout(Duration::new(0, 0)) {
+        commands.push(command);
+    }
+    commands
+}
+
+#[cfg(test)]
+fn read_all_commands(transcript: &[u8]) -> Vec<Command> {
+    let (tx, rx) = command_queue(1024, OverflowPolicy::Block);
+    let (error_tx, _error_rx) = channel();
+    read_commands(Cursor::new(transcript.to_vec()), tx, error_tx);
+    drain(rx)
+}
+
+// Plays back a scripted sequence of reads: `Ok` chunks are returned as data, `Err` kinds as errors.
+// Once the script runs out, the pipe is broken.
+#[cfg(test)]
+struct ScriptedReader {
+    script: Vec<Result<&'static [u8], ErrorKind>>,
+}
+
+#[cfg(test)]
+impl Read for ScriptedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.script.is_empty() {
+            return Err(io::Error::new(ErrorKind::BrokenPipe, "pipe failed"));
+        }
+        match self.script.remove(0) {
+            Ok(chunk) => {
+                buf[..chunk.len()].copy_from_slice(chunk);
+                Ok(chunk.len())
+            }
+            Err(kind) => Err(io::Error::new(kind, "scripted failure")),
+        }
+    }
+}
+
+#[cfg(test)]
+fn read_script(script: Vec<Result<&'static [u8], ErrorKind>>) -> (Vec<Command>, Vec<Error>) {
+    let (tx, rx) = command_queue(1024, OverflowPolicy::Block);
+    let (error_tx, error_rx) = channel();
+    read_commands(BufReader::new(ScriptedReader { script }), tx, error_tx);
+    (drain(rx), error_rx.iter().collect())
+}
+
+#[test]
+fn test_reader_survives_garbage() {
+    let commands = read_all_commands(b"Stockfish 10 by T. Romstad\n\xff\xfe garbage\nuciok\n");
+
+    assert_eq!(commands.len(), 3);
+    assert!(matches!(commands[0], Command::Unknown(_)));
+    assert!(matches!(commands[1], Command::Unknown(_)));
+    assert_eq!(commands[2], Command::Engine(EngineCommand::UciOk));
+}
+
+#[test]
+fn test_reader_bounds_line_length() {
+    let mut transcript = b"info depth 1 pv".to_vec();
+    while transcript.len() <= MAX_LINE_LENGTH {
+        transcript.extend_from_slice(b" e2e4");
+    }
+    transcript.extend_from_slice(b"\nreadyok\n");
+    let commands = read_all_commands(&transcript);
+
+    assert_eq!(commands.len(), 2);
+    match commands[0] {
+        Command::Unknown(ref line) => assert_eq!(line.len(), MAX_LINE_LENGTH),
+        ref c => panic!("expected an unknown command, got {:?}", c),
+    }
+    assert_eq!(commands[1], Command::Engine(EngineCommand::ReadyOk));
+}
+
+#[test]
+fn test_reader_stops_at_eof() {
+    assert_eq!(read_all_commands(b"readyok"), vec![Command::Engine(EngineCommand::ReadyOk)]);
+    assert_eq!(read_all_commands(b""), vec![]);
+}
+
+#[test]
+fn test_reader_reports_eof() {
+    let (commands, errors) = read_script(vec![Ok(b"uciok\n"), Ok(b"")]);
+
+    assert_eq!(commands, vec![Command::Engine(EngineCommand::UciOk)]);
+    assert_eq!(errors, vec![Error::EngineDeadError]);
+}
+
+#[test]
+fn test_reader_reports_io_error() {
+    let (commands, errors) = read_script(vec![Ok(b"readyok\n"), Ok(b"garbage\n")]);
+
+    assert_eq!(commands.len(), 2);
+    assert_eq!(commands[0], Command::Engine(EngineCommand::ReadyOk));
+    assert!(matches!(commands[1], Command::Unknown(_)));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].io_error_kind(), Some(ErrorKind::BrokenPipe));
+}
+
+#[test]
+fn test_reader_retries_interrupted() {
+    let (commands, errors) =
+        read_script(vec![Ok(b"uci"), Err(ErrorKind::Interrupted), Ok(b"ok\n"), Ok(b"")]);
+
+    assert_eq!(commands, vec![Command::Engine(EngineCommand::UciOk)]);
+    assert_eq!(errors, vec![Error::EngineDeadError]);
+}
+
+#[cfg(test)]
+use engine::id::Id;
+#[cfg(test)]
+use engine::score::Score;
+#[cfg(test)]
+use transport::tcp::TcpTransport;
+#[cfg(test)]
+use test_engines::{connect_fake, fake_engine, scripted_engine};
+#[cfg(test)]
+use chess::Color;
+
+// The `setoption` lines among those an engine received.
+#[cfg(test)]
+fn setoptions(received: Vec<String>) -> Vec<String> {
+    received
+        .into_iter()
+        .filter(|line| line.starts_with("setoption"))
+        .collect()
+}
+
+#[test]
+fn test_connect_over_tcp() {
+    // A fake engine that only knows how to handshake.
+    let (address, engine) = scripted_engine(
+        "id name Fake\noption name Ponder type check default false\n",
+        |_, _| true,
+    );
+
+    let mut connection = connect_fake(&address);
+    assert_eq!(
+        connection.history()[1].get_command(),
+        &Command::Engine(EngineCommand::Id(Id::name("Fake")))
+    );
+    assert_eq!(connection.engine_info().get_name(), Some(&"Fake".to_string()));
+    assert!(connection.engine_info().supports_ponder());
+    assert!(!connection.engine_info().supports_multipv());
+    connection.send(GuiCommand::Quit).unwrap();
+    engine.join().unwrap();
+}
+
+#[test]
+fn test_auto_configure_over_tcp() {
+    let (address, engine) =
+        scripted_engine("option name Threads type spin default 1 min 1 max 2\n", |_, _| true);
+
+    let mut connection = EngineConnectionBuilder::from_transport(TcpTransport::new(&address))
+        .auto_configure(true)
+        .connect()
+        .unwrap();
+    let config = connection.get_auto_config().unwrap();
+    assert!(config.get_threads().unwrap() >= 1);
+    assert!(config.get_threads().unwrap() <= 2);
+    assert_eq!(config.get_hash(), None);
+
+    connection.send(GuiCommand::Quit).unwrap();
+    assert_eq!(
+        setoptions(engine.join().unwrap()),
+        vec![format!("setoption name Threads value {}", config.get_threads().unwrap())]
+    );
+}
+
+#[test]
+fn test_options_and_init_strings_over_tcp() {
+    let (address, engine) = scripted_engine("", |_, _| true);
+
+    let mut connection = EngineConnectionBuilder::from_transport(TcpTransport::new(&address))
+        .option("Hash", Some("32"))
+        .init_string("debug on")
+        .init_string("xyzzy 1")
+        .connect()
+        .unwrap();
+    assert!(connection
+        .history()
+        .by_direction(Direction::Sent)
+        .any(|c| c == &Command::Unknown("xyzzy 1".to_string())));
+    connection.send(GuiCommand::Quit).unwrap();
+    // Everything is sent between the handshake and `isready`.
+    assert_eq!(
+        engine.join().unwrap(),
+        vec!["uci", "setoption name Hash value 32", "debug on", "xyzzy 1", "isready", "quit"]
+    );
+}
+
+#[test]
+fn test_resource_usage_needs_a_process() {
+    let connection = connect_fake(&fake_engine(false));
+    assert_eq!(connection.get_process_id(), None);
+    assert!(connection.resource_usage().is_err());
+    assert!(connection
+        .monitor_resources(Duration::from_millis(10))
+        .is_err());
+}
+
+#[test]
+fn test_process_controls_need_a_process() {
+    let result = EngineConnectionBuilder::from_transport(TcpTransport::new("127.0.0.1:1"))
+        .process_controls(ProcessControls::new().nice(10))
+        .connect();
+    match result {
+        Err(Error::ConfigError(_)) => {}
+        _ => panic!("process controls were accepted for a TCP engine"),
+    }
+}
+
+#[test]
+fn test_apply_config_over_tcp() {
+    let (address, engine) =
+        scripted_engine("option name Hash type spin default 16 min 1 max 1024\n", |_, _| true);
+
+    let mut options = EngineOptions::default();
+    options.create_spin("Hash".to_string(), 16, 1, 1024);
+    options.create_spin("Threads".to_string(), 1, 1, 8);
+    options.create_check("Ponder".to_string(), false);
+    options.set_option("Hash", Some("64")).unwrap();
+    options.set_option("Threads", Some("4")).unwrap();
+
+    let mut connection = connect_fake(&address);
+    assert_eq!(connection.apply_config(&options), Ok(vec!["Threads".to_string()]));
+    connection.send(GuiCommand::Quit).unwrap();
+    assert_eq!(setoptions(engine.join().unwrap()), vec!["setoption name Hash value 64"]);
+}
+
+#[test]
+fn test_from_config_rejects_xboard() {
+    let xboard = EngineConfig::new("GNU Chess", "gnuchess -x").protocol("xboard");
+    assert!(EngineConnectionBuilder::from_config(&xboard).is_err());
+}
+
+#[test]
+fn test_commands_iterator() {
+    // Answers one `go` slowly, then quits on its own after the second.
+    let mut searched = false;
+    let (address, engine) = scripted_engine("", move |line, output| match line {
+        "go" if !searched => {
+            searched = true;
+            sleep(Duration::from_millis(50));
+            output.write_all(b"info depth 1\nbestmove e2e4\n").unwrap();
+            true
+        }
+        "go" => false,
+        _ => true,
+    });
+
+    let mut connection = connect_fake(&address);
+    connection.send_go().unwrap();
+    let received = connection.commands().take(2).collect::<Vec<Result<EngineCommand, Error>>>();
+    assert_eq!(received[0], Ok(EngineCommand::Info(Info::depth(1))));
+    assert!(matches!(received[1], Ok(EngineCommand::BestMove(_))));
+
+    let mut commands = connection.commands().timeout(Duration::from_millis(10));
+    assert_eq!(
+        commands.next(),
+        Some(Err(Error::RecvTimeout {
+            waited: Duration::from_millis(10)
+        }))
+    );
+
+    connection.send_go().unwrap();
+    engine.join().unwrap();
+    assert_eq!(connection.commands().next(), None);
+    assert_eq!(connection.reader_error(), Some(Error::EngineDeadError));
+}
+
+#[test]
+fn test_game_history_over_tcp() {
+    // Always brings its knight home.
+    let (address, engine) = scripted_engine("", |line, output| {
+        if line == "go" {
+            output.write_all(b"bestmove f6g8\n").unwrap();
+        }
+        true
+    });
+
+    let shuffle = vec![
+        ChessMove::new(Square::G1, Square::F3, None),
+        ChessMove::new(Square::G8, Square::F6, None),
+        ChessMove::new(Square::F3, Square::G1, None),
+        ChessMove::new(Square::F6, Square::G8, None),
+    ];
+    let mut moves = shuffle.clone();
+    moves.extend_from_slice(&shuffle[..3]);
+
+    let mut connection = connect_fake(&address);
+    connection.send_position(Board::default(), moves).unwrap();
+    assert!(!connection.is_threefold());
+    connection.send_go().unwrap();
+    connection.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+    assert!(connection.is_threefold());
+    assert_eq!(connection.current_board(), Board::default());
+    assert_eq!(connection.game_history().get_moves().len(), 8);
+    assert_eq!(connection.legal_moves().len(), 20);
+    assert!(!connection.is_fifty_move());
+
+    let sent = connection.history().len();
+    assert!(matches!(
+        connection.send_position(Board::default(), shuffle[1..].to_vec()),
+        Err(Error::IllegalMove { index: 0, .. })
+    ));
+    assert_eq!(connection.history().len(), sent);
+    assert!(connection.is_threefold());
+
+    connection.send(GuiCommand::Quit).unwrap();
+    engine.join().unwrap();
+}
+
+#[test]
+fn test_illegal_best_move() {
+    // Always plays 1. e4, whatever the position, or gives up when it may only look one ply ahead.
+    let (address, engine) = scripted_engine("", |line, output| {
+        match line {
+            "go" => output.write_all(b"bestmove e2e4\n").unwrap(),
+            "go depth 1" => output.write_all(b"bestmove 0000\n").unwrap(),
+            _ => {}
+        }
+        true
+    });
+
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let mut connection = connect_fake(&address);
+    connection.send_go().unwrap();
+    assert_eq!(connection.recv_best_move_timeout(Duration::from_secs(5)).unwrap().get_move(), Some(e2e4));
+
+    connection.send_position(Board::default(), vec![e2e4]).unwrap();
+    let board = connection.current_board();
+    connection.send_go().unwrap();
+    let error = connection.recv_best_move_timeout(Duration::from_secs(5)).unwrap_err();
+    assert_eq!(error, Error::IllegalBestMove { mv: e2e4, board });
+    assert!(!error.is_fatal());
+    assert_eq!(connection.game_history().get_moves(), &[e2e4]);
+
+    connection.send_go_with(Go::depth(1)).unwrap();
+    let best_move = connection.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+    assert!(best_move.is_null());
+    assert_eq!(connection.game_history().get_moves(), &[e2e4]);
+
+    connection.send(GuiCommand::Quit).unwrap();
+    engine.join().unwrap();
+}
+
+#[test]
+fn test_recv_with_deadlines() {
+    // Thinks for a while, and only answers `go` once.
+    let mut searched = false;
+    let (address, engine) = scripted_engine("", move |line, output| {
+        if line == "go" && !searched {
+            searched = true;
+            sleep(Duration::from_millis(50));
+            output
+                .write_all(b"info depth 1 score cp 20 pv d2d4\ninfo depth 2 score cp 35 pv e2e4\nbestmove e2e4\n")
+                .unwrap();
+        }
+        true
+    });
+
+    let mut connection = connect_fake(&address);
+    assert_eq!(connection.try_recv_command(), Ok(None));
+
+    connection.send_go().unwrap();
+    let best_move = connection.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(best_move.get_move(), Some(ChessMove::new(Square::E2, Square::E4, None)));
+    assert_eq!(connection.analysis_history().len(), 2);
+    assert_eq!(connection.analysis_history().best_move_changes(), 1);
+
+    connection.send_go().unwrap();
+    assert!(connection.analysis_history().is_empty());
+    let error = connection.recv_best_move_timeout(Duration::from_millis(20)).unwrap_err();
+    assert!(error.is_timeout());
+    assert!(!error.is_fatal());
+    match error {
+        Error::RecvTimeout { waited } => assert!(waited >= Duration::from_millis(20)),
+        e => panic!("unexpected error {}", e),
+    }
+
+    connection.send(GuiCommand::Quit).unwrap();
+    engine.join().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    assert_eq!(connection.recv_until(|_| true, deadline), Err(Error::EngineDeadError));
+}
+
+#[test]
+fn test_supervision() {
+    // Never finishes a search by itself, and only answers the first `stop`.
+    let mut stopped = false;
+    let (address, _) = scripted_engine("", move |line, output| {
+        if line == "stop" && !stopped {
+            stopped = true;
+            output.write_all(b"bestmove e2e4\n").unwrap();
+        }
+        true
+    });
+
+    let supervision = Supervision::new(1.0)
+        .slack(Duration::new(0, 0))
+        .stop_grace(Duration::from_millis(200))
+        .tail_lines(2);
+    let mut connection = EngineConnectionBuilder::from_transport(TcpTransport::new(&address))
+        .supervision(supervision)
+        .connect()
+        .unwrap();
+
+    connection.send_go_with(Go::movetime(20)).unwrap();
+    let best_move = connection.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(best_move.get_move(), Some(ChessMove::new(Square::E2, Square::E4, None)));
+    assert_eq!(connection.history_tail(2), vec!["> stop", "< bestmove e2e4"]);
+
+    connection.send_go_with(Go::movetime(20)).unwrap();
+    match connection.recv_best_move() {
+        Err(Error::NoCommandError) => {}
+        r => panic!("unexpected {:?}", r),
+    }
+    let error = connection.recv_best_move_timeout(Duration::from_secs(5)).unwrap_err();
+    assert!(error.is_fatal());
+    match error {
+        Error::EngineUnresponsive {
+            waited,
+            transcript_tail,
+        } => {
+            assert!(waited >= Duration::from_millis(220));
+            assert_eq!(transcript_tail, vec!["> go movetime 20", "> stop"]);
+        }
+        e => panic!("unexpected error {}", e),
+    }
+    assert!(connection.send_stop().is_err());
+}
+
+#[test]
+fn test_supervision_removed_mid_search() {
+    let (address, _) = scripted_engine("", |_, _| true);
+    let mut connection = EngineConnectionBuilder::from_transport(TcpTransport::new(&address))
+        .supervision(Supervision::new(1.0).slack(Duration::new(0, 0)))
+        .connect()
+        .unwrap();
+
+    // Without supervision the search is left to run past its deadline.
+    connection.send_go_with(Go::movetime(20)).unwrap();
+    connection.set_supervision(None);
+    match connection.recv_best_move_timeout(Duration::from_millis(200)) {
+        Err(Error::RecvTimeout { .. }) => {}
+        r => panic!("unexpected {:?}", r),
+    }
+    assert!(connection.state().is_searching());
+}
+
+#[test]
+fn test_probe_capabilities() {
+    // Honors searchmoves and multipv, but not ponder, and blows through node limits.
+    let mut multi_pv = 1;
+    let options = "option name MultiPV type spin default 1 min 1 max 4\n\
+                   option name Ponder type check default false\n";
+    let (address, engine) = scripted_engine(options, move |line, output| {
+        if line.starts_with("setoption name MultiPV value ") {
+            multi_pv = line.rsplit(' ').next().unwrap().parse().unwrap();
+        } else if line.starts_with("go") {
+            for i in 1..=multi_pv {
+                writeln!(output, "info depth 1 multipv {} nodes 1000000 pv e2e4", i).unwrap();
+            }
+            let best = if line.contains("searchmoves") { "a2a3" } else { "e2e4" };
+            writeln!(output, "bestmove {}", best).unwrap();
+        }
+        true
+    });
+
+    let mut connection = connect_fake(&address);
+    assert_eq!(
+        connection.probe_capabilities().unwrap(),
+        Capabilities::SEARCH_MOVES | Capabilities::MULTI_PV
+    );
+    connection.send(GuiCommand::Quit).unwrap();
+    engine.join().unwrap();
+}
+
+#[test]
+fn test_send_go_budgeted() {
+    let address = fake_engine(false);
+    let mut timer = Timer::new_with_increment(Duration::new(60, 0), Duration::new(1, 0));
+    let mut e = connect_fake(&address);
+    e.set_timer(&mut timer);
+    e.start_transcript();
+    e.send_position(Board::default(), vec![]).unwrap();
+    e.send_go_budgeted(&BudgetPolicy::new(10)).unwrap();
+    e.recv_best_move_using_timer().unwrap();
+
+    let transcript = e.take_transcript().unwrap();
+    assert!(transcript.get_lines().iter().any(|l| l.get_line() == "go movetime 7000"));
+}
+
+#[test]
+fn test_play_move() {
+    let address = fake_engine(false);
+    let mut timer = Timer::new_with_increment(Duration::new(60, 0), Duration::new(1, 0));
+    let mut e = connect_fake(&address);
+    e.set_timer(&mut timer);
+    e.send_position(Board::default(), vec![]).unwrap();
+    e.send_go().unwrap();
+    let engine_move = e.recv_best_move_using_timer().unwrap().get_move().unwrap();
+
+    e.start_transcript();
+    let e7e5 = ChessMove::new(Square::E7, Square::E5, None);
+    let sent = e.history().len();
+    assert!(matches!(
+        e.play_move(ChessMove::new(Square::E2, Square::E4, None)),
+        Err(Error::IllegalMove { index: 1, .. })
+    ));
+    assert_eq!(e.history().len(), sent);
+    e.play_move(e7e5).unwrap();
+    assert_eq!(e.game_history().get_moves(), &[engine_move, e7e5]);
+
+    // The engine can move again straight away, against the position it was sent.
+    e.send_go().unwrap();
+    let reply = e.recv_best_move_using_timer().unwrap().get_move().unwrap();
+    assert_eq!(e.game_history().get_moves(), &[engine_move, e7e5, reply]);
+    let transcript = e.take_transcript().unwrap();
+    assert_eq!(
+        transcript.get_lines()[0].get_line(),
+        format!("position startpos moves {} e7e5", engine_move)
+    );
+    drop(e);
+    assert_eq!(timer.get_player(), Color::Black);
+}
+
+#[test]
+fn test_warm_up() {
+    let address = fake_engine(false);
+    let mut e = connect_fake(&address);
+    let endgame = Board::from_str("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    let report = e.warm_up(&[Board::default(), endgame], Duration::from_millis(10)).unwrap();
+    assert_eq!(report.get_searches().len(), 2);
+    assert_eq!(report.get_searches()[1].get_board(), endgame);
+    assert!(report.get_searches().iter().all(|s| s.get_nodes().is_none()));
+    assert_eq!(e.game_history().get_start(), endgame);
+}
+
+#[test]
+fn test_one_search_at_a_time() {
+    let address = fake_engine(false);
+    let mut e = connect_fake(&address);
+    let endgame = Board::from_str("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+
+    // Until the first search has its best move, a second one can't be set up or started.
+    e.send_position(Board::default(), vec![]).unwrap();
+    e.send_go_with(Go::depth(1)).unwrap();
+    assert_eq!(e.state(), ConnectionState::Searching);
+    assert_eq!(
+        e.send_position(endgame, vec![]),
+        Err(Error::InvalidTransition {
+            state: ConnectionState::Searching,
+            command: "position fen 7k/8/8/8/8/8/8/K7 w - - 0 1".to_string(),
+        })
+    );
+    assert_eq!(
+        e.send_go_with(Go::depth(1)),
+        Err(Error::InvalidTransition {
+            state: ConnectionState::Searching,
+            command: "go depth 1".to_string(),
+        })
+    );
+    let best_move = e.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+    assert!(Board::default().legal(best_move.get_move().unwrap()));
+    assert_eq!(e.state(), ConnectionState::Idle);
+    assert_eq!(e.go_generation(), 1);
+    assert_eq!(e.stale_best_moves(), 0);
+
+    // Stopping a search that has already finished does no harm.
+    e.send_stop().unwrap();
+    assert_eq!(e.state(), ConnectionState::Idle);
+    e.send_position(endgame, vec![]).unwrap();
+    e.send_go_with(Go::depth(1)).unwrap();
+    let best_move = e.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+    assert!(endgame.legal(best_move.get_move().unwrap()));
+    assert_eq!(e.history().last_bestmove(), Some(&best_move));
+}
+
+#[test]
+fn test_options_during_search() {
+    let address = fake_engine(false);
+    let mut e = connect_fake(&address);
+    let hash = GuiCommand::SetOption("Hash".to_string(), Some("16".to_string()));
+    let last_sent = |e: &EngineConnection| e.history().by_direction(Direction::Sent).last().cloned();
+
+    e.send_go_with(Go::depth(1)).unwrap();
+    e.send_setoption("Hash", Some("16")).unwrap();
+    assert_eq!(e.get_queued_options(), std::slice::from_ref(&hash));
+    assert_eq!(last_sent(&e), Some(Command::new_from_gui(GuiCommand::Go(Go::depth(1)))));
+    e.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+    assert!(e.get_queued_options().is_empty());
+    // It goes once the best move has been received.
+    let entries = e.history().get_entries();
+    assert_eq!(entries[entries.len() - 1].get_command(), &Command::new_from_gui(hash.clone()));
+    assert_eq!(entries[entries.len() - 1].get_direction(), Direction::Sent);
+
+    e.set_options_during_search(OptionsDuringSearch::Refuse);
+    e.send_go_with(Go::depth(1)).unwrap();
+    assert_eq!(
+        e.send_setoption("Hash", Some("16")),
+        Err(Error::InvalidTransition {
+            state: ConnectionState::Searching,
+            command: "setoption name Hash value 16".to_string(),
+        })
+    );
+    e.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+
+    e.set_options_during_search(OptionsDuringSearch::Send);
+    e.send_go_with(Go::depth(1)).unwrap();
+    e.send_setoption("Hash", Some("16")).unwrap();
+    assert_eq!(last_sent(&e), Some(Command::new_from_gui(hash)));
+    e.recv_best_move_timeout(Duration::from_secs(5)).unwrap();
+}
+
+#[test]
+fn test_recv_search_result() {
+    let address = fake_engine(false);
+    let mut e = connect_fake(&address);
+    e.send_position(Board::default(), vec![]).unwrap();
+    e.send_go_with(Go::depth(1)).unwrap();
+    let outcome = e.recv_search_result(Duration::from_secs(5)).unwrap();
+
+    let best_move = outcome.get_best_move().unwrap();
+    assert!(Board::default().legal(best_move));
+    assert_eq!(outcome.get_all_infos().len(), 1);
+    let final_info = outcome.get_final_info().unwrap();
+    assert_eq!(final_info.get_pv(), &vec![best_move]);
+    assert_eq!(outcome.get_score(), Some(Score::cp(0)));
+    assert_eq!(e.state(), ConnectionState::Idle);
+}
+
 #[test]
 fn test_stockfish_if_exists() {
     let mut timer = Timer::new_with_increment(Duration::new(5, 0), Duration::new(1, 0));
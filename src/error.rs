@@ -1,27 +1,244 @@
+use chess::{Board, ChessMove};
 use nom::error::ErrorKind;
 use nom::Err;
 use std::convert::From;
+use std::error;
 use std::fmt;
-use std::io::Error as IoError;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Clone, Debug, PartialEq)]
+use connection_state::ConnectionState;
+
+/// Which kind of command a parser was trying to read when it failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CommandFamily {
+    Gui,
+    Engine,
+    Id,
+    BestMove,
+    CopyProtection,
+    Registration,
+    Info,
+    Score,
+    EngineOption,
+    OptionType,
+}
+
+impl fmt::Display for CommandFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CommandFamily::Gui => "gui command",
+            CommandFamily::Engine => "engine command",
+            CommandFamily::Id => "id",
+            CommandFamily::BestMove => "bestmove",
+            CommandFamily::CopyProtection => "copyprotection",
+            CommandFamily::Registration => "registration",
+            CommandFamily::Info => "info",
+            CommandFamily::Score => "score",
+            CommandFamily::EngineOption => "option",
+            CommandFamily::OptionType => "option type",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// IO errors are kept behind an `Arc` so that `Error` stays `Clone`.
+#[derive(Clone, Debug)]
 pub enum Error {
-    SpawnError,
+    SpawnError(Arc<IoError>),
     SendError,
     RecvError,
     CommandError,
-    IoError,
+    IoError(Arc<IoError>),
     EngineDeadError,
     NoCommandError,
     Timeout,
-    IncompleteParseError,
-    ParseError { text: String, error: ErrorKind },
+    // Nothing the caller was waiting for arrived before its deadline.
+    RecvTimeout {
+        waited: Duration,
+    },
+    IncompleteParseError {
+        line: String,
+        family: CommandFamily,
+    },
+    ParseError {
+        line: String,
+        family: CommandFamily,
+        text: String,
+        error: ErrorKind,
+    },
+    IllegalMove {
+        index: usize,
+        chess_move: ChessMove,
+        fen: String,
+    },
+    InvalidSan {
+        san: String,
+        fen: String,
+    },
+    // The engine's best move can't be played in the position it was searching.
+    IllegalBestMove {
+        mv: ChessMove,
+        board: Board,
+    },
+    // A configuration file that couldn't be read.
+    ConfigError(String),
+    // The engine went on searching long past its time, and didn't answer `stop`, so it was
+    // killed.  The tail of what was said before then is kept, to see where it got stuck.
+    EngineUnresponsive {
+        waited: Duration,
+        transcript_tail: Vec<String>,
+    },
+    // A command that can't be sent in the connection's state, such as `go` during a search.
+    InvalidTransition {
+        state: ConnectionState,
+        command: String,
+    },
+}
+
+impl Error {
+    /// Wrap a parser failure on `line` with what was being parsed.
+    pub fn parse(family: CommandFamily, line: &str, e: Err<(&str, ErrorKind)>) -> Error {
+        match e {
+            Err::Incomplete(_) => Error::IncompleteParseError {
+                line: line.to_string(),
+                family,
+            },
+            Err::Error((text, error)) | Err::Failure((text, error)) => Error::ParseError {
+                line: line.to_string(),
+                family,
+                text: text.to_string(),
+                error,
+            },
+        }
+    }
+
+    pub fn spawn(e: IoError) -> Error {
+        Error::SpawnError(Arc::new(e))
+    }
+
+    /// The engine didn't answer in time (`NoCommandError` or `RecvTimeout`), or ran out of
+    /// clock (`Timeout`).
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            Error::Timeout | Error::NoCommandError | Error::RecvTimeout { .. }
+        )
+    }
+
+    /// The connection to the engine can't be used any more.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Error::SpawnError(_)
+                | Error::SendError
+                | Error::RecvError
+                | Error::IoError(_)
+                | Error::EngineDeadError
+                | Error::EngineUnresponsive { .. }
+        )
+    }
+
+    /// The kind of the underlying IO error, if there is one.
+    pub fn io_error_kind(&self) -> Option<IoErrorKind> {
+        match self {
+            Error::SpawnError(e) | Error::IoError(e) => Some(e.kind()),
+            _ => None,
+        }
+    }
+}
+
+// IO errors can't be compared, so two are considered equal when they are of the same kind.
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        match (self, other) {
+            (Error::SpawnError(a), Error::SpawnError(b)) | (Error::IoError(a), Error::IoError(b)) => {
+                a.kind() == b.kind()
+            }
+            (
+                Error::IncompleteParseError { line, family },
+                Error::IncompleteParseError {
+                    line: other_line,
+                    family: other_family,
+                },
+            ) => line == other_line && family == other_family,
+            (
+                Error::ParseError {
+                    line,
+                    family,
+                    text,
+                    error,
+                },
+                Error::ParseError {
+                    line: other_line,
+                    family: other_family,
+                    text: other_text,
+                    error: other_error,
+                },
+            ) => line == other_line && family == other_family && text == other_text && error == other_error,
+            (
+                Error::IllegalMove {
+                    index,
+                    chess_move,
+                    fen,
+                },
+                Error::IllegalMove {
+                    index: other_index,
+                    chess_move: other_move,
+                    fen: other_fen,
+                },
+            ) => index == other_index && chess_move == other_move && fen == other_fen,
+            (
+                Error::InvalidSan { san, fen },
+                Error::InvalidSan {
+                    san: other_san,
+                    fen: other_fen,
+                },
+            ) => san == other_san && fen == other_fen,
+            (
+                Error::IllegalBestMove { mv, board },
+                Error::IllegalBestMove {
+                    mv: other_mv,
+                    board: other_board,
+                },
+            ) => mv == other_mv && board == other_board,
+            (Error::RecvTimeout { waited }, Error::RecvTimeout { waited: other_waited }) => {
+                waited == other_waited
+            }
+            (Error::ConfigError(a), Error::ConfigError(b)) => a == b,
+            (
+                Error::EngineUnresponsive {
+                    waited,
+                    transcript_tail,
+                },
+                Error::EngineUnresponsive {
+                    waited: other_waited,
+                    transcript_tail: other_tail,
+                },
+            ) => waited == other_waited && transcript_tail == other_tail,
+            (
+                Error::InvalidTransition { state, command },
+                Error::InvalidTransition {
+                    state: other_state,
+                    command: other_command,
+                },
+            ) => state == other_state && command == other_command,
+            (Error::SendError, Error::SendError)
+            | (Error::RecvError, Error::RecvError)
+            | (Error::CommandError, Error::CommandError)
+            | (Error::EngineDeadError, Error::EngineDeadError)
+            | (Error::NoCommandError, Error::NoCommandError)
+            | (Error::Timeout, Error::Timeout) => true,
+            _ => false,
+        }
+    }
 }
 
 impl From<IoError> for Error {
-    fn from(_: IoError) -> Error {
-        Error::IoError
+    fn from(e: IoError) -> Error {
+        Error::IoError(Arc::new(e))
     }
 }
 
@@ -34,37 +251,119 @@ impl From<TryRecvError> for Error {
     }
 }
 
-impl From<Err<(&str, ErrorKind)>> for Error {
-    fn from(x: Err<(&str, ErrorKind)>) -> Error {
-        match x {
-            Err::Incomplete(_) => Error::IncompleteParseError,
-            Err::Error(y) => Error::ParseError {
-                text: y.0.to_string(),
-                error: y.1.clone(),
-            },
-            Err::Failure(y) => Error::ParseError {
-                text: y.0.to_string(),
-                error: y.1.clone(),
-            },
-        }
-    }
-}
-
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::SpawnError => write!(f, "Spawn Error"),
+            Error::SpawnError(e) => write!(f, "Spawn Error: {}", e),
             Error::SendError => write!(f, "Send Error"),
             Error::RecvError => write!(f, "Recv Error"),
             Error::CommandError => write!(f, "Command Error"),
-            Error::IoError => write!(f, "IO Error"),
+            Error::IoError(e) => write!(f, "IO Error: {}", e),
             Error::NoCommandError => write!(f, "No comand could be read"),
             Error::EngineDeadError => write!(f, "Engine Dead"),
             Error::Timeout => write!(f, "Timeout"),
-            Error::ParseError { text, error } => {
-                write!(f, "Parse Error: {:?} on \"{}\"", error, text)
+            Error::RecvTimeout { waited } => write!(f, "Nothing received within {:?}", waited),
+            Error::ParseError {
+                line,
+                family,
+                text,
+                error,
+            } => write!(
+                f,
+                "Parse Error: {:?} on \"{}\" while parsing {} \"{}\"",
+                error,
+                text,
+                family,
+                line.trim()
+            ),
+            Error::IncompleteParseError { line, family } => write!(
+                f,
+                "Incomplete Data - Parse Error while parsing {} \"{}\"",
+                family,
+                line.trim()
+            ),
+            Error::IllegalMove {
+                index,
+                chess_move,
+                fen,
+            } => write!(
+                f,
+                "Illegal Move: {} (move {}) in position {}",
+                chess_move,
+                index + 1,
+                fen
+            ),
+            Error::InvalidSan { san, fen } => {
+                write!(f, "Invalid SAN: \"{}\" in position {}", san, fen)
+            }
+            Error::IllegalBestMove { mv, board } => {
+                write!(f, "Illegal Best Move: {} in position {}", mv, board)
+            }
+            Error::ConfigError(e) => write!(f, "Config Error: {}", e),
+            Error::EngineUnresponsive {
+                waited,
+                transcript_tail,
+            } => {
+                write!(f, "Engine Unresponsive after {:?}", waited)?;
+                for line in transcript_tail {
+                    write!(f, "\n  {}", line)?;
+                }
+                Ok(())
+            }
+            Error::InvalidTransition { state, command } => {
+                write!(f, "Invalid Transition: \"{}\" while {}", command, state)
             }
-            Error::IncompleteParseError => write!(f, "Incomplete Data - Parse Error"),
         }
     }
 }
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::SpawnError(e) | Error::IoError(e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+use std::error::Error as StdError;
+
+#[test]
+fn test_io_error_source() {
+    let e: Error = IoError::new(IoErrorKind::BrokenPipe, "pipe closed").into();
+
+    assert!(e.is_fatal());
+    assert!(!e.is_timeout());
+    assert_eq!(e.io_error_kind(), Some(IoErrorKind::BrokenPipe));
+    assert_eq!(e.source().unwrap().to_string(), "pipe closed");
+    assert_eq!(e, Error::from(IoError::new(IoErrorKind::BrokenPipe, "other message")));
+}
+
+#[test]
+fn test_parse_error_context() {
+    let e = Error::parse(
+        CommandFamily::Info,
+        "info depth x\n",
+        Err::Error(("x\n", ErrorKind::Digit)),
+    );
+
+    assert!(!e.is_fatal());
+    assert!(e.source().is_none());
+    assert_eq!(
+        e.to_string(),
+        "Parse Error: Digit on \"x\n\" while parsing info \"info depth x\""
+    );
+}
+
+#[test]
+fn test_timeouts() {
+    assert!(Error::Timeout.is_timeout());
+    assert!(Error::NoCommandError.is_timeout());
+    assert!(Error::RecvTimeout {
+        waited: Duration::from_millis(5)
+    }
+    .is_timeout());
+    assert!(!Error::EngineDeadError.is_timeout());
+    assert!(Error::EngineDeadError.is_fatal());
+}
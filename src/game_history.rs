@@ -0,0 +1,176 @@
+use chess::{Board, ChessMove, MoveGen, Piece};
+
+use engine_base::zobrist;
+use error::Error;
+
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use std::str::FromStr;
+
+/// The moves of a game so far, and what they take to tell the draws a single `Board` can't:
+/// threefold repetition and the fifty move rule.
+///
+/// `Board` doesn't keep the halfmove clock from a FEN, so the clock starts from the first
+/// position at zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameHistory {
+    start: Board,
+    board: Board,
+    moves: Vec<ChessMove>,
+    // The Zobrist key of every position since the last capture or pawn move, the current one
+    // last.
+    hashes: Vec<u64>,
+}
+
+impl GameHistory {
+    pub fn new(board: Board) -> GameHistory {
+        GameHistory {
+            start: board,
+            board,
+            moves: vec![],
+            hashes: vec![zobrist::hash(&board)],
+        }
+    }
+
+    /// The game after `moves`, played from `board`.
+    pub fn from_moves(board: Board, moves: &[ChessMove]) -> Result<GameHistory, Error> {
+        let mut history = GameHistory::new(board);
+        for (index, m) in moves.iter().enumerate() {
+            history.push(*m).map_err(|e| match e {
+                Error::IllegalMove { chess_move, fen, .. } => Error::IllegalMove {
+                    index,
+                    chess_move,
+                    fen,
+                },
+                e => e,
+            })?;
+        }
+        Ok(history)
+    }
+
+    /// Play `m`, if it is legal.
+    pub fn push(&mut self, m: ChessMove) -> Result<(), Error> {
+        if !self.board.legal(m) {
+            return Err(Error::IllegalMove {
+                index: self.moves.len(),
+                chess_move: m,
+                fen: self.board.to_string(),
+            });
+        }
+
+        let irreversible = self.board.piece_on(m.get_source()) == Some(Piece::Pawn)
+            || self.board.piece_on(m.get_dest()).is_some();
+        let before = self.board;
+        self.board = before.make_move_new(m);
+        self.moves.push(m);
+        let hash = zobrist::update(self.current_hash(), &before, m, &self.board);
+        if irreversible {
+            self.hashes.clear();
+        }
+        self.hashes.push(hash);
+        Ok(())
+    }
+
+    pub fn get_start(&self) -> Board {
+        self.start
+    }
+
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    pub fn get_moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    /// Plies since the last capture or pawn move.
+    pub fn get_halfmove_clock(&self) -> usize {
+        self.hashes.len() - 1
+    }
+
+    /// The `zobrist` key of the current position.
+    pub fn current_hash(&self) -> u64 {
+        self.hashes[self.hashes.len() - 1]
+    }
+
+    /// How many times the current position has occurred, counting this time.
+    pub fn repetitions(&self) -> usize {
+        let current = self.current_hash();
+        self.hashes.iter().filter(|&&h| h == current).count()
+    }
+
+    pub fn is_threefold(&self) -> bool {
+        self.repetitions() >= 3
+    }
+
+    /// Have fifty moves each passed without a capture or pawn move?  A checkmate on the last
+    /// of them still wins; check `game_result` first.
+    pub fn is_fifty_move(&self) -> bool {
+        self.get_halfmove_clock() >= 100
+    }
+
+    pub fn legal_moves(&self) -> Vec<ChessMove> {
+        MoveGen::new_legal(&self.board).collect()
+    }
+}
+
+#[test]
+fn test_threefold_repetition() {
+    let shuffle = [
+        ChessMove::new(Square::G1, Square::F3, None),
+        ChessMove::new(Square::G8, Square::F6, None),
+        ChessMove::new(Square::F3, Square::G1, None),
+        ChessMove::new(Square::F6, Square::G8, None),
+    ];
+    let mut history = GameHistory::new(Board::default());
+    for m in shuffle.iter().chain(shuffle.iter()) {
+        assert!(!history.is_threefold());
+        history.push(*m).unwrap();
+    }
+
+    assert_eq!(history.repetitions(), 3);
+    assert!(history.is_threefold());
+    assert_eq!(history.get_board(), Board::default());
+    assert_eq!(history.get_halfmove_clock(), 8);
+    assert_eq!(history.legal_moves().len(), 20);
+
+    // A pawn move makes the earlier positions unreachable.
+    history.push(ChessMove::new(Square::E2, Square::E4, None)).unwrap();
+    assert_eq!(history.get_halfmove_clock(), 0);
+    assert_eq!(history.repetitions(), 1);
+}
+
+#[test]
+fn test_fifty_move_rule() {
+    let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    let rook = [Square::A1, Square::A2, Square::A3, Square::A4, Square::A5, Square::A6];
+    let king = [Square::E8, Square::D8, Square::C8, Square::D8];
+    let mut history = GameHistory::new(board);
+    for ply in 0..100 {
+        assert!(!history.is_fifty_move());
+        let (from, to) = if ply % 2 == 0 {
+            (rook[(ply / 2) % 6], rook[(ply / 2 + 1) % 6])
+        } else {
+            (king[(ply / 2) % 4], king[(ply / 2 + 1) % 4])
+        };
+        history.push(ChessMove::new(from, to, None)).unwrap();
+    }
+    assert!(history.is_fifty_move());
+}
+
+#[test]
+fn test_game_history_illegal_move() {
+    let e2e5 = ChessMove::new(Square::E2, Square::E5, None);
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    match GameHistory::from_moves(Board::default(), &[e2e4, e2e5]) {
+        Err(Error::IllegalMove { index, chess_move, .. }) => {
+            assert_eq!(index, 1);
+            assert_eq!(chess_move, e2e5);
+        }
+        other => panic!("expected an illegal move, got {:?}", other),
+    }
+    let history = GameHistory::from_moves(Board::default(), &[e2e4]).unwrap();
+    assert_eq!(history.get_moves(), &[e2e4]);
+    assert_eq!(history.get_start(), Board::default());
+}
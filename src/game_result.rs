@@ -0,0 +1,113 @@
+use chess::{BitBoard, Board, BoardStatus, Color, Piece};
+use std::fmt;
+
+#[cfg(test)]
+use std::str::FromStr;
+
+/// How a finished game ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    Win(Color),
+    Draw,
+}
+
+impl GameResult {
+    /// Points for `color`: 1 for a win, half for a draw.
+    pub fn score_for(&self, color: Color) -> f32 {
+        match self {
+            GameResult::Win(winner) if *winner == color => 1.0,
+            GameResult::Win(_) => 0.0,
+            GameResult::Draw => 0.5,
+        }
+    }
+}
+
+/// Is the game over on `board`?  Only checkmate and stalemate are detected; draws by repetition
+/// or the fifty move rule need the game's history (see `GameHistory`), and draws by insufficient
+/// material are left to `is_insufficient_material`.
+pub fn game_result(board: &Board) -> Option<GameResult> {
+    match board.status() {
+        BoardStatus::Checkmate => Some(GameResult::Win(!board.side_to_move())),
+        BoardStatus::Stalemate => Some(GameResult::Draw),
+        BoardStatus::Ongoing => None,
+    }
+}
+
+// The light squares, a1 being dark.
+const LIGHT_SQUARES: BitBoard = BitBoard(0x55aa_55aa_55aa_55aa);
+
+/// Can neither side ever mate, whatever is played?  True for king against king, king and bishop
+/// or king and knight against king, and kings and bishops where every bishop is on the same color
+/// of square.  Whatever the evaluation says, these positions are draws.
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let pawns_and_rooks = *board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook);
+    if (pawns_and_rooks | *board.pieces(Piece::Queen)).popcnt() > 0 {
+        return false;
+    }
+    let knights = *board.pieces(Piece::Knight);
+    let bishops = *board.pieces(Piece::Bishop);
+    match (knights.popcnt(), bishops.popcnt()) {
+        (0, 0) | (1, 0) | (0, 1) => true,
+        (0, _) => {
+            (bishops & LIGHT_SQUARES).popcnt() == 0 || (bishops & !LIGHT_SQUARES).popcnt() == 0
+        }
+        _ => false,
+    }
+}
+
+// As written at the end of a PGN game.
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::Win(Color::White) => write!(f, "1-0"),
+            GameResult::Win(Color::Black) => write!(f, "0-1"),
+            GameResult::Draw => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
+#[test]
+fn test_game_result_checkmate() {
+    // Fool's mate: White is mated.
+    let board = Board::from_str("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+    let result = game_result(&board).unwrap();
+
+    assert_eq!(result, GameResult::Win(Color::Black));
+    assert_eq!(result.score_for(Color::White), 0.0);
+    assert_eq!(result.to_string(), "0-1");
+}
+
+#[test]
+fn test_game_result_stalemate() {
+    let board = Board::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    let result = game_result(&board).unwrap();
+
+    assert_eq!(result, GameResult::Draw);
+    assert_eq!(result.score_for(Color::Black), 0.5);
+    assert_eq!(result.to_string(), "1/2-1/2");
+    assert_eq!(game_result(&Board::default()), None);
+}
+
+#[test]
+fn test_insufficient_material() {
+    let insufficient = [
+        "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+        "8/8/4k3/8/8/3K4/8/5B2 w - - 0 1",
+        "8/8/4k3/8/8/3K4/8/6n1 b - - 0 1",
+        // Both bishops on light squares.
+        "8/8/4k3/8/8/3K4/8/5B1b w - - 0 1",
+    ];
+    let sufficient = [
+        Board::default().to_string(),
+        "8/8/4k3/8/8/3K4/7P/8 w - - 0 1".to_string(),
+        "8/8/4k3/8/8/3K4/8/4Bb2 w - - 0 1".to_string(),
+        "8/8/4k3/8/8/3K4/8/5BN1 w - - 0 1".to_string(),
+        "8/8/4k3/8/8/3K4/8/5N1n w - - 0 1".to_string(),
+    ];
+    for fen in insufficient.iter() {
+        assert!(is_insufficient_material(&Board::from_str(fen).unwrap()), "{}", fen);
+    }
+    for fen in sufficient.iter() {
+        assert!(!is_insufficient_material(&Board::from_str(fen).unwrap()), "{}", fen);
+    }
+}
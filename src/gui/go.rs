@@ -4,7 +4,7 @@ use parsers::*;
 use nom::IResult;
 use nom::combinator::{map, complete, value};
 use nom::bytes::streaming::tag;
-use nom::multi::fold_many1;
+use nom::multi::fold_many0;
 use nom::branch::alt;
 use nom::sequence::tuple;
 
@@ -166,7 +166,7 @@ fn parse_go_winc(input: &str) -> IResult<&str, Go> {
     map(
         tuple((
             space,
-            tag("btime"),
+            tag("winc"),
             space,
             integer
         )),
@@ -284,7 +284,7 @@ pub fn parse_go(input: &str) -> IResult<&str, Go> {
     map(
         tuple((
             tag("go"),
-            fold_many1(
+            fold_many0(
                 alt((
                     complete(parse_go_wtime),
                     complete(parse_go_btime),
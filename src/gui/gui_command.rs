@@ -1,13 +1,19 @@
 use chess::{Board, ChessMove};
-use error::Error;
+use error::{CommandFamily, Error};
 use nom::combinator::rest;
 use std::fmt;
 use std::str::FromStr;
 
 #[cfg(test)]
 use chess::{File, Piece, Rank, Square};
+#[cfg(test)]
+use gui::position::PositionStart;
+#[cfg(test)]
+use test_transcripts::gui_transcript;
 
 use gui::go::{parse_go, Go};
+use gui::position::{MoveCounters, PositionSpec};
+use gui::register::{parse_register, Register};
 use parsers::*;
 
 use nom::IResult;
@@ -23,13 +29,16 @@ pub enum GuiCommand {
     Debug(bool),
     IsReady,
     SetOption(String, Option<String>),
-    Register(String),
+    Register(Register),
     UciNewGame,
-    Position(Board, Vec<ChessMove>),
+    Position(PositionSpec),
     Go(Go),
     Stop,
     PonderHit,
     Quit,
+    /// `d`, or `board`: not UCI, but many engines answer it by showing the position, for
+    /// debugging by hand.
+    Board,
 }
 
 fn parse_uci(input: &str) -> IResult<&str, GuiCommand> {
@@ -82,15 +91,8 @@ fn parse_setoption_novalue(input: &str) -> IResult<&str, GuiCommand> {
     )(input)
 }
 
-fn parse_register(input: &str) -> IResult<&str, GuiCommand> {
-    map(
-        tuple((
-            tag("register"),
-            space,
-            rest,
-        )),
-        |(_, _, token)| GuiCommand::Register(token.to_string())
-    )(input)
+fn parse_gui_register(input: &str) -> IResult<&str, GuiCommand> {
+    map(parse_register, GuiCommand::Register)(input)
 }
 
 fn parse_ucinewgame(input: &str) -> IResult<&str, GuiCommand> {
@@ -109,25 +111,30 @@ fn parse_quit(input: &str) -> IResult<&str, GuiCommand> {
     value(GuiCommand::Quit, tag("quit"))(input)
 }
 
+fn parse_board(input: &str) -> IResult<&str, GuiCommand> {
+    map(
+        tuple((alt((tag("board"), tag("d"))), end_of_line)),
+        |_| GuiCommand::Board
+    )(input)
+}
+
 fn parse_gui_go(input: &str) -> IResult<&str, GuiCommand> {
     map(parse_go,
         |go| GuiCommand::Go(go)
     )(input)
 }
 
-fn parse_position_fen(input: &str) -> IResult<&str, Board> {
-    map(
-        tuple((
-            tag("fen"),
-            space,
-            parse_fen
-        )),
-        |(_, _, board)| board
-    )(input)
+// The FEN is kept as it was written, as well as the board read from it.
+fn parse_position_fen(input: &str) -> IResult<&str, Option<(&str, Board, MoveCounters)>> {
+    let (fen, _) = tuple((tag("fen"), space))(input)?;
+    let (rest, (board, halfmove_clock, fullmove_number)) = parse_fen(fen)?;
+    let counters = MoveCounters::new(halfmove_clock.unwrap_or(0), fullmove_number.unwrap_or(1));
+    let fen = fen[..fen.len() - rest.len()].trim_end();
+    Ok((rest, Some((fen, board, counters))))
 }
 
-fn parse_position_startpos(input: &str) -> IResult<&str, Board> {
-    value(Board::default(), tag("startpos"))(input)
+fn parse_position_startpos(input: &str) -> IResult<&str, Option<(&str, Board, MoveCounters)>> {
+    value(None, tag("startpos"))(input)
 }
 
 fn parse_position_moves(input: &str) -> IResult<&str, Vec<ChessMove>> {
@@ -143,13 +150,7 @@ fn parse_position_moves(input: &str) -> IResult<&str, Vec<ChessMove>> {
 }
 
 fn parse_position_moves_empty(input: &str) -> IResult<&str, Vec<ChessMove>> {
-    value(
-        Vec::new(),
-        tuple((
-            non_newline_space,
-            tag("\n"),
-        ))
-    )(input)
+    value(Vec::new(), end_of_line)(input)
 }
 
 fn parse_position(input: &str) -> IResult<&str, GuiCommand> {
@@ -166,7 +167,12 @@ fn parse_position(input: &str) -> IResult<&str, GuiCommand> {
                 complete(parse_position_moves_empty),
             ))
         )),
-        |(_, _, board, moves)| GuiCommand::Position(board, moves)
+        |(_, _, start, moves)| match start {
+            Some((fen, board, counters)) => {
+                GuiCommand::Position(PositionSpec::from_parts(fen, board, counters, moves))
+            }
+            None => GuiCommand::Position(PositionSpec::startpos(moves)),
+        }
     )(input)
 }
 
@@ -179,19 +185,56 @@ fn parse_all(input: &str) -> IResult<&str, GuiCommand> {
         complete(parse_isready),
         complete(parse_setoption_value),
         complete(parse_setoption_novalue),
-        complete(parse_register),
+        complete(parse_gui_register),
         complete(parse_stop),
         complete(parse_ponderhit),
         complete(parse_gui_go),
-        complete(parse_position)
+        complete(parse_position),
+        complete(parse_board)
     ))(input)
 }
 
+/// Play `moves` from `board`, stopping at the first one that isn't legal.
+pub fn apply_moves(board: Board, moves: &[ChessMove]) -> Result<Board, Error> {
+    let mut result = board;
+    for (index, m) in moves.iter().enumerate() {
+        if !result.legal(*m) {
+            return Err(Error::IllegalMove {
+                index,
+                chess_move: *m,
+                fen: result.to_string(),
+            });
+        }
+        result = result.make_move_new(*m);
+    }
+    Ok(result)
+}
+
+impl GuiCommand {
+    /// A `position` command from `board`, as `PositionSpec::from_board` writes it.
+    pub fn position(board: Board, moves: Vec<ChessMove>) -> GuiCommand {
+        GuiCommand::Position(PositionSpec::from_board(board, moves))
+    }
+
+    /// Parse a command, checking that every move of a `position` command is legal.  For a
+    /// `position`, the board after all of the moves is returned as well.
+    pub fn parse_validated(s: &str) -> Result<(GuiCommand, Option<Board>), Error> {
+        let command = GuiCommand::from_str(s)?;
+        let board = match command {
+            GuiCommand::Position(ref position) => Some(position.final_board()?),
+            _ => None,
+        };
+        Ok((command, board))
+    }
+}
+
 impl FromStr for GuiCommand {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(parse_all(s)?.1)
+        parse_all(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::Gui, s, e))
     }
 }
 
@@ -205,33 +248,13 @@ impl fmt::Display for GuiCommand {
                 None => writeln!(f, "setoption name {}", name),
                 Some(v) => writeln!(f, "setoption name {} value {}", name, v),
             },
-            GuiCommand::Register(code) => writeln!(f, "register {}", code),
+            GuiCommand::Register(register) => writeln!(f, "{}", register),
             GuiCommand::UciNewGame => writeln!(f, "ucinewgame"),
-            GuiCommand::Position(pos, moves) => {
-                if pos == &Board::default() {
-                    write!(f, "position startpos")?;
-                } else {
-                    write!(f, "position fen {}", pos)?;
-                }
-
-                if moves.len() != 0 {
-                    writeln!(
-                        f,
-                        "{}",
-                        moves
-                            .iter()
-                            .map(|x| x.to_string())
-                            .collect::<Vec<String>>()
-                            .join(" ")
-                    )
-                } else {
-                    writeln!(f, "")
-                }
-            }
+            GuiCommand::Position(position) => writeln!(f, "{}", position),
             GuiCommand::Go(go) => {
                 write!(f, "go")?;
                 match go.get_ponder() {
-                    Some(ref p) => write!(f, "ponder {}", p)?,
+                    Some(ref p) => write!(f, " ponder {}", p)?,
                     None => {}
                 };
 
@@ -282,6 +305,7 @@ impl fmt::Display for GuiCommand {
             GuiCommand::Stop => writeln!(f, "stop"),
             GuiCommand::PonderHit => writeln!(f, "ponderhit"),
             GuiCommand::Quit => writeln!(f, "quit"),
+            GuiCommand::Board => writeln!(f, "d"),
         }
     }
 }
@@ -307,6 +331,14 @@ fn test_parse_debug_off() {
     test_parse("debug off", GuiCommand::Debug(false));
 }
 
+#[test]
+fn test_parse_board() {
+    test_parse("d", GuiCommand::Board);
+    test_parse("board\n", GuiCommand::Board);
+    assert!(GuiCommand::from_str("dump").is_err());
+    assert_eq!(GuiCommand::Board.to_string(), "d\n");
+}
+
 #[test]
 fn test_parse_setoption_noval() {
     test_parse(
@@ -330,7 +362,15 @@ fn test_isready() {
 
 #[test]
 fn test_registration() {
-    test_parse("register code", GuiCommand::Register("code".to_string()));
+    test_parse("register later", GuiCommand::Register(Register::Later));
+    test_parse(
+        "register name Jordan Bray code 1234\n",
+        GuiCommand::Register(Register::NameCode {
+            name: "Jordan Bray".to_string(),
+            code: "1234".to_string(),
+        }),
+    );
+    assert!(GuiCommand::from_str("register code").is_err());
 }
 
 #[test]
@@ -361,11 +401,37 @@ fn test_parse_go_times() {
     );
 }
 
+#[test]
+fn test_parse_go_increments() {
+    test_parse(
+        "go wtime 100 winc 10 btime 200 binc 20\n",
+        GuiCommand::Go(
+            Go::wtime(100)
+                .combine(&Go::winc(10))
+                .combine(&Go::btime(200))
+                .combine(&Go::binc(20)),
+        ),
+    );
+}
+
+#[test]
+fn test_display_go_ponder() {
+    let e2e4 = ChessMove::new(
+        Square::make_square(Rank::Second, File::E),
+        Square::make_square(Rank::Fourth, File::E),
+        None,
+    );
+
+    let go = GuiCommand::Go(Go::ponder(e2e4).combine(&Go::wtime(100)));
+    assert_eq!(go.to_string(), "go ponder e2e4 wtime 100\n");
+    test_parse(&go.to_string(), go);
+}
+
 #[test]
 fn test_parse_startpos() {
     test_parse(
         "position startpos\n",
-        GuiCommand::Position(Board::default(), vec![]),
+        GuiCommand::position(Board::default(), vec![]),
     );
 }
 #[test]
@@ -383,7 +449,7 @@ fn test_parse_startpos_moves() {
 
     test_parse(
         "position startpos moves e2e4 e7e5\n",
-        GuiCommand::Position(Board::default(), vec![e2e4, e7e5]),
+        GuiCommand::position(Board::default(), vec![e2e4, e7e5]),
     );
 }
 
@@ -391,7 +457,7 @@ fn test_parse_startpos_moves() {
 fn test_position_fen() {
     test_parse(
         "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1\n",
-        GuiCommand::Position(Board::default(), vec![]),
+        GuiCommand::position(Board::default(), vec![]),
     );
 }
 
@@ -410,7 +476,7 @@ fn test_parse_position_fen_moves() {
 
     test_parse(
         "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e2e4 e7e5\n",
-        GuiCommand::Position(Board::default(), vec![e2e4, e7e5]),
+        GuiCommand::position(Board::default(), vec![e2e4, e7e5]),
     );
 }
 
@@ -424,6 +490,133 @@ fn test_parse_queening_move() {
 
     test_parse(
         "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e7e8q\n",
-        GuiCommand::Position(Board::default(), vec![queening]),
+        GuiCommand::position(Board::default(), vec![queening]),
+    );
+}
+
+#[test]
+fn test_display_position_startpos_moves() {
+    let e2e4 = ChessMove::new(
+        Square::make_square(Rank::Second, File::E),
+        Square::make_square(Rank::Fourth, File::E),
+        None,
+    );
+    let e7e5 = ChessMove::new(
+        Square::make_square(Rank::Seventh, File::E),
+        Square::make_square(Rank::Fifth, File::E),
+        None,
+    );
+
+    let position = GuiCommand::position(Board::default(), vec![e2e4, e7e5]);
+    assert_eq!(position.to_string(), "position startpos moves e2e4 e7e5\n");
+    test_parse(&position.to_string(), position);
+
+    let position = GuiCommand::position(Board::default(), vec![]);
+    assert_eq!(position.to_string(), "position startpos\n");
+    test_parse(&position.to_string(), position);
+}
+
+#[test]
+fn test_display_position_fen_moves() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1";
+    let f1c4 = ChessMove::new(
+        Square::make_square(Rank::First, File::F),
+        Square::make_square(Rank::Fourth, File::C),
+        None,
+    );
+
+    let position = GuiCommand::position(Board::from_str(fen).unwrap(), vec![f1c4]);
+    assert_eq!(
+        position.to_string(),
+        format!("position fen {} moves f1c4\n", fen)
+    );
+    test_parse(&position.to_string(), position);
+}
+
+#[test]
+fn test_parse_position_fen_counters() {
+    let fen = "8/8/4k3/8/8/4K3/4P3/8 w - - 17 42";
+    let e2e4 = ChessMove::new(
+        Square::make_square(Rank::Second, File::E),
+        Square::make_square(Rank::Fourth, File::E),
+        None,
+    );
+
+    let line = format!("position fen {} moves e2e4\n", fen);
+    let position = GuiCommand::Position(PositionSpec::from_fen(fen, vec![e2e4]).unwrap());
+    test_parse(&line, position.clone());
+    assert_eq!(position.to_string(), line);
+
+    // The FEN is kept as it was written; counters left out are those of a new game.
+    let short = "position fen 8/8/4k3/8/8/4K3/4P3/8 w - -\n";
+    match GuiCommand::from_str(short) {
+        Ok(GuiCommand::Position(position)) => {
+            assert_eq!(position.get_start(), &PositionStart::Fen("8/8/4k3/8/8/4K3/4P3/8 w - -".to_string()));
+            assert_eq!(position.get_start_board(), Board::from_str(fen).unwrap());
+            assert_eq!(position.get_counters(), MoveCounters::default());
+            assert_eq!(position.to_string() + "\n", short);
+        }
+        parsed => panic!("unexpected {:?}", parsed),
+    }
+}
+
+#[test]
+fn test_parse_without_newline() {
+    test_parse(
+        "position startpos",
+        GuiCommand::position(Board::default(), vec![]),
+    );
+    test_parse(
+        "position fen 8/8/4k3/8/8/4K3/4P3/8 w - - 17 42",
+        GuiCommand::Position(PositionSpec::from_fen("8/8/4k3/8/8/4K3/4P3/8 w - - 17 42", vec![]).unwrap()),
+    );
+    test_parse("register later", GuiCommand::Register(Register::Later));
+}
+
+#[test]
+fn test_gui_transcript() {
+    for line in gui_transcript().lines() {
+        let expected = GuiCommand::from_str(&(line.to_string() + "\n"));
+        assert!(expected.is_ok(), "could not parse {:?}", line);
+
+        assert_eq!(GuiCommand::from_str(line), expected, "{:?}", line);
+        assert_eq!(
+            GuiCommand::from_str(&(line.to_string() + "\r\n")),
+            expected,
+            "{:?}",
+            line
+        );
+        assert_eq!(
+            GuiCommand::from_str(&(line.to_string() + "  ")),
+            expected,
+            "{:?}",
+            line
+        );
+    }
+}
+
+#[test]
+fn test_parse_validated_position() {
+    let (command, board) = GuiCommand::parse_validated("position startpos moves e2e4 e7e5 g1f3\n").unwrap();
+
+    assert!(matches!(command, GuiCommand::Position(ref position) if position.get_moves().len() == 3));
+    assert_eq!(
+        board,
+        Some(Board::from_str("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 1").unwrap())
+    );
+    assert_eq!(GuiCommand::parse_validated("isready\n"), Ok((GuiCommand::IsReady, None)));
+}
+
+#[test]
+fn test_parse_validated_illegal_move() {
+    let after_e4 = Board::default().make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+
+    assert_eq!(
+        GuiCommand::parse_validated("position startpos moves e2e4 e2e4\n"),
+        Err(Error::IllegalMove {
+            index: 1,
+            chess_move: ChessMove::new(Square::E2, Square::E4, None),
+            fen: after_e4.to_string(),
+        })
     );
 }
@@ -1,2 +1,4 @@
 pub mod go;
 pub mod gui_command;
+pub mod position;
+pub mod register;
@@ -0,0 +1,249 @@
+use chess::{Board, ChessMove, Color, Square};
+use nom::error::ErrorKind;
+use std::fmt;
+
+use engine_base::zobrist;
+use error::{CommandFamily, Error};
+use gui::gui_command::apply_moves;
+use parsers::parse_fen;
+
+#[cfg(test)]
+use std::str::FromStr;
+
+/// Where a `position` command starts from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PositionStart {
+    StartPos,
+    /// The FEN exactly as it was given.
+    Fen(String),
+}
+
+/// The halfmove clock and fullmove number of a FEN, which `Board` doesn't keep.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MoveCounters {
+    halfmove_clock: u64,
+    fullmove_number: u64,
+}
+
+impl MoveCounters {
+    pub fn new(halfmove_clock: u64, fullmove_number: u64) -> MoveCounters {
+        MoveCounters {
+            halfmove_clock,
+            fullmove_number,
+        }
+    }
+
+    pub fn get_halfmove_clock(&self) -> u64 {
+        self.halfmove_clock
+    }
+
+    pub fn get_fullmove_number(&self) -> u64 {
+        self.fullmove_number
+    }
+
+    /// The counters after `m` is played on `board`.
+    pub fn after(&self, board: &Board, m: ChessMove) -> MoveCounters {
+        let resets = board.piece_on(m.get_source()) == Some(chess::Piece::Pawn)
+            || board.piece_on(m.get_dest()).is_some();
+        MoveCounters {
+            halfmove_clock: if resets { 0 } else { self.halfmove_clock + 1 },
+            fullmove_number: match board.side_to_move() {
+                Color::White => self.fullmove_number,
+                Color::Black => self.fullmove_number + 1,
+            },
+        }
+    }
+}
+
+// The counters of a new game, which are also what a FEN without them is taken to have.
+impl Default for MoveCounters {
+    fn default() -> MoveCounters {
+        MoveCounters::new(0, 1)
+    }
+}
+
+impl fmt::Display for MoveCounters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.halfmove_clock, self.fullmove_number)
+    }
+}
+
+/// The FEN of `board` with `counters`.  The en passant square is the one behind the pawn, which
+/// `Board`'s own FEN gets wrong.
+pub fn to_fen(board: &Board, counters: MoveCounters) -> String {
+    let fen = board.to_string();
+    let fields = fen.split_whitespace().take(3).collect::<Vec<&str>>();
+    let en_passant = match en_passant_target(board) {
+        Some(square) => square.to_string(),
+        None => "-".to_string(),
+    };
+    format!("{} {} {}", fields.join(" "), en_passant, counters)
+}
+
+// The board keeps the pawn that can be taken en passant, rather than the square behind it.
+pub(crate) fn en_passant_target(board: &Board) -> Option<Square> {
+    board.en_passant().map(|pawn| match board.side_to_move() {
+        Color::White => pawn.uup(),
+        Color::Black => pawn.udown(),
+    })
+}
+
+/// What a `position` command says: where the game started, and the moves played since.
+///
+/// The start is kept as it was given, so the command can be passed on unchanged, along with the
+/// board and counters read from it.
+#[derive(Clone, Debug)]
+pub struct PositionSpec {
+    start: PositionStart,
+    board: Board,
+    counters: MoveCounters,
+    moves: Vec<ChessMove>,
+}
+
+impl PositionSpec {
+    pub fn startpos(moves: Vec<ChessMove>) -> PositionSpec {
+        PositionSpec {
+            start: PositionStart::StartPos,
+            board: Board::default(),
+            counters: MoveCounters::default(),
+            moves,
+        }
+    }
+
+    /// Start from `board`, which is written as `startpos` if it is the starting position, and
+    /// otherwise as a FEN with the counters of a new game.
+    pub fn from_board(board: Board, moves: Vec<ChessMove>) -> PositionSpec {
+        if board == Board::default() {
+            return PositionSpec::startpos(moves);
+        }
+        PositionSpec {
+            start: PositionStart::Fen(board.to_string()),
+            board,
+            counters: MoveCounters::default(),
+            moves,
+        }
+    }
+
+    /// Start from `fen`, failing with a `ParseError` if it isn't one.  Counters left out of the
+    /// FEN are taken to be those of a new game.
+    pub fn from_fen(fen: &str, moves: Vec<ChessMove>) -> Result<PositionSpec, Error> {
+        let error = |e| Error::parse(CommandFamily::Gui, fen, e);
+        let (rest, (board, halfmove_clock, fullmove_number)) = parse_fen(fen).map_err(error)?;
+        if !rest.trim().is_empty() {
+            return Err(error(nom::Err::Error((rest, ErrorKind::Eof))));
+        }
+        let counters = MoveCounters::new(halfmove_clock.unwrap_or(0), fullmove_number.unwrap_or(1));
+        Ok(PositionSpec::from_parts(fen.trim(), board, counters, moves))
+    }
+
+    // For the parser, which has already read the FEN.
+    pub(crate) fn from_parts(
+        fen: &str,
+        board: Board,
+        counters: MoveCounters,
+        moves: Vec<ChessMove>,
+    ) -> PositionSpec {
+        PositionSpec {
+            start: PositionStart::Fen(fen.to_string()),
+            board,
+            counters,
+            moves,
+        }
+    }
+
+    pub fn get_start(&self) -> &PositionStart {
+        &self.start
+    }
+
+    /// The board before any of the moves.
+    pub fn get_start_board(&self) -> Board {
+        self.board
+    }
+
+    /// The counters of the start, before any of the moves.
+    pub fn get_counters(&self) -> MoveCounters {
+        self.counters
+    }
+
+    pub fn get_moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    /// The board after every move, failing with `IllegalMove` if one of them can't be played.
+    pub fn final_board(&self) -> Result<Board, Error> {
+        apply_moves(self.board, &self.moves)
+    }
+
+    /// The `zobrist` key of the start, and of the board after each move, for engines that look
+    /// for repetitions.  Fails like `final_board`.
+    pub fn zobrist_history(&self) -> Result<Vec<u64>, Error> {
+        let mut hashes = Vec::with_capacity(self.moves.len() + 1);
+        hashes.push(zobrist::hash(&self.board));
+        let mut board = self.board;
+        for (index, &m) in self.moves.iter().enumerate() {
+            if !board.legal(m) {
+                return Err(Error::IllegalMove {
+                    index,
+                    chess_move: m,
+                    fen: board.to_string(),
+                });
+            }
+            let after = board.make_move_new(m);
+            hashes.push(zobrist::update(hashes[index], &board, m, &after));
+            board = after;
+        }
+        Ok(hashes)
+    }
+}
+
+// Two positions are the same if they start from the same board and counters, however the start
+// was written, and play the same moves.
+impl PartialEq for PositionSpec {
+    fn eq(&self, other: &PositionSpec) -> bool {
+        self.board == other.board && self.counters == other.counters && self.moves == other.moves
+    }
+}
+
+// The command as it is sent, without its newline.
+impl fmt::Display for PositionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.start {
+            PositionStart::StartPos => write!(f, "position startpos")?,
+            PositionStart::Fen(ref fen) => write!(f, "position fen {}", fen)?,
+        }
+        if !self.moves.is_empty() {
+            write!(f, " moves")?;
+            for m in &self.moves {
+                write!(f, " {}", m)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_position_spec() {
+    let fen = "8/8/4k3/8/8/3K4/4P3/8 w - - 17 42";
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let e6e5 = ChessMove::new(Square::E6, Square::E5, None);
+    let e3e2 = ChessMove::new(Square::E3, Square::E2, None);
+
+    let position = PositionSpec::from_fen(fen, vec![e2e4, e6e5]).unwrap();
+    assert_eq!(position.get_start(), &PositionStart::Fen(fen.to_string()));
+    assert_eq!(position.get_counters(), MoveCounters::new(17, 42));
+    assert_eq!(position.to_string(), format!("position fen {} moves e2e4 e6e5", fen));
+
+    let after = Board::from_str(fen).unwrap().make_move_new(e2e4).make_move_new(e6e5);
+    assert_eq!(position.final_board(), Ok(after));
+    let hashes = position.zobrist_history().unwrap();
+    assert_eq!(hashes.len(), 3);
+    assert_eq!(hashes[0], zobrist::hash(&position.get_start_board()));
+    assert_eq!(hashes[2], zobrist::hash(&after));
+
+    let illegal = PositionSpec::from_fen(fen, vec![e2e4, e3e2]).unwrap();
+    assert!(matches!(illegal.final_board(), Err(Error::IllegalMove { index: 1, .. })));
+    assert!(matches!(illegal.zobrist_history(), Err(Error::IllegalMove { index: 1, .. })));
+
+    assert!(PositionSpec::from_fen("8/8/8 w", vec![]).is_err());
+    assert_eq!(PositionSpec::from_board(Board::default(), vec![]), PositionSpec::startpos(vec![]));
+}
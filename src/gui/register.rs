@@ -0,0 +1,97 @@
+use error::{CommandFamily, Error};
+use std::fmt;
+use std::str::FromStr;
+
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::streaming::tag;
+use nom::combinator::{complete, map, value};
+use nom::error::ErrorKind;
+use nom::sequence::tuple;
+
+use parsers::*;
+
+/// The two forms of the `register` command.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Register {
+    /// Register some other time.
+    Later,
+    /// The name may have spaces in it.
+    NameCode { name: String, code: String },
+}
+
+fn parse_register_later(input: &str) -> IResult<&str, Register> {
+    value(Register::Later, tuple((tag("later"), end_of_line)))(input)
+}
+
+// Split on the last ` code `, as the name may have spaces or even the word `code` in it.
+fn parse_register_name_code(input: &str) -> IResult<&str, Register> {
+    let (args, _) = tuple((tag("name"), space))(input)?;
+    let args = args.trim_end();
+    match args.rfind(" code ") {
+        Some(split) => Ok((
+            "",
+            Register::NameCode {
+                name: args[..split].trim().to_string(),
+                code: args[split + " code ".len()..].trim().to_string(),
+            },
+        )),
+        None => Err(nom::Err::Error((input, ErrorKind::Tag))),
+    }
+}
+
+pub fn parse_register(input: &str) -> IResult<&str, Register> {
+    map(
+        tuple((
+            tag("register"),
+            space,
+            alt((
+                complete(parse_register_later),
+                complete(parse_register_name_code),
+            )),
+        )),
+        |(_, _, register)| register
+    )(input)
+}
+
+impl FromStr for Register {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_register(s)
+            .map(|(_, x)| x)
+            .map_err(|e| Error::parse(CommandFamily::Gui, s, e))
+    }
+}
+
+// The command as it is sent, without its newline.
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::Later => write!(f, "register later"),
+            Register::NameCode { name, code } => write!(f, "register name {} code {}", name, code),
+        }
+    }
+}
+
+#[test]
+fn test_register() {
+    assert_eq!(Register::from_str("register later\n"), Ok(Register::Later));
+
+    let register = Register::NameCode {
+        name: "Stefan MK".to_string(),
+        code: "4359874324".to_string(),
+    };
+    assert_eq!(Register::from_str("register name Stefan MK code 4359874324\n"), Ok(register.clone()));
+    assert_eq!(register.to_string(), "register name Stefan MK code 4359874324");
+    assert_eq!(
+        Register::from_str("register name Codey code 12"),
+        Ok(Register::NameCode {
+            name: "Codey".to_string(),
+            code: "12".to_string(),
+        })
+    );
+
+    assert!(Register::from_str("register code 4359874324\n").is_err());
+    assert!(Register::from_str("register later please\n").is_err());
+}
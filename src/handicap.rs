@@ -0,0 +1,202 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use chess::{Board, ChessMove, MoveGen};
+
+use analysis::analysis_state::AnalysisState;
+use analysis::move_selector::{MoveSelector, SelectionPolicy};
+use engine::engine_command::EngineCommand;
+use engine_connection::EngineConnection;
+use error::Error;
+use gui::go::Go;
+use rng::Rng;
+
+#[cfg(test)]
+use test_engines::{connect_fake, fake_engine};
+
+const DEFAULT_MOVE_TIMEOUT: Duration = Duration::from_secs(60);
+// Ratings are clamped to this range.  At the top the engine never blunders.
+const MIN_RATING: u32 = 800;
+const MAX_RATING: u32 = 2800;
+// The chance of a blunder at the bottom of the range, in thousandths.
+const MAX_BLUNDER_PERMILLE: u64 = 300;
+// How many lines a blunder is chosen from, for engines that offer `MultiPV`.
+const BLUNDER_LINES: u64 = 4;
+
+/// Turns an engine into a weaker opponent: every move is searched no further than its limit,
+/// takes at least a minimum time, and now and then is a blunder.
+///
+/// A blunder is a move chosen at random from the engine's lines within a margin of its best,
+/// and the lower the rating the more often it happens and the wider the margin.  `MultiPV` is
+/// turned on for that if the engine has it; otherwise a blunder is any legal move.
+pub struct HandicappedEngine<'a> {
+    engine: EngineConnection<'a>,
+    limit: Go,
+    rating: Option<u32>,
+    min_think_time: Duration,
+    move_timeout: Duration,
+    selector: MoveSelector,
+    rng: Rng,
+    multi_pv_set: bool,
+}
+
+impl<'a> HandicappedEngine<'a> {
+    /// Search every move to depth one, at full strength, until told otherwise.
+    pub fn new(engine: EngineConnection<'a>) -> HandicappedEngine<'a> {
+        HandicappedEngine {
+            engine,
+            limit: Go::depth(1),
+            rating: None,
+            min_think_time: Duration::from_secs(0),
+            move_timeout: DEFAULT_MOVE_TIMEOUT,
+            selector: MoveSelector::new(SelectionPolicy::Best, 1),
+            rng: Rng::new(1),
+            multi_pv_set: false,
+        }
+    }
+
+    /// The `go` every move is searched with, such as `Go::nodes(1000)`.
+    pub fn limit(mut self, limit: Go) -> HandicappedEngine<'a> {
+        self.limit = limit;
+        self
+    }
+
+    /// Blunder like a player of about this rating.  From 2800 up there are no blunders.
+    pub fn rating(mut self, rating: u32) -> HandicappedEngine<'a> {
+        let rating = rating.clamp(MIN_RATING, MAX_RATING);
+        let margin = (MAX_RATING - rating) as i64 / 4 + 50;
+        self.rating = Some(rating);
+        self.selector = MoveSelector::new(SelectionPolicy::WithinCp(margin), self.rng.get_state());
+        self
+    }
+
+    /// Don't answer any faster than this, however quickly the engine does.
+    pub fn min_think_time(mut self, time: Duration) -> HandicappedEngine<'a> {
+        self.min_think_time = time;
+        self
+    }
+
+    /// How long to wait for the engine's best move.  A minute unless set.
+    pub fn move_timeout(mut self, timeout: Duration) -> HandicappedEngine<'a> {
+        self.move_timeout = timeout;
+        self
+    }
+
+    /// Blunders are random, but the same every time for the same seed.
+    pub fn seed(mut self, seed: u64) -> HandicappedEngine<'a> {
+        self.rng = Rng::new(seed);
+        self.selector = MoveSelector::new(self.selector.get_policy(), seed);
+        self
+    }
+
+    pub fn get_limit(&self) -> &Go {
+        &self.limit
+    }
+
+    pub fn get_rating(&self) -> Option<u32> {
+        self.rating
+    }
+
+    pub fn get_min_think_time(&self) -> Duration {
+        self.min_think_time
+    }
+
+    pub fn get_engine(&mut self) -> &mut EngineConnection<'a> {
+        &mut self.engine
+    }
+
+    pub fn into_inner(self) -> EngineConnection<'a> {
+        self.engine
+    }
+
+    fn blunders(&mut self) -> bool {
+        match self.rating {
+            Some(rating) => {
+                let permille = (MAX_RATING - rating) as u64 * MAX_BLUNDER_PERMILLE
+                    / (MAX_RATING - MIN_RATING) as u64;
+                self.rng.below(1000) < permille
+            }
+            None => false,
+        }
+    }
+
+    /// The move to play after `moves` from `board`, or `None` if the engine has none.  Fails
+    /// like `EngineConnection::recv_best_move_timeout`.
+    pub fn best_move(
+        &mut self,
+        board: Board,
+        moves: Vec<ChessMove>,
+    ) -> Result<Option<ChessMove>, Error> {
+        let start = Instant::now();
+        if self.rating.is_some() && !self.multi_pv_set {
+            self.multi_pv_set = true;
+            let info = self.engine.engine_info();
+            if let Some(name) = info.get_option("MultiPV").map(|o| o.get_name().clone()) {
+                self.engine.send_setoption(&name, Some(&BLUNDER_LINES.to_string()))?;
+            }
+        }
+
+        self.engine.send_position(board, moves)?;
+        // The connection plays the best move on its own board once it arrives.
+        let position = self.engine.current_board();
+        self.engine.send_go_with(self.limit.clone())?;
+        let mut analysis = AnalysisState::new();
+        let deadline = start + self.move_timeout;
+        let best_move = match self.engine.recv_until(
+            |c| match c {
+                EngineCommand::Info(info) => {
+                    analysis.update(info);
+                    false
+                }
+                EngineCommand::BestMove(_) => true,
+                _ => false,
+            },
+            deadline,
+        )? {
+            EngineCommand::BestMove(best_move) => self.engine.check_best_move(best_move)?,
+            _ => unreachable!(),
+        };
+        let mut chosen = best_move.get_move();
+
+        if chosen.is_some() && self.blunders() {
+            chosen = if analysis.get_lines().count() > 1 {
+                self.selector.select(&analysis).or(chosen)
+            } else {
+                let legal = MoveGen::new_legal(&position).collect::<Vec<ChessMove>>();
+                let pick = self.rng.below(legal.len() as u64) as usize;
+                Some(legal[pick])
+            };
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed < self.min_think_time {
+            sleep(self.min_think_time - elapsed);
+        }
+        Ok(chosen)
+    }
+}
+
+#[test]
+fn test_handicapped_engine() {
+    let address = fake_engine(false);
+    let board = Board::default();
+    let first = MoveGen::new_legal(&board).next();
+
+    let mut full =
+        HandicappedEngine::new(connect_fake(&address)).min_think_time(Duration::from_millis(50));
+    let start = Instant::now();
+    assert_eq!(full.best_move(board, vec![]).unwrap(), first);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    let mut weak = HandicappedEngine::new(connect_fake(&address)).rating(0).seed(11);
+    assert_eq!(weak.get_rating(), Some(MIN_RATING));
+    let picks = (0..40)
+        .map(|_| weak.best_move(board, vec![]).unwrap().unwrap())
+        .collect::<Vec<ChessMove>>();
+    assert!(picks.iter().all(|m| board.legal(*m)));
+    assert!(picks.iter().any(|m| Some(*m) == first));
+    assert!(picks.iter().any(|m| Some(*m) != first));
+
+    let mut strong = HandicappedEngine::new(connect_fake(&address)).rating(3000).seed(11);
+    assert!((0..20).all(|_| strong.best_move(board, vec![]).unwrap() == first));
+}
@@ -0,0 +1,150 @@
+use std::ops::Index;
+use std::time::{Duration, Instant};
+
+use command::Command;
+use engine::best_move::BestMove;
+use engine::engine_command::EngineCommand;
+use engine::engine_option::EngineOption;
+use engine::info::Info;
+use transcript::Direction;
+
+#[cfg(test)]
+use chess::{ChessMove, Square};
+#[cfg(test)]
+use engine::option_type::OptionType;
+#[cfg(test)]
+use gui::gui_command::GuiCommand;
+
+/// One command in an engine connection's history, and which way it went.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    direction: Direction,
+    at: Duration,
+    command: Command,
+}
+
+impl HistoryEntry {
+    pub fn get_direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// How long after the connection was made.
+    pub fn get_at(&self) -> Duration {
+        self.at
+    }
+
+    pub fn get_command(&self) -> &Command {
+        &self.command
+    }
+}
+
+/// Every command sent to and received from an engine, in order.
+#[derive(Clone, Debug)]
+pub struct History {
+    started: Instant,
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            started: Instant::now(),
+            entries: vec![],
+        }
+    }
+
+    pub fn push(&mut self, direction: Direction, command: Command) {
+        self.entries.push(HistoryEntry {
+            direction,
+            at: self.started.elapsed(),
+            command,
+        });
+    }
+
+    pub fn get_entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The commands that went one way, in order.
+    pub fn by_direction(&self, direction: Direction) -> impl Iterator<Item = &Command> {
+        self.entries
+            .iter()
+            .filter(move |e| e.direction == direction)
+            .map(|e| &e.command)
+    }
+
+    fn received(&self) -> impl DoubleEndedIterator<Item = &EngineCommand> {
+        self.entries.iter().filter_map(|e| match e.command {
+            Command::Engine(ref c) if e.direction == Direction::Received => Some(c),
+            _ => None,
+        })
+    }
+
+    /// Every `info` the engine sent.
+    pub fn infos(&self) -> impl Iterator<Item = &Info> {
+        self.received().filter_map(|c| match c {
+            EngineCommand::Info(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    pub fn last_bestmove(&self) -> Option<&BestMove> {
+        self.received().rev().find_map(|c| match c {
+            EngineCommand::BestMove(best_move) => Some(best_move),
+            _ => None,
+        })
+    }
+
+    /// The options the engine announced.
+    pub fn options_seen(&self) -> impl Iterator<Item = &EngineOption> {
+        self.received().filter_map(|c| match c {
+            EngineCommand::EngineOption(option) => Some(option),
+            _ => None,
+        })
+    }
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new()
+    }
+}
+
+impl Index<usize> for History {
+    type Output = HistoryEntry;
+
+    fn index(&self, index: usize) -> &HistoryEntry {
+        &self.entries[index]
+    }
+}
+
+#[test]
+fn test_history() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let hash = EngineOption::new("Hash".to_string(), OptionType::Spin(16, 1, 1024));
+
+    let mut history = History::new();
+    history.push(Direction::Sent, Command::Gui(GuiCommand::Uci));
+    history.push(Direction::Received, Command::Engine(EngineCommand::EngineOption(hash.clone())));
+    history.push(Direction::Received, Command::Engine(EngineCommand::UciOk));
+    history.push(Direction::Sent, Command::Unknown("xyzzy".to_string()));
+    history.push(Direction::Received, Command::Engine(EngineCommand::Info(Info::depth(1))));
+    history.push(Direction::Received, Command::Engine(EngineCommand::BestMove(BestMove::new(e2e4))));
+
+    assert_eq!(history.len(), 6);
+    assert_eq!(history[2].get_command(), &Command::Engine(EngineCommand::UciOk));
+    assert_eq!(history[3].get_direction(), Direction::Sent);
+    assert_eq!(history.by_direction(Direction::Sent).count(), 2);
+    assert_eq!(history.infos().collect::<Vec<&Info>>(), vec![&Info::depth(1)]);
+    assert_eq!(history.last_bestmove().and_then(|b| b.get_move()), Some(e2e4));
+    assert_eq!(history.options_seen().collect::<Vec<&EngineOption>>(), vec![&hash]);
+    assert!(History::new().last_bestmove().is_none());
+}
@@ -0,0 +1,311 @@
+// Just enough JSON to read and write the configuration files of other chess programs.
+use std::fmt;
+
+use error::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    // Kept in file order, so that saved files stay in the order they were read.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub(crate) fn parse(text: &str) -> Result<Json, Error> {
+        let mut parser = Parser {
+            chars: text.char_indices().peekable(),
+            text,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            None => Ok(value),
+            Some((at, _)) => Err(parser.error(at, "trailing characters")),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    // Strings as they are, and other scalars the way UCI would write them.
+    pub(crate) fn to_option_value(&self) -> Option<String> {
+        match self {
+            Json::String(s) => Some(s.clone()),
+            Json::Bool(b) => Some(b.to_string()),
+            Json::Number(n) => Some(format_number(*n)),
+            _ => None,
+        }
+    }
+
+    // The reverse of `to_option_value`: numbers and booleans are written unquoted.
+    pub(crate) fn from_option_value(value: &str) -> Json {
+        match value {
+            "true" => Json::Bool(true),
+            "false" => Json::Bool(false),
+            _ => match value.parse::<i64>() {
+                Ok(n) => Json::Number(n as f64),
+                Err(_) => Json::String(value.to_string()),
+            },
+        }
+    }
+
+    fn write_indented(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        let pad = "    ".repeat(indent + 1);
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", format_number(*n)),
+            Json::String(s) => write_string(f, s),
+            Json::Array(values) if values.is_empty() => write!(f, "[]"),
+            Json::Object(members) if members.is_empty() => write!(f, "{{}}"),
+            Json::Array(values) => {
+                writeln!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    write!(f, "{}", pad)?;
+                    value.write_indented(f, indent + 1)?;
+                    writeln!(f, "{}", if i + 1 < values.len() { "," } else { "" })?;
+                }
+                write!(f, "{}]", "    ".repeat(indent))
+            }
+            Json::Object(members) => {
+                writeln!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    write!(f, "{}", pad)?;
+                    write_string(f, key)?;
+                    write!(f, ": ")?;
+                    value.write_indented(f, indent + 1)?;
+                    writeln!(f, "{}", if i + 1 < members.len() { "," } else { "" })?;
+                }
+                write!(f, "{}}}", "    ".repeat(indent))
+            }
+        }
+    }
+}
+
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{}", n)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    text: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, at: usize, what: &str) -> Error {
+        let line = self.text[..at].matches('\n').count() + 1;
+        Error::ConfigError(format!("{} on line {}", what, line))
+    }
+
+    fn end(&self) -> Error {
+        self.error(self.text.len(), "unexpected end of file")
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|&(_, c)| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((at, _)) => Err(self.error(at, &format!("expected '{}'", expected))),
+            None => Err(self.end()),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, Error> {
+        self.skip_whitespace();
+        let (at, c) = match self.chars.peek() {
+            Some(&next) => next,
+            None => return Err(self.end()),
+        };
+        match c {
+            '{' => self.object(),
+            '[' => self.array(),
+            '"' => self.string().map(Json::String),
+            't' => self.literal(at, "true", Json::Bool(true)),
+            'f' => self.literal(at, "false", Json::Bool(false)),
+            'n' => self.literal(at, "null", Json::Null),
+            '-' | '0'..='9' => self.number(at),
+            _ => Err(self.error(at, "expected a value")),
+        }
+    }
+
+    fn literal(&mut self, at: usize, word: &str, value: Json) -> Result<Json, Error> {
+        if !self.text[at..].starts_with(word) {
+            return Err(self.error(at, "expected a value"));
+        }
+        for _ in 0..word.len() {
+            self.chars.next();
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self, at: usize) -> Result<Json, Error> {
+        let mut end = at;
+        while let Some(&(i, c)) = self.chars.peek() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                break;
+            }
+            end = i + c.len_utf8();
+            self.chars.next();
+        }
+        self.text[at..end]
+            .parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| self.error(at, "invalid number"))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect('"')?;
+        let mut result = String::new();
+        loop {
+            let (at, c) = self.chars.next().ok_or_else(|| self.end())?;
+            match c {
+                '"' => return Ok(result),
+                '\\' => {
+                    let (_, escaped) = self.chars.next().ok_or_else(|| self.end())?;
+                    result.push(match escaped {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            let hex = self.text.get(at + 2..at + 6).unwrap_or("");
+                            let code = u32::from_str_radix(hex, 16)
+                                .map_err(|_| self.error(at, "invalid escape"))?;
+                            for _ in 0..4 {
+                                self.chars.next();
+                            }
+                            // Surrogate pairs aren't needed for anything read here.
+                            std::char::from_u32(code).unwrap_or('\u{fffd}')
+                        }
+                        _ => return Err(self.error(at, "invalid escape")),
+                    });
+                }
+                c => result.push(c),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, Error> {
+        self.expect('[')?;
+        let mut values = vec![];
+        self.skip_whitespace();
+        if self.chars.peek().is_some_and(|&(_, c)| c == ']') {
+            self.chars.next();
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => {}
+                Some((_, ']')) => return Ok(Json::Array(values)),
+                Some((at, _)) => return Err(self.error(at, "expected ',' or ']'")),
+                None => return Err(self.end()),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, Error> {
+        self.expect('{')?;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.chars.peek().is_some_and(|&(_, c)| c == '}') {
+            self.chars.next();
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => {}
+                Some((_, '}')) => return Ok(Json::Object(members)),
+                Some((at, _)) => return Err(self.error(at, "expected ',' or '}'")),
+                None => return Err(self.end()),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_json_round_trip() {
+    let text = r#"{"name": "A \"quoted\" éngine", "list": [1, -2.5, true, null, []], "empty": {}}"#;
+    let json = Json::parse(text).unwrap();
+    assert_eq!(json.get("name").and_then(|n| n.as_str()), Some("A \"quoted\" éngine"));
+    assert_eq!(json.get("list").and_then(|l| l.as_array()).map(|l| l.len()), Some(5));
+    assert_eq!(Json::parse(&json.to_string()), Ok(json));
+
+    assert_eq!(
+        Json::parse("[1,\n 2"),
+        Err(Error::ConfigError("unexpected end of file on line 2".to_string()))
+    );
+    assert!(Json::parse("{\"a\" 1}").is_err());
+    assert!(Json::parse("[1] 2").is_err());
+}
@@ -1,40 +1,149 @@
 extern crate nom;
-extern crate arrayvec;
 extern crate chess;
-extern crate nodrop;
 extern crate num_traits;
 
+mod adjudication;
+mod analysis;
+mod auto_configure;
+mod bridge;
+mod capabilities;
 mod command;
+mod command_queue;
+mod connection_state;
 mod engine;
 mod engine_base;
+mod engine_config;
 mod engine_connection;
 mod error;
+mod game_history;
+mod game_result;
 mod gui;
+mod handicap;
+mod history;
+mod json;
 mod parsers;
+mod proxy;
+mod resource_usage;
+mod rng;
+mod san;
+mod search_outcome;
+mod supervision;
+#[cfg(test)]
+mod roundtrip_tests;
+#[cfg(test)]
+mod test_engines;
+#[cfg(test)]
+mod test_transcripts;
 mod timer;
+mod tournament;
+mod transcript;
+mod transport;
+mod tuning;
+mod warm_up;
 
+pub use adjudication::{Adjudication, AdjudicationReason, Adjudicator, TablebaseProbe};
+pub use analysis::analysis_session::AnalysisSession;
+pub use analysis::analysis_history::{AnalysisHistory, EvalSwing};
+pub use analysis::analysis_state::{AnalysisChange, AnalysisLine, AnalysisState};
+pub use analysis::annotate::{Annotation, Annotator, MoveClass, PositionEvaluator, SearchEvaluator};
+pub use analysis::eco::{classify, classify_position, Opening};
+pub use analysis::move_selector::{MoveSelector, SelectionPolicy};
+pub use auto_configure::{AutoConfig, SystemResources};
+pub use bridge::{Bridge, BridgeEnd};
+pub use capabilities::Capabilities;
 pub use command::Command;
+pub use command_queue::OverflowPolicy;
+pub use connection_state::{ConnectionState, OptionsDuringSearch};
 pub use engine::best_move::BestMove;
 pub use engine::copyprotection::CopyProtection;
 pub use engine::engine_command::EngineCommand;
+pub use engine::engine_info::EngineInfo;
 pub use engine::engine_option::EngineOption;
 pub use engine::id::Id;
 pub use engine::info::Info;
 pub use engine::option_type::OptionType;
 pub use engine::registration::Registration;
-pub use engine::score::Score;
-pub use engine_base::engine_options::EngineOptions;
+pub use engine::score::{Score, ScoreBound, ScoreKind};
+pub use engine_base::endgame::{EndgameKnowledge, KpkBitbase, KNOWN_WIN};
+pub use engine_base::engine::{EngineBase, OptionDiagnostics};
+pub use engine_base::engine_options::{
+    EngineOptions, INFO_INTERVAL, MOVE_OVERHEAD, NODES_TIME, PARAMS_FILE, PONDER, SEARCH_TRACE,
+    UCI_ANALYSE_MODE, UCI_CHESS960,
+};
 pub use engine_base::eval::Eval;
-pub use engine_base::evaluate::{DefaultEvaluate, Evaluate};
+pub use engine_base::eval_params::EvalParams;
+pub use engine_base::evaluate::{
+    DefaultEvaluate, EndgameEvaluate, Evaluate, PawnStructureEvaluate,
+};
+pub use engine_base::float_eval::{FloatEval, FLOAT_EVAL_MAX};
+pub use engine_base::info_throttle::InfoThrottle;
 pub use engine_base::iterative_deepening::{DefaultIterativeDeepening, IterativeDeepening};
-pub use engine_base::pv::Pv;
+pub use engine_base::mate_search::MateSearch;
+pub use engine_base::mcts::{
+    EvaluatePolicy, MctsSearch, PolicyValue, DEFAULT_C_PUCT, DEFAULT_MAX_TREE_NODES,
+    FIRST_ITERATION_PLAYOUTS,
+};
+pub use engine_base::node_counter::{NodeCounter, NodeCounterHandle, NODE_COUNTER_FLUSH};
+pub use engine_base::option_schema::{
+    CheckOption, ComboOption, OptionsBuilder, SpinOption, StringOption, TypedOption,
+};
+pub use engine_base::pawn_table::{PawnEntry, PawnTable};
+pub use engine_base::pruning::{
+    piece_value, PruningMargins, DELTA_MARGIN, FUTILITY_MARGIN, RAZOR_MARGIN, REVERSE_FUTILITY_MARGIN,
+};
+pub use engine_base::pv::{Pv, PvWalk};
+pub use engine_base::pv_table::PvTable;
+pub use engine_base::resettable::Resettable;
+pub use engine_base::root_moves::{RootMove, RootMoves};
 pub use engine_base::search::{DefaultSearch, Search};
+pub use engine_base::search_handle::SearchHandle;
 pub use engine_base::search_info::SearchInfo;
+pub use engine_base::search_limits::SearchLimits;
+pub use engine_base::search_result::SearchResult;
+pub use engine_base::search_stats::SearchStats;
+pub use engine_base::search_trace::{PruneReason, SearchTrace, TraceNode, DEFAULT_TRACE_NODES};
+pub use engine_base::server::serve_uci;
 pub use engine_base::time_manager::{DefaultTimeManager, TimeManager};
+pub use engine_base::watchdog::{hard_limit, Watchdog, HARD_LIMIT_PERCENT};
+pub use engine_base::transposition_table::TranspositionTable;
 pub use engine_base::tt_entry::TtEntry;
 pub use engine_base::tt_score::TtScore;
-pub use engine_connection::EngineConnection;
+pub use engine_base::zobrist;
+pub use engine_config::EngineConfig;
+pub use engine_connection::{Commands, EngineConnection, EngineConnectionBuilder};
 pub use error::*;
+pub use game_history::GameHistory;
+pub use game_result::{game_result, is_insufficient_material, GameResult};
 pub use gui::go::Go;
 pub use gui::gui_command::*;
+pub use gui::position::{MoveCounters, PositionSpec, PositionStart};
+pub use gui::register::Register;
+pub use handicap::HandicappedEngine;
+pub use history::{History, HistoryEntry};
+pub use parsers::{Counter, NumberOverflow};
+pub use proxy::UciProxy;
+pub use resource_usage::{ResourceMonitor, ResourceUsage};
+pub use san::{from_san, line_to_san, to_san};
+pub use search_outcome::SearchOutcome;
+pub use supervision::{Supervision, DEFAULT_SLACK, DEFAULT_STOP_GRACE, DEFAULT_TAIL_LINES};
+pub use timer::budget_policy::BudgetPolicy;
+pub use timer::clock_watcher::{ClockEvent, ClockWatcher};
 pub use timer::timer::Timer;
+pub use timer::timer_state::TimerState;
+pub use tournament::crosstable::{Crosstable, Record};
+pub use tournament::game::{Game, GameRecord, PlayedMove, Termination, TranscriptPolicy};
+pub use tournament::openings::{OpeningLine, OpeningOrder, OpeningSet};
+pub use tournament::runner::{Pairing, Tournament, TournamentEvent, TournamentFormat};
+pub use transcript::{Direction, Transcript, TranscriptLine};
+pub use transport::process::ProcessTransport;
+pub use transport::process_controls::ProcessControls;
+pub use transport::tcp::TcpTransport;
+#[cfg(feature = "websocket")]
+pub use transport::websocket::WebSocketTransport;
+pub use transport::{Transport, TransportReader, TransportWriter};
+pub use tuning::datagen::{DataFormat, DataGenerator, TrainingRecord, BINARY_RECORD_SIZE};
+pub use tuning::spsa::{
+    SpsaIteration, SpsaParam, SpsaTuner, SPSA_ALPHA, SPSA_A_RATIO, SPSA_GAMMA,
+};
+pub use tuning::texel::{texel_sigmoid, LabeledPosition, TexelTuner, DEFAULT_TEXEL_K};
+pub use warm_up::{WarmUpReport, WarmUpSearch};
@@ -1,86 +1,143 @@
 use chess::{Board, ChessMove, File, Piece, Rank, Square};
 use nom::branch::alt;
-use nom::bytes::complete::take_while;
+use nom::bytes::complete::{take_while, take_while1};
 use nom::bytes::streaming::tag;
-use nom::character::complete::digit1;
-use nom::combinator::{complete, map, map_res, opt, recognize, value};
-use nom::multi::fold_many1;
+use nom::combinator::{complete, map, opt, recognize};
+use nom::error::ErrorKind;
 use nom::sequence::{pair, tuple};
 use nom::IResult;
-use nom::{FindToken, InputTakeAtPosition};
+use num_traits::PrimInt;
 use std::str::FromStr;
 
-pub fn parse_rank(input: &str) -> IResult<&str, Rank> {
-    alt((
-        value(Rank::First, tag("1")),
-        value(Rank::Second, tag("2")),
-        value(Rank::Third, tag("3")),
-        value(Rank::Fourth, tag("4")),
-        value(Rank::Fifth, tag("5")),
-        value(Rank::Sixth, tag("6")),
-        value(Rank::Seventh, tag("7")),
-        value(Rank::Eighth, tag("8")),
-    ))(input)
-}
-
-pub fn parse_file(input: &str) -> IResult<&str, File> {
-    alt((
-        value(File::A, tag("a")),
-        value(File::B, tag("b")),
-        value(File::C, tag("c")),
-        value(File::D, tag("d")),
-        value(File::E, tag("e")),
-        value(File::F, tag("f")),
-        value(File::G, tag("g")),
-        value(File::H, tag("h")),
-    ))(input)
-}
-
-pub fn parse_square(input: &str) -> IResult<&str, Square> {
-    map(pair(parse_file, parse_rank), |(f, r)| {
-        Square::make_square(r, f)
-    })(input)
+// Limits on what the parsers accept, so that a broken or hostile peer can't make them allocate
+// without bound.  Each is well past anything a real engine or GUI sends.
+pub const MAX_MOVES: usize = 2048;
+pub const MAX_TOKEN_LENGTH: usize = 1024;
+pub const MAX_FEN_LENGTH: usize = 128;
+
+fn too_large(input: &str) -> nom::Err<(&str, ErrorKind)> {
+    nom::Err::Failure((input, ErrorKind::TooLarge))
 }
 
-pub fn parse_promotion_piece(input: &str) -> IResult<&str, Option<Piece>> {
-    opt(alt((
-        complete(value(Piece::Knight, tag("n"))),
-        complete(value(Piece::Bishop, tag("b"))),
-        complete(value(Piece::Rook, tag("r"))),
-        complete(value(Piece::Queen, tag("q"))),
-    )))(input)
+// The move at the start of `bytes`, and how many bytes it takes up.  Moves are read a byte at a
+// time rather than through nom, as move lists make up most of the text in an info line.
+#[inline]
+fn move_at(bytes: &[u8]) -> Option<(ChessMove, usize)> {
+    let square = |file: u8, rank: u8| match (file, rank) {
+        (b'a'..=b'h', b'1'..=b'8') => Some(Square::make_square(
+            Rank::from_index((rank - b'1') as usize),
+            File::from_index((file - b'a') as usize),
+        )),
+        _ => None,
+    };
+    let source = square(*bytes.first()?, *bytes.get(1)?)?;
+    let dest = square(*bytes.get(2)?, *bytes.get(3)?)?;
+    let promotion = match bytes.get(4) {
+        Some(b'n') => Some(Piece::Knight),
+        Some(b'b') => Some(Piece::Bishop),
+        Some(b'r') => Some(Piece::Rook),
+        Some(b'q') => Some(Piece::Queen),
+        _ => None,
+    };
+    let length = if promotion.is_some() { 5 } else { 4 };
+    Some((ChessMove::new(source, dest, promotion), length))
 }
 
 pub fn parse_move(input: &str) -> IResult<&str, ChessMove> {
-    map(
-        tuple((parse_square, parse_square, parse_promotion_piece)),
-        |(s1, s2, promotion)| (ChessMove::new(s1, s2, promotion)),
-    )(input)
+    match move_at(input.as_bytes()) {
+        Some((m, length)) => Ok((&input[length..], m)),
+        None => Err(nom::Err::Error((input, ErrorKind::Tag))),
+    }
+}
+
+// Whitespace is allowed to run up to the end of the input, so commands parse the same with or
+// without a trailing newline.
+#[inline]
+pub fn space(input: &str) -> IResult<&str, &str> {
+    let end = input
+        .bytes()
+        .position(|c| !matches!(c, b' ' | b'\t' | b'\r' | b'\n'))
+        .unwrap_or(input.len());
+    Ok((&input[end..], &input[..end]))
 }
 
-pub fn parse_move_space(input: &str) -> IResult<&str, ChessMove> {
+pub fn non_newline_space(input: &str) -> IResult<&str, &str> {
+    let end = input
+        .bytes()
+        .position(|c| !matches!(c, b' ' | b'\t' | b'\r'))
+        .unwrap_or(input.len());
+    Ok((&input[end..], &input[..end]))
+}
+
+pub fn end_of_input(input: &str) -> IResult<&str, &str> {
+    if input.is_empty() {
+        Ok((input, input))
+    } else {
+        Err(nom::Err::Error((input, nom::error::ErrorKind::Eof)))
+    }
+}
+
+pub fn end_of_line(input: &str) -> IResult<&str, &str> {
     map(
-        tuple((parse_square, parse_square, parse_promotion_piece, space)),
-        |(s1, s2, promotion, _)| (ChessMove::new(s1, s2, promotion)),
+        tuple((non_newline_space, alt((complete(tag("\n")), end_of_input)))),
+        |(_, end)| end,
     )(input)
 }
 
-pub fn space(input: &str) -> IResult<&str, &str> {
-    input.split_at_position(|c| !(" \t\r\n").find_token(c))
+// The digits at the start of `input`, of which there must be at least one.
+#[inline]
+fn digit_run(input: &str) -> IResult<&str, &str> {
+    match input.bytes().position(|c| !c.is_ascii_digit()).unwrap_or(input.len()) {
+        0 => Err(nom::Err::Error((input, ErrorKind::Digit))),
+        end => Ok((&input[end..], &input[..end])),
+    }
 }
 
-pub fn non_newline_space(input: &str) -> IResult<&str, &str> {
-    input.split_at_position(|c| !(" \t\r").find_token(c))
+#[inline]
+pub fn token(input: &str) -> IResult<&str, &str> {
+    // Tokens are almost always ASCII, which can be scanned a byte at a time.
+    let end = input.bytes().position(|c| matches!(c, b'\t'..=b'\r' | b' ') || !c.is_ascii());
+    let (rest, token) = match end {
+        Some(end) if end > 0 && input.as_bytes()[end].is_ascii() => (&input[end..], &input[..end]),
+        None if !input.is_empty() => ("", input),
+        _ => take_while1(|c: char| !c.is_whitespace())(input)?,
+    };
+    if token.len() > MAX_TOKEN_LENGTH {
+        return Err(too_large(input));
+    }
+    Ok((rest, token))
 }
 
-pub fn parse_fen(input: &str) -> IResult<&str, Board> {
+/// A FEN, with its halfmove clock and fullmove number, which `Board` doesn't keep.  Either is
+/// `None` if the FEN leaves it out.
+// Castling rights as the chess crate reads them.  Chess960 GUIs name the castling rook's file
+// instead, as Shredder-FEN does; the chess crate can only castle with the rooks in the corners,
+// so any other file is refused rather than have the right silently dropped.
+fn shredder_castling(castle: &str) -> Result<String, nom::Err<(&'static str, ErrorKind)>> {
+    castle
+        .chars()
+        .map(|c| match c {
+            'H' => Ok('K'),
+            'A' => Ok('Q'),
+            'h' => Ok('k'),
+            'a' => Ok('q'),
+            'B'..='G' | 'b'..='g' => Err(nom::Err::Failure((
+                "Chess960 castling the chess crate can't play",
+                ErrorKind::Verify,
+            ))),
+            c => Ok(c),
+        })
+        .collect()
+}
+
+pub fn parse_fen(input: &str) -> IResult<&str, (Board, Option<u64>, Option<u64>)> {
     let parsed = map(
         tuple((
             take_while(|y| "pPnNbBrRqQkK12345678/".contains(y)),
             space,
             alt((tag("w"), tag("b"))),
             space,
-            take_while(|y| "-kKqQ".contains(y)),
+            take_while(|y| "-kKqQABCDEFGHabcdefgh".contains(y)),
             space,
             take_while(|y| "abcdefgh12345678-".contains(y)),
             space,
@@ -89,47 +146,165 @@ pub fn parse_fen(input: &str) -> IResult<&str, Board> {
             take_while(|y| "0123456789".contains(y)),
         )),
         |(board, _, player, _, castle, _, ep, _, m1, _, m2)| {
-            Board::from_str(&format!(
-                "{} {} {} {} {} {}",
-                board, player, castle, ep, m1, m2
-            ))
-            .map_err(|_| nom::Err::Failure(("Invalid FEN", nom::error::ErrorKind::Verify)))
+            let castle = shredder_castling(castle)?;
+            let fen = format!("{} {} {} {} {} {}", board, player, castle, ep, m1, m2);
+            if fen.len() > MAX_FEN_LENGTH {
+                return Err(too_large("Invalid FEN"));
+            }
+            let board = Board::from_str(&fen)
+                .map_err(|_| nom::Err::Failure(("Invalid FEN", nom::error::ErrorKind::Verify)))?;
+            let counter = |digits: &str| match digits {
+                "" => None,
+                digits => Some(checked_digits(digits, 0).unwrap_or(u64::MAX)),
+            };
+            Ok((board, counter(m1), counter(m2)))
         },
     )(input)?;
 
     Ok((parsed.0, parsed.1?))
 }
 
+/// What the number parsers do with a value too large for the type it is read into.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NumberOverflow {
+    /// Read it as the largest value the type can hold.
+    #[default]
+    Saturate,
+    /// Fail with `ErrorKind::TooLarge`.
+    Error,
+}
+
+/// Node counts, nodes per second and tablebase hits.  With the `wide-counters` feature they are
+/// kept in a `u128`, for analysis long enough to count past `u64`.
+#[cfg(not(feature = "wide-counters"))]
+pub type Counter = u64;
+#[cfg(feature = "wide-counters")]
+pub type Counter = u128;
+
+fn overflow<T: PrimInt>(input: &str, policy: NumberOverflow) -> Result<T, nom::Err<(&str, ErrorKind)>> {
+    match policy {
+        NumberOverflow::Saturate => Ok(T::max_value()),
+        NumberOverflow::Error => Err(too_large(input)),
+    }
+}
+
+// `digits` followed by `zeros` zeros, as a `T`.
+fn checked_digits<T: PrimInt>(digits: &str, zeros: usize) -> Option<T> {
+    let ten = T::from(10)?;
+    let mut value = T::zero();
+    for b in digits.bytes() {
+        value = value.checked_mul(&ten)?.checked_add(&T::from(b - b'0')?)?;
+    }
+    for _ in 0..zeros {
+        value = value.checked_mul(&ten)?;
+    }
+    Some(value)
+}
+
+/// An unsigned integer, with values too large for `T` handled as `policy` says.
+pub fn unsigned_with<T: PrimInt>(policy: NumberOverflow) -> impl Fn(&str) -> IResult<&str, T> {
+    move |input| {
+        let (rest, digits) = digit_run(input)?;
+        match checked_digits(digits, 0) {
+            Some(value) => Ok((rest, value)),
+            None => Ok((rest, overflow(input, policy)?)),
+        }
+    }
+}
+
+/// A counter, which engines sometimes write in scientific notation (`1.5e+09`) once it gets
+/// large.  Any fraction left over after the exponent is dropped.
+#[inline]
+pub fn counter_with(policy: NumberOverflow) -> impl Fn(&str) -> IResult<&str, Counter> {
+    move |input| {
+        // Most counters are plain integers, which don't need the combinators below.
+        let (rest, whole) = digit_run(input)?;
+        if !matches!(rest.as_bytes().first(), Some(b'.' | b'e' | b'E')) {
+            return match checked_digits(whole, 0) {
+                Some(value) => Ok((rest, value)),
+                None => Ok((rest, overflow(input, policy)?)),
+            };
+        }
+        let (rest, (whole, fraction, exponent)) = tuple((
+            digit_run,
+            opt(complete(pair(tag("."), digit_run))),
+            opt(complete(tuple((alt((tag("e"), tag("E"))), opt(tag("+")), digit_run)))),
+        ))(input)?;
+        let fraction = fraction.map_or("", |(_, digits)| digits);
+        let exponent = match exponent {
+            Some((_, _, digits)) => match usize::from_str(digits) {
+                Ok(exponent) => exponent,
+                Err(_) => return Ok((rest, overflow(input, policy)?)),
+            },
+            None => 0,
+        };
+
+        let digits = format!("{}{}", whole, fraction);
+        let value = if exponent >= fraction.len() {
+            checked_digits(&digits, exponent - fraction.len())
+        } else {
+            checked_digits(&digits[..digits.len() - (fraction.len() - exponent)], 0)
+        };
+        match value {
+            Some(value) => Ok((rest, value)),
+            None => Ok((rest, overflow(input, policy)?)),
+        }
+    }
+}
+
+/// An unsigned integer, saturating at `u64::MAX`.
 pub fn integer(input: &str) -> IResult<&str, u64> {
-    map_res(digit1, u64::from_str)(input)
+    unsigned_with(NumberOverflow::Saturate)(input)
 }
 
+/// A signed integer, saturating at `i64::MIN` or `i64::MAX`.
 pub fn parse_i64(input: &str) -> IResult<&str, i64> {
-    map_res(recognize(pair(opt(tag("-")), digit1)), |s: &str| {
+    map(recognize(pair(opt(tag("-")), digit_run)), |s: &str| {
+        // The text is all digits, so the only way to fail is to be out of range.
         s.parse::<i64>()
+            .unwrap_or(if s.starts_with('-') { i64::MIN } else { i64::MAX })
     })(input)
 }
 
 pub fn parse_movelist(input: &str) -> IResult<&str, Vec<ChessMove>> {
-    map(
-        fold_many1(
-            alt((complete(parse_move_space), complete(parse_move))),
-            Vec::new(),
-            |mut acc: Vec<ChessMove>, item: ChessMove| {
-                acc.push(item);
-                acc
-            },
-        ),
-        |moves| moves.to_vec(),
-    )(input)
+    let (input, first) = parse_move(input)?;
+    // Moves take at least five bytes each with the space after them, so this is enough for a
+    // line that runs to the end of the input, as a `pv` usually does.
+    let mut moves = Vec::with_capacity((input.len() / 5 + 1).min(MAX_MOVES));
+    moves.push(first);
+
+    let bytes = input.as_bytes();
+    let mut at = 0;
+    loop {
+        while matches!(bytes.get(at), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            at += 1;
+        }
+        match move_at(&bytes[at..]) {
+            Some((m, length)) => {
+                if moves.len() == MAX_MOVES {
+                    return Err(too_large(&input[at..]));
+                }
+                moves.push(m);
+                at += length;
+            }
+            None => break,
+        }
+    }
+
+    Ok((&input[at..], moves))
 }
 
+#[cfg(test)]
+use command::Command;
+#[cfg(test)]
+use rng::Rng;
+
 #[test]
 fn test_parse_fen_success() {
     let parsed = parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
     let want = Board::default();
 
-    assert_eq!(parsed, Ok(("", want)));
+    assert_eq!(parsed, Ok(("", (want, Some(0), Some(1)))));
 }
 
 #[test]
@@ -139,3 +314,101 @@ fn test_parse_fen_failure() {
 
     assert_eq!(res, Err(want));
 }
+
+#[test]
+fn test_parse_fen_shredder_castling() {
+    let (_, (board, _, _)) =
+        parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1").unwrap();
+    assert_eq!(board, Board::default());
+
+    // A Chess960 start with the rooks off the corners, which the chess crate can't castle in.
+    let res = parse_fen("bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1");
+    let want = nom::Err::Failure((
+        "Chess960 castling the chess crate can't play",
+        nom::error::ErrorKind::Verify,
+    ));
+    assert_eq!(res, Err(want));
+}
+
+#[test]
+fn test_parse_movelist() {
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let e7e8q = ChessMove::new(Square::E7, Square::E8, Some(Piece::Queen));
+
+    assert_eq!(
+        parse_movelist("e2e4 e7e8q string x"),
+        Ok(("string x", vec![e2e4, e7e8q]))
+    );
+    assert_eq!(parse_movelist("e2e4\n"), Ok(("", vec![e2e4])));
+    assert!(parse_movelist("i2e4").is_err());
+}
+
+#[test]
+fn test_token() {
+    assert_eq!(token("depth 12"), Ok((" 12", "depth")));
+    assert_eq!(token("depth"), Ok(("", "depth")));
+    assert_eq!(token("d\u{e9}pth\u{a0}12"), Ok(("\u{a0}12", "d\u{e9}pth")));
+    assert!(token("").is_err());
+    assert!(token(" depth").is_err());
+}
+
+#[test]
+fn test_number_overflow() {
+    let huge = "123456789012345678901234567890";
+    assert_eq!(integer("42 "), Ok((" ", 42)));
+    assert_eq!(integer(huge), Ok(("", u64::MAX)));
+    assert_eq!(
+        unsigned_with::<u64>(NumberOverflow::Error)(huge),
+        Err(nom::Err::Failure((huge, ErrorKind::TooLarge)))
+    );
+    assert_eq!(unsigned_with::<u8>(NumberOverflow::Saturate)("300"), Ok(("", 255)));
+    assert_eq!(parse_i64("-99999999999999999999"), Ok(("", i64::MIN)));
+
+    let counter = counter_with(NumberOverflow::Error);
+    assert_eq!(counter("1.5e+09 pv"), Ok((" pv", 1_500_000_000)));
+    assert_eq!(counter("2.75E1"), Ok(("", 27)));
+    assert_eq!(counter("12.5"), Ok(("", 12)));
+    assert!(counter("1e400").is_err());
+    assert_eq!(counter_with(NumberOverflow::Saturate)("1e400"), Ok(("", Counter::MAX)));
+}
+
+#[test]
+fn test_parser_limits() {
+    let moves = "e2e4 ".repeat(MAX_MOVES);
+    assert_eq!(parse_movelist(&moves).map(|(_, m)| m.len()), Ok(MAX_MOVES));
+    let moves = moves + "e2e4";
+    assert_eq!(parse_movelist(&moves), Err(too_large("e2e4")));
+
+    let long = "x".repeat(MAX_TOKEN_LENGTH + 1);
+    assert_eq!(token(&long[1..]), Ok(("", &long[1..])));
+    assert_eq!(token(&long), Err(too_large(&long)));
+
+    let fen = format!("8/8/8/8/8/8/8/{} w - - 0 1", "1".repeat(MAX_FEN_LENGTH));
+    assert_eq!(parse_fen(&fen), Err(too_large("Invalid FEN")));
+}
+
+// A cheap stand-in for the fuzz targets: random bytes, and random splices of real commands,
+// must never make a parser panic.
+#[test]
+fn test_parsers_on_garbage() {
+    let pieces = [
+        "info", " depth", " 12", " score", " cp", " -35", " mate", " pv", " e2e4", " e7e8q", " a7",
+        " string", " wdl", " position", " fen", " startpos", " moves", " 8/8/8", " w", " KQkq",
+        " -", " 0", " 1", " bestmove", " ponder", " option", " name", " type", " spin", " go",
+        " \n", "\n", " \u{e9}", " 18446744073709551616", " lowerbound",
+    ];
+    let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+    for _ in 0..2000 {
+        let mut input = String::new();
+        for _ in 0..rng.below(24) {
+            if rng.below(4) == 0 {
+                input.push(rng.below(128) as u8 as char);
+            } else {
+                input.push_str(pieces[rng.below(pieces.len() as u64) as usize]);
+            }
+        }
+        let _ = Command::parse_many(&input);
+        let _ = parse_fen(&input);
+        let _ = parse_movelist(&input);
+    }
+}
@@ -0,0 +1,319 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Sender};
+use std::thread::spawn;
+
+use engine::engine_command::EngineCommand;
+use error::Error;
+use gui::gui_command::GuiCommand;
+use transcript::{Direction, Transcript};
+use transport::process::ProcessTransport;
+use transport::Transport;
+
+#[cfg(test)]
+use engine::id::Id;
+#[cfg(test)]
+use std::io::Cursor;
+#[cfg(test)]
+use std::net::TcpListener;
+#[cfg(test)]
+use transport::tcp::TcpTransport;
+
+type GuiRewrite = Box<dyn FnMut(GuiCommand) -> Vec<GuiCommand>>;
+type EngineRewrite = Box<dyn FnMut(EngineCommand) -> Option<EngineCommand>>;
+
+// A line from either side, or the news that it has closed.
+enum Event {
+    Gui(String),
+    GuiClosed,
+    Engine(String),
+    EngineClosed,
+}
+
+fn read_lines<R: BufRead + Send + 'static, F: Fn(String) -> Event + Send + 'static>(
+    reader: R,
+    event: F,
+    closed: Event,
+    tx: Sender<Event>,
+) {
+    spawn(move || {
+        for line in reader.lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(event(line)).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        let _ = tx.send(closed);
+    });
+}
+
+/// Sits between a GUI and an engine, passing on what each says to the other, and keeping a
+/// transcript of it all.  Commands on the way can be changed: `Threads` kept within limits,
+/// options the GUI doesn't know about set, or anything else a `rewrite_gui` or `rewrite_engine`
+/// does.
+///
+/// Lines that aren't commands are passed on as they are, and so are commands nothing changed,
+/// so an engine's quirks survive the trip.  The proxy stops once the engine closes its output;
+/// if the GUI goes first, the engine is told to `quit`.
+pub struct UciProxy {
+    transport: Box<dyn Transport>,
+    clamps: Vec<(String, i64, i64)>,
+    injected: Vec<(String, Option<String>)>,
+    gui_rewrite: Option<GuiRewrite>,
+    engine_rewrite: Option<EngineRewrite>,
+    log: Option<Box<dyn Write>>,
+}
+
+impl UciProxy {
+    /// Run the engine at `path` as a child process.
+    pub fn new(path: &str) -> UciProxy {
+        UciProxy::from_transport(ProcessTransport::new(path))
+    }
+
+    pub fn from_transport<T: Transport + 'static>(transport: T) -> UciProxy {
+        UciProxy {
+            transport: Box::new(transport),
+            clamps: vec![],
+            injected: vec![],
+            gui_rewrite: None,
+            engine_rewrite: None,
+            log: None,
+        }
+    }
+
+    /// Keep the spin option `name` between `min` and `max`, whatever the GUI sets it to.
+    pub fn clamp_option(mut self, name: &str, min: i64, max: i64) -> UciProxy {
+        self.clamps.push((name.to_string(), min, max));
+        self
+    }
+
+    /// Set the option `name` before the GUI's first `isready`, which it sends once it has set
+    /// its own options.
+    pub fn inject_option(mut self, name: &str, value: Option<&str>) -> UciProxy {
+        self.injected
+            .push((name.to_string(), value.map(|v| v.to_string())));
+        self
+    }
+
+    /// Send the engine what `rewrite` makes of each command from the GUI, after the clamps:
+    /// nothing, the command itself, or several commands.
+    pub fn rewrite_gui<F>(mut self, rewrite: F) -> UciProxy
+    where
+        F: FnMut(GuiCommand) -> Vec<GuiCommand> + 'static,
+    {
+        self.gui_rewrite = Some(Box::new(rewrite));
+        self
+    }
+
+    /// Send the GUI what `rewrite` makes of each command from the engine, or nothing for `None`.
+    pub fn rewrite_engine<F>(mut self, rewrite: F) -> UciProxy
+    where
+        F: FnMut(EngineCommand) -> Option<EngineCommand> + 'static,
+    {
+        self.engine_rewrite = Some(Box::new(rewrite));
+        self
+    }
+
+    /// Write each line to `log` as it passes, as a `Transcript` shows it.
+    pub fn log<W: Write + 'static>(mut self, log: W) -> UciProxy {
+        self.log = Some(Box::new(log));
+        self
+    }
+
+    /// Stand in for the engine on this process's stdin and stdout.
+    pub fn run_stdio(self) -> Result<Transcript, Error> {
+        self.run(BufReader::new(io::stdin()), io::stdout())
+    }
+
+    /// Pass commands between the GUI, reading from `gui_input` and writing to `gui_output`, and
+    /// the engine, until the engine closes.  Returns everything that was said, with lines from
+    /// the GUI as sent and the engine's as received.
+    pub fn run<R, W>(mut self, gui_input: R, mut gui_output: W) -> Result<Transcript, Error>
+    where
+        R: BufRead + Send + 'static,
+        W: Write,
+    {
+        let (reader, mut writer) = self.transport.open()?;
+        let (tx, rx) = channel();
+        read_lines(gui_input, Event::Gui, Event::GuiClosed, tx.clone());
+        read_lines(
+            BufReader::new(reader),
+            Event::Engine,
+            Event::EngineClosed,
+            tx,
+        );
+
+        let mut transcript = Transcript::new();
+        let mut ready = false;
+        let mut quit = false;
+        for event in rx {
+            let (direction, lines) = match event {
+                Event::Gui(line) => {
+                    let lines = self.relay_gui(&line, &mut ready);
+                    quit |= lines.iter().any(|line| line == "quit");
+                    (Direction::Sent, lines)
+                }
+                Event::GuiClosed if quit => continue,
+                Event::GuiClosed => {
+                    quit = true;
+                    (Direction::Sent, vec!["quit".to_string()])
+                }
+                Event::Engine(line) => (Direction::Received, self.relay_engine(&line)),
+                Event::EngineClosed => break,
+            };
+            for line in lines {
+                match direction {
+                    Direction::Sent => writeln!(writer, "{}", line)?,
+                    Direction::Received => writeln!(gui_output, "{}", line)?,
+                }
+                transcript.push(direction, &line);
+                if let (Some(log), Some(line)) = (self.log.as_mut(), transcript.get_lines().last())
+                {
+                    writeln!(log, "{}", line)?;
+                }
+            }
+            writer.flush()?;
+            gui_output.flush()?;
+        }
+        Ok(transcript)
+    }
+
+    // What to send the engine for `line` from the GUI.
+    fn relay_gui(&mut self, line: &str, ready: &mut bool) -> Vec<String> {
+        let command = match GuiCommand::from_str(line) {
+            Ok(command) => command,
+            Err(_) => return vec![line.trim_end().to_string()],
+        };
+        let mut lines = vec![];
+        if command == GuiCommand::IsReady && !*ready {
+            *ready = true;
+            for (name, value) in &self.injected {
+                let command = GuiCommand::SetOption(name.clone(), value.clone());
+                lines.push(command.to_string().trim_end().to_string());
+            }
+        }
+        let clamped = self.clamp(command.clone());
+        let commands = match self.gui_rewrite {
+            Some(ref mut rewrite) => rewrite(clamped),
+            None => vec![clamped],
+        };
+        for c in commands {
+            if c == command {
+                lines.push(line.trim_end().to_string());
+            } else {
+                lines.push(c.to_string().trim_end().to_string());
+            }
+        }
+        lines
+    }
+
+    fn clamp(&self, command: GuiCommand) -> GuiCommand {
+        if let GuiCommand::SetOption(ref name, Some(ref value)) = command {
+            let clamp = self
+                .clamps
+                .iter()
+                .find(|(n, _, _)| n.eq_ignore_ascii_case(name));
+            if let (Some((_, min, max)), Ok(x)) = (clamp, value.trim().parse::<i64>()) {
+                return GuiCommand::SetOption(name.clone(), Some(x.clamp(*min, *max).to_string()));
+            }
+        }
+        command
+    }
+
+    // What to send the GUI for `line` from the engine.
+    fn relay_engine(&mut self, line: &str) -> Vec<String> {
+        let rewrite = match self.engine_rewrite {
+            Some(ref mut rewrite) => rewrite,
+            None => return vec![line.trim_end().to_string()],
+        };
+        match EngineCommand::from_str(line) {
+            Ok(command) => match rewrite(command.clone()) {
+                Some(c) if c == command => vec![line.trim_end().to_string()],
+                Some(c) => vec![c.to_string().trim_end().to_string()],
+                None => vec![],
+            },
+            Err(_) => vec![line.trim_end().to_string()],
+        }
+    }
+}
+
+// Echoes each `setoption` it is sent as an `info string`.
+#[cfg(test)]
+fn echo_engine() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut output = stream.try_clone().unwrap();
+        for line in BufReader::new(stream).lines() {
+            let line = line.unwrap();
+            let reply = match line.trim() {
+                "uci" => "id name Echo\nid author Nobody\nuciok".to_string(),
+                "isready" => "readyok".to_string(),
+                "quit" => break,
+                line => format!("info string got {}", line),
+            };
+            writeln!(output, "{}", reply).unwrap();
+        }
+    });
+    address
+}
+
+#[test]
+fn test_uci_proxy() {
+    let proxy = UciProxy::from_transport(TcpTransport::new(&echo_engine()))
+        .clamp_option("Threads", 1, 8)
+        .inject_option("Hash", Some("32"))
+        .rewrite_gui(|command| match command {
+            GuiCommand::UciNewGame => vec![],
+            command => vec![command],
+        })
+        .rewrite_engine(|command| match command {
+            EngineCommand::Id(ref id) if *id == Id::name("Echo") => {
+                Some(EngineCommand::Id(Id::name("Echo (proxied)")))
+            }
+            command => Some(command),
+        });
+    let mut output = vec![];
+    let transcript = proxy
+        .run(
+            Cursor::new(
+                "uci\nsetoption name threads value 64\nucinewgame\nisready\nflip\nquit\n"
+                    .to_string(),
+            ),
+            &mut output,
+        )
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "id name Echo (proxied)\n\
+         id author Nobody\n\
+         uciok\n\
+         info string got setoption name threads value 8\n\
+         info string got setoption name Hash value 32\n\
+         readyok\n\
+         info string got flip\n"
+    );
+    let sent = transcript
+        .get_lines()
+        .iter()
+        .filter(|l| l.get_direction() == Direction::Sent)
+        .map(|l| l.get_line())
+        .collect::<Vec<&str>>();
+    assert_eq!(
+        sent,
+        vec![
+            "uci",
+            "setoption name threads value 8",
+            "setoption name Hash value 32",
+            "isready",
+            "flip",
+            "quit"
+        ]
+    );
+}
@@ -0,0 +1,225 @@
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use error::Error;
+
+#[cfg(test)]
+use std::process;
+
+/// What an engine's process was using when it was sampled: the CPU time it has had, in user and
+/// system mode, and its resident memory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ResourceUsage {
+    cpu_time: Duration,
+    rss: u64,
+    sampled_at: Instant,
+}
+
+impl ResourceUsage {
+    pub fn new(cpu_time: Duration, rss: u64, sampled_at: Instant) -> ResourceUsage {
+        ResourceUsage {
+            cpu_time,
+            rss,
+            sampled_at,
+        }
+    }
+
+    /// The CPU time used since the process started, over all its threads.
+    pub fn get_cpu_time(&self) -> Duration {
+        self.cpu_time
+    }
+
+    /// The resident set size, in bytes.
+    pub fn get_rss(&self) -> u64 {
+        self.rss
+    }
+
+    pub fn get_sampled_at(&self) -> Instant {
+        self.sampled_at
+    }
+
+    /// How many cores' worth of CPU time was used between `earlier` and this sample.  An engine
+    /// that should be idle but keeps using a core has run away.
+    pub fn cpu_load_since(&self, earlier: &ResourceUsage) -> f64 {
+        let wall = self
+            .sampled_at
+            .saturating_duration_since(earlier.sampled_at);
+        if wall == Duration::new(0, 0) {
+            return 0.0;
+        }
+        let cpu = self.cpu_time.saturating_sub(earlier.cpu_time);
+        cpu.as_secs_f64() / wall.as_secs_f64()
+    }
+}
+
+fn unsupported(message: &str) -> Error {
+    Error::from(IoError::new(IoErrorKind::Unsupported, message))
+}
+
+/// Sample the process `pid`.  Only Linux is supported, with the `resource-usage` feature.
+#[cfg(all(feature = "resource-usage", target_os = "linux"))]
+pub(crate) fn sample(pid: u32) -> Result<ResourceUsage, Error> {
+    use std::fs::read_to_string;
+    use std::os::raw::{c_int, c_long};
+
+    const SC_CLK_TCK: c_int = 2;
+    const SC_PAGESIZE: c_int = 30;
+
+    extern "C" {
+        fn sysconf(name: c_int) -> c_long;
+    }
+
+    let sampled_at = Instant::now();
+    let invalid = || {
+        Error::from(IoError::new(
+            IoErrorKind::InvalidData,
+            "unreadable /proc entry",
+        ))
+    };
+    // The command name, in brackets, can hold spaces, so the fields are counted from its end:
+    // the state is the third field, and the user and system time the fourteenth and fifteenth.
+    let stat = read_to_string(format!("/proc/{}/stat", pid))?;
+    let fields = stat
+        .rsplit_once(')')
+        .ok_or_else(invalid)?
+        .1
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+    let ticks = |index: usize| -> Result<u64, Error> {
+        fields
+            .get(index)
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let cpu_ticks = ticks(11)? + ticks(12)?;
+    // Resident pages are the second field.
+    let statm = read_to_string(format!("/proc/{}/statm", pid))?;
+    let pages: u64 = statm
+        .split_whitespace()
+        .nth(1)
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(invalid)?;
+
+    let (ticks_per_second, page_size) = unsafe { (sysconf(SC_CLK_TCK), sysconf(SC_PAGESIZE)) };
+    if ticks_per_second <= 0 || page_size <= 0 {
+        return Err(invalid());
+    }
+    Ok(ResourceUsage::new(
+        Duration::from_millis(cpu_ticks * 1000 / ticks_per_second as u64),
+        pages * page_size as u64,
+        sampled_at,
+    ))
+}
+
+#[cfg(not(all(feature = "resource-usage", target_os = "linux")))]
+pub(crate) fn sample(_pid: u32) -> Result<ResourceUsage, Error> {
+    Err(unsupported(
+        "resource usage needs Linux and the resource-usage feature",
+    ))
+}
+
+/// There is no process to sample, as the engine wasn't started by the connection.
+pub(crate) fn not_a_process() -> Error {
+    unsupported("the engine isn't a child process")
+}
+
+#[derive(Default)]
+struct MonitorState {
+    latest: Option<ResourceUsage>,
+    peak_rss: u64,
+    error: Option<Error>,
+}
+
+/// Samples an engine's process on a thread of its own, every `interval`, until it is dropped or
+/// a sample fails, such as once the engine has exited.  See `EngineConnection::monitor_resources`.
+pub struct ResourceMonitor {
+    state: Arc<Mutex<MonitorState>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ResourceMonitor {
+    pub(crate) fn start(pid: u32, interval: Duration) -> ResourceMonitor {
+        let state = Arc::new(Mutex::new(MonitorState::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (thread_state, thread_stop) = (state.clone(), stop.clone());
+        spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                let sampled = sample(pid);
+                let mut state = thread_state.lock().unwrap();
+                match sampled {
+                    Ok(usage) => {
+                        state.peak_rss = state.peak_rss.max(usage.get_rss());
+                        state.latest = Some(usage);
+                    }
+                    Err(e) => {
+                        state.error = Some(e);
+                        return;
+                    }
+                }
+                drop(state);
+                sleep(interval);
+            }
+        });
+        ResourceMonitor { state, stop }
+    }
+
+    /// The last sample taken, if there has been one.
+    pub fn get_latest(&self) -> Option<ResourceUsage> {
+        self.state.lock().unwrap().latest
+    }
+
+    /// The most resident memory seen in any sample, in bytes, to hold against a memory cap.
+    pub fn get_peak_rss(&self) -> u64 {
+        self.state.lock().unwrap().peak_rss
+    }
+
+    /// Why sampling stopped, if it did.
+    pub fn get_error(&self) -> Option<Error> {
+        self.state.lock().unwrap().error.clone()
+    }
+}
+
+impl Drop for ResourceMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_cpu_load() {
+    let start = Instant::now();
+    let earlier = ResourceUsage::new(Duration::from_millis(100), 0, start);
+    let later = ResourceUsage::new(
+        Duration::from_millis(300),
+        0,
+        start + Duration::from_millis(100),
+    );
+    assert!((later.cpu_load_since(&earlier) - 2.0).abs() < 1e-9);
+    assert_eq!(earlier.cpu_load_since(&earlier), 0.0);
+}
+
+#[cfg(all(feature = "resource-usage", target_os = "linux"))]
+#[test]
+fn test_sample_this_process() {
+    let usage = sample(process::id()).unwrap();
+    assert!(usage.get_rss() > 0);
+
+    let monitor = ResourceMonitor::start(process::id(), Duration::from_millis(1));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while monitor.get_latest().is_none() && Instant::now() < deadline {
+        sleep(Duration::from_millis(1));
+    }
+    assert!(monitor.get_latest().is_some());
+    assert!(monitor.get_peak_rss() > 0);
+    assert_eq!(monitor.get_error(), None);
+}
+
+#[cfg(not(all(feature = "resource-usage", target_os = "linux")))]
+#[test]
+fn test_sample_unsupported() {
+    let e = sample(process::id()).unwrap_err();
+    assert_eq!(e.io_error_kind(), Some(IoErrorKind::Unsupported));
+}
@@ -0,0 +1,64 @@
+// The crate's one random number generator, for everything that has to be random but the same
+// every time for the same seed: blunders, shuffled openings, SPSA directions, self-play
+// openings and test inputs.  None of it needs to be strong, just fast and repeatable.
+
+/// A xorshift generator.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Any seed will do; a xorshift generator can't start from zero, so the low bit is set.
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed | 1 }
+    }
+
+    /// Where the generator is, so it can be saved and carried on with `Rng::new`.
+    pub fn get_state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// A number from 0 up to but not including `n`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// One step of xorshift64*: the next state, and the number it gives.  Zobrist keys are taken
+/// from this at compile time, so it must never change.
+pub(crate) const fn xorshift_star(state: u64) -> (u64, u64) {
+    let mut state = state;
+    state ^= state >> 12;
+    state ^= state << 25;
+    state ^= state >> 27;
+    (state, state.wrapping_mul(0x2545_f491_4f6c_dd1d))
+}
+
+#[test]
+fn test_rng_repeats() {
+    let mut a = Rng::new(42);
+    let mut b = Rng::new(43);
+    assert_eq!(a, b);
+    for _ in 0..100 {
+        assert_eq!(a.next(), b.next());
+    }
+    let state = a.get_state();
+    assert_eq!(Rng::new(state).next(), a.next());
+    assert!((0..1000).all(|_| a.below(7) < 7));
+}
+
+#[test]
+fn test_xorshift_star() {
+    let (state, first) = xorshift_star(0x9e37_79b9_7f4a_7c15);
+    let (_, second) = xorshift_star(state);
+    assert_ne!(first, second);
+    assert_eq!(xorshift_star(state), xorshift_star(state));
+}
@@ -0,0 +1,314 @@
+// Property-style round-trip tests: generate random protocol values, print them, parse them back,
+// and make sure nothing was lost.  The generator is seeded, so any failure is reproducible from
+// the seed printed in the assertion message.
+
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+use chess::{Board, ChessMove, MoveGen, Piece, ALL_SQUARES};
+
+use engine::engine_option::EngineOption;
+use engine::info::Info;
+use engine::option_type::OptionType;
+use engine::score::{Score, ScoreBound, ScoreKind};
+use error::Error;
+use gui::go::Go;
+use gui::gui_command::GuiCommand;
+use gui::position::{MoveCounters, PositionSpec};
+use parsers::Counter;
+use rng::Rng as SeededRng;
+
+const ITERATIONS: u64 = 500;
+
+// The crate's generator, with helpers for making protocol values.
+pub struct Rng {
+    rng: SeededRng,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            rng: SeededRng::new(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+        }
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.rng.next()
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    pub fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+
+    pub fn maybe<T, F: FnMut(&mut Rng) -> T>(&mut self, mut f: F) -> Option<T> {
+        if self.chance(50) {
+            Some(f(self))
+        } else {
+            None
+        }
+    }
+
+    pub fn integer(&mut self) -> u64 {
+        match self.below(3) {
+            0 => self.below(10),
+            1 => self.below(100_000),
+            _ => self.next(),
+        }
+    }
+
+    pub fn signed(&mut self) -> i64 {
+        (self.below(20_001) as i64) - 10_000
+    }
+
+    pub fn chess_move(&mut self) -> ChessMove {
+        let source = ALL_SQUARES[self.below(64) as usize];
+        let dest = ALL_SQUARES[self.below(64) as usize];
+        let promotion = match self.below(8) {
+            0 => Some(Piece::Knight),
+            1 => Some(Piece::Bishop),
+            2 => Some(Piece::Rook),
+            3 => Some(Piece::Queen),
+            _ => None,
+        };
+        ChessMove::new(source, dest, promotion)
+    }
+
+    pub fn moves(&mut self, min: u64, max: u64) -> Vec<ChessMove> {
+        let count = min + self.below(max - min + 1);
+        (0..count).map(|_| self.chess_move()).collect()
+    }
+
+    // A word that can't be confused with a protocol keyword.
+    pub fn word(&mut self) -> String {
+        const WORDS: [&str; 8] = [
+            "Hash", "Threads", "Skill", "Level", "Move-Overhead", "UCI_Elo", "x1", "Contempt",
+        ];
+        WORDS[self.below(WORDS.len() as u64) as usize].to_string()
+    }
+
+    pub fn words(&mut self, min: u64, max: u64) -> String {
+        let count = min + self.below(max - min + 1);
+        (0..count)
+            .map(|_| self.word())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+pub fn random_go(rng: &mut Rng) -> Go {
+    let mut go = Go::default();
+    if rng.chance(30) {
+        go = go.combine(&Go::search_moves(rng.moves(1, 5)));
+    }
+    if let Some(m) = rng.maybe(|r| r.chess_move()) {
+        go = go.combine(&Go::ponder(m));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::wtime(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::btime(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::winc(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::binc(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::movestogo(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::depth(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::nodes(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::mate(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        go = go.combine(&Go::movetime(x));
+    }
+    if rng.chance(20) {
+        go = go.combine(&Go::infinite(true));
+    }
+    go
+}
+
+pub fn random_score(rng: &mut Rng) -> Score {
+    let kind = if rng.chance(50) {
+        ScoreKind::Cp
+    } else {
+        ScoreKind::Mate
+    };
+    let bound = match rng.below(3) {
+        0 => ScoreBound::Exact,
+        1 => ScoreBound::Lower,
+        _ => ScoreBound::Upper,
+    };
+    Score::new(kind, rng.signed(), bound)
+}
+
+pub fn random_info(rng: &mut Rng) -> Info {
+    // An `info` line with nothing in it isn't a command, so always send the depth.
+    let mut info = Info::depth(rng.integer());
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        info = info.combine(&Info::seldepth(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        info = info.combine(&Info::time(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        info = info.combine(&Info::nodes(x as Counter));
+    }
+    if rng.chance(50) {
+        info = info.combine(&Info::pv(rng.moves(1, 20)));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        info = info.combine(&Info::multi_pv(x));
+    }
+    if let Some(x) = rng.maybe(random_score) {
+        info = info.combine(&Info::score(x));
+    }
+    if let Some(x) = rng.maybe(|r| (r.below(1001), r.below(1001), r.below(1001))) {
+        info = info.combine(&Info::wdl(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.chess_move()) {
+        info = info.combine(&Info::cur_move(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        info = info.combine(&Info::cur_move_number(x));
+    }
+    if let Some(x) = rng.maybe(|r| r.below(1001)) {
+        info = info.combine(&Info::hash_full(x as f32));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        info = info.combine(&Info::nps(x as Counter));
+    }
+    if let Some(x) = rng.maybe(|r| r.integer()) {
+        info = info.combine(&Info::tb_hits(x as Counter));
+    }
+    if let Some(x) = rng.maybe(|r| r.below(1001)) {
+        info = info.combine(&Info::cpu_load(x as f32));
+    }
+    if rng.chance(20) {
+        info = info.combine(&Info::refutation(rng.moves(1, 4)));
+    }
+    if rng.chance(20) {
+        info = info.combine(&Info::cur_line(rng.moves(1, 4)));
+    }
+    if rng.chance(20) {
+        info = info.combine(&Info::engine_string(rng.words(1, 6)));
+    }
+    info
+}
+
+pub fn random_option_type(rng: &mut Rng) -> OptionType {
+    match rng.below(5) {
+        0 => OptionType::Check(rng.chance(50)),
+        1 => OptionType::Spin(rng.signed(), rng.signed(), rng.signed()),
+        2 => OptionType::Combo(rng.word(), (0..1 + rng.below(4)).map(|_| rng.word()).collect()),
+        3 => OptionType::Button,
+        _ => OptionType::Str(rng.words(0, 3)),
+    }
+}
+
+pub fn random_engine_option(rng: &mut Rng) -> EngineOption {
+    EngineOption::new(rng.words(1, 3), random_option_type(rng))
+}
+
+// Play random legal moves from the starting position, so every board is reachable and valid.
+pub fn random_board(rng: &mut Rng) -> Board {
+    let mut board = Board::default();
+    for _ in 0..rng.below(40) {
+        let moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
+        if moves.is_empty() {
+            break;
+        }
+        board = board.make_move_new(moves[rng.below(moves.len() as u64) as usize]);
+    }
+    board
+}
+
+pub fn random_position(rng: &mut Rng) -> GuiCommand {
+    let board = random_board(rng);
+    let moves = rng.moves(0, 10);
+    if rng.chance(50) {
+        // `Board` writes its FEN with the counters of a new game; give it some others.
+        let fen = board.to_string();
+        let fields = fen.split(' ').take(4).collect::<Vec<&str>>().join(" ");
+        let counters = MoveCounters::new(rng.below(100), 1 + rng.below(300));
+        let fen = format!("{} {}", fields, counters);
+        GuiCommand::Position(PositionSpec::from_fen(&fen, moves).unwrap())
+    } else {
+        GuiCommand::position(board, moves)
+    }
+}
+
+pub fn random_setoption(rng: &mut Rng) -> GuiCommand {
+    GuiCommand::SetOption(rng.words(1, 3), rng.maybe(|r| r.words(1, 3)))
+}
+
+pub fn check_roundtrip<T, F>(seed: u64, generate: F)
+where
+    T: Display + FromStr<Err = Error> + PartialEq + Debug,
+    F: Fn(&mut Rng) -> T,
+{
+    let mut rng = Rng::new(seed);
+    for iteration in 0..ITERATIONS {
+        let value = generate(&mut rng);
+        let text = value.to_string();
+        assert_eq!(
+            T::from_str(&text),
+            Ok(value),
+            "seed {}, iteration {}: {:?}",
+            seed,
+            iteration,
+            text
+        );
+    }
+}
+
+#[test]
+fn test_roundtrip_go() {
+    check_roundtrip(1, |rng| GuiCommand::Go(random_go(rng)));
+}
+
+#[test]
+fn test_roundtrip_info() {
+    check_roundtrip(2, random_info);
+}
+
+#[test]
+fn test_roundtrip_score() {
+    check_roundtrip(3, random_score);
+}
+
+#[test]
+fn test_roundtrip_engine_option() {
+    check_roundtrip(4, random_engine_option);
+}
+
+#[test]
+fn test_roundtrip_setoption() {
+    check_roundtrip(5, random_setoption);
+}
+
+#[test]
+fn test_roundtrip_position() {
+    check_roundtrip(6, random_position);
+}
+
+#[test]
+fn test_rng_is_deterministic() {
+    let mut a = Rng::new(42);
+    let mut b = Rng::new(42);
+    for _ in 0..100 {
+        assert_eq!(a.next(), b.next());
+    }
+}
@@ -0,0 +1,252 @@
+// Standard Algebraic Notation, as used in PGN: "Nf3", "exd5", "O-O", "e8=Q+", and so on.
+
+use chess::{Board, BoardStatus, ChessMove, File, MoveGen, Piece, Rank, Square};
+
+use error::Error;
+
+#[cfg(test)]
+use std::str::FromStr;
+
+fn piece_letter(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "",
+        Piece::Knight => "N",
+        Piece::Bishop => "B",
+        Piece::Rook => "R",
+        Piece::Queen => "Q",
+        Piece::King => "K",
+    }
+}
+
+fn letter_piece(letter: char) -> Option<Piece> {
+    match letter {
+        'N' => Some(Piece::Knight),
+        'B' => Some(Piece::Bishop),
+        'R' => Some(Piece::Rook),
+        'Q' => Some(Piece::Queen),
+        'K' => Some(Piece::King),
+        _ => None,
+    }
+}
+
+fn file_char(file: File) -> char {
+    (b'a' + file.to_index() as u8) as char
+}
+
+fn rank_char(rank: Rank) -> char {
+    (b'1' + rank.to_index() as u8) as char
+}
+
+fn illegal(board: &Board, index: usize, m: ChessMove) -> Error {
+    Error::IllegalMove {
+        index,
+        chess_move: m,
+        fen: board.to_string(),
+    }
+}
+
+/// Write `m` in SAN.  The move has to be legal on `board`.
+pub fn to_san(board: &Board, m: ChessMove) -> Result<String, Error> {
+    if !board.legal(m) {
+        return Err(illegal(board, 0, m));
+    }
+
+    let source = m.get_source();
+    let dest = m.get_dest();
+    let piece = board.piece_on(source).unwrap();
+    let file_distance = (source.get_file().to_index() as i64 - dest.get_file().to_index() as i64).abs();
+
+    let mut san = String::new();
+    if piece == Piece::King && file_distance == 2 {
+        san.push_str(if dest.get_file() == File::G { "O-O" } else { "O-O-O" });
+    } else {
+        // A pawn that changes file is capturing, even when the square it lands on is empty.
+        let capture = board.piece_on(dest).is_some() || (piece == Piece::Pawn && file_distance != 0);
+
+        san.push_str(piece_letter(piece));
+        if piece == Piece::Pawn {
+            if capture {
+                san.push(file_char(source.get_file()));
+            }
+        } else {
+            let others = MoveGen::new_legal(board)
+                .filter(|o| {
+                    o.get_dest() == dest
+                        && o.get_source() != source
+                        && board.piece_on(o.get_source()) == Some(piece)
+                })
+                .collect::<Vec<ChessMove>>();
+            if !others.is_empty() {
+                if others.iter().all(|o| o.get_source().get_file() != source.get_file()) {
+                    san.push(file_char(source.get_file()));
+                } else if others.iter().all(|o| o.get_source().get_rank() != source.get_rank()) {
+                    san.push(rank_char(source.get_rank()));
+                } else {
+                    san.push_str(&source.to_string());
+                }
+            }
+        }
+
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&dest.to_string());
+        if let Some(promotion) = m.get_promotion() {
+            san.push('=');
+            san.push_str(piece_letter(promotion));
+        }
+    }
+
+    let after = board.make_move_new(m);
+    if after.status() == BoardStatus::Checkmate {
+        san.push('#');
+    } else if after.checkers().popcnt() != 0 {
+        san.push('+');
+    }
+    Ok(san)
+}
+
+/// Read a move in SAN.  Check and annotation marks are optional, captures don't need the `x`,
+/// and more disambiguation than necessary is accepted.
+pub fn from_san(board: &Board, san: &str) -> Result<ChessMove, Error> {
+    let invalid = || Error::InvalidSan {
+        san: san.to_string(),
+        fen: board.to_string(),
+    };
+
+    let text = san
+        .trim()
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace('0', "O");
+    let mut candidates = MoveGen::new_legal(board).collect::<Vec<ChessMove>>();
+
+    if text == "O-O" || text == "O-O-O" {
+        let file = if text == "O-O" { File::G } else { File::C };
+        candidates.retain(|m| {
+            board.piece_on(m.get_source()) == Some(Piece::King)
+                && m.get_source().get_file() == File::E
+                && m.get_dest().get_file() == file
+        });
+    } else {
+        let mut chars = text.chars().filter(|c| *c != 'x' && *c != '=').collect::<Vec<char>>();
+
+        let piece = match chars.first().and_then(|c| letter_piece(*c)) {
+            Some(p) => {
+                chars.remove(0);
+                p
+            }
+            None => Piece::Pawn,
+        };
+        let promotion = match chars.last().and_then(|c| letter_piece(*c)) {
+            Some(p) if piece == Piece::Pawn => {
+                chars.pop();
+                Some(p)
+            }
+            _ => None,
+        };
+        if chars.len() < 2 {
+            return Err(invalid());
+        }
+        let dest_text = chars.split_off(chars.len() - 2).into_iter().collect::<String>();
+        let dest = Square::from_string(dest_text).ok_or_else(invalid)?;
+
+        let mut source_file = None;
+        let mut source_rank = None;
+        for c in chars.iter() {
+            match c {
+                'a'..='h' => source_file = Some(File::from_index((*c as u8 - b'a') as usize)),
+                '1'..='8' => source_rank = Some(Rank::from_index((*c as u8 - b'1') as usize)),
+                _ => return Err(invalid()),
+            }
+        }
+
+        candidates.retain(|m| {
+            m.get_dest() == dest
+                && m.get_promotion() == promotion
+                && board.piece_on(m.get_source()) == Some(piece)
+                && source_file.is_none_or(|f| m.get_source().get_file() == f)
+                && source_rank.is_none_or(|r| m.get_source().get_rank() == r)
+        });
+    }
+
+    if candidates.len() == 1 {
+        Ok(candidates[0])
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Write a line of moves played one after another from `board` in SAN.
+pub fn line_to_san(board: &Board, moves: &[ChessMove]) -> Result<Vec<String>, Error> {
+    let mut current = *board;
+    let mut result = Vec::with_capacity(moves.len());
+    for (index, m) in moves.iter().enumerate() {
+        let san = to_san(&current, *m).map_err(|_| illegal(&current, index, *m))?;
+        result.push(san);
+        current = current.make_move_new(*m);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+fn mv(s: &str) -> ChessMove {
+    let source = Square::from_string(s[0..2].to_string()).unwrap();
+    let dest = Square::from_string(s[2..4].to_string()).unwrap();
+    ChessMove::new(source, dest, s.chars().nth(4).and_then(|c| letter_piece(c.to_ascii_uppercase())))
+}
+
+#[cfg(test)]
+fn check_san(fen: &str, m: &str, san: &str) {
+    let board = Board::from_str(fen).unwrap();
+    assert_eq!(to_san(&board, mv(m)), Ok(san.to_string()));
+    assert_eq!(from_san(&board, san), Ok(mv(m)));
+}
+
+#[test]
+fn test_san_simple_moves() {
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    check_san(start, "e2e4", "e4");
+    check_san(start, "g1f3", "Nf3");
+}
+
+#[test]
+fn test_san_captures_and_en_passant() {
+    check_san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1", "e4d5", "exd5");
+    check_san("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 1", "e5f6", "exf6");
+}
+
+#[test]
+fn test_san_castling_and_mate() {
+    check_san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1", "O-O");
+    check_san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1", "O-O-O");
+    check_san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8", "Ra8#");
+}
+
+#[test]
+fn test_san_disambiguation_and_promotion() {
+    check_san("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1d2", "Nbd2");
+    check_san("4k3/8/8/8/R7/8/8/R3K3 w - - 0 1", "a1a2", "R1a2");
+    check_san("8/4P3/8/8/8/8/8/k3K3 w - - 0 1", "e7e8q", "e8=Q");
+}
+
+#[test]
+fn test_from_san_lenient() {
+    let board = Board::default();
+    assert_eq!(from_san(&board, "Ng1f3"), Ok(mv("g1f3")));
+    assert_eq!(from_san(&board, "e4!?"), Ok(mv("e2e4")));
+    assert!(from_san(&board, "Nd4").is_err());
+    assert!(from_san(&board, "x").is_err());
+}
+
+#[test]
+fn test_line_to_san() {
+    let board = Board::default();
+    assert_eq!(
+        line_to_san(&board, &[mv("e2e4"), mv("e7e5"), mv("d1h5")]),
+        Ok(vec!["e4".to_string(), "e5".to_string(), "Qh5".to_string()])
+    );
+    assert!(matches!(
+        line_to_san(&board, &[mv("e2e4"), mv("e2e4")]),
+        Err(Error::IllegalMove { index: 1, .. })
+    ));
+}
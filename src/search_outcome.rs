@@ -0,0 +1,85 @@
+use chess::ChessMove;
+
+use engine::best_move::BestMove;
+use engine::info::Info;
+use engine::score::Score;
+
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use std::str::FromStr;
+
+/// How a search from `EngineConnection::recv_search_result` ended: the best move, and everything
+/// the engine said on the way to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchOutcome {
+    best_move: Option<ChessMove>,
+    ponder: Option<ChessMove>,
+    final_info: Option<Info>,
+    all_infos: Vec<Info>,
+}
+
+impl SearchOutcome {
+    /// The outcome of a search that sent `infos` and then `best_move`.
+    pub fn new(best_move: BestMove, infos: Vec<Info>) -> SearchOutcome {
+        // The engine's last word on the main line, rather than a `currmove` or `string` after it.
+        let final_info = infos
+            .iter()
+            .rev()
+            .find(|info| info.get_score().is_some() && info.get_multi_pv().unwrap_or(1) == 1)
+            .cloned();
+        SearchOutcome {
+            best_move: best_move.get_move(),
+            ponder: best_move.get_ponder(),
+            final_info,
+            all_infos: infos,
+        }
+    }
+
+    /// `None` if the engine sent the null move.
+    pub fn get_best_move(&self) -> Option<ChessMove> {
+        self.best_move
+    }
+
+    pub fn get_ponder(&self) -> Option<ChessMove> {
+        self.ponder
+    }
+
+    /// The last `info` with a score for the main line, which holds the engine's final evaluation.
+    pub fn get_final_info(&self) -> Option<&Info> {
+        self.final_info.as_ref()
+    }
+
+    /// The score of the final `info`.
+    pub fn get_score(&self) -> Option<Score> {
+        self.final_info.as_ref().and_then(|info| info.get_score())
+    }
+
+    /// Every `info` the search sent, in order.
+    pub fn get_all_infos(&self) -> &[Info] {
+        &self.all_infos
+    }
+}
+
+#[test]
+fn test_search_outcome_final_info() {
+    let infos = vec![
+        Info::from_str("info depth 1 score cp 20 pv e2e4").unwrap(),
+        Info::from_str("info depth 2 multipv 1 score cp 35 pv d2d4").unwrap(),
+        Info::from_str("info depth 2 multipv 2 score cp 10 pv e2e4").unwrap(),
+        Info::from_str("info currmove g1f3 currmovenumber 3").unwrap(),
+    ];
+    let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+    let d7d5 = ChessMove::new(Square::D7, Square::D5, None);
+    let outcome = SearchOutcome::new(BestMove::new_with_ponder(d2d4, d7d5), infos.clone());
+
+    assert_eq!(outcome.get_best_move(), Some(d2d4));
+    assert_eq!(outcome.get_ponder(), Some(d7d5));
+    assert_eq!(outcome.get_final_info(), Some(&infos[1]));
+    assert_eq!(outcome.get_score(), infos[1].get_score());
+    assert_eq!(outcome.get_all_infos(), &infos[..]);
+
+    let outcome = SearchOutcome::new(BestMove::new(d2d4), vec![]);
+    assert_eq!(outcome.get_final_info(), None);
+    assert_eq!(outcome.get_ponder(), None);
+}
@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+use chess::Color;
+
+use gui::go::Go;
+
+/// How long an engine has to answer `stop` before it is killed.
+pub const DEFAULT_STOP_GRACE: Duration = Duration::from_secs(1);
+/// How far past its time an engine may go however little time it was given, for the time it
+/// takes to send and read the best move.
+pub const DEFAULT_SLACK: Duration = Duration::from_millis(100);
+/// How many of the last lines to and from an engine are kept when it is killed.
+pub const DEFAULT_TAIL_LINES: usize = 20;
+
+/// When to give up on an engine that doesn't stop searching: once it has taken `factor` times
+/// the time its `go` allotted it, plus some slack, it is sent `stop`, and if it still hasn't
+/// answered after the stop grace it is killed.  The search then fails with
+/// `Error::EngineUnresponsive`, so a tournament moves on rather than hanging.
+///
+/// The time allotted is the `movetime`, or else all the time left on the mover's clock and its
+/// increment.  Searches limited only by depth or nodes, or `go infinite`, aren't supervised.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Supervision {
+    factor: f64,
+    slack: Duration,
+    stop_grace: Duration,
+    tail_lines: usize,
+}
+
+impl Supervision {
+    pub fn new(factor: f64) -> Supervision {
+        Supervision {
+            factor,
+            slack: DEFAULT_SLACK,
+            stop_grace: DEFAULT_STOP_GRACE,
+            tail_lines: DEFAULT_TAIL_LINES,
+        }
+    }
+
+    pub fn slack(mut self, slack: Duration) -> Supervision {
+        self.slack = slack;
+        self
+    }
+
+    pub fn stop_grace(mut self, stop_grace: Duration) -> Supervision {
+        self.stop_grace = stop_grace;
+        self
+    }
+
+    pub fn tail_lines(mut self, tail_lines: usize) -> Supervision {
+        self.tail_lines = tail_lines;
+        self
+    }
+
+    pub fn get_factor(&self) -> f64 {
+        self.factor
+    }
+
+    pub fn get_slack(&self) -> Duration {
+        self.slack
+    }
+
+    pub fn get_stop_grace(&self) -> Duration {
+        self.stop_grace
+    }
+
+    pub fn get_tail_lines(&self) -> usize {
+        self.tail_lines
+    }
+
+    /// The time `go` gives `player` for the move, if it sets one.
+    pub fn allotted(go: &Go, player: Color) -> Option<Duration> {
+        if go.get_infinite() {
+            return None;
+        }
+        if let Some(movetime) = go.get_movetime() {
+            return Some(Duration::from_millis(movetime));
+        }
+        let (time, increment) = match player {
+            Color::White => (go.get_wtime()?, go.get_winc()),
+            Color::Black => (go.get_btime()?, go.get_binc()),
+        };
+        Some(Duration::from_millis(time + increment.unwrap_or(0)))
+    }
+
+    /// When a search `player` started at `start` with `go` is to be stopped, if it is supervised.
+    pub fn deadline(&self, go: &Go, player: Color, start: Instant) -> Option<Instant> {
+        let allotted = Supervision::allotted(go, player)?;
+        Some(start + allotted.mul_f64(self.factor) + self.slack)
+    }
+}
+
+#[test]
+fn test_supervision_deadline() {
+    let start = Instant::now();
+    let supervision = Supervision::new(2.0).slack(Duration::from_millis(50));
+    assert_eq!(
+        supervision.deadline(&Go::movetime(100), Color::White, start),
+        Some(start + Duration::from_millis(250))
+    );
+
+    let clocks = Go::wtime(1000)
+        .combine(&Go::btime(400))
+        .combine(&Go::binc(100));
+    assert_eq!(
+        Supervision::allotted(&clocks, Color::Black),
+        Some(Duration::from_millis(500))
+    );
+    assert_eq!(Supervision::allotted(&Go::depth(5), Color::White), None);
+    assert_eq!(
+        Supervision::allotted(&Go::infinite(true), Color::White),
+        None
+    );
+    assert_eq!(
+        supervision.deadline(&Go::infinite(true), Color::White, start),
+        None
+    );
+}
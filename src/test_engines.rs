@@ -0,0 +1,94 @@
+// Pretend engines on local ports, for testing what talks to engines without starting a real one.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{spawn, JoinHandle};
+
+use chess::{Board, MoveGen};
+
+use engine_connection::{EngineConnection, EngineConnectionBuilder};
+use gui::gui_command::GuiCommand;
+use transport::tcp::TcpTransport;
+
+// A pretend engine answering every `go` with the first legal move, or with an illegal move if
+// `illegal` is set.  Each connection gets its own session.  Returns its address.
+pub fn fake_engine(illegal: bool) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            spawn(move || {
+                let mut output = stream.try_clone().unwrap();
+                let mut board = Board::default();
+                for line in BufReader::new(stream).lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    let reply = match GuiCommand::parse_validated(&line) {
+                        Ok((GuiCommand::Uci, _)) => "uciok".to_string(),
+                        Ok((GuiCommand::IsReady, _)) => "readyok".to_string(),
+                        Ok((GuiCommand::Position(..), Some(after))) => {
+                            board = after;
+                            continue;
+                        }
+                        Ok((GuiCommand::Go(_), _)) if illegal => "bestmove a1a5".to_string(),
+                        Ok((GuiCommand::Go(_), _)) => match MoveGen::new_legal(&board).next() {
+                            Some(m) => format!("info depth 1 score cp 0 pv {0}\nbestmove {0}", m),
+                            None => continue,
+                        },
+                        Ok((GuiCommand::Quit, _)) => break,
+                        _ => continue,
+                    };
+                    if writeln!(output, "{}", reply).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    address
+}
+
+pub fn connect_fake(address: &str) -> EngineConnection<'static> {
+    EngineConnectionBuilder::from_transport(TcpTransport::new(address))
+        .connect()
+        .unwrap()
+}
+
+// A pretend engine for one connection, scripted by `answer`.  It handles the handshake itself,
+// sending `uci_reply` (its `id` and `option` lines) before `uciok`, answers `isready`, and hangs
+// up on `quit`.  Every other line is passed to `answer` with the stream to reply on, and the
+// engine hangs up if it returns false.  Returns its address, and a handle that joins with every
+// line the engine received.
+pub fn scripted_engine<F>(uci_reply: &'static str, mut answer: F) -> (String, JoinHandle<Vec<String>>)
+where
+    F: FnMut(&str, &mut TcpStream) -> bool + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let engine = spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut output = stream.try_clone().unwrap();
+        let mut received = vec![];
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            received.push(line.clone());
+            let more = match line.trim() {
+                "uci" => writeln!(output, "{}uciok", uci_reply).is_ok(),
+                "isready" => output.write_all(b"readyok\n").is_ok(),
+                "quit" => false,
+                line => answer(line, &mut output),
+            };
+            if !more {
+                break;
+            }
+        }
+        received
+    });
+    (address, engine)
+}
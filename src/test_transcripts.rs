@@ -0,0 +1,72 @@
+// Engine output captured from real engines, used to make sure the parsers understand what is
+// actually sent over the wire, and not only what the specification says.
+
+pub fn stockfish_transcript() -> &'static str {
+    "Stockfish 10 64 POPCNT by T. Romstad, M. Costalba, J. Kiiski, G. Linscott\n\
+     id name Stockfish 10 64 POPCNT\n\
+     id author T. Romstad, M. Costalba, J. Kiiski, G. Linscott\n\
+     option name Debug Log File type string default\n\
+     option name Contempt type spin default 24 min -100 max 100\n\
+     option name Analysis Contempt type combo default Both var Off var White var Black var Both\n\
+     option name Threads type spin default 1 min 1 max 512\n\
+     option name Hash type spin default 16 min 1 max 131072\n\
+     option name Clear Hash type button\n\
+     option name Ponder type check default false\n\
+     option name MultiPV type spin default 1 min 1 max 500\n\
+     option name SyzygyPath type string default <empty>\n\
+     uciok\n\
+     readyok\n\
+     info depth 1 seldepth 1 multipv 1 score cp 116 nodes 20 nps 10000 tbhits 0 time 2 pv e2e4\n\
+     info depth 2 seldepth 2 multipv 1 score cp 112 nodes 54 nps 27000 tbhits 0 time 2 pv e2e4 b7b6\n\
+     info depth 10 currmove g1f3 currmovenumber 2\n\
+     info depth 12 seldepth 16 multipv 1 score cp 37 nodes 28268 nps 1061280 hashfull 12 tbhits 0 time 26 pv e2e4 e7e5 g1f3 b8c6\n\
+     info depth 13 seldepth 18 multipv 1 score cp 34 nodes 51476 nps 1029520 hashfull 21 tbhits 0 time 50 pv e2e4\n\
+     bestmove e2e4 ponder e7e5\n"
+}
+
+pub fn lc0_transcript() -> &'static str {
+    "       _\n\
+     |   _ | |\n\
+     |_ |_ |_| v0.25.1 built Apr 27 2020\n\
+     id name Lc0 v0.25.1\n\
+     id author The LCZero Authors.\n\
+     option name WeightsFile type string default <autodiscover>\n\
+     option name Backend type combo default cuda-auto var cuda-auto var cuda var cuda-fp16 var blas var random\n\
+     option name Threads type spin default 0 min 0 max 128\n\
+     option name NNCacheSize type spin default 2000000 min 0 max 999999999\n\
+     option name VerboseMoveStats type check default false\n\
+     uciok\n\
+     readyok\n\
+     info string Found pb network file: ./bc6e8b56e2d19e5e0e2ea7e8.pb.gz\n\
+     info depth 1 seldepth 2 time 2031 nodes 3 score cp 12 wdl 319 455 226 hashfull 0 nps 1 tbhits 0 multipv 1 pv d2d4 g8f6\n\
+     info depth 2 seldepth 3 time 2034 nodes 5 score cp 12 wdl 319 455 226 hashfull 0 nps 2 tbhits 0 multipv 2 pv e2e4 e7e5\n\
+     info depth 6 seldepth 17 time 3104 nodes 10645 score cp 14 wdl 324 449 227 hashfull 6 nps 9924 tbhits 0 movesleft 42 pv d2d4 g8f6 c2c4\n\
+     info string d2d4  (293 ) N:    5201 (+ 3) (P: 13.80%) (WL:  0.03302) (D: 0.451) (Q:  0.03302) (V:  0.0281)\n\
+     bestmove d2d4 ponder g8f6\n"
+}
+
+// What GUIs actually send.  Some terminate lines with `\r\n`, and some drop the final newline
+// when a command is written by hand or through a pipe.
+pub fn gui_transcript() -> &'static str {
+    "uci\n\
+     setoption name Hash value 128\n\
+     setoption name Threads value 4\n\
+     setoption name SyzygyPath value /home/user/syzygy\n\
+     setoption name Clear Hash\n\
+     isready\n\
+     ucinewgame\n\
+     position startpos\n\
+     go wtime 300000 btime 300000 winc 2000 binc 2000\n\
+     position startpos moves e2e4 e7e5\n\
+     go wtime 298000 btime 299000 winc 2000 binc 2000 movestogo 40\n\
+     position fen r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3\n\
+     go depth 20\n\
+     position fen 8/8/4k3/8/8/4K3/4P3/8 w - - 17 42 moves e3d4\n\
+     go movetime 1000\n\
+     go infinite\n\
+     stop\n\
+     ponderhit\n\
+     debug off\n\
+     register later\n\
+     quit\n"
+}
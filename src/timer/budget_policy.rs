@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+/// How much of the clock a client gives the engine for one move, as a `movetime`.
+///
+/// The budget is `percent` of the time left, or the time left shared out over the moves to go
+/// when there is a `movestogo`, plus the increment, kept between `min` and `max`.  It never goes
+/// past the time left.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BudgetPolicy {
+    percent: u32,
+    min: Duration,
+    max: Option<Duration>,
+}
+
+impl BudgetPolicy {
+    pub fn new(percent: u32) -> BudgetPolicy {
+        BudgetPolicy {
+            percent,
+            min: Duration::new(0, 0),
+            max: None,
+        }
+    }
+
+    pub fn min(mut self, min: Duration) -> BudgetPolicy {
+        self.min = min;
+        self
+    }
+
+    pub fn max(mut self, max: Duration) -> BudgetPolicy {
+        self.max = Some(max);
+        self
+    }
+
+    pub fn get_percent(&self) -> u32 {
+        self.percent
+    }
+
+    pub fn get_min(&self) -> Duration {
+        self.min
+    }
+
+    pub fn get_max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    /// The budget with `remaining` on the clock, `increment` to come, and `moves_to_go` (zero for
+    /// none) until the next time control.
+    pub fn budget(&self, remaining: Duration, increment: Duration, moves_to_go: u64) -> Duration {
+        let share = if moves_to_go > 0 {
+            remaining / moves_to_go.min(u32::MAX as u64) as u32
+        } else {
+            remaining * self.percent.min(100) / 100
+        };
+        let mut budget = (share + increment).max(self.min);
+        if let Some(max) = self.max {
+            budget = budget.min(max);
+        }
+        budget.min(remaining)
+    }
+}
+
+// Five percent of the time left, as is commonly used for sudden death.
+impl Default for BudgetPolicy {
+    fn default() -> BudgetPolicy {
+        BudgetPolicy::new(5)
+    }
+}
+
+#[test]
+fn test_budget_policy() {
+    let policy = BudgetPolicy::new(10);
+    let secs = Duration::from_secs;
+    assert_eq!(policy.budget(secs(60), secs(0), 0), secs(6));
+    assert_eq!(policy.budget(secs(60), secs(2), 0), secs(8));
+    assert_eq!(policy.budget(secs(60), secs(0), 20), secs(3));
+
+    let capped = policy.min(secs(1)).max(secs(5));
+    assert_eq!(capped.budget(secs(60), secs(2), 0), secs(5));
+    assert_eq!(capped.budget(secs(5), secs(0), 0), secs(1));
+    // Never more than is left, whatever the minimum.
+    assert_eq!(capped.budget(Duration::from_millis(300), secs(0), 0), Duration::from_millis(300));
+}
@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::Duration;
+
+use chess::Color;
+
+use timer::timer::Timer;
+
+#[cfg(test)]
+use std::time::Instant;
+
+/// What a `ClockWatcher` sees.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ClockEvent {
+    /// The clocks as they stand, sent every interval.  `None` for a side without a clock.
+    Tick {
+        player: Color,
+        white: Option<Duration>,
+        black: Option<Duration>,
+    },
+    /// The player ran out of time.  This is the last event.
+    Flag(Color),
+}
+
+/// Watches a `Timer` on its own thread, so that clocks can be drawn and time losses noticed
+/// without polling.  The watcher has its own copy of the timer: pass it the timer again with
+/// `update` whenever a move is made.
+///
+/// Watching stops after a flag, or when the watcher is stopped or dropped.
+pub struct ClockWatcher {
+    timer: Arc<Mutex<Timer>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ClockWatcher {
+    /// Watch `timer`, getting the events on the returned channel.
+    pub fn new(timer: Timer, interval: Duration) -> (ClockWatcher, Receiver<ClockEvent>) {
+        let (tx, rx) = channel();
+        let watcher = ClockWatcher::with_callback(timer, interval, move |event| {
+            let _ = tx.send(event);
+        });
+        (watcher, rx)
+    }
+
+    /// Watch `timer`, calling `callback` with each event on the watcher's thread.
+    pub fn with_callback<F>(timer: Timer, interval: Duration, mut callback: F) -> ClockWatcher
+    where
+        F: FnMut(ClockEvent) + Send + 'static,
+    {
+        let timer = Arc::new(Mutex::new(timer));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let timer = timer.clone();
+            let stop = stop.clone();
+            spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let timer = *timer.lock().unwrap();
+                    let player = timer.get_player();
+                    if timer.timeout_for(player) {
+                        callback(ClockEvent::Flag(player));
+                        break;
+                    }
+                    callback(ClockEvent::Tick {
+                        player,
+                        white: timer.white_remaining(),
+                        black: timer.black_remaining(),
+                    });
+                    sleep(interval);
+                }
+            })
+        };
+
+        ClockWatcher {
+            timer,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Replace the watched timer, such as after `made_move`.
+    pub fn update(&self, timer: Timer) {
+        *self.timer.lock().unwrap() = timer;
+    }
+
+    /// Has watching stopped, either because of a flag or `stop`?
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|t| t.is_finished())
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ClockWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[test]
+fn test_clock_watcher_ticks_and_flags() {
+    let mut timer = Timer::new_without_increment(Duration::from_millis(50));
+    timer.start();
+    let (watcher, events) = ClockWatcher::new(timer, Duration::from_millis(5));
+
+    let events = events.iter().collect::<Vec<ClockEvent>>();
+    // The thread drops the channel just before it returns, so give it a moment to finish.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !watcher.is_finished() && Instant::now() < deadline {
+        sleep(Duration::from_millis(1));
+    }
+    assert!(watcher.is_finished());
+    assert_eq!(events.last(), Some(&ClockEvent::Flag(Color::White)));
+    match events[0] {
+        ClockEvent::Tick {
+            player,
+            white,
+            black,
+        } => {
+            assert_eq!(player, Color::White);
+            assert!(white.unwrap() <= Duration::from_millis(50));
+            assert_eq!(black, Some(Duration::from_millis(50)));
+        }
+        e => panic!("unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn test_clock_watcher_update() {
+    let mut timer = Timer::new_without_increment(Duration::from_secs(100));
+    timer.start();
+    let (mut watcher, events) = ClockWatcher::new(timer, Duration::from_millis(1));
+
+    timer.made_move();
+    watcher.update(timer);
+    let black_to_move = events.iter().find(|e| match e {
+        ClockEvent::Tick { player, .. } => *player == Color::Black,
+        _ => false,
+    });
+    assert!(black_to_move.is_some());
+
+    watcher.stop();
+    assert!(watcher.is_finished());
+    assert!(events.iter().all(|e| e != ClockEvent::Flag(Color::Black)));
+}
@@ -1 +1,4 @@
+pub mod budget_policy;
+pub mod clock_watcher;
 pub mod timer;
+pub mod timer_state;
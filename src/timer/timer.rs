@@ -2,21 +2,34 @@ use chess::Color;
 use std::time::{Duration, Instant};
 
 use gui::go::Go;
+use timer::budget_policy::BudgetPolicy;
+use timer::timer_state::TimerState;
 use std::convert::Into;
 
+// With byo-yomi, `time` is the main time, and `periods` of `period` each follow it.  A move made
+// within a period doesn't use it up.
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
 struct PlayerTimer {
     time: Duration,
     increment: Duration,
+    period: Duration,
+    periods: u32,
+}
+
+// The time since the move started that is charged to the player to move.
+fn used_since(elapsed: Option<Duration>, overhead: Duration) -> Duration {
+    elapsed.map_or(Duration::new(0, 0), |elapsed| elapsed.saturating_sub(overhead))
 }
 
 fn duration_to_millis(duration: Duration) -> u64 {
     duration.as_secs() * 1000 + (duration.subsec_millis() as u64)
 }
 
-fn remaining_or_zero(optional_start: Option<Instant>, time: Duration) -> Duration {
-    if let Some(start) = optional_start {
-        let elapsed = start.elapsed();
+// Up to `overhead` of the time since the move started isn't charged, as it was spent getting
+// the move to and from the engine.
+fn remaining_or_zero(elapsed: Option<Duration>, time: Duration, overhead: Duration) -> Duration {
+    if let Some(elapsed) = elapsed {
+        let elapsed = elapsed.saturating_sub(overhead);
         if elapsed > time {
             Duration::new(0, 0)
         } else {
@@ -28,11 +41,33 @@ fn remaining_or_zero(optional_start: Option<Instant>, time: Duration) -> Duratio
 }
 
 impl PlayerTimer {
-    pub fn remaining(&self, start: Option<Instant>, playing: bool) -> Duration {
+    fn new(time: Duration, increment: Duration) -> PlayerTimer {
+        PlayerTimer {
+            time,
+            increment,
+            period: Duration::new(0, 0),
+            periods: 0,
+        }
+    }
+
+    // Everything left, byo-yomi periods included.
+    pub fn remaining(&self, elapsed: Option<Duration>, playing: bool, overhead: Duration) -> Duration {
+        let total = self.time + self.period * self.periods;
         if !playing {
-            self.time
+            total
+        } else {
+            remaining_or_zero(elapsed, total, overhead)
+        }
+    }
+
+    // What can be used on this move without losing a byo-yomi period, which is what the engine
+    // is told it has.
+    pub fn remaining_for_move(&self, elapsed: Option<Duration>, playing: bool, overhead: Duration) -> Duration {
+        let remaining = self.remaining(elapsed, playing, overhead);
+        if self.periods > 1 {
+            remaining.saturating_sub(self.period * (self.periods - 1))
         } else {
-            remaining_or_zero(start, self.time)
+            remaining
         }
     }
 
@@ -44,11 +79,25 @@ impl PlayerTimer {
         self.time
     }
 
-    pub fn made_move(&mut self, start: Option<Instant>, extra_time: Duration) {
-        self.time = self.remaining(start, true);
+    pub fn made_move(&mut self, used: Duration, extra_time: Duration) {
+        self.charge(used);
         self.time += self.increment;
         self.time += extra_time;
     }
+
+    // Take `used` off the main time, and then off the byo-yomi periods.
+    fn charge(&mut self, used: Duration) {
+        if used <= self.time {
+            self.time -= used;
+        } else {
+            let over = used - self.time;
+            self.time = Duration::new(0, 0);
+            if self.period > Duration::new(0, 0) {
+                let lost = over.as_nanos() / self.period.as_nanos();
+                self.periods = self.periods.saturating_sub(lost.min(u32::MAX as u128) as u32);
+            }
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
@@ -61,33 +110,48 @@ pub struct Timer {
     moves_to_go: u64,
     start_moves_to_go: u64,
     add_time_on_move_n: Duration,
+    move_overhead: Duration,
+    // The time one side uses is added to the other's clock.
+    hourglass: bool,
+    // Nodes per millisecond, or zero to use the wall clock.
+    nodes_time: u64,
+    nodes_searched: u64,
 }
 
+// The engine is told about `move_overhead` less than it has, so that its move still arrives in
+// time after the latency.
 impl Into<Go> for Timer {
     fn into(self) -> Go {
         let mut go = Go::default();
 
         let zero = Duration::new(0, 0);
+        let overhead = self.move_overhead;
 
         if let Some(white) = self.white {
             go = go.combine(&Go::wtime(duration_to_millis(
-                white.remaining(self.start, self.player == Color::White),
+                white
+                    .remaining_for_move(self.elapsed(), self.player == Color::White, overhead)
+                    .saturating_sub(overhead),
             )));
-            if white.increment != zero {
-                go = go.combine(&Go::winc(duration_to_millis(white.get_increment())));
+            if white.increment + white.period != zero {
+                go = go.combine(&Go::winc(duration_to_millis(white.increment + white.period)));
             }
         }
         if let Some(black) = self.black {
             go = go.combine(&Go::btime(duration_to_millis(
-                black.remaining(self.start, self.player == Color::Black),
+                black
+                    .remaining_for_move(self.elapsed(), self.player == Color::Black, overhead)
+                    .saturating_sub(overhead),
             )));
-            if black.increment != zero {
-                go = go.combine(&Go::binc(duration_to_millis(black.get_increment())));
+            if black.increment + black.period != zero {
+                go = go.combine(&Go::binc(duration_to_millis(black.increment + black.period)));
             }
         }
 
         if let Some(move_time) = self.move_time {
-            go = go.combine(&Go::movetime(duration_to_millis(move_time)));
+            go = go.combine(&Go::movetime(duration_to_millis(
+                move_time.saturating_sub(overhead),
+            )));
         }
 
         if self.moves_to_go != 0 {
@@ -146,12 +210,17 @@ impl Timer {
         };
 
         if let Some(t) = timer {
-            Some(t.remaining(self.start, self.player == player))
+            let remaining = t.remaining(self.elapsed(), self.player == player, self.move_overhead);
+            if self.hourglass && self.player != player {
+                Some(remaining + used_since(self.elapsed(), self.move_overhead))
+            } else {
+                Some(remaining)
+            }
         } else if let Some(move_time) = self.move_time {
             if self.player == player {
                 Some(move_time)
             } else {
-                Some(remaining_or_zero(self.start, move_time))
+                Some(remaining_or_zero(self.elapsed(), move_time, self.move_overhead))
             }
         } else {
             None
@@ -162,6 +231,27 @@ impl Timer {
         self.remaining_for(self.player)
     }
 
+    /// A `movetime` for the player to move, from their clock and `policy`, or `None` if they
+    /// have no clock.  A timer with a fixed move time always suggests that, less the overhead.
+    pub fn suggest_movetime(&self, policy: &BudgetPolicy) -> Option<Duration> {
+        let overhead = self.move_overhead;
+        let clock = if self.player == Color::White {
+            self.white
+        } else {
+            self.black
+        };
+
+        match clock {
+            Some(clock) => {
+                let remaining = clock
+                    .remaining_for_move(self.elapsed(), true, overhead)
+                    .saturating_sub(overhead);
+                Some(policy.budget(remaining, clock.increment + clock.period, self.moves_to_go))
+            }
+            None => self.move_time.map(|x| x.saturating_sub(overhead)),
+        }
+    }
+
     pub fn timeout_for(&self, player: Color) -> bool {
         if let Some(remaining) = self.remaining_for(player) {
             remaining == Duration::new(0, 0)
@@ -170,14 +260,33 @@ impl Timer {
         }
     }
 
+    /// The time used on this move so far.  With `nodes_time` set, this is worked out from the
+    /// nodes searched rather than the wall clock.
     pub fn elapsed(&self) -> Option<Duration> {
-        if let Some(start) = self.start {
-            Some(start.elapsed())
-        } else {
-            None
+        let start = self.start?;
+        match self.nodes_searched.checked_div(self.nodes_time) {
+            Some(millis) => Some(Duration::from_millis(millis)),
+            None => Some(start.elapsed()),
         }
     }
 
+    /// Measure time in nodes, like the engines' `nodestime` option: each millisecond on the
+    /// clocks is worth `nodes_time` nodes, so games play out the same on any machine.  Zero goes
+    /// back to the wall clock.
+    pub fn set_nodes_time(&mut self, nodes_time: u64) {
+        self.nodes_time = nodes_time;
+    }
+
+    pub fn get_nodes_time(&self) -> u64 {
+        self.nodes_time
+    }
+
+    /// The nodes searched on this move so far, such as from the engine's last `info nodes`.
+    /// Only used with `nodes_time`.
+    pub fn set_nodes_searched(&mut self, nodes: u64) {
+        self.nodes_searched = nodes;
+    }
+
     pub fn get_start(&self) -> Option<Instant> {
         self.start
     }
@@ -194,6 +303,17 @@ impl Timer {
         self.add_time_on_move_n = add;
     }
 
+    pub fn get_move_overhead(&self) -> Duration {
+        self.move_overhead
+    }
+
+    /// Time lost on every move to the GUI and the connection, like the engines' `Move Overhead`
+    /// option.  It is left out of the time the engine is told about, and not charged to the engine
+    /// when it moves, so latency alone doesn't lose on time.
+    pub fn set_move_overhead(&mut self, overhead: Duration) {
+        self.move_overhead = overhead;
+    }
+
     pub fn made_move(&mut self) {
         if self.player == Color::Black && self.moves_to_go > 0 {
             self.moves_to_go -= 1;
@@ -205,14 +325,20 @@ impl Timer {
         } else {
             Duration::new(0, 0)
         };
+        let used = used_since(self.elapsed(), self.move_overhead);
         {
-            let clock = if self.player == Color::White {
-                &mut self.white
+            let (clock, other) = if self.player == Color::White {
+                (&mut self.white, &mut self.black)
             } else {
-                &mut self.black
+                (&mut self.black, &mut self.white)
             };
             if let Some(player_clock) = clock {
-                player_clock.made_move(self.start, add_time);
+                player_clock.made_move(used, add_time);
+            }
+            if self.hourglass {
+                if let Some(other_clock) = other {
+                    other_clock.time += used;
+                }
             }
         }
 
@@ -266,6 +392,77 @@ impl Timer {
 
     pub fn start(&mut self) {
         self.start = Some(Instant::now());
+        self.nodes_searched = 0;
+    }
+
+    // A player's clock as it stands now, with the time used so far on this move charged.
+    fn clock_now(&self, player: Color) -> Option<PlayerTimer> {
+        let clock = if player == Color::White {
+            self.white
+        } else {
+            self.black
+        };
+        let used = used_since(self.elapsed(), self.move_overhead);
+        clock.map(|mut clock| {
+            if player == self.player {
+                clock.charge(used);
+            } else if self.hourglass {
+                clock.time += used;
+            }
+            clock
+        })
+    }
+
+    /// The clocks as they stand now.  The time the side to move has used so far is charged to it.
+    pub fn to_state(&self) -> TimerState {
+        let white = self.clock_now(Color::White);
+        let black = self.clock_now(Color::Black);
+        TimerState {
+            wtime: white.map(|w| duration_to_millis(w.time)),
+            winc: white.map_or(0, |w| duration_to_millis(w.get_increment())),
+            btime: black.map(|b| duration_to_millis(b.time)),
+            binc: black.map_or(0, |b| duration_to_millis(b.get_increment())),
+            period: duration_to_millis(self.get_period()),
+            wperiods: white.map_or(0, |w| w.periods),
+            bperiods: black.map_or(0, |b| b.periods),
+            hourglass: self.hourglass,
+            move_time: self.move_time.map(duration_to_millis),
+            moves_to_go: self.moves_to_go,
+            start_moves_to_go: self.start_moves_to_go,
+            add_time_on_move_n: duration_to_millis(self.add_time_on_move_n),
+            move_overhead: duration_to_millis(self.move_overhead),
+            nodes_time: self.nodes_time,
+            player: self.player,
+        }
+    }
+
+    /// A stopped timer with the clocks in `state`; `start` it when the game is resumed.
+    pub fn from_state(state: &TimerState) -> Timer {
+        let mut timer = Timer::new_from_durations(
+            state.wtime.map(Duration::from_millis),
+            Duration::from_millis(state.winc),
+            state.btime.map(Duration::from_millis),
+            Duration::from_millis(state.binc),
+            state.move_time.map(Duration::from_millis),
+            state.moves_to_go,
+            state.start_moves_to_go,
+            Duration::from_millis(state.add_time_on_move_n),
+            state.player,
+            None,
+        );
+        timer.set_move_overhead(Duration::from_millis(state.move_overhead));
+        timer.hourglass = state.hourglass;
+        timer.nodes_time = state.nodes_time;
+        let period = Duration::from_millis(state.period);
+        if let Some(ref mut white) = timer.white {
+            white.period = period;
+            white.periods = state.wperiods;
+        }
+        if let Some(ref mut black) = timer.black {
+            black.period = period;
+            black.periods = state.bperiods;
+        }
+        timer
     }
 
     pub fn started(&self) -> bool {
@@ -317,6 +514,50 @@ impl Timer {
         )
     }
 
+    /// `main` time each, followed by `periods` byo-yomi periods of `period`.  A move that runs
+    /// over into a period but ends within it keeps that period; each whole period used is lost.
+    /// The flag falls when the last period runs out.
+    ///
+    /// UCI has no byo-yomi, so the engine is told its time is what it can use on this move
+    /// without losing a period, with the period as its increment.
+    pub fn new_byo_yomi(main: Duration, period: Duration, periods: u32) -> Timer {
+        let mut timer = Timer::new_without_increment(main);
+        for clock in timer.white.iter_mut().chain(timer.black.iter_mut()) {
+            clock.period = period;
+            clock.periods = periods;
+        }
+        timer
+    }
+
+    /// An hourglass: the time one side uses goes to the other side.  The engine is told the
+    /// clocks as they stand, without an increment.
+    pub fn new_hourglass(time: Duration) -> Timer {
+        let mut timer = Timer::new_without_increment(time);
+        timer.hourglass = true;
+        timer
+    }
+
+    pub fn is_hourglass(&self) -> bool {
+        self.hourglass
+    }
+
+    /// The byo-yomi period length, or zero without byo-yomi.
+    pub fn get_period(&self) -> Duration {
+        self.white
+            .or(self.black)
+            .map_or(Duration::new(0, 0), |clock| clock.period)
+    }
+
+    /// The byo-yomi periods `player` has left.
+    pub fn periods_for(&self, player: Color) -> u32 {
+        let timer = if player == Color::White {
+            self.white
+        } else {
+            self.black
+        };
+        timer.map_or(0, |clock| clock.periods)
+    }
+
     pub fn new_from_durations(
         wtime: Option<Duration>,
         winc: Duration,
@@ -330,20 +571,18 @@ impl Timer {
         start: Option<Instant>,
     ) -> Timer {
         Timer {
-            white: wtime.map(|x| PlayerTimer {
-                time: x,
-                increment: winc,
-            }),
-            black: btime.map(|x| PlayerTimer {
-                time: x,
-                increment: binc,
-            }),
+            white: wtime.map(|x| PlayerTimer::new(x, winc)),
+            black: btime.map(|x| PlayerTimer::new(x, binc)),
             move_time: move_time,
             moves_to_go: moves_to_go,
             start_moves_to_go: start_moves_to_go,
             add_time_on_move_n: add_time_on_move_n,
             player: player,
             start: start,
+            move_overhead: Duration::new(0, 0),
+            hourglass: false,
+            nodes_time: 0,
+            nodes_searched: 0,
         }
     }
 }
@@ -383,6 +622,25 @@ fn test_without_increment_into_go() {
     assert_eq!(go, timer.into());
 }
 
+#[test]
+fn test_suggest_movetime() {
+    let policy = BudgetPolicy::new(10);
+    let mut timer = Timer::new_with_increment(Duration::new(60, 0), Duration::new(1, 0));
+    assert_eq!(timer.suggest_movetime(&policy), Some(Duration::new(7, 0)));
+
+    timer.set_move_overhead(Duration::new(10, 0));
+    assert_eq!(timer.suggest_movetime(&policy), Some(Duration::new(6, 0)));
+
+    let fixed = Timer::new_static_move_time(Duration::new(3, 0));
+    assert_eq!(fixed.suggest_movetime(&policy), Some(Duration::new(3, 0)));
+
+    let mut infinite = Timer::new_static_move_time(Duration::new(3, 0));
+    infinite.move_time = None;
+    assert_eq!(infinite.suggest_movetime(&policy), None);
+}
+
+#[cfg(test)]
+use std::str::FromStr;
 #[cfg(test)]
 use std::thread::sleep;
 
@@ -419,3 +677,141 @@ fn test_make_move_with_inc() {
         Duration::new(2, 0)
     ));
 }
+
+#[test]
+fn test_move_overhead_into_go() {
+    let mut timer = Timer::new_with_increment(Duration::new(5, 0), Duration::new(1, 0));
+    timer.set_move_overhead(Duration::from_millis(100));
+
+    let go = Go::default()
+        .combine(&Go::wtime(4900))
+        .combine(&Go::winc(1000))
+        .combine(&Go::btime(4900))
+        .combine(&Go::binc(1000));
+    assert_eq!(go, timer.into());
+
+    let mut timer = Timer::new_static_move_time(Duration::from_millis(50));
+    timer.set_move_overhead(Duration::from_millis(100));
+    assert_eq!(Go::movetime(0), timer.into());
+}
+
+#[test]
+fn test_move_overhead_is_not_charged() {
+    let mut timer = Timer::new_without_increment(Duration::new(5, 0));
+    timer.set_move_overhead(Duration::from_millis(200));
+    timer.start();
+
+    sleep(Duration::from_millis(100));
+    assert!(!timer.timeout_for(Color::White));
+    assert_eq!(timer.white_remaining(), Some(Duration::new(5, 0)));
+
+    sleep(Duration::from_millis(200));
+    timer.made_move();
+    assert!(durations_within_5ms(
+        timer.white_remaining().unwrap(),
+        Duration::from_millis(4900)
+    ));
+}
+
+#[test]
+fn test_timer_state_resume() {
+    let mut timer = Timer::new_with_increment(Duration::new(5, 0), Duration::new(1, 0));
+    timer.set_move_overhead(Duration::from_millis(10));
+    timer.start();
+    sleep(Duration::from_millis(100));
+    timer.made_move();
+    sleep(Duration::from_millis(100));
+
+    let state = timer.to_state();
+    assert_eq!(state.player, Color::Black);
+    assert!(state.wtime.unwrap() > 5800 && state.wtime.unwrap() <= 5910);
+    assert!(state.btime.unwrap() > 4800 && state.btime.unwrap() <= 4910);
+    assert_eq!(state.winc, 1000);
+
+    // Nothing more is charged until the resumed game starts.
+    let mut resumed = Timer::from_state(&TimerState::from_str(&state.to_string()).unwrap());
+    assert!(!resumed.started());
+    sleep(Duration::from_millis(20));
+    assert_eq!(resumed.to_state(), state);
+    resumed.start();
+    resumed.made_move();
+    assert_eq!(resumed.get_player(), Color::White);
+    assert_eq!(resumed.get_move_overhead(), Duration::from_millis(10));
+}
+
+#[test]
+fn test_byo_yomi() {
+    let mut timer = Timer::new_byo_yomi(Duration::from_millis(100), Duration::from_millis(100), 3);
+    assert_eq!(
+        Go::wtime(200).combine(&Go::winc(100)).combine(&Go::btime(200)).combine(&Go::binc(100)),
+        timer.into()
+    );
+    assert_eq!(timer.white_remaining(), Some(Duration::from_millis(400)));
+
+    // Into the first period, but within it: nothing is lost.
+    timer.start();
+    sleep(Duration::from_millis(150));
+    timer.made_move();
+    assert_eq!(timer.periods_for(Color::White), 3);
+    assert_eq!(timer.to_state().wtime, Some(0));
+    timer.made_move();
+
+    // Past a whole period.
+    sleep(Duration::from_millis(150));
+    timer.made_move();
+    assert_eq!(timer.periods_for(Color::White), 2);
+    assert!(!timer.timeout_for(Color::White));
+    let go: Go = timer.into();
+    assert_eq!(go.get_wtime(), Some(100));
+
+    // Out of periods.
+    timer.made_move();
+    sleep(Duration::from_millis(220));
+    assert!(timer.timeout_for(Color::White));
+    timer.made_move();
+    assert_eq!(timer.periods_for(Color::White), 0);
+    assert!(timer.timeout_for(Color::White));
+
+    let resumed = Timer::from_state(&timer.to_state());
+    assert_eq!(resumed.get_period(), Duration::from_millis(100));
+    assert_eq!(resumed.periods_for(Color::Black), 3);
+}
+
+#[test]
+fn test_hourglass() {
+    let mut timer = Timer::new_hourglass(Duration::new(5, 0));
+    assert!(timer.is_hourglass());
+    timer.start();
+    sleep(Duration::from_millis(200));
+    assert!(durations_within_5ms(timer.black_remaining().unwrap(), Duration::from_millis(5200)));
+    timer.made_move();
+
+    assert!(durations_within_5ms(timer.white_remaining().unwrap(), Duration::from_millis(4800)));
+    assert!(durations_within_5ms(timer.black_remaining().unwrap(), Duration::from_millis(5200)));
+    let go: Go = timer.into();
+    assert_eq!(go.get_winc(), None);
+
+    let state = timer.to_state();
+    assert!(state.hourglass);
+    assert!(Timer::from_state(&state).is_hourglass());
+}
+
+#[test]
+fn test_nodes_time() {
+    let mut timer = Timer::new_without_increment(Duration::new(1, 0));
+    timer.set_nodes_time(1000);
+    timer.start();
+
+    // The wall clock doesn't count; only the nodes do.
+    sleep(Duration::from_millis(20));
+    assert_eq!(timer.white_remaining(), Some(Duration::new(1, 0)));
+    timer.set_nodes_searched(250_000);
+    assert_eq!(timer.elapsed(), Some(Duration::from_millis(250)));
+    timer.made_move();
+    assert_eq!(timer.white_remaining(), Some(Duration::from_millis(750)));
+    assert_eq!(timer.elapsed(), Some(Duration::new(0, 0)));
+
+    timer.set_nodes_searched(2_000_000);
+    assert!(timer.timeout_for(Color::Black));
+    assert_eq!(Timer::from_state(&timer.to_state()).get_nodes_time(), 1000);
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chess::Color;
+
+use error::Error;
+
+/// A snapshot of a `Timer` that doesn't depend on when it was taken, so that an adjourned game
+/// can be resumed by another process.  Times are in milliseconds, as in `go`.
+///
+/// It is written as one line of `name value` pairs, such as
+/// `wtime 295000 winc 2000 btime 300000 binc 2000 movestogo 0 startmovestogo 0 addtime 0
+/// overhead 0 player w`, with `wtime`, `btime` and `movetime` left out when there is no such
+/// clock.  Byo-yomi (`period`, `wperiods` and `bperiods`) and `hourglass` are only written when
+/// they are used, as is `nodestime`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimerState {
+    /// Main time, when there is byo-yomi.
+    pub wtime: Option<u64>,
+    pub winc: u64,
+    pub btime: Option<u64>,
+    pub binc: u64,
+    /// The byo-yomi period length, or zero for none.
+    pub period: u64,
+    pub wperiods: u32,
+    pub bperiods: u32,
+    pub hourglass: bool,
+    pub move_time: Option<u64>,
+    pub moves_to_go: u64,
+    pub start_moves_to_go: u64,
+    pub add_time_on_move_n: u64,
+    pub move_overhead: u64,
+    /// Nodes per millisecond, or zero for the wall clock.
+    pub nodes_time: u64,
+    pub player: Color,
+}
+
+impl fmt::Display for TimerState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(wtime) = self.wtime {
+            write!(f, "wtime {} ", wtime)?;
+        }
+        write!(f, "winc {} ", self.winc)?;
+        if let Some(btime) = self.btime {
+            write!(f, "btime {} ", btime)?;
+        }
+        write!(f, "binc {} ", self.binc)?;
+        if self.period != 0 {
+            write!(
+                f,
+                "period {} wperiods {} bperiods {} ",
+                self.period, self.wperiods, self.bperiods
+            )?;
+        }
+        if self.hourglass {
+            write!(f, "hourglass 1 ")?;
+        }
+        if let Some(move_time) = self.move_time {
+            write!(f, "movetime {} ", move_time)?;
+        }
+        if self.nodes_time != 0 {
+            write!(f, "nodestime {} ", self.nodes_time)?;
+        }
+        write!(
+            f,
+            "movestogo {} startmovestogo {} addtime {} overhead {} player {}",
+            self.moves_to_go,
+            self.start_moves_to_go,
+            self.add_time_on_move_n,
+            self.move_overhead,
+            if self.player == Color::White { "w" } else { "b" }
+        )
+    }
+}
+
+impl FromStr for TimerState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<TimerState, Error> {
+        // Anything left out is zero, or missing, with White to move.
+        let mut state = TimerState {
+            wtime: None,
+            winc: 0,
+            btime: None,
+            binc: 0,
+            period: 0,
+            wperiods: 0,
+            bperiods: 0,
+            hourglass: false,
+            move_time: None,
+            moves_to_go: 0,
+            start_moves_to_go: 0,
+            add_time_on_move_n: 0,
+            move_overhead: 0,
+            nodes_time: 0,
+            player: Color::White,
+        };
+        let mut words = s.split_whitespace();
+        while let Some(name) = words.next() {
+            let value = words.next().ok_or(Error::CommandError)?;
+            if name == "player" {
+                state.player = match value {
+                    "w" => Color::White,
+                    "b" => Color::Black,
+                    _ => return Err(Error::CommandError),
+                };
+                continue;
+            }
+
+            let value = value.parse::<u64>().map_err(|_| Error::CommandError)?;
+            match name {
+                "wtime" => state.wtime = Some(value),
+                "winc" => state.winc = value,
+                "btime" => state.btime = Some(value),
+                "binc" => state.binc = value,
+                "period" => state.period = value,
+                "wperiods" => state.wperiods = value.min(u32::MAX as u64) as u32,
+                "bperiods" => state.bperiods = value.min(u32::MAX as u64) as u32,
+                "hourglass" => state.hourglass = value != 0,
+                "movetime" => state.move_time = Some(value),
+                "movestogo" => state.moves_to_go = value,
+                "startmovestogo" => state.start_moves_to_go = value,
+                "addtime" => state.add_time_on_move_n = value,
+                "overhead" => state.move_overhead = value,
+                "nodestime" => state.nodes_time = value,
+                _ => return Err(Error::CommandError),
+            }
+        }
+        Ok(state)
+    }
+}
+
+#[test]
+fn test_timer_state_round_trip() {
+    let state = TimerState {
+        wtime: Some(295000),
+        winc: 2000,
+        btime: None,
+        binc: 0,
+        period: 0,
+        wperiods: 0,
+        bperiods: 0,
+        hourglass: false,
+        move_time: Some(1000),
+        moves_to_go: 12,
+        start_moves_to_go: 40,
+        add_time_on_move_n: 60000,
+        move_overhead: 30,
+        nodes_time: 0,
+        player: Color::Black,
+    };
+    let text = state.to_string();
+    assert_eq!(
+        text,
+        "wtime 295000 winc 2000 binc 0 movetime 1000 movestogo 12 startmovestogo 40 addtime 60000 overhead 30 player b"
+    );
+    assert_eq!(TimerState::from_str(&text), Ok(state));
+
+    let byo_yomi = TimerState {
+        period: 30000,
+        wperiods: 3,
+        bperiods: 1,
+        hourglass: true,
+        nodes_time: 1000,
+        ..state
+    };
+    let text = byo_yomi.to_string();
+    assert!(text.contains(" period 30000 wperiods 3 bperiods 1 hourglass 1 movetime 1000 nodestime 1000 "));
+    assert_eq!(TimerState::from_str(&text), Ok(byo_yomi));
+
+    assert_eq!(TimerState::from_str("wtime"), Err(Error::CommandError));
+    assert_eq!(TimerState::from_str("wtime soon"), Err(Error::CommandError));
+    assert_eq!(TimerState::from_str("player x"), Err(Error::CommandError));
+}
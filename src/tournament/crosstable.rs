@@ -0,0 +1,158 @@
+use std::fmt;
+
+use chess::Color;
+
+use game_result::GameResult;
+
+/// Wins, draws and losses, from one player's point of view.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl Record {
+    pub fn get_wins(&self) -> u32 {
+        self.wins
+    }
+
+    pub fn get_draws(&self) -> u32 {
+        self.draws
+    }
+
+    pub fn get_losses(&self) -> u32 {
+        self.losses
+    }
+
+    pub fn get_games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// A point for a win, half for a draw.
+    pub fn get_score(&self) -> f32 {
+        self.wins as f32 + self.draws as f32 / 2.0
+    }
+
+    fn add(&mut self, other: &Record) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+}
+
+/// The results of a tournament so far, by pair of players.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crosstable {
+    names: Vec<String>,
+    // `records[i][j]` is how player `i` did against player `j`.
+    records: Vec<Vec<Record>>,
+}
+
+impl Crosstable {
+    pub fn new(names: Vec<String>) -> Crosstable {
+        let records = vec![vec![Record::default(); names.len()]; names.len()];
+        Crosstable { names, records }
+    }
+
+    /// Count a game between two players, given by their index.
+    pub fn add(&mut self, white: usize, black: usize, result: GameResult) {
+        match result {
+            GameResult::Win(Color::White) => {
+                self.records[white][black].wins += 1;
+                self.records[black][white].losses += 1;
+            }
+            GameResult::Win(Color::Black) => {
+                self.records[white][black].losses += 1;
+                self.records[black][white].wins += 1;
+            }
+            GameResult::Draw => {
+                self.records[white][black].draws += 1;
+                self.records[black][white].draws += 1;
+            }
+        }
+    }
+
+    pub fn get_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// How `player` did against `opponent`.
+    pub fn get_record(&self, player: usize, opponent: usize) -> Record {
+        self.records[player][opponent]
+    }
+
+    /// How `player` did against everyone.
+    pub fn get_total(&self, player: usize) -> Record {
+        let mut total = Record::default();
+        for record in &self.records[player] {
+            total.add(record);
+        }
+        total
+    }
+
+    /// Every player, best score first.  Ties keep the order the players were given in.
+    pub fn standings(&self) -> Vec<usize> {
+        let mut players: Vec<usize> = (0..self.names.len()).collect();
+        players.sort_by(|&a, &b| {
+            self.get_total(b)
+                .get_score()
+                .partial_cmp(&self.get_total(a).get_score())
+                .unwrap()
+        });
+        players
+    }
+}
+
+// One line per player, in order of standing, with the score against each opponent in the
+// order of the lines.
+impl fmt::Display for Crosstable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let standings = self.standings();
+        let width = self.names.iter().map(|n| n.len()).max().unwrap_or(0);
+        for (rank, &player) in standings.iter().enumerate() {
+            let total = self.get_total(player);
+            write!(
+                f,
+                "{:>2} {:<width$} {:>5.1}/{:<3}",
+                rank + 1,
+                self.names[player],
+                total.get_score(),
+                total.get_games(),
+                width = width
+            )?;
+            for &opponent in &standings {
+                let record = self.get_record(player, opponent);
+                if opponent == player {
+                    write!(f, "    -")?;
+                } else if record.get_games() == 0 {
+                    write!(f, "    .")?;
+                } else {
+                    write!(f, " {:>4.1}", record.get_score())?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_crosstable() {
+    let mut crosstable = Crosstable::new(vec!["A".to_string(), "Bee".to_string(), "C".to_string()]);
+    crosstable.add(0, 1, GameResult::Win(Color::Black));
+    crosstable.add(1, 0, GameResult::Draw);
+    crosstable.add(2, 1, GameResult::Win(Color::White));
+
+    assert_eq!(crosstable.get_record(1, 0).get_score(), 1.5);
+    assert_eq!(crosstable.get_record(0, 1).get_losses(), 1);
+    assert_eq!(crosstable.get_total(1).get_games(), 3);
+    assert_eq!(crosstable.get_total(1).get_score(), 1.5);
+    assert_eq!(crosstable.standings(), vec![1, 2, 0]);
+    assert_eq!(
+        crosstable.to_string(),
+        " 1 Bee   1.5/3      -  0.0  1.5\n \
+         2 C     1.0/1    1.0    -    .\n \
+         3 A     0.5/2    0.5    .    -\n"
+    );
+}
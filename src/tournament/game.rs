@@ -0,0 +1,409 @@
+use std::time::{Duration, Instant};
+
+use chess::{Board, BoardStatus, ChessMove, Color};
+
+use adjudication::{Adjudication, Adjudicator};
+use engine::score::Score;
+use engine_connection::EngineConnection;
+use error::Error;
+use game_history::GameHistory;
+use game_result::GameResult;
+use gui::go::Go;
+use transcript::Transcript;
+
+#[cfg(test)]
+use chess::Square;
+#[cfg(test)]
+use std::str::FromStr;
+#[cfg(test)]
+use test_engines::{connect_fake, fake_engine};
+
+const DEFAULT_MOVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Why a game ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    Adjudication(Adjudication),
+    /// The side to move answered with a move that can't be played.
+    IllegalMove(ChessMove),
+    /// The side to move answered with the null move, though it had moves to play.
+    NullMove,
+    /// The side to move didn't answer in time, or its engine failed.
+    Forfeit(Error),
+}
+
+impl Termination {
+    /// Whether an engine lost because it misbehaved, rather than over the board.
+    pub fn is_abnormal(&self) -> bool {
+        matches!(
+            self,
+            Termination::IllegalMove(_) | Termination::NullMove | Termination::Forfeit(_)
+        )
+    }
+}
+
+/// Which games keep the transcripts of what was said to and by each engine.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TranscriptPolicy {
+    Never,
+    Always,
+    /// Only games lost to an illegal move, a crash or a time loss.
+    Abnormal,
+}
+
+/// A finished game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    start: Board,
+    moves: Vec<ChessMove>,
+    result: GameResult,
+    termination: Termination,
+    white_transcript: Option<Transcript>,
+    black_transcript: Option<Transcript>,
+}
+
+impl GameRecord {
+    pub(crate) fn new(
+        start: Board,
+        moves: Vec<ChessMove>,
+        result: GameResult,
+        termination: Termination,
+    ) -> GameRecord {
+        GameRecord {
+            start,
+            moves,
+            result,
+            termination,
+            white_transcript: None,
+            black_transcript: None,
+        }
+    }
+
+    pub(crate) fn forfeit(start: Board, loser: Color, error: Error) -> GameRecord {
+        GameRecord {
+            start,
+            moves: vec![],
+            result: GameResult::Win(!loser),
+            termination: Termination::Forfeit(error),
+            white_transcript: None,
+            black_transcript: None,
+        }
+    }
+
+    pub fn get_start(&self) -> Board {
+        self.start
+    }
+
+    /// Every move of the game, the opening included.
+    pub fn get_moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    pub fn get_result(&self) -> GameResult {
+        self.result
+    }
+
+    pub fn get_termination(&self) -> &Termination {
+        &self.termination
+    }
+
+    /// Everything sent to and received from white during the game, if the game's
+    /// `TranscriptPolicy` kept it.
+    pub fn get_white_transcript(&self) -> Option<&Transcript> {
+        self.white_transcript.as_ref()
+    }
+
+    pub fn get_black_transcript(&self) -> Option<&Transcript> {
+        self.black_transcript.as_ref()
+    }
+}
+
+/// A move as it was played, with what the engine thought of it and how long it took.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayedMove {
+    ply: usize,
+    color: Color,
+    chess_move: ChessMove,
+    score: Option<Score>,
+    time: Duration,
+}
+
+impl PlayedMove {
+    /// How many moves were played before this one, counting the opening.
+    pub fn get_ply(&self) -> usize {
+        self.ply
+    }
+
+    pub fn get_color(&self) -> Color {
+        self.color
+    }
+
+    pub fn get_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// The score of the engine's deepest search, from its own point of view.
+    pub fn get_score(&self) -> Option<Score> {
+        self.score
+    }
+
+    /// From sending `go` to receiving the move.
+    pub fn get_time(&self) -> Duration {
+        self.time
+    }
+}
+
+/// How to play one game between two engines: where it starts, and how long each move may take.
+#[derive(Clone, Debug)]
+pub struct Game {
+    start: Board,
+    opening: Vec<ChessMove>,
+    limit: Go,
+    move_timeout: Duration,
+    transcripts: TranscriptPolicy,
+}
+
+impl Game {
+    /// Every move is searched with `limit`.
+    pub fn new(limit: Go) -> Game {
+        Game {
+            start: Board::default(),
+            opening: vec![],
+            limit,
+            move_timeout: DEFAULT_MOVE_TIMEOUT,
+            transcripts: TranscriptPolicy::Never,
+        }
+    }
+
+    pub fn start(mut self, board: Board) -> Game {
+        self.start = board;
+        self
+    }
+
+    /// Moves played from the start before the engines take over.
+    pub fn opening(mut self, moves: Vec<ChessMove>) -> Game {
+        self.opening = moves;
+        self
+    }
+
+    /// How long to wait for a move before the engine forfeits.
+    pub fn move_timeout(mut self, timeout: Duration) -> Game {
+        self.move_timeout = timeout;
+        self
+    }
+
+    pub fn transcripts(mut self, policy: TranscriptPolicy) -> Game {
+        self.transcripts = policy;
+        self
+    }
+
+    pub fn get_start(&self) -> Board {
+        self.start
+    }
+
+    pub fn get_transcripts(&self) -> TranscriptPolicy {
+        self.transcripts
+    }
+
+    pub fn get_opening(&self) -> &[ChessMove] {
+        &self.opening
+    }
+
+    pub fn get_limit(&self) -> &Go {
+        &self.limit
+    }
+
+    /// Play the game to the end.  Fails only if the opening can't be played; anything that goes
+    /// wrong with an engine during the game loses it for that engine.
+    pub fn play<'a>(
+        &self,
+        white: &mut EngineConnection<'a>,
+        black: &mut EngineConnection<'a>,
+        adjudicator: &mut Adjudicator,
+    ) -> Result<GameRecord, Error> {
+        self.play_observed(white, black, adjudicator, |_| {})
+    }
+
+    /// Like `play`, calling `on_move` after every legal move the engines make.
+    pub fn play_observed<'a, F: FnMut(PlayedMove)>(
+        &self,
+        white: &mut EngineConnection<'a>,
+        black: &mut EngineConnection<'a>,
+        adjudicator: &mut Adjudicator,
+        mut on_move: F,
+    ) -> Result<GameRecord, Error> {
+        let mut game = GameHistory::from_moves(self.start, &self.opening)?;
+        adjudicator.clear();
+        for (color, engine) in [(Color::White, &mut *white), (Color::Black, &mut *black)] {
+            if self.transcripts != TranscriptPolicy::Never {
+                engine.start_transcript();
+            }
+            if let Err(e) = engine.send_ucinewgame() {
+                return Ok(GameRecord::forfeit(self.start, color, e));
+            }
+        }
+
+        let (result, termination) = loop {
+            if let Some(end) = game_over(&game) {
+                break end;
+            }
+
+            let mover = game.get_board().side_to_move();
+            let engine = if mover == Color::White { &mut *white } else { &mut *black };
+            let started = Instant::now();
+            let (chess_move, score) = match self.search(engine, &game) {
+                Ok((Some(chess_move), score)) => (chess_move, score),
+                // `game_over` has already made sure there is something to play.
+                Ok((None, _)) => break (GameResult::Win(!mover), Termination::NullMove),
+                Err(Error::IllegalBestMove { mv, .. }) => {
+                    break (GameResult::Win(!mover), Termination::IllegalMove(mv))
+                }
+                Err(e) => break (GameResult::Win(!mover), Termination::Forfeit(e)),
+            };
+            let ply = game.get_moves().len();
+            if game.push(chess_move).is_err() {
+                break (GameResult::Win(!mover), Termination::IllegalMove(chess_move));
+            }
+            on_move(PlayedMove {
+                ply,
+                color: mover,
+                chess_move,
+                score,
+                time: started.elapsed(),
+            });
+            if let Some(adjudication) = adjudicator.update(&game, score) {
+                break (adjudication.get_result(), Termination::Adjudication(adjudication));
+            }
+        };
+
+        let keep = match self.transcripts {
+            TranscriptPolicy::Never => false,
+            TranscriptPolicy::Always => true,
+            TranscriptPolicy::Abnormal => termination.is_abnormal(),
+        };
+        let (white_transcript, black_transcript) = (white.take_transcript(), black.take_transcript());
+        Ok(GameRecord {
+            start: self.start,
+            moves: game.get_moves().to_vec(),
+            result,
+            termination,
+            white_transcript: white_transcript.filter(|_| keep),
+            black_transcript: black_transcript.filter(|_| keep),
+        })
+    }
+
+    fn search(
+        &self,
+        engine: &mut EngineConnection,
+        game: &GameHistory,
+    ) -> Result<(Option<ChessMove>, Option<Score>), Error> {
+        engine.send_position(game.get_start(), game.get_moves().to_vec())?;
+        engine.send_go_with(self.limit.clone())?;
+        let best_move = engine.recv_best_move_timeout(self.move_timeout)?;
+        let score = engine.analysis_history().get_last().and_then(|info| info.get_score());
+        Ok((best_move.get_move(), score))
+    }
+}
+
+pub(crate) fn game_over(game: &GameHistory) -> Option<(GameResult, Termination)> {
+    let board = game.get_board();
+    match board.status() {
+        BoardStatus::Checkmate => Some((GameResult::Win(!board.side_to_move()), Termination::Checkmate)),
+        BoardStatus::Stalemate => Some((GameResult::Draw, Termination::Stalemate)),
+        BoardStatus::Ongoing if game.is_threefold() => Some((GameResult::Draw, Termination::Repetition)),
+        BoardStatus::Ongoing if game.is_fifty_move() => Some((GameResult::Draw, Termination::FiftyMoves)),
+        BoardStatus::Ongoing => None,
+    }
+}
+
+#[test]
+fn test_play_game() {
+    let address = fake_engine(false);
+    let (mut white, mut black) = (connect_fake(&address), connect_fake(&address));
+    let mut adjudicator = Adjudicator::new();
+
+    // Both kings can only shuffle, so the game is drawn by repetition.
+    let start = Board::from_str("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    let record = Game::new(Go::depth(1))
+        .start(start)
+        .play(&mut white, &mut black, &mut adjudicator)
+        .unwrap();
+    assert_eq!(record.get_result(), GameResult::Draw);
+    assert_eq!(record.get_termination(), &Termination::Repetition);
+    assert_eq!(record.get_start(), start);
+    assert!(record.get_moves().len() >= 8);
+
+    let mut adjudicator = Adjudicator::new().draw(10, 1, 0);
+    let mut played = vec![];
+    let record = Game::new(Go::depth(1))
+        .play_observed(&mut white, &mut black, &mut adjudicator, |m| played.push(m))
+        .unwrap();
+    assert!(matches!(record.get_termination(), Termination::Adjudication(_)));
+    assert_eq!(record.get_moves().len(), 2);
+    assert_eq!(played.len(), 2);
+    assert_eq!(played[1].get_ply(), 1);
+    assert_eq!(played[1].get_color(), Color::Black);
+    assert_eq!(played[1].get_move(), record.get_moves()[1]);
+    assert_eq!(played[1].get_score(), Some(Score::cp(0)));
+}
+
+#[test]
+fn test_illegal_move_loses() {
+    let (good, bad) = (fake_engine(false), fake_engine(true));
+    let (mut white, mut black) = (connect_fake(&good), connect_fake(&bad));
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+
+    let record = Game::new(Go::depth(1))
+        .opening(vec![e2e4])
+        .play(&mut white, &mut black, &mut Adjudicator::new())
+        .unwrap();
+    assert_eq!(record.get_result(), GameResult::Win(Color::White));
+    assert!(matches!(record.get_termination(), Termination::IllegalMove(_)));
+    assert_eq!(record.get_moves(), &[e2e4]);
+
+    let e2e5 = ChessMove::new(Square::E2, Square::E5, None);
+    assert!(Game::new(Go::depth(1))
+        .opening(vec![e2e5])
+        .play(&mut white, &mut black, &mut Adjudicator::new())
+        .is_err());
+}
+
+#[test]
+fn test_game_transcripts() {
+    let (good, bad) = (fake_engine(false), fake_engine(true));
+    let (mut white, mut black) = (connect_fake(&good), connect_fake(&bad));
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let game = Game::new(Go::depth(1)).opening(vec![e2e4]);
+
+    let record = game.play(&mut white, &mut black, &mut Adjudicator::new()).unwrap();
+    assert_eq!(record.get_white_transcript(), None);
+
+    let record = game
+        .clone()
+        .transcripts(TranscriptPolicy::Abnormal)
+        .play(&mut white, &mut black, &mut Adjudicator::new())
+        .unwrap();
+    assert!(!record.get_white_transcript().unwrap().get_lines().is_empty());
+    let black_lines = record
+        .get_black_transcript()
+        .unwrap()
+        .get_lines()
+        .iter()
+        .map(|line| line.get_line())
+        .collect::<Vec<&str>>();
+    assert_eq!(black_lines[0], "ucinewgame");
+    assert_eq!(black_lines[black_lines.len() - 1], "bestmove a1a5");
+
+    // Kept only when something went wrong.
+    let mut opponent = connect_fake(&good);
+    let record = game
+        .transcripts(TranscriptPolicy::Abnormal)
+        .play(&mut white, &mut opponent, &mut Adjudicator::new().draw(10, 1, 0))
+        .unwrap();
+    assert!(!record.get_termination().is_abnormal());
+    assert_eq!(record.get_black_transcript(), None);
+}
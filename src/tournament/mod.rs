@@ -0,0 +1,4 @@
+pub mod crosstable;
+pub mod game;
+pub mod openings;
+pub mod runner;
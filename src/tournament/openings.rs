@@ -0,0 +1,317 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use chess::{Board, ChessMove};
+
+use error::Error;
+use rng::Rng;
+use san::from_san;
+use super::game::Game;
+
+/// A position to start a game from, and the moves already played from it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpeningLine {
+    start: Board,
+    moves: Vec<ChessMove>,
+}
+
+impl OpeningLine {
+    pub fn new(start: Board, moves: Vec<ChessMove>) -> OpeningLine {
+        OpeningLine { start, moves }
+    }
+
+    pub fn get_start(&self) -> Board {
+        self.start
+    }
+
+    pub fn get_moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    /// `game`, played from this opening.
+    pub fn apply(&self, game: &Game) -> Game {
+        game.clone().start(self.start).opening(self.moves.clone())
+    }
+}
+
+/// The order openings are handed out in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OpeningOrder {
+    Sequential,
+    /// Shuffled, the same way every time for the same seed.
+    Random(u64),
+}
+
+/// The openings to play a match or tournament from.
+///
+/// Openings are handed out by game number, so games can be played in any order, or at the same
+/// time, and still get the same openings.  Once every opening has been used, they are used
+/// again in the same order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpeningSet {
+    openings: Vec<OpeningLine>,
+    order: Vec<usize>,
+    mirrored: bool,
+}
+
+fn opening_error(line: usize, what: &str) -> Error {
+    Error::ConfigError(format!("{} on line {}", what, line))
+}
+
+// Split a PGN tag pair such as `[FEN "8/8/8/8/8/8/8/8 w - - 0 1"]` into its name and value.
+fn parse_tag(tag: &str) -> Option<(&str, &str)> {
+    let inner = tag.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name, value))
+}
+
+// Drop comments, variations and everything else that isn't a move from PGN movetext.
+fn movetext_tokens(text: &str) -> Vec<String> {
+    let mut cleaned = String::new();
+    let mut depth = 0;
+    let mut in_comment = false;
+    let mut in_line_comment = false;
+    for c in text.chars() {
+        match c {
+            '\n' if in_line_comment => in_line_comment = false,
+            _ if in_line_comment => {}
+            '}' if in_comment => in_comment = false,
+            _ if in_comment => {}
+            '{' => in_comment = true,
+            ';' => in_line_comment = true,
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ if depth > 0 => {}
+            c => cleaned.push(c),
+        }
+        if matches!(c, '{' | '}' | '(' | ')') {
+            cleaned.push(' ');
+        }
+    }
+
+    cleaned
+        .split_whitespace()
+        .filter(|token| !["1-0", "0-1", "1/2-1/2", "*"].contains(token) && !token.starts_with('$'))
+        .map(|token| {
+            // Move numbers, which may be written up against the move: `1.`, `3...` or `12.Nf3`.
+            match token.split_once('.') {
+                Some((number, rest)) if number.bytes().all(|b| b.is_ascii_digit()) => {
+                    rest.trim_start_matches('.')
+                }
+                _ => token,
+            }
+        })
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_string())
+        .collect()
+}
+
+impl OpeningSet {
+    pub fn new(openings: Vec<OpeningLine>) -> OpeningSet {
+        let order = (0..openings.len()).collect();
+        OpeningSet {
+            openings,
+            order,
+            mirrored: false,
+        }
+    }
+
+    /// Every game of a PGN file, up to its last move.  A game starts from its `FEN` tag, if it
+    /// has one.
+    pub fn from_pgn(text: &str) -> Result<OpeningSet, Error> {
+        let mut openings = vec![];
+        let mut start = Board::default();
+        let mut movetext = String::new();
+        // Where the current game's moves start, for error messages.
+        let mut game_line = 1;
+
+        let mut finish = |start: Board, movetext: &str, line: usize| -> Result<(), Error> {
+            let mut board = start;
+            let mut moves = vec![];
+            for san in movetext_tokens(movetext) {
+                let m = from_san(&board, &san).map_err(|_| {
+                    opening_error(line, &format!("illegal move {} in the game", san))
+                })?;
+                board = board.make_move_new(m);
+                moves.push(m);
+            }
+            openings.push(OpeningLine::new(start, moves));
+            Ok(())
+        };
+
+        for (i, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                if !movetext.trim().is_empty() {
+                    finish(start, &movetext, game_line)?;
+                    start = Board::default();
+                    movetext.clear();
+                }
+                match parse_tag(trimmed) {
+                    Some(("FEN", fen)) => {
+                        start = Board::from_str(fen)
+                            .map_err(|_| opening_error(i + 1, "invalid FEN tag"))?;
+                    }
+                    Some(_) => {}
+                    None => return Err(opening_error(i + 1, "invalid tag")),
+                }
+                game_line = i + 2;
+            } else {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+        if !movetext.trim().is_empty() {
+            finish(start, &movetext, game_line)?;
+        }
+
+        Ok(OpeningSet::new(openings))
+    }
+
+    /// One position per line of an EPD file.  Operations after the position, such as `bm` or
+    /// `id`, are ignored.
+    pub fn from_epd(text: &str) -> Result<OpeningSet, Error> {
+        let mut openings = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let fields = line.split_whitespace().take(4).collect::<Vec<&str>>();
+            if fields.is_empty() {
+                continue;
+            }
+            if fields.len() < 4 {
+                return Err(opening_error(i + 1, "incomplete position"));
+            }
+            let fen = format!("{} 0 1", fields.join(" "));
+            let board = Board::from_str(&fen).map_err(|_| opening_error(i + 1, "invalid position"))?;
+            openings.push(OpeningLine::new(board, vec![]));
+        }
+        Ok(OpeningSet::new(openings))
+    }
+
+    /// Read a PGN or EPD file, going by its extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<OpeningSet, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("epd") => OpeningSet::from_epd(&text),
+            _ => OpeningSet::from_pgn(&text),
+        }
+    }
+
+    pub fn order(mut self, order: OpeningOrder) -> OpeningSet {
+        self.order = (0..self.openings.len()).collect();
+        if let OpeningOrder::Random(seed) = order {
+            // Fisher-Yates, with a seeded generator so that the order is the same everywhere.
+            let mut rng = Rng::new(seed);
+            for i in (1..self.order.len()).rev() {
+                self.order.swap(i, rng.below(i as u64 + 1) as usize);
+            }
+        }
+        self
+    }
+
+    /// Play every opening twice, so both engines get to play each side of it.
+    pub fn mirrored(mut self, mirrored: bool) -> OpeningSet {
+        self.mirrored = mirrored;
+        self
+    }
+
+    pub fn is_mirrored(&self) -> bool {
+        self.mirrored
+    }
+
+    pub fn len(&self) -> usize {
+        self.openings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.openings.is_empty()
+    }
+
+    /// The `n`th opening in order, counting each one once even if mirrored.  `None` if there
+    /// are no openings at all.
+    pub fn get(&self, n: usize) -> Option<&OpeningLine> {
+        if self.openings.is_empty() {
+            return None;
+        }
+        Some(&self.openings[self.order[n % self.order.len()]])
+    }
+
+    /// The opening for game `game` of a match between two engines that swap colours every game.
+    pub fn get_for_game(&self, game: usize) -> Option<&OpeningLine> {
+        self.get(if self.mirrored { game / 2 } else { game })
+    }
+}
+
+#[cfg(test)]
+use chess::Square;
+
+#[test]
+fn test_openings_from_pgn() {
+    let pgn = r#"[Event "Openings"]
+[Result "*"]
+
+1. e4 {The king's pawn} e5 (1... c5 2. Nf3) 2. Nf3 $1 Nc6 ; the knights
+3.Bb5 a6 4.O-O *
+
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+[SetUp "1"]
+1. e4 Kd7 1/2-1/2
+"#;
+    let openings = OpeningSet::from_pgn(pgn).unwrap();
+
+    assert_eq!(openings.len(), 2);
+    let ruy_lopez = openings.get(0).unwrap();
+    assert_eq!(ruy_lopez.get_start(), Board::default());
+    assert_eq!(ruy_lopez.get_moves().len(), 7);
+    assert_eq!(ruy_lopez.get_moves()[4], ChessMove::new(Square::F1, Square::B5, None));
+
+    let endgame = openings.get(1).unwrap();
+    assert_eq!(endgame.get_start().to_string(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+    assert_eq!(endgame.get_moves().len(), 2);
+
+    assert_eq!(
+        OpeningSet::from_pgn("[Event \"x\"]\n1. e4 e4 *\n"),
+        Err(Error::ConfigError("illegal move e4 in the game on line 2".to_string()))
+    );
+}
+
+#[test]
+fn test_openings_from_epd() {
+    let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"1. e4\";\n\n\
+               4k3/8/8/8/8/8/4P3/4K3 w - - bm e4;\n";
+    let openings = OpeningSet::from_epd(epd).unwrap();
+
+    assert_eq!(openings.len(), 2);
+    assert!(openings.get(1).unwrap().get_moves().is_empty());
+    assert!(OpeningSet::from_epd("8/8/8 w -\n").is_err());
+}
+
+#[test]
+fn test_opening_order() {
+    let boards = [
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/3K4 w - - 0 1",
+        "4k3/8/8/8/8/8/8/2K5 w - - 0 1",
+        "4k3/8/8/8/8/8/8/1K6 w - - 0 1",
+    ];
+    let lines = boards
+        .iter()
+        .map(|fen| OpeningLine::new(Board::from_str(fen).unwrap(), vec![]))
+        .collect::<Vec<OpeningLine>>();
+    let sequential = OpeningSet::new(lines.clone()).mirrored(true);
+
+    assert_eq!(sequential.get_for_game(2), Some(&lines[1]));
+    assert_eq!(sequential.get_for_game(3), Some(&lines[1]));
+    assert_eq!(sequential.get_for_game(8), Some(&lines[0]));
+
+    let random = OpeningSet::new(lines.clone()).order(OpeningOrder::Random(7));
+    let order = (0..4).map(|n| random.get(n).unwrap()).collect::<Vec<&OpeningLine>>();
+    assert_eq!(random.clone().order(OpeningOrder::Random(7)), random);
+    for line in &lines {
+        assert!(order.contains(&line));
+    }
+    assert_eq!(random.get(4), random.get(0));
+    assert_eq!(OpeningSet::new(vec![]).get(0), None);
+}
@@ -0,0 +1,387 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use chess::Color;
+
+use adjudication::Adjudicator;
+use engine_config::EngineConfig;
+use engine_connection::{EngineConnection, EngineConnectionBuilder};
+use error::Error;
+use super::crosstable::Crosstable;
+use super::game::{Game, GameRecord, PlayedMove};
+use super::openings::OpeningSet;
+
+#[cfg(test)]
+use test_engines::{connect_fake, fake_engine};
+#[cfg(test)]
+use chess::Board;
+#[cfg(test)]
+use game_result::GameResult;
+#[cfg(test)]
+use gui::go::Go;
+#[cfg(test)]
+use std::str::FromStr;
+
+/// Who plays whom.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TournamentFormat {
+    /// Every engine plays every other engine.
+    RoundRobin,
+    /// The first engine plays every other engine, which don't play each other.
+    Gauntlet,
+}
+
+/// One game of a tournament: the round it is in, and the engines playing it, by index.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pairing {
+    round: usize,
+    white: usize,
+    black: usize,
+    opening: usize,
+}
+
+impl Pairing {
+    pub fn get_round(&self) -> usize {
+        self.round
+    }
+
+    pub fn get_white(&self) -> usize {
+        self.white
+    }
+
+    pub fn get_black(&self) -> usize {
+        self.black
+    }
+
+    /// Which of the tournament's openings the game is played from.
+    pub fn get_opening(&self) -> usize {
+        self.opening
+    }
+}
+
+/// What is happening in a tournament, as it happens.  Events about one game arrive in order,
+/// but those of games played at the same time are interleaved.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum TournamentEvent {
+    GameStarted(Pairing),
+    MoveMade(Pairing, PlayedMove),
+    GameEnded(Pairing, GameRecord),
+    /// The standings, after every game.
+    CrosstableUpdated(Crosstable),
+    /// Every game of the round, and of the rounds before it, is over.
+    RoundFinished(usize, Crosstable),
+}
+
+// What the workers playing the games tell the thread running the tournament.
+#[allow(clippy::large_enum_variant)]
+enum Progress {
+    Started(Pairing),
+    Moved(Pairing, PlayedMove),
+    Ended(Pairing, Result<GameRecord, Error>),
+}
+
+type Connector = dyn Fn(&EngineConfig) -> Result<EngineConnection<'static>, Error> + Send + Sync;
+type AdjudicatorFactory = dyn Fn() -> Adjudicator + Send + Sync;
+
+/// Plays a round robin or a gauntlet between engines, several games at a time.
+///
+/// Each game gets fresh engine processes, which are told to quit when it ends.  In every round
+/// each pair of engines plays once; the colours alternate from game to game and round to round.
+pub struct Tournament {
+    engines: Vec<EngineConfig>,
+    game: Game,
+    format: TournamentFormat,
+    rounds: usize,
+    concurrency: usize,
+    openings: Option<OpeningSet>,
+    connector: Box<Connector>,
+    adjudicator: Box<AdjudicatorFactory>,
+}
+
+impl Tournament {
+    /// A single round robin between `engines`, each game played as `game` says, one at a time.
+    pub fn new(engines: Vec<EngineConfig>, game: Game) -> Tournament {
+        Tournament {
+            engines,
+            game,
+            format: TournamentFormat::RoundRobin,
+            rounds: 1,
+            concurrency: 1,
+            openings: None,
+            connector: Box::new(|config| EngineConnectionBuilder::from_config(config)?.connect()),
+            adjudicator: Box::new(Adjudicator::new),
+        }
+    }
+
+    pub fn format(mut self, format: TournamentFormat) -> Tournament {
+        self.format = format;
+        self
+    }
+
+    pub fn rounds(mut self, rounds: usize) -> Tournament {
+        self.rounds = rounds;
+        self
+    }
+
+    /// How many games to play at once.
+    pub fn concurrency(mut self, concurrency: usize) -> Tournament {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Play each round from the next openings of `openings`.  If they are mirrored, each pair of
+    /// engines plays the same opening in two rounds in a row, with the colours swapped.
+    pub fn openings(mut self, openings: OpeningSet) -> Tournament {
+        self.openings = Some(openings);
+        self
+    }
+
+    /// Start engines with `connector` instead of running the command of their config.
+    pub fn connector<F>(mut self, connector: F) -> Tournament
+    where
+        F: Fn(&EngineConfig) -> Result<EngineConnection<'static>, Error> + Send + Sync + 'static,
+    {
+        self.connector = Box::new(connector);
+        self
+    }
+
+    /// Adjudicate every game with a new adjudicator from `factory`.
+    pub fn adjudicator<F>(mut self, factory: F) -> Tournament
+    where
+        F: Fn() -> Adjudicator + Send + Sync + 'static,
+    {
+        self.adjudicator = Box::new(factory);
+        self
+    }
+
+    pub fn get_engines(&self) -> &[EngineConfig] {
+        &self.engines
+    }
+
+    /// Every game to be played, round by round.
+    pub fn schedule(&self) -> Vec<Pairing> {
+        let n = self.engines.len();
+        let pairs: Vec<(usize, usize)> = match self.format {
+            TournamentFormat::RoundRobin => (0..n)
+                .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+                .collect(),
+            TournamentFormat::Gauntlet => (1..n).map(|b| (0, b)).collect(),
+        };
+
+        let mirrored = self.openings.as_ref().is_some_and(|o| o.is_mirrored());
+        let mut pairings = vec![];
+        for round in 0..self.rounds {
+            let first_opening = if mirrored { round / 2 } else { round } * pairs.len();
+            for (i, &(a, b)) in pairs.iter().enumerate() {
+                let (white, black) = if (i + round) % 2 == 0 { (a, b) } else { (b, a) };
+                pairings.push(Pairing {
+                    round,
+                    white,
+                    black,
+                    opening: first_opening + i,
+                });
+            }
+        }
+        pairings
+    }
+
+    /// Play every game, calling `on_event` as games start, moves are made and games and rounds
+    /// finish, and return the final crosstable.  Fails if a game can't be started, such as when
+    /// the opening is illegal.
+    pub fn run<F: FnMut(TournamentEvent)>(&self, mut on_event: F) -> Result<Crosstable, Error> {
+        let pairings = self.schedule();
+        let mut remaining = vec![0; self.rounds];
+        for pairing in &pairings {
+            remaining[pairing.round] += 1;
+        }
+        let queue = Mutex::new(pairings.into_iter().collect::<VecDeque<Pairing>>());
+        let names = self.engines.iter().map(|e| e.get_name().to_string()).collect();
+        let mut crosstable = Crosstable::new(names);
+        let mut next_round = 0;
+        let mut error = None;
+
+        thread::scope(|scope| {
+            let (tx, rx) = channel();
+            for _ in 0..self.concurrency {
+                let tx = tx.clone();
+                let queue = &queue;
+                scope.spawn(move || loop {
+                    let pairing = match queue.lock().unwrap().pop_front() {
+                        Some(pairing) => pairing,
+                        None => break,
+                    };
+                    let _ = tx.send(Progress::Started(pairing));
+                    let record = self.play(pairing, &tx);
+                    if tx.send(Progress::Ended(pairing, record)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+
+            for progress in rx {
+                let (pairing, record) = match progress {
+                    Progress::Started(pairing) => {
+                        on_event(TournamentEvent::GameStarted(pairing));
+                        continue;
+                    }
+                    Progress::Moved(pairing, played) => {
+                        on_event(TournamentEvent::MoveMade(pairing, played));
+                        continue;
+                    }
+                    Progress::Ended(pairing, Ok(record)) => (pairing, record),
+                    Progress::Ended(_, Err(e)) => {
+                        // Let the games already being played finish, but start no more.
+                        queue.lock().unwrap().clear();
+                        error.get_or_insert(e);
+                        continue;
+                    }
+                };
+                crosstable.add(pairing.white, pairing.black, record.get_result());
+                on_event(TournamentEvent::GameEnded(pairing, record));
+                on_event(TournamentEvent::CrosstableUpdated(crosstable.clone()));
+
+                remaining[pairing.round] -= 1;
+                while next_round < self.rounds && remaining[next_round] == 0 {
+                    on_event(TournamentEvent::RoundFinished(next_round, crosstable.clone()));
+                    next_round += 1;
+                }
+            }
+        });
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(crosstable),
+        }
+    }
+
+    /// `run`, sending every event to `events`.  The tournament goes on even if nothing is
+    /// receiving them any more.
+    pub fn run_to(&self, events: Sender<TournamentEvent>) -> Result<Crosstable, Error> {
+        self.run(|event| {
+            let _ = events.send(event);
+        })
+    }
+
+    fn play(&self, pairing: Pairing, progress: &Sender<Progress>) -> Result<GameRecord, Error> {
+        let game = match self.openings.as_ref().and_then(|o| o.get(pairing.opening)) {
+            Some(opening) => opening.apply(&self.game),
+            None => self.game.clone(),
+        };
+        let start = game.get_start();
+        let mut white = match (self.connector)(&self.engines[pairing.white]) {
+            Ok(white) => white,
+            Err(e) => return Ok(GameRecord::forfeit(start, Color::White, e)),
+        };
+        let mut black = match (self.connector)(&self.engines[pairing.black]) {
+            Ok(black) => black,
+            Err(e) => {
+                let _ = white.send_quit();
+                return Ok(GameRecord::forfeit(start, Color::Black, e));
+            }
+        };
+
+        let on_move = |played| {
+            let _ = progress.send(Progress::Moved(pairing, played));
+        };
+        let record = game.play_observed(&mut white, &mut black, &mut (self.adjudicator)(), on_move);
+        let _ = white.send_quit();
+        let _ = black.send_quit();
+        record
+    }
+}
+
+#[test]
+fn test_schedule() {
+    let engines = vec![
+        EngineConfig::new("A", "a"),
+        EngineConfig::new("B", "b"),
+        EngineConfig::new("C", "c"),
+    ];
+    let tournament = Tournament::new(engines, Game::new(Go::depth(1))).rounds(2);
+    let games = |schedule: Vec<Pairing>| {
+        schedule
+            .iter()
+            .map(|p| (p.get_round(), p.get_white(), p.get_black()))
+            .collect::<Vec<(usize, usize, usize)>>()
+    };
+
+    assert_eq!(
+        games(tournament.schedule()),
+        vec![(0, 0, 1), (0, 2, 0), (0, 1, 2), (1, 1, 0), (1, 0, 2), (1, 2, 1)]
+    );
+    let gauntlet = tournament.format(TournamentFormat::Gauntlet);
+    assert_eq!(
+        games(gauntlet.schedule()),
+        vec![(0, 0, 1), (0, 2, 0), (1, 1, 0), (1, 0, 2)]
+    );
+
+    let mirrored = gauntlet.rounds(4).openings(OpeningSet::new(vec![]).mirrored(true));
+    let openings = mirrored.schedule().iter().map(|p| p.get_opening()).collect::<Vec<usize>>();
+    assert_eq!(openings, vec![0, 1, 0, 1, 2, 3, 2, 3]);
+}
+
+#[test]
+fn test_round_robin() {
+    let (good, bad) = (fake_engine(false), fake_engine(true));
+    let engines = vec![
+        EngineConfig::new("First", "first"),
+        EngineConfig::new("Illegal", "illegal"),
+        EngineConfig::new("Second", "second"),
+    ];
+    // Only the kings are left, so games between the good engines end quickly in a repetition.
+    let start = Board::from_str("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    let openings = OpeningSet::from_epd("7k/8/8/8/8/8/8/K7 w - -\n").unwrap();
+    let tournament = Tournament::new(engines, Game::new(Go::depth(1)))
+        .openings(openings)
+        .rounds(2)
+        .concurrency(3)
+        .connector(move |config| match config.get_name() {
+            "Illegal" => Ok(connect_fake(&bad)),
+            _ => Ok(connect_fake(&good)),
+        });
+
+    let (mut started, mut games, mut updates) = (0, 0, 0);
+    let mut moves = [0; 6];
+    let mut rounds = vec![];
+    let crosstable = tournament
+        .run(|event| match event {
+            TournamentEvent::GameStarted(_) => started += 1,
+            // Every game has its own opening number, as none are mirrored.
+            TournamentEvent::MoveMade(pairing, _) => moves[pairing.get_opening()] += 1,
+            TournamentEvent::GameEnded(pairing, record) => {
+                games += 1;
+                assert_eq!(record.get_start(), start);
+                assert_eq!(record.get_moves().len(), moves[pairing.get_opening()]);
+                if pairing.get_white() == 1 {
+                    assert_eq!(record.get_result(), GameResult::Win(Color::Black));
+                }
+            }
+            TournamentEvent::CrosstableUpdated(crosstable) => {
+                updates += 1;
+                let played: u32 = (0..3).map(|i| crosstable.get_total(i).get_games()).sum();
+                assert_eq!(played, 2 * updates);
+            }
+            TournamentEvent::RoundFinished(round, _) => rounds.push(round),
+        })
+        .unwrap();
+
+    assert_eq!((started, games, updates), (6, 6, 6));
+    assert_eq!(rounds, vec![0, 1]);
+    assert_eq!(crosstable.get_total(1).get_score(), 0.0);
+    assert_eq!(crosstable.get_total(0).get_score(), 3.0);
+    assert_eq!(crosstable.get_record(0, 2).get_draws(), 2);
+    assert_eq!(crosstable.standings()[2], 1);
+
+    // An engine that can't be started loses its games.
+    let engines = vec![EngineConfig::new("A", "a"), EngineConfig::new("B", "b")];
+    let failing =
+        Tournament::new(engines, Game::new(Go::depth(1))).connector(|_| Err(Error::EngineDeadError));
+    let (tx, rx) = channel();
+    let crosstable = failing.run_to(tx).unwrap();
+    assert_eq!(crosstable.get_total(1).get_wins(), 1);
+    assert_eq!(rx.iter().count(), 4);
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Which way a line went between us and the engine.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// One line of a transcript, and when it was sent or received.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TranscriptLine {
+    at: Duration,
+    direction: Direction,
+    line: String,
+}
+
+impl TranscriptLine {
+    /// How long after the transcript was started.
+    pub fn get_at(&self) -> Duration {
+        self.at
+    }
+
+    pub fn get_direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The line, without its newline.
+    pub fn get_line(&self) -> &str {
+        &self.line
+    }
+}
+
+/// Everything sent to and received from an engine over some stretch of time, such as a game.
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    started: Instant,
+    lines: Vec<TranscriptLine>,
+}
+
+impl Transcript {
+    pub fn new() -> Transcript {
+        Transcript {
+            started: Instant::now(),
+            lines: vec![],
+        }
+    }
+
+    pub fn push(&mut self, direction: Direction, line: &str) {
+        self.lines.push(TranscriptLine {
+            at: self.started.elapsed(),
+            direction,
+            line: line.trim_end().to_string(),
+        });
+    }
+
+    pub fn get_lines(&self) -> &[TranscriptLine] {
+        &self.lines
+    }
+}
+
+impl Default for Transcript {
+    fn default() -> Transcript {
+        Transcript::new()
+    }
+}
+
+// Two transcripts are the same if they hold the same lines, whenever they were started.
+impl PartialEq for Transcript {
+    fn eq(&self, other: &Transcript) -> bool {
+        self.lines == other.lines
+    }
+}
+
+// The time in seconds, and `>` for a line sent to the engine or `<` for one it sent back.
+impl fmt::Display for TranscriptLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arrow = match self.direction {
+            Direction::Sent => '>',
+            Direction::Received => '<',
+        };
+        write!(f, "[{:>9.3}] {} {}", self.at.as_secs_f64(), arrow, self.line)
+    }
+}
+
+// One line each.
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_transcript() {
+    let mut transcript = Transcript::new();
+    transcript.push(Direction::Sent, "isready\n");
+    transcript.push(Direction::Received, "readyok");
+
+    assert_eq!(transcript.get_lines().len(), 2);
+    assert_eq!(transcript.get_lines()[0].get_line(), "isready");
+    assert_eq!(transcript.get_lines()[1].get_direction(), Direction::Received);
+    assert!(transcript.get_lines()[0].get_at() <= transcript.get_lines()[1].get_at());
+
+    let text = transcript.to_string();
+    assert!(text.starts_with("[    0.000] > isready\n"));
+    assert!(text.ends_with("] < readyok\n"));
+}
@@ -0,0 +1,52 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use error::Error;
+use transport::process_controls::ProcessControls;
+
+mod platform;
+pub mod process;
+pub mod process_controls;
+pub mod tcp;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+pub type TransportReader = Box<dyn Read + Send>;
+pub type TransportWriter = Box<dyn Write + Send>;
+
+/// A way of reaching an engine: something that, once opened, gives a stream of the engine's
+/// output and a stream to write commands to.
+///
+/// `EngineConnection` reads the engine's output on its own thread, so the reader must be `Send`.
+pub trait Transport: Send {
+    fn open(&mut self) -> Result<(TransportReader, TransportWriter), Error>;
+
+    /// Run the engine's process with `controls`, for a transport that starts one.  Returns
+    /// whether it does.
+    fn set_process_controls(&mut self, _controls: ProcessControls) -> bool {
+        false
+    }
+
+    /// What was applied to the engine's process when it was opened, for a transport that starts
+    /// one.
+    fn get_applied_controls(&self) -> Option<&ProcessControls> {
+        None
+    }
+
+    /// The id of the engine's process, once opened, for a transport that starts one.
+    fn get_process_id(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Stands in for the writer of a connection that has been closed: every write fails.
+pub(crate) struct ClosedWriter;
+
+impl Write for ClosedWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(ErrorKind::BrokenPipe, "the connection was closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
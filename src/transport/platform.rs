@@ -0,0 +1,314 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// How often a process is checked while waiting for it to exit.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The program at `path`, to be run in `working_dir`.  A relative path with a directory in it,
+/// like `./stockfish`, is taken to be in the working directory, as GUIs take it, and made
+/// absolute.  Left to the platform, Unix would find it there but Windows would look in this
+/// process's directory.
+fn program(path: &str, working_dir: Option<&str>) -> PathBuf {
+    let program = Path::new(path);
+    match working_dir {
+        Some(dir) if program.is_relative() && program.components().count() > 1 => {
+            let program = Path::new(dir).join(program);
+            match env::current_dir() {
+                Ok(current) if program.is_relative() => current.join(program),
+                _ => program,
+            }
+        }
+        _ => program.to_path_buf(),
+    }
+}
+
+/// A command to run `path` with `args`, each passed as one argument however many spaces it has,
+/// in `working_dir`, as the root of a process tree that `ProcessTree` can kill.
+pub(crate) fn command(path: &str, args: &[String], working_dir: Option<&str>) -> Command {
+    let mut command = Command::new(program(path, working_dir));
+    command.args(args);
+    if let Some(dir) = working_dir {
+        command.current_dir(dir);
+    }
+    sys::configure(&mut command);
+    command
+}
+
+/// A running engine and any processes it starts, killed together when this is dropped.
+///
+/// On Unix the engine leads a process group of its own, and the group is killed if the engine is
+/// still running by then, as its id can be reused once it has exited.  On Windows it is put in a
+/// job object that kills everything in it once closed.
+pub(crate) struct ProcessTree {
+    child: Child,
+    exit_grace: Duration,
+    job: sys::Job,
+    // Whether the engine has exited and been waited for, after which its id may be reused.
+    reaped: bool,
+}
+
+impl ProcessTree {
+    /// Look after `child`, started from a `command`.  On drop it is given `exit_grace` to exit by
+    /// itself first.
+    pub fn new(child: Child, exit_grace: Duration) -> ProcessTree {
+        let job = sys::Job::new(&child);
+        ProcessTree {
+            child,
+            exit_grace,
+            job,
+            reaped: false,
+        }
+    }
+
+    // Kill the engine and everything it started.
+    fn kill(&mut self) {
+        self.job.kill(&self.child, self.reaped);
+        // In case it couldn't be killed as a tree.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    fn wait_for_exit(&mut self) {
+        let deadline = Instant::now() + self.exit_grace;
+        loop {
+            match self.child.try_wait() {
+                Ok(None) if Instant::now() < deadline => sleep(EXIT_POLL_INTERVAL),
+                Ok(None) | Err(_) => return,
+                Ok(Some(_)) => {
+                    self.reaped = true;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ProcessTree {
+    // Anything the engine started is killed even if it exited by itself, where the platform
+    // allows.
+    fn drop(&mut self) {
+        self.wait_for_exit();
+        self.kill();
+    }
+}
+
+/// The engine's stdin, which keeps its process tree alive.  Dropping it closes stdin first, the
+/// usual way to ask an engine to exit, then waits for the engine and kills the tree.
+pub(crate) struct ProcessWriter {
+    // Dropped before the tree, as fields are dropped in order.
+    stdin: ChildStdin,
+    _tree: ProcessTree,
+}
+
+impl ProcessWriter {
+    pub fn new(stdin: ChildStdin, tree: ProcessTree) -> ProcessWriter {
+        ProcessWriter { stdin, _tree: tree }
+    }
+}
+
+impl Write for ProcessWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdin.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.flush()
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::c_int;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Child, Command};
+
+    const SIGKILL: c_int = 9;
+
+    extern "C" {
+        fn kill(pid: c_int, sig: c_int) -> c_int;
+    }
+
+    pub fn configure(command: &mut Command) {
+        command.process_group(0);
+    }
+
+    // The process group stands in for a job.
+    pub struct Job;
+
+    impl Job {
+        pub fn new(_child: &Child) -> Job {
+            Job
+        }
+
+        // Once the engine has been reaped the group's id is free for another group to take, so
+        // what the engine left running can't safely be killed.
+        pub fn kill(&self, child: &Child, reaped: bool) {
+            if reaped {
+                return;
+            }
+            // The group's id is its leader's, the engine.
+            unsafe {
+                kill(-(child.id() as c_int), SIGKILL);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::mem::size_of;
+    use std::os::raw::c_void;
+    use std::os::windows::io::AsRawHandle;
+    use std::os::windows::process::CommandExt;
+    use std::process::{Child, Command};
+    use std::ptr;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const JOB_OBJECT_EXTENDED_LIMIT_INFORMATION: i32 = 9;
+    const JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE: u32 = 0x2000;
+
+    // JOBOBJECT_EXTENDED_LIMIT_INFORMATION and what it holds, of which only the limit flags are
+    // set.
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct BasicLimitInformation {
+        per_process_user_time_limit: i64,
+        per_job_user_time_limit: i64,
+        limit_flags: u32,
+        minimum_working_set_size: usize,
+        maximum_working_set_size: usize,
+        active_process_limit: u32,
+        affinity: usize,
+        priority_class: u32,
+        scheduling_class: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct IoCounters {
+        read_operation_count: u64,
+        write_operation_count: u64,
+        other_operation_count: u64,
+        read_transfer_count: u64,
+        write_transfer_count: u64,
+        other_transfer_count: u64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct ExtendedLimitInformation {
+        basic_limit_information: BasicLimitInformation,
+        io_info: IoCounters,
+        process_memory_limit: usize,
+        job_memory_limit: usize,
+        peak_process_memory_used: usize,
+        peak_job_memory_used: usize,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateJobObjectW(attributes: *mut c_void, name: *const u16) -> *mut c_void;
+        fn SetInformationJobObject(
+            job: *mut c_void,
+            class: i32,
+            information: *mut c_void,
+            length: u32,
+        ) -> i32;
+        fn AssignProcessToJobObject(job: *mut c_void, process: *mut c_void) -> i32;
+        fn TerminateJobObject(job: *mut c_void, exit_code: u32) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    pub fn configure(command: &mut Command) {
+        // A console engine started from a GUI would otherwise open a window of its own.
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    // A job object that kills its processes once closed, kept as an integer so that it can be
+    // sent to another thread with the engine's stdin.  Zero if one couldn't be made, when only
+    // the engine itself is killed.
+    pub struct Job(usize);
+
+    impl Job {
+        pub fn new(child: &Child) -> Job {
+            unsafe {
+                let job = CreateJobObjectW(ptr::null_mut(), ptr::null());
+                if job.is_null() {
+                    return Job(0);
+                }
+                let mut info = ExtendedLimitInformation::default();
+                info.basic_limit_information.limit_flags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                let set = SetInformationJobObject(
+                    job,
+                    JOB_OBJECT_EXTENDED_LIMIT_INFORMATION,
+                    &mut info as *mut ExtendedLimitInformation as *mut c_void,
+                    size_of::<ExtendedLimitInformation>() as u32,
+                );
+                if set == 0
+                    || AssignProcessToJobObject(job, child.as_raw_handle() as *mut c_void) == 0
+                {
+                    CloseHandle(job);
+                    return Job(0);
+                }
+                Job(job as usize)
+            }
+        }
+
+        pub fn kill(&self, _child: &Child, _reaped: bool) {
+            if self.0 != 0 {
+                unsafe {
+                    TerminateJobObject(self.0 as *mut c_void, 1);
+                }
+            }
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            if self.0 != 0 {
+                unsafe {
+                    CloseHandle(self.0 as *mut c_void);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::process::{Child, Command};
+
+    pub fn configure(_command: &mut Command) {}
+
+    // Only the engine itself can be killed.
+    pub struct Job;
+
+    impl Job {
+        pub fn new(_child: &Child) -> Job {
+            Job
+        }
+
+        pub fn kill(&self, _child: &Child, _reaped: bool) {}
+    }
+}
+
+#[test]
+fn test_program_in_working_dir() {
+    let dir = Path::new("engines").join("stockfish");
+    assert_eq!(
+        program("./stockfish", dir.to_str()),
+        env::current_dir().unwrap().join(&dir).join("./stockfish")
+    );
+    // A bare name is looked for on the path, and an absolute path is left alone.
+    assert_eq!(program("stockfish", dir.to_str()), Path::new("stockfish"));
+    let absolute = env::temp_dir().join("stockfish");
+    assert_eq!(program(absolute.to_str().unwrap(), dir.to_str()), absolute);
+    assert_eq!(program("./stockfish", None), Path::new("./stockfish"));
+}
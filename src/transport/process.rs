@@ -0,0 +1,183 @@
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use std::process::Stdio;
+use std::time::Duration;
+
+use error::Error;
+use transport::platform::{self, ProcessTree, ProcessWriter};
+use transport::process_controls::ProcessControls;
+use transport::{Transport, TransportReader, TransportWriter};
+
+#[cfg(test)]
+use std::fs;
+#[cfg(test)]
+use std::io::{BufRead, BufReader};
+#[cfg(test)]
+use std::thread::sleep;
+#[cfg(test)]
+use std::time::Instant;
+
+// How long an engine has to exit by itself once its stdin is closed.
+const DEFAULT_EXIT_GRACE: Duration = Duration::from_millis(500);
+
+/// Runs the engine as a child process, talking to it over stdin and stdout.
+///
+/// The engine, and any processes it starts, are killed once the writer is dropped and it has had
+/// `exit_grace` to exit by itself, so a match that stops early doesn't leave engines running.
+pub struct ProcessTransport {
+    path: String,
+    args: Vec<String>,
+    working_dir: Option<String>,
+    controls: ProcessControls,
+    applied: Option<ProcessControls>,
+    exit_grace: Duration,
+    process_id: Option<u32>,
+}
+
+impl ProcessTransport {
+    pub fn new(path: &str) -> ProcessTransport {
+        ProcessTransport {
+            path: path.to_string(),
+            args: vec![],
+            working_dir: None,
+            controls: ProcessControls::new(),
+            applied: None,
+            exit_grace: DEFAULT_EXIT_GRACE,
+            process_id: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: &str) -> ProcessTransport {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Run the engine in `dir` rather than the current directory.  A relative path to the engine
+    /// with a directory in it, like `./stockfish`, is then taken to be in `dir`.
+    pub fn working_dir(mut self, dir: &str) -> ProcessTransport {
+        self.working_dir = Some(dir.to_string());
+        self
+    }
+
+    /// Set the engine's niceness and CPU affinity once it has started.
+    pub fn controls(mut self, controls: ProcessControls) -> ProcessTransport {
+        self.controls = controls;
+        self
+    }
+
+    /// How long the engine has to exit by itself, once its stdin is closed, before it is killed.
+    pub fn exit_grace(mut self, exit_grace: Duration) -> ProcessTransport {
+        self.exit_grace = exit_grace;
+        self
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn get_args(&self) -> &Vec<String> {
+        &self.args
+    }
+
+    pub fn get_working_dir(&self) -> Option<&String> {
+        self.working_dir.as_ref()
+    }
+
+    pub fn get_exit_grace(&self) -> Duration {
+        self.exit_grace
+    }
+
+    pub fn get_controls(&self) -> &ProcessControls {
+        &self.controls
+    }
+}
+
+impl Transport for ProcessTransport {
+    fn open(&mut self) -> Result<(TransportReader, TransportWriter), Error> {
+        let mut command = platform::command(&self.path, &self.args, self.working_dir.as_deref());
+        command.stdin(Stdio::piped()).stdout(Stdio::piped());
+        let mut process = command.spawn().map_err(Error::spawn)?;
+        self.applied = Some(self.controls.apply(&process));
+        self.process_id = Some(process.id());
+
+        match (process.stdout.take(), process.stdin.take()) {
+            (Some(stdout), Some(stdin)) => {
+                let tree = ProcessTree::new(process, self.exit_grace);
+                Ok((Box::new(stdout), Box::new(ProcessWriter::new(stdin, tree))))
+            }
+            _ => Err(Error::spawn(IoError::new(
+                IoErrorKind::BrokenPipe,
+                "engine stdio was not captured",
+            ))),
+        }
+    }
+
+    fn set_process_controls(&mut self, controls: ProcessControls) -> bool {
+        self.controls = controls;
+        true
+    }
+
+    fn get_applied_controls(&self) -> Option<&ProcessControls> {
+        self.applied.as_ref()
+    }
+
+    fn get_process_id(&self) -> Option<u32> {
+        self.process_id
+    }
+}
+
+#[test]
+fn test_process_transport_missing_engine() {
+    let mut transport = ProcessTransport::new("/this/engine/does/not/exist").arg("--uci");
+    assert_eq!(transport.get_args(), &vec!["--uci".to_string()]);
+    match transport.open() {
+        Err(e) => {
+            assert!(e.is_fatal());
+            assert_eq!(e.io_error_kind(), Some(IoErrorKind::NotFound));
+        }
+        Ok(_) => panic!("spawned a missing engine"),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_process_transport_controls() {
+    let mut transport =
+        ProcessTransport::new("cat").controls(ProcessControls::new().nice(19).affinity(vec![0]));
+    assert_eq!(transport.get_applied_controls(), None);
+    let (_reader, _writer) = transport.open().unwrap();
+    let applied = transport.get_applied_controls().unwrap();
+    // Lowering its own priority is always allowed.
+    assert_eq!(applied.get_nice(), Some(19));
+    assert_eq!(applied.get_affinity(), Some(&vec![0]));
+}
+
+// Whether the process `pid` is alive, and not waiting to be reaped.
+#[cfg(target_os = "linux")]
+#[cfg(test)]
+fn running(pid: &str) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .map(|stat| !stat.contains(") Z "))
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_process_tree_killed_on_drop() {
+    // The shell starts a process of its own, which outlives it unless the tree is killed.
+    let mut transport = ProcessTransport::new("sh")
+        .arg("-c")
+        .arg("sleep 30 & echo $!; exec sleep 30")
+        .exit_grace(Duration::from_millis(10));
+    let (reader, writer) = transport.open().unwrap();
+    let mut pid = String::new();
+    BufReader::new(reader).read_line(&mut pid).unwrap();
+    let pid = pid.trim();
+    assert!(running(pid));
+
+    drop(writer);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while running(pid) && Instant::now() < deadline {
+        sleep(Duration::from_millis(5));
+    }
+    assert!(!running(pid));
+}
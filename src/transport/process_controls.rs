@@ -0,0 +1,140 @@
+use std::process::Child;
+
+/// How an engine's process should be scheduled: its niceness, and the CPUs it may run on.  The
+/// same type reports what was applied once the process is running, with anything the platform
+/// doesn't support, or refused, left out.
+///
+/// Pinning each engine in a match to its own cores keeps them from slowing each other down, so
+/// the time each is given means the same to both.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessControls {
+    nice: Option<i32>,
+    affinity: Option<Vec<usize>>,
+}
+
+impl ProcessControls {
+    pub fn new() -> ProcessControls {
+        ProcessControls::default()
+    }
+
+    /// Run at niceness `nice`, higher being lower priority.  Raising the priority above the
+    /// current process's usually needs privileges.  Unix only.
+    pub fn nice(mut self, nice: i32) -> ProcessControls {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Run only on the CPUs numbered in `cpus`.  Linux only.
+    pub fn affinity(mut self, cpus: Vec<usize>) -> ProcessControls {
+        let mut cpus = cpus;
+        cpus.sort_unstable();
+        cpus.dedup();
+        self.affinity = Some(cpus);
+        self
+    }
+
+    pub fn get_nice(&self) -> Option<i32> {
+        self.nice
+    }
+
+    pub fn get_affinity(&self) -> Option<&Vec<usize>> {
+        self.affinity.as_ref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nice.is_none() && self.affinity.is_none()
+    }
+
+    /// Apply these to `child`, returning what was applied.  The child has already started, so it
+    /// runs unrestricted for the moment before this, while an engine is normally waiting for `uci`.
+    pub(crate) fn apply(&self, child: &Child) -> ProcessControls {
+        ProcessControls {
+            nice: self.nice.filter(|&nice| sys::set_nice(child.id(), nice)),
+            affinity: self
+                .affinity
+                .as_ref()
+                .and_then(|cpus| sys::set_affinity(child.id(), cpus)),
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::{c_int, c_uint};
+
+    const PRIO_PROCESS: c_int = 0;
+
+    extern "C" {
+        fn setpriority(which: c_int, who: c_uint, prio: c_int) -> c_int;
+    }
+
+    pub fn set_nice(pid: u32, nice: i32) -> bool {
+        unsafe { setpriority(PRIO_PROCESS, pid as c_uint, nice as c_int) == 0 }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub use self::linux::set_affinity;
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn set_affinity(_pid: u32, _cpus: &[usize]) -> Option<Vec<usize>> {
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    mod linux {
+        use std::mem::size_of;
+        use std::os::raw::{c_int, c_ulong};
+
+        // CPUs in a `cpu_set_t`, as glibc defines it.
+        const CPU_SETSIZE: usize = 1024;
+        const BITS: usize = c_ulong::BITS as usize;
+
+        type CpuSet = [c_ulong; CPU_SETSIZE / BITS];
+
+        extern "C" {
+            fn sched_setaffinity(pid: c_int, size: usize, mask: *const c_ulong) -> c_int;
+            fn sched_getaffinity(pid: c_int, size: usize, mask: *mut c_ulong) -> c_int;
+        }
+
+        /// Pin `pid` to `cpus`, returning the CPUs it was then allowed, as the kernel reports.
+        pub fn set_affinity(pid: u32, cpus: &[usize]) -> Option<Vec<usize>> {
+            let mut set: CpuSet = [0; CPU_SETSIZE / BITS];
+            for &cpu in cpus.iter().filter(|&&cpu| cpu < CPU_SETSIZE) {
+                set[cpu / BITS] |= 1 << (cpu % BITS);
+            }
+            let size = size_of::<CpuSet>();
+            if unsafe { sched_setaffinity(pid as c_int, size, set.as_ptr()) } != 0 {
+                return None;
+            }
+            let mut applied: CpuSet = [0; CPU_SETSIZE / BITS];
+            if unsafe { sched_getaffinity(pid as c_int, size, applied.as_mut_ptr()) } != 0 {
+                return None;
+            }
+            Some(
+                (0..CPU_SETSIZE)
+                    .filter(|&cpu| applied[cpu / BITS] & (1 << (cpu % BITS)) != 0)
+                    .collect(),
+            )
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    pub fn set_nice(_pid: u32, _nice: i32) -> bool {
+        false
+    }
+
+    pub fn set_affinity(_pid: u32, _cpus: &[usize]) -> Option<Vec<usize>> {
+        None
+    }
+}
+
+#[test]
+fn test_process_controls() {
+    let controls = ProcessControls::new().nice(5).affinity(vec![1, 0, 1]);
+    assert_eq!(controls.get_nice(), Some(5));
+    assert_eq!(controls.get_affinity(), Some(&vec![0, 1]));
+    assert!(!controls.is_empty());
+    assert!(ProcessControls::new().is_empty());
+}
@@ -0,0 +1,30 @@
+use std::net::{TcpStream, ToSocketAddrs};
+
+use error::Error;
+use transport::{Transport, TransportReader, TransportWriter};
+
+/// Talks to an engine served over a plain TCP socket, one UCI command per line.
+pub struct TcpTransport {
+    address: String,
+}
+
+impl TcpTransport {
+    pub fn new(address: &str) -> TcpTransport {
+        TcpTransport {
+            address: address.to_string(),
+        }
+    }
+
+    pub fn get_address(&self) -> &String {
+        &self.address
+    }
+}
+
+impl Transport for TcpTransport {
+    fn open(&mut self) -> Result<(TransportReader, TransportWriter), Error> {
+        let addresses = self.address.to_socket_addrs()?.collect::<Vec<_>>();
+        let stream = TcpStream::connect(&addresses[..])?;
+        stream.set_nodelay(true)?;
+        Ok((Box::new(stream.try_clone()?), Box::new(stream)))
+    }
+}
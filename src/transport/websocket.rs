@@ -0,0 +1,485 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use error::Error;
+use rng::Rng;
+use transport::{Transport, TransportReader, TransportWriter};
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// The longest message, or single frame, accepted from the server, so that a broken or hostile
+// one can't make the client allocate whatever length it likes.
+const MAX_MESSAGE_LENGTH: usize = 1024 * 1024;
+
+/// Talks to an engine behind a WebSocket server (`ws://host:port/path`), with each message holding
+/// one or more UCI lines.  Only unencrypted `ws://` URLs are supported.
+pub struct WebSocketTransport {
+    url: String,
+}
+
+impl WebSocketTransport {
+    pub fn new(url: &str) -> WebSocketTransport {
+        WebSocketTransport {
+            url: url.to_string(),
+        }
+    }
+
+    pub fn get_url(&self) -> &String {
+        &self.url
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+// Split `ws://host:port/path` into the address to connect to, the host header and the path.
+fn parse_url(url: &str) -> io::Result<(String, String, String)> {
+    let url = match url.strip_prefix("ws://") {
+        Some(url) => url,
+        None => return Err(invalid_data("only ws:// URLs are supported")),
+    };
+
+    let (host, path) = match url.find('/') {
+        Some(i) => (&url[..i], &url[i..]),
+        None => (url, "/"),
+    };
+    if host.is_empty() {
+        return Err(invalid_data("missing host"));
+    }
+
+    let address = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    Ok((address, host.to_string(), path.to_string()))
+}
+
+// Masking keys and handshake nonces only need to be unpredictable to the network, not
+// cryptographically strong, so the crate's generator seeded from the clock will do.
+fn clock_rng() -> Rng {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    Rng::new(nanos)
+}
+
+fn next_bytes(rng: &mut Rng) -> [u8; 4] {
+    (rng.next() as u32).to_be_bytes()
+}
+
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn sha1(input: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((input.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([
+                block[4 * i],
+                block[4 * i + 1],
+                block[4 * i + 2],
+                block[4 * i + 3],
+            ]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, x) in h.iter().enumerate() {
+        digest[4 * i..4 * i + 4].copy_from_slice(&x.to_be_bytes());
+    }
+    digest
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+fn write_frame<W: Write>(writer: &mut W, opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    if payload.len() < 126 {
+        frame.push(mask_bit | payload.len() as u8);
+    } else if payload.len() <= u16::MAX as usize {
+        frame.push(mask_bit | 126);
+        frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    } else {
+        frame.push(mask_bit | 127);
+        frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+// Returns (fin, opcode, payload), unmasking the payload if needed.
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0f;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE_LENGTH as u64 {
+        return Err(invalid_data("websocket frame too long"));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    if masked {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+
+    Ok((fin, opcode, payload))
+}
+
+// Presents the data messages from the server as one continuous stream of lines.  Each complete
+// message is taken to end a line, so servers that send one line per message without a trailing
+// newline still work.
+struct WebSocketReader {
+    reader: BufReader<TcpStream>,
+    writer: Arc<Mutex<WebSocketWriter>>,
+    buffer: Vec<u8>,
+    position: usize,
+    closed: bool,
+}
+
+impl WebSocketReader {
+    fn fill(&mut self) -> io::Result<()> {
+        self.buffer.clear();
+        self.position = 0;
+
+        loop {
+            let (fin, opcode, payload) = read_frame(&mut self.reader)?;
+            match opcode {
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    if self.buffer.len() + payload.len() > MAX_MESSAGE_LENGTH {
+                        return Err(invalid_data("websocket message too long"));
+                    }
+                    self.buffer.extend_from_slice(&payload);
+                    if fin {
+                        if self.buffer.last() != Some(&b'\n') {
+                            self.buffer.push(b'\n');
+                        }
+                        return Ok(());
+                    }
+                }
+                OPCODE_PING => {
+                    self.writer.lock().unwrap().send(OPCODE_PONG, &payload)?;
+                }
+                OPCODE_PONG => {}
+                OPCODE_CLOSE => {
+                    let _ = self.writer.lock().unwrap().send(OPCODE_CLOSE, &payload);
+                    self.closed = true;
+                    return Ok(());
+                }
+                _ => return Err(invalid_data("unknown websocket opcode")),
+            }
+        }
+    }
+}
+
+impl Read for WebSocketReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.buffer.len() {
+            if self.closed {
+                return Ok(0);
+            }
+            self.fill()?;
+            if self.closed {
+                return Ok(0);
+            }
+        }
+
+        let n = buf.len().min(self.buffer.len() - self.position);
+        buf[..n].copy_from_slice(&self.buffer[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+struct WebSocketWriter {
+    stream: TcpStream,
+    rng: Rng,
+}
+
+impl WebSocketWriter {
+    fn send(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mask = next_bytes(&mut self.rng);
+        write_frame(&mut self.stream, opcode, payload, Some(mask))
+    }
+}
+
+// Shared with the reader, which has to answer pings.
+struct SharedWriter(Arc<Mutex<WebSocketWriter>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().send(OPCODE_TEXT, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().stream.flush()
+    }
+}
+
+fn handshake(reader: &mut BufReader<TcpStream>, writer: &mut WebSocketWriter, host: &str, path: &str) -> io::Result<()> {
+    let mut nonce = vec![];
+    for _ in 0..4 {
+        nonce.extend_from_slice(&next_bytes(&mut writer.rng));
+    }
+    let key = base64(&nonce);
+
+    write!(
+        writer.stream,
+        "GET {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\r\n",
+        path, host, key
+    )?;
+
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(invalid_data("server refused the websocket upgrade"));
+    }
+
+    let mut accepted = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("connection closed during the websocket handshake"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(i) = line.find(':') {
+            let (name, value) = line.split_at(i);
+            if name.trim().eq_ignore_ascii_case("sec-websocket-accept") {
+                accepted = value[1..].trim() == accept_key(&key);
+            }
+        }
+    }
+
+    if accepted {
+        Ok(())
+    } else {
+        Err(invalid_data("invalid Sec-WebSocket-Accept"))
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn open(&mut self) -> Result<(TransportReader, TransportWriter), Error> {
+        let (address, host, path) = parse_url(&self.url)?;
+        let stream = TcpStream::connect(address.as_str())?;
+        stream.set_nodelay(true)?;
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = WebSocketWriter {
+            stream,
+            rng: clock_rng(),
+        };
+        handshake(&mut reader, &mut writer, &host, &path)?;
+
+        let writer = Arc::new(Mutex::new(writer));
+        let reader = WebSocketReader {
+            reader,
+            writer: writer.clone(),
+            buffer: vec![],
+            position: 0,
+            closed: false,
+        };
+        Ok((Box::new(reader), Box::new(SharedWriter(writer))))
+    }
+}
+
+#[cfg(test)]
+use std::net::TcpListener;
+#[cfg(test)]
+use std::thread::spawn;
+
+#[test]
+fn test_accept_key() {
+    // The example from RFC 6455, section 1.3.
+    assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}
+
+#[test]
+fn test_parse_url() {
+    assert_eq!(
+        parse_url("ws://localhost:8080/uci").unwrap(),
+        ("localhost:8080".to_string(), "localhost:8080".to_string(), "/uci".to_string())
+    );
+    assert_eq!(
+        parse_url("ws://example.com").unwrap(),
+        ("example.com:80".to_string(), "example.com".to_string(), "/".to_string())
+    );
+    assert!(parse_url("wss://example.com").is_err());
+}
+
+#[test]
+fn test_websocket_transport() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // A minimal server: upgrade the connection, wait for `uci`, ping the client, then answer with
+    // `UCIOK` split across two frames.
+    let server = spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+
+        let mut key = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Sec-WebSocket-Key:") {
+                key = value.trim().to_string();
+            }
+        }
+        write!(
+            writer,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(&key)
+        )
+        .unwrap();
+
+        let (_, opcode, payload) = read_frame(&mut reader).unwrap();
+        assert_eq!((opcode, &payload[..]), (OPCODE_TEXT, &b"uci\n"[..]));
+
+        write_frame(&mut writer, OPCODE_PING, b"hi", None).unwrap();
+        let (_, opcode, payload) = read_frame(&mut reader).unwrap();
+        assert_eq!((opcode, payload), (OPCODE_PONG, b"hi".to_vec()));
+
+        writer.write_all(&[OPCODE_TEXT, 2]).unwrap();
+        writer.write_all(b"UC").unwrap();
+        write_frame(&mut writer, OPCODE_CONTINUATION, b"IOK", None).unwrap();
+        write_frame(&mut writer, OPCODE_CLOSE, b"", None).unwrap();
+    });
+
+    let mut transport = WebSocketTransport::new(&format!("ws://{}/engine", address));
+    let (reader, mut writer) = transport.open().unwrap();
+    writer.write_all(b"uci\n").unwrap();
+
+    let lines = BufReader::new(reader)
+        .lines()
+        .collect::<io::Result<Vec<String>>>()
+        .unwrap();
+    assert_eq!(lines, vec!["UCIOK".to_string()]);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_oversized_frames_rejected() {
+    let mut frame = vec![OPCODE_TEXT | 0x80, 127];
+    frame.extend_from_slice(&u64::MAX.to_be_bytes());
+    let e = read_frame(&mut &frame[..]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+
+    let mut frame = Vec::new();
+    write_frame(&mut frame, OPCODE_TEXT, &vec![b'x'; MAX_MESSAGE_LENGTH], None).unwrap();
+    let (_, _, payload) = read_frame(&mut &frame[..]).unwrap();
+    assert_eq!(payload.len(), MAX_MESSAGE_LENGTH);
+}
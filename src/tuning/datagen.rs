@@ -0,0 +1,438 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+
+use chess::{Board, ChessMove, Color, File, MoveGen, Piece, Square};
+
+use engine_base::eval::Eval;
+use engine_base::search::{is_quiet, Search};
+use engine_base::search_limits::SearchLimits;
+use error::Error;
+use game_history::GameHistory;
+use game_result::{game_result, is_insufficient_material, GameResult};
+use gui::position::{en_passant_target, to_fen, MoveCounters};
+use rng::Rng;
+
+#[cfg(test)]
+use engine_base::evaluate::DefaultEvaluate;
+#[cfg(test)]
+use engine_base::search::DefaultSearch;
+#[cfg(test)]
+use std::str::FromStr;
+#[cfg(test)]
+use std::sync::atomic::AtomicBool;
+#[cfg(test)]
+use std::sync::Arc;
+
+/// The size of a record in `DataFormat::Binary`.
+pub const BINARY_RECORD_SIZE: usize = 32;
+
+// How deep a fixed node search may go, for positions where the budget is never spent.
+const MAX_DATAGEN_DEPTH: i16 = 64;
+
+/// How training records are written.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    /// A `fen,score,result` line per record, the score in centipawns for white and the result
+    /// as white's score: 1, 0.5 or 0.
+    Csv,
+    /// 32 bytes a record, in the layout known as marlinformat: the occupied squares as a
+    /// little endian `u64`, then a nibble per piece in square order (pawn to king as 0 to 5, a
+    /// rook that can still castle as 6, plus 8 for black), the side to move in the top bit of
+    /// a byte holding the en passant square (64 for none), the halfmove clock, the fullmove
+    /// number as a `u16`, the score for white as an `i16`, the result as 0, 1 or 2 for a black
+    /// win, a draw and a white win, and a spare byte.
+    Binary,
+}
+
+/// A position from a self-play game, with the search's score for it and how the game ended.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrainingRecord {
+    board: Board,
+    halfmove_clock: u8,
+    fullmove_number: u16,
+    score: i16,
+    result: GameResult,
+}
+
+impl TrainingRecord {
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    /// In centipawns, for white.
+    pub fn get_score(&self) -> i16 {
+        self.score
+    }
+
+    pub fn get_result(&self) -> GameResult {
+        self.result
+    }
+
+    /// The board's FEN, with the game's move counters.
+    pub fn to_fen(&self) -> String {
+        let counters = MoveCounters::new(
+            u64::from(self.halfmove_clock),
+            u64::from(self.fullmove_number),
+        );
+        to_fen(&self.board, counters)
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{}\n",
+            self.to_fen(),
+            self.score,
+            self.result.score_for(Color::White)
+        )
+    }
+
+    pub fn to_bytes(&self) -> [u8; BINARY_RECORD_SIZE] {
+        let mut bytes = [0u8; BINARY_RECORD_SIZE];
+        let occupied = *self.board.combined();
+        bytes[0..8].copy_from_slice(&occupied.0.to_le_bytes());
+        for (n, square) in occupied.enumerate() {
+            let code = self.piece_code(square);
+            bytes[8 + n / 2] |= if n % 2 == 0 { code } else { code << 4 };
+        }
+
+        let side = if self.board.side_to_move() == Color::Black {
+            0x80
+        } else {
+            0
+        };
+        let en_passant = en_passant_target(&self.board).map_or(64, |square| square.to_index());
+        bytes[24] = side | en_passant as u8;
+        bytes[25] = self.halfmove_clock;
+        bytes[26..28].copy_from_slice(&self.fullmove_number.to_le_bytes());
+        bytes[28..30].copy_from_slice(&self.score.to_le_bytes());
+        bytes[30] = match self.result {
+            GameResult::Win(Color::Black) => 0,
+            GameResult::Draw => 1,
+            GameResult::Win(Color::White) => 2,
+        };
+        bytes
+    }
+
+    fn piece_code(&self, square: Square) -> u8 {
+        let color = self.board.color_on(square).unwrap_or(Color::White);
+        let code = match self.board.piece_on(square) {
+            Some(Piece::Pawn) => 0,
+            Some(Piece::Knight) => 1,
+            Some(Piece::Bishop) => 2,
+            Some(Piece::Rook) if self.can_castle_with(square, color) => 6,
+            Some(Piece::Rook) => 3,
+            Some(Piece::Queen) => 4,
+            _ => 5,
+        };
+        if color == Color::Black {
+            code | 8
+        } else {
+            code
+        }
+    }
+
+    fn can_castle_with(&self, square: Square, color: Color) -> bool {
+        let rights = self.board.castle_rights(color);
+        let back_rank = color.to_my_backrank();
+        (rights.has_kingside() && square == Square::make_square(back_rank, File::H))
+            || (rights.has_queenside() && square == Square::make_square(back_rank, File::A))
+    }
+
+    pub fn write<W: Write>(&self, format: DataFormat, writer: &mut W) -> Result<(), Error> {
+        match format {
+            DataFormat::Csv => writer.write_all(self.to_csv().as_bytes())?,
+            DataFormat::Binary => writer.write_all(&self.to_bytes())?,
+        }
+        Ok(())
+    }
+}
+
+/// Plays self-play games with a built-in searcher at a fixed number of nodes a move, and keeps
+/// each position with the searcher's score and the game's result, as training data for an
+/// evaluation network.
+///
+/// Each game opens with a few random moves, drawn from a generator seeded by the seed and the
+/// game's number, and searches start from a reset searcher, so the same seed gives the same
+/// games however many threads play them.  Positions in check, those whose best move is a capture
+/// or promotion, and those with mate scores are left out, as is usual for such data.
+pub struct DataGenerator<F> {
+    factory: F,
+    games: usize,
+    nodes: u64,
+    random_plies: usize,
+    max_plies: usize,
+    seed: u64,
+    threads: usize,
+}
+
+impl<F, S> DataGenerator<F>
+where
+    F: Fn() -> S + Sync,
+    S: Search<i32>,
+{
+    /// Play `games` games, each thread searching with a searcher from `factory`.
+    pub fn new(factory: F, games: usize) -> DataGenerator<F> {
+        DataGenerator {
+            factory,
+            games,
+            nodes: 5000,
+            random_plies: 8,
+            max_plies: 400,
+            seed: 1,
+            threads: 1,
+        }
+    }
+
+    pub fn nodes(mut self, nodes: u64) -> Self {
+        self.nodes = nodes.max(1);
+        self
+    }
+
+    /// How many random moves open each game.
+    pub fn random_plies(mut self, random_plies: usize) -> Self {
+        self.random_plies = random_plies;
+        self
+    }
+
+    /// Games still going after this many plies are drawn.
+    pub fn max_plies(mut self, max_plies: usize) -> Self {
+        self.max_plies = max_plies;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Play game `game` of the run, and return its records.
+    pub fn play_game(&self, searcher: &mut S, game: usize) -> Vec<TrainingRecord> {
+        let mut rng = Rng::new(self.seed ^ (game as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+
+        searcher.reset();
+        let mut history = GameHistory::new(Board::default());
+        let mut positions = vec![];
+        let result = loop {
+            let board = history.get_board();
+            if let Some(result) = game_result(&board) {
+                break result;
+            }
+            let ply = history.get_moves().len();
+            if history.is_threefold()
+                || history.is_fifty_move()
+                || is_insufficient_material(&board)
+                || ply >= self.max_plies
+            {
+                break GameResult::Draw;
+            }
+
+            let chess_move = if ply < self.random_plies {
+                let moves = history.legal_moves();
+                moves[rng.below(moves.len() as u64) as usize]
+            } else {
+                let (chess_move, score) = self.search(searcher, board);
+                if board.checkers().popcnt() == 0
+                    && is_quiet(&board, chess_move)
+                    && score.depth_to_mate().is_none()
+                {
+                    let white_score = match board.side_to_move() {
+                        Color::White => score,
+                        Color::Black => -score,
+                    };
+                    positions.push((board, history.get_halfmove_clock(), ply, white_score));
+                }
+                chess_move
+            };
+            if history.push(chess_move).is_err() {
+                break GameResult::Draw;
+            }
+        };
+
+        positions
+            .into_iter()
+            .map(|(board, clock, ply, score)| TrainingRecord {
+                board,
+                halfmove_clock: clock.min(255) as u8,
+                fullmove_number: (ply / 2 + 1) as u16,
+                score: score.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                result,
+            })
+            .collect()
+    }
+
+    // Deepen until the node budget runs out, keeping the last search that finished.
+    fn search(&self, searcher: &mut S, board: Board) -> (ChessMove, i32) {
+        searcher.set_limits(SearchLimits::nodes(self.nodes));
+        let mut best = None;
+        for depth in 1..=MAX_DATAGEN_DEPTH {
+            let result = searcher.search(board, i32::min_eval(), i32::max_eval(), depth);
+            match result.get_best_move() {
+                Some(m) if !result.is_stopped() => best = Some((m, result.get_score())),
+                _ => break,
+            }
+            if result.get_score().depth_to_mate().is_some() {
+                break;
+            }
+        }
+        // Even a search cut short at depth one leaves the game a legal move to play.
+        best.unwrap_or_else(|| {
+            let m = MoveGen::new_legal(&board)
+                .next()
+                .expect("The game is over.");
+            (m, 0)
+        })
+    }
+
+    /// Play every game, on `threads` threads, writing the records to `writer` in the order of
+    /// the games.  Returns how many records were written.
+    pub fn run<W: Write>(&self, format: DataFormat, mut writer: W) -> Result<usize, Error> {
+        let next_game = AtomicUsize::new(0);
+        let mut written = 0;
+        let mut error = None;
+        thread::scope(|scope| {
+            let (tx, rx) = channel();
+            for _ in 0..self.threads.min(self.games.max(1)) {
+                let tx = tx.clone();
+                let next_game = &next_game;
+                scope.spawn(move || {
+                    let mut searcher = (self.factory)();
+                    loop {
+                        let game = next_game.fetch_add(1, Ordering::SeqCst);
+                        if game >= self.games {
+                            break;
+                        }
+                        if tx
+                            .send((game, self.play_game(&mut searcher, game)))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            // Games finishing early wait here for those before them.
+            let mut finished = BTreeMap::new();
+            let mut next_to_write = 0;
+            for (game, records) in rx {
+                finished.insert(game, records);
+                while let Some(records) = finished.remove(&next_to_write) {
+                    next_to_write += 1;
+                    if error.is_some() {
+                        continue;
+                    }
+                    for record in records {
+                        if let Err(e) = record.write(format, &mut writer) {
+                            error = Some(e);
+                            // Let the games already being played finish, but start no more.
+                            next_game.store(self.games, Ordering::SeqCst);
+                            break;
+                        }
+                        written += 1;
+                    }
+                }
+            }
+        });
+        if let Some(e) = error {
+            return Err(e);
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+fn new_searcher() -> DefaultSearch<i32, DefaultEvaluate> {
+    DefaultSearch::new(Arc::new(AtomicBool::new(false)), DefaultEvaluate::default())
+}
+
+#[test]
+fn test_training_record() {
+    let board = Board::from_str("r3k2r/8/8/3pP3/8/8/8/R3K1NR w Kkq d6 0 20").unwrap();
+    let record = TrainingRecord {
+        board,
+        halfmove_clock: 3,
+        fullmove_number: 20,
+        score: -150,
+        result: GameResult::Win(Color::Black),
+    };
+    assert_eq!(
+        record.to_csv(),
+        "r3k2r/8/8/3pP3/8/8/8/R3K1NR w Kkq d6 3 20,-150,0\n"
+    );
+
+    let bytes = record.to_bytes();
+    assert_eq!(
+        u64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]
+        ]),
+        board.combined().0
+    );
+    // The a1 rook can't castle and the h1 rook can, then come the pawns, and black's castling
+    // rooks either side of the king.
+    assert_eq!(&bytes[8..14], &[0x53, 0x61, 0x08, 0xde, 0x0e, 0]);
+    assert_eq!(bytes[24], Square::D6.to_index() as u8);
+    assert_eq!(bytes[25], 3);
+    assert_eq!(&bytes[26..28], &[20, 0]);
+    assert_eq!(i16::from_le_bytes([bytes[28], bytes[29]]), -150);
+    assert_eq!(bytes[30], 0);
+}
+
+#[test]
+fn test_datagen_game() {
+    let generator = DataGenerator::new(new_searcher, 1)
+        .nodes(200)
+        .random_plies(4)
+        .max_plies(40)
+        .seed(7);
+    let mut searcher = new_searcher();
+    let records = generator.play_game(&mut searcher, 0);
+    assert!(!records.is_empty());
+    assert!(records.len() <= 36);
+    for record in &records {
+        assert_eq!(record.get_board().checkers().popcnt(), 0);
+        assert_eq!(record.get_result(), records[0].get_result());
+    }
+    // The same game is played again, and a different one with another number.
+    assert_eq!(generator.play_game(&mut searcher, 0), records);
+    assert!(generator.play_game(&mut searcher, 1) != records);
+}
+
+#[test]
+fn test_datagen_threads() {
+    let generate = |threads: usize, format: DataFormat| {
+        let mut out = vec![];
+        let generator = DataGenerator::new(new_searcher, 4)
+            .nodes(100)
+            .random_plies(6)
+            .max_plies(24)
+            .threads(threads);
+        let written = generator.run(format, &mut out).unwrap();
+        (written, out)
+    };
+    let (written, csv) = generate(1, DataFormat::Csv);
+    assert!(written > 0);
+    assert_eq!(generate(3, DataFormat::Csv), (written, csv.clone()));
+    let text = String::from_utf8(csv).unwrap();
+    assert_eq!(text.lines().count(), written);
+    for line in text.lines() {
+        let fields = line.split(',').collect::<Vec<&str>>();
+        assert_eq!(fields.len(), 3);
+        assert!(Board::from_str(fields[0]).is_ok());
+        assert!(fields[1].parse::<i16>().is_ok());
+        assert!(["1", "0.5", "0"].contains(&fields[2]));
+    }
+
+    let (binary_written, binary) = generate(2, DataFormat::Binary);
+    assert_eq!(binary_written, written);
+    assert_eq!(binary.len(), written * BINARY_RECORD_SIZE);
+}
@@ -0,0 +1,632 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use engine_config::EngineConfig;
+use engine_connection::EngineConnection;
+use error::Error;
+use json::Json;
+use rng::Rng;
+use tournament::game::Game;
+use tournament::openings::OpeningSet;
+use tournament::runner::Tournament;
+
+#[cfg(test)]
+use chess::Board;
+#[cfg(test)]
+use gui::go::Go;
+#[cfg(test)]
+use std::str::FromStr;
+#[cfg(test)]
+use test_engines::{connect_fake, fake_engine};
+
+/// The exponent the step size shrinks by over the iterations, as in Spall's paper and Fishtest.
+pub const SPSA_ALPHA: f64 = 0.602;
+/// The exponent the perturbation shrinks by over the iterations.
+pub const SPSA_GAMMA: f64 = 0.101;
+/// The stability constant `A`, as a share of the iterations.
+pub const SPSA_A_RATIO: f64 = 0.1;
+
+type Connector = dyn Fn(&EngineConfig) -> Result<EngineConnection<'static>, Error> + Send + Sync;
+
+/// One engine parameter tuned by SPSA, set through the UCI option of the same name.
+///
+/// Following Fishtest, `c_end` is how far the parameter is moved either way to play the last
+/// iteration's games, and `r_end` the learning rate at the end, so that the last step is
+/// `r_end * c_end` for each game won.  Values are kept as real numbers, and rounded when they are
+/// sent to the engine.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpsaParam {
+    name: String,
+    value: f64,
+    min: f64,
+    max: f64,
+    c_end: f64,
+    r_end: f64,
+}
+
+impl SpsaParam {
+    /// A parameter starting at `value`, moved by a twentieth of its range at the end, with a
+    /// learning rate of 0.002.
+    ///
+    /// Panics unless `min` is below `max`, and all three are finite.
+    pub fn new(name: &str, value: f64, min: f64, max: f64) -> SpsaParam {
+        let mut param = SpsaParam {
+            name: name.to_string(),
+            value,
+            min,
+            max,
+            c_end: (max - min) / 20.0,
+            r_end: 0.002,
+        };
+        param.validate().unwrap_or_else(|e| panic!("{}", e));
+        param.value = value.clamp(min, max);
+        param
+    }
+
+    /// Panics unless `c_end` is positive and finite, as with none the parameter can't be tuned.
+    pub fn c_end(mut self, c_end: f64) -> SpsaParam {
+        self.c_end = c_end;
+        self.validate().unwrap_or_else(|e| panic!("{}", e));
+        self
+    }
+
+    pub fn r_end(mut self, r_end: f64) -> SpsaParam {
+        self.r_end = r_end;
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn get_min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn get_max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn get_c_end(&self) -> f64 {
+        self.c_end
+    }
+
+    pub fn get_r_end(&self) -> f64 {
+        self.r_end
+    }
+
+    // Whether the parameter can be tuned: with `min` at or above `max`, or no `c_end`, the steps
+    // would be NaN.
+    fn validate(&self) -> Result<(), Error> {
+        let numbers = [self.value, self.min, self.max, self.c_end, self.r_end];
+        if numbers.iter().any(|n| !n.is_finite()) {
+            return Err(config_error(&format!("{} has a value that isn't finite", self.name)));
+        }
+        if self.min >= self.max {
+            return Err(config_error(&format!("{} has a min that isn't below its max", self.name)));
+        }
+        if self.c_end <= 0.0 {
+            return Err(config_error(&format!("{} has a c_end that isn't positive", self.name)));
+        }
+        Ok(())
+    }
+
+    fn to_json(&self) -> Json {
+        let number = |n: f64| Json::Number(n);
+        Json::Object(vec![
+            ("name".to_string(), Json::String(self.name.clone())),
+            ("value".to_string(), number(self.value)),
+            ("min".to_string(), number(self.min)),
+            ("max".to_string(), number(self.max)),
+            ("c_end".to_string(), number(self.c_end)),
+            ("r_end".to_string(), number(self.r_end)),
+        ])
+    }
+
+    fn from_json(json: &Json) -> Result<SpsaParam, Error> {
+        let number = |key: &str| {
+            json.get(key)
+                .and_then(|v| v.as_f64())
+                .ok_or_else(|| config_error(&format!("parameter without a {}", key)))
+        };
+        let name = json
+            .get("name")
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| config_error("parameter without a name"))?;
+        let mut param = SpsaParam {
+            name: name.to_string(),
+            value: number("value")?,
+            min: number("min")?,
+            max: number("max")?,
+            c_end: number("c_end")?,
+            r_end: number("r_end")?,
+        };
+        param.validate()?;
+        param.value = param.value.clamp(param.min, param.max);
+        Ok(param)
+    }
+}
+
+// Options are whole numbers, so the engines get the parameters rounded.
+fn format_value(value: f64) -> String {
+    (value.round() as i64).to_string()
+}
+
+fn config_error(message: &str) -> Error {
+    Error::ConfigError(message.to_string())
+}
+
+/// What one SPSA iteration played, and where it left the parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpsaIteration {
+    iteration: usize,
+    plus: Vec<f64>,
+    minus: Vec<f64>,
+    result: f64,
+    values: Vec<f64>,
+}
+
+impl SpsaIteration {
+    /// Counted from zero.
+    pub fn get_iteration(&self) -> usize {
+        self.iteration
+    }
+
+    /// The parameters the first engine played with.
+    pub fn get_plus(&self) -> &[f64] {
+        &self.plus
+    }
+
+    /// The parameters the second engine played with.
+    pub fn get_minus(&self) -> &[f64] {
+        &self.minus
+    }
+
+    /// The first engine's wins less its losses.
+    pub fn get_result(&self) -> f64 {
+        self.result
+    }
+
+    /// The parameters after the update.
+    pub fn get_values(&self) -> &[f64] {
+        &self.values
+    }
+}
+
+/// Tunes engine parameters by simultaneous perturbation stochastic approximation.  Each
+/// iteration moves every parameter a random way by the same share of its `c_end`, plays an engine
+/// with the parameters moved up against one with them moved down, and moves the parameters
+/// towards whichever side won, by how much it won.
+///
+/// The games are played by a `Tournament` between two copies of `engine`, which set the
+/// parameters as options.  The tuner's state can be saved after every iteration, so a long run
+/// can be picked up where it stopped.
+pub struct SpsaTuner {
+    engine: EngineConfig,
+    game: Game,
+    params: Vec<SpsaParam>,
+    iterations: usize,
+    iteration: usize,
+    games: usize,
+    concurrency: usize,
+    openings: Option<OpeningSet>,
+    connector: Option<Arc<Connector>>,
+    checkpoint: Option<String>,
+    rng: Rng,
+}
+
+impl SpsaTuner {
+    /// Tune `params` of `engine` over `iterations` iterations of two games each, played as `game`
+    /// says.
+    pub fn new(
+        engine: EngineConfig,
+        game: Game,
+        params: Vec<SpsaParam>,
+        iterations: usize,
+    ) -> SpsaTuner {
+        SpsaTuner {
+            engine,
+            game,
+            params,
+            iterations: iterations.max(1),
+            iteration: 0,
+            games: 2,
+            concurrency: 1,
+            openings: None,
+            connector: None,
+            checkpoint: None,
+            rng: Rng::new(0x2545_f491_4f6c_dd1d),
+        }
+    }
+
+    /// Games per iteration.  An even number lets each engine play both colours.
+    pub fn games(mut self, games: usize) -> SpsaTuner {
+        self.games = games.max(1);
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> SpsaTuner {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Play each iteration's games from the next openings of `openings`.
+    pub fn openings(mut self, openings: OpeningSet) -> SpsaTuner {
+        self.openings = Some(openings);
+        self
+    }
+
+    /// Start engines with `connector`, as `Tournament::connector`.
+    pub fn connector<F>(mut self, connector: F) -> SpsaTuner
+    where
+        F: Fn(&EngineConfig) -> Result<EngineConnection<'static>, Error> + Send + Sync + 'static,
+    {
+        self.connector = Some(Arc::new(connector));
+        self
+    }
+
+    /// Save the tuner's state to `path` after every iteration of `run`.
+    pub fn checkpoint(mut self, path: &str) -> SpsaTuner {
+        self.checkpoint = Some(path.to_string());
+        self
+    }
+
+    /// Seed the generator choosing which way each parameter is moved.
+    pub fn seed(mut self, seed: u64) -> SpsaTuner {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn get_params(&self) -> &[SpsaParam] {
+        &self.params
+    }
+
+    /// The iterations played so far.
+    pub fn get_iteration(&self) -> usize {
+        self.iteration
+    }
+
+    pub fn get_iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.iteration >= self.iterations
+    }
+
+    // The next direction, +1 or -1, from the seeded generator so that a seeded run repeats.
+    fn flip(&mut self) -> f64 {
+        if self.rng.next() & 1 == 0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    /// Play one iteration, with `play` scoring the first set of parameters against the second
+    /// as wins less losses, and update the parameters.  `step` plays it with games; this is
+    /// for tuning against something else, such as a test position suite.
+    pub fn step_with<F>(&mut self, play: F) -> Result<SpsaIteration, Error>
+    where
+        F: FnOnce(&[f64], &[f64]) -> Result<f64, Error>,
+    {
+        let k = self.iteration as f64;
+        let n = self.iterations as f64;
+        let big_a = SPSA_A_RATIO * n;
+        let deltas = (0..self.params.len())
+            .map(|_| self.flip())
+            .collect::<Vec<f64>>();
+
+        // Fishtest's schedule: c_k falls to c_end, and a_k to r_end * c_end^2, by the end.
+        let c_k = |p: &SpsaParam| p.c_end * n.powf(SPSA_GAMMA) / (k + 1.0).powf(SPSA_GAMMA);
+        let a_k = |p: &SpsaParam| {
+            p.r_end * p.c_end * p.c_end * (big_a + n).powf(SPSA_ALPHA)
+                / (big_a + k + 1.0).powf(SPSA_ALPHA)
+        };
+        let shifted = |sign: f64| {
+            self.params
+                .iter()
+                .zip(&deltas)
+                .map(|(p, d)| (p.value + sign * c_k(p) * d).clamp(p.min, p.max))
+                .collect::<Vec<f64>>()
+        };
+        let (plus, minus) = (shifted(1.0), shifted(-1.0));
+
+        let result = play(&plus, &minus)?;
+        for (p, d) in self.params.iter_mut().zip(&deltas) {
+            let step = a_k(p) * result / (c_k(p) * d);
+            p.value = (p.value + step).clamp(p.min, p.max);
+        }
+        self.iteration += 1;
+        Ok(SpsaIteration {
+            iteration: self.iteration - 1,
+            plus,
+            minus,
+            result,
+            values: self.params.iter().map(|p| p.value).collect(),
+        })
+    }
+
+    /// Play one iteration's games and update the parameters.
+    pub fn step(&mut self) -> Result<SpsaIteration, Error> {
+        // Each iteration takes up the openings where the last one left off.
+        let first_game = self.iteration * self.games;
+        let openings = self.openings.as_ref().map(|openings| {
+            OpeningSet::new(
+                (0..self.games)
+                    .filter_map(|i| openings.get_for_game(first_game + i).cloned())
+                    .collect(),
+            )
+        });
+        let game = self.game.clone();
+        let (games, concurrency) = (self.games, self.concurrency);
+        let connector = self.connector.clone();
+        let engine = self.engine.clone();
+        let params = self.params.clone();
+        self.step_with(|plus, minus| {
+            let configure = |values: &[f64]| {
+                params
+                    .iter()
+                    .zip(values)
+                    .fold(engine.clone(), |engine, (param, value)| {
+                        engine.option(&param.name, &format_value(*value))
+                    })
+            };
+            let mut tournament = Tournament::new(vec![configure(plus), configure(minus)], game)
+                .rounds(games)
+                .concurrency(concurrency);
+            if let Some(openings) = openings {
+                tournament = tournament.openings(openings);
+            }
+            if let Some(connector) = connector {
+                tournament = tournament.connector(move |config| connector(config));
+            }
+            let record = tournament.run(|_| {})?.get_record(0, 1);
+            Ok(record.get_wins() as f64 - record.get_losses() as f64)
+        })
+    }
+
+    /// Run the remaining iterations, calling `on_iteration` after each one and saving the state
+    /// to the checkpoint, if there is one.
+    pub fn run<F: FnMut(&SpsaIteration)>(&mut self, mut on_iteration: F) -> Result<(), Error> {
+        while !self.is_finished() {
+            let iteration = self.step()?;
+            if let Some(ref path) = self.checkpoint {
+                self.save(path)?;
+            }
+            on_iteration(&iteration);
+        }
+        Ok(())
+    }
+
+    /// Write the parameters, the iterations played and the generator's state to `path` as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let state = Json::Object(vec![
+            ("iteration".to_string(), Json::Number(self.iteration as f64)),
+            (
+                "iterations".to_string(),
+                Json::Number(self.iterations as f64),
+            ),
+            // As a string, as a JSON number can't hold every u64.
+            ("rng".to_string(), Json::String(self.rng.get_state().to_string())),
+            (
+                "params".to_string(),
+                Json::Array(self.params.iter().map(|p| p.to_json()).collect()),
+            ),
+        ]);
+        fs::write(path, format!("{}\n", state))?;
+        Ok(())
+    }
+
+    /// Pick up the state saved in `path`, replacing the parameters and iterations.
+    pub fn restore<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let state = Json::parse(&fs::read_to_string(path)?)?;
+        let number = |key: &str| {
+            state
+                .get(key)
+                .and_then(|v| v.as_f64())
+                .ok_or_else(|| config_error(&format!("checkpoint without {}", key)))
+        };
+        let iteration = number("iteration")? as usize;
+        let iterations = number("iterations")? as usize;
+        let rng = state
+            .get("rng")
+            .and_then(|r| r.as_str())
+            .and_then(|r| r.parse::<u64>().ok())
+            .ok_or_else(|| config_error("checkpoint without rng"))?;
+        let params = state
+            .get("params")
+            .and_then(|p| p.as_array())
+            .ok_or_else(|| config_error("checkpoint without params"))?
+            .iter()
+            .map(SpsaParam::from_json)
+            .collect::<Result<Vec<SpsaParam>, Error>>()?;
+        self.iteration = iteration;
+        self.iterations = iterations.max(1);
+        self.rng = Rng::new(rng);
+        self.params = params;
+        Ok(())
+    }
+}
+
+// A match the parameters win by being nearer `target`, two games a time.
+#[cfg(test)]
+fn closer_wins(target: &[f64], plus: &[f64], minus: &[f64]) -> f64 {
+    let distance = |values: &[f64]| -> f64 {
+        values
+            .iter()
+            .zip(target)
+            .map(|(x, t)| (x - t) * (x - t))
+            .sum()
+    };
+    (distance(minus) - distance(plus)).signum() * 2.0
+}
+
+#[test]
+fn test_spsa_converges() {
+    let params = vec![
+        SpsaParam::new("Pawn", 60.0, 0.0, 200.0)
+            .c_end(5.0)
+            .r_end(0.5),
+        SpsaParam::new("Knight", 400.0, 200.0, 500.0)
+            .c_end(5.0)
+            .r_end(0.5),
+    ];
+    let mut tuner = SpsaTuner::new(
+        EngineConfig::new("Engine", "engine"),
+        Game::new(Go::depth(1)),
+        params,
+        300,
+    );
+    while !tuner.is_finished() {
+        let iteration = tuner
+            .step_with(|plus, minus| Ok(closer_wins(&[100.0, 320.0], plus, minus)))
+            .unwrap();
+        assert!(
+            iteration.get_plus()[0] > iteration.get_minus()[0]
+                || iteration.get_plus()[0] < iteration.get_minus()[0]
+        );
+    }
+    assert_eq!(tuner.get_iteration(), 300);
+    let values: Vec<f64> = tuner.get_params().iter().map(|p| p.get_value()).collect();
+    assert!((values[0] - 100.0).abs() < 10.0, "{:?}", values);
+    assert!((values[1] - 320.0).abs() < 10.0, "{:?}", values);
+    assert!(tuner.step_with(|_, _| Ok(0.0)).is_ok());
+}
+
+#[test]
+fn test_spsa_bounds() {
+    let params = vec![SpsaParam::new("Margin", 150.0, 0.0, 100.0)];
+    assert_eq!(params[0].get_value(), 100.0);
+    let mut tuner = SpsaTuner::new(
+        EngineConfig::new("Engine", "engine"),
+        Game::new(Go::depth(1)),
+        params,
+        10,
+    );
+    for _ in 0..10 {
+        let iteration = tuner.step_with(|_, _| Ok(100.0)).unwrap();
+        assert!(iteration.get_plus()[0] <= 100.0 && iteration.get_minus()[0] <= 100.0);
+        assert!(iteration.get_values()[0] >= 0.0 && iteration.get_values()[0] <= 100.0);
+    }
+    assert!(tuner.step_with(|_, _| Err(Error::EngineDeadError)).is_err());
+}
+
+#[test]
+#[should_panic]
+fn test_spsa_param_empty_range() {
+    SpsaParam::new("Margin", 100.0, 100.0, 100.0);
+}
+
+#[test]
+#[should_panic]
+fn test_spsa_param_no_perturbation() {
+    SpsaParam::new("Margin", 100.0, 0.0, 200.0).c_end(0.0);
+}
+
+#[test]
+fn test_spsa_checkpoint() {
+    let params = vec![
+        SpsaParam::new("Pawn", 60.0, 0.0, 200.0)
+            .c_end(5.0)
+            .r_end(0.5),
+        SpsaParam::new("Knight", 400.0, 200.0, 500.0),
+    ];
+    let new_tuner = || {
+        SpsaTuner::new(
+            EngineConfig::new("Engine", "engine"),
+            Game::new(Go::depth(1)),
+            params.clone(),
+            50,
+        )
+        .seed(42)
+    };
+    let play = |plus: &[f64], minus: &[f64]| Ok(closer_wins(&[100.0, 320.0], plus, minus));
+    let path = std::env::temp_dir().join(format!("chess_uci_spsa_{}.json", std::process::id()));
+
+    let mut tuner = new_tuner();
+    for _ in 0..5 {
+        tuner.step_with(play).unwrap();
+    }
+    tuner.save(&path).unwrap();
+
+    // A tuner picked up from the checkpoint goes on as the first one does.
+    let mut restored = new_tuner();
+    restored.restore(&path).unwrap();
+    assert_eq!(restored.get_iteration(), 5);
+    assert_eq!(restored.get_params(), tuner.get_params());
+    for _ in 0..5 {
+        assert_eq!(
+            restored.step_with(play).unwrap(),
+            tuner.step_with(play).unwrap()
+        );
+    }
+
+    fs::write(&path, "{\"iteration\": 5}").unwrap();
+    assert!(restored.restore(&path).is_err());
+    assert_eq!(restored.get_iteration(), 10);
+
+    // Parameters that can't be tuned are refused rather than turning into NaN.
+    let checkpoint = |min: i32, max: i32, c_end: i32| {
+        format!(
+            "{{\"iteration\": 5, \"iterations\": 50, \"rng\": \"1\", \"params\": [{{\"name\": \"Pawn\", \
+             \"value\": 100, \"min\": {}, \"max\": {}, \"c_end\": {}, \"r_end\": 0.5}}]}}",
+            min, max, c_end
+        )
+    };
+    fs::write(&path, checkpoint(0, 200, 5)).unwrap();
+    assert!(restored.restore(&path).is_ok());
+    for bad in [checkpoint(200, 0, 5), checkpoint(100, 100, 5), checkpoint(0, 200, 0)].iter() {
+        fs::write(&path, bad).unwrap();
+        assert!(matches!(restored.restore(&path), Err(Error::ConfigError(_))));
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_spsa_match() {
+    let (good, bad) = (fake_engine(false), fake_engine(true));
+    // The engine with the higher weight plays legal moves and the other doesn't, so the weight
+    // should go up whichever way it is tried.
+    let start = Board::from_str("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    let params = vec![SpsaParam::new("Weight", 50.0, 0.0, 100.0)
+        .c_end(10.0)
+        .r_end(0.01)];
+    let path =
+        std::env::temp_dir().join(format!("chess_uci_spsa_match_{}.json", std::process::id()));
+    let mut tuner = SpsaTuner::new(
+        EngineConfig::new("Engine", "engine"),
+        Game::new(Go::depth(1)).start(start),
+        params,
+        1,
+    )
+    .connector(move |config| {
+        let weight = config.get_option("Weight").unwrap().parse::<i64>().unwrap();
+        Ok(connect_fake(if weight > 50 { &good } else { &bad }))
+    })
+    .checkpoint(path.to_str().unwrap());
+
+    let mut results = vec![];
+    tuner
+        .run(|iteration| results.push(iteration.get_result()))
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].abs(), 2.0);
+    assert!(tuner.get_params()[0].get_value() > 50.0);
+    assert!(tuner.is_finished());
+
+    let mut restored = SpsaTuner::new(
+        EngineConfig::new("Engine", "engine"),
+        Game::new(Go::depth(1)),
+        vec![],
+        2,
+    );
+    restored.restore(&path).unwrap();
+    assert_eq!(restored.get_params(), tuner.get_params());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,335 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use chess::Board;
+
+use engine_base::eval_params::EvalParams;
+use engine_base::evaluate::Evaluate;
+use engine_base::search_window::AlphaBetaSearchParams;
+use error::Error;
+
+#[cfg(test)]
+use engine_base::evaluate::{DefaultEvaluate, PawnStructureEvaluate};
+
+/// The scaling constant texel tuning usually settles on for centipawn evaluations.
+pub const DEFAULT_TEXEL_K: f64 = 1.13;
+
+/// A position and how the game it came from ended, as white's score: 1 for a win, 0.5 for a
+/// draw and 0 for a loss.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LabeledPosition {
+    board: Board,
+    result: f64,
+}
+
+impl LabeledPosition {
+    pub fn new(board: Board, result: f64) -> LabeledPosition {
+        LabeledPosition { board, result }
+    }
+
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    pub fn get_result(&self) -> f64 {
+        self.result
+    }
+
+    /// Read a line of a position file: a FEN followed by the result, as `1-0`, `0-1`,
+    /// `1/2-1/2` or white's score, on its own or in brackets or quotes, after a space, a `;`,
+    /// a `|` or an EPD `c9` opcode.
+    pub fn parse(line: &str) -> Result<LabeledPosition, Error> {
+        let error = || Error::ConfigError(format!("expected a FEN and a result: {}", line));
+        // EPD operations end with a `;`.
+        let line = line.trim().trim_end_matches(';');
+        let split = line
+            .rfind(|c: char| c.is_whitespace() || c == ';' || c == '|')
+            .ok_or_else(error)?;
+        let (fen, result) = line.split_at(split);
+        let result = match result[1..].trim_matches(|c| "[]\"() ;".contains(c)) {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            "1/2-1/2" | "1/2" => 0.5,
+            score => score.parse::<f64>().map_err(|_| error())?,
+        };
+        let fen = fen.trim().trim_end_matches(&[';', '|'][..]);
+        let fen = fen.trim().trim_end_matches(" c9").trim();
+        // EPD positions have no move counters.
+        let board = Board::from_str(fen)
+            .or_else(|_| Board::from_str(&format!("{} 0 1", fen)))
+            .map_err(|_| error())?;
+        if !(0.0..=1.0).contains(&result) {
+            return Err(error());
+        }
+        Ok(LabeledPosition::new(board, result))
+    }
+
+    /// Every position of `text`, one a line, skipping blank lines and `#` comments.
+    pub fn parse_all(text: &str) -> Result<Vec<LabeledPosition>, Error> {
+        text.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(LabeledPosition::parse)
+            .collect()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<LabeledPosition>, Error> {
+        LabeledPosition::parse_all(&fs::read_to_string(path)?)
+    }
+}
+
+/// How well `score`, in centipawns for white, predicts white's result.
+pub fn texel_sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+/// Tunes an evaluator's weights so that its static evaluation of labeled positions, mapped
+/// through `texel_sigmoid`, predicts their games' results as well as it can.
+///
+/// The weights tuned are the ones the evaluator's `get_params` gives, or those chosen with
+/// `only`.  As only the static evaluation is looked at, the positions should be quiet ones.
+pub struct TexelTuner<V: Evaluate<i32>> {
+    evaluator: V,
+    positions: Vec<LabeledPosition>,
+    names: Vec<String>,
+    values: Vec<f64>,
+    k: f64,
+}
+
+impl<V: Evaluate<i32>> TexelTuner<V> {
+    pub fn new(evaluator: V, positions: Vec<LabeledPosition>) -> TexelTuner<V> {
+        let params = evaluator.get_params();
+        let names = params
+            .get_names()
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>();
+        let values = names.iter().filter_map(|name| params.get(name)).collect();
+        TexelTuner {
+            evaluator,
+            positions,
+            names,
+            values,
+            k: DEFAULT_TEXEL_K,
+        }
+    }
+
+    /// Tune only the weights named in `names`, leaving the rest as they are.
+    pub fn only(mut self, names: &[&str]) -> TexelTuner<V> {
+        let (names, values) = self
+            .names
+            .iter()
+            .zip(&self.values)
+            .filter(|(name, _)| names.contains(&name.as_str()))
+            .map(|(name, value)| (name.clone(), *value))
+            .unzip();
+        self.names = names;
+        self.values = values;
+        self
+    }
+
+    pub fn k(mut self, k: f64) -> TexelTuner<V> {
+        self.k = k;
+        self
+    }
+
+    pub fn get_k(&self) -> f64 {
+        self.k
+    }
+
+    pub fn get_positions(&self) -> &[LabeledPosition] {
+        &self.positions
+    }
+
+    /// The tuned weights as they are now, ready to save as a `ParamsFile`.
+    pub fn get_params(&self) -> EvalParams {
+        let mut params = EvalParams::new();
+        for (name, value) in self.names.iter().zip(&self.values) {
+            params.insert(name, value.round());
+        }
+        params
+    }
+
+    /// The mean squared difference between the results and what `values` predicts of them.
+    fn error_with(&mut self, values: &[f64], k: f64) -> f64 {
+        if self.positions.is_empty() {
+            return 0.0;
+        }
+        let mut params = EvalParams::new();
+        for (name, value) in self.names.iter().zip(values) {
+            params.insert(name, *value);
+        }
+        self.evaluator.set_params(&params);
+        let evaluator = &mut self.evaluator;
+        let total: f64 = self
+            .positions
+            .iter()
+            .map(|position| {
+                let mut sp = AlphaBetaSearchParams::new(position.board, 0, 0, 0);
+                let score = evaluator.evaluate(&mut sp) as f64;
+                (position.result - texel_sigmoid(score, k)).powi(2)
+            })
+            .sum();
+        total / self.positions.len() as f64
+    }
+
+    /// The error of the weights as they are now.
+    pub fn error(&mut self) -> f64 {
+        let (values, k) = (self.values.clone(), self.k);
+        self.error_with(&values, k)
+    }
+
+    /// Set `k` to the scaling, between 0 and 10, that fits the evaluator's current weights best,
+    /// as is usually done once before tuning.  Returns the error with it.
+    pub fn fit_k(&mut self) -> f64 {
+        let values = self.values.clone();
+        let (mut low, mut high) = (0.0, 10.0);
+        // The error is unimodal in k, so a ternary search finds its minimum.
+        for _ in 0..100 {
+            let a = low + (high - low) / 3.0;
+            let b = high - (high - low) / 3.0;
+            if self.error_with(&values, a) < self.error_with(&values, b) {
+                high = b;
+            } else {
+                low = a;
+            }
+        }
+        self.k = (low + high) / 2.0;
+        self.error()
+    }
+
+    /// Texel's local search: try each weight one higher and one lower, keeping any change that
+    /// lowers the error, until a pass changes nothing or `passes` passes are done.  Returns the
+    /// error at the end.  Slow, but it needs nothing of the evaluator.
+    pub fn local_search(&mut self, passes: usize) -> f64 {
+        let k = self.k;
+        let mut best = self.error();
+        for _ in 0..passes {
+            let mut improved = false;
+            for i in 0..self.values.len() {
+                for &step in &[1.0, -1.0] {
+                    let mut values = self.values.clone();
+                    values[i] += step;
+                    let error = self.error_with(&values, k);
+                    if error < best {
+                        best = error;
+                        self.values = values;
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+        self.error_with(&self.values.clone(), k)
+    }
+
+    /// Gradient descent with `rate` as the learning rate, over `iterations` steps, with the
+    /// gradient estimated by moving each weight a centipawn either way.  Weights are kept as
+    /// real numbers while tuning, but the evaluator only sees them rounded, so the estimate is
+    /// rough when the weights are close.  Returns the error at the end.
+    pub fn gradient_descent(&mut self, iterations: usize, rate: f64) -> f64 {
+        let k = self.k;
+        for _ in 0..iterations {
+            let mut gradient = vec![0.0; self.values.len()];
+            for (i, g) in gradient.iter_mut().enumerate() {
+                let mut values = self.values.clone();
+                values[i] += 1.0;
+                let up = self.error_with(&values, k);
+                values[i] -= 2.0;
+                let down = self.error_with(&values, k);
+                *g = (up - down) / 2.0;
+            }
+            for (value, g) in self.values.iter_mut().zip(&gradient) {
+                *value -= rate * g;
+            }
+        }
+        self.error_with(&self.values.clone(), k)
+    }
+}
+
+#[test]
+fn test_labeled_positions() {
+    let text = "# Quiet positions\n\
+                rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 [0.5]\n\
+                4k3/8/8/8/8/8/4P3/4K3 w - - 0 1; 1-0\n\
+                4k3/4p3/8/8/8/8/8/4K3 b - - c9 \"0-1\";\n\
+                \n\
+                4k3/8/8/8/8/8/8/4K3 w - - 0 1 | 1/2-1/2\n";
+    let positions = LabeledPosition::parse_all(text).unwrap();
+    assert_eq!(positions.len(), 4);
+    assert_eq!(positions[0].get_board(), Board::default());
+    let results: Vec<f64> = positions.iter().map(|p| p.get_result()).collect();
+    assert_eq!(results, vec![0.5, 1.0, 0.0, 0.5]);
+    assert_eq!(
+        positions[2].get_board(),
+        Board::from_str("4k3/4p3/8/8/8/8/8/4K3 b - - 0 1").unwrap()
+    );
+
+    assert!(LabeledPosition::parse("4k3/8/8/8/8/8/8/4K3 w - -").is_err());
+    assert!(LabeledPosition::parse("4k3/8/8/8/8/8/8/4K3 w - - 0 1 2.0").is_err());
+    assert!(LabeledPosition::parse("not a position 1-0").is_err());
+}
+
+#[test]
+fn test_texel_sigmoid() {
+    assert_eq!(texel_sigmoid(0.0, 1.0), 0.5);
+    assert!((texel_sigmoid(400.0, 1.0) - 10.0 / 11.0).abs() < 1e-12);
+    assert!((texel_sigmoid(-400.0, 1.0) - 1.0 / 11.0).abs() < 1e-12);
+}
+
+// Positions where white is a pawn up and scores about three quarters, on average.
+#[cfg(test)]
+fn pawn_up_positions() -> Vec<LabeledPosition> {
+    let up = Board::from_str("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    let even = Board::from_str("4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    let mut positions = vec![];
+    for result in &[1.0, 1.0, 1.0, 0.5, 0.5, 0.0] {
+        positions.push(LabeledPosition::new(up, *result));
+        positions.push(LabeledPosition::new(even, 0.5));
+    }
+    positions
+}
+
+#[test]
+fn test_texel_local_search() {
+    let mut tuner = TexelTuner::new(DefaultEvaluate::default(), pawn_up_positions())
+        .only(&["material.pawn"])
+        .k(1.0);
+    let before = tuner.error();
+    let after = tuner.local_search(1000);
+    assert!(after < before);
+    assert_eq!(tuner.get_params().len(), 1);
+
+    // White scores 2/3 a pawn up, which the sigmoid gives at 400 * log10(2) centipawns.
+    let pawn = tuner.get_params().get("material.pawn").unwrap();
+    assert!((pawn - 120.0).abs() <= 1.0, "{}", pawn);
+}
+
+#[test]
+fn test_texel_gradient_descent() {
+    let mut tuner = TexelTuner::new(PawnStructureEvaluate::default(), pawn_up_positions())
+        .only(&["material.pawn", "pawns.passed.1"])
+        .k(1.0);
+    let before = tuner.error();
+    let after = tuner.gradient_descent(100, 300_000.0);
+    assert!(after < before, "{} {}", before, after);
+
+    // The lone pawn is passed and isolated, so it is worth its material and bonus less the
+    // isolated pawn penalty.
+    let params = tuner.get_params();
+    let total = params.get("material.pawn").unwrap() + params.get("pawns.passed.1").unwrap();
+    assert!((total - 12.0 - 120.0).abs() <= 3.0, "{:?}", params);
+}
+
+#[test]
+fn test_texel_fit_k() {
+    let mut tuner = TexelTuner::new(DefaultEvaluate::default(), pawn_up_positions());
+    assert_eq!(tuner.get_params().len(), 5);
+    tuner.fit_k();
+    // A pawn is worth 100, so k scales it to the 120 the results call for.
+    assert!((tuner.get_k() - 1.2041).abs() < 0.01, "{}", tuner.get_k());
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use chess::Board;
+
+use parsers::Counter;
+
+/// One search of `EngineConnection::warm_up`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WarmUpSearch {
+    board: Board,
+    time: Duration,
+    nodes: Option<Counter>,
+}
+
+impl WarmUpSearch {
+    pub fn new(board: Board, time: Duration, nodes: Option<Counter>) -> WarmUpSearch {
+        WarmUpSearch { board, time, nodes }
+    }
+
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    /// From `go` to `bestmove`.
+    pub fn get_time(&self) -> Duration {
+        self.time
+    }
+
+    /// The nodes the engine last reported, if it did.
+    pub fn get_nodes(&self) -> Option<Counter> {
+        self.nodes
+    }
+
+    pub fn get_nps(&self) -> Option<Counter> {
+        let millis = self.time.as_millis() as Counter;
+        match (self.nodes, millis) {
+            (Some(_), 0) | (None, _) => None,
+            (Some(nodes), millis) => Some(nodes.saturating_mul(1000) / millis),
+        }
+    }
+}
+
+/// How the searches of `EngineConnection::warm_up` went, in the order they were run.  An engine
+/// that is slow to start shows it as a first search much slower than the rest.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct WarmUpReport {
+    searches: Vec<WarmUpSearch>,
+}
+
+impl WarmUpReport {
+    pub fn new(searches: Vec<WarmUpSearch>) -> WarmUpReport {
+        WarmUpReport { searches }
+    }
+
+    pub fn get_searches(&self) -> &[WarmUpSearch] {
+        &self.searches
+    }
+
+    pub fn get_total_time(&self) -> Duration {
+        self.searches.iter().map(|s| s.time).sum()
+    }
+
+    pub fn get_slowest(&self) -> Option<&WarmUpSearch> {
+        self.searches.iter().max_by_key(|s| s.time)
+    }
+}
+
+#[test]
+fn test_warm_up_report() {
+    let board = Board::default();
+    let report = WarmUpReport::new(vec![
+        WarmUpSearch::new(board, Duration::from_millis(250), Some(1000)),
+        WarmUpSearch::new(board, Duration::from_millis(100), None),
+    ]);
+    assert_eq!(report.get_total_time(), Duration::from_millis(350));
+    assert_eq!(report.get_slowest(), Some(&report.get_searches()[0]));
+    assert_eq!(report.get_searches()[0].get_nps(), Some(4000));
+    assert_eq!(report.get_searches()[1].get_nps(), None);
+    assert_eq!(WarmUpReport::default().get_slowest(), None);
+}